@@ -0,0 +1,59 @@
+"""Generates the gRPC stubs from proto/scheduler.proto before building.
+
+Inside the repository the stubs are regenerated on every build, so the
+package always matches the scheduler next to it. An sdist carries the
+stubs generated when it was made.
+"""
+
+import pathlib
+import re
+
+from setuptools import setup
+from setuptools.command.build_py import build_py
+from setuptools.command.sdist import sdist
+
+HERE = pathlib.Path(__file__).resolve().parent
+PROTO_DIR = HERE.parent.parent / "proto"
+OUT_DIR = HERE / "tgp_client" / "_proto"
+
+
+def generate_stubs():
+    proto = PROTO_DIR / "scheduler.proto"
+    if not proto.exists():
+        if not (OUT_DIR / "scheduler_pb2.py").exists():
+            raise RuntimeError(f"{proto} not found and no generated stubs present")
+        return
+
+    from grpc_tools import protoc
+
+    status = protoc.main([
+        "grpc_tools.protoc",
+        f"-I{PROTO_DIR}",
+        f"--python_out={OUT_DIR}",
+        f"--grpc_python_out={OUT_DIR}",
+        str(proto),
+    ])
+    if status != 0:
+        raise RuntimeError(f"protoc failed on {proto}")
+
+    # protoc writes a top-level import; the stubs live in a package
+    grpc_stub = OUT_DIR / "scheduler_pb2_grpc.py"
+    grpc_stub.write_text(re.sub(
+        r"^import scheduler_pb2 as", "from . import scheduler_pb2 as",
+        grpc_stub.read_text(), flags=re.M,
+    ))
+
+
+class BuildPy(build_py):
+    def run(self):
+        generate_stubs()
+        super().run()
+
+
+class Sdist(sdist):
+    def run(self):
+        generate_stubs()
+        super().run()
+
+
+setup(cmdclass={"build_py": BuildPy, "sdist": Sdist})
@@ -0,0 +1,75 @@
+"""Wrapper tests against a stand-in stub (run after generating the stubs:
+`pip install -e .`, then `python -m unittest`)."""
+
+import unittest
+
+import grpc
+
+from tgp_client import Client, JobStatus
+from tgp_client._proto import scheduler_pb2 as pb
+
+
+class Unavailable(grpc.RpcError):
+    def code(self):
+        return grpc.StatusCode.UNAVAILABLE
+
+    def details(self):
+        return "connection reset"
+
+
+class FakeStub:
+    def __init__(self):
+        self.calls = []
+        self.watches = 0
+
+    def SubmitJob(self, request, metadata, timeout):
+        self.calls.append(("SubmitJob", request, metadata))
+        return pb.JobSubmitResponse(success=True, job_id=request.job_id)
+
+    def WatchJob(self, request, metadata, timeout):
+        self.watches += 1
+        yield pb.JobStatusResponse(job_id=request.job_id, status=pb.JOB_STATUS_RUNNING)
+        if self.watches == 1:
+            raise Unavailable()
+        yield pb.JobStatusResponse(
+            job_id=request.job_id,
+            status=pb.JOB_STATUS_COMPLETED,
+            final_cost=pb.CostEstimate(total_cost_usd=0.42),
+        )
+
+    def GetJobStatus(self, request, metadata, timeout):
+        return pb.JobStatusResponse(job_id=request.job_id, status=pb.JOB_STATUS_RUNNING)
+
+    def GetJobLogs(self, request, metadata, timeout):
+        return pb.JobLogsResponse(job_id=request.job_id, logs="epoch 3: loss 0.12\n", available=True)
+
+
+class ClientTest(unittest.TestCase):
+    def setUp(self):
+        self.client = Client("localhost:50051", token="s3cret")
+        self.stub = self.client._stub = FakeStub()
+
+    def tearDown(self):
+        self.client.close()
+
+    def test_submit_wait_logs(self):
+        job_id = self.client.submit("pytorch/pytorch:2.1", ["python", "train.py"], gpus=1, budget_usd=5.0)
+        _, request, metadata = self.stub.calls[0]
+        self.assertTrue(job_id.startswith("job-"))
+        self.assertEqual(request.resources.gpu_count, 1)
+        self.assertEqual(request.sla.max_budget_usd, 5.0)
+        self.assertIn(("authorization", "Bearer s3cret"), metadata)
+
+        # The first watch drops mid-stream and is resumed
+        info = self.client.wait(job_id)
+        self.assertEqual(info.status, JobStatus.COMPLETED)
+        self.assertEqual(info.cost_usd, 0.42)
+        self.assertEqual(self.stub.watches, 2)
+
+        self.assertEqual(self.client.logs(job_id), "epoch 3: loss 0.12\n")
+        with self.assertRaises(ValueError):
+            self.client.submit("alpine", job_type="batch")
+
+
+if __name__ == "__main__":
+    unittest.main()
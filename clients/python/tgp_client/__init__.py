@@ -0,0 +1,15 @@
+"""Python client for the TGP scheduler.
+
+    from tgp_client import Client
+
+    client = Client("scheduler.example:50051", token="...")
+    job_id = client.submit("pytorch/pytorch:2.1", ["python", "train.py"], gpus=1, memory_gb=16)
+    info = client.wait(job_id)
+    print(info.status, client.cost(job_id))
+    print(client.logs(job_id))
+"""
+
+from .client import Client, JobInfo, JobStatus, TgpError
+
+__all__ = ["Client", "JobInfo", "JobStatus", "TgpError"]
+__version__ = "0.1.0"
@@ -0,0 +1 @@
+"""Stubs generated from proto/scheduler.proto at build time."""
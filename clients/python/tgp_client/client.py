@@ -0,0 +1,227 @@
+"""Thin wrapper over the generated SchedulerService stub."""
+
+import enum
+import time
+import uuid
+from dataclasses import dataclass, field
+from typing import Dict, Iterator, List, Optional, Sequence
+
+import grpc
+
+from ._proto import scheduler_pb2 as pb
+from ._proto import scheduler_pb2_grpc as pb_grpc
+
+_JOB_TYPES = {
+    "training": pb.JOB_TYPE_TRAINING,
+    "inference": pb.JOB_TYPE_INFERENCE,
+    "data_processing": pb.JOB_TYPE_DATA_PROCESSING,
+}
+
+# Stands for the client's default timeout (None means no deadline)
+_DEFAULT = object()
+
+_PRIORITIES = {
+    "low": pb.JOB_PRIORITY_LOW,
+    "normal": pb.JOB_PRIORITY_NORMAL,
+    "high": pb.JOB_PRIORITY_HIGH,
+}
+
+
+class TgpError(Exception):
+    """A scheduler call failed; `code` is its gRPC status code, if any."""
+
+    def __init__(self, message: str, code: Optional[grpc.StatusCode] = None):
+        super().__init__(message)
+        self.code = code
+
+
+class JobStatus(enum.Enum):
+    UNSPECIFIED = pb.JOB_STATUS_UNSPECIFIED
+    PENDING = pb.JOB_STATUS_PENDING
+    SCHEDULED = pb.JOB_STATUS_SCHEDULED
+    RUNNING = pb.JOB_STATUS_RUNNING
+    COMPLETED = pb.JOB_STATUS_COMPLETED
+    FAILED = pb.JOB_STATUS_FAILED
+    CANCELLED = pb.JOB_STATUS_CANCELLED
+
+    @property
+    def is_terminal(self) -> bool:
+        return self in (JobStatus.COMPLETED, JobStatus.FAILED, JobStatus.CANCELLED)
+
+
+@dataclass
+class JobInfo:
+    job_id: str
+    status: JobStatus
+    assigned_node: str = ""
+    # Cost in USD once the job is priced
+    cost_usd: Optional[float] = None
+    progress_percent: Optional[float] = None
+    # While pending: expected start (unix seconds) and jobs ahead
+    estimated_start_at: Optional[int] = None
+    queue_position: int = 0
+    cached_from_job: str = ""
+    gang_nodes: List[str] = field(default_factory=list)
+
+    @classmethod
+    def from_proto(cls, response: "pb.JobStatusResponse") -> "JobInfo":
+        return cls(
+            job_id=response.job_id,
+            status=JobStatus(response.status),
+            assigned_node=response.assigned_node,
+            cost_usd=response.final_cost.total_cost_usd if response.HasField("final_cost") else None,
+            progress_percent=response.progress.percent if response.HasField("progress") else None,
+            estimated_start_at=response.estimated_start_at or None,
+            queue_position=response.queue_position,
+            cached_from_job=response.cached_from_job,
+            gang_nodes=list(response.gang_nodes),
+        )
+
+
+class Client:
+    """Connection to a TGP scheduler.
+
+    `token` is sent as a bearer token on every call. With `secure`, the
+    channel uses TLS with the system roots (or `credentials`).
+    """
+
+    def __init__(
+        self,
+        target: str = "localhost:50051",
+        token: Optional[str] = None,
+        *,
+        secure: bool = False,
+        credentials: Optional[grpc.ChannelCredentials] = None,
+        timeout: float = 30.0,
+    ):
+        if secure or credentials is not None:
+            self._channel = grpc.secure_channel(target, credentials or grpc.ssl_channel_credentials())
+        else:
+            self._channel = grpc.insecure_channel(target)
+        self._stub = pb_grpc.SchedulerServiceStub(self._channel)
+        self._metadata = [("authorization", f"Bearer {token}")] if token else []
+        self.timeout = timeout
+
+    def close(self) -> None:
+        self._channel.close()
+
+    def __enter__(self) -> "Client":
+        return self
+
+    def __exit__(self, *exc) -> None:
+        self.close()
+
+    def _call(self, method: str, request, timeout=_DEFAULT):
+        try:
+            return getattr(self._stub, method)(
+                request, metadata=self._metadata, timeout=self.timeout if timeout is _DEFAULT else timeout
+            )
+        except grpc.RpcError as e:
+            raise TgpError(f"{method} failed: {e.details()}", e.code()) from e
+
+    def submit(
+        self,
+        image: str,
+        command: Sequence[str] = (),
+        *,
+        job_id: Optional[str] = None,
+        job_type: str = "training",
+        cpus: int = 1,
+        memory_gb: int = 1,
+        gpus: int = 0,
+        disk_gb: int = 10,
+        max_latency_ms: int = 1000,
+        budget_usd: Optional[float] = None,
+        deadline: Optional[int] = None,
+        env: Optional[Dict[str, str]] = None,
+        input: bytes = b"",
+        output_path: str = "",
+        datasets: Sequence[str] = (),
+        priority: str = "normal",
+        tenant: str = "",
+        queue: str = "",
+        callback_url: str = "",
+    ) -> str:
+        """Submit a job; returns its id (generated unless given)."""
+        if job_type not in _JOB_TYPES:
+            raise ValueError(f"job_type must be one of {sorted(_JOB_TYPES)}")
+        if priority not in _PRIORITIES:
+            raise ValueError(f"priority must be one of {sorted(_PRIORITIES)}")
+
+        request = pb.JobSubmitRequest(
+            job_id=job_id or f"job-{uuid.uuid4().hex}",
+            job_type=_JOB_TYPES[job_type],
+            resources=pb.ResourceRequirements(cpu_cores=cpus, memory_gb=memory_gb, gpu_count=gpus, disk_gb=disk_gb),
+            sla=pb.SlaConstraints(max_latency_ms=max_latency_ms),
+            job_data=input,
+            container_image=image,
+            command=list(command),
+            datasets=list(datasets),
+            priority=_PRIORITIES[priority],
+            tenant=tenant,
+            environment=env or {},
+            output_path=output_path,
+            queue=queue,
+            callback_url=callback_url,
+        )
+        if budget_usd is not None:
+            request.sla.max_budget_usd = budget_usd
+        if deadline is not None:
+            request.sla.deadline = deadline
+
+        response = self._call("SubmitJob", request)
+        if not response.success:
+            raise TgpError(f"Job {request.job_id} was not accepted: {response.message}")
+        return response.job_id
+
+    def status(self, job_id: str) -> JobInfo:
+        return JobInfo.from_proto(self._call("GetJobStatus", pb.JobStatusRequest(job_id=job_id)))
+
+    def watch(self, job_id: str, timeout: Optional[float] = None) -> Iterator[JobInfo]:
+        """Yield the job's status on every change until it finishes (or
+        `timeout` seconds pass)."""
+        stream = self._call("WatchJob", pb.JobStatusRequest(job_id=job_id), timeout=timeout)
+        try:
+            for response in stream:
+                yield JobInfo.from_proto(response)
+        except grpc.RpcError as e:
+            raise TgpError(f"WatchJob failed: {e.details()}", e.code()) from e
+
+    def wait(self, job_id: str, timeout: Optional[float] = None) -> JobInfo:
+        """Block until the job finishes (or `timeout` seconds pass); returns
+        its final status."""
+        started = time.monotonic()
+        # The stream can drop (e.g. a scheduler restart); resume from the job's state
+        while True:
+            remaining = None if timeout is None else timeout - (time.monotonic() - started)
+            if remaining is not None and remaining <= 0:
+                raise TgpError(f"Job {job_id} did not finish within {timeout}s", grpc.StatusCode.DEADLINE_EXCEEDED)
+            try:
+                for info in self.watch(job_id, timeout=remaining):
+                    if info.status.is_terminal:
+                        return info
+            except TgpError as e:
+                if e.code not in (grpc.StatusCode.UNAVAILABLE, grpc.StatusCode.DEADLINE_EXCEEDED):
+                    raise
+                time.sleep(1)
+            info = self.status(job_id)
+            if info.status.is_terminal:
+                return info
+
+    def logs(self, job_id: str) -> Optional[str]:
+        """Tail of a finished job's logs; None while it runs or once the
+        scheduler no longer keeps them."""
+        response = self._call("GetJobLogs", pb.JobLogsRequest(job_id=job_id))
+        return response.logs if response.available else None
+
+    def cost(self, job_id: str) -> Optional[float]:
+        """Cost of the job in USD, None until it is priced."""
+        return self.status(job_id).cost_usd
+
+    def output(self, job_id: str) -> bytes:
+        """The job's output archive (see `output_path` in `submit`)."""
+        stream = self._call("GetJobOutput", pb.JobOutputRequest(job_id=job_id))
+        try:
+            return b"".join(chunk.data for chunk in stream)
+        except grpc.RpcError as e:
+            raise TgpError(f"GetJobOutput failed: {e.details()}", e.code()) from e
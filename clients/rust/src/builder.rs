@@ -0,0 +1,365 @@
+//! Typed job submissions
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::error::{ClientError, Result};
+use crate::proto::soft_constraint::Preference;
+use crate::proto::{
+    JobHook, JobPriority, JobSubmitRequest, JobType, ResourceRequirements, SlaConstraints, SoftConstraint,
+};
+
+/// A job ready to submit
+#[derive(Debug, Clone, PartialEq)]
+pub struct JobSpec {
+    pub(crate) request: JobSubmitRequest,
+}
+
+impl JobSpec {
+    /// A job running `image`: 1 CPU core, 1GB of memory, 10GB of disk and a
+    /// 1000ms latency limit unless set otherwise
+    pub fn builder(image: impl Into<String>) -> JobSpecBuilder {
+        JobSpecBuilder {
+            request: JobSubmitRequest {
+                job_type: JobType::Training.into(),
+                container_image: image.into(),
+                resources: Some(ResourceRequirements {
+                    cpu_cores: 1,
+                    memory_gb: 1,
+                    disk_gb: 10,
+                    ..Default::default()
+                }),
+                sla: Some(SlaConstraints { max_latency_ms: 1000, ..Default::default() }),
+                priority: JobPriority::Normal.into(),
+                ..Default::default()
+            },
+        }
+    }
+
+    pub fn id(&self) -> &str {
+        &self.request.job_id
+    }
+
+    /// The submission as sent (before a large input is uploaded)
+    pub fn request(&self) -> &JobSubmitRequest {
+        &self.request
+    }
+
+    pub fn into_request(self) -> JobSubmitRequest {
+        self.request
+    }
+}
+
+/// Lifecycle hook running `command` with sh in the job's image
+pub fn shell_hook(command: impl Into<String>) -> JobHook {
+    JobHook {
+        image: String::new(),
+        command: vec!["sh".to_string(), "-c".to_string(), command.into()],
+        timeout_secs: 0,
+    }
+}
+
+/// Builds a `JobSpec`; see the `JobSubmitRequest` fields for what each sets
+#[derive(Debug, Clone)]
+pub struct JobSpecBuilder {
+    request: JobSubmitRequest,
+}
+
+impl JobSpecBuilder {
+    /// Job id (default: a generated `job-<hex>`)
+    pub fn id(mut self, id: impl Into<String>) -> Self {
+        self.request.job_id = id.into();
+        self
+    }
+
+    pub fn job_type(mut self, job_type: JobType) -> Self {
+        self.request.job_type = job_type.into();
+        self
+    }
+
+    pub fn command<I, S>(mut self, command: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.request.command = command.into_iter().map(Into::into).collect();
+        self
+    }
+
+    pub fn cpu_cores(mut self, cores: u32) -> Self {
+        self.resources().cpu_cores = cores;
+        self
+    }
+
+    pub fn memory_gb(mut self, gb: u32) -> Self {
+        self.resources().memory_gb = gb;
+        self
+    }
+
+    pub fn gpus(mut self, count: u32) -> Self {
+        self.resources().gpu_count = count;
+        self
+    }
+
+    pub fn disk_gb(mut self, gb: u32) -> Self {
+        self.resources().disk_gb = gb;
+        self
+    }
+
+    pub fn max_latency_ms(mut self, ms: u64) -> Self {
+        self.sla().max_latency_ms = ms;
+        self
+    }
+
+    pub fn budget_usd(mut self, usd: f64) -> Self {
+        self.sla().max_budget_usd = Some(usd);
+        self
+    }
+
+    /// Cap the job's cost per hour, for services with no natural end
+    pub fn max_hourly_rate_usd(mut self, usd: f64) -> Self {
+        self.sla().max_hourly_rate_usd = Some(usd);
+        self
+    }
+
+    /// Finish by this Unix time
+    pub fn deadline(mut self, at: i64) -> Self {
+        self.sla().deadline = Some(at);
+        self
+    }
+
+    /// Do not start before this Unix time
+    pub fn earliest_start(mut self, at: i64) -> Self {
+        self.sla().earliest_start = Some(at);
+        self
+    }
+
+    /// Prefer nodes with at most `ms` of latency; `weight` is the share of
+    /// a node's price it is worth (1.0: pay up to double to meet it)
+    pub fn prefer_latency_ms(self, ms: u64, weight: f64) -> Self {
+        self.prefer(Preference::MaxLatencyMs(ms), weight)
+    }
+
+    /// Prefer nodes in `region`, e.g. "eu" for eu-west-1
+    pub fn prefer_region(self, region: impl Into<String>, weight: f64) -> Self {
+        self.prefer(Preference::Region(region.into()), weight)
+    }
+
+    /// Prefer placements costing at most `usd`
+    pub fn prefer_cost_usd(self, usd: f64, weight: f64) -> Self {
+        self.prefer(Preference::MaxCostUsd(usd), weight)
+    }
+
+    /// Expected run time on a reference node
+    pub fn duration_hours(mut self, hours: f64) -> Self {
+        self.request.estimated_duration_hours = Some(hours);
+        self
+    }
+
+    pub fn priority(mut self, priority: JobPriority) -> Self {
+        self.request.priority = priority.into();
+        self
+    }
+
+    pub fn tenant(mut self, tenant: impl Into<String>) -> Self {
+        self.request.tenant = tenant.into();
+        self
+    }
+
+    pub fn queue(mut self, queue: impl Into<String>) -> Self {
+        self.request.queue = queue.into();
+        self
+    }
+
+    pub fn pool(mut self, pool: impl Into<String>) -> Self {
+        self.request.pool = pool.into();
+        self
+    }
+
+    /// Add a CPU architecture the job can run on
+    pub fn arch(mut self, arch: impl Into<String>) -> Self {
+        self.request.arch.push(arch.into());
+        self
+    }
+
+    /// Add an input dataset
+    pub fn dataset(mut self, dataset: impl Into<String>) -> Self {
+        self.request.datasets.push(dataset.into());
+        self
+    }
+
+    pub fn env(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.request.environment.insert(name.into(), value.into());
+        self
+    }
+
+    pub fn envs(mut self, envs: HashMap<String, String>) -> Self {
+        self.request.environment.extend(envs);
+        self
+    }
+
+    /// Publish a container port
+    pub fn port(mut self, port: u32) -> Self {
+        self.request.ports.push(port);
+        self
+    }
+
+    /// Job input; inputs over the inline limit are uploaded on submission
+    pub fn input(mut self, data: impl Into<Vec<u8>>) -> Self {
+        self.request.job_data = data.into();
+        self
+    }
+
+    /// Feed the input to stdin rather than a file
+    pub fn input_stdin(mut self, stdin: bool) -> Self {
+        self.request.input_stdin = stdin;
+        self
+    }
+
+    pub fn output_path(mut self, path: impl Into<String>) -> Self {
+        self.request.output_path = path.into();
+        self
+    }
+
+    pub fn gang_size(mut self, nodes: u32) -> Self {
+        self.request.gang_size = nodes;
+        self
+    }
+
+    pub fn movable(mut self, movable: bool) -> Self {
+        self.request.movable = movable;
+        self
+    }
+
+    pub fn best_effort(mut self, best_effort: bool) -> Self {
+        self.request.best_effort = best_effort;
+        self
+    }
+
+    pub fn pin_cpus(mut self, pin: bool) -> Self {
+        self.request.pin_cpus = pin;
+        self
+    }
+
+    pub fn egress_limit_mbps(mut self, mbps: u32) -> Self {
+        self.request.egress_limit_mbps = Some(mbps);
+        self
+    }
+
+    pub fn disk_io_mbps(mut self, mbps: u32) -> Self {
+        self.resources().disk_io_mbps = Some(mbps);
+        self
+    }
+
+    /// Always run, even if an identical job already completed
+    pub fn disable_result_cache(mut self, disable: bool) -> Self {
+        self.request.disable_result_cache = disable;
+        self
+    }
+
+    /// Over budget everywhere: stay pending this long for prices to drop
+    pub fn price_hold_secs(mut self, secs: u64) -> Self {
+        self.request.price_hold_secs = Some(secs);
+        self
+    }
+
+    pub fn pre_start_hook(mut self, hook: JobHook) -> Self {
+        self.request.pre_start_hook = Some(hook);
+        self
+    }
+
+    pub fn post_complete_hook(mut self, hook: JobHook) -> Self {
+        self.request.post_complete_hook = Some(hook);
+        self
+    }
+
+    pub fn callback_url(mut self, url: impl Into<String>) -> Self {
+        self.request.callback_url = url.into();
+        self
+    }
+
+    /// Attach metadata returned unchanged with the job's status, e.g. the
+    /// git SHA or experiment id it belongs to
+    pub fn annotation(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.request.annotations.insert(key.into(), value.into());
+        self
+    }
+
+    /// Check the job and give it an id if it has none
+    pub fn build(mut self) -> Result<JobSpec> {
+        if self.request.container_image.is_empty() {
+            return Err(ClientError::Invalid("a job needs a container image".to_string()));
+        }
+        let resources = self.request.resources.clone().unwrap_or_default();
+        if resources.cpu_cores == 0 && resources.gpu_count == 0 {
+            return Err(ClientError::Invalid("a job needs at least one CPU core or GPU".to_string()));
+        }
+        if self.request.sla.as_ref().and_then(|sla| sla.max_budget_usd).is_some_and(|usd| usd < 0.0) {
+            return Err(ClientError::Invalid("budget must not be negative".to_string()));
+        }
+        if self.request.job_id.is_empty() {
+            self.request.job_id = generate_id();
+        }
+        Ok(JobSpec { request: self.request })
+    }
+
+    fn resources(&mut self) -> &mut ResourceRequirements {
+        self.request.resources.get_or_insert_with(Default::default)
+    }
+
+    fn prefer(mut self, preference: Preference, weight: f64) -> Self {
+        self.sla().preferences.push(SoftConstraint { weight, preference: Some(preference) });
+        self
+    }
+
+    fn sla(&mut self) -> &mut SlaConstraints {
+        self.request.sla.get_or_insert_with(Default::default)
+    }
+}
+
+/// Unique within the process and, by the clock and pid, across them
+fn generate_id() -> String {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_nanos() as u64).unwrap_or(0);
+    format!(
+        "job-{:x}{:04x}{:04x}",
+        nanos,
+        std::process::id() & 0xffff,
+        COUNTER.fetch_add(1, Ordering::Relaxed) & 0xffff
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builder_fills_the_submission() {
+        let spec = JobSpec::builder("pytorch/pytorch:2.1")
+            .command(["python", "train.py"])
+            .gpus(2)
+            .memory_gb(32)
+            .budget_usd(5.0)
+            .priority(JobPriority::High)
+            .env("EPOCHS", "10")
+            .post_complete_hook(shell_hook("echo $TGP_JOB_STATUS"))
+            .build()
+            .unwrap();
+        let request = spec.request();
+        assert!(spec.id().starts_with("job-"));
+        assert_eq!(request.command, ["python", "train.py"]);
+        let resources = request.resources.as_ref().unwrap();
+        assert_eq!((resources.cpu_cores, resources.memory_gb, resources.gpu_count), (1, 32, 2));
+        let sla = request.sla.as_ref().unwrap();
+        assert_eq!((sla.max_latency_ms, sla.max_budget_usd), (1000, Some(5.0)));
+        assert_eq!(request.priority(), JobPriority::High);
+        assert_eq!(request.post_complete_hook.as_ref().unwrap().command[0], "sh");
+
+        let other = JobSpec::builder("alpine").build().unwrap();
+        assert_ne!(other.id(), spec.id());
+        assert_eq!(JobSpec::builder("alpine").id("etl-7").build().unwrap().id(), "etl-7");
+        assert!(JobSpec::builder("").build().is_err());
+        assert!(JobSpec::builder("alpine").cpu_cores(0).build().is_err());
+    }
+}
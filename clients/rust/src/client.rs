@@ -0,0 +1,349 @@
+//! Scheduler connection
+
+use std::future::Future;
+use std::time::Duration;
+
+use tokio_stream::{Stream, StreamExt};
+use tonic::metadata::{Ascii, MetadataValue};
+use tonic::transport::{Channel, Endpoint};
+use tonic::{Code, Request, Response, Status, Streaming};
+
+use crate::builder::JobSpec;
+use crate::error::{ClientError, Result};
+use crate::proto::scheduler_service_client::SchedulerServiceClient;
+use crate::proto::{
+    exec_in_job_request, BuildStatusRequest, BuildStatusResponse, BuildSubmitRequest, BuildSubmitResponse,
+    ClusterStatusRequest, ClusterStatusResponse, CostEstimate, CreditBalance, CreditBalanceRequest, DemandSignals,
+    DemandSignalsRequest, ExecInJobRequest, ExecInJobResponse, ExecStart, JobArrayStatusRequest, JobArrayStatusResponse,
+    JobArraySubmitRequest, JobArraySubmitResponse, JobHistoryResponse, JobLogsRequest, JobOutputRequest, JobStatus,
+    JobStatusRequest, JobStatusResponse, JobSubmitResponse, ListCapacityReservationsRequest,
+    ListCapacityReservationsResponse, PayloadChunk, ReserveCapacityRequest, ReserveCapacityResponse, RightSizingRequest,
+    RightSizingResponse,
+};
+use crate::retry::RetryPolicy;
+
+/// Inputs larger than this are uploaded with UploadPayload first (the
+/// scheduler's limit on inline input)
+pub const INLINE_INPUT_BYTES: usize = 1024 * 1024;
+
+/// Whether a job in this state is finished
+pub fn is_terminal(status: JobStatus) -> bool {
+    matches!(status, JobStatus::Completed | JobStatus::Failed | JobStatus::Cancelled)
+}
+
+/// Connection settings for a `TgpClient`
+#[derive(Debug, Clone)]
+pub struct TgpClientBuilder {
+    endpoint: String,
+    token: Option<String>,
+    timeout: Duration,
+    connect_timeout: Duration,
+    retry: RetryPolicy,
+}
+
+impl TgpClientBuilder {
+    /// API token sent as a bearer token with every call
+    pub fn token(mut self, token: impl Into<String>) -> Self {
+        self.token = Some(token.into());
+        self
+    }
+
+    /// Deadline of each unary call (streams run until they end)
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = timeout;
+        self
+    }
+
+    pub fn retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    pub async fn connect(self) -> Result<TgpClient> {
+        let channel = Endpoint::from_shared(self.endpoint)?
+            .connect_timeout(self.connect_timeout)
+            .connect()
+            .await?;
+        let authorization = self.token.as_deref().map(bearer).transpose()?;
+        Ok(TgpClient {
+            inner: SchedulerServiceClient::new(channel),
+            authorization,
+            timeout: self.timeout,
+            retry: self.retry,
+        })
+    }
+}
+
+fn bearer(token: &str) -> Result<MetadataValue<Ascii>> {
+    format!("Bearer {}", token)
+        .parse()
+        .map_err(|_| ClientError::Invalid("token is not valid in a header".to_string()))
+}
+
+/// A scheduler connection; cheap to clone, clones share the channel
+#[derive(Debug, Clone)]
+pub struct TgpClient {
+    inner: SchedulerServiceClient<Channel>,
+    authorization: Option<MetadataValue<Ascii>>,
+    timeout: Duration,
+    retry: RetryPolicy,
+}
+
+impl TgpClient {
+    /// Settings for a connection to `endpoint`, e.g. `http://scheduler:50051`
+    pub fn builder(endpoint: impl Into<String>) -> TgpClientBuilder {
+        TgpClientBuilder {
+            endpoint: endpoint.into(),
+            token: None,
+            timeout: Duration::from_secs(30),
+            connect_timeout: Duration::from_secs(10),
+            retry: RetryPolicy::default(),
+        }
+    }
+
+    /// Connect with the default settings
+    pub async fn connect(endpoint: impl Into<String>) -> Result<Self> {
+        Self::builder(endpoint).connect().await
+    }
+
+    /// This connection, calling with `token` instead
+    pub fn with_token(&self, token: &str) -> Result<Self> {
+        Ok(Self { authorization: Some(bearer(token)?), ..self.clone() })
+    }
+
+    /// The generated client, for calls not wrapped here (pair it with
+    /// `request` for the token and deadline)
+    pub fn raw(&self) -> SchedulerServiceClient<Channel> {
+        self.inner.clone()
+    }
+
+    /// `message` with this connection's token and deadline
+    pub fn request<T>(&self, message: T) -> Request<T> {
+        let mut request = self.stream_request(message);
+        request.set_timeout(self.timeout);
+        request
+    }
+
+    /// `message` with this connection's token and no deadline
+    fn stream_request<T>(&self, message: T) -> Request<T> {
+        let mut request = Request::new(message);
+        if let Some(authorization) = &self.authorization {
+            request.metadata_mut().insert("authorization", authorization.clone());
+        }
+        request
+    }
+
+    /// Make a unary call, retried per the policy
+    async fn unary<T, R, F, Fut>(&self, message: T, call: F) -> Result<R>
+    where
+        T: Clone,
+        F: Fn(SchedulerServiceClient<Channel>, Request<T>) -> Fut,
+        Fut: Future<Output = std::result::Result<Response<R>, Status>>,
+    {
+        let response = self.retry
+            .run(|| call(self.inner.clone(), self.request(message.clone())))
+            .await?;
+        Ok(response.into_inner())
+    }
+
+    /// Submit a job, uploading its input first if it is too large to send
+    /// inline
+    pub async fn submit(&self, spec: JobSpec) -> Result<JobSubmitResponse> {
+        let mut request = spec.request;
+        if request.job_data.len() > INLINE_INPUT_BYTES {
+            request.payload_digest = self.upload_payload(std::mem::take(&mut request.job_data)).await?;
+        }
+        let job_id = request.job_id.clone();
+        let response = self.unary(request, |mut client, request| async move { client.submit_job(request).await }).await?;
+        if !response.success {
+            return Err(ClientError::Rejected { job_id, message: response.message });
+        }
+        Ok(response)
+    }
+
+    /// Upload a job input, returning the digest to submit it by
+    pub async fn upload_payload(&self, data: Vec<u8>) -> Result<String> {
+        tracing::debug!("Uploading {} byte input payload", data.len());
+        let chunks: Vec<PayloadChunk> = data
+            .chunks(INLINE_INPUT_BYTES)
+            .map(|chunk| PayloadChunk { data: chunk.to_vec() })
+            .collect();
+        let ack = self.retry
+            .run(|| {
+                let mut client = self.inner.clone();
+                let request = self.stream_request(tokio_stream::iter(chunks.clone()));
+                async move { client.upload_payload(request).await }
+            })
+            .await?
+            .into_inner();
+        Ok(ack.digest)
+    }
+
+    pub async fn status(&self, job_id: &str) -> Result<JobStatusResponse> {
+        let request = JobStatusRequest { job_id: job_id.to_string() };
+        self.unary(request, |mut client, request| async move { client.get_job_status(request).await }).await
+    }
+
+    /// Every status change the job went through, oldest first
+    pub async fn history(&self, job_id: &str) -> Result<JobHistoryResponse> {
+        let request = JobStatusRequest { job_id: job_id.to_string() };
+        self.unary(request, |mut client, request| async move { client.get_job_history(request).await }).await
+    }
+
+    /// The job's current state, then each change until it finishes
+    pub async fn watch(&self, job_id: &str) -> Result<impl Stream<Item = Result<JobStatusResponse>> + Unpin + Send> {
+        let stream = self.retry
+            .run(|| {
+                let mut client = self.inner.clone();
+                let request = self.stream_request(JobStatusRequest { job_id: job_id.to_string() });
+                async move { client.watch_job(request).await }
+            })
+            .await?
+            .into_inner();
+        Ok(stream.map(|update| update.map_err(ClientError::from)))
+    }
+
+    /// Wait for the job to finish, following it across dropped streams;
+    /// returns its final state
+    pub async fn wait(&self, job_id: &str, timeout: Option<Duration>) -> Result<JobStatusResponse> {
+        let wait = async {
+            let mut failures = 0;
+            loop {
+                let mut stream = self.watch(job_id).await?;
+                while let Some(update) = stream.next().await {
+                    match update {
+                        Ok(status) if is_terminal(status.status()) => return Ok(status),
+                        Ok(_) => failures = 0,
+                        Err(e) if e.code() == Some(Code::Unavailable) => break,
+                        Err(e) => return Err(e),
+                    }
+                }
+                failures += 1;
+                if failures >= self.retry.max_attempts {
+                    return Err(Status::unavailable(format!("Lost the status stream of job {}", job_id)).into());
+                }
+                tokio::time::sleep(self.retry.backoff(failures)).await;
+            }
+        };
+        match timeout {
+            Some(timeout) => tokio::time::timeout(timeout, wait).await.map_err(|_| ClientError::Timeout(timeout))?,
+            None => wait.await,
+        }
+    }
+
+    /// Tail of a finished job's logs; none while it runs or once the
+    /// scheduler no longer keeps them
+    pub async fn logs(&self, job_id: &str) -> Result<Option<String>> {
+        let request = JobLogsRequest { job_id: job_id.to_string() };
+        let response = self.unary(request, |mut client, request| async move { client.get_job_logs(request).await }).await?;
+        Ok(response.available.then_some(response.logs))
+    }
+
+    /// What the job cost, once it is priced
+    pub async fn cost(&self, job_id: &str) -> Result<Option<CostEstimate>> {
+        Ok(self.status(job_id).await?.final_cost)
+    }
+
+    /// The job's output archive
+    pub async fn output(&self, job_id: &str) -> Result<Vec<u8>> {
+        let mut stream = self.inner.clone()
+            .get_job_output(self.stream_request(JobOutputRequest { job_id: job_id.to_string() }))
+            .await?
+            .into_inner();
+        let mut archive = Vec::new();
+        while let Some(chunk) = stream.message().await? {
+            archive.extend_from_slice(&chunk.data);
+        }
+        Ok(archive)
+    }
+
+    /// Submit `template` as `size` indexed tasks
+    pub async fn submit_array(
+        &self,
+        template: JobSpec,
+        size: u32,
+        max_parallel: u32,
+        max_failures: Option<u32>,
+    ) -> Result<JobArraySubmitResponse> {
+        let request = JobArraySubmitRequest { template: Some(template.request), size, max_parallel, max_failures };
+        self.unary(request, |mut client, request| async move { client.submit_job_array(request).await }).await
+    }
+
+    pub async fn array_status(&self, array_id: &str) -> Result<JobArrayStatusResponse> {
+        let request = JobArrayStatusRequest { array_id: array_id.to_string() };
+        self.unary(request, |mut client, request| async move { client.get_job_array_status(request).await }).await
+    }
+
+    pub async fn cluster_status(&self) -> Result<ClusterStatusResponse> {
+        let request = ClusterStatusRequest { known_version: None };
+        self.unary(request, |mut client, request| async move { client.get_cluster_status(request).await }).await
+    }
+
+    /// Queue depth, utilization and market rate per resource class
+    pub async fn demand_signals(&self) -> Result<DemandSignals> {
+        let request = DemandSignalsRequest { interval_secs: 0 };
+        self.unary(request, |mut client, request| async move { client.get_demand_signals(request).await }).await
+    }
+
+    /// Demand signals every `interval` (whole seconds)
+    pub async fn watch_demand_signals(&self, interval: Duration) -> Result<impl Stream<Item = Result<DemandSignals>> + Unpin + Send> {
+        let interval_secs = interval.as_secs().clamp(1, u32::MAX as u64) as u32;
+        let stream = self.retry
+            .run(|| {
+                let mut client = self.inner.clone();
+                let request = self.stream_request(DemandSignalsRequest { interval_secs });
+                async move { client.watch_demand_signals(request).await }
+            })
+            .await?
+            .into_inner();
+        Ok(stream.map(|signals| signals.map_err(ClientError::from)))
+    }
+
+    /// Build an image from a .tar.gz context
+    pub async fn submit_build(&self, request: BuildSubmitRequest) -> Result<BuildSubmitResponse> {
+        self.unary(request, |mut client, request| async move { client.submit_build(request).await }).await
+    }
+
+    pub async fn build_status(&self, build_id: &str) -> Result<BuildStatusResponse> {
+        let request = BuildStatusRequest { build_id: build_id.to_string() };
+        self.unary(request, |mut client, request| async move { client.get_build(request).await }).await
+    }
+
+    pub async fn right_sizing(&self, template: Option<&str>) -> Result<RightSizingResponse> {
+        let request = RightSizingRequest { template: template.unwrap_or_default().to_string() };
+        self.unary(request, |mut client, request| async move { client.get_right_sizing(request).await }).await
+    }
+
+    pub async fn reserve_capacity(&self, request: ReserveCapacityRequest) -> Result<ReserveCapacityResponse> {
+        self.unary(request, |mut client, request| async move { client.reserve_capacity(request).await }).await
+    }
+
+    /// The tenant's prepaid balance, reserved estimates and available credit
+    pub async fn credit_balance(&self, tenant: &str) -> Result<CreditBalance> {
+        let request = CreditBalanceRequest { tenant: tenant.to_string() };
+        self.unary(request, |mut client, request| async move { client.get_credit_balance(request).await }).await
+    }
+
+    pub async fn reservations(&self, tenant: Option<&str>) -> Result<ListCapacityReservationsResponse> {
+        let request = ListCapacityReservationsRequest { tenant: tenant.unwrap_or_default().to_string() };
+        self.unary(request, |mut client, request| async move { client.list_capacity_reservations(request).await }).await
+    }
+
+    /// Run a command in a running job's container, feeding it `stdin`
+    pub async fn exec<S>(&self, job_id: &str, command: Vec<String>, tty: bool, stdin: S) -> Result<Streaming<ExecInJobResponse>>
+    where
+        S: Stream<Item = Vec<u8>> + Send + 'static,
+    {
+        let start = ExecInJobRequest {
+            body: Some(exec_in_job_request::Body::Start(ExecStart { job_id: job_id.to_string(), command, tty })),
+        };
+        let input = tokio_stream::once(start)
+            .chain(stdin.map(|data| ExecInJobRequest { body: Some(exec_in_job_request::Body::Stdin(data)) }));
+        Ok(self.inner.clone().exec_in_job(self.stream_request(input)).await?.into_inner())
+    }
+}
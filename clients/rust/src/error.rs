@@ -0,0 +1,50 @@
+//! Client errors
+
+use std::time::Duration;
+
+use crate::proto::ErrorDetail;
+
+pub type Result<T> = std::result::Result<T, ClientError>;
+
+#[derive(Debug, thiserror::Error)]
+pub enum ClientError {
+    #[error("Failed to connect to the scheduler: {0}")]
+    Connect(#[from] tonic::transport::Error),
+    /// The scheduler refused or failed the call (boxed: a `Status` is large)
+    #[error("{}: {}", .0.code(), .0.message())]
+    Status(Box<tonic::Status>),
+    /// The scheduler answered but did not take the job
+    #[error("Job {job_id} was not accepted: {message}")]
+    Rejected { job_id: String, message: String },
+    #[error("Gave up after {0:?}")]
+    Timeout(Duration),
+    #[error("Invalid request: {0}")]
+    Invalid(String),
+}
+
+impl From<tonic::Status> for ClientError {
+    fn from(status: tonic::Status) -> Self {
+        ClientError::Status(Box::new(status))
+    }
+}
+
+impl ClientError {
+    /// gRPC code of a refused call
+    pub fn code(&self) -> Option<tonic::Code> {
+        match self {
+            ClientError::Status(status) => Some(status.code()),
+            _ => None,
+        }
+    }
+
+    /// Structured reason (and SLA suggestions) the scheduler attached to a
+    /// refusal
+    pub fn detail(&self) -> Option<ErrorDetail> {
+        match self {
+            ClientError::Status(status) if !status.details().is_empty() => {
+                <ErrorDetail as prost::Message>::decode(status.details()).ok()
+            }
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,26 @@
+//! TGP client library
+//!
+//! Submits jobs to a TGP scheduler and follows them, so services can embed
+//! submission without carrying their own copy of the proto plumbing.
+//! `JobSpec::builder` assembles a submission, `TgpClient` sends it (large
+//! inputs are uploaded first) and waits on, streams or fetches the result.
+//! Calls carry a deadline and are retried per a `RetryPolicy` while the
+//! scheduler is unreachable; anything not wrapped is reachable through
+//! `TgpClient::raw` and the generated `proto` types.
+
+pub mod builder;
+pub mod client;
+pub mod error;
+pub mod retry;
+
+// Include generated proto code
+pub mod proto {
+    // Command messages carry a whole job assignment
+    #![allow(clippy::large_enum_variant)]
+    tonic::include_proto!("tgp.scheduler.v1");
+}
+
+pub use builder::{shell_hook, JobSpec, JobSpecBuilder};
+pub use client::{is_terminal, TgpClient, TgpClientBuilder};
+pub use error::{ClientError, Result};
+pub use retry::RetryPolicy;
@@ -0,0 +1,114 @@
+//! Retries of calls that did not reach the scheduler
+//!
+//! Only failures that mean the scheduler could not be reached (or shed the
+//! call) are retried, with exponential backoff; refusals are returned at
+//! once. A retried submission the scheduler did receive the first time
+//! fails with `AlreadyExists`.
+
+use std::future::Future;
+use std::time::Duration;
+
+use tonic::{Code, Status};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RetryPolicy {
+    /// Attempts in all, the first included (1: no retries)
+    pub max_attempts: u32,
+    /// Wait before the first retry, doubled for each one after
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 4,
+            initial_backoff: Duration::from_millis(250),
+            max_backoff: Duration::from_secs(5),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// A policy that never retries
+    pub fn none() -> Self {
+        Self { max_attempts: 1, ..Default::default() }
+    }
+
+    /// Whether a failed call is worth trying again
+    pub fn is_retryable(status: &Status) -> bool {
+        matches!(status.code(), Code::Unavailable | Code::Aborted)
+    }
+
+    /// Wait before retry `retry` (1 for the first)
+    pub fn backoff(&self, retry: u32) -> Duration {
+        self.initial_backoff
+            .saturating_mul(2u32.saturating_pow(retry.saturating_sub(1)))
+            .min(self.max_backoff)
+    }
+
+    /// Run `call` until it succeeds, fails for good or runs out of attempts
+    pub async fn run<T, F, Fut>(&self, mut call: F) -> Result<T, Status>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, Status>>,
+    {
+        let mut attempt = 1;
+        loop {
+            match call().await {
+                Err(status) if attempt < self.max_attempts && Self::is_retryable(&status) => {
+                    tracing::debug!("Scheduler call failed (attempt {}): {}", attempt, status.message());
+                    tokio::time::sleep(self.backoff(attempt)).await;
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    #[tokio::test]
+    async fn test_only_unreachable_calls_are_retried() {
+        let policy = RetryPolicy {
+            max_attempts: 3,
+            initial_backoff: Duration::from_millis(1),
+            max_backoff: Duration::from_millis(3),
+        };
+        assert_eq!(policy.backoff(1), Duration::from_millis(1));
+        assert_eq!(policy.backoff(2), Duration::from_millis(2));
+        assert_eq!(policy.backoff(5), Duration::from_millis(3));
+
+        let calls = AtomicU32::new(0);
+        let result = policy.run(|| async {
+            if calls.fetch_add(1, Ordering::SeqCst) < 2 {
+                Err(Status::unavailable("connection refused"))
+            } else {
+                Ok("job-1")
+            }
+        }).await;
+        assert_eq!(result.unwrap(), "job-1");
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+
+        // Refusals come back at once, unreachable calls after the last attempt
+        calls.store(0, Ordering::SeqCst);
+        let result: Result<(), _> = policy.run(|| async {
+            calls.fetch_add(1, Ordering::SeqCst);
+            Err(Status::invalid_argument("no image"))
+        }).await;
+        assert_eq!(result.unwrap_err().code(), Code::InvalidArgument);
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        calls.store(0, Ordering::SeqCst);
+        let result: Result<(), _> = policy.run(|| async {
+            calls.fetch_add(1, Ordering::SeqCst);
+            Err(Status::unavailable("connection refused"))
+        }).await;
+        assert!(result.is_err());
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+    }
+}
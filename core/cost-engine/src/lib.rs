@@ -66,6 +66,7 @@ impl CostCalculator {
     }
 
     /// Calculate total cost: C_total = C_comp + C_data + C_idle
+    #[allow(clippy::too_many_arguments)]
     pub fn total_cost(
         &self,
         instance_price_per_hour: f64,
@@ -2,13 +2,29 @@
 //!
 //! Implements optimization algorithms for job placement and resource allocation
 
+pub mod consolidation;
+
+pub use consolidation::{ConsolidationConfig, ConsolidationPlan, Migration, NodeLoad, PlacedJob};
+
 /// Optimizer for job placement decisions
 #[derive(Debug, Clone)]
-pub struct Optimizer;
+pub struct Optimizer {
+    /// Seed for tie-breaking; `None` breaks ties by id
+    seed: Option<u64>,
+}
 
 impl Optimizer {
     pub fn new() -> Self {
-        Self
+        Self { seed: None }
+    }
+
+    /// An optimizer whose plans depend only on its input and `seed`
+    pub fn seeded(seed: u64) -> Self {
+        Self { seed: Some(seed) }
+    }
+
+    pub fn seed(&self) -> Option<u64> {
+        self.seed
     }
 
     /// Find optimal placement using greedy algorithm (MVP)
@@ -18,6 +34,16 @@ impl Optimizer {
         // TODO: Implement optimization logic
         OptimizationResult::default()
     }
+
+    /// Plan job migrations that consolidate load onto fewer nodes
+    pub fn plan_consolidation(
+        &self,
+        nodes: &[NodeLoad],
+        jobs: &[PlacedJob],
+        config: &ConsolidationConfig,
+    ) -> ConsolidationPlan {
+        consolidation::plan_consolidation_seeded(nodes, jobs, config, self.seed)
+    }
 }
 
 impl Default for Optimizer {
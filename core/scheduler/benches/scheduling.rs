@@ -0,0 +1,128 @@
+//! Scheduling hot-path benchmarks
+//!
+//! Measures single-job `schedule()` latency across cluster sizes and batch
+//! placement throughput (sequential and concurrent submitters), so optimizer
+//! and locking changes can be compared run over run:
+//!
+//!     cargo bench -p tgp-scheduler --bench scheduling
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use tgp_scheduler::{EconomicScheduler, JobSpec, NodeInfo, ResourceRequirements, SlaConstraints};
+use tokio::runtime::Runtime;
+
+const CLUSTER_SIZES: [usize; 3] = [100, 1_000, 10_000];
+const BATCH_SIZE: usize = 100;
+
+/// Deterministic heterogeneous cluster of `size` nodes
+///
+/// Uses a fixed linear congruential sequence so runs are comparable without
+/// pulling in a RNG dependency.
+fn synthetic_cluster(size: usize, seed: u64) -> Vec<NodeInfo> {
+    let mut state = seed;
+    let mut next = move || {
+        state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+        state >> 33
+    };
+
+    (0..size)
+        .map(|i| {
+            let cpu = [2, 4, 8, 16, 32][(next() % 5) as usize];
+            NodeInfo {
+                id: format!("bench-node-{}", i),
+                available_cpu: cpu,
+                available_memory_gb: cpu * 4,
+                available_gpu: if next() % 5 == 0 { 1 } else { 0 },
+                location: format!("region-{}", next() % 8),
+                cost_per_hour: cpu as f64 * (0.02 + (next() % 40) as f64 / 1000.0),
+                ..Default::default()
+            }
+        })
+        .collect()
+}
+
+fn scheduler_with_cluster(size: usize) -> EconomicScheduler {
+    let scheduler = EconomicScheduler::new();
+    for node in synthetic_cluster(size, 42) {
+        scheduler.register_node(node).unwrap();
+    }
+    scheduler
+}
+
+fn job(id: String) -> JobSpec {
+    JobSpec {
+        resources: ResourceRequirements {
+            cpu_cores: 4,
+            memory_gb: 8,
+            disk_gb: 10,
+            ..Default::default()
+        },
+        sla: SlaConstraints {
+            max_latency_ms: 1_000,
+            ..Default::default()
+        },
+        job_data: id.as_bytes().to_vec(),
+        disable_result_cache: true,
+        id,
+        ..Default::default()
+    }
+}
+
+fn bench_schedule_latency(c: &mut Criterion) {
+    let rt = Runtime::new().unwrap();
+    let mut group = c.benchmark_group("schedule_latency");
+
+    for size in CLUSTER_SIZES {
+        let scheduler = rt.block_on(async { scheduler_with_cluster(size) });
+        group.bench_with_input(BenchmarkId::from_parameter(size), &size, |b, _| {
+            b.iter(|| {
+                let placement = rt.block_on(scheduler.schedule(job("bench-job".to_string())));
+                black_box(placement.unwrap());
+            });
+        });
+    }
+
+    group.finish();
+}
+
+fn bench_batch_throughput(c: &mut Criterion) {
+    let rt = Runtime::new().unwrap();
+    let mut group = c.benchmark_group("batch_placement");
+    group.throughput(Throughput::Elements(BATCH_SIZE as u64));
+    group.sample_size(20);
+
+    for size in CLUSTER_SIZES {
+        let scheduler = rt.block_on(async { scheduler_with_cluster(size) });
+
+        group.bench_with_input(BenchmarkId::new("sequential", size), &size, |b, _| {
+            b.iter(|| {
+                rt.block_on(async {
+                    for i in 0..BATCH_SIZE {
+                        black_box(scheduler.schedule(job(format!("seq-{}", i))).await.unwrap());
+                    }
+                });
+            });
+        });
+
+        // Concurrent submitters contend on the scheduler's shared state
+        group.bench_with_input(BenchmarkId::new("concurrent", size), &size, |b, _| {
+            b.iter(|| {
+                rt.block_on(async {
+                    let handles: Vec<_> = (0..BATCH_SIZE)
+                        .map(|i| {
+                            let scheduler = scheduler.clone();
+                            tokio::spawn(async move { scheduler.schedule(job(format!("par-{}", i))).await })
+                        })
+                        .collect();
+                    for handle in handles {
+                        black_box(handle.await.unwrap().unwrap());
+                    }
+                });
+            });
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_schedule_latency, bench_batch_throughput);
+criterion_main!(benches);
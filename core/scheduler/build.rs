@@ -1,10 +1,20 @@
 fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // The client serves the binary's admin subcommands
     tonic_build::configure()
         .build_server(true)
-        .build_client(false)
+        .build_client(true)
         .compile(
             &["../../proto/scheduler.proto"],
             &["../../proto"],
         )?;
+
+    // The scheduler queries workers' control service
+    tonic_build::configure()
+        .build_server(false)
+        .build_client(true)
+        .compile(
+            &["../../proto/worker.proto"],
+            &["../../proto"],
+        )?;
     Ok(())
 }
@@ -0,0 +1,9 @@
+-- Registration fencing identities (see fencing.rs), so a restarted
+-- scheduler still rejects superseded workers
+
+CREATE TABLE IF NOT EXISTS node_identities (
+    node_id     TEXT PRIMARY KEY,
+    token       BIGINT NOT NULL,
+    identity    JSONB NOT NULL,
+    updated_at  TIMESTAMPTZ NOT NULL DEFAULT now()
+);
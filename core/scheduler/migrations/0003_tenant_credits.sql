@@ -0,0 +1,13 @@
+-- Tenant credit balances and the top-up references already credited
+-- (see credits.rs)
+
+CREATE TABLE IF NOT EXISTS tenant_credits (
+    tenant      TEXT PRIMARY KEY,
+    account     JSONB NOT NULL,
+    updated_at  TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE TABLE IF NOT EXISTS credit_references (
+    reference   TEXT PRIMARY KEY,
+    created_at  TIMESTAMPTZ NOT NULL DEFAULT now()
+);
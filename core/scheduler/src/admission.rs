@@ -0,0 +1,295 @@
+//! Admission hooks
+//!
+//! Before a submission is accepted it can be sent to an external policy
+//! engine, which allows it, denies it with a reason, or returns a mutated
+//! spec (e.g. a forced image registry or a capped priority). Two backends:
+//!
+//! - webhook: POST the `JobSpec` as JSON, expect an `AdmissionDecision`
+//! - OPA: POST `{"input": <JobSpec>}` to an OPA data API path whose Rego
+//!   policy evaluates to an `AdmissionDecision`-shaped object
+//!
+//! When the hook cannot be reached the submission is rejected, unless the
+//! hook is configured to fail open.
+
+use anyhow::Result;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+use crate::error::SchedulerError;
+use crate::{EconomicScheduler, JobSpec};
+
+/// Policy engine verdict on one submission
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AdmissionDecision {
+    pub allowed: bool,
+    /// Why the job was denied (or a note on the mutation)
+    #[serde(default)]
+    pub reason: String,
+    /// Replacement spec, if the policy mutated the job
+    #[serde(default)]
+    pub job: Option<JobSpec>,
+}
+
+/// External policy check run on every submission
+#[async_trait]
+pub trait AdmissionHook: Send + Sync {
+    async fn review(&self, job: &JobSpec) -> Result<AdmissionDecision>;
+}
+
+/// A hook and what to do when it errors
+#[derive(Clone)]
+pub struct AdmissionController {
+    hook: Arc<dyn AdmissionHook>,
+    /// Accept submissions unchanged when the hook errors
+    fail_open: bool,
+}
+
+impl AdmissionController {
+    pub fn new(hook: Arc<dyn AdmissionHook>, fail_open: bool) -> Self {
+        Self { hook, fail_open }
+    }
+}
+
+/// Connect to the hook named by `url`
+///
+/// `https://...` (JSON webhook) or `opa+http://opa:8181/v1/data/tgp/admission`.
+pub fn connect_hook(url: &str) -> Result<Arc<dyn AdmissionHook>> {
+    if let Some(endpoint) = url.strip_prefix("opa+") {
+        #[cfg(feature = "webhooks")]
+        return Ok(Arc::new(webhook::OpaHook::new(endpoint)));
+        #[cfg(not(feature = "webhooks"))]
+        {
+            let _ = endpoint;
+            anyhow::bail!("OPA support not compiled in (enable the `webhooks` feature)");
+        }
+    }
+
+    if url.starts_with("http://") || url.starts_with("https://") {
+        #[cfg(feature = "webhooks")]
+        return Ok(Arc::new(webhook::WebhookHook::new(url)));
+        #[cfg(not(feature = "webhooks"))]
+        anyhow::bail!("Webhook support not compiled in (enable the `webhooks` feature)");
+    }
+
+    anyhow::bail!("Unsupported admission hook URL: {}", url)
+}
+
+impl EconomicScheduler {
+    pub fn set_admission(&mut self, admission: Option<AdmissionController>) {
+        self.admission = admission;
+    }
+
+    /// Resolve a `build:` image, run the plugin admission hooks, then the
+    /// external hook, on a submission, pin the image digest and store the
+    /// input payload of the spec they leave, put it in its named queue and
+    /// node pool, and finally apply the job type's cost ceiling; return the
+    /// spec to accept, an error means the job is denied
+    pub async fn admit(&self, job: JobSpec) -> Result<JobSpec> {
+        let job = self.resolve_build_image(job)?;
+        let job = self.plugins.admit(job)?;
+        let job = self.review(job).await?;
+        // Hooks may swap the image or the input, so pin and store the final ones
+        let job = self.pin_image(job).await?;
+        let job = self.check_image_arch(job).await?;
+        let job = self.store_payload(job).await?;
+        let job = self.assign_queue(job)?;
+        self.check_pool(&job)?;
+        self.check_hooks(&job)?;
+        self.check_callback(&job)?;
+        self.check_start_window(&job)?;
+        self.apply_cost_ceiling(job)
+    }
+
+    /// Run the external admission hook, if any
+    async fn review(&self, job: JobSpec) -> Result<JobSpec> {
+        let Some(admission) = &self.admission else {
+            return Ok(job);
+        };
+
+        let decision = match admission.hook.review(&job).await {
+            Ok(decision) => decision,
+            Err(e) if admission.fail_open => {
+                tracing::warn!("Admission hook failed for job {}, admitting unchanged: {}", job.id, e);
+                return Ok(job);
+            }
+            Err(e) => return Err(SchedulerError::Unavailable { reason: format!("admission hook: {}", e) }.into()),
+        };
+
+        if !decision.allowed {
+            tracing::info!("Job {} denied by admission policy: {}", job.id, decision.reason);
+            return Err(SchedulerError::AdmissionDenied { reason: decision.reason }.into());
+        }
+        match decision.job {
+            Some(mutated) if mutated.id != job.id => {
+                Err(SchedulerError::AdmissionDenied {
+                    reason: format!("policy may not change the job id ({} -> {})", job.id, mutated.id),
+                }.into())
+            }
+            Some(mutated) => {
+                tracing::info!("Job {} mutated by admission policy", job.id);
+                Ok(mutated)
+            }
+            None => Ok(job),
+        }
+    }
+}
+
+#[cfg(feature = "webhooks")]
+mod webhook {
+    use super::*;
+    use std::time::Duration;
+
+    const TIMEOUT: Duration = Duration::from_secs(5);
+
+    /// POSTs the spec and reads back a decision
+    pub struct WebhookHook {
+        client: reqwest::Client,
+        url: String,
+    }
+
+    impl WebhookHook {
+        pub fn new(url: &str) -> Self {
+            Self { client: reqwest::Client::new(), url: url.to_string() }
+        }
+    }
+
+    #[async_trait]
+    impl AdmissionHook for WebhookHook {
+        async fn review(&self, job: &JobSpec) -> Result<AdmissionDecision> {
+            Ok(self.client.post(&self.url)
+                .json(job)
+                .timeout(TIMEOUT)
+                .send()
+                .await?
+                .error_for_status()?
+                .json()
+                .await?)
+        }
+    }
+
+    /// Queries a policy document on an OPA server
+    pub struct OpaHook {
+        client: reqwest::Client,
+        url: String,
+    }
+
+    impl OpaHook {
+        pub fn new(url: &str) -> Self {
+            Self { client: reqwest::Client::new(), url: url.to_string() }
+        }
+    }
+
+    #[derive(Deserialize)]
+    struct OpaResponse {
+        result: Option<AdmissionDecision>,
+    }
+
+    #[async_trait]
+    impl AdmissionHook for OpaHook {
+        async fn review(&self, job: &JobSpec) -> Result<AdmissionDecision> {
+            let response: OpaResponse = self.client.post(&self.url)
+                .json(&serde_json::json!({ "input": job }))
+                .timeout(TIMEOUT)
+                .send()
+                .await?
+                .error_for_status()?
+                .json()
+                .await?;
+            // An undefined policy document is an error, not a silent allow
+            response.result.ok_or_else(|| anyhow::anyhow!("OPA policy at {} is undefined", self.url))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::images::{ImagePolicy, ImageRef, ImageResolver};
+    use crate::payloads::{PayloadLimits, PayloadStore};
+    use crate::priority::PriorityClass;
+    use std::collections::HashMap;
+
+    /// Denies tenant "blocked", caps everyone else at normal priority
+    struct TenantPolicy;
+
+    #[async_trait]
+    impl AdmissionHook for TenantPolicy {
+        async fn review(&self, job: &JobSpec) -> Result<AdmissionDecision> {
+            if job.tenant == "blocked" {
+                return Ok(AdmissionDecision { allowed: false, reason: "tenant suspended".to_string(), job: None });
+            }
+            let mut mutated = job.clone();
+            mutated.priority = PriorityClass::Normal;
+            Ok(AdmissionDecision { allowed: true, reason: String::new(), job: Some(mutated) })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_admission_denies_and_mutates() {
+        let mut scheduler = EconomicScheduler::new();
+        scheduler.set_admission(Some(AdmissionController::new(Arc::new(TenantPolicy), false)));
+
+        let denied = scheduler.admit(JobSpec { id: "job-1".to_string(), tenant: "blocked".to_string(), ..Default::default() }).await;
+        assert!(denied.unwrap_err().to_string().contains("tenant suspended"));
+
+        let admitted = scheduler.admit(JobSpec {
+            id: "job-2".to_string(),
+            tenant: "lab".to_string(),
+            priority: PriorityClass::High,
+            ..Default::default()
+        }).await.unwrap();
+        assert_eq!(admitted.priority, PriorityClass::Normal);
+    }
+
+    /// Replaces every image with python:3.11 and every input with "input"
+    struct Rewrite;
+
+    #[async_trait]
+    impl AdmissionHook for Rewrite {
+        async fn review(&self, job: &JobSpec) -> Result<AdmissionDecision> {
+            let mut mutated = job.clone();
+            mutated.container_image = "python:3.11".to_string();
+            mutated.job_data = b"input".to_vec();
+            Ok(AdmissionDecision { allowed: true, reason: String::new(), job: Some(mutated) })
+        }
+    }
+
+    struct FixedDigest;
+
+    #[async_trait]
+    impl ImageResolver for FixedDigest {
+        async fn resolve(&self, _image: &ImageRef) -> Result<String> {
+            Ok(format!("sha256:{}", "c".repeat(64)))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_image_set_by_a_hook_is_pinned() {
+        let mut scheduler = EconomicScheduler::new();
+        scheduler.set_admission(Some(AdmissionController::new(Arc::new(Rewrite), false)));
+        scheduler.set_image_policy(Some(ImagePolicy::new(Arc::new(FixedDigest), HashMap::new(), false)));
+
+        let admitted = scheduler.admit(JobSpec {
+            id: "job-1".to_string(),
+            container_image: "alpine:3.19".to_string(),
+            ..Default::default()
+        }).await.unwrap();
+        assert_eq!(admitted.container_image, format!("docker.io/library/python@sha256:{}", "c".repeat(64)));
+    }
+
+    #[tokio::test]
+    async fn test_input_set_by_a_hook_is_stored() {
+        let dir = std::env::temp_dir().join(format!("tgp-admission-test-{}", std::process::id()));
+        let mut scheduler = EconomicScheduler::new();
+        scheduler.set_admission(Some(AdmissionController::new(Arc::new(Rewrite), false)));
+        scheduler.set_payload_store(Some(PayloadStore::open(&dir, PayloadLimits::default()).unwrap()));
+
+        let admitted = scheduler.admit(JobSpec { id: "job-1".to_string(), ..Default::default() }).await.unwrap();
+        assert!(admitted.job_data.is_empty());
+        let digest = admitted.payload_digest.unwrap();
+        let stored = scheduler.payload_store().unwrap().get(&digest).await.unwrap().unwrap();
+        assert_eq!(stored, b"input".to_vec());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,135 @@
+//! Resources committed to placed jobs
+//!
+//! `NodeInfo::available_*` is what a node offers jobs in total (capacity
+//! minus its system reserve). Placement checks a job against that minus the
+//! requests of the jobs already placed on the node, and never against more
+//! than the worker last reported free. Best-effort jobs in harvested
+//! capacity overcommit by design: they are left out of the tally and
+//! their measured use counts as free, since they yield when reclaimed.
+
+use anyhow::Result;
+use std::collections::HashMap;
+
+use crate::{EconomicScheduler, NodeInfo, ResourceRequirements};
+
+/// Requests of the jobs placed on one node
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct NodeLoad {
+    pub cpu_cores: u32,
+    pub memory_gb: u32,
+    pub gpu_count: u32,
+    pub disk_gb: u32,
+    /// CPU and memory of harvested best-effort jobs (not in the totals above)
+    pub harvested_cpu: u32,
+    pub harvested_memory_gb: u32,
+}
+
+impl NodeLoad {
+    /// Whether `required` fits on `node` alongside this load; `reported` is
+    /// the (cpu, memory GB) the node's worker last reported free
+    pub fn fits(&self, node: &NodeInfo, required: &ResourceRequirements, reported: Option<(u32, u32)>) -> bool {
+        let mut cpu = node.available_cpu.saturating_sub(self.cpu_cores);
+        let mut memory_gb = node.available_memory_gb.saturating_sub(self.memory_gb);
+        if let Some((free_cpu, free_memory_gb)) = reported {
+            cpu = cpu.min(free_cpu.saturating_add(self.harvested_cpu));
+            memory_gb = memory_gb.min(free_memory_gb.saturating_add(self.harvested_memory_gb));
+        }
+
+        cpu >= required.cpu_cores
+            && memory_gb >= required.memory_gb
+            && node.available_gpu.saturating_sub(self.gpu_count) >= required.gpu_count
+            && node.available_disk_gb.map_or(true, |free| free.saturating_sub(self.disk_gb) >= required.disk_gb)
+    }
+}
+
+impl EconomicScheduler {
+    /// Load of every node with unfinished jobs placed on it
+    pub(crate) fn node_loads(&self) -> Result<HashMap<String, NodeLoad>> {
+        let placed: Vec<_> = self.placed_jobs.lock()
+            .map_err(|e| anyhow::anyhow!("Lock poisoned: {}", e))?
+            .values()
+            .cloned()
+            .collect();
+
+        let mut loads: HashMap<String, NodeLoad> = HashMap::new();
+        for spec in placed {
+            let Some(state) = self.get_job_state(&spec.id).filter(|state| !state.status.is_terminal()) else {
+                continue;
+            };
+            let harvested = self.harvest.is_harvested(&spec.id);
+            let nodes = if state.gang_nodes.is_empty() {
+                state.assigned_node.into_iter().collect()
+            } else {
+                state.gang_nodes
+            };
+            for node_id in nodes {
+                let load = loads.entry(node_id).or_default();
+                let r = &spec.resources;
+                if harvested {
+                    load.harvested_cpu += r.cpu_cores;
+                    load.harvested_memory_gb += r.memory_gb;
+                } else {
+                    load.cpu_cores += r.cpu_cores;
+                    load.memory_gb += r.memory_gb;
+                    load.gpu_count += r.gpu_count;
+                    load.disk_gb += r.disk_gb;
+                }
+            }
+        }
+        Ok(loads)
+    }
+
+    /// Free (cpu, memory GB) of each node per its latest resource report
+    pub(crate) fn reported_free(&self) -> HashMap<String, (u32, u32)> {
+        self.harvest.measured()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::node;
+    use crate::{JobSpec, JobStatus, SlaConstraints};
+
+    fn job(id: &str, cpu_cores: u32) -> JobSpec {
+        JobSpec {
+            id: id.to_string(),
+            resources: ResourceRequirements { cpu_cores, memory_gb: 1, ..Default::default() },
+            sla: SlaConstraints { max_latency_ms: 1_000, ..Default::default() },
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_placed_jobs_hold_their_resources() {
+        let scheduler = EconomicScheduler::new();
+        scheduler.register_node(node("n1", 0.1)).unwrap();
+
+        scheduler.schedule(job("a", 6)).await.unwrap();
+        assert!(scheduler.schedule(job("b", 6)).await.is_err());
+        scheduler.schedule(job("c", 2)).await.unwrap();
+        assert!(scheduler.check_invariants().await.is_empty());
+
+        // Reported free memory caps placement below what the tally leaves
+        scheduler.update_job_state("a".to_string(), JobStatus::Completed, None).unwrap();
+        scheduler.record_node_usage("n1", 6, 0).await.unwrap();
+        assert!(scheduler.schedule(job("d", 1)).await.is_err());
+        scheduler.record_node_usage("n1", 6, 20).await.unwrap();
+        scheduler.schedule(job("e", 6)).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_placed_jobs_hold_their_disk() {
+        let scheduler = EconomicScheduler::new();
+        scheduler.register_node(NodeInfo { available_disk_gb: Some(100), ..node("n1", 0.1) }).unwrap();
+        let job = |id: &str, disk_gb: u32| JobSpec {
+            resources: ResourceRequirements { cpu_cores: 1, memory_gb: 1, disk_gb, ..Default::default() },
+            ..job(id, 1)
+        };
+
+        scheduler.schedule(job("a", 60)).await.unwrap();
+        assert!(scheduler.schedule(job("b", 60)).await.is_err());
+        scheduler.schedule(job("c", 40)).await.unwrap();
+        assert!(scheduler.schedule(job("d", 1)).await.is_err());
+    }
+}
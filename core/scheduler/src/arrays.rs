@@ -0,0 +1,317 @@
+//! Job arrays (parameter sweeps)
+//!
+//! One submission expands into `size` indexed tasks `<array>-<index>`. Each
+//! task gets `TGP_ARRAY_ID`, `TGP_ARRAY_INDEX` and `TGP_ARRAY_SIZE` in its
+//! environment, and `{{index}}` in its command or environment values is
+//! replaced by the task index. Tasks are ordinary jobs: they are queued and
+//! placed individually, with at most `max_parallel` running at once in
+//! queued mode (see `limits`).
+//!
+//! Failures are tolerated up to `max_failures`; past that the array is
+//! aborted and tasks that have not started are cancelled. Arrays larger
+//! than the configured maximum are refused outright.
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use crate::error::SchedulerError;
+use crate::{EconomicScheduler, JobSpec, JobStatus};
+
+/// Placeholder replaced by the task index in commands and env values
+pub const INDEX_PLACEHOLDER: &str = "{{index}}";
+
+/// Most tasks one array may expand into unless configured otherwise
+pub const DEFAULT_MAX_ARRAY_SIZE: u32 = 10_000;
+
+/// Overall state of an array
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ArrayState {
+    /// Some tasks have not finished
+    Running,
+    /// Every task completed
+    Completed,
+    /// All tasks finished, some failed within the failure budget
+    PartiallyFailed,
+    /// Failure budget exceeded; remaining tasks were cancelled
+    Failed,
+}
+
+/// Task counts of an array by status
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ArrayStatus {
+    pub array_id: String,
+    pub total: u32,
+    pub pending: u32,
+    pub running: u32,
+    pub completed: u32,
+    pub failed: u32,
+    pub cancelled: u32,
+    pub state: ArrayState,
+}
+
+#[derive(Debug, Clone)]
+struct JobArray {
+    task_ids: Vec<String>,
+    max_failures: Option<u32>,
+    aborted: bool,
+}
+
+/// Submitted arrays and the array of each task
+#[derive(Debug, Clone)]
+pub struct JobArrays {
+    arrays: Arc<Mutex<HashMap<String, JobArray>>>,
+    tasks: Arc<Mutex<HashMap<String, String>>>,
+    max_size: u32,
+}
+
+impl Default for JobArrays {
+    fn default() -> Self {
+        Self {
+            arrays: Arc::default(),
+            tasks: Arc::default(),
+            max_size: DEFAULT_MAX_ARRAY_SIZE,
+        }
+    }
+}
+
+impl JobArrays {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn array_of(&self, job_id: &str) -> Option<String> {
+        self.tasks.lock().ok()?.get(job_id).cloned()
+    }
+
+    fn is_aborted(&self, array_id: &str) -> bool {
+        self.arrays.lock().is_ok_and(|arrays| arrays.get(array_id).is_some_and(|a| a.aborted))
+    }
+}
+
+/// Expand `template` into the tasks of array `array_id`
+pub fn expand(template: &JobSpec, array_id: &str, size: u32, max_parallel: u32) -> Vec<JobSpec> {
+    (0..size)
+        .map(|index| {
+            let index_str = index.to_string();
+            let mut task = template.clone();
+            task.id = format!("{}-{}", array_id, index);
+            task.array_id = Some(array_id.to_string());
+            task.max_parallel = max_parallel;
+            task.command = template.command.iter()
+                .map(|arg| arg.replace(INDEX_PLACEHOLDER, &index_str))
+                .collect();
+            for value in task.env.values_mut() {
+                *value = value.replace(INDEX_PLACEHOLDER, &index_str);
+            }
+            task.env.insert("TGP_ARRAY_ID".to_string(), array_id.to_string());
+            task.env.insert("TGP_ARRAY_INDEX".to_string(), index_str);
+            task.env.insert("TGP_ARRAY_SIZE".to_string(), size.to_string());
+            task
+        })
+        .collect()
+}
+
+impl EconomicScheduler {
+    /// Largest array `submit_array` accepts
+    pub fn set_max_array_size(&mut self, max_size: u32) {
+        self.arrays.max_size = max_size;
+    }
+
+    /// Submit `template` as an array of `size` tasks; returns the task ids
+    ///
+    /// The template id names the array. `max_failures` of None tolerates
+    /// any number of failed tasks.
+    pub async fn submit_array(
+        &self,
+        template: JobSpec,
+        size: u32,
+        max_parallel: u32,
+        max_failures: Option<u32>,
+    ) -> Result<Vec<String>> {
+        if size == 0 {
+            return Err(SchedulerError::invalid_spec(format!("Job array {} must have at least one task", template.id)).into());
+        }
+        if size > self.arrays.max_size {
+            return Err(SchedulerError::invalid_spec(format!(
+                "Job array {} has {} tasks, more than the maximum of {}", template.id, size, self.arrays.max_size
+            )).into());
+        }
+        let array_id = template.id.clone();
+        let tasks = expand(&template, &array_id, size, max_parallel);
+        let task_ids: Vec<String> = tasks.iter().map(|t| t.id.clone()).collect();
+
+        {
+            let mut arrays = self.arrays.arrays.lock()
+                .map_err(|e| anyhow::anyhow!("Lock poisoned: {}", e))?;
+            if arrays.contains_key(&array_id) {
+                return Err(SchedulerError::already_exists("Job array", &array_id).into());
+            }
+            arrays.insert(array_id.clone(), JobArray {
+                task_ids: task_ids.clone(),
+                max_failures,
+                aborted: false,
+            });
+        }
+        {
+            let mut by_task = self.arrays.tasks.lock()
+                .map_err(|e| anyhow::anyhow!("Lock poisoned: {}", e))?;
+            for id in &task_ids {
+                by_task.insert(id.clone(), array_id.clone());
+            }
+        }
+        tracing::info!("Job array {} submitted with {} tasks (max_parallel {})", array_id, size, max_parallel);
+
+        for task in tasks {
+            // Direct placement failures can abort the array part way through
+            if self.arrays.is_aborted(&array_id) {
+                break;
+            }
+            let task_id = task.id.clone();
+            if let Err(e) = self.requeue(task).await {
+                tracing::warn!("Array task {} not placed: {}", task_id, e);
+            }
+        }
+        Ok(task_ids)
+    }
+
+    /// Task counts and overall state of an array
+    pub fn array_status(&self, array_id: &str) -> Option<ArrayStatus> {
+        let array = self.arrays.arrays.lock().ok()?.get(array_id).cloned()?;
+
+        let mut status = ArrayStatus {
+            array_id: array_id.to_string(),
+            total: array.task_ids.len() as u32,
+            pending: 0,
+            running: 0,
+            completed: 0,
+            failed: 0,
+            cancelled: 0,
+            state: ArrayState::Running,
+        };
+        for id in &array.task_ids {
+            match self.get_job_state(id).map(|s| s.status) {
+                None | Some(JobStatus::Pending) => status.pending += 1,
+                Some(JobStatus::Scheduled) | Some(JobStatus::Running) => status.running += 1,
+                Some(JobStatus::Completed) => status.completed += 1,
+                Some(JobStatus::Failed) => status.failed += 1,
+                Some(JobStatus::Cancelled) => status.cancelled += 1,
+            }
+        }
+
+        status.state = if array.aborted {
+            ArrayState::Failed
+        } else if status.pending + status.running > 0 {
+            ArrayState::Running
+        } else if status.failed > 0 {
+            ArrayState::PartiallyFailed
+        } else {
+            ArrayState::Completed
+        };
+        Some(status)
+    }
+
+    /// Abort the array of a failed task once its failure budget is spent
+    pub(crate) fn check_array_failures(&self, job_id: &str) -> Result<()> {
+        let Some(array_id) = self.arrays.array_of(job_id) else {
+            return Ok(());
+        };
+        let Some(status) = self.array_status(&array_id) else {
+            return Ok(());
+        };
+
+        let task_ids = {
+            let mut arrays = self.arrays.arrays.lock()
+                .map_err(|e| anyhow::anyhow!("Lock poisoned: {}", e))?;
+            let Some(array) = arrays.get_mut(&array_id) else {
+                return Ok(());
+            };
+            match array.max_failures {
+                Some(max) if !array.aborted && status.failed > max => array.aborted = true,
+                _ => return Ok(()),
+            }
+            array.task_ids.clone()
+        };
+
+        tracing::warn!("Job array {} aborted after {} failed tasks", array_id, status.failed);
+        for id in task_ids {
+            let not_started = self.get_job_state(&id)
+                .map(|s| matches!(s.status, JobStatus::Pending | JobStatus::Scheduled))
+                .unwrap_or(true);
+            if not_started {
+                self.cancel_job(&id)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{NodeInfo, SlaConstraints};
+
+    #[test]
+    fn test_expand_templates_index() {
+        let template = JobSpec {
+            id: "sweep".to_string(),
+            command: vec!["train".to_string(), "--seed={{index}}".to_string()],
+            env: HashMap::from([("OUT".to_string(), "/out/{{index}}".to_string())]),
+            ..Default::default()
+        };
+        let tasks = expand(&template, "sweep", 3, 2);
+        assert_eq!(tasks.len(), 3);
+        assert_eq!(tasks[2].id, "sweep-2");
+        assert_eq!(tasks[2].command[1], "--seed=2");
+        assert_eq!(tasks[2].env["OUT"], "/out/2");
+        assert_eq!(tasks[2].env["TGP_ARRAY_INDEX"], "2");
+        assert_eq!(tasks[2].array_id.as_deref(), Some("sweep"));
+        assert_eq!(tasks[2].max_parallel, 2);
+    }
+
+    #[tokio::test]
+    async fn test_array_aborts_past_failure_budget() {
+        let scheduler = EconomicScheduler::new();
+        scheduler.register_node(NodeInfo {
+            id: "node-1".to_string(),
+            available_cpu: 16,
+            available_memory_gb: 64,
+            location: "vps-1".to_string(),
+            cost_per_hour: 0.1,
+            ..Default::default()
+        }).unwrap();
+
+        let template = JobSpec {
+            id: "sweep".to_string(),
+            sla: SlaConstraints { max_latency_ms: 1_000, ..Default::default() },
+            ..Default::default()
+        };
+        let ids = scheduler.submit_array(template, 4, 0, Some(1)).await.unwrap();
+        assert_eq!(scheduler.array_status("sweep").unwrap().running, 4);
+
+        scheduler.update_job_state(ids[0].clone(), JobStatus::Running, None).unwrap();
+        scheduler.update_job_state(ids[1].clone(), JobStatus::Completed, None).unwrap();
+        scheduler.update_job_state(ids[2].clone(), JobStatus::Failed, None).unwrap();
+        assert_eq!(scheduler.array_status("sweep").unwrap().state, ArrayState::Running);
+
+        // Second failure exceeds the budget; the unstarted task is cancelled
+        scheduler.update_job_state(ids[0].clone(), JobStatus::Failed, None).unwrap();
+        let status = scheduler.array_status("sweep").unwrap();
+        assert_eq!(status.state, ArrayState::Failed);
+        assert_eq!((status.completed, status.failed, status.cancelled), (1, 2, 1));
+    }
+
+    #[tokio::test]
+    async fn test_oversized_arrays_are_refused() {
+        let mut scheduler = EconomicScheduler::new();
+        scheduler.set_max_array_size(3);
+        let template = JobSpec { id: "sweep".to_string(), ..Default::default() };
+
+        let err = scheduler.submit_array(template.clone(), 4, 0, None).await.unwrap_err();
+        assert_eq!(crate::error::classify(&err).map(SchedulerError::reason), Some("INVALID_SPEC"));
+        assert!(scheduler.array_status("sweep").is_none());
+        assert!(scheduler.get_job_state("sweep-0").is_none());
+    }
+}
@@ -0,0 +1,365 @@
+//! Gang reservations and backfill
+//!
+//! A gang job (`gang_size` > 1) needs that many nodes at once, each with
+//! room for the per-member resources after the requests of the jobs already
+//! placed there. When too few nodes have room, the nodes that free up
+//! earliest (by the placed jobs' expected end times) are reserved from that
+//! start time and the gang waits as Pending. Other jobs may still backfill
+//! onto reserved nodes if they are expected to finish before the gang
+//! starts. Waiting gangs are retried whenever a job finishes.
+
+use anyhow::Result;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use crate::bandwidth;
+use crate::error::SchedulerError;
+use crate::events::SchedulerEvent;
+use crate::node_index::NodeIndex;
+use crate::{unix_now, EconomicScheduler, JobSpec, JobStatus, Placement, ResourceRequirements};
+
+/// Nodes held for a gang job until it can start
+#[derive(Debug, Clone)]
+pub struct GangReservation {
+    pub job: JobSpec,
+    /// Per-member requirements (after peak-memory prediction)
+    pub required: ResourceRequirements,
+    /// Reference-node duration used for costing the members
+    pub duration_hours: f64,
+    pub nodes: Vec<String>,
+    /// Expected time the reserved nodes all have room
+    pub start_at: i64,
+}
+
+/// Waiting gangs and the expected end time of every placed job
+#[derive(Debug, Clone, Default)]
+pub struct Reservations {
+    gangs: Arc<Mutex<Vec<GangReservation>>>,
+    ends: Arc<Mutex<HashMap<String, i64>>>,
+}
+
+impl Reservations {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Gang jobs currently waiting for capacity
+    pub fn waiting(&self) -> Vec<GangReservation> {
+        self.gangs.lock().map(|gangs| gangs.clone()).unwrap_or_default()
+    }
+
+    /// Reserved nodes mapped to the earliest gang start on them
+    pub fn reserved_until(&self) -> HashMap<String, i64> {
+        let mut reserved: HashMap<String, i64> = HashMap::new();
+        for gang in self.waiting() {
+            for node_id in gang.nodes {
+                let start = reserved.entry(node_id).or_insert(gang.start_at);
+                *start = (*start).min(gang.start_at);
+            }
+        }
+        reserved
+    }
+
+    pub(crate) fn record_end(&self, job_id: &str, end_at: i64) {
+        if let Ok(mut ends) = self.ends.lock() {
+            ends.insert(job_id.to_string(), end_at);
+        }
+    }
+
+    pub(crate) fn forget(&self, job_id: &str) {
+        if let Ok(mut ends) = self.ends.lock() {
+            ends.remove(job_id);
+        }
+    }
+
+    pub(crate) fn end_of(&self, job_id: &str) -> Option<i64> {
+        self.ends.lock().ok()?.get(job_id).copied()
+    }
+
+    fn hold(&self, reservation: GangReservation) {
+        if let Ok(mut gangs) = self.gangs.lock() {
+            gangs.retain(|gang| gang.job.id != reservation.job.id);
+            gangs.push(reservation);
+        }
+    }
+
+    fn release(&self, job_id: &str) {
+        if let Ok(mut gangs) = self.gangs.lock() {
+            gangs.retain(|gang| gang.job.id != job_id);
+        }
+    }
+}
+
+/// Result of trying to place a gang
+enum GangOutcome {
+    Placed(Placement),
+    Reserved { nodes: Vec<String>, start_at: i64 },
+    /// Fewer than `gang_size` nodes could ever hold a member
+    Impossible,
+}
+
+/// Whether `placement` finishes before any gang reserving its node starts
+pub(crate) fn fits_window(placement: &Placement, reserved: &HashMap<String, i64>, now: i64) -> bool {
+    let Some(start_at) = reserved.get(&placement.node_id) else {
+        return true;
+    };
+    now + expected_secs(placement) <= *start_at
+}
+
+/// Latency plus run time of a placement in seconds
+pub(crate) fn expected_secs(placement: &Placement) -> i64 {
+    (placement.estimated_latency_ms / 1000) as i64 + (placement.estimated_duration_hours * 3600.0).ceil() as i64
+}
+
+impl EconomicScheduler {
+    /// Gang reservations and expected job end times
+    pub fn reservations(&self) -> &Reservations {
+        &self.reservations
+    }
+
+    /// Place a gang job or reserve nodes for it
+    pub(crate) fn schedule_gang(&self, job: JobSpec, required: ResourceRequirements, duration_hours: f64) -> Result<Placement> {
+        match self.place_gang(&job, &required, duration_hours, unix_now())? {
+            GangOutcome::Placed(placement) => self.commit_gang(&job, placement),
+            GangOutcome::Reserved { nodes, start_at } => {
+                tracing::info!(
+                    "Gang job {} waits for {} nodes, reserving {:?} from {}",
+                    job.id, job.gang_size, nodes, start_at
+                );
+                self.publish_event(SchedulerEvent::GangReserved {
+                    job_id: job.id.clone(),
+                    nodes: nodes.clone(),
+                    start_at,
+                    timestamp: unix_now(),
+                });
+                let job_id = job.id.clone();
+                self.reservations.hold(GangReservation { job, required, duration_hours, nodes, start_at });
+                anyhow::bail!("Gang job {} is waiting for capacity (reserved from {})", job_id, start_at)
+            }
+            GangOutcome::Impossible => {
+                self.update_job_state(job.id.clone(), JobStatus::Failed, None)?;
+                tracing::info!("Cluster has fewer than {} nodes that fit gang job {}", job.gang_size, job.id);
+                Err(SchedulerError::NoCapacity { job_id: job.id }.into())
+            }
+        }
+    }
+
+    /// Retry waiting gangs after capacity was released
+    pub(crate) fn start_waiting_gangs(&self) {
+        let now = unix_now();
+        for gang in self.reservations.waiting() {
+            match self.place_gang(&gang.job, &gang.required, gang.duration_hours, now) {
+                Ok(GangOutcome::Placed(placement)) => {
+                    self.reservations.release(&gang.job.id);
+                    if let Err(e) = self.commit_gang(&gang.job, placement) {
+                        tracing::warn!("Failed to start gang job {}: {}", gang.job.id, e);
+                    }
+                }
+                Ok(GangOutcome::Reserved { nodes, start_at }) => {
+                    self.reservations.hold(GangReservation { nodes, start_at, ..gang });
+                }
+                Ok(GangOutcome::Impossible) | Err(_) => {}
+            }
+        }
+    }
+
+    /// Requests of placed jobs on each node, with their expected end times
+    pub(crate) fn node_commitments(&self) -> Result<HashMap<String, Vec<(ResourceRequirements, i64)>>> {
+        let placed = self.placed_jobs.lock()
+            .map_err(|e| anyhow::anyhow!("Lock poisoned: {}", e))?
+            .values()
+            .cloned()
+            .collect::<Vec<_>>();
+
+        let mut commitments: HashMap<String, Vec<(ResourceRequirements, i64)>> = HashMap::new();
+        for spec in placed {
+            let Some(state) = self.get_job_state(&spec.id) else {
+                continue;
+            };
+            let end_at = self.reservations.end_of(&spec.id).unwrap_or(i64::MAX);
+            let nodes = if state.gang_nodes.is_empty() {
+                state.assigned_node.into_iter().collect()
+            } else {
+                state.gang_nodes
+            };
+            for node_id in nodes {
+                commitments.entry(node_id).or_default().push((spec.resources.clone(), end_at));
+            }
+        }
+        Ok(commitments)
+    }
+
+    fn place_gang(&self, job: &JobSpec, required: &ResourceRequirements, duration_hours: f64, now: i64) -> Result<GangOutcome> {
+        let commitments = self.node_commitments()?;
+        let nodes = self.available_nodes.lock()
+            .map_err(|e| anyhow::anyhow!("Lock poisoned: {}", e))?;
+        let index: &NodeIndex = &nodes;
+
+        // When each node that could ever hold a member has room for one
+        let mut ready_at: Vec<(i64, String)> = index.candidates(required)
+            .into_iter()
+            .filter_map(|node| {
+                let mut jobs = commitments.get(&node.id).cloned().unwrap_or_default();
+                jobs.sort_by_key(|(_, end_at)| *end_at);
+
+                let mut used_cpu: u32 = jobs.iter().map(|(r, _)| r.cpu_cores).sum();
+                let mut used_memory: u32 = jobs.iter().map(|(r, _)| r.memory_gb).sum();
+                let mut used_gpu: u32 = jobs.iter().map(|(r, _)| r.gpu_count).sum();
+                let mut used_disk: u32 = jobs.iter().map(|(r, _)| r.disk_gb).sum();
+                let mut used_network: u32 = jobs.iter().map(|(r, _)| r.network_mbps).sum();
+                let fits = |cpu: u32, memory: u32, gpu: u32, disk: u32, network: u32| {
+                    node.available_cpu.saturating_sub(cpu) >= required.cpu_cores
+                        && node.available_memory_gb.saturating_sub(memory) >= required.memory_gb
+                        && node.available_gpu.saturating_sub(gpu) >= required.gpu_count
+                        && node.available_disk_gb.map_or(true, |free| free.saturating_sub(disk) >= required.disk_gb)
+                        && bandwidth::fits_network(node, required.network_mbps, network)
+                };
+
+                if fits(used_cpu, used_memory, used_gpu, used_disk, used_network) {
+                    return Some((now, node.id.clone()));
+                }
+                for (resources, end_at) in jobs {
+                    used_cpu -= resources.cpu_cores;
+                    used_memory -= resources.memory_gb;
+                    used_gpu -= resources.gpu_count;
+                    used_disk -= resources.disk_gb;
+                    used_network -= resources.network_mbps;
+                    if fits(used_cpu, used_memory, used_gpu, used_disk, used_network) {
+                        return Some((end_at.max(now), node.id.clone()));
+                    }
+                }
+                None
+            })
+            .collect();
+
+        let gang_size = job.gang_size as usize;
+        if ready_at.len() < gang_size {
+            return Ok(GangOutcome::Impossible);
+        }
+
+        let mut members: Vec<Placement> = ready_at.iter()
+            .filter(|(at, _)| *at <= now)
+            .filter_map(|(_, node_id)| index.get(node_id))
+            .filter_map(|node| self.evaluate_node(node, job, duration_hours, index))
+            .collect();
+
+        if members.len() < gang_size {
+            ready_at.sort();
+            let reserved: Vec<(i64, String)> = ready_at.into_iter().take(gang_size).collect();
+            let start_at = reserved.iter().map(|(at, _)| *at).max().unwrap_or(now);
+            return Ok(GangOutcome::Reserved {
+                nodes: reserved.into_iter().map(|(_, node_id)| node_id).collect(),
+                start_at,
+            });
+        }
+
+        // Cheapest members; the gang costs the sum and waits for the slowest
+        members.sort_by(Self::cheaper);
+        members.truncate(gang_size);
+        let mut placement = members[0].clone();
+        for member in &members[1..] {
+            placement.estimated_cost.compute_usd += member.estimated_cost.compute_usd;
+            placement.estimated_cost.data_transfer_usd += member.estimated_cost.data_transfer_usd;
+            placement.estimated_cost.idle_opportunity_usd += member.estimated_cost.idle_opportunity_usd;
+            placement.estimated_cost.total_usd += member.estimated_cost.total_usd;
+            placement.score_usd += member.score_usd;
+            placement.estimated_latency_ms = placement.estimated_latency_ms.max(member.estimated_latency_ms);
+            placement.estimated_staging_ms = placement.estimated_staging_ms.max(member.estimated_staging_ms);
+            placement.estimated_duration_hours = placement.estimated_duration_hours.max(member.estimated_duration_hours);
+        }
+        placement.gang_nodes = members.into_iter().map(|m| m.node_id).collect();
+        Ok(GangOutcome::Placed(placement))
+    }
+
+    fn commit_gang(&self, job: &JobSpec, placement: Placement) -> Result<Placement> {
+        if let Err(e) = self.reserve_credit(job, placement.estimated_cost.total_usd) {
+            self.update_job_state(job.id.clone(), JobStatus::Failed, None)?;
+            return Err(e.into());
+        }
+        self.allocate_devices(job, &placement.gang_nodes)?;
+        self.update_job_state(job.id.clone(), JobStatus::Scheduled, Some(placement.node_id.clone()))?;
+        {
+            let mut states = self.job_states.lock()
+                .map_err(|e| anyhow::anyhow!("Lock poisoned: {}", e))?;
+            if let Some(state) = states.get_mut(&job.id) {
+                state.estimated_cost = Some(placement.estimated_cost.clone());
+                state.gang_nodes = placement.gang_nodes.clone();
+            }
+        }
+        self.notify_job_update(&job.id);
+
+        if let Ok(mut placed) = self.placed_jobs.lock() {
+            placed.insert(job.id.clone(), job.clone());
+        }
+        self.reservations.record_end(&job.id, unix_now() + expected_secs(&placement));
+
+        tracing::info!(
+            "Gang job {} scheduled on {:?} with TCO ${:.4}",
+            job.id, placement.gang_nodes, placement.estimated_cost.total_usd
+        );
+        self.publish_event(SchedulerEvent::JobScheduled {
+            job_id: job.id.clone(),
+            node_id: placement.node_id.clone(),
+            estimated_cost_usd: placement.estimated_cost.total_usd,
+            timestamp: unix_now(),
+        });
+        Ok(placement)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{NodeInfo, SlaConstraints};
+
+    fn job(id: &str, cpu_cores: u32, hours: f64, gang_size: u32) -> JobSpec {
+        JobSpec {
+            id: id.to_string(),
+            resources: ResourceRequirements { cpu_cores, memory_gb: 1, disk_gb: 1, ..Default::default() },
+            sla: SlaConstraints { max_latency_ms: 1_000, ..Default::default() },
+            job_data: id.as_bytes().to_vec(),
+            estimated_duration_hours: Some(hours),
+            gang_size,
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_gang_reserves_nodes_and_short_jobs_backfill() {
+        let scheduler = EconomicScheduler::new();
+        for id in ["n1", "n2"] {
+            scheduler.register_node(NodeInfo {
+                id: id.to_string(),
+                available_cpu: 8,
+                available_memory_gb: 16,
+                location: "vps-1".to_string(),
+                cost_per_hour: 0.1,
+                ..Default::default()
+            }).unwrap();
+        }
+
+        scheduler.schedule(job("long-a", 6, 4.0, 0)).await.unwrap();
+        scheduler.schedule(job("long-b", 6, 2.0, 0)).await.unwrap();
+        scheduler.update_job_state("long-b".to_string(), JobStatus::Running, Some("n2".to_string())).unwrap();
+
+        // Both nodes are busy: the gang reserves them until the 4h job ends
+        assert!(scheduler.schedule(job("gang", 4, 1.0, 2)).await.is_err());
+        assert_eq!(scheduler.get_job_state("gang").unwrap().status, JobStatus::Pending);
+        let waiting = scheduler.reservations().waiting();
+        assert_eq!(waiting.len(), 1);
+        assert!(waiting[0].start_at >= unix_now() + 4 * 3600 - 60);
+
+        // A 1h job backfills into the window; a 6h job would delay the gang
+        assert!(scheduler.schedule(job("short", 1, 1.0, 0)).await.is_ok());
+        scheduler.update_job_state("short".to_string(), JobStatus::Completed, None).unwrap();
+        assert!(scheduler.schedule(job("too-long", 1, 6.0, 0)).await.is_err());
+
+        // Once the long jobs finish, the gang starts on both nodes
+        scheduler.update_job_state("long-a".to_string(), JobStatus::Completed, None).unwrap();
+        scheduler.update_job_state("long-b".to_string(), JobStatus::Completed, None).unwrap();
+        let state = scheduler.get_job_state("gang").unwrap();
+        assert_eq!(state.status, JobStatus::Scheduled);
+        assert_eq!(state.gang_nodes.len(), 2);
+        assert!(scheduler.reservations().waiting().is_empty());
+    }
+}
@@ -0,0 +1,137 @@
+//! Link bandwidth model for data staging estimates
+//!
+//! Bandwidth is modelled per pair of locations. Configured links take
+//! precedence; nodes in the same location get the intra-location rate and
+//! everything else falls back to the default WAN rate. Observed node-to-node
+//! throughput from the transfer ledger overrides the model when available.
+//!
+//! A node's own uplink, measured by its worker, is also a schedulable
+//! resource: jobs declaring `network_mbps` only go to nodes with that much
+//! bandwidth left after the jobs already placed there.
+
+use anyhow::Result;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use crate::{EconomicScheduler, NodeInfo};
+
+/// Assumed bandwidth between two nodes in the same location (Mbit/s)
+pub const DEFAULT_INTRA_LOCATION_MBPS: f64 = 1000.0;
+
+/// Assumed bandwidth between locations without a configured link (Mbit/s)
+pub const DEFAULT_INTER_LOCATION_MBPS: f64 = 100.0;
+
+/// Thread-safe per-link bandwidth model
+#[derive(Debug, Clone)]
+pub struct BandwidthModel {
+    links: Arc<Mutex<HashMap<(String, String), f64>>>,
+    intra_location_mbps: f64,
+    inter_location_mbps: f64,
+}
+
+impl BandwidthModel {
+    pub fn new() -> Self {
+        Self {
+            links: Arc::new(Mutex::new(HashMap::new())),
+            intra_location_mbps: DEFAULT_INTRA_LOCATION_MBPS,
+            inter_location_mbps: DEFAULT_INTER_LOCATION_MBPS,
+        }
+    }
+
+    /// Configure the bandwidth of a link (applies to both directions)
+    pub fn set_link(&self, from: &str, to: &str, mbps: f64) {
+        if let Ok(mut links) = self.links.lock() {
+            links.insert(Self::key(from, to), mbps);
+        }
+    }
+
+    /// Bandwidth between two locations in Mbit/s
+    pub fn link_mbps(&self, from: &str, to: &str) -> f64 {
+        let configured = self.links.lock()
+            .ok()
+            .and_then(|links| links.get(&Self::key(from, to)).copied());
+
+        match configured {
+            Some(mbps) => mbps,
+            None if from == to => self.intra_location_mbps,
+            None => self.inter_location_mbps,
+        }
+    }
+
+    /// Bandwidth assumed for data fetched from outside the cluster
+    pub fn external_mbps(&self) -> f64 {
+        self.inter_location_mbps
+    }
+
+    /// Time to move `size_gb` over a link of `mbps`, in milliseconds
+    pub fn transfer_time_ms(size_gb: f64, mbps: f64) -> u64 {
+        if size_gb <= 0.0 {
+            return 0;
+        }
+        if mbps <= 0.0 {
+            return u64::MAX;
+        }
+        (size_gb * 8.0 * 1000.0 / mbps * 1000.0).ceil() as u64
+    }
+
+    /// Links are symmetric: store them under a canonical key
+    fn key(a: &str, b: &str) -> (String, String) {
+        if a <= b {
+            (a.to_string(), b.to_string())
+        } else {
+            (b.to_string(), a.to_string())
+        }
+    }
+}
+
+impl Default for BandwidthModel {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Whether `node` has `required` Mbit/s left with `in_use` already
+/// committed (nodes without a measured uplink always fit)
+pub(crate) fn fits_network(node: &NodeInfo, required: u32, in_use: u32) -> bool {
+    required == 0 || node.network_mbps.map_or(true, |mbps| mbps.saturating_sub(in_use) >= required)
+}
+
+impl EconomicScheduler {
+    /// Bandwidth committed to jobs placed on each node, in Mbit/s
+    pub(crate) fn network_in_use(&self) -> Result<HashMap<String, u32>> {
+        Ok(self.node_commitments()?
+            .into_iter()
+            .map(|(node_id, jobs)| (node_id, jobs.iter().map(|(r, _)| r.network_mbps).sum()))
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_link_lookup_and_transfer_time() {
+        let model = BandwidthModel::new();
+        model.set_link("eu-1", "us-1", 50.0);
+
+        assert_eq!(model.link_mbps("us-1", "eu-1"), 50.0);
+        assert_eq!(model.link_mbps("eu-1", "eu-1"), DEFAULT_INTRA_LOCATION_MBPS);
+        assert_eq!(model.link_mbps("eu-1", "ap-1"), DEFAULT_INTER_LOCATION_MBPS);
+
+        // 1GB over 1000 Mbit/s = 8 seconds
+        assert_eq!(BandwidthModel::transfer_time_ms(1.0, 1000.0), 8000);
+        assert_eq!(BandwidthModel::transfer_time_ms(0.0, 10.0), 0);
+    }
+
+    #[test]
+    fn test_network_fit_counts_committed_bandwidth() {
+        let node = NodeInfo { network_mbps: Some(100), ..Default::default() };
+        assert!(fits_network(&node, 60, 0));
+        assert!(!fits_network(&node, 60, 50));
+        assert!(fits_network(&node, 0, 100));
+
+        let unmeasured = NodeInfo::default();
+        assert!(fits_network(&unmeasured, 1000, 0));
+    }
+}
@@ -0,0 +1,395 @@
+//! Builders for jobs and nodes
+//!
+//! `JobSpec::builder()` and `NodeInfo::builder(id)` spare library users the
+//! full struct literals and check the result on `build()`, so a spec the
+//! scheduler could never place is refused with an `InvalidSpec` error up
+//! front instead of failing at submission. Fields without a setter keep
+//! their defaults; set them on the built value.
+
+use anyhow::Result;
+
+use crate::error::SchedulerError;
+use crate::lifecycle::JobHook;
+use crate::preferences::{self, Preference, SoftConstraint};
+use crate::priority::PriorityClass;
+use crate::{JobSpec, JobType, NodeInfo, MAX_JOB_INPUT_BYTES};
+
+impl JobSpec {
+    pub fn builder() -> JobSpecBuilder {
+        JobSpecBuilder { spec: JobSpec::default() }
+    }
+}
+
+impl NodeInfo {
+    pub fn builder(id: impl Into<String>) -> NodeInfoBuilder {
+        NodeInfoBuilder { node: NodeInfo { id: id.into(), ..Default::default() } }
+    }
+}
+
+/// Builds a `JobSpec`
+#[derive(Debug, Clone)]
+pub struct JobSpecBuilder {
+    spec: JobSpec,
+}
+
+impl JobSpecBuilder {
+    pub fn id(mut self, id: impl Into<String>) -> Self {
+        self.spec.id = id.into();
+        self
+    }
+
+    pub fn job_type(mut self, job_type: JobType) -> Self {
+        self.spec.job_type = job_type;
+        self
+    }
+
+    pub fn image(mut self, image: impl Into<String>) -> Self {
+        self.spec.container_image = image.into();
+        self
+    }
+
+    pub fn command<I, S>(mut self, command: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.spec.command = command.into_iter().map(Into::into).collect();
+        self
+    }
+
+    pub fn input(mut self, data: impl Into<Vec<u8>>) -> Self {
+        self.spec.job_data = data.into();
+        self
+    }
+
+    pub fn cpu(mut self, cores: u32) -> Self {
+        self.spec.resources.cpu_cores = cores;
+        self
+    }
+
+    pub fn memory_gb(mut self, gb: u32) -> Self {
+        self.spec.resources.memory_gb = gb;
+        self
+    }
+
+    pub fn gpus(mut self, count: u32) -> Self {
+        self.spec.resources.gpu_count = count;
+        self
+    }
+
+    pub fn disk_gb(mut self, gb: u32) -> Self {
+        self.spec.resources.disk_gb = gb;
+        self
+    }
+
+    pub fn network_mbps(mut self, mbps: u32) -> Self {
+        self.spec.resources.network_mbps = mbps;
+        self
+    }
+
+    pub fn max_latency_ms(mut self, ms: u64) -> Self {
+        self.spec.sla.max_latency_ms = ms;
+        self
+    }
+
+    /// Maximum budget in USD
+    pub fn budget(mut self, usd: f64) -> Self {
+        self.spec.sla.max_budget_usd = Some(usd);
+        self
+    }
+
+    /// Most the job may cost per hour, in USD
+    pub fn max_hourly_rate(mut self, usd: f64) -> Self {
+        self.spec.sla.max_hourly_rate_usd = Some(usd);
+        self
+    }
+
+    pub fn deadline(mut self, at: i64) -> Self {
+        self.spec.sla.deadline = Some(at);
+        self
+    }
+
+    pub fn earliest_start(mut self, at: i64) -> Self {
+        self.spec.sla.earliest_start = Some(at);
+        self
+    }
+
+    pub fn duration_hours(mut self, hours: f64) -> Self {
+        self.spec.estimated_duration_hours = Some(hours);
+        self
+    }
+
+    pub fn priority(mut self, priority: PriorityClass) -> Self {
+        self.spec.priority = priority;
+        self
+    }
+
+    pub fn tenant(mut self, tenant: impl Into<String>) -> Self {
+        self.spec.tenant = tenant.into();
+        self
+    }
+
+    pub fn queue(mut self, queue: impl Into<String>) -> Self {
+        self.spec.queue = Some(queue.into());
+        self
+    }
+
+    pub fn pool(mut self, pool: impl Into<String>) -> Self {
+        self.spec.pool = Some(pool.into());
+        self
+    }
+
+    pub fn dataset(mut self, dataset: impl Into<String>) -> Self {
+        self.spec.datasets.push(dataset.into());
+        self
+    }
+
+    pub fn env(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.spec.env.insert(name.into(), value.into());
+        self
+    }
+
+    pub fn port(mut self, port: u16) -> Self {
+        self.spec.ports.push(port);
+        self
+    }
+
+    pub fn arch(mut self, arch: impl Into<String>) -> Self {
+        self.spec.arch.push(arch.into());
+        self
+    }
+
+    pub fn gang_size(mut self, nodes: u32) -> Self {
+        self.spec.gang_size = nodes;
+        self
+    }
+
+    pub fn movable(mut self) -> Self {
+        self.spec.movable = true;
+        self
+    }
+
+    pub fn best_effort(mut self) -> Self {
+        self.spec.best_effort = true;
+        self
+    }
+
+    pub fn output_path(mut self, path: impl Into<String>) -> Self {
+        self.spec.output_path = Some(path.into());
+        self
+    }
+
+    pub fn pre_start_hook(mut self, hook: JobHook) -> Self {
+        self.spec.pre_start_hook = Some(hook);
+        self
+    }
+
+    pub fn post_complete_hook(mut self, hook: JobHook) -> Self {
+        self.spec.post_complete_hook = Some(hook);
+        self
+    }
+
+    pub fn callback_url(mut self, url: impl Into<String>) -> Self {
+        self.spec.callback_url = Some(url.into());
+        self
+    }
+
+    /// Prefer nodes meeting `preference`, weighed against price
+    pub fn prefer(mut self, preference: Preference, weight: f64) -> Self {
+        self.spec.sla.preferences.push(SoftConstraint::new(preference, weight));
+        self
+    }
+
+    pub fn annotation(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.spec.annotations.insert(key.into(), value.into());
+        self
+    }
+
+    pub fn build(self) -> Result<JobSpec> {
+        let spec = self.spec;
+        let invalid = |reason: String| -> Result<JobSpec> { Err(SchedulerError::invalid_spec(reason).into()) };
+
+        if spec.id.is_empty() {
+            return invalid("Job id must not be empty".to_string());
+        }
+        if spec.resources.cpu_cores == 0 && spec.resources.gpu_count == 0 {
+            return invalid(format!("Job {} needs at least one CPU core or GPU", spec.id));
+        }
+        if let Some(budget) = spec.sla.max_budget_usd.filter(|usd| !usd.is_finite() || *usd < 0.0) {
+            return invalid(format!("Job {} has an invalid budget {}", spec.id, budget));
+        }
+        if let Some(rate) = spec.sla.max_hourly_rate_usd.filter(|usd| !usd.is_finite() || *usd <= 0.0) {
+            return invalid(format!("Job {} has an invalid hourly rate cap {}", spec.id, rate));
+        }
+        if let Some(hours) = spec.estimated_duration_hours.filter(|hours| !hours.is_finite() || *hours <= 0.0) {
+            return invalid(format!("Job {} has an invalid duration of {}h", spec.id, hours));
+        }
+        if let (Some(start), Some(deadline)) = (spec.sla.earliest_start, spec.sla.deadline) {
+            if start >= deadline {
+                return invalid(format!("Job {} may not start before its deadline", spec.id));
+            }
+        }
+        if spec.job_data.len() > MAX_JOB_INPUT_BYTES {
+            return invalid(format!(
+                "Job {} input is {} bytes, the limit is {}", spec.id, spec.job_data.len(), MAX_JOB_INPUT_BYTES
+            ));
+        }
+        for (i, port) in spec.ports.iter().enumerate() {
+            if *port == 0 || spec.ports[..i].contains(port) {
+                return invalid(format!("Job {} cannot publish port {} (zero or repeated)", spec.id, port));
+            }
+        }
+        if let Err(reason) = preferences::validate(&spec) {
+            return invalid(format!("Job {}: {}", spec.id, reason));
+        }
+        Ok(spec)
+    }
+}
+
+/// Builds a `NodeInfo`
+#[derive(Debug, Clone)]
+pub struct NodeInfoBuilder {
+    node: NodeInfo,
+}
+
+impl NodeInfoBuilder {
+    /// Allocatable CPU cores
+    pub fn cpu(mut self, cores: u32) -> Self {
+        self.node.available_cpu = cores;
+        self
+    }
+
+    /// Allocatable memory
+    pub fn memory_gb(mut self, gb: u32) -> Self {
+        self.node.available_memory_gb = gb;
+        self
+    }
+
+    pub fn gpus(mut self, count: u32) -> Self {
+        self.node.available_gpu = count;
+        self
+    }
+
+    /// Full capacity, including the system reserve
+    pub fn capacity(mut self, cpu: u32, memory_gb: u32) -> Self {
+        self.node.capacity_cpu = cpu;
+        self.node.capacity_memory_gb = memory_gb;
+        self
+    }
+
+    pub fn disk_gb(mut self, gb: u32) -> Self {
+        self.node.available_disk_gb = Some(gb);
+        self
+    }
+
+    pub fn network_mbps(mut self, mbps: u32) -> Self {
+        self.node.network_mbps = Some(mbps);
+        self
+    }
+
+    pub fn location(mut self, location: impl Into<String>) -> Self {
+        self.node.location = location.into();
+        self
+    }
+
+    pub fn cost_per_hour(mut self, usd: f64) -> Self {
+        self.node.cost_per_hour = usd;
+        self
+    }
+
+    pub fn performance_score(mut self, score: f64) -> Self {
+        self.node.performance_score = score;
+        self
+    }
+
+    pub fn preemptible(mut self) -> Self {
+        self.node.preemptible = true;
+        self
+    }
+
+    pub fn provider(mut self, provider: impl Into<String>) -> Self {
+        self.node.provider = provider.into();
+        self
+    }
+
+    pub fn arch(mut self, arch: impl Into<String>) -> Self {
+        self.node.arch = arch.into();
+        self
+    }
+
+    pub fn label(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.node.labels.insert(name.into(), value.into());
+        self
+    }
+
+    pub fn data_service_addr(mut self, addr: impl Into<String>) -> Self {
+        self.node.data_service_addr = addr.into();
+        self
+    }
+
+    pub fn control_addr(mut self, addr: impl Into<String>) -> Self {
+        self.node.control_addr = addr.into();
+        self
+    }
+
+    pub fn build(self) -> Result<NodeInfo> {
+        let node = self.node;
+        let invalid = |reason: String| -> Result<NodeInfo> { Err(SchedulerError::invalid_spec(reason).into()) };
+
+        if node.id.is_empty() {
+            return invalid("Node id must not be empty".to_string());
+        }
+        if node.available_cpu == 0 && node.available_memory_gb == 0 && node.available_gpu == 0 {
+            return invalid(format!("Node {} offers no resources", node.id));
+        }
+        if !node.cost_per_hour.is_finite() || node.cost_per_hour < 0.0 {
+            return invalid(format!("Node {} has an invalid price {}", node.id, node.cost_per_hour));
+        }
+        if !node.performance_score.is_finite() || node.performance_score < 0.0 {
+            return invalid(format!("Node {} has an invalid performance score {}", node.id, node.performance_score));
+        }
+        if (node.capacity_cpu > 0 && node.capacity_cpu < node.available_cpu)
+            || (node.capacity_memory_gb > 0 && node.capacity_memory_gb < node.available_memory_gb)
+        {
+            return invalid(format!("Node {} has more allocatable than its capacity", node.id));
+        }
+        Ok(node)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::classify;
+
+    #[test]
+    fn test_builders_validate() {
+        let job = JobSpec::builder().id("job-1").cpu(2).memory_gb(4).budget(0.5).build().unwrap();
+        assert_eq!((job.resources.cpu_cores, job.resources.memory_gb), (2, 4));
+        assert_eq!(job.sla.max_budget_usd, Some(0.5));
+
+        let err = JobSpec::builder().id("job-2").memory_gb(4).build().unwrap_err();
+        assert!(matches!(classify(&err), Some(SchedulerError::InvalidSpec { .. })));
+        assert!(JobSpec::builder().cpu(1).build().is_err());
+        assert!(JobSpec::builder().id("job-3").cpu(1).budget(-1.0).build().is_err());
+        assert!(JobSpec::builder().id("job-3").cpu(1).max_hourly_rate(0.0).build().is_err());
+        assert!(JobSpec::builder().id("job-4").cpu(1).earliest_start(200).deadline(100).build().is_err());
+        assert!(JobSpec::builder().id("job-5").cpu(1).port(8080).port(8080).build().is_err());
+        assert!(JobSpec::builder().id("job-6").cpu(1).prefer(Preference::MaxLatencyMs(100), -1.0).build().is_err());
+
+        let node = NodeInfo::builder("node-1")
+            .cpu(14)
+            .memory_gb(60)
+            .capacity(16, 64)
+            .location("us-east")
+            .cost_per_hour(0.8)
+            .label("pool", "gpu")
+            .build()
+            .unwrap();
+        assert_eq!(node.available_cpu, 14);
+        assert_eq!(node.labels["pool"], "gpu");
+        assert!(NodeInfo::builder("node-2").build().is_err());
+        assert!(NodeInfo::builder("node-3").cpu(8).capacity(4, 0).build().is_err());
+        assert!(NodeInfo::builder("node-4").cpu(8).cost_per_hour(f64::NAN).build().is_err());
+    }
+}
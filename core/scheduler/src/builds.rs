@@ -0,0 +1,315 @@
+//! Container image builds for user code
+//!
+//! Users without a registry of their own submit a build context (a
+//! `.tar.gz` with a Dockerfile) with `SubmitBuild`. The scheduler runs it
+//! as a build job on a builder node: the builder image (kaniko by default)
+//! unpacks the context from the job input, builds it, pushes the image to
+//! the internal registry and writes the pushed digest to a file that comes
+//! back as the job's output. Jobs then use `build:<build id>` as their
+//! image, which admission replaces with the digest-pinned reference.
+//!
+//! Contexts travel as job input, so they are capped at
+//! `MAX_JOB_INPUT_BYTES`; larger sources should be fetched by the
+//! Dockerfile. Buildpack builds are not supported.
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use crate::error::SchedulerError;
+use crate::{EconomicScheduler, JobSpec, JobStatus, JobType, ResourceRequirements};
+
+/// Appended to a build id to name its build job
+pub const BUILD_JOB_SUFFIX: &str = ".build";
+
+/// Image prefix naming a build instead of a registry image
+pub const BUILD_IMAGE_PREFIX: &str = "build:";
+
+/// Where the builder writes the pushed image digest
+const DIGEST_FILE: &str = "/kaniko/digest";
+
+/// Longest build id (it becomes a repository name)
+const MAX_BUILD_ID_LEN: usize = 128;
+
+/// Where builds run and where their images go
+#[derive(Debug, Clone)]
+pub struct BuildConfig {
+    /// Internal registry host images are pushed to (`registry.tgp:5000`)
+    pub registry: String,
+    /// Nodes allowed to build (empty: any)
+    pub builder_nodes: Vec<String>,
+    pub builder_image: String,
+    /// Push over plain HTTP
+    pub insecure_registry: bool,
+    pub resources: ResourceRequirements,
+}
+
+impl BuildConfig {
+    pub fn new(registry: impl Into<String>) -> Self {
+        Self {
+            registry: registry.into(),
+            builder_nodes: Vec::new(),
+            builder_image: "gcr.io/kaniko-project/executor:latest".to_string(),
+            insecure_registry: false,
+            resources: ResourceRequirements {
+                cpu_cores: 2,
+                memory_gb: 4,
+                disk_gb: 20,
+                ..Default::default()
+            },
+        }
+    }
+
+    /// Repository a build's image is pushed to
+    pub fn repository(&self, build_id: &str) -> String {
+        format!("{}/builds/{}", self.registry, build_id)
+    }
+}
+
+/// State of one build
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BuildStatus {
+    Building,
+    /// Pushed; `image` is pinned by digest
+    Succeeded { image: String },
+    Failed { reason: String },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BuildRecord {
+    pub build_id: String,
+    pub tenant: String,
+    /// Job running the build
+    pub job_id: String,
+    pub status: BuildStatus,
+}
+
+/// Build records, shared by scheduler clones
+#[derive(Debug, Clone, Default)]
+pub struct ImageBuilds {
+    config: Option<BuildConfig>,
+    records: Arc<Mutex<HashMap<String, BuildRecord>>>,
+}
+
+impl ImageBuilds {
+    pub fn new(config: Option<BuildConfig>) -> Self {
+        Self { config, ..Default::default() }
+    }
+
+    pub fn config(&self) -> Option<&BuildConfig> {
+        self.config.as_ref()
+    }
+
+    pub fn get(&self, build_id: &str) -> Option<BuildRecord> {
+        self.records.lock().ok().and_then(|records| records.get(build_id).cloned())
+    }
+
+    fn insert(&self, record: BuildRecord) -> Result<()> {
+        let mut records = self.records.lock()
+            .map_err(|e| anyhow::anyhow!("Lock poisoned: {}", e))?;
+        if records.get(&record.build_id).is_some_and(|existing| existing.status == BuildStatus::Building) {
+            return Err(SchedulerError::already_exists("Build", &record.build_id).into());
+        }
+        records.insert(record.build_id.clone(), record);
+        Ok(())
+    }
+
+    fn finish(&self, build_id: &str, status: BuildStatus) {
+        let Ok(mut records) = self.records.lock() else {
+            return;
+        };
+        if let Some(record) = records.get_mut(build_id) {
+            record.status = status;
+        }
+    }
+}
+
+/// Build ids become repository names: lowercase letters, digits, `-`, `_`, `.`
+fn validate_build_id(build_id: &str) -> Result<(), SchedulerError> {
+    let valid = !build_id.is_empty()
+        && build_id.len() <= MAX_BUILD_ID_LEN
+        && build_id.starts_with(|c: char| c.is_ascii_lowercase() || c.is_ascii_digit())
+        && build_id.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || "-_.".contains(c));
+    if valid {
+        Ok(())
+    } else {
+        Err(SchedulerError::invalid_spec(format!(
+            "Build id {:?} must be lowercase letters, digits, '-', '_' or '.'",
+            build_id
+        )))
+    }
+}
+
+/// Job that builds `context` and pushes the image
+pub fn build_job(config: &BuildConfig, build_id: &str, tenant: &str, context: Vec<u8>, dockerfile: &str) -> JobSpec {
+    let mut command = vec![
+        "--context=tar://{input}".to_string(),
+        format!("--dockerfile={}", dockerfile),
+        format!("--destination={}:latest", config.repository(build_id)),
+        format!("--digest-file={}", DIGEST_FILE),
+    ];
+    if config.insecure_registry {
+        command.push("--insecure".to_string());
+    }
+    JobSpec {
+        id: format!("{}{}", build_id, BUILD_JOB_SUFFIX),
+        job_type: JobType::DataProcessing,
+        resources: config.resources.clone(),
+        container_image: config.builder_image.clone(),
+        command,
+        job_data: context,
+        // A cached result would skip the push
+        disable_result_cache: true,
+        tenant: tenant.to_string(),
+        pin_nodes: config.builder_nodes.clone(),
+        output_path: Some(DIGEST_FILE.to_string()),
+        ..Default::default()
+    }
+}
+
+/// Pushed digest from the builder's output archive (a tar holding the
+/// digest file)
+pub fn parse_digest(archive: &[u8]) -> Option<String> {
+    // One ustar entry: a 512-byte header, the size in octal at 124..136
+    let header = archive.get(..512)?;
+    let size = std::str::from_utf8(&header[124..136]).ok()?
+        .trim_matches(|c: char| c == '\0' || c.is_whitespace());
+    let size = usize::from_str_radix(size, 8).ok()?;
+    let content = std::str::from_utf8(archive.get(512..512 + size)?).ok()?.trim();
+
+    let hex = content.strip_prefix("sha256:")?;
+    (hex.len() == 64 && hex.chars().all(|c| c.is_ascii_hexdigit())).then(|| content.to_string())
+}
+
+impl EconomicScheduler {
+    /// Enable builds, pushing to `config.registry`
+    pub fn set_build_config(&mut self, config: BuildConfig) {
+        self.builds = ImageBuilds::new(Some(config));
+    }
+
+    pub fn image_builds(&self) -> &ImageBuilds {
+        &self.builds
+    }
+
+    /// Start building `context`; returns the build job's id
+    pub async fn submit_build(&self, build_id: &str, tenant: &str, context: Vec<u8>, dockerfile: &str) -> Result<String> {
+        let config = self.builds.config().ok_or_else(|| SchedulerError::Unavailable {
+            reason: "image builds are not enabled (no internal registry)".to_string(),
+        })?;
+        validate_build_id(build_id)?;
+        if context.is_empty() || context.len() > crate::MAX_JOB_INPUT_BYTES {
+            return Err(SchedulerError::invalid_spec(format!(
+                "Build context is {} bytes, it must be between 1 and {}",
+                context.len(), crate::MAX_JOB_INPUT_BYTES
+            )).into());
+        }
+
+        let job = build_job(config, build_id, tenant, context, dockerfile);
+        let job_id = job.id.clone();
+        self.builds.insert(BuildRecord {
+            build_id: build_id.to_string(),
+            tenant: tenant.to_string(),
+            job_id: job_id.clone(),
+            status: BuildStatus::Building,
+        })?;
+
+        tracing::info!("Building image {} with job {}", build_id, job_id);
+        if let Err(e) = self.requeue(job).await {
+            self.builds.finish(build_id, BuildStatus::Failed { reason: format!("{:#}", e) });
+            return Err(e);
+        }
+        Ok(job_id)
+    }
+
+    /// Record the outcome of a finished build job; other jobs are ignored
+    pub async fn build_finished(&self, job_id: &str, status: &JobStatus) -> Result<()> {
+        let Some(build_id) = job_id.strip_suffix(BUILD_JOB_SUFFIX) else {
+            return Ok(());
+        };
+        let Some(config) = self.builds.config() else {
+            return Ok(());
+        };
+        if self.builds.get(build_id).map(|record| record.job_id).as_deref() != Some(job_id) {
+            return Ok(());
+        }
+
+        let outcome = match status {
+            JobStatus::Completed => match self.job_outputs().get(job_id).await?.as_deref().and_then(|archive| parse_digest(archive)) {
+                Some(digest) => BuildStatus::Succeeded {
+                    image: format!("{}@{}", config.repository(build_id), digest),
+                },
+                None => BuildStatus::Failed { reason: "builder reported no image digest".to_string() },
+            },
+            other => BuildStatus::Failed { reason: format!("build job {:?}", other) },
+        };
+        tracing::info!("Build {} finished: {:?}", build_id, outcome);
+        self.builds.finish(build_id, outcome);
+        Ok(())
+    }
+
+    /// Replace a `build:<id>` image with the build's pushed image
+    pub fn resolve_build_image(&self, mut job: JobSpec) -> Result<JobSpec> {
+        let Some(build_id) = job.container_image.strip_prefix(BUILD_IMAGE_PREFIX) else {
+            return Ok(job);
+        };
+        let record = self.builds.get(build_id)
+            .ok_or_else(|| SchedulerError::not_found("Build", build_id))?;
+        match record.status {
+            BuildStatus::Succeeded { image } => {
+                job.container_image = image;
+                Ok(job)
+            }
+            BuildStatus::Building => Err(SchedulerError::invalid_spec(format!("Build {} has not finished", build_id)).into()),
+            BuildStatus::Failed { reason } => {
+                Err(SchedulerError::invalid_spec(format!("Build {} failed: {}", build_id, reason)).into())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn digest_archive(content: &str) -> Vec<u8> {
+        let mut header = vec![0u8; 512];
+        header[..6].copy_from_slice(b"digest");
+        header[124..136].copy_from_slice(format!("{:011o}\0", content.len()).as_bytes());
+        let mut archive = header;
+        archive.extend_from_slice(content.as_bytes());
+        archive.resize(2048, 0);
+        archive
+    }
+
+    #[tokio::test]
+    async fn test_build_resolves_to_pinned_image() {
+        let mut scheduler = EconomicScheduler::new();
+        let mut config = BuildConfig::new("registry.tgp:5000");
+        config.builder_nodes = vec!["builder-1".to_string()];
+        scheduler.set_build_config(config.clone());
+
+        let job = build_job(&config, "my-app", "acme", vec![1; 16], "Dockerfile");
+        assert_eq!(job.pin_nodes, vec!["builder-1"]);
+        assert!(job.command.contains(&"--destination=registry.tgp:5000/builds/my-app:latest".to_string()));
+        assert!(scheduler.submit_build("My App", "acme", vec![1; 16], "Dockerfile").await.is_err());
+
+        scheduler.builds.insert(BuildRecord {
+            build_id: "my-app".to_string(),
+            tenant: "acme".to_string(),
+            job_id: "my-app.build".to_string(),
+            status: BuildStatus::Building,
+        }).unwrap();
+        let user_job = JobSpec { id: "job-1".to_string(), container_image: "build:my-app".to_string(), ..Default::default() };
+        assert!(scheduler.resolve_build_image(user_job.clone()).is_err());
+
+        let digest = format!("sha256:{}", "a".repeat(64));
+        scheduler.job_outputs().put("my-app.build", digest_archive(&digest)).await.unwrap();
+        scheduler.build_finished("my-app.build", &JobStatus::Completed).await.unwrap();
+        let resolved = scheduler.resolve_build_image(user_job).unwrap();
+        assert_eq!(resolved.container_image, format!("registry.tgp:5000/builds/my-app@{}", digest));
+
+        assert_eq!(parse_digest(&digest_archive("not a digest")), None);
+    }
+}
@@ -0,0 +1,435 @@
+//! Capacity planning reports
+//!
+//! A sampler records queue depth, demand and capacity at a fixed interval.
+//! The capacity report fits a linear trend to the samples in its window and
+//! projects, per resource dimension (CPU, memory, GPU), when demand reaches
+//! capacity. For every dimension that saturates within the planning horizon
+//! it recommends nodes from the pricing catalog, cheapest per unit of the
+//! scarce resource first, with the hourly and monthly cost they add.
+//!
+//! Demand is the requests of placed, unfinished jobs plus waiting gangs;
+//! jobs still in the shared queue only count towards queue depth. Without a
+//! configured catalog, the shapes and prices of registered nodes are used.
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::{unix_now, EconomicScheduler, JobStatus, NodeInfo};
+
+/// Samples kept (a week at one per minute)
+pub const MAX_SAMPLES: usize = 10_080;
+
+/// Hours in a billing month, for monthly cost impact
+const HOURS_PER_MONTH: f64 = 730.0;
+
+/// Node type that can be added to the cluster
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NodeOffering {
+    pub name: String,
+    pub cpu_cores: u32,
+    pub memory_gb: u32,
+    #[serde(default)]
+    pub gpu_count: u32,
+    pub cost_per_hour: f64,
+}
+
+/// Resource amounts per dimension
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct ResourceTotals {
+    pub cpu_cores: f64,
+    pub memory_gb: f64,
+    pub gpu_count: f64,
+}
+
+impl ResourceTotals {
+    fn get(&self, resource: Resource) -> f64 {
+        match resource {
+            Resource::Cpu => self.cpu_cores,
+            Resource::Memory => self.memory_gb,
+            Resource::Gpu => self.gpu_count,
+        }
+    }
+
+    fn add_node(&mut self, node: &NodeInfo) {
+        self.cpu_cores += node.available_cpu as f64;
+        self.memory_gb += node.available_memory_gb as f64;
+        self.gpu_count += node.available_gpu as f64;
+    }
+}
+
+/// Resource dimension
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Resource {
+    Cpu,
+    Memory,
+    Gpu,
+}
+
+impl Resource {
+    pub const ALL: [Resource; 3] = [Resource::Cpu, Resource::Memory, Resource::Gpu];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Resource::Cpu => "cpu",
+            Resource::Memory => "memory",
+            Resource::Gpu => "gpu",
+        }
+    }
+
+    fn of(self, offering: &NodeOffering) -> f64 {
+        match self {
+            Resource::Cpu => offering.cpu_cores as f64,
+            Resource::Memory => offering.memory_gb as f64,
+            Resource::Gpu => offering.gpu_count as f64,
+        }
+    }
+}
+
+/// Cluster demand and capacity at one point in time
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CapacitySample {
+    pub at: i64,
+    /// Jobs waiting for placement
+    pub queued_jobs: u32,
+    pub demand: ResourceTotals,
+    /// Active and dormant nodes
+    pub capacity: ResourceTotals,
+}
+
+/// Projection for one resource dimension
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DimensionForecast {
+    pub resource: Resource,
+    pub capacity: f64,
+    pub demand: f64,
+    pub utilization: f64,
+    /// Demand trend per hour (negative when shrinking)
+    pub growth_per_hour: f64,
+    /// Hours until demand reaches capacity at the current trend (0 if it
+    /// already has; None if it never will)
+    pub hours_to_saturation: Option<f64>,
+}
+
+/// Nodes to add and what they cost
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NodeRecommendation {
+    pub offering: String,
+    pub count: u32,
+    /// Dimension whose saturation prompted the recommendation
+    pub resource: Resource,
+    pub added_cost_per_hour: f64,
+    pub added_cost_per_month: f64,
+}
+
+/// Forward-looking capacity report
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CapacityReport {
+    pub generated_at: i64,
+    pub horizon_hours: f64,
+    /// Samples the trends were fitted to
+    pub samples: u32,
+    pub queued_jobs: u32,
+    pub queue_growth_per_hour: f64,
+    pub dimensions: Vec<DimensionForecast>,
+    pub recommendations: Vec<NodeRecommendation>,
+}
+
+/// How reports are computed
+#[derive(Debug, Clone, PartialEq)]
+pub struct CapacityPlanConfig {
+    /// Trends are fitted to samples from this many seconds back
+    pub window_secs: i64,
+    /// Recommendations keep projected utilization at or below this
+    pub target_utilization: f64,
+}
+
+impl Default for CapacityPlanConfig {
+    fn default() -> Self {
+        Self {
+            window_secs: 24 * 3600,
+            target_utilization: 0.8,
+        }
+    }
+}
+
+/// Sample history and the pricing catalog
+#[derive(Debug, Clone, Default)]
+pub struct CapacityPlanner {
+    config: CapacityPlanConfig,
+    samples: Arc<Mutex<VecDeque<CapacitySample>>>,
+    catalog: Arc<Mutex<Vec<NodeOffering>>>,
+}
+
+impl CapacityPlanner {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record(&self, sample: CapacitySample) {
+        if let Ok(mut samples) = self.samples.lock() {
+            if samples.len() >= MAX_SAMPLES {
+                samples.pop_front();
+            }
+            samples.push_back(sample);
+        }
+    }
+
+    pub fn set_catalog(&self, offerings: Vec<NodeOffering>) {
+        if let Ok(mut catalog) = self.catalog.lock() {
+            *catalog = offerings;
+        }
+    }
+
+    pub fn catalog(&self) -> Vec<NodeOffering> {
+        self.catalog.lock().map(|catalog| catalog.clone()).unwrap_or_default()
+    }
+}
+
+/// Least-squares slope of `value` per hour over `at` (seconds)
+fn slope_per_hour(points: &[(i64, f64)]) -> f64 {
+    if points.len() < 2 {
+        return 0.0;
+    }
+    let n = points.len() as f64;
+    let mean_t = points.iter().map(|(t, _)| *t as f64).sum::<f64>() / n;
+    let mean_v = points.iter().map(|(_, v)| *v).sum::<f64>() / n;
+    let (mut cov, mut var) = (0.0, 0.0);
+    for (t, v) in points {
+        let dt = *t as f64 - mean_t;
+        cov += dt * (v - mean_v);
+        var += dt * dt;
+    }
+    if var == 0.0 { 0.0 } else { cov / var * 3600.0 }
+}
+
+/// Offerings derived from registered nodes, one per distinct shape
+fn offerings_from_nodes(nodes: &[NodeInfo]) -> Vec<NodeOffering> {
+    let mut offerings: Vec<NodeOffering> = Vec::new();
+    for node in nodes {
+        let duplicate = offerings.iter().any(|o| {
+            (o.cpu_cores, o.memory_gb, o.gpu_count) == (node.available_cpu, node.available_memory_gb, node.available_gpu)
+                && o.cost_per_hour <= node.cost_per_hour
+        });
+        if !duplicate {
+            offerings.push(NodeOffering {
+                name: format!("like {}", node.id),
+                cpu_cores: node.available_cpu,
+                memory_gb: node.available_memory_gb,
+                gpu_count: node.available_gpu,
+                cost_per_hour: node.cost_per_hour,
+            });
+        }
+    }
+    offerings
+}
+
+impl EconomicScheduler {
+    pub fn set_capacity_plan_config(&mut self, config: CapacityPlanConfig) {
+        self.capacity.config = config;
+    }
+
+    pub fn capacity_planner(&self) -> &CapacityPlanner {
+        &self.capacity
+    }
+
+    /// Active and dormant nodes
+    fn all_nodes(&self) -> Vec<NodeInfo> {
+        let mut nodes = self.cluster_status();
+        nodes.extend(self.power().parked_nodes());
+        nodes
+    }
+
+    /// Jobs waiting for placement: the external queue's depth if one is
+    /// configured, otherwise pending jobs
+    pub(crate) async fn queued_jobs(&self) -> Result<u32> {
+        Ok(match self.job_queue() {
+            Some(queue) => queue.len().await? as u32,
+            None => self.job_states.lock()
+                .map_err(|e| anyhow::anyhow!("Lock poisoned: {}", e))?
+                .values()
+                .filter(|state| state.status == JobStatus::Pending)
+                .count() as u32,
+        })
+    }
+
+    /// Measure demand and capacity now and add the sample to the history
+    pub async fn record_capacity_sample(&self) -> Result<CapacitySample> {
+        let queued_jobs = self.queued_jobs().await?;
+
+        let mut demand = ResourceTotals::default();
+        let mut add = |cpu: u32, memory_gb: u32, gpu: u32, members: u32| {
+            let members = members.max(1) as f64;
+            demand.cpu_cores += cpu as f64 * members;
+            demand.memory_gb += memory_gb as f64 * members;
+            demand.gpu_count += gpu as f64 * members;
+        };
+        {
+            let placed = self.placed_jobs.lock()
+                .map_err(|e| anyhow::anyhow!("Lock poisoned: {}", e))?;
+            for spec in placed.values() {
+                let r = &spec.resources;
+                add(r.cpu_cores, r.memory_gb, r.gpu_count, spec.gang_size);
+            }
+        }
+        for gang in self.reservations.waiting() {
+            let r = &gang.required;
+            add(r.cpu_cores, r.memory_gb, r.gpu_count, gang.job.gang_size);
+        }
+
+        let mut capacity = ResourceTotals::default();
+        for node in self.all_nodes() {
+            capacity.add_node(&node);
+        }
+
+        let sample = CapacitySample { at: unix_now(), queued_jobs, demand, capacity };
+        self.capacity.record(sample.clone());
+        Ok(sample)
+    }
+
+    /// Project demand `horizon_hours` ahead and recommend node additions
+    pub fn capacity_report(&self, horizon_hours: f64, now: i64) -> CapacityReport {
+        let since = now - self.capacity.config.window_secs;
+        let samples: Vec<CapacitySample> = self.capacity.samples.lock()
+            .map(|samples| samples.iter().filter(|s| s.at >= since).cloned().collect())
+            .unwrap_or_default();
+        let latest = samples.last().cloned();
+
+        let queue_points: Vec<(i64, f64)> = samples.iter().map(|s| (s.at, s.queued_jobs as f64)).collect();
+        let mut report = CapacityReport {
+            generated_at: now,
+            horizon_hours,
+            samples: samples.len() as u32,
+            queued_jobs: latest.as_ref().map(|s| s.queued_jobs).unwrap_or(0),
+            queue_growth_per_hour: slope_per_hour(&queue_points),
+            dimensions: Vec::new(),
+            recommendations: Vec::new(),
+        };
+        let Some(latest) = latest else {
+            return report;
+        };
+
+        for resource in Resource::ALL {
+            let points: Vec<(i64, f64)> = samples.iter().map(|s| (s.at, s.demand.get(resource))).collect();
+            let growth_per_hour = slope_per_hour(&points);
+            let capacity = latest.capacity.get(resource);
+            let demand = latest.demand.get(resource);
+            // Dimensions the cluster does not offer and nobody asks for
+            if capacity == 0.0 && demand == 0.0 && growth_per_hour <= 0.0 {
+                continue;
+            }
+            let hours_to_saturation = if demand >= capacity {
+                Some(0.0)
+            } else if growth_per_hour > 0.0 {
+                Some((capacity - demand) / growth_per_hour)
+            } else {
+                None
+            };
+            report.dimensions.push(DimensionForecast {
+                resource,
+                capacity,
+                demand,
+                utilization: if capacity > 0.0 { demand / capacity } else { 1.0 },
+                growth_per_hour,
+                hours_to_saturation,
+            });
+        }
+
+        let mut catalog = self.capacity.catalog();
+        if catalog.is_empty() {
+            catalog = offerings_from_nodes(&self.all_nodes());
+        }
+        let target = self.capacity.config.target_utilization.clamp(0.05, 1.0);
+
+        // Capacity added by earlier recommendations counts for later dimensions
+        let mut added = ResourceTotals::default();
+        for forecast in &report.dimensions {
+            if !forecast.hours_to_saturation.is_some_and(|hours| hours <= horizon_hours) {
+                continue;
+            }
+            let resource = forecast.resource;
+            let projected = forecast.demand + forecast.growth_per_hour.max(0.0) * horizon_hours;
+            let shortfall = projected / target - forecast.capacity - added.get(resource);
+            if shortfall <= 0.0 {
+                continue;
+            }
+            let Some(offering) = catalog.iter()
+                .filter(|o| resource.of(o) > 0.0)
+                .min_by(|a, b| (a.cost_per_hour / resource.of(a)).total_cmp(&(b.cost_per_hour / resource.of(b))))
+            else {
+                tracing::warn!("No catalog offering provides {}", resource.as_str());
+                continue;
+            };
+
+            let count = (shortfall / resource.of(offering)).ceil() as u32;
+            added.cpu_cores += offering.cpu_cores as f64 * count as f64;
+            added.memory_gb += offering.memory_gb as f64 * count as f64;
+            added.gpu_count += offering.gpu_count as f64 * count as f64;
+            let added_cost_per_hour = offering.cost_per_hour * count as f64;
+            report.recommendations.push(NodeRecommendation {
+                offering: offering.name.clone(),
+                count,
+                resource,
+                added_cost_per_hour,
+                added_cost_per_month: added_cost_per_hour * HOURS_PER_MONTH,
+            });
+        }
+        report
+    }
+}
+
+/// Sample capacity and market rates every `interval` until the process
+/// exits
+pub async fn run_capacity_sampler(scheduler: EconomicScheduler, interval: Duration) {
+    let mut ticker = tokio::time::interval(interval);
+    loop {
+        ticker.tick().await;
+        if let Err(e) = scheduler.record_capacity_sample().await {
+            tracing::warn!("Capacity sampling failed: {}", e);
+        }
+        if let Err(e) = scheduler.record_market_rates() {
+            tracing::warn!("Market rate sampling failed: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_growing_cpu_demand_recommends_cheapest_cores() {
+        let scheduler = EconomicScheduler::new();
+        let planner = scheduler.capacity_planner();
+        planner.set_catalog(vec![
+            NodeOffering { name: "small".to_string(), cpu_cores: 4, memory_gb: 16, gpu_count: 0, cost_per_hour: 0.2 },
+            NodeOffering { name: "large".to_string(), cpu_cores: 16, memory_gb: 64, gpu_count: 0, cost_per_hour: 0.6 },
+        ]);
+
+        // CPU demand grows 2 cores/hour towards 64 cores; memory stays flat
+        for hour in 0..=10 {
+            planner.record(CapacitySample {
+                at: hour * 3600,
+                queued_jobs: hour as u32,
+                demand: ResourceTotals { cpu_cores: 30.0 + 2.0 * hour as f64, memory_gb: 100.0, gpu_count: 0.0 },
+                capacity: ResourceTotals { cpu_cores: 64.0, memory_gb: 256.0, gpu_count: 0.0 },
+            });
+        }
+
+        let report = scheduler.capacity_report(24.0, 10 * 3600);
+        assert!((report.queue_growth_per_hour - 1.0).abs() < 1e-9);
+        let cpu = &report.dimensions[0];
+        assert_eq!(cpu.resource, Resource::Cpu);
+        assert!((cpu.hours_to_saturation.unwrap() - 7.0).abs() < 1e-9);
+        assert_eq!(report.dimensions[1].hours_to_saturation, None);
+
+        // 98 cores projected at 80% target needs 122.5; 64 + 4 x 16 covers it
+        assert_eq!(report.recommendations.len(), 1);
+        let recommendation = &report.recommendations[0];
+        assert_eq!((recommendation.offering.as_str(), recommendation.count), ("large", 4));
+        assert!((recommendation.added_cost_per_hour - 2.4).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,160 @@
+//! Per-job-type cost ceilings
+//!
+//! Operators cap what a single job of a type may cost (e.g. inference jobs
+//! never more than $0.05), whatever budget the submitter gave, so a
+//! misconfigured submission cannot burn money. Admission stamps the ceiling
+//! onto the spec, after plugins and the admission hook so neither can lift
+//! it, and refuses the job if even its cheapest placement on the current
+//! cluster is over it. Placement never picks a node over the ceiling either,
+//! so a job admitted while the cluster was cheaper fails rather than run at
+//! any price. A gang's ceiling covers all of its members.
+
+use anyhow::Result;
+use std::collections::HashMap;
+
+use crate::commands::job_type_name;
+use crate::error::SchedulerError;
+use crate::node_index::NodeIndex;
+use crate::{EconomicScheduler, JobSpec, JobType};
+
+/// Cost caps by job type
+#[derive(Debug, Clone, Default)]
+pub struct CostCeilings {
+    per_type: HashMap<JobType, f64>,
+}
+
+impl CostCeilings {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_ceiling(mut self, job_type: JobType, max_usd: f64) -> Self {
+        self.per_type.insert(job_type, max_usd);
+        self
+    }
+
+    /// Parse `type=usd` pairs, e.g. `inference=0.05,training=20`
+    pub fn parse(mut self, spec: &str) -> Result<Self> {
+        for pair in spec.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+            let (name, max_usd) = pair.split_once('=')
+                .ok_or_else(|| anyhow::anyhow!("Expected type=usd, got {}", pair))?;
+            let job_type = [JobType::Training, JobType::Inference, JobType::DataProcessing]
+                .into_iter()
+                .find(|job_type| job_type_name(job_type) == name.trim())
+                .ok_or_else(|| anyhow::anyhow!("Unknown job type {}", name))?;
+            let max_usd: f64 = max_usd.trim().parse()
+                .map_err(|e| anyhow::anyhow!("Invalid ceiling for {}: {}", name, e))?;
+            if max_usd.is_nan() || max_usd < 0.0 {
+                anyhow::bail!("Ceiling for {} must not be negative", name);
+            }
+            self = self.with_ceiling(job_type, max_usd);
+        }
+        Ok(self)
+    }
+
+    pub fn ceiling(&self, job_type: &JobType) -> Option<f64> {
+        self.per_type.get(job_type).copied()
+    }
+}
+
+/// Ceiling on one node's share of a job
+pub(crate) fn member_ceiling(job: &JobSpec) -> Option<f64> {
+    job.cost_ceiling_usd.map(|ceiling| ceiling / job.gang_size.max(1) as f64)
+}
+
+impl EconomicScheduler {
+    pub fn set_cost_ceilings(&mut self, ceilings: CostCeilings) {
+        self.ceilings = ceilings;
+    }
+
+    pub fn cost_ceilings(&self) -> &CostCeilings {
+        &self.ceilings
+    }
+
+    /// Stamp the ceiling for the job's type onto a submission; refuse it if
+    /// no current node could run it under the ceiling
+    pub fn apply_cost_ceiling(&self, mut job: JobSpec) -> Result<JobSpec> {
+        // Only the operator's ceiling counts, never one in the submission
+        job.cost_ceiling_usd = self.ceilings.ceiling(&job.job_type);
+        let Some(ceiling_usd) = job.cost_ceiling_usd else {
+            return Ok(job);
+        };
+
+        if let Some(cheapest_usd) = self.cheapest_placement_usd(&job)? {
+            if cheapest_usd > ceiling_usd {
+                tracing::info!("Job {} refused: ${:.4} is over its ${:.4} ceiling", job.id, cheapest_usd, ceiling_usd);
+                return Err(SchedulerError::OverCostCeiling {
+                    job_id: job.id.clone(),
+                    job_type: job_type_name(&job.job_type).to_string(),
+                    ceiling_usd,
+                    cheapest_usd,
+                }.into());
+            }
+        }
+        Ok(job)
+    }
+
+    /// Cost of the cheapest placement on the current nodes ignoring budget
+    /// and ceiling (times the gang size); None if nothing fits right now
+    pub(crate) fn cheapest_placement_usd(&self, job: &JobSpec) -> Result<Option<f64>> {
+        let mut job = job.clone();
+        job.sla.max_budget_usd = None;
+        job.cost_ceiling_usd = None;
+
+        let prediction = self.predict_job(&job);
+        let duration_hours = self.reference_duration_hours(&job, prediction.as_ref());
+        let required = Self::predicted_requirements(&job, prediction.as_ref());
+
+        let nodes = self.available_nodes.lock()
+            .map_err(|e| anyhow::anyhow!("Lock poisoned: {}", e))?;
+        let index: &NodeIndex = &nodes;
+        let cheapest = index.candidates(&required)
+            .into_iter()
+            .filter(|node| self.devices.fits(node, &job))
+            .filter_map(|node| self.evaluate_node(node, &job, duration_hours, index))
+            .map(|placement| placement.estimated_cost.total_usd)
+            .min_by(f64::total_cmp);
+        Ok(cheapest.map(|usd| usd * job.gang_size.max(1) as f64))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{NodeInfo, SlaConstraints};
+
+    #[tokio::test]
+    async fn test_ceiling_overrides_budget_at_admission() {
+        let mut scheduler = EconomicScheduler::new();
+        scheduler.set_cost_ceilings(CostCeilings::new().parse("inference=0.05").unwrap());
+        assert!(CostCeilings::new().parse("batch=1").is_err());
+        scheduler.register_node(NodeInfo {
+            id: "gpu-box".to_string(),
+            available_cpu: 8,
+            available_memory_gb: 32,
+            cost_per_hour: 2.0,
+            ..Default::default()
+        }).unwrap();
+
+        // A generous budget does not lift the ceiling
+        let job = JobSpec {
+            id: "infer-1".to_string(),
+            job_type: JobType::Inference,
+            sla: SlaConstraints { max_latency_ms: 5000, max_budget_usd: Some(100.0), ..Default::default() },
+            cost_ceiling_usd: Some(1000.0),
+            ..Default::default()
+        };
+        let err = scheduler.admit(job.clone()).await.unwrap_err();
+        assert_eq!(crate::error::classify(&err).unwrap().reason(), "OVER_COST_CEILING");
+
+        // Other types are not capped
+        let training = JobSpec { id: "train-1".to_string(), job_type: JobType::Training, ..job.clone() };
+        assert_eq!(scheduler.admit(training).await.unwrap().cost_ceiling_usd, None);
+
+        // Short enough to fit under the ceiling
+        let short = JobSpec { estimated_duration_hours: Some(0.01), ..job };
+        let admitted = scheduler.admit(short).await.unwrap();
+        assert_eq!(admitted.cost_ceiling_usd, Some(0.05));
+        scheduler.schedule(admitted).await.unwrap();
+    }
+}
@@ -0,0 +1,260 @@
+//! Fault injection for resilience tests
+//!
+//! Builds with the `chaos` feature accept the InjectFault admin RPC, which
+//! makes the scheduler misbehave on purpose so integration tests can watch
+//! workers reconnect and jobs get rescheduled:
+//!
+//! - drop heartbeats: ResourceReport from a node (or every node) fails as
+//!   if it never arrived
+//! - delay RPCs: calls to one method (or all) wait before being handled
+//! - kill a container: the job's worker stops it at once and reports it
+//!   failed, as after a crash
+//! - poison locks: the placement lock cannot be taken, so queued jobs stay
+//!   queued
+//!
+//! Faults last until they expire or ClearFaults. Without the feature the
+//! hooks below cost nothing and the RPCs answer Unimplemented.
+
+use anyhow::Result;
+use async_trait::async_trait;
+use std::sync::Arc;
+use std::time::Duration;
+#[cfg(feature = "chaos")]
+use std::sync::Mutex;
+#[cfg(feature = "chaos")]
+use std::time::Instant;
+#[cfg(feature = "chaos")]
+use tonic::codegen::{http, BoxFuture, Context, Poll, Service};
+
+use crate::commands::{CommandTransport, WorkerCommand};
+use crate::error::SchedulerError;
+use crate::queue::LockManager;
+use crate::EconomicScheduler;
+
+/// A fault to inject
+#[derive(Debug, Clone, PartialEq)]
+pub enum Fault {
+    /// Drop resource reports from `node_id` (None: from every node)
+    DropHeartbeats { node_id: Option<String> },
+    /// Hold calls to `method`, e.g. "SubmitJob" (None: every method)
+    DelayRpcs { method: Option<String>, delay: Duration },
+    /// Fail every attempt to take a scheduling lock
+    PoisonLocks,
+}
+
+impl Fault {
+    /// Whether `self` holds for a heartbeat from `node_id`
+    fn drops_heartbeat(&self, node_id: &str) -> bool {
+        matches!(self, Fault::DropHeartbeats { node_id: target } if !target.as_deref().is_some_and(|target| target != node_id))
+    }
+
+    /// Delay for a call to the gRPC `path` (`/package.Service/Method`)
+    fn delay_for(&self, path: &str) -> Option<Duration> {
+        match self {
+            Fault::DelayRpcs { method, delay } => {
+                let matches = !method.as_deref()
+                    .is_some_and(|method| path.rsplit('/').next() != Some(method));
+                matches.then_some(*delay)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Active faults, shared by every clone of the scheduler
+#[derive(Debug, Clone, Default)]
+pub struct Faults {
+    #[cfg(feature = "chaos")]
+    active: Arc<Mutex<Vec<(Fault, Option<Instant>)>>>,
+}
+
+impl Faults {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Inject `fault` for `duration` (None: until cleared)
+    pub fn inject(&self, fault: Fault, duration: Option<Duration>) -> Result<()> {
+        #[cfg(feature = "chaos")]
+        {
+            tracing::warn!("Injecting fault {:?} for {:?}", fault, duration);
+            let until = duration.map(|duration| Instant::now() + duration);
+            self.active.lock()
+                .map_err(|e| anyhow::anyhow!("Lock poisoned: {}", e))?
+                .push((fault, until));
+            Ok(())
+        }
+        #[cfg(not(feature = "chaos"))]
+        {
+            let _ = (fault, duration);
+            anyhow::bail!("Fault injection not compiled in (enable the `chaos` feature)")
+        }
+    }
+
+    /// Remove every fault, returning how many were active
+    pub fn clear(&self) -> usize {
+        #[cfg(feature = "chaos")]
+        if let Ok(mut active) = self.active.lock() {
+            let cleared = active.len();
+            active.clear();
+            return cleared;
+        }
+        0
+    }
+
+    /// Faults still in effect
+    pub fn active(&self) -> Vec<Fault> {
+        #[cfg(feature = "chaos")]
+        if let Ok(mut active) = self.active.lock() {
+            let now = Instant::now();
+            active.retain(|(_, until)| !until.is_some_and(|until| until <= now));
+            return active.iter().map(|(fault, _)| fault.clone()).collect();
+        }
+        Vec::new()
+    }
+
+    pub fn drops_heartbeat(&self, node_id: &str) -> bool {
+        self.active().iter().any(|fault| fault.drops_heartbeat(node_id))
+    }
+
+    pub fn rpc_delay(&self, path: &str) -> Option<Duration> {
+        self.active().iter().filter_map(|fault| fault.delay_for(path)).max()
+    }
+
+    pub fn locks_poisoned(&self) -> bool {
+        self.active().contains(&Fault::PoisonLocks)
+    }
+}
+
+impl EconomicScheduler {
+    pub fn faults(&self) -> &Faults {
+        &self.faults
+    }
+
+    /// Have the workers running `job_id` kill its container without
+    /// warning; returns how many were told
+    ///
+    /// The scheduler is not told first, so the job fails as it would after
+    /// a crash or an OOM kill.
+    pub async fn kill_job_container(&self, job_id: &str, transport: &dyn CommandTransport) -> Result<usize> {
+        if !cfg!(feature = "chaos") {
+            anyhow::bail!("Fault injection not compiled in (enable the `chaos` feature)");
+        }
+        let nodes = self.job_nodes(job_id);
+        if nodes.is_empty() {
+            return Err(SchedulerError::not_found("Placed job", job_id).into());
+        }
+        tracing::warn!("Killing the container of job {} (fault injection)", job_id);
+        let mut killed = 0;
+        for node_id in nodes {
+            let command = WorkerCommand::Kill { job_id: job_id.to_string() };
+            if self.push_command(transport, &node_id, &command).await? {
+                killed += 1;
+            }
+        }
+        Ok(killed)
+    }
+}
+
+/// Lock manager that refuses every lock while locks are poisoned
+pub struct FaultyLocks {
+    inner: Arc<dyn LockManager>,
+    faults: Faults,
+}
+
+impl FaultyLocks {
+    pub fn wrap(inner: Arc<dyn LockManager>, faults: Faults) -> Arc<dyn LockManager> {
+        Arc::new(Self { inner, faults })
+    }
+}
+
+#[async_trait]
+impl LockManager for FaultyLocks {
+    async fn try_lock(&self, name: &str, ttl: Duration) -> Result<Option<String>> {
+        if self.faults.locks_poisoned() {
+            anyhow::bail!("Lock {} is poisoned (fault injection)", name);
+        }
+        self.inner.try_lock(name, ttl).await
+    }
+
+    async fn unlock(&self, name: &str, token: &str) -> Result<()> {
+        self.inner.unlock(name, token).await
+    }
+}
+
+/// gRPC service wrapper holding calls while an RPC delay is injected
+#[cfg(feature = "chaos")]
+#[derive(Debug, Clone)]
+pub struct FaultyService<S> {
+    inner: S,
+    faults: Faults,
+}
+
+#[cfg(feature = "chaos")]
+impl<S> FaultyService<S> {
+    pub fn new(inner: S, faults: Faults) -> Self {
+        Self { inner, faults }
+    }
+}
+
+#[cfg(feature = "chaos")]
+impl<S: tonic::server::NamedService> tonic::server::NamedService for FaultyService<S> {
+    const NAME: &'static str = S::NAME;
+}
+
+#[cfg(feature = "chaos")]
+impl<S, B> Service<http::Request<B>> for FaultyService<S>
+where
+    S: Service<http::Request<B>> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+    B: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = BoxFuture<S::Response, S::Error>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: http::Request<B>) -> Self::Future {
+        let delay = self.faults.rpc_delay(request.uri().path());
+        // Call the instance poll_ready was called on; keep a fresh clone
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        Box::pin(async move {
+            if let Some(delay) = delay {
+                tokio::time::sleep(delay).await;
+            }
+            inner.call(request).await
+        })
+    }
+}
+
+#[cfg(all(test, feature = "chaos"))]
+mod tests {
+    use super::*;
+    use crate::queue::MemoryLocks;
+
+    #[tokio::test]
+    async fn test_faults_apply_until_cleared() {
+        let faults = Faults::new();
+        faults.inject(Fault::DropHeartbeats { node_id: Some("node-1".to_string()) }, None).unwrap();
+        faults.inject(Fault::DelayRpcs { method: Some("SubmitJob".to_string()), delay: Duration::from_millis(200) }, None).unwrap();
+        faults.inject(Fault::PoisonLocks, Some(Duration::ZERO)).unwrap();
+
+        assert!(faults.drops_heartbeat("node-1"));
+        assert!(!faults.drops_heartbeat("node-2"));
+        assert_eq!(faults.rpc_delay("/tgp.scheduler.v1.SchedulerService/SubmitJob"), Some(Duration::from_millis(200)));
+        assert_eq!(faults.rpc_delay("/tgp.scheduler.v1.SchedulerService/GetJobStatus"), None);
+
+        // The lock fault has already expired
+        let locks = FaultyLocks::wrap(Arc::new(MemoryLocks::new()), faults.clone());
+        assert!(locks.try_lock("placement", Duration::from_secs(1)).await.unwrap().is_some());
+        faults.inject(Fault::PoisonLocks, None).unwrap();
+        assert!(locks.try_lock("other", Duration::from_secs(1)).await.is_err());
+
+        assert_eq!(faults.clear(), 3);
+        assert!(faults.active().is_empty());
+    }
+}
@@ -0,0 +1,132 @@
+//! Worker clock skew
+//!
+//! Workers stamp resource reports, job progress and interruption notices
+//! with their own clock, which on cheap VPS nodes can be minutes off. Every
+//! resource report measures the node's offset from the scheduler's clock
+//! (smoothed, as network delay adds noise to each sample). A node whose
+//! offset exceeds the limit is logged once each time it drifts out, and
+//! worker timestamps are shifted onto the scheduler's clock before deadline
+//! or staleness math sees them.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use crate::EconomicScheduler;
+
+/// Offset logged as excessive unless configured otherwise
+pub const DEFAULT_MAX_SKEW_SECS: i64 = 30;
+
+/// Weight of a new sample in the smoothed offset
+const SMOOTHING: f64 = 0.3;
+
+/// A node's measured clock offset
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NodeSkew {
+    /// Worker clock minus scheduler clock, in seconds
+    pub offset_secs: f64,
+    /// Scheduler time of the last sample
+    pub measured_at: i64,
+    /// Offset is over the limit
+    pub excessive: bool,
+}
+
+/// Clock offsets of all nodes, shared by scheduler clones
+#[derive(Debug, Clone)]
+pub struct ClockSkew {
+    nodes: Arc<Mutex<HashMap<String, NodeSkew>>>,
+    max_skew_secs: i64,
+}
+
+impl Default for ClockSkew {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_SKEW_SECS)
+    }
+}
+
+impl ClockSkew {
+    pub fn new(max_skew_secs: i64) -> Self {
+        Self { nodes: Arc::new(Mutex::new(HashMap::new())), max_skew_secs: max_skew_secs.max(1) }
+    }
+
+    pub fn max_skew_secs(&self) -> i64 {
+        self.max_skew_secs
+    }
+
+    /// Record a worker timestamp received at scheduler time `now`; returns
+    /// the node's smoothed offset
+    pub fn record(&self, node_id: &str, worker_time: i64, now: i64) -> f64 {
+        let sample = (worker_time - now) as f64;
+        let mut nodes = self.nodes.lock().unwrap_or_else(|e| e.into_inner());
+        let skew = nodes.entry(node_id.to_string()).or_insert(NodeSkew {
+            offset_secs: sample,
+            measured_at: now,
+            excessive: false,
+        });
+        skew.offset_secs += SMOOTHING * (sample - skew.offset_secs);
+        skew.measured_at = now;
+
+        let excessive = skew.offset_secs.abs() > self.max_skew_secs as f64;
+        if excessive && !skew.excessive {
+            tracing::warn!(
+                "Clock of node {} is {:.0}s {} the scheduler's (limit {}s); its timestamps are corrected",
+                node_id,
+                skew.offset_secs.abs(),
+                if skew.offset_secs > 0.0 { "ahead of" } else { "behind" },
+                self.max_skew_secs
+            );
+        } else if !excessive && skew.excessive {
+            tracing::info!("Clock of node {} is back within {}s of the scheduler's", node_id, self.max_skew_secs);
+        }
+        skew.excessive = excessive;
+        skew.offset_secs
+    }
+
+    pub fn skew_of(&self, node_id: &str) -> Option<NodeSkew> {
+        self.nodes.lock().unwrap_or_else(|e| e.into_inner()).get(node_id).copied()
+    }
+
+    /// A worker timestamp on the scheduler's clock (0 and unmeasured nodes
+    /// are left alone)
+    pub fn normalize(&self, node_id: &str, worker_time: i64) -> i64 {
+        match self.skew_of(node_id) {
+            Some(skew) if worker_time > 0 => worker_time - skew.offset_secs.round() as i64,
+            _ => worker_time,
+        }
+    }
+}
+
+impl EconomicScheduler {
+    pub fn set_max_clock_skew(&mut self, max_skew_secs: i64) {
+        self.clock_skew = ClockSkew::new(max_skew_secs);
+    }
+
+    pub fn clock_skew(&self) -> &ClockSkew {
+        &self.clock_skew
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_skewed_worker_timestamps_are_normalized() {
+        let skew = ClockSkew::new(30);
+        let now = 1_700_000_000;
+
+        // A worker 120s fast, with a second of network jitter
+        assert_eq!(skew.record("fast", now + 120, now), 120.0);
+        skew.record("fast", now + 131, now + 10);
+        let fast = skew.skew_of("fast").unwrap();
+        assert!(fast.excessive && (fast.offset_secs - 120.3).abs() < 1e-9);
+        assert_eq!(skew.normalize("fast", now + 620), now + 500);
+
+        skew.record("synced", now + 2, now);
+        assert!(!skew.skew_of("synced").unwrap().excessive);
+        assert_eq!(skew.normalize("synced", now), now - 2);
+
+        // Unknown timestamps and unmeasured nodes pass through
+        assert_eq!(skew.normalize("fast", 0), 0);
+        assert_eq!(skew.normalize("new", now), now);
+    }
+}
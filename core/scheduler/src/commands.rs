@@ -0,0 +1,412 @@
+//! Scheduler-initiated worker commands
+//!
+//! When a job is placed or cancelled the scheduler tells the worker
+//! directly instead of waiting for the worker to ask. Commands are derived
+//! from lifecycle events, so every placement path (direct submission,
+//! queue dispatch, gangs, backfill) is covered, and sent to the worker's
+//! control service, or queued on the worker's own command stream when it
+//! cannot be dialled (see `relay`).
+//!
+//! Control services run jobs, so every call carries the worker control
+//! token (TGP_WORKER_CONTROL_TOKEN, the same secret every worker is given)
+//! as a bearer token; workers refuse calls without it.
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::broadcast;
+use tonic::metadata::{Ascii, MetadataValue};
+use tonic::service::interceptor::InterceptedService;
+use tonic::service::Interceptor;
+use tonic::transport::{Channel, Endpoint};
+use tonic::{Request, Status};
+
+use crate::devices::DeviceAssignment;
+use crate::events::SchedulerEvent;
+use crate::relay::CommandRelay;
+use crate::{EconomicScheduler, JobSpec, JobStatus, JobType, NodeInfo};
+
+// Include generated worker service client code
+pub mod worker_proto {
+    tonic::include_proto!("tgp.worker.v1");
+}
+
+use worker_proto::{
+    worker_service_client::WorkerServiceClient, CancelJobRequest, ExecuteJobRequest, JobHook, PingRequest,
+};
+
+/// Seconds a cancelled job's container gets to checkpoint before SIGKILL
+pub const CANCEL_GRACE_SECS: i64 = 30;
+
+/// How long to wait for a control service to accept without a call timeout
+const CONTROL_CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Command for one worker
+#[derive(Debug, Clone)]
+pub enum WorkerCommand {
+    /// Run the job with the devices it holds on the target node
+    Execute(Box<JobSpec>, DeviceAssignment),
+    Cancel { job_id: String, grace_secs: i64 },
+    /// Kill the job's container at once and report the job failed, as a
+    /// crash would (fault injection)
+    Kill { job_id: String },
+}
+
+impl WorkerCommand {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::Execute(..) => "execute",
+            Self::Cancel { .. } => "cancel",
+            Self::Kill { .. } => "kill",
+        }
+    }
+
+    pub fn job_id(&self) -> &str {
+        match self {
+            Self::Execute(job, _) => &job.id,
+            Self::Cancel { job_id, .. } | Self::Kill { job_id } => job_id,
+        }
+    }
+}
+
+/// Delivers commands to workers
+#[async_trait]
+pub trait CommandTransport: Send + Sync {
+    async fn send(&self, node: &NodeInfo, command: &WorkerCommand) -> Result<()>;
+
+    /// Whether this transport can deliver to `node`
+    fn reaches(&self, node: &NodeInfo) -> bool {
+        !node.control_addr.is_empty()
+    }
+}
+
+/// Worker control token the scheduler presents on every control call
+#[derive(Clone, Default)]
+pub struct ControlAuth {
+    token: Option<Arc<str>>,
+    header: Option<MetadataValue<Ascii>>,
+}
+
+impl std::fmt::Debug for ControlAuth {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ControlAuth").field("configured", &self.header.is_some()).finish()
+    }
+}
+
+impl ControlAuth {
+    pub fn new(token: &str) -> Result<Self> {
+        let token = token.trim();
+        anyhow::ensure!(!token.is_empty(), "Worker control token is empty");
+        let header = format!("Bearer {}", token).parse()
+            .map_err(|_| anyhow::anyhow!("Worker control token is not valid in a header"))?;
+        Ok(Self { token: Some(token.into()), header: Some(header) })
+    }
+
+    pub fn is_configured(&self) -> bool {
+        self.header.is_some()
+    }
+
+    pub(crate) fn token(&self) -> Option<&str> {
+        self.token.as_deref()
+    }
+}
+
+impl Interceptor for ControlAuth {
+    fn call(&mut self, mut request: Request<()>) -> Result<Request<()>, Status> {
+        if let Some(header) = &self.header {
+            request.metadata_mut().insert("authorization", header.clone());
+        }
+        Ok(request)
+    }
+}
+
+/// Client of a worker's control service presenting the control token
+pub type WorkerClient = WorkerServiceClient<InterceptedService<Channel, ControlAuth>>;
+
+/// Connect to a worker's control service; `timeout` of None leaves calls
+/// unbounded (exec sessions)
+pub(crate) async fn connect_worker(node: &NodeInfo, timeout: Option<Duration>, auth: &ControlAuth) -> Result<WorkerClient> {
+    let mut endpoint = Endpoint::from_shared(format!("http://{}", node.control_addr))
+        .with_context(|| format!("Invalid control address {}", node.control_addr))?
+        .connect_timeout(timeout.unwrap_or(CONTROL_CONNECT_TIMEOUT));
+    if let Some(timeout) = timeout {
+        endpoint = endpoint.timeout(timeout);
+    }
+    let channel = endpoint
+        .connect()
+        .await
+        .with_context(|| format!("Failed to connect to {}", node.control_addr))?;
+    Ok(WorkerServiceClient::with_interceptor(channel, auth.clone()))
+}
+
+pub(crate) fn job_type_name(job_type: &JobType) -> &'static str {
+    match job_type {
+        JobType::Training => "training",
+        JobType::Inference => "inference",
+        JobType::DataProcessing => "data_processing",
+    }
+}
+
+/// ExecuteJob request for a placed job
+pub fn execute_request(job: &JobSpec, devices: &DeviceAssignment) -> ExecuteJobRequest {
+    ExecuteJobRequest {
+        job_id: job.id.clone(),
+        job_type: job_type_name(&job.job_type).to_string(),
+        container_image: job.container_image.clone(),
+        command: job.command.clone(),
+        environment: job.env.clone(),
+        cpu_limit: job.resources.cpu_cores,
+        memory_limit_mb: job.resources.memory_gb as u64 * 1024,
+        disk_limit_gb: job.resources.disk_gb,
+        gpu_indices: devices.gpu_indices.clone(),
+        port_bindings: devices.ports.iter()
+            .map(|binding| (binding.container_port as u32, binding.host_port as u32))
+            .collect(),
+        cpu_set: devices.cpu_set.clone(),
+        numa_node: devices.numa_node,
+        disk_io_mbps: job.resources.disk_io_mbps.unwrap_or(0),
+        egress_limit_mbps: job.egress_limit_mbps.unwrap_or(0),
+        input: job.job_data.clone(),
+        input_stdin: job.input_stdin,
+        output_path: job.output_path.clone().unwrap_or_default(),
+        payload_digest: job.payload_digest.clone().unwrap_or_default(),
+        pre_start_hook: job.pre_start_hook.as_ref().map(job_hook),
+        post_complete_hook: job.post_complete_hook.as_ref().map(job_hook),
+        datasets: job.datasets.clone(),
+    }
+}
+
+fn job_hook(hook: &crate::lifecycle::JobHook) -> JobHook {
+    JobHook {
+        image: hook.image.clone(),
+        command: hook.command.clone(),
+        timeout_secs: hook.timeout_secs,
+    }
+}
+
+/// Transport calling the worker's control service over gRPC
+pub struct GrpcCommandTransport {
+    timeout: Duration,
+    auth: ControlAuth,
+}
+
+impl GrpcCommandTransport {
+    pub fn new(timeout: Duration, auth: ControlAuth) -> Self {
+        Self { timeout, auth }
+    }
+
+    /// Number of jobs the worker is running, if it answers
+    pub async fn ping(&self, node: &NodeInfo) -> Result<u32> {
+        let reply = connect_worker(node, Some(self.timeout), &self.auth).await?
+            .ping(PingRequest {})
+            .await
+            .context("Ping failed")?
+            .into_inner();
+        Ok(reply.running_jobs)
+    }
+}
+
+#[async_trait]
+impl CommandTransport for GrpcCommandTransport {
+    async fn send(&self, node: &NodeInfo, command: &WorkerCommand) -> Result<()> {
+        let mut client = connect_worker(node, Some(self.timeout), &self.auth).await?;
+        match command {
+            WorkerCommand::Execute(job, devices) => {
+                let reply = client.execute_job(execute_request(job, devices)).await
+                    .context("ExecuteJob failed")?
+                    .into_inner();
+                if !reply.accepted {
+                    anyhow::bail!("Worker refused job {}: {}", job.id, reply.message);
+                }
+            }
+            WorkerCommand::Cancel { job_id, grace_secs } => {
+                let reply = client.cancel_job(CancelJobRequest { job_id: job_id.clone(), grace_secs: *grace_secs, crash: false })
+                    .await
+                    .context("CancelJob failed")?
+                    .into_inner();
+                if !reply.cancelled {
+                    tracing::debug!("Job {} was not running on node {}", job_id, node.id);
+                }
+            }
+            WorkerCommand::Kill { job_id } => {
+                let reply = client.cancel_job(CancelJobRequest { job_id: job_id.clone(), grace_secs: 0, crash: true })
+                    .await
+                    .context("CancelJob failed")?
+                    .into_inner();
+                if !reply.cancelled {
+                    tracing::debug!("Job {} was not running on node {}", job_id, node.id);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl EconomicScheduler {
+    pub fn set_control_auth(&mut self, auth: ControlAuth) {
+        self.control_auth = auth;
+    }
+
+    /// Token presented to worker control services
+    pub fn control_auth(&self) -> &ControlAuth {
+        &self.control_auth
+    }
+
+    /// Nodes a job was placed on (every node of a gang)
+    pub(crate) fn job_nodes(&self, job_id: &str) -> Vec<String> {
+        let Some(state) = self.get_job_state(job_id) else {
+            return Vec::new();
+        };
+        if state.gang_nodes.is_empty() {
+            state.assigned_node.into_iter().collect()
+        } else {
+            state.gang_nodes
+        }
+    }
+
+    /// Commands an event calls for, with the node each goes to
+    pub fn commands_for(&self, event: &SchedulerEvent) -> Vec<(String, WorkerCommand)> {
+        match event {
+            SchedulerEvent::JobScheduled { job_id, .. } => {
+                let Some(job) = self.placed_jobs.lock().ok().and_then(|placed| placed.get(job_id).cloned()) else {
+                    return Vec::new();
+                };
+                self.job_nodes(job_id).into_iter()
+                    .map(|node| {
+                        let devices = self.devices.assignment(job_id, &node).unwrap_or_default();
+                        (node, WorkerCommand::Execute(Box::new(job.clone()), devices))
+                    })
+                    .collect()
+            }
+            SchedulerEvent::JobStatusChanged { job_id, status: JobStatus::Cancelled, node_id: Some(_), .. } => {
+                self.job_nodes(job_id).into_iter()
+                    .map(|node| (node, WorkerCommand::Cancel {
+                        job_id: job_id.clone(),
+                        grace_secs: CANCEL_GRACE_SECS,
+                    }))
+                    .collect()
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    /// Outboxes of workers connected over a command stream
+    pub fn command_relay(&self) -> &CommandRelay {
+        &self.relay
+    }
+
+    /// Send a command to a node directly if `transport` reaches it, else
+    /// over its command stream; false if the node is reachable neither way
+    pub async fn push_command(&self, transport: &dyn CommandTransport, node_id: &str, command: &WorkerCommand) -> Result<bool> {
+        let node = self.available_nodes.lock()
+            .map_err(|e| anyhow::anyhow!("Lock poisoned: {}", e))?
+            .get(node_id)
+            .cloned()
+            .with_context(|| format!("Node {} is not registered", node_id))?;
+
+        if transport.reaches(&node) {
+            match transport.send(&node, command).await {
+                Ok(()) => {
+                    tracing::info!("Sent {} of job {} to node {}", command.kind(), command.job_id(), node_id);
+                    return Ok(true);
+                }
+                // The worker may still be reachable through its own stream
+                Err(e) if self.relay.is_connected(node_id) => {
+                    tracing::warn!("Direct {} to node {} failed, relaying: {:#}", command.kind(), node_id, e);
+                }
+                Err(e) => return Err(e),
+            }
+        } else if !self.relay.reaches(&node) {
+            return Ok(false);
+        }
+        self.relay.send(&node, command).await?;
+        tracing::info!("Queued {} of job {} for node {}", command.kind(), command.job_id(), node_id);
+        Ok(true)
+    }
+}
+
+/// Push commands to workers as jobs are placed and cancelled
+pub async fn run_command_pusher(scheduler: EconomicScheduler, transport: Arc<dyn CommandTransport>) {
+    let mut events = scheduler.subscribe_events();
+    loop {
+        let event = match events.recv().await {
+            Ok(event) => event,
+            Err(broadcast::error::RecvError::Lagged(missed)) => {
+                tracing::warn!("Command pusher missed {} events", missed);
+                continue;
+            }
+            Err(broadcast::error::RecvError::Closed) => return,
+        };
+
+        for (node_id, command) in scheduler.commands_for(&event) {
+            let scheduler = scheduler.clone();
+            let transport = transport.clone();
+            tokio::spawn(async move {
+                if let Err(e) = scheduler.push_command(transport.as_ref(), &node_id, &command).await {
+                    tracing::warn!("Failed to push job {} to node {}: {:#}", command.job_id(), node_id, e);
+                }
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ResourceRequirements, SlaConstraints};
+    use std::sync::Mutex;
+
+    #[derive(Default)]
+    struct RecordingTransport(Mutex<Vec<(String, String)>>);
+
+    #[async_trait]
+    impl CommandTransport for RecordingTransport {
+        async fn send(&self, node: &NodeInfo, command: &WorkerCommand) -> Result<()> {
+            self.0.lock().unwrap().push((node.id.clone(), format!("{} {}", command.kind(), command.job_id())));
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_placement_and_cancel_are_pushed_to_reachable_workers() {
+        let scheduler = EconomicScheduler::new();
+        scheduler.register_node(NodeInfo {
+            id: "node-1".to_string(),
+            available_cpu: 4,
+            available_memory_gb: 16,
+            location: "vps-1".to_string(),
+            cost_per_hour: 0.1,
+            control_addr: "10.0.0.1:50053".to_string(),
+            ..Default::default()
+        }).unwrap();
+        let mut events = scheduler.subscribe_events();
+        let transport = RecordingTransport::default();
+        let push_pending = |events: &mut broadcast::Receiver<SchedulerEvent>| {
+            let mut commands = Vec::new();
+            while let Ok(event) = events.try_recv() {
+                commands.extend(scheduler.commands_for(&event));
+            }
+            commands
+        };
+
+        scheduler.schedule(JobSpec {
+            id: "job-1".to_string(),
+            resources: ResourceRequirements { cpu_cores: 2, memory_gb: 4, ..Default::default() },
+            sla: SlaConstraints { max_latency_ms: 1_000, ..Default::default() },
+            container_image: "alpine:latest".to_string(),
+            ..Default::default()
+        }).await.unwrap();
+        for (node_id, command) in push_pending(&mut events) {
+            assert!(scheduler.push_command(&transport, &node_id, &command).await.unwrap());
+        }
+        scheduler.cancel_job("job-1").unwrap();
+        for (node_id, command) in push_pending(&mut events) {
+            assert!(scheduler.push_command(&transport, &node_id, &command).await.unwrap());
+        }
+        assert_eq!(*transport.0.lock().unwrap(), vec![
+            ("node-1".to_string(), "execute job-1".to_string()),
+            ("node-1".to_string(), "cancel job-1".to_string()),
+        ]);
+    }
+}
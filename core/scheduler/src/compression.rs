@@ -0,0 +1,96 @@
+//! Compression-aware data transfer costs
+//!
+//! The sending worker compresses a dataset transfer with zstd when a sample
+//! of the dataset shrinks enough to pay for the CPU time, and the receiving
+//! worker reports both the dataset bytes and the bytes that crossed the
+//! network. The transfer ledger keeps the observed ratio per dataset; C_data
+//! then charges a compressible dataset for its wire bytes plus the CPU spent
+//! compressing and decompressing it, and staging estimates move only the
+//! wire bytes. Datasets never transferred are assumed incompressible.
+
+use crate::datasets::DatasetInfo;
+use crate::{EconomicScheduler, DATA_TRANSFER_PRICE_PER_GB};
+
+/// CPU cost of compressing and decompressing one GB with zstd (about five
+/// core-seconds on both ends at typical vCPU prices), in USD
+pub const COMPRESSION_CPU_USD_PER_GB: f64 = 0.0002;
+
+/// Smallest saving, as a fraction of the bytes, workers compress for
+pub const MIN_COMPRESSION_SAVING: f64 = 0.1;
+
+/// Fraction of a dataset's bytes that crosses the network, given its
+/// observed wire ratio
+pub fn wire_fraction(ratio: Option<f64>) -> f64 {
+    ratio
+        .filter(|ratio| *ratio > 0.0 && *ratio <= 1.0 - MIN_COMPRESSION_SAVING)
+        .unwrap_or(1.0)
+}
+
+/// Fraction of a dataset's size paid at the transfer price: the wire bytes
+/// plus the compression CPU expressed in transfer GB, never more than
+/// sending it as is
+pub fn billed_fraction(ratio: Option<f64>, price_per_gb: f64) -> f64 {
+    let wire = wire_fraction(ratio);
+    if wire >= 1.0 || price_per_gb <= 0.0 {
+        return 1.0;
+    }
+    (wire + COMPRESSION_CPU_USD_PER_GB / price_per_gb).min(1.0)
+}
+
+impl EconomicScheduler {
+    /// GB charged at the transfer price to stage `names` onto `node_id`
+    pub(crate) fn billed_transfer_gb(&self, node_id: &str, names: &[String]) -> f64 {
+        self.datasets.missing(node_id, names)
+            .iter()
+            .map(|dataset| {
+                let ratio = self.transfers.compression_ratio(&dataset.name);
+                self.staged_gb(node_id, dataset) * billed_fraction(ratio, DATA_TRANSFER_PRICE_PER_GB)
+            })
+            .sum()
+    }
+
+    /// GB that cross the network when staging `dataset` onto `node_id`
+    pub(crate) fn wire_gb(&self, node_id: &str, dataset: &DatasetInfo) -> f64 {
+        self.staged_gb(node_id, dataset) * wire_fraction(self.transfers.compression_ratio(&dataset.name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transfers::TransferRecord;
+
+    #[test]
+    fn test_compressible_datasets_cost_less_to_stage() {
+        let scheduler = EconomicScheduler::new();
+        scheduler.datasets.sync_node("node-a", &[("logs".to_string(), 100.0), ("jpegs".to_string(), 100.0)]);
+        let wanted = vec!["logs".to_string(), "jpegs".to_string()];
+        assert_eq!(scheduler.billed_transfer_gb("node-b", &wanted), 200.0);
+
+        for (dataset, wire_bytes) in [("logs", 250), ("jpegs", 990)] {
+            scheduler.record_transfer(TransferRecord {
+                transfer_id: format!("{}-1", dataset),
+                dataset: dataset.to_string(),
+                source_node: "node-a".to_string(),
+                dest_node: "node-c".to_string(),
+                bytes_transferred: 1000,
+                total_bytes: 1000,
+                elapsed_ms: 10,
+                completed: true,
+                error: None,
+                wire_bytes,
+                delta_sync: false,
+                reused_bytes: 0,
+            });
+        }
+
+        // logs: a quarter on the wire plus CPU; jpegs barely shrink and go as is
+        let expected = 100.0 * (0.25 + COMPRESSION_CPU_USD_PER_GB / DATA_TRANSFER_PRICE_PER_GB) + 100.0;
+        assert!((scheduler.billed_transfer_gb("node-b", &wanted) - expected).abs() < 1e-9);
+        let logs = scheduler.datasets.get("logs").unwrap();
+        let jpegs = scheduler.datasets.get("jpegs").unwrap();
+        assert!((scheduler.wire_gb("node-b", &logs) - 25.0).abs() < 1e-9);
+        assert_eq!(scheduler.wire_gb("node-b", &jpegs), 100.0);
+        assert_eq!(billed_fraction(Some(0.5), 0.0), 1.0);
+    }
+}
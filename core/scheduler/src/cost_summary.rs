@@ -0,0 +1,200 @@
+//! Cluster-wide TCO summary
+//!
+//! Breaks the spend of a time window down into the Formula 4.1 components,
+//! per node and per tenant. When a placed job finishes, its C_comp and
+//! C_data estimate is recorded in the metrics history against each node it
+//! ran on (split evenly across a gang) and against its tenant. C_idle is
+//! recorded per node as the energy manager accounts idle time; a node is
+//! only charged idle cost while it holds no jobs. Tenants carry C_idle only
+//! for the unused part of capacity reserved for them (see
+//! `tenant_reservations`), a charge-back that is not added to the total.
+//! Node pools add up the spend of the nodes they hold now, and the market
+//! rate of each resource class is averaged over the window.
+
+use std::collections::{BTreeMap, HashMap};
+
+use crate::market::MARKET_RATE_USD;
+use crate::timeseries::TimeSeriesStore;
+use crate::{EconomicScheduler, JobStatus};
+
+/// C_comp of finished jobs in USD, label: node id
+pub const NODE_COMPUTE_USD: &str = "node_compute_usd";
+/// C_data of finished jobs in USD, label: node id
+pub const NODE_DATA_USD: &str = "node_data_usd";
+/// C_idle of powered, idle time in USD, label: node id
+pub const NODE_IDLE_USD: &str = "node_idle_usd";
+/// C_comp of finished jobs in USD, label: tenant
+pub const TENANT_COMPUTE_USD: &str = "tenant_compute_usd";
+/// C_data of finished jobs in USD, label: tenant
+pub const TENANT_DATA_USD: &str = "tenant_data_usd";
+/// C_idle of reserved capacity the tenant left unused, label: tenant
+pub const TENANT_IDLE_USD: &str = "tenant_idle_usd";
+
+/// Formula 4.1 components over a window
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct CostBreakdown {
+    pub compute_usd: f64,
+    pub data_usd: f64,
+    pub idle_usd: f64,
+}
+
+impl CostBreakdown {
+    pub fn total_usd(&self) -> f64 {
+        self.compute_usd + self.data_usd + self.idle_usd
+    }
+
+    fn add(&mut self, other: &CostBreakdown) {
+        self.compute_usd += other.compute_usd;
+        self.data_usd += other.data_usd;
+        self.idle_usd += other.idle_usd;
+    }
+}
+
+/// Spend of `[since, until)` by component, node and tenant
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CostSummary {
+    pub since: i64,
+    pub until: i64,
+    pub total: CostBreakdown,
+    /// By node id
+    pub nodes: BTreeMap<String, CostBreakdown>,
+    /// By tenant (empty: no tenant)
+    pub tenants: BTreeMap<String, CostBreakdown>,
+    /// By node pool
+    pub pools: BTreeMap<String, CostBreakdown>,
+    /// Average market rate per resource class, USD per unit-hour (see
+    /// `market`)
+    pub market_rates: BTreeMap<String, f64>,
+}
+
+/// Add each series of `metric` in the window to the breakdown of its label
+fn add_series(
+    into: &mut BTreeMap<String, CostBreakdown>,
+    store: &TimeSeriesStore,
+    metric: &str,
+    (since, until): (i64, i64),
+    component: impl Fn(&mut CostBreakdown, f64),
+) {
+    let step = (until - since).max(1);
+    for series in store.query(metric, None, since, until, step) {
+        let usd = series.points.iter().map(|(_, value)| value).sum();
+        component(into.entry(series.label).or_default(), usd);
+    }
+}
+
+impl EconomicScheduler {
+    /// Formula 4.1 spend over a window, per node and per tenant
+    pub fn cost_summary(&self, since: i64, until: i64) -> CostSummary {
+        let store = &self.metrics_history;
+        let mut summary = CostSummary { since, until, ..Default::default() };
+
+        let window = (since, until);
+        add_series(&mut summary.nodes, store, NODE_COMPUTE_USD, window, |b, usd| b.compute_usd += usd);
+        add_series(&mut summary.nodes, store, NODE_DATA_USD, window, |b, usd| b.data_usd += usd);
+        add_series(&mut summary.nodes, store, NODE_IDLE_USD, window, |b, usd| b.idle_usd += usd);
+        add_series(&mut summary.tenants, store, TENANT_COMPUTE_USD, window, |b, usd| b.compute_usd += usd);
+        add_series(&mut summary.tenants, store, TENANT_DATA_USD, window, |b, usd| b.data_usd += usd);
+        add_series(&mut summary.tenants, store, TENANT_IDLE_USD, window, |b, usd| b.idle_usd += usd);
+
+        // Every cost is recorded against a node, so nodes add up to the total
+        for breakdown in summary.nodes.values() {
+            summary.total.add(breakdown);
+        }
+
+        let mut nodes = self.cluster_status();
+        nodes.extend(self.power.parked_nodes());
+        let pool_of: HashMap<String, String> = nodes.iter()
+            .filter_map(|node| Some((node.id.clone(), self.pools.pool_of(node)?.name.clone())))
+            .collect();
+        for (node_id, breakdown) in &summary.nodes {
+            if let Some(pool) = pool_of.get(node_id) {
+                summary.pools.entry(pool.clone()).or_default().add(breakdown);
+            }
+        }
+
+        for series in store.query(MARKET_RATE_USD, None, since, until, (until - since).max(1)) {
+            let count = series.points.len();
+            if count > 0 {
+                let rate = series.points.iter().map(|(_, value)| value).sum::<f64>() / count as f64;
+                summary.market_rates.insert(series.label, rate);
+            }
+        }
+        summary
+    }
+
+    /// Record a placed job's cost components once it finishes
+    pub(crate) fn observe_for_costs(&self, job_id: &str, status: &JobStatus, now: i64) {
+        if !status.is_terminal() {
+            return;
+        }
+        let Some(tenant) = self.placed_jobs.lock()
+            .ok()
+            .and_then(|placed| placed.get(job_id).map(|spec| spec.tenant.clone()))
+        else {
+            return;
+        };
+        let Some(state) = self.get_job_state(job_id) else {
+            return;
+        };
+        let Some(cost) = state.estimated_cost else {
+            return;
+        };
+        let nodes = if state.gang_nodes.is_empty() {
+            state.assigned_node.into_iter().collect()
+        } else {
+            state.gang_nodes
+        };
+        if nodes.is_empty() {
+            return;
+        }
+
+        let share = 1.0 / nodes.len() as f64;
+        for node_id in &nodes {
+            self.metrics_history.record_counter(NODE_COMPUTE_USD, node_id, cost.compute_usd * share, now);
+            self.metrics_history.record_counter(NODE_DATA_USD, node_id, cost.data_transfer_usd * share, now);
+        }
+        self.metrics_history.record_counter(TENANT_COMPUTE_USD, &tenant, cost.compute_usd, now);
+        self.metrics_history.record_counter(TENANT_DATA_USD, &tenant, cost.data_transfer_usd, now);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{unix_now, JobSpec, NodeInfo, SlaConstraints};
+
+    #[tokio::test]
+    async fn test_summary_splits_components_by_node_and_tenant() {
+        let scheduler = EconomicScheduler::new();
+        scheduler.register_node(NodeInfo {
+            id: "node-1".to_string(),
+            available_cpu: 8,
+            available_memory_gb: 32,
+            cost_per_hour: 2.0,
+            ..Default::default()
+        }).unwrap();
+
+        for (id, tenant) in [("job-1", "lab"), ("job-2", "acme")] {
+            scheduler.schedule(JobSpec {
+                id: id.to_string(),
+                tenant: tenant.to_string(),
+                estimated_duration_hours: Some(0.5),
+                sla: SlaConstraints { max_latency_ms: 1_000, ..Default::default() },
+                ..Default::default()
+            }).await.unwrap();
+        }
+        scheduler.update_job_state("job-1".to_string(), JobStatus::Completed, None).unwrap();
+        let now = unix_now();
+        scheduler.metrics_history().record_counter(NODE_IDLE_USD, "node-1", 0.25, now);
+
+        let summary = scheduler.cost_summary(now - 3600, now + 1);
+        let compute = scheduler.get_job_state("job-1").unwrap().estimated_cost.unwrap().compute_usd;
+        assert!(compute > 0.0);
+        assert!((summary.nodes["node-1"].compute_usd - compute).abs() < 1e-9);
+        assert_eq!(summary.nodes["node-1"].idle_usd, 0.25);
+        assert!((summary.tenants["lab"].compute_usd - compute).abs() < 1e-9);
+        // job-2 has not finished
+        assert!(!summary.tenants.contains_key("acme"));
+        assert!((summary.total.total_usd() - (compute + 0.25)).abs() < 1e-9);
+    }
+}
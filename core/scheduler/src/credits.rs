@@ -0,0 +1,313 @@
+//! Tenant credit balances
+//!
+//! Tenants pay for compute from a prepaid balance. Placing a job reserves
+//! its estimated TCO against its tenant's balance; when the job finishes
+//! the reservation is released and the provider charges of its runs (see
+//! `providers`) are deducted instead, so a failed run costs nothing. A
+//! tenant's available credit is its balance less open reservations.
+//!
+//! With enforcement on, a tenant without available credit cannot submit,
+//! and a placement whose estimate exceeds the available credit is refused.
+//! With it off, balances are still kept and go negative as tenants spend.
+//! A top-up may carry a reference (e.g. a payment id); each reference is
+//! credited once, so a retried top-up is harmless.
+//!
+//! Balances and credited references are written to the state store and
+//! included in snapshots, so a restart neither loses credit nor credits a
+//! replayed payment again. Reservations are rebuilt as jobs are placed.
+
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use tokio::sync::mpsc;
+
+use crate::error::SchedulerError;
+use crate::providers::LedgerEntry;
+use crate::store::PersistOp;
+use crate::{EconomicScheduler, JobSpec};
+
+/// A tenant's credit
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CreditBalance {
+    pub tenant: String,
+    /// Top-ups less charges
+    pub balance_usd: f64,
+    /// Estimates of the tenant's placed, unfinished jobs
+    pub reserved_usd: f64,
+    /// Every top-up so far
+    pub topped_up_usd: f64,
+    /// Every charge so far
+    pub charged_usd: f64,
+}
+
+impl CreditBalance {
+    /// What new work may still reserve
+    pub fn available_usd(&self) -> f64 {
+        self.balance_usd - self.reserved_usd
+    }
+}
+
+#[derive(Debug, Default)]
+struct CreditState {
+    /// By tenant; `reserved_usd` is filled in when read
+    accounts: HashMap<String, CreditBalance>,
+    /// Open reservations by job id: (tenant, estimate)
+    reserved: HashMap<String, (String, f64)>,
+    /// Top-up references already credited
+    references: HashSet<String>,
+}
+
+impl CreditState {
+    fn balance(&self, tenant: &str) -> CreditBalance {
+        let mut balance = self.accounts.get(tenant).cloned().unwrap_or_else(|| CreditBalance {
+            tenant: tenant.to_string(),
+            ..Default::default()
+        });
+        balance.reserved_usd = self.reserved.values()
+            .filter(|(owner, _)| owner == tenant)
+            .map(|(_, usd)| usd)
+            .sum();
+        balance
+    }
+}
+
+/// Tenant balances and open reservations, shared by scheduler clones
+#[derive(Debug, Clone, Default)]
+pub struct TenantCredits {
+    enforced: bool,
+    state: Arc<Mutex<CreditState>>,
+    /// Write-behind queue of the attached state store
+    persist: Option<mpsc::UnboundedSender<PersistOp>>,
+}
+
+impl TenantCredits {
+    /// Balances that block work when `enforced`, or are only kept
+    pub fn new(enforced: bool) -> Self {
+        Self { enforced, state: Arc::default(), persist: None }
+    }
+
+    /// Every account, without reservations
+    pub fn accounts(&self) -> Vec<CreditBalance> {
+        self.state.lock().map(|state| state.accounts.values().cloned().collect()).unwrap_or_default()
+    }
+
+    /// Top-up references already credited
+    pub fn references(&self) -> Vec<String> {
+        self.state.lock().map(|state| state.references.iter().cloned().collect()).unwrap_or_default()
+    }
+
+    /// Merge accounts and references: accounts replace those of the same
+    /// tenant, references are added
+    pub fn restore(&self, accounts: Vec<CreditBalance>, references: Vec<String>) {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        for reference in references {
+            self.persist(PersistOp::CreditReference(reference.clone()));
+            state.references.insert(reference);
+        }
+        for mut account in accounts {
+            account.reserved_usd = 0.0;
+            self.persist(PersistOp::Credit(account.clone()));
+            state.accounts.insert(account.tenant.clone(), account);
+        }
+    }
+
+    /// Write later changes behind through `persist`
+    pub(crate) fn attach(&mut self, persist: mpsc::UnboundedSender<PersistOp>) {
+        self.persist = Some(persist);
+    }
+
+    fn persist(&self, op: PersistOp) {
+        if let Some(persist) = &self.persist {
+            let _ = persist.send(op);
+        }
+    }
+
+    pub fn is_enforced(&self) -> bool {
+        self.enforced
+    }
+
+    pub fn balance(&self, tenant: &str) -> CreditBalance {
+        self.state.lock()
+            .map(|state| state.balance(tenant))
+            .unwrap_or_else(|_| CreditBalance { tenant: tenant.to_string(), ..Default::default() })
+    }
+
+    /// Credit `amount_usd` to `tenant`; a reference already credited is
+    /// not credited again
+    pub fn top_up(&self, tenant: &str, amount_usd: f64, reference: Option<&str>) -> Result<CreditBalance, SchedulerError> {
+        if !amount_usd.is_finite() || amount_usd <= 0.0 {
+            return Err(SchedulerError::invalid_spec(format!("Top-up of {} must be a positive amount", amount_usd)));
+        }
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(reference) = reference.filter(|reference| !reference.is_empty()) {
+            if !state.references.insert(reference.to_string()) {
+                tracing::info!("Top-up {} for tenant {} was already credited", reference, tenant);
+                return Ok(state.balance(tenant));
+            }
+            self.persist(PersistOp::CreditReference(reference.to_string()));
+        }
+
+        let account = state.accounts.entry(tenant.to_string()).or_insert_with(|| CreditBalance {
+            tenant: tenant.to_string(),
+            ..Default::default()
+        });
+        account.balance_usd += amount_usd;
+        account.topped_up_usd += amount_usd;
+        tracing::info!("Tenant {} topped up ${:.4} (balance ${:.4})", tenant, amount_usd, account.balance_usd);
+        self.persist(PersistOp::Credit(account.clone()));
+        Ok(state.balance(tenant))
+    }
+
+    /// Reserve `estimate_usd` for `job_id`, replacing an earlier
+    /// reservation of the same job
+    fn reserve(&self, tenant: &str, job_id: &str, estimate_usd: f64) -> Result<(), SchedulerError> {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        let previous = state.reserved.remove(job_id);
+        let available_usd = state.balance(tenant).available_usd();
+        if self.enforced && estimate_usd > available_usd {
+            if let Some(previous) = previous {
+                state.reserved.insert(job_id.to_string(), previous);
+            }
+            return Err(SchedulerError::InsufficientCredit {
+                tenant: tenant.to_string(),
+                available_usd,
+                required_usd: estimate_usd,
+            });
+        }
+        state.reserved.insert(job_id.to_string(), (tenant.to_string(), estimate_usd));
+        Ok(())
+    }
+
+    /// Release the reservation of a finished job and deduct what its runs
+    /// were charged
+    fn settle(&self, job_id: &str, entries: &[LedgerEntry]) {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        state.reserved.remove(job_id);
+        for entry in entries {
+            let account = state.accounts.entry(entry.tenant.clone()).or_insert_with(|| CreditBalance {
+                tenant: entry.tenant.clone(),
+                ..Default::default()
+            });
+            account.balance_usd -= entry.charged_usd;
+            account.charged_usd += entry.charged_usd;
+            self.persist(PersistOp::Credit(account.clone()));
+        }
+    }
+}
+
+impl EconomicScheduler {
+    /// Replace the credit policy and balances (call before `attach_store`,
+    /// which loads balances into it)
+    pub fn set_tenant_credits(&mut self, credits: TenantCredits) {
+        self.credits = credits;
+    }
+
+    pub fn tenant_credits(&self) -> &TenantCredits {
+        &self.credits
+    }
+
+    /// Refuse new work from a tenant that is out of credit
+    pub fn check_credit(&self, job: &JobSpec) -> Result<(), SchedulerError> {
+        if !self.credits.enforced {
+            return Ok(());
+        }
+        let available_usd = self.credits.balance(&job.tenant).available_usd();
+        if available_usd <= 0.0 {
+            return Err(SchedulerError::InsufficientCredit { tenant: job.tenant.clone(), available_usd, required_usd: 0.0 });
+        }
+        Ok(())
+    }
+
+    /// Reserve a placed job's estimated cost against its tenant's credit
+    pub(crate) fn reserve_credit(&self, job: &JobSpec, estimate_usd: f64) -> Result<(), SchedulerError> {
+        self.credits.reserve(&job.tenant, &job.id, estimate_usd)
+    }
+
+    /// Settle a finished job's credit reservation against its charges
+    pub(crate) fn settle_credit(&self, job_id: &str, entries: &[LedgerEntry]) {
+        self.credits.settle(job_id, entries);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::classify;
+    use crate::{JobStatus, NodeInfo, SlaConstraints};
+
+    fn job(id: &str) -> JobSpec {
+        JobSpec {
+            id: id.to_string(),
+            tenant: "lab".to_string(),
+            sla: SlaConstraints { max_latency_ms: 1_000, ..Default::default() },
+            job_data: id.as_bytes().to_vec(),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_credit_is_reserved_at_placement_and_settled_at_completion() {
+        let mut scheduler = EconomicScheduler::new();
+        scheduler.set_tenant_credits(TenantCredits::new(true));
+        scheduler.register_node(NodeInfo {
+            id: "node-1".to_string(),
+            available_cpu: 16,
+            available_memory_gb: 64,
+            location: "vps-1".to_string(),
+            cost_per_hour: 0.5,
+            provider: "acme".to_string(),
+            ..Default::default()
+        }).unwrap();
+
+        let err = scheduler.check_credit(&job("job-1")).unwrap_err();
+        assert_eq!(err.reason(), "INSUFFICIENT_CREDIT");
+
+        let credits = scheduler.tenant_credits();
+        credits.top_up("lab", 10.0, Some("pay-1")).unwrap();
+        assert_eq!(credits.top_up("lab", 10.0, Some("pay-1")).unwrap().balance_usd, 10.0, "credited once");
+        assert!(credits.top_up("lab", -1.0, None).is_err());
+        scheduler.check_credit(&job("job-1")).unwrap();
+
+        let placement = scheduler.schedule(job("job-1")).await.unwrap();
+        let reserved = credits.balance("lab").reserved_usd;
+        assert!(reserved > 0.0 && (reserved - placement.estimated_cost.total_usd).abs() < 1e-9);
+
+        // An estimate over what is left is refused
+        let mut costly = job("job-2");
+        costly.estimated_duration_hours = Some(1_000.0);
+        let err = scheduler.schedule(costly).await.unwrap_err();
+        assert_eq!(classify(&err).map(SchedulerError::reason), Some("INSUFFICIENT_CREDIT"));
+        assert_eq!(scheduler.get_job_state("job-2").unwrap().status, JobStatus::Failed);
+
+        // Two hours at $0.50/h
+        scheduler.observe_for_settlement("job-1", &JobStatus::Running, 0);
+        scheduler.observe_for_settlement("job-1", &JobStatus::Completed, 7200);
+        let balance = credits.balance("lab");
+        assert_eq!(balance.reserved_usd, 0.0);
+        assert!((balance.balance_usd - 9.0).abs() < 1e-9);
+        assert!((balance.charged_usd - 1.0).abs() < 1e-9);
+    }
+
+    #[tokio::test]
+    async fn test_balances_and_references_survive_a_restart() {
+        let store: Arc<dyn crate::store::StateStore> = Arc::new(crate::store::MemoryStore::new());
+        let mut before = EconomicScheduler::new();
+        before.attach_store(store.clone()).await.unwrap();
+        before.tenant_credits().top_up("lab", 10.0, Some("pay-1")).unwrap();
+        before.flush_state().await.unwrap();
+
+        // A replayed payment is not credited again after the restart
+        let mut after = EconomicScheduler::new();
+        after.set_tenant_credits(TenantCredits::new(true));
+        after.attach_store(store).await.unwrap();
+        let credits = after.tenant_credits();
+        assert_eq!(credits.balance("lab").balance_usd, 10.0);
+        assert_eq!(credits.top_up("lab", 10.0, Some("pay-1")).unwrap().balance_usd, 10.0);
+
+        // Snapshots carry them too
+        let fresh = EconomicScheduler::new();
+        fresh.restore(after.snapshot().await.unwrap()).await.unwrap();
+        assert_eq!(fresh.tenant_credits().balance("lab").topped_up_usd, 10.0);
+        assert_eq!(fresh.tenant_credits().top_up("lab", 10.0, Some("pay-1")).unwrap().balance_usd, 10.0);
+    }
+}
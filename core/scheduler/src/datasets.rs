@@ -0,0 +1,209 @@
+//! Dataset registry for data-locality-aware placement
+//!
+//! Workers advertise the named datasets they hold locally with every
+//! resource report. Placement uses the registry to compute how many GB a
+//! node would have to pull before the job can start, which feeds C_data.
+//!
+//! Workers also report a content digest of each dataset. A node whose copy
+//! changes sets the dataset's latest version; nodes still holding another
+//! version are stale replicas: they no longer count as holding the dataset
+//! but re-stage it by delta sync, moving only the changed chunks, so their
+//! C_data is the dataset size scaled by the fraction delta syncs of that
+//! dataset have had to fetch.
+
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+
+use crate::EconomicScheduler;
+
+/// Dataset known to the cluster
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DatasetInfo {
+    pub name: String,
+    /// Size in GB (largest size advertised by any replica)
+    pub size_gb: f64,
+    /// Nodes holding a local copy of the latest version
+    pub replicas: HashSet<String>,
+    /// Content digest of the latest version (None until a worker reports one)
+    #[serde(default)]
+    pub digest: Option<String>,
+    /// Nodes holding an older version
+    #[serde(default)]
+    pub stale_replicas: HashSet<String>,
+    /// Digest of each node's copy
+    #[serde(default)]
+    versions: HashMap<String, String>,
+}
+
+impl DatasetInfo {
+    /// Sort the nodes holding a copy into current and stale replicas;
+    /// copies without a digest are taken to be current
+    fn classify(&mut self) {
+        let holders: Vec<String> = self.replicas.drain().chain(self.stale_replicas.drain()).collect();
+        for node_id in holders {
+            match (self.versions.get(&node_id), &self.digest) {
+                (Some(version), Some(latest)) if version != latest => self.stale_replicas.insert(node_id),
+                _ => self.replicas.insert(node_id),
+            };
+        }
+    }
+}
+
+/// Thread-safe registry of datasets and their replica locations
+#[derive(Debug, Clone, Default)]
+pub struct DatasetRegistry {
+    datasets: Arc<Mutex<HashMap<String, DatasetInfo>>>,
+}
+
+impl DatasetRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Replace the set of datasets held by a node with its latest advertisement
+    pub fn sync_node(&self, node_id: &str, local: &[(String, f64)]) {
+        let local: Vec<_> = local.iter().map(|(name, size_gb)| (name.clone(), *size_gb, None)).collect();
+        self.sync_node_versions(node_id, &local);
+    }
+
+    /// `sync_node` with the content digest of each copy, where known
+    pub fn sync_node_versions(&self, node_id: &str, local: &[(String, f64, Option<String>)]) {
+        let Ok(mut datasets) = self.datasets.lock() else {
+            return;
+        };
+
+        for info in datasets.values_mut() {
+            if !local.iter().any(|(name, _, _)| name == &info.name) {
+                info.versions.remove(node_id);
+            }
+            info.replicas.remove(node_id);
+            info.stale_replicas.remove(node_id);
+        }
+
+        for (name, size_gb, digest) in local {
+            let info = datasets.entry(name.clone()).or_insert_with(|| DatasetInfo {
+                name: name.clone(),
+                size_gb: 0.0,
+                replicas: HashSet::new(),
+                digest: None,
+                stale_replicas: HashSet::new(),
+                versions: HashMap::new(),
+            });
+            info.size_gb = info.size_gb.max(*size_gb);
+            info.replicas.insert(node_id.to_string());
+
+            if let Some(digest) = digest {
+                let previous = info.versions.insert(node_id.to_string(), digest.clone());
+                // The first version seen, or a copy that changed in place, is the latest
+                let changed = previous.is_some_and(|previous| &previous != digest);
+                if info.digest.is_none() || changed {
+                    info.digest = Some(digest.clone());
+                }
+            }
+            info.classify();
+        }
+    }
+
+    /// GB that must be transferred to `node_id` before a job using `names` can run
+    ///
+    /// Datasets nobody has advertised count as zero; they are fetched from
+    /// outside the cluster and cost the same on every node.
+    pub fn transfer_gb(&self, node_id: &str, names: &[String]) -> f64 {
+        let Ok(datasets) = self.datasets.lock() else {
+            return 0.0;
+        };
+
+        names.iter()
+            .filter_map(|name| datasets.get(name))
+            .filter(|info| !info.replicas.contains(node_id))
+            .map(|info| info.size_gb)
+            .sum()
+    }
+
+    /// Datasets in `names` that `node_id` does not hold locally
+    pub fn missing(&self, node_id: &str, names: &[String]) -> Vec<DatasetInfo> {
+        let Ok(datasets) = self.datasets.lock() else {
+            return Vec::new();
+        };
+
+        names.iter()
+            .filter_map(|name| datasets.get(name))
+            .filter(|info| !info.replicas.contains(node_id))
+            .cloned()
+            .collect()
+    }
+
+    /// Look up a dataset
+    pub fn get(&self, name: &str) -> Option<DatasetInfo> {
+        self.datasets.lock()
+            .ok()
+            .and_then(|datasets| datasets.get(name).cloned())
+    }
+
+    /// All known datasets
+    pub fn list(&self) -> Vec<DatasetInfo> {
+        self.datasets.lock()
+            .map(|datasets| datasets.values().cloned().collect())
+            .unwrap_or_default()
+    }
+}
+
+impl EconomicScheduler {
+    /// GB of `dataset` that staging it onto `node_id` moves: all of it,
+    /// or for a stale copy the share delta syncs of it have fetched
+    pub(crate) fn staged_gb(&self, node_id: &str, dataset: &DatasetInfo) -> f64 {
+        if !dataset.stale_replicas.contains(node_id) {
+            return dataset.size_gb;
+        }
+        dataset.size_gb * self.transfers.delta_fraction(&dataset.name).unwrap_or(1.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_transfer_gb_counts_only_missing_datasets() {
+        let registry = DatasetRegistry::new();
+        registry.sync_node("node-a", &[("imagenet".to_string(), 150.0), ("coco".to_string(), 20.0)]);
+        registry.sync_node("node-b", &[("coco".to_string(), 20.0)]);
+
+        let wanted = vec!["imagenet".to_string(), "coco".to_string()];
+        assert_eq!(registry.transfer_gb("node-a", &wanted), 0.0);
+        assert_eq!(registry.transfer_gb("node-b", &wanted), 150.0);
+        assert_eq!(registry.transfer_gb("node-c", &wanted), 170.0);
+
+        // node-a dropped imagenet
+        registry.sync_node("node-a", &[("coco".to_string(), 20.0)]);
+        assert_eq!(registry.transfer_gb("node-a", &wanted), 150.0);
+        assert!(registry.get("imagenet").unwrap().replicas.is_empty());
+    }
+
+    #[test]
+    fn test_changed_copy_makes_other_replicas_stale() {
+        let registry = DatasetRegistry::new();
+        let version = |digest: &str| vec![("ckpt".to_string(), 10.0, Some(digest.to_string()))];
+        registry.sync_node("legacy", &[("ckpt".to_string(), 10.0)]);
+        registry.sync_node_versions("node-a", &version("v1"));
+        registry.sync_node_versions("node-b", &version("v1"));
+        assert_eq!(registry.get("ckpt").unwrap().replicas.len(), 3);
+
+        // node-a's copy changed: node-b now holds an older version
+        registry.sync_node_versions("node-a", &version("v2"));
+        let info = registry.get("ckpt").unwrap();
+        assert_eq!(info.digest.as_deref(), Some("v2"));
+        assert!(info.stale_replicas.contains("node-b"));
+        assert!(info.replicas.contains("node-a") && info.replicas.contains("legacy"));
+        assert_eq!(registry.transfer_gb("node-b", &["ckpt".to_string()]), 10.0);
+
+        // A node joining with the old version is stale as well, until it syncs
+        registry.sync_node_versions("node-c", &version("v1"));
+        assert!(registry.get("ckpt").unwrap().stale_replicas.contains("node-c"));
+        registry.sync_node_versions("node-b", &version("v2"));
+        let info = registry.get("ckpt").unwrap();
+        assert!(info.replicas.contains("node-b"));
+        assert_eq!(info.digest.as_deref(), Some("v2"));
+    }
+}
@@ -0,0 +1,72 @@
+//! Deterministic scheduling for reproducible runs
+//!
+//! Placement already breaks cost ties by node id, but the node registry and
+//! the placed-job table are hash maps: node listings, the jobs found on a
+//! node and the rebalancer's input come out in a different order from one
+//! process to the next. In deterministic mode those lists are sorted by id
+//! and the rebalancer uses an optimizer seeded with the run's seed, so the
+//! same workload, seed and clock give the same placements, migrations and
+//! events. Simulations and CI tests turn it on; a production scheduler has
+//! no use for the extra sorting.
+
+use std::sync::Arc;
+use tgp_optimizer::Optimizer;
+
+use crate::EconomicScheduler;
+
+impl EconomicScheduler {
+    /// Order node and job lists by id and seed the consolidation optimizer
+    /// (replacing one set with `set_optimizer`)
+    pub fn set_deterministic(&mut self, seed: u64) {
+        self.deterministic = true;
+        self.optimizer = Arc::new(Optimizer::seeded(seed));
+    }
+
+    pub fn is_deterministic(&self) -> bool {
+        self.deterministic
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{JobSpec, JobStatus, ResourceRequirements, SlaConstraints};
+    use crate::test_support::node;
+
+    fn job(id: &str) -> JobSpec {
+        JobSpec {
+            id: id.to_string(),
+            resources: ResourceRequirements { cpu_cores: 1, memory_gb: 1, ..Default::default() },
+            sla: SlaConstraints { max_latency_ms: 10_000, ..Default::default() },
+            movable: true,
+            ..Default::default()
+        }
+    }
+
+    /// Run the same workload against nodes registered in `order`
+    async fn run(order: &[&str]) -> (Vec<String>, Vec<(String, String)>) {
+        let mut scheduler = EconomicScheduler::new();
+        scheduler.set_deterministic(7);
+        for id in order {
+            scheduler.register_node(node(id, 0.5)).unwrap();
+        }
+
+        let mut placements = Vec::new();
+        for id in ["job-1", "job-2", "job-3"] {
+            let placement = scheduler.schedule(job(id)).await.unwrap();
+            scheduler.update_job_state(id.to_string(), JobStatus::Running, Some(placement.node_id.clone())).unwrap();
+            placements.push((id.to_string(), placement.node_id));
+        }
+
+        let listed = scheduler.cluster_status().into_iter().map(|node| node.id).collect();
+        (listed, placements)
+    }
+
+    #[tokio::test]
+    async fn test_runs_do_not_depend_on_registration_order() {
+        let (listed, placements) = run(&["node-c", "node-a", "node-b"]).await;
+        assert_eq!(listed, ["node-a", "node-b", "node-c"]);
+        assert_eq!((listed, placements), run(&["node-b", "node-c", "node-a"]).await);
+        assert!(!EconomicScheduler::new().is_deterministic());
+    }
+}
@@ -0,0 +1,321 @@
+//! Device-level allocation on nodes
+//!
+//! Node capacity says how many GPUs a node has, not which ones a job may
+//! use, and nothing tracks host ports. Placed jobs are given concrete GPU
+//! indices and host ports for the container ports they publish, and those
+//! are held until the job ends or is placed again, so concurrent jobs on a
+//! node never share a device or collide on a published port. Jobs that ask
+//! for CPU pinning get dedicated cores the same way, taken from one NUMA
+//! node (the one next to their GPUs, if any) whenever one has enough free.
+
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+
+use crate::error::SchedulerError;
+use crate::{EconomicScheduler, JobSpec, NodeInfo};
+
+/// Host ports handed out to published container ports (the Kubernetes
+/// NodePort range)
+pub const DEFAULT_PORT_RANGE: (u16, u16) = (30000, 32767);
+
+/// A container port published on a host port
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PortBinding {
+    pub container_port: u16,
+    pub host_port: u16,
+}
+
+/// Devices one job holds on one node
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DeviceAssignment {
+    pub gpu_indices: Vec<u32>,
+    pub ports: Vec<PortBinding>,
+    /// Dedicated CPU cores (empty: not pinned)
+    #[serde(default)]
+    pub cpu_set: Vec<u32>,
+    /// NUMA node all of `cpu_set` belongs to
+    #[serde(default)]
+    pub numa_node: Option<u32>,
+}
+
+#[derive(Debug, Default)]
+struct NodeDevices {
+    /// GPU index -> job
+    gpus: BTreeMap<u32, String>,
+    /// Host port -> job
+    ports: BTreeMap<u16, String>,
+    /// Pinned CPU core -> job
+    cpus: BTreeMap<u32, String>,
+}
+
+#[derive(Debug, Default)]
+struct AllocatorState {
+    nodes: HashMap<String, NodeDevices>,
+    /// Job -> node -> what it holds there
+    jobs: HashMap<String, HashMap<String, DeviceAssignment>>,
+}
+
+/// GPU index and host port assignments of every node
+#[derive(Debug, Clone)]
+pub struct DeviceAllocator {
+    port_range: (u16, u16),
+    state: Arc<Mutex<AllocatorState>>,
+}
+
+impl Default for DeviceAllocator {
+    fn default() -> Self {
+        Self::new(DEFAULT_PORT_RANGE)
+    }
+}
+
+impl DeviceAllocator {
+    pub fn new(port_range: (u16, u16)) -> Self {
+        Self { port_range, state: Arc::default() }
+    }
+
+    /// Whether `node` has room for the job
+    pub fn fits(&self, node: &NodeInfo, job: &JobSpec) -> bool {
+        let Ok(state) = self.state.lock() else {
+            return false;
+        };
+        let (gpus_used, ports_used, cpus_used) = state.nodes.get(&node.id)
+            .map_or((0, 0, 0), |held| (held.gpus.len(), held.ports.len(), held.cpus.len()));
+        let port_total = (self.port_range.1 as usize + 1).saturating_sub(self.port_range.0 as usize);
+        gpus_used + job.resources.gpu_count as usize <= node.available_gpu as usize
+            && ports_used + job.ports.len() <= port_total
+            && cpus_used + pinned_cores(job) <= node.available_cpu as usize
+    }
+
+    /// GPU indices of `node_id` no job holds
+    pub fn free_gpus(&self, node_id: &str, gpu_total: u32) -> Vec<u32> {
+        let Ok(state) = self.state.lock() else {
+            return Vec::new();
+        };
+        (0..gpu_total)
+            .filter(|index| state.nodes.get(node_id).map_or(true, |node| !node.gpus.contains_key(index)))
+            .collect()
+    }
+
+    /// Assign free GPU indices, host ports and (if the job asks for
+    /// pinning) CPU cores to `job` on `node`, releasing whatever it held
+    /// there before; with a GPU topology, the best connected free GPUs are
+    /// chosen
+    pub fn allocate(&self, node: &NodeInfo, job: &JobSpec) -> Result<DeviceAssignment, SchedulerError> {
+        let mut guard = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        let state = &mut *guard;
+        let held = state.nodes.entry(node.id.clone()).or_default();
+        held.gpus.retain(|_, holder| holder != &job.id);
+        held.ports.retain(|_, holder| holder != &job.id);
+        held.cpus.retain(|_, holder| holder != &job.id);
+
+        let free: Vec<u32> = (0..node.available_gpu)
+            .filter(|index| !held.gpus.contains_key(index))
+            .collect();
+        let gpu_count = job.resources.gpu_count as usize;
+        let gpu_indices = match &node.gpu_topology {
+            Some(topology) if gpu_count > 1 => topology.best_set(&free, gpu_count).unwrap_or_default(),
+            _ => free.into_iter().take(gpu_count).collect(),
+        };
+        let host_ports: Vec<u16> = (self.port_range.0..=self.port_range.1)
+            .filter(|port| !held.ports.contains_key(port))
+            .take(job.ports.len())
+            .collect();
+        let (cpu_set, numa_node) = pick_cores(node, held, &gpu_indices, pinned_cores(job));
+        if gpu_indices.len() < gpu_count
+            || host_ports.len() < job.ports.len()
+            || cpu_set.len() < pinned_cores(job)
+        {
+            return Err(SchedulerError::NoCapacity { job_id: job.id.clone() });
+        }
+
+        for index in &gpu_indices {
+            held.gpus.insert(*index, job.id.clone());
+        }
+        for port in &host_ports {
+            held.ports.insert(*port, job.id.clone());
+        }
+        for cpu in &cpu_set {
+            held.cpus.insert(*cpu, job.id.clone());
+        }
+        let assignment = DeviceAssignment {
+            gpu_indices,
+            ports: job.ports.iter().zip(host_ports)
+                .map(|(&container_port, host_port)| PortBinding { container_port, host_port })
+                .collect(),
+            cpu_set,
+            numa_node,
+        };
+        state.jobs.entry(job.id.clone()).or_default().insert(node.id.clone(), assignment.clone());
+        Ok(assignment)
+    }
+
+    /// Free everything `job_id` holds on any node
+    pub fn release(&self, job_id: &str) {
+        let Ok(mut guard) = self.state.lock() else {
+            return;
+        };
+        let state = &mut *guard;
+        let Some(held) = state.jobs.remove(job_id) else {
+            return;
+        };
+        for node_id in held.keys() {
+            if let Some(node) = state.nodes.get_mut(node_id) {
+                node.gpus.retain(|_, holder| holder != job_id);
+                node.ports.retain(|_, holder| holder != job_id);
+                node.cpus.retain(|_, holder| holder != job_id);
+            }
+        }
+    }
+
+    /// What `job_id` holds on `node_id`
+    pub fn assignment(&self, job_id: &str, node_id: &str) -> Option<DeviceAssignment> {
+        self.state.lock().ok()?.jobs.get(job_id)?.get(node_id).cloned()
+    }
+}
+
+/// Cores a job needs pinned (0: not pinned)
+fn pinned_cores(job: &JobSpec) -> usize {
+    if job.pin_cpus {
+        job.resources.cpu_cores.max(1) as usize
+    } else {
+        0
+    }
+}
+
+/// Lowest free cores of the NUMA node next to the job's first GPU if it
+/// has `count` free, else of the fullest NUMA node that does; spread over
+/// the whole node when none does
+fn pick_cores(node: &NodeInfo, held: &NodeDevices, gpu_indices: &[u32], count: usize) -> (Vec<u32>, Option<u32>) {
+    if count == 0 || held.cpus.len() + count > node.available_cpu as usize {
+        return (Vec::new(), None);
+    }
+    let free_in = |cpus: &[u32]| -> Vec<u32> {
+        let mut free: Vec<u32> = cpus.iter().copied().filter(|cpu| !held.cpus.contains_key(cpu)).collect();
+        free.sort_unstable();
+        free
+    };
+    if node.numa_cpus.is_empty() {
+        let all: Vec<u32> = (0..node.capacity_cpu.max(node.available_cpu)).collect();
+        return (free_in(&all).into_iter().take(count).collect(), None);
+    }
+
+    let gpu_numa = gpu_indices.first()
+        .and_then(|&gpu| node.gpu_topology.as_ref()?.numa_nodes.get(gpu as usize).copied())
+        .and_then(|numa| u32::try_from(numa).ok());
+    let fitting: Vec<(u32, Vec<u32>)> = node.numa_cpus.iter()
+        .enumerate()
+        .map(|(numa, cpus)| (numa as u32, free_in(cpus)))
+        .filter(|(_, free)| free.len() >= count)
+        .collect();
+    let chosen = fitting.iter()
+        .find(|(numa, _)| Some(*numa) == gpu_numa)
+        .or_else(|| fitting.iter().min_by_key(|(numa, free)| (free.len(), *numa)));
+    match chosen {
+        Some((numa, free)) => (free[..count].to_vec(), Some(*numa)),
+        None => {
+            let all: Vec<u32> = node.numa_cpus.concat();
+            (free_in(&all).into_iter().take(count).collect(), None)
+        }
+    }
+}
+
+impl EconomicScheduler {
+    /// Replace the host port range (call before cloning the scheduler)
+    pub fn set_port_range(&mut self, start: u16, end: u16) {
+        self.devices = DeviceAllocator::new((start, end));
+    }
+
+    pub fn device_allocator(&self) -> &DeviceAllocator {
+        &self.devices
+    }
+
+    /// Allocate devices for `job` on each of `node_ids`; all or nothing
+    pub(crate) fn allocate_devices(&self, job: &JobSpec, node_ids: &[String]) -> Result<(), SchedulerError> {
+        self.devices.release(&job.id);
+        if job.resources.gpu_count == 0 && job.ports.is_empty() && !job.pin_cpus {
+            return Ok(());
+        }
+        for node_id in node_ids {
+            let node = self.available_nodes.lock()
+                .ok()
+                .and_then(|nodes| nodes.get(node_id).cloned())
+                .unwrap_or_else(|| NodeInfo { id: node_id.clone(), ..Default::default() });
+            if let Err(e) = self.devices.allocate(&node, job) {
+                self.devices.release(&job.id);
+                return Err(e);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ResourceRequirements;
+
+    fn job(id: &str, gpu_count: u32, ports: Vec<u16>) -> JobSpec {
+        JobSpec {
+            id: id.to_string(),
+            resources: ResourceRequirements { gpu_count, ..Default::default() },
+            ports,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_concurrent_jobs_get_distinct_devices() {
+        let devices = DeviceAllocator::new((8000, 8002));
+        let node_1 = NodeInfo { id: "node-1".to_string(), available_gpu: 4, ..Default::default() };
+        let a = devices.allocate(&node_1, &job("a", 2, vec![80])).unwrap();
+        let b = devices.allocate(&node_1, &job("b", 2, vec![80, 443])).unwrap();
+        assert_eq!(a.gpu_indices, vec![0, 1]);
+        assert_eq!(b.gpu_indices, vec![2, 3]);
+        assert_eq!(a.ports, vec![PortBinding { container_port: 80, host_port: 8000 }]);
+        assert_eq!(b.ports.iter().map(|p| p.host_port).collect::<Vec<_>>(), vec![8001, 8002]);
+
+        // Node is full until a job releases its devices
+        assert!(!devices.fits(&node_1, &job("c", 1, vec![])));
+        assert!(devices.allocate(&node_1, &job("c", 0, vec![22])).is_err());
+        assert!(devices.fits(&NodeInfo { id: "node-2".to_string(), ..node_1.clone() }, &job("c", 1, vec![22])));
+
+        devices.release("a");
+        let c = devices.allocate(&node_1, &job("c", 1, vec![22])).unwrap();
+        assert_eq!(c.gpu_indices, vec![0]);
+        assert_eq!(c.ports[0].host_port, 8000);
+        assert_eq!(devices.assignment("b", "node-1"), Some(b));
+        assert_eq!(devices.assignment("a", "node-1"), None);
+    }
+
+    #[test]
+    fn test_pinned_cores_stay_on_the_gpu_numa_node() {
+        let devices = DeviceAllocator::default();
+        let node = NodeInfo {
+            id: "node-1".to_string(),
+            available_cpu: 8,
+            available_gpu: 2,
+            gpu_topology: Some(crate::topology::GpuTopology { numa_nodes: vec![0, 1], links: Vec::new() }),
+            numa_cpus: vec![vec![0, 1, 2, 3], vec![4, 5, 6, 7]],
+            ..Default::default()
+        };
+        let pinned = |id: &str, cpu_cores: u32, gpu_count: u32| JobSpec {
+            pin_cpus: true,
+            resources: ResourceRequirements { cpu_cores, ..job(id, gpu_count, vec![]).resources },
+            ..job(id, gpu_count, vec![])
+        };
+
+        // Without GPUs the fullest NUMA node that fits is used
+        let a = devices.allocate(&node, &pinned("a", 3, 0)).unwrap();
+        assert_eq!((a.cpu_set, a.numa_node), (vec![0, 1, 2], Some(0)));
+        // GPU 0 sits on NUMA node 0, but only one core is left there
+        let b = devices.allocate(&node, &pinned("b", 2, 1)).unwrap();
+        assert_eq!((b.cpu_set, b.numa_node), (vec![4, 5], Some(1)));
+        // Too few free cores in any one NUMA node: spread
+        let c = devices.allocate(&node, &pinned("c", 3, 0)).unwrap();
+        assert_eq!((c.cpu_set, c.numa_node), (vec![3, 6, 7], None));
+        // Only the 8 allocatable cores may be pinned
+        assert!(!devices.fits(&node, &pinned("d", 1, 0)));
+        assert!(devices.fits(&node, &job("d", 0, vec![])));
+    }
+}
@@ -0,0 +1,175 @@
+//! Per-node efficiency
+//!
+//! Compares what a node costs with what it does, so operators can see which
+//! VPS contracts are worth keeping. Paid hours run from the node's first
+//! registration with this scheduler (at most the metrics retention ago),
+//! less the time it spent dormant. Utilized hours weigh that time by the
+//! measured CPU utilization in the metrics history. The paid hours at the
+//! node's current price, divided by the jobs it completed, give its cost
+//! per completed job.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use crate::timeseries::NODE_CPU_UTILIZATION;
+use crate::{EconomicScheduler, JobStatus};
+
+/// Jobs completed, label: node id
+pub const NODE_COMPLETED_JOBS: &str = "node_completed_jobs";
+
+/// Paid versus utilized time of one node
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct NodeEfficiency {
+    pub node_id: String,
+    pub paid_hours: f64,
+    pub utilized_hours: f64,
+    pub completed_jobs: u64,
+    /// Paid hours at the current hourly price
+    pub cost_usd: f64,
+}
+
+impl NodeEfficiency {
+    /// Share of paid time spent working (0-1)
+    pub fn utilization(&self) -> f64 {
+        if self.paid_hours > 0.0 {
+            (self.utilized_hours / self.paid_hours).min(1.0)
+        } else {
+            0.0
+        }
+    }
+
+    /// None until the node completed a job
+    pub fn cost_per_completed_job_usd(&self) -> Option<f64> {
+        (self.completed_jobs > 0).then(|| self.cost_usd / self.completed_jobs as f64)
+    }
+}
+
+/// First registration time of every node, shared by scheduler clones
+#[derive(Debug, Clone, Default)]
+pub struct NodeTenure {
+    since: Arc<Mutex<HashMap<String, i64>>>,
+}
+
+impl NodeTenure {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record a registration; later ones keep the first time
+    pub(crate) fn registered(&self, node_id: &str, at: i64) {
+        if let Ok(mut since) = self.since.lock() {
+            since.entry(node_id.to_string()).or_insert(at);
+        }
+    }
+
+    pub fn since(&self, node_id: &str) -> Option<i64> {
+        self.since.lock().ok()?.get(node_id).copied()
+    }
+}
+
+impl EconomicScheduler {
+    /// Efficiency of every registered node, active or dormant, as of `now`
+    pub fn node_efficiency(&self, now: i64) -> Vec<NodeEfficiency> {
+        let store = &self.metrics_history;
+        let config = store.config();
+        let step = config.resolution_secs.max(1);
+        let dormant: HashMap<String, u64> = self.power.report()
+            .into_iter()
+            .map(|energy| (energy.node_id, energy.dormant_secs))
+            .collect();
+
+        let mut nodes = self.cluster_status();
+        nodes.extend(self.power.parked_nodes());
+        let mut report: Vec<NodeEfficiency> = nodes.into_iter()
+            .map(|node| {
+                let since = self.tenure.since(&node.id)
+                    .unwrap_or(now)
+                    .max(now - config.retention_secs);
+                let dormant_secs = dormant.get(&node.id).copied().unwrap_or(0) as i64;
+                let paid_hours = ((now - since) - dormant_secs).max(0) as f64 / 3600.0;
+
+                let utilized_hours = store.query(NODE_CPU_UTILIZATION, Some(&node.id), since, now + 1, step)
+                    .iter()
+                    .flat_map(|series| &series.points)
+                    .map(|(_, utilization)| utilization * step as f64 / 3600.0)
+                    .sum::<f64>()
+                    .min(paid_hours);
+                let completed_jobs = store.query(NODE_COMPLETED_JOBS, Some(&node.id), since, now + 1, now + 1 - since)
+                    .iter()
+                    .flat_map(|series| &series.points)
+                    .map(|(_, count)| count)
+                    .sum::<f64>()
+                    .round() as u64;
+
+                NodeEfficiency {
+                    cost_usd: paid_hours * node.cost_per_hour,
+                    node_id: node.id,
+                    paid_hours,
+                    utilized_hours,
+                    completed_jobs,
+                }
+            })
+            .collect();
+        report.sort_by(|a, b| a.node_id.cmp(&b.node_id));
+        report
+    }
+
+    /// Count a completed job against the nodes it ran on
+    pub(crate) fn observe_for_efficiency(&self, job_id: &str, status: &JobStatus, now: i64) {
+        if *status != JobStatus::Completed {
+            return;
+        }
+        for node_id in self.job_nodes(job_id) {
+            self.metrics_history.record_counter(NODE_COMPLETED_JOBS, &node_id, 1.0, now);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{unix_now, JobSpec, NodeInfo, SlaConstraints};
+
+    #[tokio::test]
+    async fn test_paid_and_utilized_hours_per_node() {
+        let scheduler = EconomicScheduler::new();
+        for id in ["busy", "spare"] {
+            scheduler.register_node(NodeInfo {
+                id: id.to_string(),
+                available_cpu: 4,
+                available_memory_gb: 16,
+                cost_per_hour: 1.0,
+                ..Default::default()
+            }).unwrap();
+        }
+        let now = unix_now();
+        // Pretend both were registered two hours ago
+        for id in ["busy", "spare"] {
+            scheduler.tenure.since.lock().unwrap().insert(id.to_string(), now - 7200);
+        }
+        // An hour at full CPU on one node
+        for minute in 0..60 {
+            scheduler.record_node_utilization("busy", 0, 16, now - 3600 + minute * 60);
+        }
+        scheduler.schedule(JobSpec {
+            id: "job-1".to_string(),
+            sla: SlaConstraints { max_latency_ms: 1_000, ..Default::default() },
+            pin_nodes: vec!["busy".to_string()],
+            ..Default::default()
+        }).await.unwrap();
+        scheduler.update_job_state("job-1".to_string(), JobStatus::Completed, None).unwrap();
+
+        let report = scheduler.node_efficiency(unix_now());
+        let busy = &report[0];
+        assert_eq!(busy.node_id, "busy");
+        assert!((busy.paid_hours - 2.0).abs() < 1e-3);
+        assert!((busy.utilized_hours - 1.0).abs() < 1e-9);
+        assert!((busy.utilization() - 0.5).abs() < 1e-3);
+        assert_eq!(busy.completed_jobs, 1);
+        assert!((busy.cost_per_completed_job_usd().unwrap() - 2.0).abs() < 1e-3);
+
+        let spare = &report[1];
+        assert_eq!(spare.utilized_hours, 0.0);
+        assert_eq!(spare.cost_per_completed_job_usd(), None);
+    }
+}
@@ -0,0 +1,181 @@
+//! Embedding the scheduler in-process
+//!
+//! `EconomicScheduler` does not need the gRPC server: simulators, test
+//! harnesses and services that place work themselves drive it directly.
+//!
+//! ```text
+//! let mut scheduler = EconomicScheduler::new();
+//! scheduler.set_cost_model(Arc::new(MyPricing));          // optional
+//! scheduler.sync_nodes(&my_inventory).await?;             // or register_node
+//! let placement = scheduler.schedule(job).await?;         // node, cost, latency
+//! scheduler.update_job_state(job_id, JobStatus::Completed, Some(node_id))?;
+//! ```
+//!
+//! Nodes come from `register_node` or from a `NodeSource` (a cloud API, an
+//! inventory file, a simulated fleet) synced once with `sync_nodes` or
+//! periodically with `run_node_source`. A `CostModel` replaces Formula 4.1
+//! pricing of candidate placements and a `ConsolidationPlanner` replaces the
+//! optimizer's consolidation plans; both are set before the scheduler is
+//! cloned. Events (`subscribe_events`) and job updates
+//! (`subscribe_job_updates`) work as they do behind the server.
+
+use anyhow::Result;
+use async_trait::async_trait;
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::Duration;
+use tgp_cost_engine::{CostCalculator, TotalCost};
+use tgp_optimizer::{ConsolidationConfig, ConsolidationPlan, NodeLoad, Optimizer, PlacedJob};
+
+use crate::{EconomicScheduler, JobSpec, NodeInfo, DATA_TRANSFER_PRICE_PER_GB};
+
+/// Prices a candidate placement
+pub trait CostModel: Send + Sync {
+    /// Cost of `job` running `duration_hours` on `node` after moving
+    /// `transfer_gb` of input onto it
+    fn placement_cost(&self, node: &NodeInfo, job: &JobSpec, duration_hours: f64, transfer_gb: f64) -> TotalCost;
+}
+
+/// Formula 4.1: the node's hourly price over the run, plus transfer
+impl CostModel for CostCalculator {
+    fn placement_cost(&self, node: &NodeInfo, _job: &JobSpec, duration_hours: f64, transfer_gb: f64) -> TotalCost {
+        self.total_cost(
+            node.cost_per_hour,
+            duration_hours,
+            1.0, // 100% utilization during job
+            transfer_gb,
+            DATA_TRANSFER_PRICE_PER_GB,
+            0.0, // No idle cost during active job
+            0.0,
+        )
+    }
+}
+
+/// Plans migrations that consolidate load (see `rebalance`)
+pub trait ConsolidationPlanner: Send + Sync {
+    fn plan_consolidation(&self, nodes: &[NodeLoad], jobs: &[PlacedJob], config: &ConsolidationConfig) -> ConsolidationPlan;
+}
+
+impl ConsolidationPlanner for Optimizer {
+    fn plan_consolidation(&self, nodes: &[NodeLoad], jobs: &[PlacedJob], config: &ConsolidationConfig) -> ConsolidationPlan {
+        Optimizer::plan_consolidation(self, nodes, jobs, config)
+    }
+}
+
+/// Supplies the nodes an embedded scheduler places onto
+#[async_trait]
+pub trait NodeSource: Send + Sync {
+    /// Every node currently available
+    async fn nodes(&self) -> Result<Vec<NodeInfo>>;
+}
+
+/// A fixed fleet
+#[async_trait]
+impl NodeSource for Vec<NodeInfo> {
+    async fn nodes(&self) -> Result<Vec<NodeInfo>> {
+        Ok(self.clone())
+    }
+}
+
+/// Outcome of one `sync_nodes`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct NodeSync {
+    /// Nodes registered or refreshed
+    pub registered: usize,
+    /// Nodes synced before that the source no longer lists
+    pub removed: Vec<String>,
+}
+
+impl EconomicScheduler {
+    pub fn set_cost_model(&mut self, model: Arc<dyn CostModel>) {
+        self.cost_model = model;
+    }
+
+    pub fn set_optimizer(&mut self, optimizer: Arc<dyn ConsolidationPlanner>) {
+        self.optimizer = optimizer;
+    }
+
+    /// Register every node `source` lists and drop those it listed before
+    /// but no longer does (nodes registered otherwise are left alone)
+    ///
+    /// A dropped node only leaves placement; jobs already on it are not
+    /// requeued.
+    pub async fn sync_nodes(&self, source: &dyn NodeSource) -> Result<NodeSync> {
+        let nodes = source.nodes().await?;
+        let listed: HashSet<String> = nodes.iter().map(|node| node.id.clone()).collect();
+        let registered = nodes.len();
+        for node in nodes {
+            self.register_node(node)?;
+        }
+
+        let removed: Vec<String> = {
+            let mut sourced = self.sourced_nodes.lock()
+                .map_err(|e| anyhow::anyhow!("Lock poisoned: {}", e))?;
+            let removed = sourced.difference(&listed).cloned().collect();
+            *sourced = listed;
+            removed
+        };
+        if !removed.is_empty() {
+            let mut index = self.available_nodes.lock()
+                .map_err(|e| anyhow::anyhow!("Lock poisoned: {}", e))?;
+            for node_id in &removed {
+                index.remove(node_id);
+            }
+            tracing::info!("Node source dropped {} nodes: {}", removed.len(), removed.join(", "));
+        }
+        Ok(NodeSync { registered, removed })
+    }
+
+    /// Sync nodes from `source` every `interval` (failed syncs are logged
+    /// and the previous nodes kept)
+    pub async fn run_node_source(self, source: Arc<dyn NodeSource>, interval: Duration) {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            if let Err(e) = self.sync_nodes(source.as_ref()).await {
+                tracing::warn!("Failed to sync nodes from source: {:#}", e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ResourceRequirements, SlaConstraints};
+    use crate::test_support::node;
+
+    /// Charges a flat fee per placement, whatever the node
+    struct FlatFee(f64);
+
+    impl CostModel for FlatFee {
+        fn placement_cost(&self, _node: &NodeInfo, _job: &JobSpec, _duration_hours: f64, _transfer_gb: f64) -> TotalCost {
+            TotalCost::new(self.0, 0.0, 0.0)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_embedded_scheduler_uses_injected_parts() {
+        let mut scheduler = EconomicScheduler::new();
+        scheduler.set_cost_model(Arc::new(FlatFee(0.25)));
+
+        let fleet = vec![node("node-a", 0.5), node("node-b", 2.0)];
+        let sync = scheduler.sync_nodes(&fleet).await.unwrap();
+        assert_eq!(sync, NodeSync { registered: 2, removed: Vec::new() });
+
+        let placement = scheduler.schedule(JobSpec {
+            id: "job-1".to_string(),
+            resources: ResourceRequirements { cpu_cores: 2, memory_gb: 4, ..Default::default() },
+            sla: SlaConstraints { max_latency_ms: 10_000, ..Default::default() },
+            ..Default::default()
+        }).await.unwrap();
+        assert_eq!(placement.estimated_cost.total_usd, 0.25);
+
+        // The source shrinks; nodes registered directly stay
+        scheduler.register_node(node("node-c", 1.0)).unwrap();
+        let sync = scheduler.sync_nodes(&vec![node("node-b", 2.0)]).await.unwrap();
+        assert_eq!(sync.removed, vec!["node-a".to_string()]);
+        let ids: HashSet<String> = scheduler.cluster_status().into_iter().map(|node| node.id).collect();
+        assert_eq!(ids, HashSet::from(["node-b".to_string(), "node-c".to_string()]));
+    }
+}
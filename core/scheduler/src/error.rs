@@ -0,0 +1,222 @@
+//! Typed scheduling errors
+//!
+//! Failures a client can act on are raised as `SchedulerError` (carried
+//! inside `anyhow::Error`, so internal plumbing keeps using `?`). The gRPC
+//! layer maps each variant to a status code and attaches an `ErrorDetail`
+//! with a stable reason string and the variant's fields, so clients branch
+//! on the reason instead of parsing messages.
+
+use std::collections::HashMap;
+
+use crate::relaxation::Relaxation;
+use crate::JobStatus;
+
+/// Why a request was refused
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+pub enum SchedulerError {
+    /// No registered node has the resources the job needs
+    #[error("No suitable node for job {job_id}: none has the capacity it needs")]
+    NoCapacity { job_id: String },
+    /// Nodes fit, but none within the latency limit
+    #[error("No node meets the {max_latency_ms}ms latency limit of job {job_id} (best {best_latency_ms}ms)")]
+    SlaLatencyUnmet { job_id: String, max_latency_ms: u64, best_latency_ms: u64, suggestions: Vec<Relaxation> },
+    /// Nodes fit, but none can finish before the deadline
+    #[error("No node can finish job {job_id} before its deadline {deadline}")]
+    DeadlineUnmet { job_id: String, deadline: i64, suggestions: Vec<Relaxation> },
+    /// Nodes fit, but every placement costs more than the budget
+    #[error("Job {job_id} costs at least ${cheapest_usd:.4}, over its ${max_budget_usd:.4} budget")]
+    OverBudget { job_id: String, max_budget_usd: f64, cheapest_usd: f64, suggestions: Vec<Relaxation> },
+    /// Every node costs more per hour than the job's cap
+    #[error("Job {job_id} costs at least ${cheapest_rate_usd:.4}/h, over its ${max_hourly_rate_usd:.4}/h cap")]
+    OverHourlyRate { job_id: String, max_hourly_rate_usd: f64, cheapest_rate_usd: f64 },
+    /// Every placement costs more than the operator's ceiling for the job type
+    #[error("Job {job_id} costs at least ${cheapest_usd:.4}, over the ${ceiling_usd:.4} ceiling for {job_type} jobs")]
+    OverCostCeiling { job_id: String, job_type: String, ceiling_usd: f64, cheapest_usd: f64 },
+    /// A tenant limit refuses the job
+    #[error("Tenant {tenant} is over its {limit} limit")]
+    QuotaExceeded { tenant: String, limit: String },
+    /// The request itself is malformed
+    #[error("Invalid spec: {reason}")]
+    InvalidSpec { reason: String },
+    /// An object with this id already exists
+    #[error("{kind} {id} already exists")]
+    AlreadyExists { kind: String, id: String },
+    #[error("{kind} {id} not found")]
+    NotFound { kind: String, id: String },
+    /// Refused by an admission hook or plugin
+    #[error("Denied by admission policy: {reason}")]
+    AdmissionDenied { reason: String },
+    /// A dependency (admission hook, queue, store) cannot be reached
+    #[error("Unavailable: {reason}")]
+    Unavailable { reason: String },
+    /// A newer registration of the node superseded the caller's
+    #[error("Registration {token} of node {node_id} was superseded by registration {current}")]
+    StaleRegistration { node_id: String, token: u64, current: u64 },
+    /// Another live worker holds this node id
+    #[error("Node {node_id} is registered to another live worker")]
+    IdentityConflict { node_id: String },
+    /// The caller's role does not allow the request
+    #[error("Not allowed to {action}")]
+    Forbidden { action: String },
+    /// The scheduler is overloaded and shed the best-effort job
+    #[error("Scheduler overloaded ({reason}), retry job {job_id} in {retry_after_secs}s")]
+    TryLater { job_id: String, reason: String, retry_after_secs: u64 },
+    /// A provider asked for a price outside the operator's bounds
+    #[error("Price ${cost_per_hour:.4}/h of node {node_id} is outside the allowed range")]
+    PriceOutOfBounds { node_id: String, cost_per_hour: f64, min_cost_per_hour: f64, max_cost_per_hour: Option<f64> },
+    /// The node was repriced too recently
+    #[error("Node {node_id} was repriced too recently, retry in {retry_after_secs}s")]
+    PriceUpdateTooSoon { node_id: String, retry_after_secs: u64 },
+    /// The job's lifecycle does not allow the status change
+    #[error("Job {job_id} cannot go from {from:?} to {to:?}")]
+    InvalidTransition { job_id: String, from: JobStatus, to: JobStatus },
+    /// The tenant's available credit does not cover the work
+    #[error("Tenant {tenant} is out of credit (${available_usd:.4} available)")]
+    InsufficientCredit { tenant: String, available_usd: f64, required_usd: f64 },
+}
+
+impl SchedulerError {
+    pub fn invalid_spec(reason: impl Into<String>) -> Self {
+        Self::InvalidSpec { reason: reason.into() }
+    }
+
+    pub fn already_exists(kind: &str, id: &str) -> Self {
+        Self::AlreadyExists { kind: kind.to_string(), id: id.to_string() }
+    }
+
+    pub fn not_found(kind: &str, id: &str) -> Self {
+        Self::NotFound { kind: kind.to_string(), id: id.to_string() }
+    }
+
+    /// SLA changes that would let the job be placed (see `relaxation`)
+    pub fn suggestions(&self) -> &[Relaxation] {
+        match self {
+            Self::SlaLatencyUnmet { suggestions, .. }
+            | Self::DeadlineUnmet { suggestions, .. }
+            | Self::OverBudget { suggestions, .. } => suggestions,
+            _ => &[],
+        }
+    }
+
+    /// Stable machine-readable reason
+    pub fn reason(&self) -> &'static str {
+        match self {
+            Self::NoCapacity { .. } => "NO_CAPACITY",
+            Self::SlaLatencyUnmet { .. } => "SLA_LATENCY_UNMET",
+            Self::DeadlineUnmet { .. } => "DEADLINE_UNMET",
+            Self::OverBudget { .. } => "OVER_BUDGET",
+            Self::OverHourlyRate { .. } => "OVER_HOURLY_RATE",
+            Self::OverCostCeiling { .. } => "OVER_COST_CEILING",
+            Self::QuotaExceeded { .. } => "QUOTA_EXCEEDED",
+            Self::InvalidSpec { .. } => "INVALID_SPEC",
+            Self::AlreadyExists { .. } => "ALREADY_EXISTS",
+            Self::NotFound { .. } => "NOT_FOUND",
+            Self::AdmissionDenied { .. } => "ADMISSION_DENIED",
+            Self::Unavailable { .. } => "UNAVAILABLE",
+            Self::StaleRegistration { .. } => "STALE_REGISTRATION",
+            Self::IdentityConflict { .. } => "IDENTITY_CONFLICT",
+            Self::Forbidden { .. } => "FORBIDDEN",
+            Self::TryLater { .. } => "TRY_LATER",
+            Self::PriceOutOfBounds { .. } => "PRICE_OUT_OF_BOUNDS",
+            Self::PriceUpdateTooSoon { .. } => "PRICE_UPDATE_TOO_SOON",
+            Self::InvalidTransition { .. } => "INVALID_TRANSITION",
+            Self::InsufficientCredit { .. } => "INSUFFICIENT_CREDIT",
+        }
+    }
+
+    /// The variant's fields as strings, for error details
+    pub fn metadata(&self) -> HashMap<String, String> {
+        let pairs: Vec<(&str, String)> = match self {
+            Self::NoCapacity { job_id } => vec![("job_id", job_id.clone())],
+            Self::SlaLatencyUnmet { job_id, max_latency_ms, best_latency_ms, .. } => vec![
+                ("job_id", job_id.clone()),
+                ("max_latency_ms", max_latency_ms.to_string()),
+                ("best_latency_ms", best_latency_ms.to_string()),
+            ],
+            Self::DeadlineUnmet { job_id, deadline, .. } => vec![
+                ("job_id", job_id.clone()),
+                ("deadline", deadline.to_string()),
+            ],
+            Self::OverBudget { job_id, max_budget_usd, cheapest_usd, .. } => vec![
+                ("job_id", job_id.clone()),
+                ("max_budget_usd", max_budget_usd.to_string()),
+                ("cheapest_usd", cheapest_usd.to_string()),
+            ],
+            Self::OverHourlyRate { job_id, max_hourly_rate_usd, cheapest_rate_usd } => vec![
+                ("job_id", job_id.clone()),
+                ("max_hourly_rate_usd", max_hourly_rate_usd.to_string()),
+                ("cheapest_rate_usd", cheapest_rate_usd.to_string()),
+            ],
+            Self::OverCostCeiling { job_id, job_type, ceiling_usd, cheapest_usd } => vec![
+                ("job_id", job_id.clone()),
+                ("job_type", job_type.clone()),
+                ("ceiling_usd", ceiling_usd.to_string()),
+                ("cheapest_usd", cheapest_usd.to_string()),
+            ],
+            Self::QuotaExceeded { tenant, limit } => vec![("tenant", tenant.clone()), ("limit", limit.clone())],
+            Self::InvalidSpec { reason } | Self::AdmissionDenied { reason } | Self::Unavailable { reason } => {
+                vec![("reason", reason.clone())]
+            }
+            Self::AlreadyExists { kind, id } | Self::NotFound { kind, id } => {
+                vec![("kind", kind.clone()), ("id", id.clone())]
+            }
+            Self::StaleRegistration { node_id, token, current } => vec![
+                ("node_id", node_id.clone()),
+                ("token", token.to_string()),
+                ("current", current.to_string()),
+            ],
+            Self::IdentityConflict { node_id } => vec![("node_id", node_id.clone())],
+            Self::Forbidden { action } => vec![("action", action.clone())],
+            Self::TryLater { job_id, reason, retry_after_secs } => vec![
+                ("job_id", job_id.clone()),
+                ("reason", reason.clone()),
+                ("retry_after_secs", retry_after_secs.to_string()),
+            ],
+            Self::PriceOutOfBounds { node_id, cost_per_hour, min_cost_per_hour, max_cost_per_hour } => vec![
+                ("node_id", node_id.clone()),
+                ("cost_per_hour", cost_per_hour.to_string()),
+                ("min_cost_per_hour", min_cost_per_hour.to_string()),
+                ("max_cost_per_hour", max_cost_per_hour.map(|max| max.to_string()).unwrap_or_default()),
+            ],
+            Self::PriceUpdateTooSoon { node_id, retry_after_secs } => vec![
+                ("node_id", node_id.clone()),
+                ("retry_after_secs", retry_after_secs.to_string()),
+            ],
+            Self::InvalidTransition { job_id, from, to } => vec![
+                ("job_id", job_id.clone()),
+                ("from", format!("{:?}", from)),
+                ("to", format!("{:?}", to)),
+            ],
+            Self::InsufficientCredit { tenant, available_usd, required_usd } => vec![
+                ("tenant", tenant.clone()),
+                ("available_usd", available_usd.to_string()),
+                ("required_usd", required_usd.to_string()),
+            ],
+        };
+        pairs.into_iter().map(|(k, v)| (k.to_string(), v)).collect()
+    }
+}
+
+/// The typed error inside `err`, if any
+pub fn classify(err: &anyhow::Error) -> Option<&SchedulerError> {
+    err.downcast_ref::<SchedulerError>()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_typed_error_survives_anyhow() {
+        let err: anyhow::Error = SchedulerError::OverBudget {
+            job_id: "job-1".to_string(),
+            max_budget_usd: 1.0,
+            cheapest_usd: 2.5,
+            suggestions: Vec::new(),
+        }.into();
+        let typed = classify(&err).unwrap();
+        assert_eq!(typed.reason(), "OVER_BUDGET");
+        assert_eq!(typed.metadata()["cheapest_usd"], "2.5");
+        assert!(classify(&anyhow::anyhow!("plain")).is_none());
+    }
+}
@@ -0,0 +1,206 @@
+//! Wait-time estimates for queued jobs
+//!
+//! A job waiting for placement gets an estimated start time so its
+//! submitter can decide whether to wait or relax its constraints. A job at
+//! queue position `p` (by priority class, then submission order) is
+//! expected to start when the `p+1`-th placed job on a node that could
+//! hold it finishes, going round again at the job's own expected run time
+//! once the known end times are used up. A dormant node that fits brings
+//! the estimate down to its wake-up time. Waiting gangs use their
+//! reservation start.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use crate::{unix_now, EconomicScheduler, JobSpec, JobStatus, NodeInfo, ResourceRequirements};
+
+/// Expected seconds from asking a dormant node to wake until it registers
+pub const NODE_WAKE_SECS: i64 = 180;
+
+/// What an estimate is based on
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EtaBasis {
+    /// Expected end times of placed jobs
+    RunningJobs,
+    /// A dormant node that fits is woken
+    NodeWaking,
+    /// Start of the gang's node reservation
+    GangReservation,
+    /// Nothing running or parked could hold the job
+    Unknown,
+}
+
+/// Estimated start of one queued job
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct QueueEta {
+    pub job_id: String,
+    /// Jobs ahead of this one (0: next)
+    pub position: u32,
+    /// Unix time the job is expected to be placed (0 when unknown)
+    pub estimated_start_at: i64,
+    pub basis: EtaBasis,
+}
+
+#[derive(Debug, Clone)]
+struct QueuedEntry {
+    job: JobSpec,
+    /// Submission order
+    seq: u64,
+}
+
+#[derive(Debug, Default)]
+struct QueuedState {
+    entries: HashMap<String, QueuedEntry>,
+    next_seq: u64,
+}
+
+/// Jobs this scheduler enqueued, in submission order
+#[derive(Debug, Clone, Default)]
+pub struct QueuedJobs {
+    state: Arc<Mutex<QueuedState>>,
+}
+
+impl QueuedJobs {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub(crate) fn record(&self, job: &JobSpec) {
+        if let Ok(mut state) = self.state.lock() {
+            let seq = state.next_seq;
+            state.next_seq += 1;
+            state.entries.insert(job.id.clone(), QueuedEntry { job: job.clone(), seq });
+        }
+    }
+
+    /// Recorded jobs for which `is_pending` holds, in submission order
+    pub(crate) fn pending(&self, is_pending: impl Fn(&str) -> bool) -> Vec<JobSpec> {
+        let Ok(state) = self.state.lock() else {
+            return Vec::new();
+        };
+        let mut entries: Vec<&QueuedEntry> = state.entries.values().filter(|entry| is_pending(&entry.job.id)).collect();
+        entries.sort_by_key(|entry| entry.seq);
+        entries.into_iter().map(|entry| entry.job.clone()).collect()
+    }
+}
+
+fn fits(node: &NodeInfo, required: &ResourceRequirements) -> bool {
+    node.available_cpu >= required.cpu_cores
+        && node.available_memory_gb >= required.memory_gb
+        && node.available_gpu >= required.gpu_count
+        && node.fits_disk(required.disk_gb)
+}
+
+impl EconomicScheduler {
+    /// Estimated start of `job_id` if it is still waiting for placement
+    pub fn queue_eta(&self, job_id: &str) -> Option<QueueEta> {
+        if self.get_job_state(job_id)?.status != JobStatus::Pending {
+            return None;
+        }
+        let now = unix_now();
+
+        if let Some(gang) = self.reservations.waiting().into_iter().find(|gang| gang.job.id == job_id) {
+            return Some(QueueEta {
+                job_id: job_id.to_string(),
+                position: 0,
+                estimated_start_at: gang.start_at.max(now),
+                basis: EtaBasis::GangReservation,
+            });
+        }
+
+        // Queue order among jobs still pending; placed ones are dropped
+        let (job, position) = {
+            let states = self.job_states.lock().ok()?;
+            let mut queued = self.queued.state.lock().ok()?;
+            queued.entries.retain(|id, _| states.get(id).is_some_and(|state| state.status == JobStatus::Pending));
+            let entry = queued.entries.get(job_id)?;
+            let ahead = queued.entries.values()
+                .filter(|other| {
+                    other.job.priority.base() > entry.job.priority.base()
+                        || (other.job.priority == entry.job.priority && other.seq < entry.seq)
+                })
+                .count();
+            (entry.job.clone(), ahead)
+        };
+        let required = &job.resources;
+
+        // Expected ends of placed jobs on nodes that could hold this one
+        let placed: Vec<String> = self.placed_jobs.lock().ok()?.keys().cloned().collect();
+        let mut ends: Vec<i64> = placed.iter()
+            .filter(|id| {
+                self.get_job_state(id)
+                    .and_then(|state| state.assigned_node)
+                    .and_then(|node_id| self.available_nodes.lock().ok()?.get(&node_id).cloned())
+                    .is_some_and(|node| fits(&node, required))
+            })
+            .filter_map(|id| self.reservations.end_of(id))
+            .map(|end| end.max(now))
+            .collect();
+        ends.sort_unstable();
+
+        let mut eta = QueueEta {
+            job_id: job_id.to_string(),
+            position: position as u32,
+            estimated_start_at: 0,
+            basis: EtaBasis::Unknown,
+        };
+        if !ends.is_empty() {
+            let run_secs = (self.reference_duration_hours(&job, None) * 3600.0).ceil() as i64;
+            let rounds = (position / ends.len()) as i64;
+            eta.estimated_start_at = ends[position % ends.len()] + rounds * run_secs;
+            eta.basis = EtaBasis::RunningJobs;
+        }
+        if self.power().parked_nodes().iter().any(|node| fits(node, required)) {
+            let woken_at = now + NODE_WAKE_SECS;
+            if eta.basis == EtaBasis::Unknown || woken_at < eta.estimated_start_at {
+                eta.estimated_start_at = woken_at;
+                eta.basis = EtaBasis::NodeWaking;
+            }
+        }
+        Some(eta)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::queue::MemoryQueue;
+    use crate::SlaConstraints;
+
+    #[tokio::test]
+    async fn test_queued_jobs_wait_for_running_jobs_in_order() {
+        let mut scheduler = EconomicScheduler::new();
+        scheduler.register_node(NodeInfo {
+            id: "node-1".to_string(),
+            available_cpu: 4,
+            available_memory_gb: 16,
+            location: "vps-1".to_string(),
+            cost_per_hour: 0.1,
+            ..Default::default()
+        }).unwrap();
+        let job = |id: &str| JobSpec {
+            id: id.to_string(),
+            resources: ResourceRequirements { cpu_cores: 4, memory_gb: 8, ..Default::default() },
+            sla: SlaConstraints { max_latency_ms: 1_000, ..Default::default() },
+            estimated_duration_hours: Some(1.0),
+            ..Default::default()
+        };
+        scheduler.schedule(job("running")).await.unwrap();
+        let running_end = scheduler.reservations().end_of("running").unwrap();
+
+        scheduler.attach_queue(Arc::new(MemoryQueue::new()));
+        scheduler.enqueue(job("first")).await.unwrap();
+        scheduler.enqueue(job("second")).await.unwrap();
+
+        let first = scheduler.queue_eta("first").unwrap();
+        assert_eq!((first.position, first.basis), (0, EtaBasis::RunningJobs));
+        assert_eq!(first.estimated_start_at, running_end);
+        // One slot: the second waits for the first to run too
+        let second = scheduler.queue_eta("second").unwrap();
+        assert_eq!(second.position, 1);
+        assert_eq!(second.estimated_start_at, running_end + 3600);
+        assert!(scheduler.queue_eta("running").is_none());
+    }
+}
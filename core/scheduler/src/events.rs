@@ -0,0 +1,292 @@
+//! Lifecycle events and external event bus integration
+//!
+//! The scheduler broadcasts a `SchedulerEvent` for every job and node
+//! lifecycle change. An optional bridge forwards them as JSON to NATS
+//! subjects or Kafka topics (`<prefix>.jobs`, `<prefix>.nodes`) so billing,
+//! notification and data pipelines can react without polling the gRPC API.
+//!
+//! Backends are behind the `nats` and `kafka` cargo features.
+
+use anyhow::Result;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
+
+use crate::overcommit::ReclaimAction;
+use crate::power::PowerState;
+use crate::JobStatus;
+
+/// Job and node lifecycle event
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum SchedulerEvent {
+    JobSubmitted {
+        job_id: String,
+        timestamp: i64,
+    },
+    JobScheduled {
+        job_id: String,
+        node_id: String,
+        estimated_cost_usd: f64,
+        timestamp: i64,
+    },
+    JobStatusChanged {
+        job_id: String,
+        status: JobStatus,
+        node_id: Option<String>,
+        timestamp: i64,
+    },
+    /// Rebalancer moved (or, in report-only mode, proposed moving) a job
+    JobMigration {
+        job_id: String,
+        from_node: String,
+        to_node: String,
+        applied: bool,
+        timestamp: i64,
+    },
+    /// Gang job is waiting; `nodes` are held for it from `start_at`
+    GangReserved {
+        job_id: String,
+        nodes: Vec<String>,
+        start_at: i64,
+        timestamp: i64,
+    },
+    /// Job is over its budget everywhere and waits for prices to drop
+    /// until `expires_at`
+    JobHeldForPrice {
+        job_id: String,
+        max_budget_usd: f64,
+        cheapest_usd: f64,
+        expires_at: i64,
+        timestamp: i64,
+    },
+    /// Job is held until its start window opens at `start_at`
+    JobDeferred {
+        job_id: String,
+        start_at: i64,
+        timestamp: i64,
+    },
+    /// Held job found no placement within its budget in time and failed
+    JobPriceHoldExpired {
+        job_id: String,
+        max_budget_usd: f64,
+        cheapest_usd: f64,
+        timestamp: i64,
+    },
+    /// Primary workload reclaimed capacity a best-effort job was harvesting
+    BestEffortReclaimed {
+        job_id: String,
+        node_id: String,
+        action: ReclaimAction,
+        timestamp: i64,
+    },
+    /// A verification replica's output differed from the original's
+    VerificationFailed {
+        job_id: String,
+        nodes: Vec<String>,
+        timestamp: i64,
+    },
+    /// This scheduler replica stopped accepting work and is draining
+    SchedulerShuttingDown {
+        timestamp: i64,
+    },
+    NodeRegistered {
+        node_id: String,
+        location: String,
+        cost_per_hour: f64,
+        timestamp: i64,
+    },
+    /// Provider announced termination of a preemptible node
+    NodeInterrupted {
+        node_id: String,
+        terminate_at: i64,
+        affected_jobs: Vec<String>,
+        timestamp: i64,
+    },
+    /// Node went dormant, was asked to wake, or became active again
+    NodePowerChanged {
+        node_id: String,
+        state: PowerState,
+        timestamp: i64,
+    },
+}
+
+impl SchedulerEvent {
+    /// Topic suffix the event is published under
+    pub fn topic(&self) -> &'static str {
+        match self {
+            SchedulerEvent::NodeRegistered { .. }
+            | SchedulerEvent::NodeInterrupted { .. }
+            | SchedulerEvent::NodePowerChanged { .. } => "nodes",
+            SchedulerEvent::SchedulerShuttingDown { .. } => "scheduler",
+            _ => "jobs",
+        }
+    }
+
+    /// Partitioning key (job or node id) so events for one entity stay ordered
+    pub fn key(&self) -> &str {
+        match self {
+            SchedulerEvent::JobSubmitted { job_id, .. }
+            | SchedulerEvent::JobScheduled { job_id, .. }
+            | SchedulerEvent::JobStatusChanged { job_id, .. }
+            | SchedulerEvent::JobMigration { job_id, .. }
+            | SchedulerEvent::GangReserved { job_id, .. }
+            | SchedulerEvent::JobHeldForPrice { job_id, .. }
+            | SchedulerEvent::JobPriceHoldExpired { job_id, .. }
+            | SchedulerEvent::JobDeferred { job_id, .. }
+            | SchedulerEvent::BestEffortReclaimed { job_id, .. }
+            | SchedulerEvent::VerificationFailed { job_id, .. } => job_id,
+            SchedulerEvent::NodeRegistered { node_id, .. }
+            | SchedulerEvent::NodeInterrupted { node_id, .. }
+            | SchedulerEvent::NodePowerChanged { node_id, .. } => node_id,
+            SchedulerEvent::SchedulerShuttingDown { .. } => "",
+        }
+    }
+}
+
+/// Destination for serialized events
+#[async_trait]
+pub trait EventPublisher: Send + Sync {
+    async fn publish(&self, topic: &str, key: &str, payload: &[u8]) -> Result<()>;
+}
+
+/// Forward scheduler events to an external bus until the scheduler shuts down
+pub async fn run_event_bridge(
+    mut events: broadcast::Receiver<SchedulerEvent>,
+    publisher: Box<dyn EventPublisher>,
+    topic_prefix: String,
+) {
+    loop {
+        let event = match events.recv().await {
+            Ok(event) => event,
+            Err(broadcast::error::RecvError::Lagged(missed)) => {
+                tracing::warn!("Event bridge lagged, {} events dropped", missed);
+                continue;
+            }
+            Err(broadcast::error::RecvError::Closed) => return,
+        };
+
+        let topic = format!("{}.{}", topic_prefix, event.topic());
+        let payload = match serde_json::to_vec(&event) {
+            Ok(payload) => payload,
+            Err(e) => {
+                tracing::error!("Failed to serialize event: {}", e);
+                continue;
+            }
+        };
+
+        if let Err(e) = publisher.publish(&topic, event.key(), &payload).await {
+            tracing::warn!("Failed to publish event to {}: {}", topic, e);
+        }
+    }
+}
+
+/// Connect to the event bus named by `url`
+///
+/// `nats://host:4222` or `kafka://broker1:9092,broker2:9092`.
+pub async fn connect_publisher(url: &str) -> Result<Box<dyn EventPublisher>> {
+    if url.starts_with("nats://") {
+        #[cfg(feature = "nats")]
+        return Ok(Box::new(nats::NatsPublisher::connect(url).await?));
+        #[cfg(not(feature = "nats"))]
+        anyhow::bail!("NATS support not compiled in (enable the `nats` feature)");
+    }
+
+    if let Some(brokers) = url.strip_prefix("kafka://") {
+        #[cfg(feature = "kafka")]
+        return Ok(Box::new(kafka::KafkaPublisher::connect(brokers)?));
+        #[cfg(not(feature = "kafka"))]
+        {
+            let _ = brokers;
+            anyhow::bail!("Kafka support not compiled in (enable the `kafka` feature)");
+        }
+    }
+
+    anyhow::bail!("Unsupported event bus URL: {}", url)
+}
+
+#[cfg(feature = "nats")]
+mod nats {
+    use super::*;
+
+    /// Publishes events as NATS messages on `<prefix>.<topic>` subjects
+    pub struct NatsPublisher {
+        client: async_nats::Client,
+    }
+
+    impl NatsPublisher {
+        pub async fn connect(url: &str) -> Result<Self> {
+            let client = async_nats::connect(url).await?;
+            tracing::info!("Connected to NATS at {}", url);
+            Ok(Self { client })
+        }
+    }
+
+    #[async_trait]
+    impl EventPublisher for NatsPublisher {
+        async fn publish(&self, topic: &str, _key: &str, payload: &[u8]) -> Result<()> {
+            self.client.publish(topic.to_string(), payload.to_vec().into()).await?;
+            Ok(())
+        }
+    }
+}
+
+#[cfg(feature = "kafka")]
+mod kafka {
+    use super::*;
+    use rdkafka::config::ClientConfig;
+    use rdkafka::producer::{FutureProducer, FutureRecord};
+    use std::time::Duration;
+
+    /// Publishes events to Kafka topics keyed by job/node id
+    pub struct KafkaPublisher {
+        producer: FutureProducer,
+    }
+
+    impl KafkaPublisher {
+        pub fn connect(brokers: &str) -> Result<Self> {
+            let producer = ClientConfig::new()
+                .set("bootstrap.servers", brokers)
+                .set("message.timeout.ms", "5000")
+                .create()?;
+            tracing::info!("Connected to Kafka at {}", brokers);
+            Ok(Self { producer })
+        }
+    }
+
+    #[async_trait]
+    impl EventPublisher for KafkaPublisher {
+        async fn publish(&self, topic: &str, key: &str, payload: &[u8]) -> Result<()> {
+            self.producer
+                .send(
+                    FutureRecord::to(topic).key(key).payload(payload),
+                    Duration::from_secs(5),
+                )
+                .await
+                .map_err(|(e, _)| anyhow::anyhow!("Kafka delivery failed: {}", e))?;
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_event_serialization_and_routing() {
+        let event = SchedulerEvent::JobStatusChanged {
+            job_id: "job-1".to_string(),
+            status: JobStatus::Completed,
+            node_id: Some("node-1".to_string()),
+            timestamp: 1,
+        };
+
+        assert_eq!(event.topic(), "jobs");
+        assert_eq!(event.key(), "job-1");
+
+        let json: serde_json::Value = serde_json::to_value(&event).unwrap();
+        assert_eq!(json["event"], "job_status_changed");
+        assert_eq!(json["status"], "Completed");
+    }
+}
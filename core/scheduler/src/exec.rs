@@ -0,0 +1,131 @@
+//! Exec into running jobs
+//!
+//! Operators debugging a stuck job can run a command in its container.
+//! `ExecInJob` is proxied to the control service of the worker running the
+//! job, which starts a Docker exec: stdin flows down the stream, output
+//! and finally the exit code flow back. Nodes behind NAT only relay
+//! commands (see `relay`), not byte streams, so their jobs cannot be
+//! exec'd into.
+//!
+//! Workers do not know the scheduler's operators, so the scheduler signs
+//! an exec grant for the job once the caller is authorized as one. The
+//! grant is an HMAC over the job id and issue time keyed with the worker
+//! control token; workers refuse exec sessions without a fresh grant for
+//! the job.
+
+use anyhow::{Context, Result};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use tokio_stream::Stream;
+use tonic::{Request, Streaming};
+
+use crate::commands::connect_worker;
+use crate::commands::worker_proto::{ExecRequest, ExecResponse};
+use crate::error::SchedulerError;
+use crate::rbac::Role;
+use crate::{unix_now, EconomicScheduler, JobStatus, NodeInfo};
+
+/// Metadata carrying the exec grant
+pub const EXEC_GRANT_HEADER: &str = "x-tgp-exec-grant";
+
+/// `<issued_at>.<hex>` grant to exec into `job_id`, signed with the worker
+/// control token
+pub fn exec_grant(token: &str, job_id: &str, issued_at: i64) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(token.as_bytes()).expect("HMAC takes keys of any length");
+    mac.update(issued_at.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(job_id.as_bytes());
+    let digest: String = mac.finalize().into_bytes().iter().map(|b| format!("{:02x}", b)).collect();
+    format!("{}.{}", issued_at, digest)
+}
+
+impl EconomicScheduler {
+    /// Node a job can be exec'd into on
+    pub fn exec_target(&self, job_id: &str) -> Result<NodeInfo> {
+        let state = self.get_job_state(job_id)
+            .ok_or_else(|| SchedulerError::not_found("Job", job_id))?;
+        if state.status != JobStatus::Running {
+            return Err(SchedulerError::invalid_spec(format!(
+                "Job {} is {:?}, only running jobs can be exec'd into",
+                job_id, state.status
+            )).into());
+        }
+        let node_id = state.assigned_node
+            .ok_or_else(|| anyhow::anyhow!("Running job {} has no node", job_id))?;
+        let node = self.available_nodes.lock()
+            .map_err(|e| anyhow::anyhow!("Lock poisoned: {}", e))?
+            .get(&node_id)
+            .cloned()
+            .ok_or_else(|| SchedulerError::not_found("Node", &node_id))?;
+        if node.control_addr.is_empty() {
+            return Err(SchedulerError::Unavailable {
+                reason: format!("node {} cannot be dialled, exec needs a control address", node_id),
+            }.into());
+        }
+        Ok(node)
+    }
+
+    /// Open an exec session on the worker running a job for a caller
+    /// authorized as `role`; `input` starts with the start message
+    pub async fn open_exec(
+        &self,
+        job_id: &str,
+        role: Role,
+        input: impl Stream<Item = ExecRequest> + Send + 'static,
+    ) -> Result<Streaming<ExecResponse>> {
+        if role < Role::Operator {
+            return Err(SchedulerError::Forbidden { action: "exec into jobs".to_string() }.into());
+        }
+        let node = self.exec_target(job_id)?;
+        let token = self.control_auth().token().ok_or_else(|| SchedulerError::Unavailable {
+            reason: "no worker control token is configured".to_string(),
+        })?;
+        let mut request = Request::new(input);
+        request.metadata_mut().insert(
+            EXEC_GRANT_HEADER,
+            exec_grant(token, job_id, unix_now()).parse().context("Exec grant is not valid in a header")?,
+        );
+        // No request timeout: a session lasts as long as the operator wants
+        let output = connect_worker(&node, None, self.control_auth()).await?
+            .exec(request)
+            .await
+            .with_context(|| format!("Exec on node {} failed", node.id))?
+            .into_inner();
+        Ok(output)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_exec_needs_running_dialable_job() {
+        let scheduler = EconomicScheduler::new();
+        scheduler.register_node(NodeInfo {
+            id: "behind-nat".to_string(),
+            available_cpu: 8,
+            available_memory_gb: 16,
+            ..Default::default()
+        }).unwrap();
+
+        assert!(scheduler.exec_target("missing").is_err());
+
+        scheduler.job_states.lock().unwrap().insert("job-1".to_string(), crate::JobState {
+            job_id: "job-1".to_string(),
+            status: JobStatus::Pending,
+            ..Default::default()
+        });
+        assert!(scheduler.exec_target("job-1").is_err());
+
+        scheduler.update_job_state("job-1".to_string(), JobStatus::Running, Some("behind-nat".to_string())).unwrap();
+        let err = scheduler.exec_target("job-1").unwrap_err();
+        assert_eq!(crate::error::classify(&err).unwrap().reason(), "UNAVAILABLE");
+
+        // Grants are bound to the job and the time they were issued
+        let grant = exec_grant("s3cret", "job-1", 1_700_000_000);
+        assert!(grant.starts_with("1700000000."));
+        assert_ne!(grant, exec_grant("s3cret", "job-2", 1_700_000_000));
+        assert_ne!(grant, exec_grant("other", "job-1", 1_700_000_000));
+    }
+}
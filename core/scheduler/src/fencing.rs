@@ -0,0 +1,232 @@
+//! Worker registration fencing
+//!
+//! Every worker keeps a secret instance key next to its node id across
+//! restarts. Each accepted registration of a node gets the next fencing
+//! token, which the worker sends with its resource reports and command
+//! stream; anything carrying an older token comes from a superseded
+//! process and is rejected.
+//!
+//! A registration with the recorded key is a restart. One with a different
+//! key is refused while the node is still reporting (impersonation, or two
+//! machines configured with the same id) and takes the node over once it
+//! has been silent for `takeover_after_secs` (e.g. a reinstalled machine).
+//! Workers registering without a key are not fenced.
+//!
+//! Identities are written to the state store and included in snapshots, so
+//! a restarted scheduler still knows each node's key and latest token.
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use crate::error::SchedulerError;
+use crate::store::PersistOp;
+use crate::{unix_now, EconomicScheduler};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FencingConfig {
+    /// Silence after which another instance may take over a node id
+    pub takeover_after_secs: i64,
+}
+
+impl Default for FencingConfig {
+    fn default() -> Self {
+        Self { takeover_after_secs: 300 }
+    }
+}
+
+/// Current registration of one node
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NodeIdentity {
+    /// Hex SHA-256 of the instance key (empty: unfenced worker)
+    pub key_hash: String,
+    pub token: u64,
+    pub registered_at: i64,
+}
+
+/// Registration tokens of known nodes
+#[derive(Debug, Clone, Default)]
+pub struct NodeFencing {
+    config: FencingConfig,
+    identities: Arc<Mutex<HashMap<String, NodeIdentity>>>,
+}
+
+fn key_hash(instance_key: &str) -> String {
+    if instance_key.is_empty() {
+        return String::new();
+    }
+    Sha256::digest(instance_key.as_bytes())
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+impl NodeFencing {
+    pub fn new(config: FencingConfig) -> Self {
+        Self { config, identities: Arc::default() }
+    }
+
+    /// Accept a registration and return its token; `last_seen` is when the
+    /// current holder last reported
+    pub fn register(&self, node_id: &str, instance_key: &str, last_seen: Option<i64>, now: i64) -> Result<u64, SchedulerError> {
+        let hash = key_hash(instance_key);
+        let mut identities = self.identities.lock().unwrap_or_else(|e| e.into_inner());
+        let token = match identities.get(node_id) {
+            None => 1,
+            Some(current) if current.key_hash == hash || current.key_hash.is_empty() => current.token + 1,
+            Some(current) => {
+                let silent_since = last_seen.unwrap_or(current.registered_at).max(current.registered_at);
+                if now - silent_since < self.config.takeover_after_secs {
+                    tracing::warn!("Refusing registration of node {} from a different instance", node_id);
+                    return Err(SchedulerError::IdentityConflict { node_id: node_id.to_string() });
+                }
+                tracing::warn!("Node {} taken over by a new instance after {}s of silence", node_id, now - silent_since);
+                current.token + 1
+            }
+        };
+        identities.insert(node_id.to_string(), NodeIdentity { key_hash: hash, token, registered_at: now });
+        Ok(token)
+    }
+
+    /// Reject `token` if a newer registration of the node superseded it
+    pub fn check(&self, node_id: &str, token: u64) -> Result<(), SchedulerError> {
+        let mut identities = self.identities.lock().unwrap_or_else(|e| e.into_inner());
+        match identities.get(node_id) {
+            // Unfenced worker, or a worker from before a scheduler restart:
+            // adopt its token until it registers again
+            None => {
+                if token > 0 {
+                    identities.insert(node_id.to_string(), NodeIdentity {
+                        key_hash: String::new(),
+                        token,
+                        registered_at: unix_now(),
+                    });
+                }
+                Ok(())
+            }
+            Some(current) if current.key_hash.is_empty() && token == 0 => Ok(()),
+            Some(current) if token < current.token => {
+                Err(SchedulerError::StaleRegistration {
+                    node_id: node_id.to_string(),
+                    token,
+                    current: current.token,
+                })
+            }
+            Some(_) => Ok(()),
+        }
+    }
+
+    pub fn identity(&self, node_id: &str) -> Option<NodeIdentity> {
+        self.identities.lock().ok()?.get(node_id).cloned()
+    }
+
+    /// Every recorded identity by node id
+    pub fn identities(&self) -> HashMap<String, NodeIdentity> {
+        self.identities.lock().map(|identities| identities.clone()).unwrap_or_default()
+    }
+
+    /// Merge recorded identities, keeping the newer registration of each node
+    pub fn restore(&self, restored: impl IntoIterator<Item = (String, NodeIdentity)>) {
+        let mut identities = self.identities.lock().unwrap_or_else(|e| e.into_inner());
+        for (node_id, identity) in restored {
+            if identities.get(&node_id).map_or(true, |current| current.token < identity.token) {
+                identities.insert(node_id, identity);
+            }
+        }
+    }
+}
+
+impl EconomicScheduler {
+    /// Replace the fencing policy (call before cloning the scheduler)
+    pub fn set_fencing(&mut self, config: FencingConfig) {
+        self.fencing = NodeFencing::new(config);
+    }
+
+    pub fn node_fencing(&self) -> &NodeFencing {
+        &self.fencing
+    }
+
+    /// Fence a node registration, returning the token its worker must present
+    pub fn fence_registration(&self, node_id: &str, instance_key: &str) -> Result<u64, SchedulerError> {
+        let last_seen = self.reputation().last_report(node_id);
+        let token = self.fencing.register(node_id, instance_key, last_seen, unix_now())?;
+        self.persist_identity(node_id);
+        Ok(token)
+    }
+
+    /// Reject messages from a superseded worker process
+    pub fn check_fencing(&self, node_id: &str, token: u64) -> Result<(), SchedulerError> {
+        let adopting = token > 0 && self.fencing.identity(node_id).is_none();
+        self.fencing.check(node_id, token)?;
+        if adopting {
+            self.persist_identity(node_id);
+        }
+        Ok(())
+    }
+
+    /// Write a node's current identity behind to the state store
+    pub(crate) fn persist_identity(&self, node_id: &str) {
+        if let (Some(persist), Some(identity)) = (&self.persist, self.fencing.identity(node_id)) {
+            let _ = persist.send(PersistOp::Identity(node_id.to_string(), identity));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_restart_supersedes_and_impersonation_is_refused() {
+        let fencing = NodeFencing::new(FencingConfig { takeover_after_secs: 300 });
+        let first = fencing.register("node-1", "key-a", None, 1_000).unwrap();
+        let restarted = fencing.register("node-1", "key-a", Some(1_010), 1_020).unwrap();
+        assert_eq!((first, restarted), (1, 2));
+
+        // The process from before the restart is fenced off
+        assert!(matches!(
+            fencing.check("node-1", first),
+            Err(SchedulerError::StaleRegistration { current: 2, .. })
+        ));
+        assert!(fencing.check("node-1", restarted).is_ok());
+
+        // A different instance while the node still reports is refused...
+        assert!(matches!(
+            fencing.register("node-1", "key-b", Some(1_100), 1_110),
+            Err(SchedulerError::IdentityConflict { .. })
+        ));
+        // ...but may take over once it has been silent long enough
+        assert_eq!(fencing.register("node-1", "key-b", Some(1_100), 1_500).unwrap(), 3);
+        assert_ne!(fencing.identity("node-1").unwrap().key_hash, key_hash("key-a"));
+    }
+
+    #[tokio::test]
+    async fn test_identities_survive_a_scheduler_restart() {
+        let store: Arc<dyn crate::store::StateStore> = Arc::new(crate::store::MemoryStore::new());
+        let mut before = EconomicScheduler::new();
+        before.attach_store(store.clone()).await.unwrap();
+        let first = before.fence_registration("node-1", "key-a").unwrap();
+        let second = before.fence_registration("node-1", "key-a").unwrap();
+        before.flush_state().await.unwrap();
+
+        // The restarted scheduler still fences off the older process and
+        // refuses another instance's key
+        let mut after = EconomicScheduler::new();
+        after.attach_store(store).await.unwrap();
+        assert!(matches!(after.check_fencing("node-1", first), Err(SchedulerError::StaleRegistration { .. })));
+        assert!(after.check_fencing("node-1", second).is_ok());
+        assert!(matches!(
+            after.fence_registration("node-1", "key-b"),
+            Err(SchedulerError::IdentityConflict { .. })
+        ));
+
+        // A snapshot carries them too, without rolling a newer token back
+        let fresh = EconomicScheduler::new();
+        fresh.fence_registration("node-1", "key-a").unwrap();
+        fresh.restore(after.snapshot().await.unwrap()).await.unwrap();
+        assert_eq!(fresh.node_fencing().identity("node-1").unwrap().token, second);
+        fresh.node_fencing().restore([("node-1".to_string(), NodeIdentity { key_hash: String::new(), token: 1, registered_at: 0 })]);
+        assert_eq!(fresh.node_fencing().identity("node-1").unwrap().token, second);
+    }
+}
@@ -2,13 +2,18 @@
 //! 
 //! Implements the SchedulerService defined in scheduler.proto
 
-use tonic::{transport::Server, Request, Response, Status};
-use tracing::{error, info};
+use tokio::sync::{broadcast, mpsc};
+use tokio_stream::wrappers::{ReceiverStream, UnboundedReceiverStream};
+use tonic::{transport::Server, Code, Request, Response, Status, Streaming};
+use tracing::{error, info, warn};
 
+use crate::trace::{now_ms, TraceRecord};
 use crate::EconomicScheduler;
 
 // Include generated proto code
 pub mod proto {
+    // Command messages carry a whole job assignment
+    #![allow(clippy::large_enum_variant)]
     tonic::include_proto!("tgp.scheduler.v1");
 }
 
@@ -17,6 +22,15 @@ use proto::{
     *,
 };
 
+/// How often WatchJob resends a pending job's wait-time estimate
+const ETA_REFRESH: std::time::Duration = std::time::Duration::from_secs(30);
+
+/// Largest SpeedTest download served, in MB
+const SPEED_TEST_MAX_MB: u32 = 64;
+
+/// Chunk size of GetJobOutput and GetPayload downloads
+const OUTPUT_CHUNK_BYTES: usize = 1024 * 1024;
+
 #[tonic::async_trait]
 impl SchedulerService for EconomicScheduler {
     async fn register_node(
@@ -26,23 +40,52 @@ impl SchedulerService for EconomicScheduler {
         let req = request.into_inner();
         info!("Registering node: {} ({})", req.node_id, req.hostname);
 
+        // Jobs are packed against allocatable, never the full capacity;
+        // workers that do not report it get their capacity
+        let allocatable_cpu = match req.allocatable_cpu {
+            0 => req.cpu_cores,
+            cpu => cpu.min(req.cpu_cores),
+        };
+        let allocatable_memory_gb = if req.allocatable_memory_gb > 0.0 {
+            req.allocatable_memory_gb.min(req.total_memory_gb)
+        } else {
+            req.total_memory_gb
+        };
+
         // Use actual scheduler to register node
         let node = crate::NodeInfo {
             id: req.node_id.clone(),
-            available_cpu: req.cpu_cores,
-            available_memory_gb: (req.total_memory_gb as u32),
+            available_cpu: allocatable_cpu,
+            available_memory_gb: (allocatable_memory_gb as u32),
             available_gpu: req.gpu_count,
             location: req.location.clone(),
             cost_per_hour: req.cost_per_hour,
+            data_service_addr: req.data_service_addr.clone(),
+            preemptible: req.preemptible,
+            performance_score: req.performance_score,
+            provider: req.provider.clone(),
+            control_addr: req.control_addr.clone(),
+            capacity_cpu: req.cpu_cores,
+            capacity_memory_gb: (req.total_memory_gb as u32),
+            available_disk_gb: req.available_disk_gb.map(|gb| gb as u32),
+            network_mbps: (req.network_mbps > 0).then_some(req.network_mbps),
+            gpu_topology: req.gpu_topology.as_ref().map(gpu_topology_from_proto),
+            numa_cpus: req.numa_nodes.iter().map(|numa| numa.cpus.clone()).collect(),
+            labels: req.labels.clone(),
+            arch: crate::images::normalize_arch(&req.arch),
         };
+        let fencing_token = self.fence_registration(&req.node_id, &req.instance_key)
+            .map_err(|e| error_status(e.into(), Code::PermissionDenied))?;
+        self.record_trace(TraceRecord::RegisterNode { at_ms: now_ms(), node: Box::new(node.clone()) });
 
         match self.register_node(node) {
             Ok(_) => {
-                info!("Node {} registered in scheduler", req.node_id);
+                info!("Node {} registered in scheduler (registration {})", req.node_id, fencing_token);
                 let response = RegisterNodeResponse {
                     success: true,
                     message: format!("Node {} registered successfully", req.node_id),
                     cluster_id: "tgp-cluster-1".to_string(),
+                    fencing_token,
                 };
                 Ok(Response::new(response))
             }
@@ -57,7 +100,12 @@ impl SchedulerService for EconomicScheduler {
         request: Request<ResourceReport>,
     ) -> Result<Response<ResourceAck>, Status> {
         let report = request.into_inner();
-        
+        self.check_fencing(&report.node_id, report.fencing_token)
+            .map_err(|e| error_status(e.into(), Code::FailedPrecondition))?;
+        if self.faults().drops_heartbeat(&report.node_id) {
+            return Err(Status::unavailable("Resource report dropped (fault injection)"));
+        }
+
         info!(
             "Resource report from {}: CPU={}, RAM={:.1}GB, Disk={:.1}GB",
             report.node_id,
@@ -66,10 +114,50 @@ impl SchedulerService for EconomicScheduler {
             report.available_disk_gb
         );
 
-        // TODO: Update node resources (requires update_node_resources method)
-        // For now, just acknowledge receipt
+        let versions: Vec<(String, f64, Option<String>)> = report.datasets
+            .into_iter()
+            .map(|d| (d.name, d.size_gb, (!d.content_digest.is_empty()).then_some(d.content_digest)))
+            .collect();
+        let datasets: Vec<(String, f64)> = versions.iter()
+            .map(|(name, size_gb, _)| (name.clone(), *size_gb))
+            .collect();
+        self.record_trace(TraceRecord::ResourceReport {
+            at_ms: now_ms(),
+            node_id: report.node_id.clone(),
+            available_cpu: report.available_cpu,
+            available_memory_gb: report.available_memory_gb,
+            available_gpu: report.available_gpu,
+            available_disk_gb: report.available_disk_gb,
+            datasets: datasets.clone(),
+        });
+        self.sync_node_dataset_versions(&report.node_id, &versions);
+        self.update_node_disk(&report.node_id, report.available_disk_gb as u32);
+        if report.timestamp > 0 {
+            self.clock_skew().record(&report.node_id, report.timestamp, crate::unix_now());
+        }
+        self.reputation().record_report(&report.node_id, (now_ms() / 1000) as i64);
+        self.record_node_utilization(
+            &report.node_id,
+            report.available_cpu,
+            report.available_memory_gb as u32,
+            (now_ms() / 1000) as i64,
+        );
+        // Placement never offers more than the worker reports free
+        if let Err(e) = self.record_node_usage(
+            &report.node_id,
+            report.available_cpu,
+            report.available_memory_gb as u32,
+        ).await {
+            error!("Failed to record usage of {}: {}", report.node_id, e);
+        }
 
-        Ok(Response::new(ResourceAck { received: true }))
+        let dormant = self.power().state(&report.node_id) == crate::power::PowerState::Dormant;
+        Ok(Response::new(ResourceAck {
+            received: true,
+            dormant,
+            scheduler_draining: self.is_draining(),
+            scheduler_time: crate::unix_now(),
+        }))
     }
 
     async fn submit_job(
@@ -81,72 +169,7 @@ impl SchedulerService for EconomicScheduler {
         info!("Job submission: {} (type: {:?})", job_req.job_id, job_req.job_type);
 
         // Convert proto types to scheduler types
-        let job_spec = crate::JobSpec {
-            id: job_req.job_id.clone(),
-            job_type: match job_req.job_type {
-                1 => crate::JobType::Training,
-                2 => crate::JobType::Inference,
-                3 => crate::JobType::DataProcessing,
-                _ => crate::JobType::Inference,
-            },
-            resources: crate::ResourceRequirements {
-                cpu_cores: job_req.resources.as_ref()
-                    .map(|r| r.cpu_cores)
-                    .unwrap_or(1),
-                memory_gb: job_req.resources.as_ref()
-                    .map(|r| r.memory_gb)
-                    .unwrap_or(1),
-                gpu_count: job_req.resources.as_ref()
-                    .map(|r| r.gpu_count)
-                    .unwrap_or(0),
-                disk_gb: job_req.resources.as_ref()
-                    .map(|r| r.disk_gb)
-                    .unwrap_or(10),
-            },
-            sla: crate::SlaConstraints {
-                max_latency_ms: job_req.sla.as_ref()
-                    .map(|s| s.max_latency_ms)
-                    .unwrap_or(1000),
-                max_budget_usd: job_req.sla.as_ref()
-                    .and_then(|s| s.max_budget_usd),
-                deadline: job_req.sla.as_ref()
-                    .and_then(|s| s.deadline),
-            },
-        };
-
-        // Use actual scheduler with Formula 4.1
-        match self.schedule(job_spec).await {
-            Ok(placement) => {
-                info!(
-                    "Job {} scheduled to {} with Formula 4.1 TCO ${:.4}",
-                    placement.job_id,
-                    placement.node_id,
-                    placement.estimated_cost.total_usd
-                );
-
-                let response = JobSubmitResponse {
-                    success: true,
-                    job_id: placement.job_id,
-                    assigned_node: placement.node_id,
-                    cost_estimate: Some(CostEstimate {
-                        compute_cost_usd: placement.estimated_cost.compute_usd,
-                        data_transfer_usd: placement.estimated_cost.data_transfer_usd,
-                        idle_opportunity_usd: placement.estimated_cost.idle_opportunity_usd,
-                        total_cost_usd: placement.estimated_cost.total_usd,
-                        estimated_latency_ms: placement.estimated_latency_ms,
-                    }),
-                    message: format!(
-                        "Job scheduled using Formula 4.1 - TCO: ${:.4}",
-                        placement.estimated_cost.total_usd
-                    ),
-                };
-
-                Ok(Response::new(response))
-            }
-            Err(e) => {
-                Err(Status::internal(format!("Scheduling failed: {}", e)))
-            }
-        }
+        self.submit_spec(job_spec_from_request(job_req)).await
     }
 
     async fn get_job_status(
@@ -157,32 +180,7 @@ impl SchedulerService for EconomicScheduler {
         
         // Query actual job state
         match self.get_job_state(&req.job_id) {
-            Some(state) => {
-                let proto_status = match state.status {
-                    crate::JobStatus::Pending => JobStatus::Pending.into(),
-                    crate::JobStatus::Scheduled => JobStatus::Scheduled.into(),
-                    crate::JobStatus::Running => JobStatus::Running.into(),
-                    crate::JobStatus::Completed => JobStatus::Completed.into(),
-                    crate::JobStatus::Failed => JobStatus::Failed.into(),
-                };
-
-                let final_cost = state.estimated_cost.map(|cost| CostEstimate {
-                    compute_cost_usd: cost.compute_usd,
-                    data_transfer_usd: cost.data_transfer_usd,
-                    idle_opportunity_usd: cost.idle_opportunity_usd,
-                    total_cost_usd: cost.total_usd,
-                    estimated_latency_ms: 0, // TODO: track actual latency
-                });
-
-                let response = JobStatusResponse {
-                    job_id: req.job_id,
-                    status: proto_status,
-                    assigned_node: state.assigned_node.unwrap_or_default(),
-                    final_cost,
-                };
-
-                Ok(Response::new(response))
-            }
+            Some(state) => Ok(Response::new(job_status_response(state, self.queue_eta(&req.job_id)))),
             None => {
                 Err(Status::not_found(format!("Job {} not found", req.job_id)))
             }
@@ -191,28 +189,84 @@ impl SchedulerService for EconomicScheduler {
 
     async fn get_cluster_status(
         &self,
-        _request: Request<ClusterStatusRequest>,
+        request: Request<ClusterStatusRequest>,
     ) -> Result<Response<ClusterStatusResponse>, Status> {
         info!("Cluster status requested");
 
+        let cluster_version = self.cluster_version();
+        if request.into_inner().known_version == Some(cluster_version) {
+            return Ok(Response::new(ClusterStatusResponse {
+                cluster_version,
+                unchanged: true,
+                ..Default::default()
+            }));
+        }
+
         // Get actual cluster status
         let nodes_info = self.cluster_status();
         
-        let proto_nodes: Vec<NodeInfo> = nodes_info.iter().map(|node| NodeInfo {
-            node_id: node.id.clone(),
-            hostname: node.id.clone(), // TODO: store actual hostname
-            available_cpu: node.available_cpu,
-            available_memory_gb: node.available_memory_gb as f64,
-            location: node.location.clone(),
-            is_active: true,
-        }).collect();
+        let dormant_nodes = self.power().parked_nodes();
+        let efficiency: std::collections::HashMap<String, crate::efficiency::NodeEfficiency> = self
+            .node_efficiency(crate::unix_now())
+            .into_iter()
+            .map(|e| (e.node_id.clone(), e))
+            .collect();
+
+        let proto_nodes: Vec<NodeInfo> = nodes_info.iter()
+            .map(|node| (node, true))
+            .chain(dormant_nodes.iter().map(|node| (node, false)))
+            .map(|(node, is_active)| NodeInfo {
+                node_id: node.id.clone(),
+                hostname: node.id.clone(), // TODO: store actual hostname
+                available_cpu: node.available_cpu,
+                available_memory_gb: node.available_memory_gb as f64,
+                location: node.location.clone(),
+                is_active,
+                capacity_cpu: node.capacity_cpu.max(node.available_cpu),
+                capacity_memory_gb: node.capacity_memory_gb.max(node.available_memory_gb) as f64,
+                efficiency: efficiency.get(&node.id).map(|e| NodeEfficiency {
+                    paid_hours: e.paid_hours,
+                    utilized_hours: e.utilized_hours,
+                    utilization: e.utilization(),
+                    completed_jobs: e.completed_jobs,
+                    cost_usd: e.cost_usd,
+                    cost_per_completed_job_usd: e.cost_per_completed_job_usd(),
+                }),
+                pool: self.node_pools().pool_of(node).map(|pool| pool.name.clone()).unwrap_or_default(),
+                arch: node.cpu_arch().to_string(),
+                clock_skew_secs: self.clock_skew().skew_of(&node.id).map_or(0.0, |skew| skew.offset_secs),
+            })
+            .collect();
         
+        let overload = self.overload_status().await;
         let response = ClusterStatusResponse {
-            total_nodes: nodes_info.len() as u32,
+            total_nodes: (nodes_info.len() + dormant_nodes.len()) as u32,
             active_nodes: nodes_info.len() as u32,
             total_jobs: 0, // TODO: track total jobs
             running_jobs: 0, // TODO: track running jobs
             nodes: proto_nodes,
+            overload: Some(OverloadStatus {
+                overloaded: overload.overloaded,
+                reason: overload.reason,
+                placement_latency_ms: overload.placement_latency_ms,
+                queue_depth: overload.queue_depth as u64,
+                shed_jobs: overload.shed_jobs,
+            }),
+            cluster_version,
+            unchanged: false,
+            pools: self.pool_status(crate::unix_now())
+                .into_iter()
+                .map(|pool| PoolStatus {
+                    name: pool.name,
+                    dedicated: pool.dedicated,
+                    nodes: pool.nodes as u32,
+                    cpu_cores: pool.cpu_cores,
+                    memory_gb: pool.memory_gb,
+                    gpus: pool.gpus,
+                    cost_per_hour: pool.cost_per_hour,
+                    utilization: pool.utilization,
+                })
+                .collect(),
         };
 
         Ok(Response::new(response))
@@ -241,7 +295,16 @@ impl SchedulerService for EconomicScheduler {
         request: Request<JobStatusUpdate>,
     ) -> Result<Response<JobStatusUpdateAck>, Status> {
         let update = request.into_inner();
-        
+        // A superseded worker process must not overwrite the current one's reports
+        let node_id = match update.node_id.as_str() {
+            "" => self.get_job_state(&update.job_id).and_then(|state| state.assigned_node),
+            node_id => Some(node_id.to_string()),
+        };
+        if let Some(node_id) = &node_id {
+            self.check_fencing(node_id, update.fencing_token)
+                .map_err(|e| error_status(e.into(), Code::FailedPrecondition))?;
+        }
+
         info!(
             "Job status update: {} -> {:?} (exit code: {})",
             update.job_id, update.status, update.exit_code
@@ -252,16 +315,1650 @@ impl SchedulerService for EconomicScheduler {
             3 => crate::JobStatus::Running,
             4 => crate::JobStatus::Completed,
             5 => crate::JobStatus::Failed,
+            6 => crate::JobStatus::Cancelled,
             _ => crate::JobStatus::Running,
         };
+        self.record_trace(TraceRecord::JobStatusUpdate {
+            at_ms: now_ms(),
+            job_id: update.job_id.clone(),
+            status: status.clone(),
+        });
+
+        // Peak usage must be in before completion turns the run into an outcome
+        if update.peak_memory_gb > 0.0 {
+            self.predictor().record_peak_memory(&update.job_id, update.peak_memory_gb);
+        }
+        self.usage_history().record_peak(&update.job_id, update.peak_memory_gb, update.peak_cpu_cores);
 
-        if let Err(e) = self.update_job_state(update.job_id, status, None) {
-            error!("Failed to update job state: {}", e);
+        if status.is_terminal() {
+            if !update.logs.is_empty() {
+                self.outputs.put_log_tail(&update.job_id, update.logs.clone());
+            }
+            if let Err(e) = self.verify_finished(&update.job_id, &status, &update.output_hash).await {
+                error!("Failed to verify result of {}: {}", update.job_id, e);
+            }
+            if let Err(e) = self.build_finished(&update.job_id, &status).await {
+                error!("Failed to record build {}: {}", update.job_id, e);
+            }
+        }
+
+        let phase = match JobPhase::try_from(update.phase) {
+            Ok(JobPhase::PreStart) => Some(crate::lifecycle::JobPhase::PreStart),
+            Ok(JobPhase::Main) => Some(crate::lifecycle::JobPhase::Main),
+            Ok(JobPhase::PostComplete) => Some(crate::lifecycle::JobPhase::PostComplete),
+            Ok(JobPhase::Unspecified) | Err(_) => None,
+        };
+        if let Some(phase) = phase.filter(|_| !status.is_terminal()) {
+            if let Err(e) = self.report_job_phase(&update.job_id, phase) {
+                error!("Failed to record phase of {}: {}", update.job_id, e);
+            }
+        }
+
+        match self.update_job_state(update.job_id, status, None) {
+            // A late report from a worker, e.g. after the job was cancelled
+            Err(e) if matches!(crate::error::classify(&e), Some(crate::error::SchedulerError::InvalidTransition { .. })) => {
+                warn!("Ignoring status update: {}", e);
+            }
+            Err(e) => error!("Failed to update job state: {}", e),
+            Ok(()) => {}
         }
 
         let response = JobStatusUpdateAck { received: true };
         Ok(Response::new(response))
     }
+
+    async fn report_job_progress(
+        &self,
+        request: Request<JobProgressReport>,
+    ) -> Result<Response<JobProgressAck>, Status> {
+        let report = request.into_inner();
+        let progress = report.progress.unwrap_or_default();
+
+        info!(
+            "Job progress: {} on {} -> {:.1}% ({})",
+            report.job_id, report.node_id, progress.percent, progress.step
+        );
+
+        let progress = crate::JobProgress {
+            percent: progress.percent.clamp(0.0, 100.0),
+            step: progress.step,
+            metrics: progress.metrics,
+            updated_at: self.clock_skew().normalize(&report.node_id, progress.updated_at),
+        };
+
+        self.report_job_progress(&report.job_id, progress)
+            .map_err(|e| Status::not_found(e.to_string()))?;
+
+        Ok(Response::new(JobProgressAck { received: true }))
+    }
+
+    type WatchClusterVersionStream = ReceiverStream<Result<ClusterVersion, Status>>;
+
+    async fn watch_cluster_version(
+        &self,
+        request: Request<ClusterStatusRequest>,
+    ) -> Result<Response<Self::WatchClusterVersionStream>, Status> {
+        let mut known = request.into_inner().known_version;
+        let mut versions = self.watch_cluster_version();
+        let (tx, rx) = mpsc::channel(16);
+
+        tokio::spawn(async move {
+            loop {
+                // Changes in between are coalesced into the latest version
+                let version = *versions.borrow_and_update();
+                if known != Some(version) {
+                    if tx.send(Ok(ClusterVersion { version })).await.is_err() {
+                        return;
+                    }
+                    known = Some(version);
+                }
+                if versions.changed().await.is_err() {
+                    return;
+                }
+            }
+        });
+
+        Ok(Response::new(ReceiverStream::new(rx)))
+    }
+
+    type WatchJobStream = ReceiverStream<Result<JobStatusResponse, Status>>;
+
+    async fn watch_job(
+        &self,
+        request: Request<JobStatusRequest>,
+    ) -> Result<Response<Self::WatchJobStream>, Status> {
+        let job_id = request.into_inner().job_id;
+
+        // Subscribe before reading the initial state so no update is missed
+        let mut updates = self.subscribe_job_updates();
+        let initial = self.get_job_state(&job_id)
+            .ok_or_else(|| Status::not_found(format!("Job {} not found", job_id)))?;
+
+        info!("Watching job {}", job_id);
+
+        let scheduler = self.clone();
+        let (tx, rx) = mpsc::channel(16);
+
+        tokio::spawn(async move {
+            let mut finished = initial.status.is_terminal();
+            let mut pending = initial.status == crate::JobStatus::Pending;
+            if tx.send(Ok(job_status_response(initial, scheduler.queue_eta(&job_id)))).await.is_err() {
+                return;
+            }
+
+            // A pending job's ETA moves as other jobs finish, so resend it
+            // periodically even without an update to this job
+            let mut eta_refresh = tokio::time::interval(ETA_REFRESH);
+            eta_refresh.tick().await;
+
+            while !finished {
+                tokio::select! {
+                    update = updates.recv() => match update {
+                        Ok(updated) if updated == job_id => {}
+                        Ok(_) => continue,
+                        // Missed some updates: fall through and re-read current state
+                        Err(broadcast::error::RecvError::Lagged(_)) => {}
+                        Err(broadcast::error::RecvError::Closed) => break,
+                    },
+                    _ = eta_refresh.tick(), if pending => {}
+                }
+
+                let Some(state) = scheduler.get_job_state(&job_id) else {
+                    break;
+                };
+                finished = state.status.is_terminal();
+                pending = state.status == crate::JobStatus::Pending;
+
+                if tx.send(Ok(job_status_response(state, scheduler.queue_eta(&job_id)))).await.is_err() {
+                    break; // Client went away
+                }
+            }
+        });
+
+        Ok(Response::new(ReceiverStream::new(rx)))
+    }
+
+    async fn get_job_history(
+        &self,
+        request: Request<JobStatusRequest>,
+    ) -> Result<Response<JobHistoryResponse>, Status> {
+        let job_id = request.into_inner().job_id;
+        if self.get_job_state(&job_id).is_none() {
+            return Err(Status::not_found(format!("Job {} not found", job_id)));
+        }
+
+        let transitions = self.job_history(&job_id)
+            .into_iter()
+            .map(|transition| JobTransition {
+                from: transition.from.as_ref().map_or(JobStatus::Unspecified.into(), proto_job_status),
+                to: proto_job_status(&transition.to),
+                node_id: transition.node_id.unwrap_or_default(),
+                at: transition.at,
+            })
+            .collect();
+        Ok(Response::new(JobHistoryResponse { job_id, transitions }))
+    }
+
+    async fn get_dataset_sources(
+        &self,
+        request: Request<DatasetSourcesRequest>,
+    ) -> Result<Response<DatasetSourcesResponse>, Status> {
+        let req = request.into_inner();
+
+        let info = self.dataset_registry()
+            .get(&req.dataset)
+            .ok_or_else(|| Status::not_found(format!("Dataset {} not found", req.dataset)))?;
+
+        let sources = self.dataset_sources(&req.dataset, &req.requester_node_id)
+            .into_iter()
+            .map(|node| DatasetSource {
+                node_id: node.id,
+                data_service_addr: node.data_service_addr,
+                location: node.location,
+            })
+            .collect();
+
+        Ok(Response::new(DatasetSourcesResponse {
+            dataset: req.dataset,
+            size_gb: info.size_gb,
+            sources,
+        }))
+    }
+
+    async fn report_transfer(
+        &self,
+        request: Request<TransferReport>,
+    ) -> Result<Response<TransferAck>, Status> {
+        let report = request.into_inner();
+
+        self.record_transfer(crate::transfers::TransferRecord {
+            transfer_id: report.transfer_id,
+            dataset: report.dataset,
+            source_node: report.source_node_id,
+            dest_node: report.dest_node_id,
+            bytes_transferred: report.bytes_transferred,
+            total_bytes: report.total_bytes,
+            elapsed_ms: report.elapsed_ms,
+            completed: report.completed,
+            error: (!report.error_message.is_empty()).then_some(report.error_message),
+            wire_bytes: report.wire_bytes,
+            delta_sync: report.delta_sync,
+            reused_bytes: report.reused_bytes,
+        });
+
+        Ok(Response::new(TransferAck { received: true }))
+    }
+
+    async fn report_interruption(
+        &self,
+        request: Request<InterruptionNotice>,
+    ) -> Result<Response<InterruptionAck>, Status> {
+        let notice = request.into_inner();
+        info!("Interruption notice for node {}: {}", notice.node_id, notice.reason);
+
+        let terminate_at = (notice.terminate_at > 0)
+            .then(|| self.clock_skew().normalize(&notice.node_id, notice.terminate_at));
+        match self.handle_interruption(&notice.node_id, terminate_at).await {
+            Ok(requeued_jobs) => Ok(Response::new(InterruptionAck { received: true, requeued_jobs })),
+            Err(e) => {
+                error!("Failed to handle interruption of {}: {}", notice.node_id, e);
+                Err(Status::internal(e.to_string()))
+            }
+        }
+    }
+
+    async fn submit_job_array(
+        &self,
+        request: Request<JobArraySubmitRequest>,
+    ) -> Result<Response<JobArraySubmitResponse>, Status> {
+        let req = request.into_inner();
+        let _in_flight = self.begin_placement()
+            .ok_or_else(|| Status::unavailable("Scheduler is shutting down"))?;
+        let template = req.template
+            .ok_or_else(|| Status::invalid_argument("Job array needs a template"))?;
+        let template = self.admit(job_spec_from_request(template))
+            .await
+            .map_err(|e| error_status(e, Code::PermissionDenied))?;
+        let array_id = template.id.clone();
+        info!("Job array submission: {} x{}", array_id, req.size);
+
+        match self.submit_array(template, req.size, req.max_parallel, req.max_failures).await {
+            Ok(task_ids) => Ok(Response::new(JobArraySubmitResponse {
+                success: true,
+                array_id,
+                message: format!("{} tasks submitted", task_ids.len()),
+                task_ids,
+            })),
+            Err(e) => Err(error_status(e, Code::InvalidArgument)),
+        }
+    }
+
+    async fn get_job_array_status(
+        &self,
+        request: Request<JobArrayStatusRequest>,
+    ) -> Result<Response<JobArrayStatusResponse>, Status> {
+        let req = request.into_inner();
+        let status = self.array_status(&req.array_id)
+            .ok_or_else(|| Status::not_found(format!("Job array {} not found", req.array_id)))?;
+
+        let state = match status.state {
+            crate::arrays::ArrayState::Running => JobArrayState::Running,
+            crate::arrays::ArrayState::Completed => JobArrayState::Completed,
+            crate::arrays::ArrayState::PartiallyFailed => JobArrayState::PartiallyFailed,
+            crate::arrays::ArrayState::Failed => JobArrayState::Failed,
+        };
+        Ok(Response::new(JobArrayStatusResponse {
+            array_id: status.array_id,
+            state: state.into(),
+            total: status.total,
+            pending: status.pending,
+            running: status.running,
+            completed: status.completed,
+            failed: status.failed,
+            cancelled: status.cancelled,
+        }))
+    }
+
+    async fn submit_workflow(
+        &self,
+        request: Request<WorkflowSubmitRequest>,
+    ) -> Result<Response<WorkflowSubmitResponse>, Status> {
+        let req = request.into_inner();
+        let _in_flight = self.begin_placement()
+            .ok_or_else(|| Status::unavailable("Scheduler is shutting down"))?;
+        info!("Workflow submission: {} ({} steps)", req.workflow_id, req.steps.len());
+
+        let steps = req.steps.into_iter()
+            .map(|step| crate::workflows::WorkflowStep {
+                name: step.name,
+                job: step.job.map(job_spec_from_request).unwrap_or_default(),
+                retry: crate::workflows::RetryPolicy {
+                    max_attempts: step.max_attempts.max(1),
+                    backoff_secs: step.retry_backoff_secs,
+                },
+                on_success: (!step.on_success.is_empty()).then_some(step.on_success),
+                on_failure: (!step.on_failure.is_empty()).then_some(step.on_failure),
+            })
+            .collect();
+        let spec = crate::workflows::WorkflowSpec { id: req.workflow_id.clone(), steps };
+
+        match self.submit_workflow(spec).await {
+            Ok(()) => Ok(Response::new(WorkflowSubmitResponse {
+                success: true,
+                workflow_id: req.workflow_id,
+                message: "Workflow started".to_string(),
+            })),
+            Err(e) => Err(error_status(e, Code::InvalidArgument)),
+        }
+    }
+
+    async fn get_workflow(
+        &self,
+        request: Request<WorkflowStatusRequest>,
+    ) -> Result<Response<WorkflowStatusResponse>, Status> {
+        let req = request.into_inner();
+        let workflow = self.get_workflow(&req.workflow_id)
+            .ok_or_else(|| Status::not_found(format!("Workflow {} not found", req.workflow_id)))?;
+
+        let state = match workflow.state {
+            crate::workflows::WorkflowState::Running => WorkflowState::Running,
+            crate::workflows::WorkflowState::Succeeded => WorkflowState::Succeeded,
+            crate::workflows::WorkflowState::Failed => WorkflowState::Failed,
+        };
+        let runs = workflow.runs.iter()
+            .map(|run| WorkflowStepRun {
+                step: run.step.clone(),
+                attempt: run.attempt,
+                job_id: run.job_id.clone(),
+                status: proto_job_status(&run.status),
+            })
+            .collect();
+        let artifacts = workflow.artifacts.into_iter()
+            .flat_map(|(step, values)| {
+                values.into_iter().map(move |(name, value)| (format!("{}.{}", step, name), value))
+            })
+            .collect();
+
+        Ok(Response::new(WorkflowStatusResponse {
+            workflow_id: workflow.id,
+            state: state.into(),
+            current_step: workflow.current_step.unwrap_or_default(),
+            runs,
+            artifacts,
+        }))
+    }
+
+    async fn report_artifacts(
+        &self,
+        request: Request<ArtifactReport>,
+    ) -> Result<Response<ArtifactAck>, Status> {
+        let report = request.into_inner();
+        info!("Job {} reported {} artifacts", report.job_id, report.artifacts.len());
+
+        self.record_artifacts(&report.job_id, report.artifacts.into_iter().collect())
+            .map_err(|e| Status::internal(e.to_string()))?;
+        Ok(Response::new(ArtifactAck { received: true }))
+    }
+
+    async fn create_job_template(
+        &self,
+        request: Request<CreateJobTemplateRequest>,
+    ) -> Result<Response<CreateJobTemplateResponse>, Status> {
+        let req = request.into_inner();
+        let template = crate::templates::JobTemplate {
+            name: req.name.clone(),
+            description: req.description,
+            job: req.job.map(job_spec_from_request).unwrap_or_default(),
+        };
+
+        match self.put_template(template, req.replace) {
+            Ok(()) => Ok(Response::new(CreateJobTemplateResponse {
+                success: true,
+                message: format!("Template {} registered", req.name),
+            })),
+            Err(e) => Err(error_status(e, Code::AlreadyExists)),
+        }
+    }
+
+    async fn list_job_templates(
+        &self,
+        _request: Request<ListJobTemplatesRequest>,
+    ) -> Result<Response<ListJobTemplatesResponse>, Status> {
+        let templates = self.list_templates()
+            .into_iter()
+            .map(|template| JobTemplateInfo {
+                parameters: template.parameters(),
+                name: template.name,
+                description: template.description,
+                container_image: template.job.container_image,
+                resources: Some(ResourceRequirements {
+                    cpu_cores: template.job.resources.cpu_cores,
+                    memory_gb: template.job.resources.memory_gb,
+                    gpu_count: template.job.resources.gpu_count,
+                    disk_gb: template.job.resources.disk_gb,
+                    network_mbps: template.job.resources.network_mbps,
+                    disk_io_mbps: template.job.resources.disk_io_mbps,
+                }),
+            })
+            .collect();
+        Ok(Response::new(ListJobTemplatesResponse { templates }))
+    }
+
+    async fn submit_from_template(
+        &self,
+        request: Request<SubmitFromTemplateRequest>,
+    ) -> Result<Response<JobSubmitResponse>, Status> {
+        let req = request.into_inner();
+        info!("Job submission: {} (template: {})", req.job_id, req.template_name);
+
+        let overrides = req.overrides.unwrap_or_default();
+        let overrides = crate::templates::TemplateOverrides {
+            container_image: overrides.container_image,
+            command: (!overrides.command.is_empty()).then_some(overrides.command),
+            env: overrides.environment,
+            cpu_cores: overrides.cpu_cores,
+            memory_gb: overrides.memory_gb,
+            gpu_count: overrides.gpu_count,
+            max_budget_usd: overrides.max_budget_usd,
+            deadline: overrides.deadline,
+            parameters: overrides.parameters,
+        };
+        let job_spec = self.instantiate_template(&req.template_name, &req.job_id, overrides)
+            .map_err(|e| error_status(e, Code::InvalidArgument))?;
+        self.submit_spec(job_spec).await
+    }
+
+    async fn set_node_price(
+        &self,
+        request: Request<SetNodePriceRequest>,
+    ) -> Result<Response<SetNodePriceResponse>, Status> {
+        let token = crate::rbac::bearer_token(request.metadata()).map(str::to_string);
+        let req = request.into_inner();
+        self.access_control()
+            .authorize_provider(token.as_deref(), &req.provider_id, "price nodes")
+            .map_err(|e| error_status(e.into(), Code::PermissionDenied))?;
+
+        self.set_node_price(&req.provider_id, &req.node_id, req.cost_per_hour)
+            .map_err(|e| error_status(e, Code::FailedPrecondition))?;
+        let moved = self.recheck_hourly_rates(&req.node_id)
+            .await
+            .map_err(|e| error_status(e, Code::Internal))?;
+
+        let mut message = format!("Node {} priced at ${:.4}/h", req.node_id, req.cost_per_hour);
+        if !moved.is_empty() {
+            message.push_str(&format!("; requeued {} jobs over their hourly cap", moved.len()));
+        }
+        Ok(Response::new(SetNodePriceResponse {
+            success: true,
+            message,
+            cluster_version: self.cluster_version(),
+            next_update_at: self.node_pricing().next_update_at(&req.node_id),
+            requeued_jobs: moved,
+        }))
+    }
+
+    async fn get_provider_earnings(
+        &self,
+        request: Request<ProviderEarningsRequest>,
+    ) -> Result<Response<ProviderEarningsResponse>, Status> {
+        let req = request.into_inner();
+        let earnings = self.provider_ledger().earnings(&req.provider_id, req.since, req.until);
+
+        Ok(Response::new(ProviderEarningsResponse {
+            provider_id: earnings.provider_id,
+            jobs: earnings.jobs,
+            compute_hours: earnings.compute_hours,
+            charged_usd: earnings.charged_usd,
+            commission_usd: earnings.commission_usd,
+            credited_usd: earnings.credited_usd,
+            settled_usd: earnings.settled_usd,
+            unsettled_usd: earnings.unsettled_usd,
+        }))
+    }
+
+    async fn settle_provider(
+        &self,
+        request: Request<SettleProviderRequest>,
+    ) -> Result<Response<SettleProviderResponse>, Status> {
+        self.access_control()
+            .authorize(crate::rbac::bearer_token(request.metadata()), crate::rbac::Role::Operator, "settle providers")
+            .map_err(|e| error_status(e.into(), Code::PermissionDenied))?;
+        let req = request.into_inner();
+
+        let settlement = self.provider_ledger()
+            .settle(&req.provider_id, crate::unix_now())
+            .map_err(|e| Status::internal(e.to_string()))?;
+        let response = match settlement {
+            Some(settlement) => SettleProviderResponse {
+                settlement_id: settlement.settlement_id,
+                amount_usd: settlement.amount_usd,
+                entries: settlement.entries,
+                settled_at: settlement.settled_at,
+            },
+            None => SettleProviderResponse::default(),
+        };
+        Ok(Response::new(response))
+    }
+
+    async fn top_up_credits(
+        &self,
+        request: Request<TopUpCreditsRequest>,
+    ) -> Result<Response<CreditBalance>, Status> {
+        self.access_control()
+            .authorize(crate::rbac::bearer_token(request.metadata()), crate::rbac::Role::Operator, "top up credits")
+            .map_err(|e| error_status(e.into(), Code::PermissionDenied))?;
+        let req = request.into_inner();
+
+        let reference = (!req.reference.is_empty()).then_some(req.reference.as_str());
+        let balance = self.tenant_credits()
+            .top_up(&req.tenant, req.amount_usd, reference)
+            .map_err(|e| error_status(e.into(), Code::InvalidArgument))?;
+        Ok(Response::new(credit_balance_to_proto(balance, self.tenant_credits().is_enforced())))
+    }
+
+    async fn get_credit_balance(
+        &self,
+        request: Request<CreditBalanceRequest>,
+    ) -> Result<Response<CreditBalance>, Status> {
+        let balance = self.tenant_credits().balance(&request.into_inner().tenant);
+        Ok(Response::new(credit_balance_to_proto(balance, self.tenant_credits().is_enforced())))
+    }
+
+    async fn get_demand_signals(
+        &self,
+        _request: Request<DemandSignalsRequest>,
+    ) -> Result<Response<DemandSignals>, Status> {
+        let signals = self.demand_signals().map_err(|e| error_status(e, Code::Internal))?;
+        Ok(Response::new(demand_signals_to_proto(signals)))
+    }
+
+    type WatchDemandSignalsStream = ReceiverStream<Result<DemandSignals, Status>>;
+
+    async fn watch_demand_signals(
+        &self,
+        request: Request<DemandSignalsRequest>,
+    ) -> Result<Response<Self::WatchDemandSignalsStream>, Status> {
+        let interval_secs = match request.into_inner().interval_secs {
+            0 => 30,
+            secs => secs,
+        };
+        let scheduler = self.clone();
+        let (tx, rx) = mpsc::channel(4);
+
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(std::time::Duration::from_secs(interval_secs as u64));
+            loop {
+                ticker.tick().await;
+                let signals = scheduler.demand_signals()
+                    .map(demand_signals_to_proto)
+                    .map_err(|e| error_status(e, Code::Internal));
+                if tx.send(signals).await.is_err() {
+                    return;
+                }
+            }
+        });
+
+        Ok(Response::new(ReceiverStream::new(rx)))
+    }
+
+    async fn get_capacity_report(
+        &self,
+        request: Request<CapacityReportRequest>,
+    ) -> Result<Response<CapacityReportResponse>, Status> {
+        let req = request.into_inner();
+        let horizon_hours = if req.horizon_hours > 0.0 { req.horizon_hours } else { 168.0 };
+        let report = self.capacity_report(horizon_hours, crate::unix_now());
+
+        Ok(Response::new(CapacityReportResponse {
+            generated_at: report.generated_at,
+            horizon_hours: report.horizon_hours,
+            samples: report.samples,
+            queued_jobs: report.queued_jobs,
+            queue_growth_per_hour: report.queue_growth_per_hour,
+            dimensions: report.dimensions.into_iter()
+                .map(|d| ResourceForecast {
+                    resource: d.resource.as_str().to_string(),
+                    capacity: d.capacity,
+                    demand: d.demand,
+                    utilization: d.utilization,
+                    growth_per_hour: d.growth_per_hour,
+                    hours_to_saturation: d.hours_to_saturation,
+                })
+                .collect(),
+            recommendations: report.recommendations.into_iter()
+                .map(|r| NodeRecommendation {
+                    offering: r.offering,
+                    count: r.count,
+                    resource: r.resource.as_str().to_string(),
+                    added_cost_per_hour: r.added_cost_per_hour,
+                    added_cost_per_month: r.added_cost_per_month,
+                })
+                .collect(),
+        }))
+    }
+
+    async fn query_metrics(
+        &self,
+        request: Request<MetricsQueryRequest>,
+    ) -> Result<Response<MetricsQueryResponse>, Status> {
+        let req = request.into_inner();
+        let until = if req.until > 0 { req.until } else { crate::unix_now() + 1 };
+        let label = (!req.label.is_empty()).then_some(req.label.as_str());
+
+        let series = self.metrics_history()
+            .query(&req.metric, label, req.since, until, req.step_secs)
+            .into_iter()
+            .map(|series| MetricSeries {
+                metric: series.metric,
+                label: series.label,
+                points: series.points.into_iter()
+                    .map(|(at, value)| MetricPoint { at, value })
+                    .collect(),
+            })
+            .collect();
+        Ok(Response::new(MetricsQueryResponse { series }))
+    }
+
+    async fn get_cost_summary(
+        &self,
+        request: Request<CostSummaryRequest>,
+    ) -> Result<Response<CostSummaryResponse>, Status> {
+        let req = request.into_inner();
+        let until = if req.until > 0 { req.until } else { crate::unix_now() + 1 };
+        if req.since >= until {
+            return Err(Status::invalid_argument("since must be before until"));
+        }
+
+        let summary = self.cost_summary(req.since, until);
+        Ok(Response::new(CostSummaryResponse {
+            since: summary.since,
+            until: summary.until,
+            total: Some(cost_breakdown(&summary.total)),
+            nodes: summary.nodes.iter()
+                .map(|(node_id, cost)| NodeCost { node_id: node_id.clone(), cost: Some(cost_breakdown(cost)) })
+                .collect(),
+            tenants: summary.tenants.iter()
+                .map(|(tenant, cost)| TenantCost { tenant: tenant.clone(), cost: Some(cost_breakdown(cost)) })
+                .collect(),
+            pools: summary.pools.iter()
+                .map(|(pool, cost)| PoolCost { pool: pool.clone(), cost: Some(cost_breakdown(cost)) })
+                .collect(),
+            market_rates: summary.market_rates.iter()
+                .map(|(class, rate)| MarketRate { resource_class: class.clone(), usd_per_unit_hour: *rate })
+                .collect(),
+        }))
+    }
+
+    async fn get_right_sizing(
+        &self,
+        request: Request<RightSizingRequest>,
+    ) -> Result<Response<RightSizingResponse>, Status> {
+        let req = request.into_inner();
+        let template = (!req.template.is_empty()).then_some(req.template.as_str());
+
+        let recommendations = self.right_sizing(template)
+            .map_err(|e| error_status(e, Code::Internal))?
+            .into_iter()
+            .map(|r| RightSizingRecommendation {
+                summary: r.to_string(),
+                template: r.template,
+                runs: r.runs as u32,
+                requested_cpu_cores: r.requested_cpu_cores,
+                requested_memory_gb: r.requested_memory_gb,
+                peak_cpu_cores: r.peak_cpu_cores,
+                peak_memory_gb: r.peak_memory_gb,
+                recommended_cpu_cores: r.recommended_cpu_cores,
+                recommended_memory_gb: r.recommended_memory_gb,
+                saving_per_run_usd: r.saving_per_run_usd,
+            })
+            .collect();
+        Ok(Response::new(RightSizingResponse { recommendations }))
+    }
+
+    type CommandStreamStream = UnboundedReceiverStream<Result<SchedulerCommand, Status>>;
+
+    async fn command_stream(
+        &self,
+        request: Request<Streaming<WorkerStreamMessage>>,
+    ) -> Result<Response<Self::CommandStreamStream>, Status> {
+        let mut inbound = request.into_inner();
+        let hello = match inbound.message().await? {
+            Some(WorkerStreamMessage { body: Some(worker_stream_message::Body::Hello(hello)) })
+                if !hello.node_id.is_empty() => hello,
+            _ => return Err(Status::invalid_argument("Command stream must start with a hello")),
+        };
+        self.check_fencing(&hello.node_id, hello.fencing_token)
+            .map_err(|e| error_status(e.into(), Code::FailedPrecondition))?;
+        let node_id = hello.node_id;
+
+        let relay = self.command_relay().clone();
+        let (tx, rx) = mpsc::unbounded_channel();
+        let connection = relay.connect(&node_id, tx)
+            .map_err(|e| Status::internal(e.to_string()))?;
+
+        tokio::spawn(async move {
+            loop {
+                match inbound.message().await {
+                    Ok(Some(WorkerStreamMessage { body: Some(worker_stream_message::Body::Ack(ack)) })) => {
+                        relay.acknowledge(&node_id, ack);
+                    }
+                    Ok(Some(_)) => {}
+                    Ok(None) => break,
+                    Err(e) => {
+                        info!("Command stream of node {} failed: {}", node_id, e);
+                        break;
+                    }
+                }
+            }
+            relay.disconnect(&node_id, connection);
+        });
+
+        Ok(Response::new(UnboundedReceiverStream::new(rx)))
+    }
+
+    type SpeedTestStream = ReceiverStream<Result<SpeedTestChunk, Status>>;
+
+    async fn speed_test(
+        &self,
+        request: Request<SpeedTestRequest>,
+    ) -> Result<Response<Self::SpeedTestStream>, Status> {
+        let size_mb = request.into_inner().size_mb.clamp(1, SPEED_TEST_MAX_MB);
+        let (tx, rx) = mpsc::channel(4);
+
+        tokio::spawn(async move {
+            let chunk = vec![0xA5u8; 1024 * 1024];
+            for _ in 0..size_mb {
+                if tx.send(Ok(SpeedTestChunk { data: chunk.clone() })).await.is_err() {
+                    break;
+                }
+            }
+        });
+
+        Ok(Response::new(ReceiverStream::new(rx)))
+    }
+
+    async fn upload_job_output(
+        &self,
+        request: Request<Streaming<JobOutputChunk>>,
+    ) -> Result<Response<JobOutputAck>, Status> {
+        let mut chunks = request.into_inner();
+        let max_bytes = self.job_outputs().limits().max_bytes;
+
+        let mut job_id = String::new();
+        let mut data = Vec::new();
+        while let Some(chunk) = chunks.message().await? {
+            if job_id.is_empty() {
+                job_id = chunk.job_id;
+                if self.get_job_state(&job_id).is_none() {
+                    return Err(Status::not_found(format!("Job {} not found", job_id)));
+                }
+            }
+            // Refused before more than the cap is buffered
+            if (data.len() + chunk.data.len()) as u64 > max_bytes {
+                return Err(Status::resource_exhausted(format!(
+                    "Output of job {} is over the {} byte limit",
+                    job_id, max_bytes
+                )));
+            }
+            data.extend_from_slice(&chunk.data);
+        }
+        if job_id.is_empty() {
+            return Err(Status::invalid_argument("Output upload must name its job"));
+        }
+
+        let size_bytes = self.job_outputs()
+            .put(&job_id, data)
+            .await
+            .map_err(|e| error_status(e, Code::Internal))?;
+        info!("Stored {} byte output of job {}", size_bytes, job_id);
+        Ok(Response::new(JobOutputAck { received: true, size_bytes }))
+    }
+
+    type GetJobOutputStream = ReceiverStream<Result<JobOutputChunk, Status>>;
+
+    async fn get_job_output(
+        &self,
+        request: Request<JobOutputRequest>,
+    ) -> Result<Response<Self::GetJobOutputStream>, Status> {
+        let job_id = request.into_inner().job_id;
+        let output = self.job_output(&job_id)
+            .await
+            .map_err(|e| error_status(e, Code::Internal))?
+            .ok_or_else(|| Status::not_found(format!("Job {} has no output", job_id)))?;
+        let (tx, rx) = mpsc::channel(4);
+
+        tokio::spawn(async move {
+            // An empty output is still sent as one (empty) chunk
+            let mut offset = 0;
+            loop {
+                let end = (offset + OUTPUT_CHUNK_BYTES).min(output.len());
+                let chunk = JobOutputChunk {
+                    job_id: if offset == 0 { job_id.clone() } else { String::new() },
+                    data: output[offset..end].to_vec(),
+                };
+                if tx.send(Ok(chunk)).await.is_err() || end == output.len() {
+                    break;
+                }
+                offset = end;
+            }
+        });
+
+        Ok(Response::new(ReceiverStream::new(rx)))
+    }
+
+    type ExecInJobStream = ReceiverStream<Result<ExecInJobResponse, Status>>;
+
+    async fn exec_in_job(
+        &self,
+        request: Request<Streaming<ExecInJobRequest>>,
+    ) -> Result<Response<Self::ExecInJobStream>, Status> {
+        use crate::commands::worker_proto::{self, exec_request, exec_response};
+
+        let role = self.access_control()
+            .authorize(crate::rbac::bearer_token(request.metadata()), crate::rbac::Role::Operator, "exec into jobs")
+            .map_err(|e| error_status(e.into(), Code::PermissionDenied))?;
+        let mut inbound = request.into_inner();
+        let start = match inbound.message().await? {
+            Some(ExecInJobRequest { body: Some(exec_in_job_request::Body::Start(start)) })
+                if !start.job_id.is_empty() && !start.command.is_empty() => start,
+            _ => return Err(Status::invalid_argument("Exec must start with a job id and command")),
+        };
+        let job_id = start.job_id.clone();
+        info!("Exec into job {}: {:?}", job_id, start.command);
+
+        // Stdin is relayed to the worker until the caller closes its side
+        let (up_tx, up_rx) = mpsc::channel(16);
+        let _ = up_tx.send(worker_proto::ExecRequest {
+            body: Some(exec_request::Body::Start(worker_proto::ExecStart {
+                job_id: start.job_id,
+                command: start.command,
+                tty: start.tty,
+            })),
+        }).await;
+        tokio::spawn(async move {
+            while let Ok(Some(message)) = inbound.message().await {
+                if let Some(exec_in_job_request::Body::Stdin(data)) = message.body {
+                    let stdin = worker_proto::ExecRequest { body: Some(exec_request::Body::Stdin(data)) };
+                    if up_tx.send(stdin).await.is_err() {
+                        break;
+                    }
+                }
+            }
+        });
+
+        let mut output = self.open_exec(&job_id, role, ReceiverStream::new(up_rx))
+            .await
+            .map_err(|e| error_status(e, Code::FailedPrecondition))?;
+        let (tx, rx) = mpsc::channel(16);
+        tokio::spawn(async move {
+            loop {
+                let body = match output.message().await {
+                    Ok(Some(worker_proto::ExecResponse { body: Some(body) })) => match body {
+                        exec_response::Body::Stdout(data) => exec_in_job_response::Body::Stdout(data),
+                        exec_response::Body::Stderr(data) => exec_in_job_response::Body::Stderr(data),
+                        exec_response::Body::ExitCode(code) => exec_in_job_response::Body::ExitCode(code),
+                    },
+                    Ok(Some(_)) => continue,
+                    Ok(None) => break,
+                    Err(status) => {
+                        let _ = tx.send(Err(status)).await;
+                        break;
+                    }
+                };
+                if tx.send(Ok(ExecInJobResponse { body: Some(body) })).await.is_err() {
+                    break;
+                }
+            }
+            info!("Exec into job {} ended", job_id);
+        });
+
+        Ok(Response::new(ReceiverStream::new(rx)))
+    }
+
+    async fn submit_build(
+        &self,
+        request: Request<BuildSubmitRequest>,
+    ) -> Result<Response<BuildSubmitResponse>, Status> {
+        let req = request.into_inner();
+        let dockerfile = match req.dockerfile.as_str() {
+            "" => "Dockerfile",
+            path => path,
+        };
+        info!("Build submission: {} ({} byte context)", req.build_id, req.context.len());
+
+        match self.submit_build(&req.build_id, &req.tenant, req.context, dockerfile).await {
+            Ok(job_id) => Ok(Response::new(BuildSubmitResponse {
+                success: true,
+                build_id: req.build_id.clone(),
+                job_id,
+                message: format!("Building image {}", req.build_id),
+            })),
+            Err(e) => Err(error_status(e, Code::Internal)),
+        }
+    }
+
+    async fn get_build(
+        &self,
+        request: Request<BuildStatusRequest>,
+    ) -> Result<Response<BuildStatusResponse>, Status> {
+        use crate::builds::BuildStatus;
+
+        let build_id = request.into_inner().build_id;
+        let record = self.image_builds()
+            .get(&build_id)
+            .ok_or_else(|| Status::not_found(format!("Build {} not found", build_id)))?;
+        let (status, image, message) = match record.status {
+            BuildStatus::Building => ("building", String::new(), String::new()),
+            BuildStatus::Succeeded { image } => ("succeeded", image, String::new()),
+            BuildStatus::Failed { reason } => ("failed", String::new(), reason),
+        };
+        Ok(Response::new(BuildStatusResponse {
+            build_id,
+            status: status.to_string(),
+            image,
+            job_id: record.job_id,
+            message,
+        }))
+    }
+
+    async fn upload_payload(
+        &self,
+        request: Request<Streaming<PayloadChunk>>,
+    ) -> Result<Response<PayloadAck>, Status> {
+        let store = self.payload_store()
+            .ok_or_else(|| Status::failed_precondition("No payload store configured"))?;
+        let max_bytes = store.limits().max_payload_bytes;
+
+        let mut chunks = request.into_inner();
+        let mut data = Vec::new();
+        while let Some(chunk) = chunks.message().await? {
+            // Refused before more than the cap is buffered
+            if (data.len() + chunk.data.len()) as u64 > max_bytes {
+                return Err(Status::resource_exhausted(format!("Payload is over the {} byte limit", max_bytes)));
+            }
+            data.extend_from_slice(&chunk.data);
+        }
+
+        let digest = store.put(&data)
+            .await
+            .map_err(|e| error_status(e, Code::Internal))?;
+        info!("Stored {} byte payload {}", data.len(), digest);
+        Ok(Response::new(PayloadAck { digest, size_bytes: data.len() as u64 }))
+    }
+
+    type GetPayloadStream = ReceiverStream<Result<PayloadChunk, Status>>;
+
+    async fn get_payload(
+        &self,
+        request: Request<PayloadRequest>,
+    ) -> Result<Response<Self::GetPayloadStream>, Status> {
+        let digest = request.into_inner().digest;
+        let store = self.payload_store()
+            .ok_or_else(|| Status::failed_precondition("No payload store configured"))?;
+        let payload = store.get(&digest)
+            .await
+            .map_err(|e| error_status(e, Code::DataLoss))?
+            .ok_or_else(|| Status::not_found(format!("Payload {} not found", digest)))?;
+        let (tx, rx) = mpsc::channel(4);
+
+        tokio::spawn(async move {
+            for chunk in payload.chunks(OUTPUT_CHUNK_BYTES) {
+                if tx.send(Ok(PayloadChunk { data: chunk.to_vec() })).await.is_err() {
+                    break;
+                }
+            }
+        });
+
+        Ok(Response::new(ReceiverStream::new(rx)))
+    }
+
+    async fn get_snapshot(
+        &self,
+        request: Request<SnapshotRequest>,
+    ) -> Result<Response<SnapshotResponse>, Status> {
+        self.access_control()
+            .authorize(crate::rbac::bearer_token(request.metadata()), crate::rbac::Role::Operator, "snapshot scheduler state")
+            .map_err(|e| error_status(e.into(), Code::PermissionDenied))?;
+        let snapshot = self.snapshot().await.map_err(|e| error_status(e, Code::Internal))?;
+        info!("Snapshot taken: {} nodes, {} jobs, {} queued", snapshot.nodes.len(), snapshot.jobs.len(), snapshot.queued.len());
+
+        Ok(Response::new(SnapshotResponse {
+            snapshot: snapshot.to_bytes().map_err(|e| error_status(e, Code::Internal))?,
+            nodes: snapshot.nodes.len() as u32,
+            jobs: snapshot.jobs.len() as u32,
+            queued_jobs: snapshot.queued.len() as u32,
+        }))
+    }
+
+    async fn restore_snapshot(
+        &self,
+        request: Request<RestoreSnapshotRequest>,
+    ) -> Result<Response<RestoreSnapshotResponse>, Status> {
+        self.access_control()
+            .authorize(crate::rbac::bearer_token(request.metadata()), crate::rbac::Role::Operator, "restore scheduler state")
+            .map_err(|e| error_status(e.into(), Code::PermissionDenied))?;
+        let snapshot = crate::snapshot::Snapshot::from_bytes(&request.into_inner().snapshot)
+            .map_err(|e| Status::invalid_argument(e.to_string()))?;
+        let summary = self.restore(snapshot).await.map_err(|e| error_status(e, Code::Internal))?;
+
+        Ok(Response::new(RestoreSnapshotResponse {
+            nodes: summary.nodes as u32,
+            jobs: summary.jobs as u32,
+            queued_jobs: summary.queued_jobs as u32,
+        }))
+    }
+
+    async fn reserve_capacity(
+        &self,
+        request: Request<ReserveCapacityRequest>,
+    ) -> Result<Response<ReserveCapacityResponse>, Status> {
+        self.access_control()
+            .authorize(crate::rbac::bearer_token(request.metadata()), crate::rbac::Role::Operator, "reserve capacity")
+            .map_err(|e| error_status(e.into(), Code::PermissionDenied))?;
+        let req = request.into_inner();
+        let block = crate::tenant_reservations::CapacityBlock {
+            id: req.reservation_id,
+            tenant: req.tenant,
+            cpu_cores: req.cpu_cores,
+            memory_gb: req.memory_gb,
+            gpus: req.gpus,
+            start: req.start,
+            end: req.end,
+        };
+        let message = format!("Capacity block {} reserved for tenant {}", block.id, block.tenant);
+        self.reserve_capacity(block).map_err(|e| error_status(e, Code::Internal))?;
+
+        Ok(Response::new(ReserveCapacityResponse { success: true, message }))
+    }
+
+    async fn cancel_capacity_reservation(
+        &self,
+        request: Request<CancelCapacityReservationRequest>,
+    ) -> Result<Response<CancelCapacityReservationResponse>, Status> {
+        self.access_control()
+            .authorize(crate::rbac::bearer_token(request.metadata()), crate::rbac::Role::Operator, "cancel capacity reservations")
+            .map_err(|e| error_status(e.into(), Code::PermissionDenied))?;
+        let block = self.cancel_capacity_block(&request.into_inner().reservation_id)
+            .map_err(|e| error_status(e, Code::Internal))?;
+
+        Ok(Response::new(CancelCapacityReservationResponse {
+            success: true,
+            message: format!("Capacity block {} of tenant {} released", block.id, block.tenant),
+        }))
+    }
+
+    async fn list_capacity_reservations(
+        &self,
+        request: Request<ListCapacityReservationsRequest>,
+    ) -> Result<Response<ListCapacityReservationsResponse>, Status> {
+        let tenant = request.into_inner().tenant;
+        let reservations = self.capacity_block_status(crate::unix_now())
+            .map_err(|e| error_status(e, Code::Internal))?
+            .into_iter()
+            .filter(|status| tenant.is_empty() || status.block.tenant == tenant)
+            .map(|status| CapacityReservation {
+                reservation_id: status.block.id,
+                tenant: status.block.tenant,
+                cpu_cores: status.block.cpu_cores,
+                memory_gb: status.block.memory_gb,
+                gpus: status.block.gpus,
+                start: status.block.start,
+                end: status.block.end,
+                active: status.active,
+                used_cpu_cores: status.used_cpu_cores,
+                used_memory_gb: status.used_memory_gb,
+                used_gpus: status.used_gpus,
+                idle_cost_usd: status.idle_cost_usd,
+            })
+            .collect();
+
+        Ok(Response::new(ListCapacityReservationsResponse { reservations }))
+    }
+
+    async fn check_worker_version(
+        &self,
+        request: Request<WorkerVersionRequest>,
+    ) -> Result<Response<WorkerVersionResponse>, Status> {
+        let req = request.into_inner();
+        let check = self.check_worker_version(&req.node_id, &req.version, &req.platform);
+
+        Ok(Response::new(WorkerVersionResponse {
+            min_version: check.min_version,
+            latest_version: check.latest_version,
+            update_required: check.update_required,
+            update_available: check.update_available,
+            sha256: check.sha256,
+            signature: check.signature,
+            size_bytes: check.size_bytes,
+        }))
+    }
+
+    type DownloadWorkerUpdateStream = ReceiverStream<Result<WorkerUpdateChunk, Status>>;
+
+    async fn download_worker_update(
+        &self,
+        request: Request<WorkerUpdateRequest>,
+    ) -> Result<Response<Self::DownloadWorkerUpdateStream>, Status> {
+        let version = request.into_inner().version;
+        let release = self.worker_release(&version)
+            .ok_or_else(|| Status::not_found(format!("Worker release {} not published", version)))?;
+        let (tx, rx) = mpsc::channel(4);
+
+        tokio::spawn(async move {
+            for chunk in release.binary.chunks(OUTPUT_CHUNK_BYTES) {
+                if tx.send(Ok(WorkerUpdateChunk { data: chunk.to_vec() })).await.is_err() {
+                    break;
+                }
+            }
+        });
+
+        Ok(Response::new(ReceiverStream::new(rx)))
+    }
+
+    async fn get_job_logs(
+        &self,
+        request: Request<JobLogsRequest>,
+    ) -> Result<Response<JobLogsResponse>, Status> {
+        let job_id = request.into_inner().job_id;
+        let logs = self.job_logs(&job_id).map_err(|e| error_status(e, Code::Internal))?;
+
+        Ok(Response::new(JobLogsResponse {
+            job_id,
+            available: logs.is_some(),
+            logs: logs.unwrap_or_default(),
+        }))
+    }
+
+    async fn inject_fault(
+        &self,
+        request: Request<InjectFaultRequest>,
+    ) -> Result<Response<InjectFaultResponse>, Status> {
+        if !cfg!(feature = "chaos") {
+            return Err(Status::unimplemented("Fault injection not compiled in (enable the `chaos` feature)"));
+        }
+        self.access_control()
+            .authorize(crate::rbac::bearer_token(request.metadata()), crate::rbac::Role::Operator, "inject faults")
+            .map_err(|e| error_status(e.into(), Code::PermissionDenied))?;
+        let req = request.into_inner();
+        let target = (!req.target.is_empty()).then(|| req.target.clone());
+        let duration = (req.duration_secs > 0).then(|| std::time::Duration::from_secs(req.duration_secs));
+
+        let fault = match req.kind() {
+            FaultKind::DropHeartbeats => crate::chaos::Fault::DropHeartbeats { node_id: target },
+            FaultKind::DelayRpcs => crate::chaos::Fault::DelayRpcs {
+                method: target,
+                delay: std::time::Duration::from_millis(req.delay_ms),
+            },
+            FaultKind::PoisonLocks => crate::chaos::Fault::PoisonLocks,
+            FaultKind::KillContainer => {
+                let transport = crate::commands::GrpcCommandTransport::new(
+                    std::time::Duration::from_secs(10),
+                    self.control_auth().clone(),
+                );
+                let killed = self.kill_job_container(&req.target, &transport).await
+                    .map_err(|e| error_status(e, Code::Internal))?;
+                return Ok(Response::new(InjectFaultResponse {
+                    active_faults: self.faults().active().len() as u32,
+                    killed: killed as u32,
+                }));
+            }
+            FaultKind::Unspecified => return Err(Status::invalid_argument("Fault kind is required")),
+        };
+        self.faults().inject(fault, duration).map_err(|e| error_status(e, Code::Internal))?;
+
+        Ok(Response::new(InjectFaultResponse {
+            active_faults: self.faults().active().len() as u32,
+            killed: 0,
+        }))
+    }
+
+    async fn clear_faults(
+        &self,
+        request: Request<ClearFaultsRequest>,
+    ) -> Result<Response<ClearFaultsResponse>, Status> {
+        self.access_control()
+            .authorize(crate::rbac::bearer_token(request.metadata()), crate::rbac::Role::Operator, "clear faults")
+            .map_err(|e| error_status(e.into(), Code::PermissionDenied))?;
+        let cleared = self.faults().clear();
+        if cleared > 0 {
+            info!("Cleared {} injected faults", cleared);
+        }
+        Ok(Response::new(ClearFaultsResponse { cleared: cleared as u32 }))
+    }
+}
+
+impl EconomicScheduler {
+    /// Admit, trace, then queue or place a converted submission
+    async fn submit_spec(&self, job_spec: crate::JobSpec) -> Result<Response<JobSubmitResponse>, Status> {
+        if job_spec.job_data.len() > crate::MAX_JOB_INPUT_BYTES {
+            return Err(error_status(
+                crate::error::SchedulerError::invalid_spec(format!(
+                    "Job {} input is {} bytes, the limit is {}",
+                    job_spec.id, job_spec.job_data.len(), crate::MAX_JOB_INPUT_BYTES
+                )).into(),
+                Code::InvalidArgument,
+            ));
+        }
+        if let Err(reason) = crate::preferences::validate(&job_spec) {
+            return Err(error_status(
+                crate::error::SchedulerError::invalid_spec(format!("Job {}: {}", job_spec.id, reason)).into(),
+                Code::InvalidArgument,
+            ));
+        }
+        let annotation_bytes: usize = job_spec.annotations.iter().map(|(key, value)| key.len() + value.len()).sum();
+        if annotation_bytes > crate::MAX_ANNOTATION_BYTES {
+            return Err(error_status(
+                crate::error::SchedulerError::invalid_spec(format!(
+                    "Job {} annotations are {} bytes, the limit is {}",
+                    job_spec.id, annotation_bytes, crate::MAX_ANNOTATION_BYTES
+                )).into(),
+                Code::InvalidArgument,
+            ));
+        }
+        if self.get_job_state(&job_spec.id).is_some() {
+            return Err(error_status(
+                crate::error::SchedulerError::already_exists("Job", &job_spec.id).into(),
+                Code::AlreadyExists,
+            ));
+        }
+        let _in_flight = self.begin_placement()
+            .ok_or_else(|| Status::unavailable("Scheduler is shutting down"))?;
+        self.shed_if_overloaded(&job_spec)
+            .await
+            .map_err(|e| error_status(e.into(), Code::Unavailable))?;
+        let job_spec = self.admit(job_spec)
+            .await
+            .map_err(|e| error_status(e, Code::PermissionDenied))?;
+        self.check_credit(&job_spec)
+            .map_err(|e| error_status(e.into(), Code::FailedPrecondition))?;
+        self.record_trace(TraceRecord::SubmitJob { at_ms: now_ms(), job: Box::new(job_spec.clone()) });
+
+        // Queued mode: a dispatcher on some replica places the job
+        if self.job_queue().is_some() {
+            let job_id = job_spec.id.clone();
+            self.enqueue(job_spec)
+                .await
+                .map_err(|e| error_status(e.context("Failed to queue job"), Code::Unavailable))?;
+
+            let estimated_start_at = self.queue_eta(&job_id).map_or(0, |eta| eta.estimated_start_at);
+            return Ok(Response::new(JobSubmitResponse {
+                success: true,
+                job_id,
+                message: "Job queued for placement".to_string(),
+                estimated_start_at,
+                ..Default::default()
+            }));
+        }
+
+        // Use actual scheduler with Formula 4.1
+        let job_id = job_spec.id.clone();
+        let started = std::time::Instant::now();
+        let result = self.schedule(job_spec).await;
+        self.load_shedder().record_placement(started.elapsed());
+        match result {
+            Ok(placement) => {
+                info!(
+                    "Job {} scheduled to {} with Formula 4.1 TCO ${:.4}",
+                    placement.job_id,
+                    placement.node_id,
+                    placement.estimated_cost.total_usd
+                );
+
+                let response = JobSubmitResponse {
+                    success: true,
+                    job_id: placement.job_id,
+                    assigned_node: placement.node_id,
+                    cost_estimate: Some(CostEstimate {
+                        compute_cost_usd: placement.estimated_cost.compute_usd,
+                        data_transfer_usd: placement.estimated_cost.data_transfer_usd,
+                        idle_opportunity_usd: placement.estimated_cost.idle_opportunity_usd,
+                        total_cost_usd: placement.estimated_cost.total_usd,
+                        estimated_latency_ms: placement.estimated_latency_ms,
+                        estimated_staging_ms: placement.estimated_staging_ms,
+                        estimated_duration_hours: placement.estimated_duration_hours,
+                    }),
+                    message: match &placement.cached_from {
+                        Some(source) => format!("Result reused from completed job {}", source),
+                        None => format!(
+                            "Job scheduled using Formula 4.1 - TCO: ${:.4}",
+                            placement.estimated_cost.total_usd
+                        ),
+                    },
+                    cached_from_job: placement.cached_from.unwrap_or_default(),
+                    gang_nodes: placement.gang_nodes,
+                    preference_violations: placement.preference_violations
+                        .into_iter()
+                        .map(preference_violation_to_proto)
+                        .collect(),
+                    ..Default::default()
+                };
+
+                Ok(Response::new(response))
+            }
+            // Gang jobs waiting on a reservation, jobs held for a price
+            // drop and jobs waiting for their start window are accepted,
+            // not failed
+            Err(e) if self.reservations().waiting().iter().any(|gang| gang.job.id == job_id)
+                || self.price_holds().is_held(&job_id)
+                || self.start_windows().start_of(&job_id).is_some() => {
+                Ok(Response::new(JobSubmitResponse {
+                    success: true,
+                    estimated_start_at: self.start_windows().start_of(&job_id)
+                        .or_else(|| self.queue_eta(&job_id).map(|eta| eta.estimated_start_at))
+                        .unwrap_or(0),
+                    job_id,
+                    message: e.to_string(),
+                    ..Default::default()
+                }))
+            }
+            Err(e) => {
+                Err(error_status(e.context("Scheduling failed"), Code::Internal))
+            }
+        }
+    }
+}
+
+fn credit_balance_to_proto(balance: crate::credits::CreditBalance, enforced: bool) -> CreditBalance {
+    CreditBalance {
+        available_usd: balance.available_usd(),
+        tenant: balance.tenant,
+        balance_usd: balance.balance_usd,
+        reserved_usd: balance.reserved_usd,
+        topped_up_usd: balance.topped_up_usd,
+        charged_usd: balance.charged_usd,
+        enforced,
+    }
+}
+
+fn demand_signals_to_proto(signals: crate::market::DemandSignals) -> DemandSignals {
+    DemandSignals {
+        at: signals.at,
+        cluster_version: signals.cluster_version,
+        classes: signals.classes.into_iter()
+            .map(|c| ClassDemand {
+                resource_class: c.class.as_str().to_string(),
+                queued_jobs: c.queued_jobs,
+                queued_units: c.queued_units,
+                placed_jobs: c.placed_jobs,
+                used_units: c.used_units,
+                capacity_units: c.capacity_units,
+                utilization: c.utilization,
+                market_rate_usd: c.market_rate_usd,
+            })
+            .collect(),
+    }
+}
+
+fn cost_breakdown(cost: &crate::cost_summary::CostBreakdown) -> CostBreakdown {
+    CostBreakdown {
+        compute_usd: cost.compute_usd,
+        data_usd: cost.data_usd,
+        idle_usd: cost.idle_usd,
+        total_usd: cost.total_usd(),
+    }
+}
+
+/// Convert a proto submission into a scheduler job spec
+fn job_spec_from_request(job_req: JobSubmitRequest) -> crate::JobSpec {
+    crate::JobSpec {
+        id: job_req.job_id,
+        job_type: match job_req.job_type {
+            1 => crate::JobType::Training,
+            2 => crate::JobType::Inference,
+            3 => crate::JobType::DataProcessing,
+            _ => crate::JobType::Inference,
+        },
+        resources: crate::ResourceRequirements {
+            cpu_cores: job_req.resources.as_ref()
+                .map(|r| r.cpu_cores)
+                .unwrap_or(1),
+            memory_gb: job_req.resources.as_ref()
+                .map(|r| r.memory_gb)
+                .unwrap_or(1),
+            gpu_count: job_req.resources.as_ref()
+                .map(|r| r.gpu_count)
+                .unwrap_or(0),
+            disk_gb: job_req.resources.as_ref()
+                .map(|r| r.disk_gb)
+                .unwrap_or(10),
+            network_mbps: job_req.resources.as_ref()
+                .map(|r| r.network_mbps)
+                .unwrap_or(0),
+            disk_io_mbps: job_req.resources.as_ref()
+                .and_then(|r| r.disk_io_mbps)
+                .filter(|&mbps| mbps > 0),
+        },
+        sla: crate::SlaConstraints {
+            max_latency_ms: job_req.sla.as_ref()
+                .map(|s| s.max_latency_ms)
+                .unwrap_or(1000),
+            max_budget_usd: job_req.sla.as_ref()
+                .and_then(|s| s.max_budget_usd),
+            deadline: job_req.sla.as_ref()
+                .and_then(|s| s.deadline),
+            earliest_start: job_req.sla.as_ref()
+                .and_then(|s| s.earliest_start),
+            max_hourly_rate_usd: job_req.sla.as_ref()
+                .and_then(|s| s.max_hourly_rate_usd)
+                .filter(|&usd| usd > 0.0),
+            preferences: job_req.sla.as_ref()
+                .map(|s| s.preferences.iter().filter_map(soft_constraint_from_proto).collect())
+                .unwrap_or_default(),
+        },
+        container_image: job_req.container_image,
+        command: job_req.command,
+        job_data: job_req.job_data,
+        disable_result_cache: job_req.disable_result_cache,
+        datasets: job_req.datasets,
+        movable: job_req.movable,
+        estimated_duration_hours: job_req.estimated_duration_hours,
+        best_effort: job_req.best_effort,
+        gang_size: job_req.gang_size,
+        priority: match job_req.priority {
+            1 => crate::priority::PriorityClass::Low,
+            3 => crate::priority::PriorityClass::High,
+            _ => crate::priority::PriorityClass::Normal,
+        },
+        tenant: job_req.tenant,
+        env: job_req.environment.into_iter().collect(),
+        ports: job_req.ports.into_iter().filter_map(|port| u16::try_from(port).ok()).collect(),
+        pin_cpus: job_req.pin_cpus,
+        input_stdin: job_req.input_stdin,
+        output_path: (!job_req.output_path.is_empty()).then_some(job_req.output_path),
+        payload_digest: (!job_req.payload_digest.is_empty()).then_some(job_req.payload_digest),
+        egress_limit_mbps: job_req.egress_limit_mbps.filter(|&mbps| mbps > 0),
+        price_hold_secs: job_req.price_hold_secs.filter(|&secs| secs > 0),
+        queue: (!job_req.queue.is_empty()).then_some(job_req.queue),
+        pool: (!job_req.pool.is_empty()).then_some(job_req.pool),
+        arch: job_req.arch,
+        pre_start_hook: job_req.pre_start_hook.map(job_hook_from_proto),
+        post_complete_hook: job_req.post_complete_hook.map(job_hook_from_proto),
+        callback_url: (!job_req.callback_url.is_empty()).then_some(job_req.callback_url),
+        annotations: job_req.annotations.into_iter().collect(),
+        ..Default::default()
+    }
+}
+
+fn soft_constraint_from_proto(constraint: &SoftConstraint) -> Option<crate::preferences::SoftConstraint> {
+    use crate::preferences::Preference;
+
+    let preference = match constraint.preference.clone()? {
+        soft_constraint::Preference::MaxLatencyMs(ms) => Preference::MaxLatencyMs(ms),
+        soft_constraint::Preference::Region(region) => Preference::Region(region),
+        soft_constraint::Preference::MaxCostUsd(usd) => Preference::MaxCostUsd(usd),
+    };
+    Some(crate::preferences::SoftConstraint::new(preference, constraint.weight))
+}
+
+fn preference_violation_to_proto(violation: crate::preferences::PreferenceViolation) -> PreferenceViolation {
+    use crate::preferences::Preference;
+
+    let preference = match violation.preference {
+        Preference::MaxLatencyMs(ms) => soft_constraint::Preference::MaxLatencyMs(ms),
+        Preference::Region(region) => soft_constraint::Preference::Region(region),
+        Preference::MaxCostUsd(usd) => soft_constraint::Preference::MaxCostUsd(usd),
+    };
+    PreferenceViolation {
+        constraint: Some(SoftConstraint { weight: violation.weight, preference: Some(preference) }),
+        degree: violation.degree,
+    }
+}
+
+fn job_hook_from_proto(hook: JobHook) -> crate::lifecycle::JobHook {
+    crate::lifecycle::JobHook {
+        image: hook.image,
+        command: hook.command,
+        timeout_secs: hook.timeout_secs,
+    }
+}
+
+/// Convert a scheduler job status to its proto enum value
+fn gpu_topology_from_proto(topology: &GpuTopology) -> crate::topology::GpuTopology {
+    use crate::topology::GpuLink;
+    let links: Vec<GpuLink> = topology.links.iter()
+        .map(|&link| match GpuLinkType::try_from(link) {
+            Ok(GpuLinkType::GpuLinkSys) => GpuLink::Sys,
+            Ok(GpuLinkType::GpuLinkNode) => GpuLink::Node,
+            Ok(GpuLinkType::GpuLinkPhb) => GpuLink::Phb,
+            Ok(GpuLinkType::GpuLinkPxb) => GpuLink::Pxb,
+            Ok(GpuLinkType::GpuLinkPix) => GpuLink::Pix,
+            Ok(GpuLinkType::GpuLinkNvlink) => GpuLink::NvLink,
+            Ok(GpuLinkType::GpuLinkUnknown) | Err(_) => GpuLink::Unknown,
+        })
+        .collect();
+    let gpus = topology.numa_nodes.len();
+    crate::topology::GpuTopology {
+        numa_nodes: topology.numa_nodes.clone(),
+        links: if gpus > 0 && links.len() == gpus * gpus {
+            links.chunks(gpus).map(<[GpuLink]>::to_vec).collect()
+        } else {
+            Vec::new()
+        },
+    }
+}
+
+fn proto_job_status(status: &crate::JobStatus) -> i32 {
+    match status {
+        crate::JobStatus::Pending => JobStatus::Pending.into(),
+        crate::JobStatus::Scheduled => JobStatus::Scheduled.into(),
+        crate::JobStatus::Running => JobStatus::Running.into(),
+        crate::JobStatus::Completed => JobStatus::Completed.into(),
+        crate::JobStatus::Failed => JobStatus::Failed.into(),
+        crate::JobStatus::Cancelled => JobStatus::Cancelled.into(),
+    }
+}
+
+/// Convert scheduler job state into its proto status response
+fn job_status_response(state: crate::JobState, eta: Option<crate::eta::QueueEta>) -> JobStatusResponse {
+    let proto_status = proto_job_status(&state.status);
+
+    let final_cost = state.estimated_cost.map(|cost| CostEstimate {
+        compute_cost_usd: cost.compute_usd,
+        data_transfer_usd: cost.data_transfer_usd,
+        idle_opportunity_usd: cost.idle_opportunity_usd,
+        total_cost_usd: cost.total_usd,
+        estimated_latency_ms: 0, // TODO: track actual latency
+        estimated_staging_ms: 0,
+        estimated_duration_hours: 0.0,
+    });
+
+    // Only a running job is in a phase
+    let phase = match state.phase.filter(|_| state.status == crate::JobStatus::Running) {
+        Some(crate::lifecycle::JobPhase::PreStart) => JobPhase::PreStart,
+        Some(crate::lifecycle::JobPhase::Main) => JobPhase::Main,
+        Some(crate::lifecycle::JobPhase::PostComplete) => JobPhase::PostComplete,
+        None => JobPhase::Unspecified,
+    };
+
+    let progress = state.progress.map(|p| JobProgress {
+        percent: p.percent,
+        step: p.step,
+        metrics: p.metrics,
+        updated_at: p.updated_at,
+    });
+
+    JobStatusResponse {
+        job_id: state.job_id,
+        status: proto_status,
+        assigned_node: state.assigned_node.unwrap_or_default(),
+        final_cost,
+        progress,
+        cached_from_job: state.cached_from.unwrap_or_default(),
+        gang_nodes: state.gang_nodes,
+        estimated_start_at: eta.as_ref().map_or(0, |eta| eta.estimated_start_at),
+        queue_position: eta.map_or(0, |eta| eta.position),
+        image_digest: state.image_digest.unwrap_or_default(),
+        queue: state.queue.unwrap_or_default(),
+        phase: phase.into(),
+        submitted_at: state.submitted_at.unwrap_or_default(),
+        scheduled_at: state.scheduled_at.unwrap_or_default(),
+        started_at: state.started_at.unwrap_or_default(),
+        finished_at: state.finished_at.unwrap_or_default(),
+        annotations: state.annotations,
+    }
+}
+
+/// gRPC status for a scheduler error: a `SchedulerError` gets its own code
+/// and an `ErrorDetail` in the status details, anything else `fallback`
+fn error_status(err: anyhow::Error, fallback: Code) -> Status {
+    use crate::error::SchedulerError;
+
+    let Some(typed) = crate::error::classify(&err) else {
+        return Status::new(fallback, format!("{:#}", err));
+    };
+    let code = match typed {
+        SchedulerError::NoCapacity { .. } | SchedulerError::QuotaExceeded { .. } => Code::ResourceExhausted,
+        SchedulerError::SlaLatencyUnmet { .. }
+        | SchedulerError::DeadlineUnmet { .. }
+        | SchedulerError::OverBudget { .. }
+        | SchedulerError::OverHourlyRate { .. }
+        | SchedulerError::OverCostCeiling { .. } => Code::FailedPrecondition,
+        SchedulerError::InvalidSpec { .. } => Code::InvalidArgument,
+        SchedulerError::AlreadyExists { .. } => Code::AlreadyExists,
+        SchedulerError::NotFound { .. } => Code::NotFound,
+        SchedulerError::AdmissionDenied { .. } => Code::PermissionDenied,
+        SchedulerError::Unavailable { .. } => Code::Unavailable,
+        SchedulerError::StaleRegistration { .. } => Code::FailedPrecondition,
+        SchedulerError::IdentityConflict { .. } => Code::PermissionDenied,
+        SchedulerError::Forbidden { .. } => Code::PermissionDenied,
+        SchedulerError::TryLater { .. } => Code::Unavailable,
+        SchedulerError::PriceOutOfBounds { .. } => Code::InvalidArgument,
+        SchedulerError::PriceUpdateTooSoon { .. } => Code::ResourceExhausted,
+        SchedulerError::InvalidTransition { .. } => Code::FailedPrecondition,
+        SchedulerError::InsufficientCredit { .. } => Code::FailedPrecondition,
+    };
+    let detail = ErrorDetail {
+        reason: typed.reason().to_string(),
+        metadata: typed.metadata(),
+        suggestions: typed.suggestions()
+            .iter()
+            .map(|relaxation| SlaSuggestion {
+                node_id: relaxation.node_id.clone(),
+                max_latency_ms: relaxation.max_latency_ms,
+                max_budget_usd: relaxation.max_budget_usd,
+                deadline: relaxation.deadline,
+                summary: relaxation.to_string(),
+            })
+            .collect(),
+    };
+    Status::with_details(code, typed.to_string(), prost::Message::encode_to_vec(&detail).into())
+}
+
+/// Service for `scheduler`, accepting requests as large as a snapshot restore
+fn scheduler_service(scheduler: EconomicScheduler) -> SchedulerServiceServer<EconomicScheduler> {
+    SchedulerServiceServer::new(scheduler).max_decoding_message_size(crate::snapshot::MAX_SNAPSHOT_BYTES)
+}
+
+/// What the server runs: the service, behind fault injection in chaos builds
+#[cfg(not(feature = "chaos"))]
+fn served(scheduler: EconomicScheduler) -> SchedulerServiceServer<EconomicScheduler> {
+    scheduler_service(scheduler)
+}
+
+#[cfg(feature = "chaos")]
+fn served(scheduler: EconomicScheduler) -> crate::chaos::FaultyService<SchedulerServiceServer<EconomicScheduler>> {
+    let faults = scheduler.faults().clone();
+    crate::chaos::FaultyService::new(scheduler_service(scheduler), faults)
 }
 
 /// Start gRPC server
@@ -272,9 +1969,42 @@ pub async fn start_grpc_server(
     info!("Starting gRPC server on {}", addr);
 
     Server::builder()
-        .add_service(SchedulerServiceServer::new(scheduler))
+        .add_service(served(scheduler))
         .serve(addr)
         .await?;
 
     Ok(())
 }
+
+/// Start gRPC server, stopping it once `signal` resolves
+///
+/// Have `signal` drain the scheduler first (see `EconomicScheduler::shutdown`)
+/// so RPCs are refused cleanly while in-flight work finishes.
+pub async fn start_grpc_server_with_shutdown(
+    scheduler: EconomicScheduler,
+    addr: std::net::SocketAddr,
+    signal: impl std::future::Future<Output = ()>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    info!("Starting gRPC server on {}", addr);
+
+    Server::builder()
+        .add_service(served(scheduler))
+        .serve_with_shutdown(addr, signal)
+        .await?;
+
+    info!("gRPC server stopped");
+    Ok(())
+}
+
+/// Serve on an already bound listener until `signal` resolves (e.g. an
+/// ephemeral port in tests, see `testkit`)
+pub async fn serve_on_listener(
+    scheduler: EconomicScheduler,
+    listener: tokio::net::TcpListener,
+    signal: impl std::future::Future<Output = ()>,
+) -> Result<(), tonic::transport::Error> {
+    Server::builder()
+        .add_service(served(scheduler))
+        .serve_with_incoming_shutdown(tokio_stream::wrappers::TcpListenerStream::new(listener), signal)
+        .await
+}
@@ -0,0 +1,98 @@
+//! Hourly rate caps
+//!
+//! `SlaConstraints::max_hourly_rate_usd` caps what a job may burn per hour,
+//! for long-running services whose total cost is open-ended. Unlike the
+//! budget it does not depend on the run time: a node is ruled out when its
+//! price (times the gang size) exceeds the cap. The cap is checked again
+//! when a node is repriced: jobs on the node that it now exceeds are
+//! requeued onto nodes within their cap, and fail if there are none.
+
+use anyhow::Result;
+
+use crate::{EconomicScheduler, JobSpec, NodeInfo};
+
+/// What `job` would burn per hour on `node`, counting every gang member
+pub fn hourly_rate(job: &JobSpec, node: &NodeInfo) -> f64 {
+    node.cost_per_hour * job.gang_size.max(1) as f64
+}
+
+/// Whether `node` is over the job's hourly rate cap
+pub fn exceeds_cap(job: &JobSpec, node: &NodeInfo) -> bool {
+    job.sla.max_hourly_rate_usd.is_some_and(|cap| hourly_rate(job, node) > cap)
+}
+
+impl EconomicScheduler {
+    /// Requeue the jobs on `node_id` whose hourly cap its current price
+    /// exceeds; returns their ids
+    pub async fn recheck_hourly_rates(&self, node_id: &str) -> Result<Vec<String>> {
+        let node = self.available_nodes.lock()
+            .map_err(|e| anyhow::anyhow!("Lock poisoned: {}", e))?
+            .get(node_id)
+            .cloned();
+        let Some(node) = node else {
+            return Ok(Vec::new());
+        };
+
+        let over: Vec<JobSpec> = self.placed_on(node_id)?
+            .into_iter()
+            .filter(|spec| exceeds_cap(spec, &node))
+            .collect();
+        let job_ids: Vec<String> = over.iter().map(|spec| spec.id.clone()).collect();
+
+        for spec in over {
+            tracing::warn!(
+                "Node {} now costs ${:.4}/h, over the ${:.4}/h cap of job {}; requeueing it",
+                node_id, hourly_rate(&spec, &node), spec.sla.max_hourly_rate_usd.unwrap_or_default(), spec.id
+            );
+            if let Ok(mut placed) = self.placed_jobs.lock() {
+                placed.remove(&spec.id);
+            }
+            let job_id = spec.id.clone();
+            if let Err(e) = self.requeue(spec).await {
+                tracing::warn!("Job {} has no placement within its hourly cap: {}", job_id, e);
+            }
+        }
+        Ok(job_ids)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::{classify, SchedulerError};
+    use crate::{JobStatus, NodeInfo, ResourceRequirements, SlaConstraints};
+    use crate::test_support::node;
+
+    fn service(id: &str, max_hourly_rate_usd: f64) -> JobSpec {
+        JobSpec {
+            id: id.to_string(),
+            resources: ResourceRequirements { cpu_cores: 1, memory_gb: 1, ..Default::default() },
+            sla: SlaConstraints { max_latency_ms: 1_000, max_hourly_rate_usd: Some(max_hourly_rate_usd), ..Default::default() },
+            // A month-long service: no total budget would do
+            estimated_duration_hours: Some(720.0),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_hourly_cap_is_enforced_at_placement_and_on_repricing() {
+        let scheduler = EconomicScheduler::new();
+        scheduler.register_node(NodeInfo { provider: "acme".to_string(), ..node("cheap", 0.10) }).unwrap();
+        scheduler.register_node(NodeInfo { provider: "acme".to_string(), ..node("spare", 0.20) }).unwrap();
+
+        let err = scheduler.schedule(service("too-cheap", 0.05)).await.unwrap_err();
+        assert_eq!(classify(&err).map(SchedulerError::reason), Some("OVER_HOURLY_RATE"));
+
+        assert_eq!(scheduler.schedule(service("web", 0.25)).await.unwrap().node_id, "cheap");
+
+        // Repriced within the cap: stays put
+        scheduler.set_node_price("acme", "cheap", 0.15).unwrap();
+        assert!(scheduler.recheck_hourly_rates("cheap").await.unwrap().is_empty());
+
+        // Over it: moved to the node still within the cap
+        scheduler.set_node_price("acme", "cheap", 0.30).unwrap();
+        assert_eq!(scheduler.recheck_hourly_rates("cheap").await.unwrap(), vec!["web".to_string()]);
+        let state = scheduler.get_job_state("web").unwrap();
+        assert_eq!((state.status, state.assigned_node.as_deref()), (JobStatus::Scheduled, Some("spare")));
+    }
+}
@@ -0,0 +1,519 @@
+//! Image digest pinning and registry mirrors
+//!
+//! A tag can move between a job's first run and its retry, or between the
+//! nodes of a gang. At admission the image tag is resolved to a manifest
+//! digest with the registry's HTTP API and the job is rewritten to run
+//! `<repository>@<digest>`, so every run of it pulls identical bits; the
+//! digest is kept in the job's state.
+//!
+//! Public images can also be routed through internal mirrors (registries
+//! run as pull-through caches): an image from a mirrored upstream registry
+//! is rewritten to the mirror host, so each image crosses the internet
+//! once instead of once per node.
+//!
+//! The pinned manifest also says which CPU architectures the image is built
+//! for. A job is narrowed to those (intersected with any `arch` it asked
+//! for), so an amd64-only image is never placed on an ARM node.
+
+use anyhow::Result;
+use async_trait::async_trait;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::error::SchedulerError;
+use crate::{EconomicScheduler, JobSpec};
+
+/// Registry of images named without one
+const DEFAULT_REGISTRY: &str = "docker.io";
+
+/// How long a resolved tag is trusted before asking the registry again
+const RESOLVE_TTL: Duration = Duration::from_secs(300);
+
+/// Architecture of nodes that do not report one
+pub const DEFAULT_ARCH: &str = "amd64";
+
+/// OCI name of a CPU architecture (`x86_64` is amd64, `aarch64` arm64)
+pub fn normalize_arch(arch: &str) -> String {
+    match arch.trim().to_ascii_lowercase().as_str() {
+        "x86_64" | "x86-64" | "x64" => "amd64".to_string(),
+        "aarch64" | "arm64v8" => "arm64".to_string(),
+        other => other.to_string(),
+    }
+}
+
+/// Architectures of the platform manifests in an image index, skipping
+/// attestation entries (platform `unknown`)
+pub fn index_architectures(index: &serde_json::Value) -> Vec<String> {
+    let mut arches: Vec<String> = index.get("manifests")
+        .and_then(|manifests| manifests.as_array())
+        .into_iter()
+        .flatten()
+        .filter_map(|manifest| manifest.pointer("/platform/architecture")?.as_str())
+        .filter(|arch| *arch != "unknown")
+        .map(normalize_arch)
+        .collect();
+    arches.sort();
+    arches.dedup();
+    arches
+}
+
+/// A parsed image reference
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImageRef {
+    /// Registry host (`docker.io`, `ghcr.io`, `registry.tgp:5000`)
+    pub registry: String,
+    /// Repository path (`library/alpine`)
+    pub repository: String,
+    pub tag: String,
+    /// Manifest digest when the reference is pinned
+    pub digest: Option<String>,
+}
+
+impl ImageRef {
+    pub fn parse(image: &str) -> Result<Self> {
+        let (name, digest) = match image.split_once('@') {
+            Some((name, digest)) => (name, Some(digest.to_string())),
+            None => (image, None),
+        };
+        // A tag follows the last ':' after the last '/' (not a registry port)
+        let (name, tag) = match name.rfind(':') {
+            Some(i) if !name[i..].contains('/') => (&name[..i], &name[i + 1..]),
+            _ => (name, "latest"),
+        };
+        let (registry, repository) = match name.split_once('/') {
+            Some((first, rest)) if first.contains('.') || first.contains(':') || first == "localhost" => {
+                (first.to_string(), rest.to_string())
+            }
+            Some(_) => (DEFAULT_REGISTRY.to_string(), name.to_string()),
+            None => (DEFAULT_REGISTRY.to_string(), format!("library/{}", name)),
+        };
+        anyhow::ensure!(
+            !repository.is_empty() && !tag.is_empty() && digest.as_deref() != Some(""),
+            "Invalid image reference {:?}",
+            image
+        );
+        Ok(Self { registry, repository, tag: tag.to_string(), digest })
+    }
+
+    /// The reference pinned to `digest`
+    pub fn pinned(&self, digest: &str) -> String {
+        format!("{}/{}@{}", self.registry, self.repository, digest)
+    }
+}
+
+/// Digest of a pinned image reference
+pub fn digest_of(image: &str) -> Option<String> {
+    image.split_once('@').map(|(_, digest)| digest.to_string())
+}
+
+/// Looks up the manifest digest a tag points at
+#[async_trait]
+pub trait ImageResolver: Send + Sync {
+    async fn resolve(&self, image: &ImageRef) -> Result<String>;
+
+    /// CPU architectures a pinned image is built for (empty: unknown)
+    async fn architectures(&self, _image: &ImageRef) -> Result<Vec<String>> {
+        Ok(Vec::new())
+    }
+}
+
+/// How submitted images are pinned and where they are pulled from
+#[derive(Clone)]
+pub struct ImagePolicy {
+    resolver: Arc<dyn ImageResolver>,
+    /// Upstream registry host to the mirror serving it
+    mirrors: HashMap<String, String>,
+    /// Keep the tag when the registry cannot be reached
+    fail_open: bool,
+    resolved: Arc<Mutex<HashMap<String, (String, Instant)>>>,
+    /// Architectures by manifest digest (digests never change)
+    architectures: Arc<Mutex<HashMap<String, Vec<String>>>>,
+}
+
+impl ImagePolicy {
+    pub fn new(resolver: Arc<dyn ImageResolver>, mirrors: HashMap<String, String>, fail_open: bool) -> Self {
+        Self {
+            resolver,
+            mirrors,
+            fail_open,
+            resolved: Arc::new(Mutex::new(HashMap::new())),
+            architectures: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Parse `upstream=mirror,...` (e.g. `docker.io=mirror.tgp:5000`)
+    pub fn parse_mirrors(spec: &str) -> Result<HashMap<String, String>> {
+        spec.split(',')
+            .map(str::trim)
+            .filter(|entry| !entry.is_empty())
+            .map(|entry| {
+                let (upstream, mirror) = entry.split_once('=')
+                    .ok_or_else(|| anyhow::anyhow!("Mirror entry {:?} is not <upstream>=<mirror>", entry))?;
+                Ok((upstream.trim().to_string(), mirror.trim().to_string()))
+            })
+            .collect()
+    }
+
+    /// `image` pinned to its current digest and routed through a mirror
+    pub async fn pin(&self, image: &str) -> Result<String> {
+        let mut reference = ImageRef::parse(image)?;
+        let digest = match &reference.digest {
+            Some(digest) => digest.clone(),
+            None => match self.resolve(&reference).await {
+                Ok(digest) => digest,
+                Err(e) if self.fail_open => {
+                    tracing::warn!("Could not resolve {}, running it by tag: {:#}", image, e);
+                    return Ok(image.to_string());
+                }
+                Err(e) => {
+                    return Err(SchedulerError::Unavailable {
+                        reason: format!("cannot resolve image {}: {:#}", image, e),
+                    }.into())
+                }
+            },
+        };
+        if let Some(mirror) = self.mirrors.get(&reference.registry) {
+            reference.registry = mirror.clone();
+        }
+        Ok(reference.pinned(&digest))
+    }
+
+    async fn resolve(&self, reference: &ImageRef) -> Result<String> {
+        let key = format!("{}/{}:{}", reference.registry, reference.repository, reference.tag);
+        let cached = self.resolved.lock()
+            .ok()
+            .and_then(|resolved| resolved.get(&key).cloned())
+            .filter(|(_, at)| at.elapsed() < RESOLVE_TTL);
+        if let Some((digest, _)) = cached {
+            return Ok(digest);
+        }
+
+        let digest = self.resolver.resolve(reference).await?;
+        if let Ok(mut resolved) = self.resolved.lock() {
+            resolved.insert(key, (digest.clone(), Instant::now()));
+        }
+        Ok(digest)
+    }
+
+    /// Architectures a pinned image is built for (empty: unknown)
+    pub async fn architectures(&self, image: &str) -> Result<Vec<String>> {
+        let reference = ImageRef::parse(image)?;
+        let Some(digest) = reference.digest.clone() else {
+            return Ok(Vec::new());
+        };
+        if let Some(arches) = self.architectures.lock().ok().and_then(|cached| cached.get(&digest).cloned()) {
+            return Ok(arches);
+        }
+
+        let arches = self.resolver.architectures(&reference).await?;
+        if let Ok(mut cached) = self.architectures.lock() {
+            cached.insert(digest, arches.clone());
+        }
+        Ok(arches)
+    }
+}
+
+/// Resolver speaking the registry HTTP API (`insecure` hosts over plain
+/// HTTP), with anonymous bearer tokens for public registries
+pub fn http_resolver(insecure: Vec<String>) -> Result<Arc<dyn ImageResolver>> {
+    #[cfg(feature = "webhooks")]
+    return Ok(Arc::new(registry::HttpResolver::new(insecure)));
+    #[cfg(not(feature = "webhooks"))]
+    {
+        let _ = insecure;
+        anyhow::bail!("Registry support not compiled in (enable the `webhooks` feature)");
+    }
+}
+
+impl EconomicScheduler {
+    pub fn set_image_policy(&mut self, policy: Option<ImagePolicy>) {
+        self.images = policy;
+    }
+
+    /// Rewrite a submission's image to its pinned (and mirrored) reference
+    pub async fn pin_image(&self, mut job: JobSpec) -> Result<JobSpec> {
+        let Some(policy) = &self.images else {
+            return Ok(job);
+        };
+        if job.container_image.is_empty() {
+            return Ok(job);
+        }
+        let pinned = policy.pin(&job.container_image).await?;
+        if pinned != job.container_image {
+            tracing::info!("Job {} image {} pinned to {}", job.id, job.container_image, pinned);
+            job.container_image = pinned;
+        }
+        Ok(job)
+    }
+
+    /// Narrow a job's architectures to those its pinned image is built for
+    pub async fn check_image_arch(&self, mut job: JobSpec) -> Result<JobSpec> {
+        job.arch = job.arch.iter().map(|arch| normalize_arch(arch)).filter(|arch| !arch.is_empty()).collect();
+        job.arch.sort();
+        job.arch.dedup();
+        let Some(policy) = &self.images else {
+            return Ok(job);
+        };
+        if job.container_image.is_empty() {
+            return Ok(job);
+        }
+        let image_arches = match policy.architectures(&job.container_image).await {
+            Ok(arches) if !arches.is_empty() => arches,
+            Ok(_) => return Ok(job),
+            Err(e) => {
+                tracing::warn!("Could not read the platforms of {}, not checking its architecture: {:#}", job.container_image, e);
+                return Ok(job);
+            }
+        };
+
+        if job.arch.is_empty() {
+            job.arch = image_arches;
+            return Ok(job);
+        }
+        let requested = std::mem::take(&mut job.arch);
+        job.arch = requested.iter().filter(|arch| image_arches.contains(arch)).cloned().collect();
+        if job.arch.is_empty() {
+            return Err(SchedulerError::invalid_spec(format!(
+                "Job {} runs on {} but image {} is only built for {}",
+                job.id, requested.join("/"), job.container_image, image_arches.join("/")
+            )).into());
+        }
+        Ok(job)
+    }
+}
+
+#[cfg(feature = "webhooks")]
+mod registry {
+    use super::*;
+
+    const TIMEOUT: Duration = Duration::from_secs(10);
+
+    /// Manifest types asked for; a multi-arch image resolves to its index
+    const MANIFEST_TYPES: &str = "application/vnd.oci.image.index.v1+json, \
+        application/vnd.docker.distribution.manifest.list.v2+json, \
+        application/vnd.docker.distribution.manifest.v2+json, \
+        application/vnd.oci.image.manifest.v1+json";
+
+    pub struct HttpResolver {
+        client: reqwest::Client,
+        insecure: Vec<String>,
+    }
+
+    impl HttpResolver {
+        pub fn new(insecure: Vec<String>) -> Self {
+            Self { client: reqwest::Client::new(), insecure }
+        }
+
+        /// URL of `kind` (`manifests` or `blobs`) `reference` in the image's repository
+        fn url(&self, image: &ImageRef, kind: &str, reference: &str) -> String {
+            let host = match image.registry.as_str() {
+                DEFAULT_REGISTRY => "registry-1.docker.io",
+                host => host,
+            };
+            let scheme = if self.insecure.contains(&image.registry) { "http" } else { "https" };
+            format!("{}://{}/v2/{}/{}/{}", scheme, host, image.repository, kind, reference)
+        }
+
+        /// Send a request, again with an anonymous pull token if challenged
+        async fn send(&self, request: impl Fn() -> reqwest::RequestBuilder) -> Result<reqwest::Response> {
+            let response = request().send().await?;
+            if response.status() != reqwest::StatusCode::UNAUTHORIZED {
+                return Ok(response);
+            }
+            let challenge = response.headers()
+                .get(reqwest::header::WWW_AUTHENTICATE)
+                .and_then(|value| value.to_str().ok())
+                .unwrap_or_default()
+                .to_string();
+            let token = self.token(&challenge).await?;
+            Ok(request().bearer_auth(token).send().await?)
+        }
+
+        async fn get_json(&self, url: &str) -> Result<serde_json::Value> {
+            let response = self.send(|| self.client.get(url)
+                .header(reqwest::header::ACCEPT, MANIFEST_TYPES)
+                .timeout(TIMEOUT)).await?;
+            Ok(response.error_for_status()?.json().await?)
+        }
+
+        /// Anonymous pull token from the realm named in a 401 challenge
+        async fn token(&self, challenge: &str) -> Result<String> {
+            let params = challenge_params(challenge);
+            let realm = params.get("realm")
+                .ok_or_else(|| anyhow::anyhow!("Auth challenge without a realm: {}", challenge))?;
+            let query: Vec<(&str, &str)> = ["service", "scope"]
+                .into_iter()
+                .filter_map(|key| params.get(key).map(|value| (key, value.as_str())))
+                .collect();
+            let body: serde_json::Value = self.client.get(realm)
+                .query(&query)
+                .timeout(TIMEOUT)
+                .send()
+                .await?
+                .error_for_status()?
+                .json()
+                .await?;
+            body.get("token")
+                .or_else(|| body.get("access_token"))
+                .and_then(|token| token.as_str())
+                .map(str::to_string)
+                .ok_or_else(|| anyhow::anyhow!("Token response without a token"))
+        }
+    }
+
+    #[async_trait]
+    impl ImageResolver for HttpResolver {
+        async fn resolve(&self, image: &ImageRef) -> Result<String> {
+            let url = self.url(image, "manifests", &image.tag);
+            let response = self.send(|| self.client.head(&url)
+                .header(reqwest::header::ACCEPT, MANIFEST_TYPES)
+                .timeout(TIMEOUT)).await?;
+            response.headers()
+                .get("Docker-Content-Digest")
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_string)
+                .ok_or_else(|| anyhow::anyhow!("{} returned {} without a digest", url, response.status()))
+        }
+
+        async fn architectures(&self, image: &ImageRef) -> Result<Vec<String>> {
+            let Some(digest) = &image.digest else {
+                return Ok(Vec::new());
+            };
+            let manifest = self.get_json(&self.url(image, "manifests", digest)).await?;
+            if manifest.get("manifests").is_some() {
+                return Ok(index_architectures(&manifest));
+            }
+            // A single-platform image names its architecture in its config
+            let Some(config) = manifest.pointer("/config/digest").and_then(|digest| digest.as_str()) else {
+                return Ok(Vec::new());
+            };
+            let config = self.get_json(&self.url(image, "blobs", config)).await?;
+            Ok(config.get("architecture")
+                .and_then(|arch| arch.as_str())
+                .map(|arch| vec![normalize_arch(arch)])
+                .unwrap_or_default())
+        }
+    }
+
+    /// `key="value"` pairs of a `Bearer realm="...",service="..."` challenge
+    fn challenge_params(challenge: &str) -> HashMap<String, String> {
+        let mut params = HashMap::new();
+        let mut rest = challenge.trim().strip_prefix("Bearer").unwrap_or(challenge).trim();
+        while let Some((key, after)) = rest.split_once('=') {
+            let after = after.trim_start();
+            let (value, next) = match after.strip_prefix('"') {
+                Some(quoted) => quoted.split_once('"').unwrap_or((quoted, "")),
+                None => after.split_once(',').unwrap_or((after, "")),
+            };
+            params.insert(key.trim().trim_start_matches(',').trim().to_string(), value.to_string());
+            rest = next.trim_start_matches(',').trim();
+        }
+        params
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::sized_node;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    struct FakeResolver(AtomicUsize);
+
+    #[async_trait]
+    impl ImageResolver for FakeResolver {
+        async fn resolve(&self, image: &ImageRef) -> Result<String> {
+            self.0.fetch_add(1, Ordering::SeqCst);
+            anyhow::ensure!(image.repository != "library/missing", "manifest unknown");
+            // Architectures are cached by digest, so each repository has its own
+            let fill = if image.repository == "library/x86only" { "a" } else { "b" };
+            Ok(format!("sha256:{}", fill.repeat(64)))
+        }
+
+        async fn architectures(&self, image: &ImageRef) -> Result<Vec<String>> {
+            let index = match image.repository.as_str() {
+                "library/x86only" => serde_json::json!({"manifests": [
+                    {"platform": {"architecture": "amd64", "os": "linux"}},
+                    {"platform": {"architecture": "unknown", "os": "unknown"}},
+                ]}),
+                _ => serde_json::json!({"manifests": [
+                    {"platform": {"architecture": "amd64", "os": "linux"}},
+                    {"platform": {"architecture": "arm64", "os": "linux", "variant": "v8"}},
+                ]}),
+            };
+            Ok(index_architectures(&index))
+        }
+    }
+
+    #[test]
+    fn test_parse_image_references() {
+        let alpine = ImageRef::parse("alpine").unwrap();
+        assert_eq!((alpine.registry.as_str(), alpine.repository.as_str(), alpine.tag.as_str()), ("docker.io", "library/alpine", "latest"));
+
+        let pytorch = ImageRef::parse("pytorch/pytorch:2.1").unwrap();
+        assert_eq!((pytorch.registry.as_str(), pytorch.repository.as_str(), pytorch.tag.as_str()), ("docker.io", "pytorch/pytorch", "2.1"));
+
+        let internal = ImageRef::parse("registry.tgp:5000/builds/app@sha256:abc").unwrap();
+        assert_eq!(internal.registry, "registry.tgp:5000");
+        assert_eq!(internal.repository, "builds/app");
+        assert_eq!(internal.digest.as_deref(), Some("sha256:abc"));
+    }
+
+    #[tokio::test]
+    async fn test_pin_resolves_once_and_mirrors() {
+        let resolver = Arc::new(FakeResolver(AtomicUsize::new(0)));
+        let mirrors = ImagePolicy::parse_mirrors("docker.io=mirror.tgp:5000").unwrap();
+        let policy = ImagePolicy::new(resolver.clone(), mirrors, false);
+
+        let digest = format!("sha256:{}", "b".repeat(64));
+        let pinned = policy.pin("python:3.11").await.unwrap();
+        assert_eq!(pinned, format!("mirror.tgp:5000/library/python@{}", digest));
+        assert_eq!(policy.pin("python:3.11").await.unwrap(), pinned);
+        assert_eq!(resolver.0.load(Ordering::SeqCst), 1);
+
+        // Pinned images are not looked up; other registries are not mirrored
+        assert_eq!(policy.pin("ghcr.io/org/app@sha256:abc").await.unwrap(), "ghcr.io/org/app@sha256:abc");
+        assert_eq!(resolver.0.load(Ordering::SeqCst), 1);
+
+        let err = policy.pin("missing:1").await.unwrap_err();
+        assert_eq!(crate::error::classify(&err).unwrap().reason(), "UNAVAILABLE");
+        let open = ImagePolicy::new(resolver, HashMap::new(), true);
+        assert_eq!(open.pin("missing:1").await.unwrap(), "missing:1");
+    }
+
+    #[tokio::test]
+    async fn test_amd64_only_image_never_lands_on_arm() {
+        let mut scheduler = EconomicScheduler::new();
+        scheduler.set_image_policy(Some(ImagePolicy::new(Arc::new(FakeResolver(AtomicUsize::new(0))), HashMap::new(), false)));
+        let node = |id: &str, arch: &str, cost_per_hour: f64| crate::NodeInfo {
+            cost_per_hour,
+            arch: arch.to_string(),
+            ..sized_node(id, 4, 8, 0)
+        };
+        scheduler.register_node(node("arm-cheap", "arm64", 0.01)).unwrap();
+        let job = |id: &str, image: &str, arch: &[&str]| JobSpec {
+            id: id.to_string(),
+            container_image: image.to_string(),
+            arch: arch.iter().map(|arch| arch.to_string()).collect(),
+            resources: crate::ResourceRequirements { cpu_cores: 1, memory_gb: 1, ..Default::default() },
+            sla: crate::SlaConstraints { max_latency_ms: 10_000, ..Default::default() },
+            disable_result_cache: true,
+            ..Default::default()
+        };
+
+        let legacy = scheduler.admit(job("legacy", "x86only:1", &[])).await.unwrap();
+        assert_eq!(legacy.arch, vec!["amd64"]);
+        assert!(scheduler.schedule(legacy.clone()).await.is_err());
+        // Nodes that never reported an architecture are amd64
+        scheduler.register_node(node("x86", "", 0.05)).unwrap();
+        let mut retry = legacy;
+        retry.id = "legacy-2".to_string();
+        assert_eq!(scheduler.schedule(retry).await.unwrap().node_id, "x86");
+
+        let multi = scheduler.admit(job("multi", "python:3.11", &["aarch64"])).await.unwrap();
+        assert_eq!(multi.arch, vec!["arm64"]);
+        assert_eq!(scheduler.schedule(multi).await.unwrap().node_id, "arm-cheap");
+        assert!(scheduler.admit(job("wrong", "x86only:1", &["arm64"])).await.is_err());
+    }
+}
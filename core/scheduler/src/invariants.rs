@@ -0,0 +1,186 @@
+//! Scheduler state invariants
+//!
+//! Checks that hold whenever no placement is in flight, for tests and
+//! debugging: no node carries more committed work than its capacity, every
+//! scheduled or running job sits on a node still in the registry, the
+//! placed-job table only holds live jobs, and every queued job is still
+//! waiting. `tests/invariants.rs` drives random submit/report/fail
+//! sequences against them.
+
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::{EconomicScheduler, JobStatus};
+
+/// A broken invariant
+#[derive(Debug, Clone, PartialEq)]
+pub enum InvariantViolation {
+    /// Jobs placed on the node request more than it has
+    NodeOverAllocated {
+        node_id: String,
+        resource: &'static str,
+        committed: u32,
+        capacity: u32,
+    },
+    /// Scheduled or running on a node that is not registered
+    OrphanedJob {
+        job_id: String,
+        status: JobStatus,
+        node_id: Option<String>,
+    },
+    /// Still in the placed-job table although finished or unknown
+    StalePlacement {
+        job_id: String,
+        status: Option<JobStatus>,
+    },
+    /// In the queue but no longer waiting for placement
+    QueuedJobNotPending {
+        job_id: String,
+        status: Option<JobStatus>,
+    },
+}
+
+impl fmt::Display for InvariantViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NodeOverAllocated { node_id, resource, committed, capacity } => {
+                write!(f, "node {} has {} {} committed but only {}", node_id, committed, resource, capacity)
+            }
+            Self::OrphanedJob { job_id, status, node_id } => {
+                write!(f, "job {} is {:?} on unregistered node {:?}", job_id, status, node_id)
+            }
+            Self::StalePlacement { job_id, status } => {
+                write!(f, "job {} is still placed with status {:?}", job_id, status)
+            }
+            Self::QueuedJobNotPending { job_id, status } => {
+                write!(f, "job {} is queued with status {:?}", job_id, status)
+            }
+        }
+    }
+}
+
+impl EconomicScheduler {
+    /// Every invariant broken right now (empty when the state is sound)
+    ///
+    /// Only meaningful between operations: a job being placed is briefly
+    /// both queued and scheduled. Harvested best-effort jobs overcommit by
+    /// design and are left out of the allocation check.
+    pub async fn check_invariants(&self) -> Vec<InvariantViolation> {
+        let mut violations = Vec::new();
+
+        let nodes = self.cluster_status();
+        let placed: Vec<_> = self.placed_jobs.lock()
+            .map(|placed| placed.values().cloned().collect())
+            .unwrap_or_default();
+        let states: HashMap<String, _> = self.job_states.lock()
+            .map(|states| states.clone())
+            .unwrap_or_default();
+
+        // Committed work per node
+        let mut committed: HashMap<&str, (u32, u32)> = HashMap::new();
+        for spec in &placed {
+            let state = states.get(&spec.id);
+            let live = state.filter(|state| !state.status.is_terminal())
+                .and_then(|state| Some((state, state.assigned_node.as_ref()?)));
+            let Some((state, node_id)) = live else {
+                violations.push(InvariantViolation::StalePlacement {
+                    job_id: spec.id.clone(),
+                    status: state.map(|state| state.status.clone()),
+                });
+                continue;
+            };
+            if self.harvest.is_harvested(&spec.id) {
+                continue;
+            }
+            let on: Vec<&str> = if state.gang_nodes.is_empty() {
+                vec![node_id.as_str()]
+            } else {
+                state.gang_nodes.iter().map(String::as_str).collect()
+            };
+            for node_id in on {
+                let entry = committed.entry(node_id).or_default();
+                entry.0 += spec.resources.cpu_cores;
+                entry.1 += spec.resources.memory_gb;
+            }
+        }
+        for node in &nodes {
+            let (cpu, memory_gb) = committed.get(node.id.as_str()).copied().unwrap_or_default();
+            let limits = [
+                ("cpu", cpu, node.capacity_cpu.max(node.available_cpu)),
+                ("memory_gb", memory_gb, node.capacity_memory_gb.max(node.available_memory_gb)),
+            ];
+            for (resource, committed, capacity) in limits {
+                if committed > capacity {
+                    violations.push(InvariantViolation::NodeOverAllocated {
+                        node_id: node.id.clone(),
+                        resource,
+                        committed,
+                        capacity,
+                    });
+                }
+            }
+        }
+
+        // Scheduled and running jobs need a live node
+        for state in states.values() {
+            if !matches!(state.status, JobStatus::Scheduled | JobStatus::Running) {
+                continue;
+            }
+            let live = state.assigned_node.as_ref()
+                .is_some_and(|node_id| nodes.iter().any(|node| &node.id == node_id));
+            if !live {
+                violations.push(InvariantViolation::OrphanedJob {
+                    job_id: state.job_id.clone(),
+                    status: state.status.clone(),
+                    node_id: state.assigned_node.clone(),
+                });
+            }
+        }
+
+        // Queued jobs wait (cancelled ones are dropped when next claimed)
+        if let Some(queue) = self.job_queue() {
+            for job in queue.jobs().await.unwrap_or_default() {
+                let status = states.get(&job.id).map(|state| state.status.clone());
+                if !matches!(status, Some(JobStatus::Pending | JobStatus::Cancelled)) {
+                    violations.push(InvariantViolation::QueuedJobNotPending { job_id: job.id, status });
+                }
+            }
+        }
+
+        violations
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{JobSpec, NodeInfo, ResourceRequirements, SlaConstraints};
+
+    #[tokio::test]
+    async fn test_reports_jobs_on_lost_nodes() {
+        let scheduler = EconomicScheduler::new();
+        scheduler.register_node(NodeInfo {
+            id: "node-1".to_string(),
+            available_cpu: 4,
+            available_memory_gb: 8,
+            location: "local".to_string(),
+            cost_per_hour: 0.1,
+            ..Default::default()
+        }).unwrap();
+        scheduler.schedule(JobSpec {
+            id: "job-1".to_string(),
+            resources: ResourceRequirements { cpu_cores: 2, memory_gb: 2, ..Default::default() },
+            sla: SlaConstraints { max_latency_ms: 10_000, ..Default::default() },
+            ..Default::default()
+        }).await.unwrap();
+        assert!(scheduler.check_invariants().await.is_empty());
+
+        // Dropped without requeueing its job
+        scheduler.available_nodes.lock().unwrap().remove("node-1");
+        assert_eq!(scheduler.check_invariants().await, vec![InvariantViolation::OrphanedJob {
+            job_id: "job-1".to_string(),
+            status: JobStatus::Scheduled,
+            node_id: Some("node-1".to_string()),
+        }]);
+    }
+}
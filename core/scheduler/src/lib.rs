@@ -2,17 +2,187 @@
 //! 
 //! Core scheduling engine that optimizes job placement based on cost, performance, and SLA constraints.
 
+pub mod admission;
+pub mod alerts;
+pub mod allocation;
+pub mod arrays;
+pub mod backfill;
+pub mod bandwidth;
+pub mod builders;
+pub mod builds;
+pub mod capacity;
+pub mod ceilings;
+pub mod chaos;
+pub mod clock_skew;
+pub mod commands;
+pub mod compression;
+pub mod cost_summary;
+pub mod credits;
+pub mod datasets;
+pub mod determinism;
+pub mod devices;
+pub mod efficiency;
+pub mod embedding;
+pub mod error;
+pub mod eta;
+pub mod exec;
+pub mod events;
+pub mod fencing;
 pub mod grpc;
+pub mod hourly_rates;
+pub mod images;
+pub mod invariants;
+pub mod lifecycle;
+pub mod limits;
+pub mod market;
+pub mod node_index;
+pub mod node_pricing;
+pub mod notifications;
+pub mod outputs;
+pub mod overcommit;
+pub mod overload;
+pub mod payloads;
+pub mod payments;
+pub mod placement_cache;
+pub mod plugins;
+pub mod pools;
+pub mod power;
+pub mod predictor;
+pub mod preemption;
+pub mod preferences;
+pub mod price_holds;
+pub mod priority;
+pub mod providers;
+pub mod queue;
+pub mod queues;
+pub mod rbac;
+pub mod rebalance;
+pub mod relaxation;
+pub mod reconcile;
+pub mod relay;
+pub mod repricing;
+pub mod reputation;
+pub mod rightsizing;
+pub mod result_cache;
+pub mod scoring;
+pub mod shutdown;
+pub mod snapshot;
+pub mod start_windows;
+pub mod store;
+pub mod templates;
+pub mod tenant_reservations;
+pub mod testkit;
+pub mod timeseries;
+pub mod topology;
+pub mod trace;
+pub mod transfers;
+pub mod transitions;
+pub mod verification;
+pub mod worker_updates;
+pub mod workflows;
+
+#[cfg(test)]
+mod test_support;
 
 use anyhow::Result;
+use rayon::prelude::*;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::sync::{Arc, Mutex};
+use tokio::sync::{broadcast, mpsc};
 use tgp_cost_engine::{CostCalculator, TotalCost};
 use tgp_optimizer::Optimizer;
 
+use admission::AdmissionController;
+use arrays::JobArrays;
+use backfill::Reservations;
+use bandwidth::BandwidthModel;
+use builds::ImageBuilds;
+use capacity::CapacityPlanner;
+use ceilings::CostCeilings;
+use chaos::Faults;
+use clock_skew::ClockSkew;
+use commands::ControlAuth;
+use credits::TenantCredits;
+use datasets::DatasetRegistry;
+use devices::DeviceAllocator;
+use efficiency::NodeTenure;
+use embedding::{ConsolidationPlanner, CostModel};
+use error::SchedulerError;
+use eta::QueuedJobs;
+use events::SchedulerEvent;
+use fencing::NodeFencing;
+use images::ImagePolicy;
+use lifecycle::{JobHook, JobPhase};
+use limits::ConcurrencyLimits;
+use node_index::NodeIndex;
+use node_pricing::NodePricing;
+use notifications::Notifier;
+use outputs::JobOutputs;
+use overcommit::{HarvestTracker, OvercommitPolicy};
+use overload::LoadShedder;
+use payloads::PayloadStore;
+use placement_cache::{PlacementCache, PlacementShape};
+use plugins::Plugins;
+use pools::NodePools;
+use power::PowerManager;
+use predictor::{Prediction, Predictor};
+use preemption::InterruptionTracker;
+use preferences::{PreferenceViolation, SoftConstraint};
+use price_holds::PriceHolds;
+use priority::PriorityClass;
+use providers::ProviderLedger;
+use queue::JobQueue;
+use queues::QueuePolicies;
+use rbac::AccessControl;
+use relay::CommandRelay;
+use reputation::ReputationTracker;
+use rightsizing::UsageHistory;
+use result_cache::{CachedResult, ResultCache};
+use scoring::CustomScoring;
+use shutdown::Drain;
+use start_windows::StartWindows;
+use store::{PersistOp, StateStore};
+use templates::JobTemplates;
+use tenant_reservations::CapacityBlocks;
+use timeseries::TimeSeriesStore;
+use trace::TraceRecord;
+use transfers::{TransferLedger, TransferRecord};
+use transitions::JobHistory;
+use verification::{VerificationPolicy, Verifications};
+use worker_updates::UpdateChannel;
+use workflows::Workflows;
+
+/// Price per GB for staging datasets onto a node that lacks a local copy
+pub const DATA_TRANSFER_PRICE_PER_GB: f64 = 0.01;
+
+/// Run time assumed on the reference node when a job gives no estimate
+pub const DEFAULT_DURATION_HOURS: f64 = 1.0;
+
+/// Largest job input accepted; bulk data belongs in a dataset
+pub const MAX_JOB_INPUT_BYTES: usize = 1024 * 1024;
+
+/// Largest total size of a job's annotation keys and values
+pub const MAX_ANNOTATION_BYTES: usize = 16 * 1024;
+
+/// Check that rejected a candidate node
+#[derive(Debug, Clone, Copy)]
+enum Rejection {
+    Avoided,
+    /// Estimated latency (ms) over the limit
+    Latency(u64),
+    Deadline,
+    /// Estimated TCO (USD) over the budget
+    Budget(f64),
+    /// Estimated TCO (USD) over the job type's cost ceiling
+    Ceiling(f64),
+    /// Hourly rate (USD) over the job's cap
+    HourlyRate(f64),
+    Plugin,
+}
+
 /// Job specification submitted by users
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct JobSpec {
     /// Unique job identifier
     pub id: String,
@@ -22,24 +192,138 @@ pub struct JobSpec {
     pub resources: ResourceRequirements,
     /// SLA constraints
     pub sla: SlaConstraints,
+    /// Container image to run
+    #[serde(default)]
+    pub container_image: String,
+    /// Container command (image default when empty)
+    #[serde(default)]
+    pub command: Vec<String>,
+    /// Job input (JSON args or a small file), handed to the container as
+    /// a file at `$TGP_INPUT_FILE` (`{input}` in the command) or on stdin
+    #[serde(default)]
+    pub job_data: Vec<u8>,
+    /// Feed `job_data` to the container's stdin rather than a file
+    #[serde(default)]
+    pub input_stdin: bool,
+    /// Always run the job, even if an identical result is cached
+    #[serde(default)]
+    pub disable_result_cache: bool,
+    /// Named input datasets (see `datasets::DatasetRegistry`)
+    #[serde(default)]
+    pub datasets: Vec<String>,
+    /// Job can be checkpointed and moved by the rebalancer
+    #[serde(default)]
+    pub movable: bool,
+    /// Expected run time on the reference node (performance score 1.0)
+    #[serde(default)]
+    pub estimated_duration_hours: Option<f64>,
+    /// May run in harvested idle capacity and be evicted when it is reclaimed
+    #[serde(default)]
+    pub best_effort: bool,
+    /// Nodes needed at once, each with `resources` (0 or 1: not a gang)
+    #[serde(default)]
+    pub gang_size: u32,
+    /// Queue priority class (aged by wait time, see `priority::AgingPolicy`)
+    #[serde(default)]
+    pub priority: PriorityClass,
+    /// Owning tenant, for per-tenant concurrency limits
+    #[serde(default)]
+    pub tenant: String,
+    /// Job array this task belongs to, if any
+    #[serde(default)]
+    pub array_id: Option<String>,
+    /// Tasks of the same array allowed to run at once (0: unlimited)
+    #[serde(default)]
+    pub max_parallel: u32,
+    /// Container environment variables
+    #[serde(default)]
+    pub env: HashMap<String, String>,
+    /// Nodes the job must not be placed on (e.g. the node a verification
+    /// replica is checking)
+    #[serde(default)]
+    pub avoid_nodes: Vec<String>,
+    /// Nodes the job may only be placed on (empty: any), e.g. builder
+    /// nodes for image builds
+    #[serde(default)]
+    pub pin_nodes: Vec<String>,
+    /// Container ports to publish; each gets a host port on the node
+    #[serde(default)]
+    pub ports: Vec<u16>,
+    /// Run on dedicated cores, kept within one NUMA node when possible
+    /// (for latency-sensitive inference)
+    #[serde(default)]
+    pub pin_cpus: bool,
+    /// Cap on the job's outbound traffic in Mbit/s (None: unlimited)
+    #[serde(default)]
+    pub egress_limit_mbps: Option<u32>,
+    /// File or directory in the container uploaded as the job's output
+    /// when it finishes (see `outputs`)
+    #[serde(default)]
+    pub output_path: Option<String>,
+    /// Input kept in the payload store instead of `job_data` (`sha256:<hex>`)
+    #[serde(default)]
+    pub payload_digest: Option<String>,
+    /// Operator's cost ceiling for the job's type, set at admission (see
+    /// `ceilings`)
+    #[serde(default)]
+    pub cost_ceiling_usd: Option<f64>,
+    /// Hold the job this long for a placement within its budget instead of
+    /// failing it (see `price_holds`)
+    #[serde(default)]
+    pub price_hold_secs: Option<u64>,
+    /// Template the job was instantiated from, for right-sizing
+    #[serde(default)]
+    pub template: Option<String>,
+    /// Named queue the job is submitted to (see `queues`)
+    #[serde(default)]
+    pub queue: Option<String>,
+    /// Node pool the job must run in (see `pools`)
+    #[serde(default)]
+    pub pool: Option<String>,
+    /// CPU architectures the job can run on (empty: any), narrowed at
+    /// admission to those its image is built for (see `images`)
+    #[serde(default)]
+    pub arch: Vec<String>,
+    /// Run before the job's container; the job fails if it does (see
+    /// `lifecycle`)
+    #[serde(default)]
+    pub pre_start_hook: Option<JobHook>,
+    /// Run after the job's container, whatever its outcome
+    #[serde(default)]
+    pub post_complete_hook: Option<JobHook>,
+    /// Notified of the job's final status and cost (None: the tenant's
+    /// URL, if any; see `notifications`)
+    #[serde(default)]
+    pub callback_url: Option<String>,
+    /// Caller's metadata, kept with the job's state and returned unchanged
+    #[serde(default)]
+    pub annotations: HashMap<String, String>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum JobType {
     Training,
+    #[default]
     Inference,
     DataProcessing,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct ResourceRequirements {
     pub cpu_cores: u32,
     pub memory_gb: u32,
     pub gpu_count: u32,
     pub disk_gb: u32,
+    /// Network bandwidth the job keeps busy, in Mbit/s (0: none)
+    #[serde(default)]
+    pub network_mbps: u32,
+    /// Disk throughput cap in MB/s, from which the IOPS cap is derived
+    /// (None: unthrottled)
+    #[serde(default)]
+    pub disk_io_mbps: Option<u32>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct SlaConstraints {
     /// Maximum acceptable latency in milliseconds
     pub max_latency_ms: u64,
@@ -47,6 +331,17 @@ pub struct SlaConstraints {
     pub max_budget_usd: Option<f64>,
     /// Deadline timestamp
     pub deadline: Option<i64>,
+    /// Do not start before this timestamp (see `start_windows`)
+    #[serde(default)]
+    pub earliest_start: Option<i64>,
+    /// Most the job may cost per hour, whatever its run time (see
+    /// `hourly_rates`)
+    #[serde(default)]
+    pub max_hourly_rate_usd: Option<f64>,
+    /// Weighted wishes that steer placement without ruling nodes out (see
+    /// `preferences`)
+    #[serde(default)]
+    pub preferences: Vec<SoftConstraint>,
 }
 
 /// Placement decision for a job
@@ -55,69 +350,572 @@ pub struct Placement {
     pub job_id: String,
     pub node_id: String,
     pub estimated_cost: TotalCost,
+    /// Compute latency plus data staging time
     pub estimated_latency_ms: u64,
+    /// Time to stage missing datasets onto the node
+    #[serde(default)]
+    pub estimated_staging_ms: u64,
+    /// Expected run time on the chosen node, scaled by its performance
+    #[serde(default)]
+    pub estimated_duration_hours: f64,
+    /// Source job id when the result was served from the result cache
+    pub cached_from: Option<String>,
+    /// Every node of a gang job (`node_id` is the first)
+    #[serde(default)]
+    pub gang_nodes: Vec<String>,
+    /// TCO scaled by the node's reliability penalty, plus any scoring
+    /// script, plugin and soft-constraint terms, used to rank candidates
+    #[serde(default)]
+    pub score_usd: f64,
+    /// Soft constraints the chosen node misses
+    #[serde(default)]
+    pub preference_violations: Vec<PreferenceViolation>,
 }
 
 /// Job status tracking
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
 pub enum JobStatus {
+    #[default]
     Pending,
     Scheduled,
     Running,
     Completed,
     Failed,
+    /// Withdrawn before it ran (e.g. its job array was aborted)
+    Cancelled,
+}
+
+impl JobStatus {
+    /// Whether the job has finished and will not change state again
+    pub fn is_terminal(&self) -> bool {
+        matches!(self, JobStatus::Completed | JobStatus::Failed | JobStatus::Cancelled)
+    }
 }
 
 /// Job state information
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct JobState {
     pub job_id: String,
     pub status: JobStatus,
     pub assigned_node: Option<String>,
     pub estimated_cost: Option<TotalCost>,
+    /// Latest progress reported by the running container
+    pub progress: Option<JobProgress>,
+    /// Source job id when the result was served from the result cache
+    pub cached_from: Option<String>,
+    /// Every node of a gang job (`assigned_node` is the first)
+    #[serde(default)]
+    pub gang_nodes: Vec<String>,
+    /// Manifest digest the job's image was pinned to at admission
+    #[serde(default)]
+    pub image_digest: Option<String>,
+    /// Named queue the job was admitted to
+    #[serde(default)]
+    pub queue: Option<String>,
+    /// Phase of a running job with lifecycle hooks, as last reported
+    #[serde(default)]
+    pub phase: Option<JobPhase>,
+    /// Unix timestamps of the job's milestones (see `transitions`)
+    #[serde(default)]
+    pub submitted_at: Option<i64>,
+    #[serde(default)]
+    pub scheduled_at: Option<i64>,
+    #[serde(default)]
+    pub started_at: Option<i64>,
+    #[serde(default)]
+    pub finished_at: Option<i64>,
+    /// The job spec's annotations
+    #[serde(default)]
+    pub annotations: HashMap<String, String>,
+}
+
+/// Progress reported by a job through the worker's progress file
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct JobProgress {
+    /// Completion percentage (0-100)
+    pub percent: f64,
+    /// Free-form description of the current step
+    pub step: String,
+    /// Custom job metrics (loss, rows processed, ...)
+    pub metrics: HashMap<String, f64>,
+    /// Unix timestamp of the last update
+    pub updated_at: i64,
 }
 
 /// The Economic Scheduler - core component of TGP (Thread-Safe)
 #[derive(Clone)]
 pub struct EconomicScheduler {
-    cost_calculator: CostCalculator,
-    #[allow(dead_code)]  // Reserved for future advanced placement algorithms
-    optimizer: Optimizer,
+    /// Prices candidate placements (Formula 4.1 unless replaced, see
+    /// `embedding`)
+    cost_model: Arc<dyn CostModel>,
+    /// Plans consolidation for the rebalancer
+    optimizer: Arc<dyn ConsolidationPlanner>,
+    /// Nodes last listed by a `NodeSource`
+    sourced_nodes: Arc<Mutex<HashSet<String>>>,
+    /// Stable ordering of node and job lists (see `determinism`)
+    deterministic: bool,
     /// Thread-safe node registry for concurrent gRPC access
-    available_nodes: Arc<Mutex<HashMap<String, NodeInfo>>>,
+    available_nodes: Arc<Mutex<NodeIndex>>,
+    /// Cluster state version of `available_nodes`
+    cluster_version: tokio::sync::watch::Receiver<u64>,
     /// Thread-safe job state tracking
     job_states: Arc<Mutex<HashMap<String, JobState>>>,
+    /// Notifies watchers with the id of every job whose state changed
+    job_updates: broadcast::Sender<String>,
+    /// Job and node lifecycle events for external consumers
+    events: broadcast::Sender<SchedulerEvent>,
+    /// Results of completed jobs, keyed by content address
+    result_cache: ResultCache,
+    /// Datasets advertised by workers, for data-locality scoring
+    datasets: DatasetRegistry,
+    /// Peer-to-peer transfer progress and bandwidth accounting
+    transfers: TransferLedger,
+    /// Per-link bandwidth between locations, for staging time estimates
+    bandwidth: BandwidthModel,
+    /// Write-behind queue to the attached state store, if any
+    persist: Option<mpsc::UnboundedSender<PersistOp>>,
+    /// Shared pending-job queue (queued mode), if any
+    queue: Option<Arc<dyn JobQueue>>,
+    /// Workload trace writer, if recording
+    trace: Option<mpsc::UnboundedSender<TraceRecord>>,
+    /// Evaluate candidates in parallel once there are at least this many
+    parallel_min_candidates: Option<usize>,
+    /// Node rankings of recently placed job shapes
+    placement_cache: PlacementCache,
+    /// Specs of jobs placed on a node and not yet finished (for rebalancing)
+    placed_jobs: Arc<Mutex<HashMap<String, JobSpec>>>,
+    /// Node sleep state and C_idle accounting
+    power: PowerManager,
+    /// Preemptible nodes with a pending termination notice
+    interruptions: InterruptionTracker,
+    /// Duration and peak-memory forecasts learned from finished jobs
+    predictor: Predictor,
+    /// Place best-effort jobs in idle reserved capacity when set
+    overcommit: Option<OvercommitPolicy>,
+    /// Measured node usage and harvested placements
+    harvest: HarvestTracker,
+    /// Waiting gang jobs and expected end times for backfill
+    reservations: Reservations,
+    /// Running-job caps checked by the queue dispatcher
+    concurrency: Arc<Mutex<ConcurrencyLimits>>,
+    /// Per-job-type cost caps applied at admission
+    ceilings: CostCeilings,
+    /// Named queues and their policies
+    queues: QueuePolicies,
+    /// Node pools jobs may target
+    pools: NodePools,
+    /// Capacity reserved for tenants over time windows
+    capacity_blocks: CapacityBlocks,
+    /// Offsets of worker clocks from the scheduler's
+    clock_skew: ClockSkew,
+    /// Completion callbacks of finished jobs
+    notifier: Notifier,
+    /// Over-budget jobs waiting for a price drop
+    price_holds: PriceHolds,
+    /// Signalled when node prices change (see `repricing`)
+    price_changes: Arc<tokio::sync::Notify>,
+    /// Jobs waiting for their start window
+    start_windows: StartWindows,
+    /// Submitted job arrays
+    arrays: JobArrays,
+    /// Submitted workflows and their step runs
+    workflows: Workflows,
+    /// Named job templates
+    templates: JobTemplates,
+    /// Per-node reliability, penalizing unreliable nodes in placement
+    reputation: ReputationTracker,
+    /// Re-run sampled jobs of untrusted nodes elsewhere when set
+    verification_policy: Option<VerificationPolicy>,
+    /// Verified jobs and their replicas
+    verifications: Verifications,
+    /// Provider credits for delivered compute and their settlements
+    providers: ProviderLedger,
+    /// Bounds and rate limits on provider price updates
+    node_pricing: NodePricing,
+    /// Prepaid tenant balances and the estimates reserved against them
+    credits: TenantCredits,
+    /// Token presented to worker control services
+    control_auth: ControlAuth,
+    /// Demand and capacity history for capacity planning
+    capacity: CapacityPlanner,
+    /// Per-node utilization and per-tenant spend over time
+    metrics_history: TimeSeriesStore,
+    /// When each node first registered, for its paid hours
+    tenure: NodeTenure,
+    /// Metered peaks of finished jobs per template, for right-sizing
+    usage: UsageHistory,
+    /// Placement load and shedding of best-effort submissions
+    shedder: LoadShedder,
+    /// External policy check on submissions, if configured
+    admission: Option<AdmissionController>,
+    /// Supported worker versions and the release workers update to
+    update_channel: UpdateChannel,
+    /// Operator script adding a per-node score term, if loaded
+    scoring: CustomScoring,
+    /// Sandboxed WASM filter/score/admission plugins
+    plugins: Plugins,
+    /// Injected faults (`chaos` feature)
+    faults: Faults,
+    /// Status changes of every job
+    history: JobHistory,
+    /// Jobs enqueued here, for wait-time estimates
+    queued: QueuedJobs,
+    /// Shutdown flag and placements in progress
+    drain: Drain,
+    /// Command outboxes of workers on a worker-initiated stream
+    relay: CommandRelay,
+    /// Registration tokens that fence off superseded worker processes
+    fencing: NodeFencing,
+    /// GPU indices and host ports held by placed jobs
+    devices: DeviceAllocator,
+    /// Uploaded job output archives
+    outputs: JobOutputs,
+    /// Caller roles for operator RPCs
+    access: AccessControl,
+    /// Image builds and the internal registry they push to
+    builds: ImageBuilds,
+    /// Digest pinning and registry mirrors for submitted images
+    images: Option<ImagePolicy>,
+    /// Content-addressed job inputs
+    payloads: Option<PayloadStore>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct NodeInfo {
     pub id: String,
+    /// Allocatable to jobs: capacity minus the node's system reserve
     pub available_cpu: u32,
     pub available_memory_gb: u32,
     pub available_gpu: u32,
     pub location: String,
     pub cost_per_hour: f64,
+    /// Address of the worker's peer-to-peer DataService (empty if none)
+    #[serde(default)]
+    pub data_service_addr: String,
+    /// Spot/preemptible instance that the provider may reclaim
+    #[serde(default)]
+    pub preemptible: bool,
+    /// Benchmark score relative to the reference node (0: not benchmarked)
+    #[serde(default)]
+    pub performance_score: f64,
+    /// Marketplace provider that owns the node (empty: operator-owned)
+    #[serde(default)]
+    pub provider: String,
+    /// Address of the worker's control service (empty if unreachable)
+    #[serde(default)]
+    pub control_addr: String,
+    /// Full CPU capacity, including the reserve (0: not reported)
+    #[serde(default)]
+    pub capacity_cpu: u32,
+    /// Full memory capacity, including the reserve (0: not reported)
+    #[serde(default)]
+    pub capacity_memory_gb: u32,
+    /// Free disk for job scratch space, updated by resource reports
+    /// (None: not tracked, any disk requirement fits)
+    #[serde(default)]
+    pub available_disk_gb: Option<u32>,
+    /// Measured network bandwidth in Mbit/s (None: not measured)
+    #[serde(default)]
+    pub network_mbps: Option<u32>,
+    /// GPU interconnect, for multi-GPU placement (None: not reported)
+    #[serde(default)]
+    pub gpu_topology: Option<topology::GpuTopology>,
+    /// CPU ids of each NUMA node (empty: not reported)
+    #[serde(default)]
+    pub numa_cpus: Vec<Vec<u32>>,
+    /// Operator labels, for node pools
+    #[serde(default)]
+    pub labels: HashMap<String, String>,
+    /// CPU architecture, e.g. amd64 or arm64 (empty: not reported)
+    #[serde(default)]
+    pub arch: String,
+}
+
+impl NodeInfo {
+    /// Relative performance, treating unbenchmarked nodes as the reference
+    pub fn performance(&self) -> f64 {
+        if self.performance_score > 0.0 { self.performance_score } else { 1.0 }
+    }
+
+    /// Price of one reference-node hour of work on this node
+    pub fn cost_per_performance(&self) -> f64 {
+        self.cost_per_hour / self.performance()
+    }
+
+    /// CPU architecture; nodes that do not report one are amd64
+    pub fn cpu_arch(&self) -> &str {
+        if self.arch.is_empty() { images::DEFAULT_ARCH } else { &self.arch }
+    }
+
+    /// Whether the node has room for `disk_gb` of scratch space
+    pub fn fits_disk(&self, disk_gb: u32) -> bool {
+        self.available_disk_gb.map_or(true, |free| free >= disk_gb)
+    }
 }
 
 impl EconomicScheduler {
     /// Create a new Economic Scheduler instance
     pub fn new() -> Self {
+        let (nodes, cluster_version) = NodeIndex::published();
         Self {
-            cost_calculator: CostCalculator::new(),
-            optimizer: Optimizer::new(),
-            available_nodes: Arc::new(Mutex::new(HashMap::new())),
+            cost_model: Arc::new(CostCalculator::new()),
+            optimizer: Arc::new(Optimizer::new()),
+            sourced_nodes: Arc::new(Mutex::new(HashSet::new())),
+            deterministic: false,
+            available_nodes: Arc::new(Mutex::new(nodes)),
+            cluster_version,
             job_states: Arc::new(Mutex::new(HashMap::new())),
+            job_updates: broadcast::channel(1024).0,
+            events: broadcast::channel(1024).0,
+            result_cache: ResultCache::new(),
+            datasets: DatasetRegistry::new(),
+            transfers: TransferLedger::new(),
+            bandwidth: BandwidthModel::new(),
+            persist: None,
+            queue: None,
+            trace: None,
+            parallel_min_candidates: None,
+            placement_cache: PlacementCache::new(None),
+            placed_jobs: Arc::new(Mutex::new(HashMap::new())),
+            power: PowerManager::new(),
+            interruptions: InterruptionTracker::new(),
+            predictor: Predictor::new(),
+            overcommit: None,
+            harvest: HarvestTracker::new(),
+            reservations: Reservations::new(),
+            concurrency: Arc::new(Mutex::new(ConcurrencyLimits::new())),
+            ceilings: CostCeilings::new(),
+            queues: QueuePolicies::default(),
+            pools: NodePools::default(),
+            capacity_blocks: CapacityBlocks::new(),
+            clock_skew: ClockSkew::default(),
+            notifier: Notifier::default(),
+            price_holds: PriceHolds::new(),
+            price_changes: Arc::new(tokio::sync::Notify::new()),
+            start_windows: StartWindows::new(),
+            arrays: JobArrays::new(),
+            workflows: Workflows::new(),
+            templates: JobTemplates::new(),
+            reputation: ReputationTracker::new(),
+            verification_policy: None,
+            verifications: Verifications::new(),
+            providers: ProviderLedger::new(),
+            node_pricing: NodePricing::default(),
+            credits: TenantCredits::default(),
+            control_auth: ControlAuth::default(),
+            capacity: CapacityPlanner::new(),
+            metrics_history: TimeSeriesStore::default(),
+            tenure: NodeTenure::new(),
+            usage: UsageHistory::new(),
+            shedder: LoadShedder::new(),
+            admission: None,
+            update_channel: UpdateChannel::default(),
+            scoring: CustomScoring::new(),
+            plugins: Plugins::new(),
+            faults: Faults::new(),
+            history: JobHistory::new(),
+            queued: QueuedJobs::new(),
+            drain: Drain::new(),
+            relay: CommandRelay::new(),
+            fencing: NodeFencing::default(),
+            devices: DeviceAllocator::default(),
+            outputs: JobOutputs::default(),
+            access: AccessControl::default(),
+            builds: ImageBuilds::default(),
+            images: None,
+            payloads: None,
+        }
+    }
+
+    /// Switch to queued mode: submissions are enqueued and placed by
+    /// `queue::run_dispatcher` on any replica sharing the queue
+    pub fn attach_queue(&mut self, queue: Arc<dyn JobQueue>) {
+        self.queue = Some(queue);
+    }
+
+    /// Shared pending-job queue, when running in queued mode
+    pub fn job_queue(&self) -> Option<Arc<dyn JobQueue>> {
+        self.queue.clone()
+    }
+
+    /// Record a new job as pending and push it onto the shared queue
+    ///
+    /// Fails with `AlreadyExists` when the job id is already known.
+    pub async fn enqueue(&self, job: JobSpec) -> Result<()> {
+        if self.queue.is_none() {
+            anyhow::bail!("No job queue attached");
+        }
+        self.mark_pending(&job, false)?;
+        self.push_queued(job).await
+    }
+
+    /// Push a job already recorded as pending onto the shared queue
+    pub(crate) async fn push_queued(&self, job: JobSpec) -> Result<()> {
+        let queue = self.queue.clone()
+            .ok_or_else(|| anyhow::anyhow!("No job queue attached"))?;
+        self.queued.record(&job);
+
+        queue.push(&job).await?;
+        tracing::info!("Job {} queued for placement", job.id);
+        Ok(())
+    }
+
+    /// Record `job` as Pending
+    ///
+    /// A submission must bring an id the scheduler has not seen; a requeue
+    /// takes back a job that has not finished (or starts a new one).
+    fn mark_pending(&self, job: &JobSpec, requeue: bool) -> Result<()> {
+        {
+            let mut states = self.job_states.lock()
+                .map_err(|e| anyhow::anyhow!("Lock poisoned: {}", e))?;
+            match states.get(&job.id).map(|state| state.status.clone()) {
+                None => {}
+                Some(from) if requeue && from.is_terminal() => {
+                    return Err(SchedulerError::InvalidTransition { job_id: job.id.clone(), from, to: JobStatus::Pending }.into());
+                }
+                Some(_) if requeue => {}
+                Some(_) => return Err(SchedulerError::already_exists("Job", &job.id).into()),
+            }
+            let submitted_at = self.record_submission(&job.id, states.get(&job.id));
+            states.insert(job.id.clone(), JobState {
+                job_id: job.id.clone(),
+                status: JobStatus::Pending,
+                image_digest: images::digest_of(&job.container_image),
+                queue: job.queue.clone(),
+                submitted_at: Some(submitted_at),
+                annotations: job.annotations.clone(),
+                ..Default::default()
+            });
+        }
+        self.retain_payload(job);
+        self.notify_job_update(&job.id);
+        self.publish_event(SchedulerEvent::JobSubmitted {
+            job_id: job.id.clone(),
+            timestamp: unix_now(),
+        });
+        Ok(())
+    }
+
+    /// Attach a persistent state store
+    ///
+    /// Nodes, jobs, node identities and tenant credits already in the store
+    /// are loaded into memory, then every subsequent change is written behind
+    /// in order. Call before cloning the scheduler into the gRPC server.
+    pub async fn attach_store(&mut self, store: Arc<dyn StateStore>) -> Result<()> {
+        let nodes = store.load_nodes().await?;
+        let jobs = store.load_jobs().await?;
+        let identities = store.load_identities().await?;
+        let credits = store.load_credits().await?;
+        let references = store.load_credit_references().await?;
+        tracing::info!(
+            "Restored {} nodes, {} jobs, {} node identities and {} credit accounts from state store",
+            nodes.len(), jobs.len(), identities.len(), credits.len()
+        );
+        self.fencing.restore(identities);
+        self.credits.restore(credits, references);
+
+        {
+            let mut available = self.available_nodes.lock()
+                .map_err(|e| anyhow::anyhow!("Lock poisoned: {}", e))?;
+            for node in nodes {
+                available.insert(node);
+            }
+        }
+        {
+            let mut states = self.job_states.lock()
+                .map_err(|e| anyhow::anyhow!("Lock poisoned: {}", e))?;
+            for job in jobs {
+                states.insert(job.job_id.clone(), job);
+            }
+        }
+
+        let (tx, rx) = mpsc::unbounded_channel();
+        tokio::spawn(store::run_persister(store, rx));
+        self.credits.attach(tx.clone());
+        self.persist = Some(tx);
+        Ok(())
+    }
+
+    /// Wait until every state write queued so far has reached the store
+    pub async fn flush_state(&self) -> Result<()> {
+        let Some(persist) = &self.persist else {
+            return Ok(());
+        };
+        let (done, flushed) = tokio::sync::oneshot::channel();
+        persist.send(PersistOp::Flush(done))
+            .map_err(|_| anyhow::anyhow!("State persister stopped"))?;
+        flushed.await.map_err(|_| anyhow::anyhow!("State persister stopped"))?;
+        Ok(())
+    }
+
+    /// Evaluate candidate nodes on the rayon pool when a job has at least
+    /// `min_candidates` of them (`None` keeps evaluation sequential)
+    pub fn set_parallel_evaluation(&mut self, min_candidates: Option<usize>) {
+        self.parallel_min_candidates = min_candidates;
+    }
+
+    /// Reuse the node ranking of a job shape for `ttl` while no node
+    /// changes (`None` evaluates every job against the whole cluster)
+    pub fn set_placement_cache_ttl(&mut self, ttl: Option<std::time::Duration>) {
+        self.placement_cache = PlacementCache::new(ttl);
+    }
+
+    pub fn placement_cache(&self) -> &PlacementCache {
+        &self.placement_cache
+    }
+
+    /// Record incoming requests to a workload trace file (JSON lines)
+    ///
+    /// Call before cloning the scheduler into the gRPC server.
+    pub fn attach_trace(&mut self, path: impl Into<std::path::PathBuf>) {
+        let path = path.into();
+        tracing::info!("Recording workload trace to {}", path.display());
+        let (tx, rx) = mpsc::unbounded_channel();
+        tokio::spawn(trace::run_trace_writer(path, rx));
+        self.trace = Some(tx);
+    }
+
+    /// Append a record to the workload trace, if recording
+    pub fn record_trace(&self, record: TraceRecord) {
+        if let Some(trace) = &self.trace {
+            let _ = trace.send(record);
         }
     }
 
     /// Register a new node in the cluster (thread-safe)
     pub fn register_node(&self, node: NodeInfo) -> Result<()> {
         tracing::info!("Registering node: {} at {}", node.id, node.location);
+
+        // Nodes about to be reclaimed must not rejoin the placement pool
+        if self.interruptions.is_interrupted(&node.id, unix_now()) {
+            tracing::warn!("Ignoring registration of node {} pending termination", node.id);
+            return Ok(());
+        }
+
+        // A dormant node registering again has been powered back up
+        self.reactivate_node(&node.id);
         
+        let event = SchedulerEvent::NodeRegistered {
+            node_id: node.id.clone(),
+            location: node.location.clone(),
+            cost_per_hour: node.cost_per_hour,
+            timestamp: unix_now(),
+        };
+
         let mut nodes = self.available_nodes.lock()
             .map_err(|e| anyhow::anyhow!("Lock poisoned: {}", e))?;
         
-        nodes.insert(node.id.clone(), node);
+        nodes.insert(node.clone());
+        drop(nodes);
+        self.tenure.registered(&node.id, unix_now());
+
+        if let Some(persist) = &self.persist {
+            let _ = persist.send(PersistOp::Node(node));
+        }
+
+        self.publish_event(event);
+        self.price_holds.wake();
+        self.prices_changed();
         Ok(())
     }
 
@@ -127,94 +925,182 @@ impl EconomicScheduler {
     /// - Calculate C_total for each possible placement using Formula 4.1
     /// - Validate SLA constraints
     /// - Select placement that minimizes TCO while satisfying SLA
+    ///
+    /// Fails with `AlreadyExists` when the job id is already known.
     pub async fn schedule(&self, job: JobSpec) -> Result<Placement> {
+        self.mark_pending(&job, false)?;
+        self.place(job).await
+    }
+
+    /// Place a job already recorded as Pending: claimed from the queue,
+    /// held for a price or start window, or requeued
+    ///
+    /// A job another replica enqueued is recorded here first; one that
+    /// finished meanwhile is refused.
+    pub async fn place(&self, job: JobSpec) -> Result<Placement> {
         tracing::info!("Scheduling job: {} (Formula 4.1)", job.id);
 
-        // Create initial job state
-        {
-            let mut states = self.job_states.lock()
-                .map_err(|e| anyhow::anyhow!("Lock poisoned: {}", e))?;
-            
-            states.insert(job.id.clone(), JobState {
-                job_id: job.id.clone(),
-                status: JobStatus::Pending,
-                assigned_node: None,
-                estimated_cost: None,
-            });
+        match self.get_job_state(&job.id).map(|state| state.status) {
+            None => self.mark_pending(&job, false)?,
+            Some(JobStatus::Pending) => {}
+            Some(from) => {
+                return Err(SchedulerError::InvalidTransition { job_id: job.id.clone(), from, to: JobStatus::Scheduled }.into());
+            }
         }
 
-        // Get nodes snapshot for scheduling
-        let nodes = {
-            let nodes_lock = self.available_nodes.lock()
-                .map_err(|e| anyhow::anyhow!("Lock poisoned: {}", e))?;
-            nodes_lock.clone()
+        // Jobs with a start window in the future wait for it
+        if let Some(start_at) = job.sla.earliest_start.filter(|&start_at| start_at > unix_now()) {
+            let job_id = job.id.clone();
+            self.defer_until(job, start_at)?;
+            anyhow::bail!("Job {} is held until its start window opens at {}", job_id, start_at);
+        }
+
+        // Identical job already completed: reuse its result instead of re-running
+        let cache_key = result_cache::cache_key(&job, &self.datasets);
+        if !job.disable_result_cache {
+            if let Some(cached) = self.result_cache.lookup(&cache_key) {
+                return self.complete_from_cache(&job, cached);
+            }
+        }
+
+        // Nodes held for waiting gangs only take jobs that finish in time
+        let reserved = self.reservations.reserved_until();
+        let now = unix_now();
+
+        // Capacity reserved for other tenants stays free
+        let held = self.capacity_held_from(&job, now)?;
+
+        // Node bandwidth is shared by the jobs placed there
+        let network_in_use = if job.resources.network_mbps > 0 {
+            self.network_in_use()?
+        } else {
+            HashMap::new()
         };
 
-        if nodes.is_empty() {
+        // Jobs already placed on a node hold their share of it
+        let loads = self.node_loads()?;
+        let reported = self.reported_free();
+
+        // Forecast once per job; memory is raised to the predicted peak
+        let prediction = self.predict_job(&job);
+        let duration_hours = self.reference_duration_hours(&job, prediction.as_ref());
+        let required = Self::predicted_requirements(&job, prediction.as_ref());
+
+        // Harvest accounting reads the node registry, so it runs before the lock
+        let harvest_targets = if job.best_effort && job.gang_size <= 1 {
+            self.harvest_targets(&required)
+        } else {
+            Default::default()
+        };
+
+        // Evaluate against the live index instead of cloning the registry
+        let nodes = self.available_nodes.lock()
+            .map_err(|e| anyhow::anyhow!("Lock poisoned: {}", e))?;
+        if held.is_some_and(|held| !held.leaves_room(&nodes, &required, job.gang_size.max(1))) {
+            drop(nodes);
             self.update_job_state(job.id.clone(), JobStatus::Failed, None)?;
-            anyhow::bail!("No nodes available in cluster");
+            return Err(anyhow::Error::from(SchedulerError::NoCapacity { job_id: job.id.clone() })
+                .context("Free capacity is reserved for other tenants"));
+        }
+        if job.gang_size > 1 {
+            // Gang placement takes the node lock itself
+            drop(nodes);
+            return self.schedule_gang(job, required, duration_hours);
         }
 
-        let mut best_placement: Option<Placement> = None;
-        let mut min_cost = f64::MAX;
+        if nodes.is_empty() {
+            drop(nodes);
+            self.wake_for(&required);
+            self.update_job_state(job.id.clone(), JobStatus::Failed, None)?;
+            return Err(anyhow::Error::from(SchedulerError::NoCapacity { job_id: job.id.clone() })
+                .context("No nodes available in cluster"));
+        }
 
-        // Evaluate each node for placement
-        for node in nodes.values() {
-            // Check resource availability
-            if !self.check_resource_fit(&job.resources, node) {
-                tracing::debug!("Node {} insufficient resources", node.id);
-                continue;
-            }
+        // Evaluate only nodes with enough free resources (indexed lookup);
+        // large candidate sets are evaluated in parallel when enabled
+        let index: &NodeIndex = &nodes;
+        let candidates: Vec<&NodeInfo> = index.candidates(&required)
+            .into_iter()
+            .filter(|node| {
+                let in_use = network_in_use.get(&node.id).copied().unwrap_or(0);
+                bandwidth::fits_network(node, required.network_mbps, in_use)
+            })
+            .filter(|node| self.devices.fits(node, &job))
+            .filter(|node| {
+                let load = loads.get(&node.id).copied().unwrap_or_default();
+                load.fits(node, &required, reported.get(&node.id).copied())
+            })
+            .collect();
+        let parallel = self.parallel_min_candidates
+            .is_some_and(|min| candidates.len() >= min);
 
-            // Calculate total cost for this placement using Formula 4.1
-            // C_total = C_comp + C_data + C_idle
-            let estimated_duration = 1.0; // TODO: estimate based on job type
-            let data_size = 0.0; // TODO: get from job spec
-            
-            let cost = self.cost_calculator.total_cost(
-                node.cost_per_hour,
-                estimated_duration,
-                1.0, // 100% utilization during job
-                data_size,
-                0.0, // VPS-to-VPS transfer is free (blueprint assumption)
-                0.0, // No idle cost during active job
-                0.0,
-            );
+        // Best-effort jobs first try idle reserved capacity (overcommit)
+        let harvested = harvest_targets.iter()
+            .filter_map(|node_id| index.get(node_id))
+            .filter_map(|node| self.evaluate_node(node, &job, duration_hours, index))
+            .min_by(Self::cheaper);
+        let is_harvested = harvested.is_some();
 
-            // Estimate latency based on node load
-            let estimated_latency = self.estimate_latency(node);
+        // Same shape placed since the last node change: try its ranking first
+        let shape = (!job.best_effort && self.placement_cache.is_enabled())
+            .then(|| PlacementShape::of(&job, &required, duration_hours));
+        let version = index.version();
+        let cached = shape.as_ref()
+            .and_then(|shape| self.placement_cache.ranking(shape, version))
+            .and_then(|ranking| {
+                ranking.iter()
+                    .filter_map(|node_id| candidates.iter().find(|node| &node.id == node_id))
+                    .filter_map(|node| self.evaluate_node(node, &job, duration_hours, index))
+                    .find(|placement| backfill::fits_window(placement, &reserved, now))
+            });
 
-            // Check SLA constraints
-            if estimated_latency > job.sla.max_latency_ms {
-                tracing::debug!("Node {} violates SLA latency requirement", node.id);
-                continue;
+        let best_placement = if is_harvested {
+            harvested
+        } else if cached.is_some() {
+            cached
+        } else {
+            let mut placements: Vec<Placement> = if parallel {
+                candidates.par_iter()
+                    .filter_map(|node| self.evaluate_node(node, &job, duration_hours, index))
+                    .collect()
+            } else {
+                candidates.iter()
+                    .filter_map(|node| self.evaluate_node(node, &job, duration_hours, index))
+                    .collect()
+            };
+            placements.sort_by(Self::cheaper);
+            if let Some(shape) = shape {
+                let ranking = placements.iter().map(|placement| placement.node_id.clone()).collect();
+                self.placement_cache.store(shape, version, ranking);
             }
+            placements.into_iter()
+                .find(|placement| backfill::fits_window(placement, &reserved, now))
+        };
+        let unplaced = match best_placement {
+            Some(_) => None,
+            None => Some(self.diagnose(&job, &candidates, duration_hours, index)),
+        };
+        drop(nodes);
 
-            if let Some(max_budget) = job.sla.max_budget_usd {
-                if cost.total_usd > max_budget {
-                    tracing::debug!("Node {} exceeds budget constraint", node.id);
-                    continue;
+        match best_placement {
+            Some(placement) => {
+                // The tenant must have credit for the estimate
+                if let Err(e) = self.reserve_credit(&job, placement.estimated_cost.total_usd) {
+                    self.update_job_state(job.id.clone(), JobStatus::Failed, None)?;
+                    return Err(e.into());
+                }
+
+                // Another placement may have taken the node's last devices
+                if let Err(e) = self.allocate_devices(&job, std::slice::from_ref(&placement.node_id)) {
+                    self.update_job_state(job.id.clone(), JobStatus::Failed, None)?;
+                    return Err(e.into());
                 }
-            }
 
-            // Track best placement (minimum cost - Formula 4.1 TCO optimization)
-            if cost.total_usd < min_cost {
-                min_cost = cost.total_usd;
-                best_placement = Some(Placement {
-                    job_id: job.id.clone(),
-                    node_id: node.id.clone(),
-                    estimated_cost: cost.clone(),
-                    estimated_latency_ms: estimated_latency,
-                });
                 tracing::info!(
                     "Formula 4.1: Best placement {} on node {} (TCO: ${:.4})",
-                    job.id, node.id, min_cost
+                    job.id, placement.node_id, placement.estimated_cost.total_usd
                 );
-            }
-        }
 
-        match best_placement {
-            Some(placement) => {
                 // Update job state to Scheduled
                 self.update_job_state(
                     job.id.clone(),
@@ -230,18 +1116,144 @@ impl EconomicScheduler {
                         state.estimated_cost = Some(placement.estimated_cost.clone());
                     }
                 }
+                self.notify_job_update(&job.id);
+
+                if !job.disable_result_cache {
+                    self.result_cache.track(&job.id, cache_key);
+                }
+
+                if let Ok(mut placed) = self.placed_jobs.lock() {
+                    placed.insert(job.id.clone(), job.clone());
+                }
+                self.harvest.set(&job.id, is_harvested.then_some(placement.node_id.as_str()));
+                self.reservations.record_end(&job.id, now + backfill::expected_secs(&placement));
+
+                self.publish_event(SchedulerEvent::JobScheduled {
+                    job_id: job.id.clone(),
+                    node_id: placement.node_id.clone(),
+                    estimated_cost_usd: placement.estimated_cost.total_usd,
+                    timestamp: unix_now(),
+                });
                 
                 tracing::info!("Job {} scheduled to {} with TCO ${:.4}", 
                     job.id, placement.node_id, placement.estimated_cost.total_usd);
                 Ok(placement)
             }
             None => {
+                // Bring capacity back for the retry if a sleeping node would fit
+                self.wake_for(&required);
+                if let Some(SchedulerError::OverBudget { max_budget_usd, cheapest_usd, .. }) = &unplaced {
+                    if let Some(expires_at) = self.hold_for_price(&job, *max_budget_usd, *cheapest_usd) {
+                        anyhow::bail!("Job {} is held until a placement fits its budget (until {})", job.id, expires_at);
+                    }
+                }
                 self.update_job_state(job.id.clone(), JobStatus::Failed, None)?;
-                anyhow::bail!("No suitable node found for job {} (Formula 4.1 constraints)", job.id)
+                Err(unplaced.unwrap_or(SchedulerError::NoCapacity { job_id: job.id.clone() }).into())
             }
         }
     }
 
+    /// Specs of the unfinished jobs currently assigned to `node_id`
+    fn placed_on(&self, node_id: &str) -> Result<Vec<JobSpec>> {
+        let placed = self.placed_jobs.lock()
+            .map_err(|e| anyhow::anyhow!("Lock poisoned: {}", e))?;
+        let mut jobs: Vec<JobSpec> = placed.values()
+            .filter(|spec| {
+                self.get_job_state(&spec.id)
+                    .is_some_and(|state| state.assigned_node.as_deref() == Some(node_id))
+            })
+            .cloned()
+            .collect();
+        if self.deterministic {
+            jobs.sort_by(|a, b| a.id.cmp(&b.id));
+        }
+        Ok(jobs)
+    }
+
+    /// Put a job that lost its node back up for placement
+    ///
+    /// Goes through the shared queue in queued mode, otherwise the job is
+    /// placed again directly; both reset it to Pending first. Also used to
+    /// start jobs the scheduler creates itself.
+    async fn requeue(&self, job: JobSpec) -> Result<()> {
+        self.mark_pending(&job, true)?;
+        if self.job_queue().is_some() {
+            self.push_queued(job).await
+        } else {
+            self.place(job).await.map(|_| ())
+        }
+    }
+
+    /// Record the datasets a node currently holds locally (thread-safe)
+    pub fn sync_node_datasets(&self, node_id: &str, local: &[(String, f64)]) {
+        self.datasets.sync_node(node_id, local);
+    }
+
+    /// `sync_node_datasets` with the content digest of each copy, where known
+    pub fn sync_node_dataset_versions(&self, node_id: &str, local: &[(String, f64, Option<String>)]) {
+        self.datasets.sync_node_versions(node_id, local);
+    }
+
+    /// Record a node's reported free disk; nodes that did not register
+    /// their disk stay untracked
+    pub fn update_node_disk(&self, node_id: &str, available_disk_gb: u32) {
+        let Ok(mut nodes) = self.available_nodes.lock() else {
+            return;
+        };
+        let Some(node) = nodes.get(node_id).filter(|node| node.available_disk_gb.is_some()) else {
+            return;
+        };
+        let mut node = node.clone();
+        node.available_disk_gb = Some(available_disk_gb);
+        nodes.insert(node);
+    }
+
+    /// Dataset registry used for data-locality-aware placement
+    pub fn dataset_registry(&self) -> &DatasetRegistry {
+        &self.datasets
+    }
+
+    /// Nodes that hold `dataset` locally and serve it over the data service
+    ///
+    /// Sources in the requester's location are listed first.
+    pub fn dataset_sources(&self, dataset: &str, requester_node_id: &str) -> Vec<NodeInfo> {
+        let Some(info) = self.datasets.get(dataset) else {
+            return Vec::new();
+        };
+
+        let nodes = match self.available_nodes.lock() {
+            Ok(nodes) => nodes,
+            Err(_) => return Vec::new(),
+        };
+        let requester_location = nodes.get(requester_node_id).map(|n| n.location.clone());
+
+        let mut sources: Vec<NodeInfo> = info.replicas.iter()
+            .filter(|id| id.as_str() != requester_node_id)
+            .filter_map(|id| nodes.get(id))
+            .filter(|node| !node.data_service_addr.is_empty())
+            .cloned()
+            .collect();
+        sources.sort_by_key(|node| Some(&node.location) != requester_location.as_ref());
+        sources
+    }
+
+    /// Record peer-to-peer transfer progress reported by a worker (thread-safe)
+    pub fn record_transfer(&self, record: TransferRecord) {
+        if record.completed {
+            tracing::info!(
+                "Transfer {} of {} complete: {} -> {} ({} bytes in {}ms)",
+                record.transfer_id, record.dataset, record.source_node,
+                record.dest_node, record.bytes_transferred, record.elapsed_ms
+            );
+        }
+        self.transfers.report(record);
+    }
+
+    /// Ledger of peer-to-peer transfers and observed link bandwidth
+    pub fn transfer_ledger(&self) -> &TransferLedger {
+        &self.transfers
+    }
+
     /// Get node count (thread-safe)
     pub fn node_count(&self) -> usize {
         self.available_nodes.lock()
@@ -257,25 +1269,332 @@ impl EconomicScheduler {
     }
 
     /// Update job state (thread-safe)
+    ///
+    /// Fails with `InvalidTransition` if the job's lifecycle does not allow
+    /// the change (see `transitions`); repeating a finished job's outcome
+    /// is a no-op.
     pub fn update_job_state(&self, job_id: String, status: JobStatus, assigned_node: Option<String>) -> Result<()> {
+        if !self.check_transition(&job_id, &status)? {
+            return Ok(());
+        }
+
+        // Before a terminal status drops the placed spec the predictor reads
+        self.observe_for_prediction(&job_id, &status, unix_now());
+        self.observe_for_reputation(&job_id, &status, assigned_node.as_deref(), unix_now());
+        self.observe_for_settlement(&job_id, &status, unix_now());
+        self.observe_for_costs(&job_id, &status, unix_now());
+        self.observe_for_efficiency(&job_id, &status, unix_now());
+        self.observe_for_rightsizing(&job_id, &status);
+        self.observe_for_notification(&job_id, &status, unix_now());
+
+        let terminal = status.is_terminal();
+        let status_failed = status == JobStatus::Failed;
+        if terminal {
+            if let Ok(mut placed) = self.placed_jobs.lock() {
+                placed.remove(&job_id);
+            }
+            self.reservations.forget(&job_id);
+            self.devices.release(&job_id);
+            self.release_payload(&job_id);
+        }
+
         let mut states = self.job_states.lock()
             .map_err(|e| anyhow::anyhow!("Lock poisoned: {}", e))?;
         
+        let mut event = None;
         if let Some(state) = states.get_mut(&job_id) {
-            state.status = status;
+            let from = std::mem::replace(&mut state.status, status);
+            let moved = assigned_node.is_some() && assigned_node != state.assigned_node;
             if let Some(node) = assigned_node {
                 state.assigned_node = Some(node);
             }
+            if from != state.status {
+                state.stamp(unix_now());
+            }
+            if from != state.status || moved {
+                self.record_transition(&job_id, from, state.status.clone(), state.assigned_node.clone());
+            }
+
+            // Successful results become reusable; anything else is dropped
+            match (&state.status, &state.assigned_node) {
+                (JobStatus::Completed, Some(node)) => self.result_cache.complete(
+                    &job_id,
+                    node,
+                    state.estimated_cost.clone().unwrap_or_default(),
+                    unix_now(),
+                ),
+                (JobStatus::Failed | JobStatus::Cancelled, _) => self.result_cache.discard(&job_id),
+                _ => {}
+            }
+
+            event = Some(SchedulerEvent::JobStatusChanged {
+                job_id: job_id.clone(),
+                status: state.status.clone(),
+                node_id: state.assigned_node.clone(),
+                timestamp: unix_now(),
+            });
+        }
+        drop(states);
+        self.notify_job_update(&job_id);
+        if let Some(event) = event {
+            self.publish_event(event);
+        }
+
+        // Freed capacity may let a waiting gang start
+        if terminal {
+            self.start_waiting_gangs();
+            self.price_holds.wake();
+        }
+        if status_failed {
+            self.check_array_failures(&job_id)?;
         }
         
         Ok(())
     }
 
-    /// Check if node has sufficient resources for job
-    fn check_resource_fit(&self, required: &ResourceRequirements, node: &NodeInfo) -> bool {
-        node.available_cpu >= required.cpu_cores
-            && node.available_memory_gb >= required.memory_gb
-            && node.available_gpu >= required.gpu_count
+    /// Withdraw a job that has not finished; a queued copy is dropped when
+    /// a dispatcher claims it
+    pub fn cancel_job(&self, job_id: &str) -> Result<()> {
+        tracing::info!("Cancelling job {}", job_id);
+        self.update_job_state(job_id.to_string(), JobStatus::Cancelled, None)
+    }
+
+    /// Whether a job was cancelled (thread-safe)
+    pub fn is_cancelled(&self, job_id: &str) -> bool {
+        self.get_job_state(job_id).is_some_and(|state| state.status == JobStatus::Cancelled)
+    }
+
+    /// Complete a job immediately using a cached result (no C_comp incurred)
+    fn complete_from_cache(&self, job: &JobSpec, cached: CachedResult) -> Result<Placement> {
+        tracing::info!(
+            "Job {} served from result cache (source: {}, saved ${:.4})",
+            job.id, cached.source_job_id, cached.cost.total_usd
+        );
+
+        {
+            let mut states = self.job_states.lock()
+                .map_err(|e| anyhow::anyhow!("Lock poisoned: {}", e))?;
+            if let Some(state) = states.get_mut(&job.id) {
+                let from = std::mem::replace(&mut state.status, JobStatus::Completed);
+                self.record_transition(&job.id, from, JobStatus::Completed, Some(cached.node_id.clone()));
+                state.stamp(unix_now());
+                state.assigned_node = Some(cached.node_id.clone());
+                state.estimated_cost = Some(TotalCost::default());
+                state.cached_from = Some(cached.source_job_id.clone());
+            }
+        }
+        self.notify_job_update(&job.id);
+
+        Ok(Placement {
+            job_id: job.id.clone(),
+            node_id: cached.node_id,
+            estimated_cost: TotalCost::default(),
+            estimated_latency_ms: 0,
+            estimated_staging_ms: 0,
+            estimated_duration_hours: 0.0,
+            cached_from: Some(cached.source_job_id),
+            gang_nodes: Vec::new(),
+            score_usd: 0.0,
+            preference_violations: Vec::new(),
+        })
+    }
+
+    /// Record progress reported by a running job (thread-safe)
+    pub fn report_job_progress(&self, job_id: &str, progress: JobProgress) -> Result<()> {
+        let mut states = self.job_states.lock()
+            .map_err(|e| anyhow::anyhow!("Lock poisoned: {}", e))?;
+
+        let state = states.get_mut(job_id)
+            .ok_or_else(|| SchedulerError::not_found("Job", job_id))?;
+        state.progress = Some(progress);
+        drop(states);
+
+        self.notify_job_update(job_id);
+        Ok(())
+    }
+
+    /// Subscribe to job state changes (receives the id of the changed job)
+    pub fn subscribe_job_updates(&self) -> broadcast::Receiver<String> {
+        self.job_updates.subscribe()
+    }
+
+    /// Subscribe to job and node lifecycle events
+    pub fn subscribe_events(&self) -> broadcast::Receiver<SchedulerEvent> {
+        self.events.subscribe()
+    }
+
+    /// Broadcast a lifecycle event; having no subscribers is not an error
+    fn publish_event(&self, event: SchedulerEvent) {
+        let _ = self.events.send(event);
+    }
+
+    /// Broadcast a job state change; having no watchers is not an error
+    ///
+    /// Every job mutation goes through here, so it also queues the new
+    /// state for the attached store.
+    fn notify_job_update(&self, job_id: &str) {
+        if let Some(persist) = &self.persist {
+            if let Some(state) = self.get_job_state(job_id) {
+                let _ = persist.send(PersistOp::Job(state));
+            }
+        }
+        let _ = self.job_updates.send(job_id.to_string());
+    }
+
+    /// Formula 4.1 cost and SLA checks for one candidate node
+    ///
+    /// Returns the placement if the node satisfies every SLA constraint.
+    fn evaluate_node(&self, node: &NodeInfo, job: &JobSpec, reference_hours: f64, nodes: &NodeIndex) -> Option<Placement> {
+        self.assess_node(node, job, reference_hours, nodes).ok()
+    }
+
+    /// Why no candidate could take `job`, reporting the constraint that
+    /// came closest to being met and the SLA changes that would place it
+    fn diagnose(&self, job: &JobSpec, candidates: &[&NodeInfo], reference_hours: f64, nodes: &NodeIndex) -> SchedulerError {
+        let mut best_latency_ms: Option<u64> = None;
+        let mut cheapest_usd: Option<f64> = None;
+        let mut cheapest_over_ceiling_usd: Option<f64> = None;
+        let mut cheapest_rate_usd: Option<f64> = None;
+        let mut deadline_missed = false;
+        for node in candidates {
+            match self.assess_node(node, job, reference_hours, nodes) {
+                Err(Rejection::Latency(ms)) => best_latency_ms = Some(best_latency_ms.map_or(ms, |best| best.min(ms))),
+                Err(Rejection::Deadline) => deadline_missed = true,
+                Err(Rejection::Budget(usd)) => cheapest_usd = Some(cheapest_usd.map_or(usd, |best| best.min(usd))),
+                Err(Rejection::Ceiling(usd)) => {
+                    cheapest_over_ceiling_usd = Some(cheapest_over_ceiling_usd.map_or(usd, |best| best.min(usd)))
+                }
+                Err(Rejection::HourlyRate(usd)) => cheapest_rate_usd = Some(cheapest_rate_usd.map_or(usd, |best| best.min(usd))),
+                // Avoided, plugin-filtered or reserved nodes say nothing about the SLA
+                Err(Rejection::Avoided | Rejection::Plugin) | Ok(_) => {}
+            }
+        }
+
+        let job_id = job.id.clone();
+        if let (Some(cheapest_usd), Some(ceiling_usd)) = (cheapest_over_ceiling_usd, job.cost_ceiling_usd) {
+            SchedulerError::OverCostCeiling {
+                job_id,
+                job_type: commands::job_type_name(&job.job_type).to_string(),
+                ceiling_usd,
+                cheapest_usd: cheapest_usd * job.gang_size.max(1) as f64,
+            }
+        } else if let (Some(cheapest_rate_usd), Some(max_hourly_rate_usd)) = (cheapest_rate_usd, job.sla.max_hourly_rate_usd) {
+            SchedulerError::OverHourlyRate { job_id, max_hourly_rate_usd, cheapest_rate_usd }
+        } else if let (Some(cheapest_usd), Some(max_budget_usd)) = (cheapest_usd, job.sla.max_budget_usd) {
+            let suggestions = self.suggest_relaxations(job, candidates, reference_hours, nodes);
+            SchedulerError::OverBudget { job_id, max_budget_usd, cheapest_usd, suggestions }
+        } else if let (true, Some(deadline)) = (deadline_missed, job.sla.deadline) {
+            let suggestions = self.suggest_relaxations(job, candidates, reference_hours, nodes);
+            SchedulerError::DeadlineUnmet { job_id, deadline, suggestions }
+        } else if let Some(best_latency_ms) = best_latency_ms {
+            let suggestions = self.suggest_relaxations(job, candidates, reference_hours, nodes);
+            SchedulerError::SlaLatencyUnmet { job_id, max_latency_ms: job.sla.max_latency_ms, best_latency_ms, suggestions }
+        } else {
+            SchedulerError::NoCapacity { job_id }
+        }
+    }
+
+    /// `evaluate_node`, saying which check rejected the node
+    fn assess_node(&self, node: &NodeInfo, job: &JobSpec, reference_hours: f64, nodes: &NodeIndex) -> Result<Placement, Rejection> {
+        if job.avoid_nodes.contains(&node.id) || (!job.pin_nodes.is_empty() && !job.pin_nodes.contains(&node.id)) {
+            return Err(Rejection::Avoided);
+        }
+        if !self.queues.admits_node(job.queue.as_deref(), node) || !self.pools.admits_node(job.pool.as_deref(), node) {
+            return Err(Rejection::Avoided);
+        }
+        if !job.arch.is_empty() && !job.arch.iter().any(|arch| arch == node.cpu_arch()) {
+            return Err(Rejection::Avoided);
+        }
+
+        // Calculate total cost for this placement using Formula 4.1
+        // C_total = C_comp + C_data + C_idle
+        // A node twice as fast as the reference finishes in half the time
+        let estimated_duration = reference_hours / node.performance();
+        // C_data: only datasets the node does not already hold are transferred,
+        // stale copies by delta sync, compressible ones at their wire size
+        // plus compression CPU
+        let data_size = self.billed_transfer_gb(&node.id, &job.datasets);
+
+        let cost = self.cost_model.placement_cost(node, job, estimated_duration, data_size);
+
+        // Estimate latency based on node load plus time to stage input data
+        let staging_ms = self.estimate_staging_ms(node, job, nodes);
+        let estimated_latency = self.estimate_latency(node).saturating_add(staging_ms);
+
+        // Check SLA constraints
+        if estimated_latency > job.sla.max_latency_ms {
+            tracing::debug!("Node {} violates SLA latency requirement", node.id);
+            return Err(Rejection::Latency(estimated_latency));
+        }
+
+        if let Some(deadline) = job.sla.deadline {
+            let finish = unix_now()
+                + (estimated_latency / 1000) as i64
+                + (estimated_duration * 3600.0) as i64;
+            if finish > deadline {
+                tracing::debug!("Node {} cannot finish before deadline", node.id);
+                return Err(Rejection::Deadline);
+            }
+        }
+
+        if let Some(ceiling) = ceilings::member_ceiling(job) {
+            if cost.total_usd > ceiling {
+                tracing::debug!("Node {} exceeds the cost ceiling", node.id);
+                return Err(Rejection::Ceiling(cost.total_usd));
+            }
+        }
+
+        if hourly_rates::exceeds_cap(job, node) {
+            tracing::debug!("Node {} exceeds the hourly rate cap", node.id);
+            return Err(Rejection::HourlyRate(hourly_rates::hourly_rate(job, node)));
+        }
+
+        if let Some(max_budget) = job.sla.max_budget_usd {
+            if cost.total_usd > max_budget {
+                tracing::debug!("Node {} exceeds budget constraint", node.id);
+                return Err(Rejection::Budget(cost.total_usd));
+            }
+        }
+
+        if !self.plugins.filter(node, job, cost.total_usd) {
+            return Err(Rejection::Plugin);
+        }
+
+        let (preference_violations, preference_usd) = preferences::assess(job, node, estimated_latency, cost.total_usd);
+        let score_usd = cost.total_usd * self.reputation.penalty(&node.id) * self.gpu_locality_penalty(node, job)
+            + self.scoring.term(node, job, cost.total_usd)
+            + self.plugins.score(node, job, cost.total_usd)
+            + preference_usd;
+        Ok(Placement {
+            job_id: job.id.clone(),
+            node_id: node.id.clone(),
+            estimated_cost: cost,
+            estimated_latency_ms: estimated_latency,
+            estimated_staging_ms: staging_ms,
+            estimated_duration_hours: estimated_duration,
+            cached_from: None,
+            gang_nodes: Vec::new(),
+            score_usd,
+            preference_violations,
+        })
+    }
+
+    /// Expected run time of `job` on the reference node: the submitter's
+    /// estimate, else the predictor's forecast, else the default
+    fn reference_duration_hours(&self, job: &JobSpec, prediction: Option<&Prediction>) -> f64 {
+        job.estimated_duration_hours
+            .filter(|hours| *hours > 0.0)
+            .or_else(|| prediction.map(|p| p.duration_hours))
+            .unwrap_or(DEFAULT_DURATION_HOURS)
+    }
+
+    /// Order placements by reliability-adjusted TCO (Formula 4.1), ties
+    /// broken by node id so sequential and parallel evaluation pick the
+    /// same node
+    fn cheaper(a: &Placement, b: &Placement) -> std::cmp::Ordering {
+        a.score_usd
+            .total_cmp(&b.score_usd)
+            .then_with(|| a.node_id.cmp(&b.node_id))
     }
 
     /// Estimate job latency based on node characteristics
@@ -290,14 +1609,68 @@ impl EconomicScheduler {
         base_latency + cpu_pressure + mem_pressure
     }
 
+    /// Estimate time to stage the job's missing datasets onto `node`
+    ///
+    /// Each dataset is pulled from its fastest replica: observed throughput
+    /// between the two nodes if known, else the modelled location link.
+    /// Datasets are staged one after another, at their observed wire size
+    /// after compression and delta sync.
+    fn estimate_staging_ms(&self, node: &NodeInfo, job: &JobSpec, nodes: &NodeIndex) -> u64 {
+        self.datasets.missing(&node.id, &job.datasets)
+            .iter()
+            .map(|dataset| {
+                let best_mbps = dataset.replicas.iter()
+                    .filter_map(|replica| nodes.get(replica))
+                    .map(|source| {
+                        self.transfers.link_bandwidth_mbps(&source.id, &node.id)
+                            .unwrap_or_else(|| self.bandwidth.link_mbps(&source.location, &node.location))
+                    })
+                    .fold(None, |best: Option<f64>, mbps| Some(best.map_or(mbps, |b| b.max(mbps))))
+                    .unwrap_or_else(|| self.bandwidth.external_mbps());
+                // Never faster than the node's own uplink
+                let best_mbps = node.network_mbps.map_or(best_mbps, |uplink| best_mbps.min(uplink as f64));
+
+                BandwidthModel::transfer_time_ms(self.wire_gb(&node.id, dataset), best_mbps)
+            })
+            .fold(0u64, |total, ms| total.saturating_add(ms))
+    }
+
+    /// Configure bandwidth between two locations (Mbit/s, both directions)
+    pub fn set_link_bandwidth(&self, from: &str, to: &str, mbps: f64) {
+        self.bandwidth.set_link(from, to, mbps);
+    }
+
     /// Get cluster status (thread-safe)
     pub fn cluster_status(&self) -> Vec<NodeInfo> {
-        self.available_nodes.lock()
+        let mut nodes: Vec<NodeInfo> = self.available_nodes.lock()
             .map(|nodes| nodes.values().cloned().collect())
-            .unwrap_or_else(|_| Vec::new())
+            .unwrap_or_else(|_| Vec::new());
+        if self.deterministic {
+            nodes.sort_by(|a, b| a.id.cmp(&b.id));
+        }
+        nodes
+    }
+
+    /// Version of the node registry, bumped whenever a node is added,
+    /// updated or removed
+    pub fn cluster_version(&self) -> u64 {
+        *self.cluster_version.borrow()
+    }
+
+    /// Receiver that sees every cluster version change
+    pub fn watch_cluster_version(&self) -> tokio::sync::watch::Receiver<u64> {
+        self.cluster_version.clone()
     }
 }
 
+/// Current Unix timestamp in seconds
+fn unix_now() -> i64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0)
+}
+
 impl Default for EconomicScheduler {
     fn default() -> Self {
         Self::new()
@@ -324,9 +1697,107 @@ mod tests {
             available_gpu: 1,
             location: "vps-1".to_string(),
             cost_per_hour: 0.5,
+            ..Default::default()
         };
 
         scheduler.register_node(node.clone()).unwrap();
         assert_eq!(scheduler.node_count(), 1);
     }
+
+    #[tokio::test]
+    async fn test_parallel_evaluation_matches_sequential() {
+        let mut parallel = EconomicScheduler::new();
+        parallel.set_parallel_evaluation(Some(1));
+        let sequential = EconomicScheduler::new();
+
+        for i in 0..64 {
+            let node = NodeInfo {
+                id: format!("node-{:02}", i),
+                available_cpu: 4 + (i % 4),
+                available_memory_gb: 16,
+                location: "vps-1".to_string(),
+                // Ties on price are broken by node id
+                cost_per_hour: 0.1 + (i % 8) as f64 * 0.01,
+                ..Default::default()
+            };
+            parallel.register_node(node.clone()).unwrap();
+            sequential.register_node(node).unwrap();
+        }
+
+        let job = JobSpec {
+            id: "job-1".to_string(),
+            resources: ResourceRequirements { cpu_cores: 6, memory_gb: 8, disk_gb: 1, ..Default::default() },
+            sla: SlaConstraints { max_latency_ms: 1_000, ..Default::default() },
+            ..Default::default()
+        };
+
+        let a = parallel.schedule(job.clone()).await.unwrap();
+        let b = sequential.schedule(job).await.unwrap();
+        assert_eq!(a.node_id, b.node_id);
+        assert_eq!(a.node_id, "node-02");
+    }
+
+    #[tokio::test]
+    async fn test_placement_uses_cost_per_performance() {
+        let scheduler = EconomicScheduler::new();
+        for (id, cost_per_hour, performance_score) in [("slow", 0.1, 0.25), ("fast", 0.2, 1.0)] {
+            scheduler.register_node(NodeInfo {
+                id: id.to_string(),
+                available_cpu: 4,
+                available_memory_gb: 8,
+                location: "vps-1".to_string(),
+                cost_per_hour,
+                performance_score,
+                ..Default::default()
+            }).unwrap();
+        }
+
+        let job = JobSpec {
+            id: "job-1".to_string(),
+            resources: ResourceRequirements { cpu_cores: 2, memory_gb: 2, disk_gb: 1, ..Default::default() },
+            sla: SlaConstraints { max_latency_ms: 1_000, ..Default::default() },
+            ..Default::default()
+        };
+
+        // $0.10/h at a quarter of the speed is $0.40 per reference hour
+        let placement = scheduler.schedule(job).await.unwrap();
+        assert_eq!(placement.node_id, "fast");
+        assert!((placement.estimated_cost.compute_usd - 0.2).abs() < 1e-9);
+    }
+
+    #[tokio::test]
+    async fn test_duration_scales_with_node_performance() {
+        let scheduler = EconomicScheduler::new();
+        for (id, cost_per_hour, performance_score) in [("slow", 0.05, 0.5), ("fast", 0.2, 1.0)] {
+            scheduler.register_node(NodeInfo {
+                id: id.to_string(),
+                available_cpu: 4,
+                available_memory_gb: 8,
+                location: "vps-1".to_string(),
+                cost_per_hour,
+                performance_score,
+                ..Default::default()
+            }).unwrap();
+        }
+
+        // 2h on the reference node: 4h ($0.20) on the slow node, 2h ($0.40) on the fast one
+        let job = |id: &str, deadline| JobSpec {
+            id: id.to_string(),
+            resources: ResourceRequirements { cpu_cores: 2, memory_gb: 2, disk_gb: 1, ..Default::default() },
+            sla: SlaConstraints { max_latency_ms: 1_000, deadline, ..Default::default() },
+            estimated_duration_hours: Some(2.0),
+            disable_result_cache: true,
+            ..Default::default()
+        };
+
+        let placement = scheduler.schedule(job("job-1", None)).await.unwrap();
+        assert_eq!(placement.node_id, "slow");
+        assert!((placement.estimated_duration_hours - 4.0).abs() < 1e-9);
+        assert!((placement.estimated_cost.compute_usd - 0.2).abs() < 1e-9);
+
+        // Only the fast node finishes within three hours
+        let placement = scheduler.schedule(job("job-2", Some(unix_now() + 3 * 3600))).await.unwrap();
+        assert_eq!(placement.node_id, "fast");
+        assert!((placement.estimated_duration_hours - 2.0).abs() < 1e-9);
+    }
 }
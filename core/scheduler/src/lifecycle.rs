@@ -0,0 +1,118 @@
+//! Job lifecycle hooks
+//!
+//! A job may name a pre-start hook (e.g. warming a cache) and a
+//! post-complete hook (e.g. pushing a notification). The worker runs each
+//! in a container of its own, in sequence with the job's: a failed
+//! pre-start hook fails the job before its container starts, while the
+//! post-complete hook runs whatever the outcome (told it in
+//! `TGP_JOB_STATUS`) and does not change it. Workers report which phase a
+//! running job is in, and the job's status shows it.
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::error::SchedulerError;
+use crate::{EconomicScheduler, JobSpec};
+
+/// Time a hook gets when it names none
+pub const DEFAULT_HOOK_TIMEOUT_SECS: u64 = 600;
+
+/// Longest a hook may be given
+pub const MAX_HOOK_TIMEOUT_SECS: u64 = 3600;
+
+/// A command run in its own container before or after the job's
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct JobHook {
+    /// Image to run (empty: the job's own image)
+    #[serde(default)]
+    pub image: String,
+    pub command: Vec<String>,
+    /// Seconds before the hook is stopped (0: `DEFAULT_HOOK_TIMEOUT_SECS`)
+    #[serde(default)]
+    pub timeout_secs: u64,
+}
+
+/// Part of a running job that is executing
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum JobPhase {
+    PreStart,
+    Main,
+    PostComplete,
+}
+
+impl EconomicScheduler {
+    /// Refuse hooks a worker could not run
+    pub fn check_hooks(&self, job: &JobSpec) -> Result<()> {
+        for (name, hook) in [("pre-start", &job.pre_start_hook), ("post-complete", &job.post_complete_hook)] {
+            let Some(hook) = hook else {
+                continue;
+            };
+            if hook.command.is_empty() {
+                return Err(SchedulerError::invalid_spec(format!("Job {} has a {} hook without a command", job.id, name)).into());
+            }
+            if hook.timeout_secs > MAX_HOOK_TIMEOUT_SECS {
+                return Err(SchedulerError::invalid_spec(format!(
+                    "Job {} gives its {} hook more than {}s", job.id, name, MAX_HOOK_TIMEOUT_SECS
+                )).into());
+            }
+            if hook.image.is_empty() && job.container_image.is_empty() {
+                return Err(SchedulerError::invalid_spec(format!("Job {} has a {} hook but no image to run it in", job.id, name)).into());
+            }
+        }
+        Ok(())
+    }
+
+    /// Record the phase a worker reports a running job in
+    pub fn report_job_phase(&self, job_id: &str, phase: JobPhase) -> Result<()> {
+        let mut states = self.job_states.lock()
+            .map_err(|e| anyhow::anyhow!("Lock poisoned: {}", e))?;
+        let state = states.get_mut(job_id)
+            .ok_or_else(|| SchedulerError::not_found("Job", job_id))?;
+        if state.phase == Some(phase) {
+            return Ok(());
+        }
+        state.phase = Some(phase);
+        drop(states);
+
+        tracing::info!("Job {} entered its {:?} phase", job_id, phase);
+        self.notify_job_update(job_id);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hooks_are_checked_and_phases_recorded() {
+        let scheduler = EconomicScheduler::new();
+        let hook = |command: &[&str], timeout_secs: u64| Some(JobHook {
+            image: String::new(),
+            command: command.iter().map(|arg| arg.to_string()).collect(),
+            timeout_secs,
+        });
+        let mut job = JobSpec {
+            id: "job-1".to_string(),
+            container_image: "python:3.11".to_string(),
+            pre_start_hook: hook(&["sh", "-c", "cp -r /cache /scratch"], 0),
+            post_complete_hook: hook(&["curl", "-d", "done", "http://hooks.example"], 60),
+            ..Default::default()
+        };
+        scheduler.check_hooks(&job).unwrap();
+
+        job.post_complete_hook = hook(&[], 60);
+        assert!(scheduler.check_hooks(&job).is_err());
+        job.post_complete_hook = hook(&["true"], MAX_HOOK_TIMEOUT_SECS + 1);
+        assert!(scheduler.check_hooks(&job).is_err());
+
+        assert!(scheduler.report_job_phase("job-1", JobPhase::PreStart).is_err());
+        scheduler.job_states.lock().unwrap().insert("job-1".to_string(), crate::JobState {
+            job_id: "job-1".to_string(),
+            status: crate::JobStatus::Running,
+            ..Default::default()
+        });
+        scheduler.report_job_phase("job-1", JobPhase::PostComplete).unwrap();
+        assert_eq!(scheduler.job_states.lock().unwrap()["job-1"].phase, Some(JobPhase::PostComplete));
+    }
+}
@@ -0,0 +1,156 @@
+//! Per-tenant and per-array concurrency limits
+//!
+//! Limits are enforced when the dispatcher claims a queued job, not at
+//! admission: a tenant may queue any number of jobs, but only
+//! `max_concurrent_jobs` of them run at once, and an array runs at most
+//! `max_parallel` tasks at once. Named queues add their own quota (see
+//! `queues`). A job over its limit is deferred in the
+//! queue (keeping its age) and retried after a short backoff.
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use crate::{EconomicScheduler, JobSpec};
+
+/// Running-job caps per tenant
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ConcurrencyLimits {
+    /// Cap for tenants without an explicit entry (None: unlimited)
+    pub default_max_concurrent_jobs: Option<u32>,
+    /// Per-tenant caps, overriding the default
+    pub max_concurrent_jobs: HashMap<String, u32>,
+}
+
+impl ConcurrencyLimits {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_tenant(mut self, tenant: &str, max_concurrent_jobs: u32) -> Self {
+        self.max_concurrent_jobs.insert(tenant.to_string(), max_concurrent_jobs);
+        self
+    }
+
+    /// Parse `tenant=limit` pairs, e.g. `acme=200,lab=10`
+    pub fn parse_tenants(mut self, spec: &str) -> Result<Self> {
+        for pair in spec.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+            let (tenant, limit) = pair.split_once('=')
+                .ok_or_else(|| anyhow::anyhow!("Expected tenant=limit, got {}", pair))?;
+            let limit: u32 = limit.trim().parse()
+                .map_err(|e| anyhow::anyhow!("Invalid limit for tenant {}: {}", tenant, e))?;
+            self = self.with_tenant(tenant.trim(), limit);
+        }
+        Ok(self)
+    }
+
+    pub fn tenant_limit(&self, tenant: &str) -> Option<u32> {
+        self.max_concurrent_jobs.get(tenant).copied().or(self.default_max_concurrent_jobs)
+    }
+}
+
+impl EconomicScheduler {
+    pub fn set_concurrency_limits(&mut self, limits: ConcurrencyLimits) {
+        self.concurrency = Arc::new(Mutex::new(limits));
+    }
+
+    pub fn concurrency_limits(&self) -> ConcurrencyLimits {
+        self.concurrency.lock().map(|limits| limits.clone()).unwrap_or_default()
+    }
+
+    /// Swap the caps on a running scheduler (seen by every clone)
+    pub fn replace_concurrency_limits(&self, limits: ConcurrencyLimits) -> Result<()> {
+        *self.concurrency.lock().map_err(|e| anyhow::anyhow!("Lock poisoned: {}", e))? = limits;
+        Ok(())
+    }
+
+    /// Why `job` may not start now, or None if it is within its limits
+    ///
+    /// Counts jobs placed and not yet finished, so it must be called under
+    /// the placement lock to be exact.
+    pub fn concurrency_blocked(&self, job: &JobSpec) -> Result<Option<String>> {
+        let tenant_limit = self.concurrency.lock()
+            .map_err(|e| anyhow::anyhow!("Lock poisoned: {}", e))?
+            .tenant_limit(&job.tenant);
+        let array_limit = job.array_id.as_ref().filter(|_| job.max_parallel > 0);
+        if tenant_limit.is_none() && array_limit.is_none() && job.queue.is_none() {
+            return Ok(None);
+        }
+
+        let placed = self.placed_jobs.lock()
+            .map_err(|e| anyhow::anyhow!("Lock poisoned: {}", e))?;
+        if let Some(reason) = self.queue_quota_blocked(job, &placed) {
+            return Ok(Some(reason));
+        }
+        let running = |same: &dyn Fn(&JobSpec) -> bool| {
+            placed.values().filter(|spec| spec.id != job.id && same(spec)).count() as u32
+        };
+
+        if let Some(limit) = tenant_limit {
+            if running(&|spec| spec.tenant == job.tenant) >= limit {
+                return Ok(Some(format!("tenant '{}' at {} concurrent jobs", job.tenant, limit)));
+            }
+        }
+        if let Some(array_id) = array_limit {
+            if running(&|spec| spec.array_id.as_ref() == Some(array_id)) >= job.max_parallel {
+                return Ok(Some(format!("array {} at max_parallel {}", array_id, job.max_parallel)));
+            }
+        }
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::queue::{run_dispatcher, DispatcherConfig, JobQueue, MemoryLocks, MemoryQueue};
+    use crate::{JobStatus, NodeInfo, SlaConstraints};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    fn job(id: &str, tenant: &str) -> JobSpec {
+        JobSpec {
+            id: id.to_string(),
+            tenant: tenant.to_string(),
+            sla: SlaConstraints { max_latency_ms: 1_000, ..Default::default() },
+            job_data: id.as_bytes().to_vec(),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_tenant_limit_defers_dispatch_until_a_job_finishes() {
+        let mut scheduler = EconomicScheduler::new();
+        scheduler.set_concurrency_limits(ConcurrencyLimits::new().with_tenant("greedy", 1));
+        scheduler.register_node(NodeInfo {
+            id: "node-1".to_string(),
+            available_cpu: 64,
+            available_memory_gb: 256,
+            location: "vps-1".to_string(),
+            cost_per_hour: 0.1,
+            ..Default::default()
+        }).unwrap();
+
+        let queue = Arc::new(MemoryQueue::new());
+        for spec in [job("g1", "greedy"), job("g2", "greedy"), job("other", "polite")] {
+            queue.push(&spec).await.unwrap();
+        }
+        let config = DispatcherConfig {
+            idle_poll: Duration::from_millis(10),
+            limit_backoff: Duration::from_millis(50),
+            ..Default::default()
+        };
+        let dispatcher = tokio::spawn(run_dispatcher(scheduler.clone(), queue.clone(), Arc::new(MemoryLocks::new()), config));
+
+        tokio::time::sleep(Duration::from_millis(30)).await;
+        assert!(scheduler.get_job_state("g1").is_some());
+        assert!(scheduler.get_job_state("other").is_some());
+        assert!(scheduler.get_job_state("g2").is_none());
+
+        scheduler.update_job_state("g1".to_string(), JobStatus::Completed, None).unwrap();
+        tokio::time::sleep(Duration::from_millis(150)).await;
+        assert!(scheduler.get_job_state("g2").is_some());
+        dispatcher.abort();
+    }
+}
@@ -0,0 +1,221 @@
+//! Demand signals and market rates
+//!
+//! Providers that price their nodes dynamically (see `node_pricing`) need
+//! to see demand to do so. Jobs fall into a resource class by what they
+//! are priced on: GPU jobs by the GPU-hour, everything else by the
+//! core-hour. Per class the scheduler reports the queue depth (jobs still
+//! waiting for placement and the units they ask for), the units held by
+//! placed jobs against the capacity of the class's nodes, and the
+//! prevailing market rate: the capacity-weighted price per unit-hour of
+//! the class's active nodes. The capacity sampler records the market rate
+//! in the metrics history, and cost summaries report its average over
+//! their window.
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::{unix_now, EconomicScheduler, JobStatus, NodeInfo, ResourceRequirements};
+
+/// Capacity-weighted USD per unit-hour of active nodes, label: resource class
+pub const MARKET_RATE_USD: &str = "market_rate_usd";
+
+/// What a job is priced on
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ResourceClass {
+    /// Core-hours of nodes without GPUs
+    Cpu,
+    /// GPU-hours
+    Gpu,
+}
+
+impl ResourceClass {
+    pub const ALL: [ResourceClass; 2] = [ResourceClass::Cpu, ResourceClass::Gpu];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ResourceClass::Cpu => "cpu",
+            ResourceClass::Gpu => "gpu",
+        }
+    }
+
+    pub fn of_job(required: &ResourceRequirements) -> Self {
+        if required.gpu_count > 0 { ResourceClass::Gpu } else { ResourceClass::Cpu }
+    }
+
+    pub fn of_node(node: &NodeInfo) -> Self {
+        if node.available_gpu > 0 { ResourceClass::Gpu } else { ResourceClass::Cpu }
+    }
+
+    /// Units of the class in a request
+    fn units(self, required: &ResourceRequirements) -> f64 {
+        match self {
+            ResourceClass::Cpu => required.cpu_cores as f64,
+            ResourceClass::Gpu => required.gpu_count as f64,
+        }
+    }
+
+    /// Units of the class a node has, in use or free
+    fn capacity(self, node: &NodeInfo) -> f64 {
+        match self {
+            ResourceClass::Cpu => node.capacity_cpu.max(node.available_cpu) as f64,
+            ResourceClass::Gpu => node.available_gpu as f64,
+        }
+    }
+}
+
+/// Demand and price of one resource class
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ClassDemand {
+    pub class: ResourceClass,
+    /// Jobs waiting for placement
+    pub queued_jobs: u32,
+    /// Units the waiting jobs ask for
+    pub queued_units: f64,
+    /// Placed, unfinished jobs
+    pub placed_jobs: u32,
+    /// Units held by placed jobs
+    pub used_units: f64,
+    /// Units of the class's active nodes
+    pub capacity_units: f64,
+    /// Used over capacity units (0 without capacity)
+    pub utilization: f64,
+    /// USD per unit-hour (None: no active node of the class)
+    pub market_rate_usd: Option<f64>,
+}
+
+impl ClassDemand {
+    fn new(class: ResourceClass) -> Self {
+        Self {
+            class,
+            queued_jobs: 0,
+            queued_units: 0.0,
+            placed_jobs: 0,
+            used_units: 0.0,
+            capacity_units: 0.0,
+            utilization: 0.0,
+            market_rate_usd: None,
+        }
+    }
+}
+
+/// Demand across the cluster at one point in time
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DemandSignals {
+    pub at: i64,
+    pub cluster_version: u64,
+    /// One entry per resource class, in `ResourceClass::ALL` order
+    pub classes: Vec<ClassDemand>,
+}
+
+impl DemandSignals {
+    pub fn class(&self, class: ResourceClass) -> Option<&ClassDemand> {
+        self.classes.iter().find(|demand| demand.class == class)
+    }
+}
+
+fn index(class: ResourceClass) -> usize {
+    match class {
+        ResourceClass::Cpu => 0,
+        ResourceClass::Gpu => 1,
+    }
+}
+
+impl EconomicScheduler {
+    /// Queue depth, utilization and market rate per resource class
+    pub fn demand_signals(&self) -> Result<DemandSignals> {
+        let mut classes: Vec<ClassDemand> = ResourceClass::ALL.into_iter().map(ClassDemand::new).collect();
+
+        let pending = {
+            let states = self.job_states.lock()
+                .map_err(|e| anyhow::anyhow!("Lock poisoned: {}", e))?;
+            self.queued.pending(|job_id| states.get(job_id).is_some_and(|state| state.status == JobStatus::Pending))
+        };
+        let gangs = self.reservations.waiting();
+        let waiting = pending.iter()
+            .map(|spec| (&spec.resources, spec.gang_size))
+            .chain(gangs.iter().map(|gang| (&gang.required, gang.job.gang_size)));
+        for (required, members) in waiting {
+            let class = ResourceClass::of_job(required);
+            let demand = &mut classes[index(class)];
+            demand.queued_jobs += 1;
+            demand.queued_units += class.units(required) * members.max(1) as f64;
+        }
+
+        {
+            let placed = self.placed_jobs.lock()
+                .map_err(|e| anyhow::anyhow!("Lock poisoned: {}", e))?;
+            for spec in placed.values() {
+                let class = ResourceClass::of_job(&spec.resources);
+                let demand = &mut classes[index(class)];
+                demand.placed_jobs += 1;
+                demand.used_units += class.units(&spec.resources) * spec.gang_size.max(1) as f64;
+            }
+        }
+
+        let mut spend = [0.0; 2];
+        for node in self.cluster_status() {
+            let class = ResourceClass::of_node(&node);
+            let units = class.capacity(&node);
+            if units <= 0.0 {
+                continue;
+            }
+            classes[index(class)].capacity_units += units;
+            spend[index(class)] += node.cost_per_hour;
+        }
+        for demand in &mut classes {
+            if demand.capacity_units > 0.0 {
+                demand.utilization = demand.used_units / demand.capacity_units;
+                demand.market_rate_usd = Some(spend[index(demand.class)] / demand.capacity_units);
+            }
+        }
+
+        Ok(DemandSignals { at: unix_now(), cluster_version: self.cluster_version(), classes })
+    }
+
+    /// Take demand signals now and record each class's market rate
+    pub fn record_market_rates(&self) -> Result<DemandSignals> {
+        let signals = self.demand_signals()?;
+        for demand in &signals.classes {
+            if let Some(rate) = demand.market_rate_usd {
+                self.metrics_history.record_gauge(MARKET_RATE_USD, demand.class.as_str(), rate, signals.at);
+            }
+        }
+        Ok(signals)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{JobSpec, NodeInfo, SlaConstraints};
+    use crate::test_support::sized_node;
+
+    #[tokio::test]
+    async fn test_demand_signals_per_resource_class() {
+        let scheduler = EconomicScheduler::new();
+        scheduler.register_node(NodeInfo { cost_per_hour: 0.20, ..sized_node("small", 4, 64, 0) }).unwrap();
+        scheduler.register_node(NodeInfo { cost_per_hour: 0.40, ..sized_node("large", 12, 64, 0) }).unwrap();
+        scheduler.register_node(NodeInfo { cost_per_hour: 3.00, ..sized_node("gpu", 8, 64, 2) }).unwrap();
+
+        scheduler.schedule(JobSpec {
+            id: "train".to_string(),
+            resources: ResourceRequirements { cpu_cores: 2, memory_gb: 8, gpu_count: 1, ..Default::default() },
+            sla: SlaConstraints { max_latency_ms: 10_000, ..Default::default() },
+            ..Default::default()
+        }).await.unwrap();
+
+        let signals = scheduler.record_market_rates().unwrap();
+        let cpu = signals.class(ResourceClass::Cpu).unwrap();
+        assert_eq!((cpu.placed_jobs, cpu.capacity_units), (0, 16.0));
+        assert!((cpu.market_rate_usd.unwrap() - 0.0375).abs() < 1e-9);
+
+        let gpu = signals.class(ResourceClass::Gpu).unwrap();
+        assert_eq!((gpu.placed_jobs, gpu.used_units), (1, 1.0));
+        assert_eq!(gpu.utilization, 0.5);
+        assert_eq!(gpu.market_rate_usd, Some(1.5));
+
+        let summary = scheduler.cost_summary(signals.at - 60, signals.at + 60);
+        assert_eq!(summary.market_rates.get("gpu"), Some(&1.5));
+    }
+}
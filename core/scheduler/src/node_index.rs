@@ -0,0 +1,198 @@
+//! Resource-indexed node registry
+//!
+//! Nodes are kept in one sorted set per resource dimension, keyed by free
+//! CPU, memory and GPU. Candidate selection range-scans every dimension in
+//! lockstep and filters only the smallest range, so the cost of finding the
+//! nodes that fit a job grows with the number of fitting nodes rather than
+//! with the cluster size.
+//!
+//! Every insert and removal bumps the cluster state version, so placement
+//! caches, watchers and clients can tell whether anything changed without
+//! re-reading the registry. A published index starts its version at its
+//! creation time in microseconds, which keeps it increasing across
+//! scheduler restarts.
+
+use std::collections::{BTreeSet, HashMap};
+use std::ops::Bound;
+use std::sync::Arc;
+use tokio::sync::watch;
+
+use crate::{NodeInfo, ResourceRequirements};
+
+/// Node registry with per-dimension free-resource indexes
+#[derive(Debug, Clone, Default)]
+pub struct NodeIndex {
+    nodes: HashMap<String, NodeInfo>,
+    /// (free CPU cores, node id)
+    by_cpu: BTreeSet<(u32, String)>,
+    /// (free memory GB, node id)
+    by_memory: BTreeSet<(u32, String)>,
+    /// (free GPUs, node id)
+    by_gpu: BTreeSet<(u32, String)>,
+    /// Cluster state version, bumped on every insert and removal
+    version: u64,
+    /// Publishes `version` to watchers, if published
+    published: Option<Arc<watch::Sender<u64>>>,
+}
+
+impl NodeIndex {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Empty index whose version changes are published to the receiver
+    pub fn published() -> (Self, watch::Receiver<u64>) {
+        let version = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_micros() as u64)
+            .unwrap_or(0);
+        let (tx, rx) = watch::channel(version);
+        (Self { version, published: Some(Arc::new(tx)), ..Self::default() }, rx)
+    }
+
+    /// Insert or replace a node, re-indexing its free resources
+    pub fn insert(&mut self, node: NodeInfo) {
+        self.unindex(&node.id);
+        self.by_cpu.insert((node.available_cpu, node.id.clone()));
+        self.by_memory.insert((node.available_memory_gb, node.id.clone()));
+        self.by_gpu.insert((node.available_gpu, node.id.clone()));
+        self.nodes.insert(node.id.clone(), node);
+        self.bump();
+    }
+
+    /// Remove a node from the registry
+    pub fn remove(&mut self, node_id: &str) -> Option<NodeInfo> {
+        self.unindex(node_id);
+        self.bump();
+        self.nodes.remove(node_id)
+    }
+
+    /// Increases whenever a node is added, updated or removed
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+
+    fn bump(&mut self) {
+        self.version += 1;
+        if let Some(published) = &self.published {
+            published.send_replace(self.version);
+        }
+    }
+
+    pub fn get(&self, node_id: &str) -> Option<&NodeInfo> {
+        self.nodes.get(node_id)
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &NodeInfo> {
+        self.nodes.values()
+    }
+
+    /// Nodes with enough free CPU, memory, GPU and disk for `required`
+    pub fn candidates(&self, required: &ResourceRequirements) -> Vec<&NodeInfo> {
+        let ranges = [
+            Self::at_least(&self.by_cpu, required.cpu_cores),
+            Self::at_least(&self.by_memory, required.memory_gb),
+            Self::at_least(&self.by_gpu, required.gpu_count),
+        ];
+
+        // Advance all ranges together; the first to run out is the most selective
+        let mut walkers = ranges.clone();
+        let narrowest = 'scan: loop {
+            for (i, walker) in walkers.iter_mut().enumerate() {
+                if walker.next().is_none() {
+                    break 'scan i;
+                }
+            }
+        };
+
+        ranges[narrowest].clone()
+            .filter_map(|(_, id)| self.nodes.get(id))
+            .filter(|node| {
+                node.available_cpu >= required.cpu_cores
+                    && node.available_memory_gb >= required.memory_gb
+                    && node.available_gpu >= required.gpu_count
+                    && node.fits_disk(required.disk_gb)
+            })
+            .collect()
+    }
+
+    fn at_least(set: &BTreeSet<(u32, String)>, min: u32) -> std::collections::btree_set::Range<'_, (u32, String)> {
+        set.range((Bound::Included((min, String::new())), Bound::Unbounded))
+    }
+
+    fn unindex(&mut self, node_id: &str) {
+        if let Some(old) = self.nodes.get(node_id) {
+            self.by_cpu.remove(&(old.available_cpu, old.id.clone()));
+            self.by_memory.remove(&(old.available_memory_gb, old.id.clone()));
+            self.by_gpu.remove(&(old.available_gpu, old.id.clone()));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::sized_node;
+
+    fn ids(nodes: Vec<&NodeInfo>) -> Vec<String> {
+        let mut ids: Vec<String> = nodes.iter().map(|n| n.id.clone()).collect();
+        ids.sort();
+        ids
+    }
+
+    #[test]
+    fn test_candidates_fit_every_dimension() {
+        let mut index = NodeIndex::new();
+        index.insert(sized_node("small", 2, 4, 0));
+        index.insert(sized_node("big", 16, 64, 0));
+        index.insert(sized_node("gpu", 8, 32, 1));
+
+        let required = ResourceRequirements { cpu_cores: 4, memory_gb: 8, ..Default::default() };
+        assert_eq!(ids(index.candidates(&required)), vec!["big", "gpu"]);
+
+        let required = ResourceRequirements { cpu_cores: 4, memory_gb: 8, gpu_count: 1, ..Default::default() };
+        assert_eq!(ids(index.candidates(&required)), vec!["gpu"]);
+
+        // Re-registering with less free capacity re-indexes the node
+        index.insert(sized_node("big", 1, 2, 0));
+        let required = ResourceRequirements { cpu_cores: 4, memory_gb: 8, ..Default::default() };
+        assert_eq!(ids(index.candidates(&required)), vec!["gpu"]);
+        assert_eq!(index.len(), 3);
+
+        index.remove("gpu");
+        assert!(index.candidates(&required).is_empty());
+    }
+
+    #[test]
+    fn test_every_change_bumps_published_version() {
+        let (mut index, versions) = NodeIndex::published();
+        let start = index.version();
+        assert!(start > 0);
+        index.insert(sized_node("a", 2, 4, 0));
+        index.insert(sized_node("a", 1, 4, 0));
+        index.remove("a");
+        assert_eq!(index.version(), start + 3);
+        assert_eq!(*versions.borrow(), start + 3);
+    }
+
+    #[test]
+    fn test_candidates_need_disk_only_where_tracked() {
+        let mut index = NodeIndex::new();
+        index.insert(NodeInfo { available_disk_gb: Some(20), ..sized_node("tracked", 4, 8, 0) });
+        index.insert(sized_node("untracked", 4, 8, 0));
+
+        let required = ResourceRequirements { cpu_cores: 2, memory_gb: 2, disk_gb: 50, ..Default::default() };
+        assert_eq!(ids(index.candidates(&required)), vec!["untracked"]);
+
+        let required = ResourceRequirements { disk_gb: 20, ..required };
+        assert_eq!(ids(index.candidates(&required)), vec!["tracked", "untracked"]);
+    }
+}
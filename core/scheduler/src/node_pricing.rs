@@ -0,0 +1,141 @@
+//! Provider price updates
+//!
+//! Providers reprice their nodes through SetNodePrice, letting supply
+//! follow demand. The operator bounds what they may ask with a
+//! `PricingPolicy`: prices outside `[min, max]` are refused, and a node may
+//! be repriced at most once per `min_update_interval`. An accepted price is
+//! a cluster-state change like a node update: the cluster version is
+//! bumped, over-budget held jobs are evaluated again, jobs now over their
+//! hourly cap are requeued (see `hourly_rates`) and the repricer looks for
+//! cheaper homes for running services (see `repricing`).
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::error::SchedulerError;
+use crate::EconomicScheduler;
+
+/// Operator limits on provider price updates
+#[derive(Debug, Clone, PartialEq)]
+pub struct PricingPolicy {
+    /// Lowest hourly price a provider may set
+    pub min_cost_per_hour: f64,
+    /// Highest hourly price a provider may set (None: unbounded)
+    pub max_cost_per_hour: Option<f64>,
+    /// Shortest time between two updates of one node (zero: unlimited)
+    pub min_update_interval: Duration,
+}
+
+impl Default for PricingPolicy {
+    fn default() -> Self {
+        Self {
+            min_cost_per_hour: 0.0,
+            max_cost_per_hour: None,
+            min_update_interval: Duration::ZERO,
+        }
+    }
+}
+
+/// The pricing policy and when each node was last repriced
+#[derive(Debug, Clone, Default)]
+pub struct NodePricing {
+    policy: PricingPolicy,
+    last_update: Arc<Mutex<HashMap<String, i64>>>,
+}
+
+impl NodePricing {
+    pub fn new(policy: PricingPolicy) -> Self {
+        Self { policy, last_update: Arc::default() }
+    }
+
+    pub fn policy(&self) -> &PricingPolicy {
+        &self.policy
+    }
+
+    /// Earliest time `node_id` may be repriced (0: now)
+    pub fn next_update_at(&self, node_id: &str) -> i64 {
+        let last = self.last_update.lock().ok().and_then(|last| last.get(node_id).copied());
+        last.map_or(0, |at| at + self.policy.min_update_interval.as_secs() as i64)
+    }
+
+    /// Check a price update of `node_id` at `now` and record it if allowed
+    pub(crate) fn admit(&self, node_id: &str, cost_per_hour: f64, now: i64) -> Result<(), SchedulerError> {
+        let max = self.policy.max_cost_per_hour.unwrap_or(f64::MAX);
+        if !cost_per_hour.is_finite() || cost_per_hour < self.policy.min_cost_per_hour || cost_per_hour > max {
+            return Err(SchedulerError::PriceOutOfBounds {
+                node_id: node_id.to_string(),
+                cost_per_hour,
+                min_cost_per_hour: self.policy.min_cost_per_hour,
+                max_cost_per_hour: self.policy.max_cost_per_hour,
+            });
+        }
+
+        let mut last = self.last_update.lock().unwrap_or_else(|e| e.into_inner());
+        let next = last.get(node_id).map_or(now, |at| at + self.policy.min_update_interval.as_secs() as i64);
+        if next > now {
+            return Err(SchedulerError::PriceUpdateTooSoon {
+                node_id: node_id.to_string(),
+                retry_after_secs: (next - now) as u64,
+            });
+        }
+        last.insert(node_id.to_string(), now);
+        Ok(())
+    }
+}
+
+impl EconomicScheduler {
+    /// Bound provider price updates
+    pub fn set_pricing_policy(&mut self, policy: PricingPolicy) {
+        self.node_pricing = NodePricing::new(policy);
+    }
+
+    pub fn node_pricing(&self) -> &NodePricing {
+        &self.node_pricing
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::classify;
+    use crate::NodeInfo;
+
+    #[test]
+    fn test_price_updates_are_bounded_and_rate_limited() {
+        let mut scheduler = EconomicScheduler::new();
+        scheduler.set_pricing_policy(PricingPolicy {
+            min_cost_per_hour: 0.05,
+            max_cost_per_hour: Some(2.0),
+            min_update_interval: Duration::from_secs(300),
+        });
+        scheduler.register_node(NodeInfo {
+            id: "node-1".to_string(),
+            available_cpu: 4,
+            available_memory_gb: 8,
+            cost_per_hour: 0.5,
+            provider: "acme".to_string(),
+            ..Default::default()
+        }).unwrap();
+        let version = scheduler.cluster_version();
+
+        let err = scheduler.set_node_price("acme", "node-1", 5.0).unwrap_err();
+        assert_eq!(classify(&err).map(SchedulerError::reason), Some("PRICE_OUT_OF_BOUNDS"));
+        // No upper bound still refuses non-finite prices
+        assert!(NodePricing::default().admit("node-1", f64::INFINITY, 0).is_err());
+        assert!(NodePricing::default().admit("node-1", f64::NAN, 0).is_err());
+        assert!(scheduler.set_node_price("acme", "node-1", f64::INFINITY).is_err());
+        assert_eq!(scheduler.cluster_version(), version);
+
+        scheduler.set_node_price("acme", "node-1", 0.8).unwrap();
+        assert!(scheduler.cluster_version() > version);
+        assert!(scheduler.node_pricing().next_update_at("node-1") > 0);
+
+        let err = scheduler.set_node_price("acme", "node-1", 0.9).unwrap_err();
+        assert!(matches!(
+            classify(&err),
+            Some(SchedulerError::PriceUpdateTooSoon { retry_after_secs, .. }) if *retry_after_secs <= 300
+        ));
+        assert_eq!(scheduler.cluster_status()[0].cost_per_hour, 0.8);
+    }
+}
@@ -0,0 +1,324 @@
+//! Job completion notifications
+//!
+//! A job may carry a callback URL; without one, its tenant's configured URL
+//! is used. When a job that reached a node finishes, the scheduler POSTs its
+//! final status and cost there as JSON. With a secret configured each
+//! delivery is signed: `X-TGP-Timestamp` holds the send time and
+//! `X-TGP-Signature` is `sha256=<hex HMAC-SHA256 of "<timestamp>.<body>">`,
+//! so receivers can reject forged and replayed calls. Failed deliveries are
+//! retried with exponential backoff; none of this holds up the job's state
+//! change.
+//!
+//! Delivery is behind the `webhooks` cargo feature.
+
+use anyhow::Result;
+use async_trait::async_trait;
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+use crate::error::SchedulerError;
+use crate::{unix_now, EconomicScheduler, JobSpec, JobStatus};
+
+/// How deliveries are signed and retried
+#[derive(Debug, Clone)]
+pub struct DeliveryPolicy {
+    /// HMAC key deliveries are signed with (none: unsigned)
+    pub secret: Option<Vec<u8>>,
+    pub max_attempts: u32,
+    /// Wait before the first retry, doubled for each one after
+    pub initial_backoff: Duration,
+}
+
+impl Default for DeliveryPolicy {
+    fn default() -> Self {
+        Self { secret: None, max_attempts: 5, initial_backoff: Duration::from_secs(2) }
+    }
+}
+
+/// Body POSTed to the callback URL
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JobNotification {
+    pub job_id: String,
+    pub status: JobStatus,
+    pub tenant: String,
+    pub node_id: Option<String>,
+    /// Cost of the run in USD, if it was priced
+    pub cost_usd: Option<f64>,
+    pub finished_at: i64,
+}
+
+/// A notification and where it goes
+#[derive(Debug, Clone)]
+pub struct Delivery {
+    pub url: String,
+    pub notification: JobNotification,
+}
+
+/// Sends a signed notification body
+#[async_trait]
+pub trait CallbackSender: Send + Sync {
+    async fn post(&self, url: &str, body: &[u8], headers: &[(&str, String)]) -> Result<()>;
+}
+
+/// Sender POSTing over HTTP(S)
+pub fn http_sender() -> Result<Arc<dyn CallbackSender>> {
+    #[cfg(feature = "webhooks")]
+    return Ok(Arc::new(webhook::HttpSender::new()));
+    #[cfg(not(feature = "webhooks"))]
+    anyhow::bail!("Callback support not compiled in (enable the `webhooks` feature)");
+}
+
+/// `sha256=<hex>` signature of a delivery sent at `timestamp`
+pub fn sign(secret: &[u8], timestamp: i64, body: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret).expect("HMAC takes keys of any length");
+    mac.update(timestamp.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(body);
+    let digest: String = mac.finalize().into_bytes().iter().map(|b| format!("{:02x}", b)).collect();
+    format!("sha256={}", digest)
+}
+
+/// Routes finished jobs to their callback URLs, shared by scheduler clones
+#[derive(Debug, Clone, Default)]
+pub struct Notifier {
+    /// Tenant to the URL used when its jobs carry none
+    tenant_urls: Arc<HashMap<String, String>>,
+    /// Delivery task input (none: notifications disabled)
+    deliveries: Option<mpsc::UnboundedSender<Delivery>>,
+}
+
+impl Notifier {
+    /// A notifier and the deliveries to hand to `run_deliveries`
+    pub fn new(tenant_urls: HashMap<String, String>) -> (Self, mpsc::UnboundedReceiver<Delivery>) {
+        let (tx, rx) = mpsc::unbounded_channel();
+        (Self { tenant_urls: Arc::new(tenant_urls), deliveries: Some(tx) }, rx)
+    }
+
+    /// `tenant=url,...`
+    pub fn parse_tenant_urls(spec: &str) -> Result<HashMap<String, String>> {
+        let mut urls = HashMap::new();
+        for entry in spec.split(',').map(str::trim).filter(|entry| !entry.is_empty()) {
+            let (tenant, url) = entry.split_once('=')
+                .ok_or_else(|| anyhow::anyhow!("Expected <tenant>=<url>, got {:?}", entry))?;
+            check_url(url.trim())?;
+            urls.insert(tenant.trim().to_string(), url.trim().to_string());
+        }
+        Ok(urls)
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.deliveries.is_some()
+    }
+
+    /// Where a job's notification goes, if anywhere
+    fn url_for(&self, job: &JobSpec) -> Option<String> {
+        job.callback_url.clone().or_else(|| self.tenant_urls.get(&job.tenant).cloned())
+    }
+}
+
+fn check_url(url: &str) -> Result<()> {
+    if url.starts_with("http://") || url.starts_with("https://") {
+        Ok(())
+    } else {
+        Err(SchedulerError::invalid_spec(format!("Callback URL must be http(s): {}", url)).into())
+    }
+}
+
+impl EconomicScheduler {
+    pub fn set_notifier(&mut self, notifier: Notifier) {
+        self.notifier = notifier;
+    }
+
+    /// Refuse callbacks that could never be delivered
+    pub fn check_callback(&self, job: &JobSpec) -> Result<()> {
+        let Some(url) = &job.callback_url else {
+            return Ok(());
+        };
+        if !self.notifier.enabled() {
+            return Err(SchedulerError::invalid_spec("Completion callbacks are not enabled on this scheduler").into());
+        }
+        check_url(url)
+    }
+
+    /// Queue the notification of a job reaching a terminal state
+    pub(crate) fn observe_for_notification(&self, job_id: &str, status: &JobStatus, now: i64) {
+        let Some(deliveries) = self.notifier.deliveries.as_ref().filter(|_| status.is_terminal()) else {
+            return;
+        };
+        let Some((url, tenant)) = self.placed_jobs.lock()
+            .ok()
+            .and_then(|placed| placed.get(job_id).and_then(|spec| Some((self.notifier.url_for(spec)?, spec.tenant.clone()))))
+        else {
+            return;
+        };
+        let state = self.get_job_state(job_id);
+        let notification = JobNotification {
+            job_id: job_id.to_string(),
+            status: status.clone(),
+            tenant,
+            node_id: state.as_ref().and_then(|state| state.assigned_node.clone()),
+            cost_usd: state.and_then(|state| state.estimated_cost).map(|cost| cost.total_usd),
+            finished_at: now,
+        };
+        if deliveries.send(Delivery { url, notification }).is_err() {
+            tracing::warn!("Notification delivery has stopped, job {} not notified", job_id);
+        }
+    }
+}
+
+/// Deliver one notification, retrying with backoff; whether it arrived
+pub async fn deliver(sender: &dyn CallbackSender, policy: &DeliveryPolicy, delivery: &Delivery) -> bool {
+    let body = match serde_json::to_vec(&delivery.notification) {
+        Ok(body) => body,
+        Err(e) => {
+            tracing::error!("Failed to encode notification of job {}: {}", delivery.notification.job_id, e);
+            return false;
+        }
+    };
+    let mut backoff = policy.initial_backoff;
+    for attempt in 1..=policy.max_attempts.max(1) {
+        // Signed per attempt, so the timestamp is the send time
+        let timestamp = unix_now();
+        let mut headers = vec![("X-TGP-Timestamp", timestamp.to_string())];
+        if let Some(secret) = &policy.secret {
+            headers.push(("X-TGP-Signature", sign(secret, timestamp, &body)));
+        }
+        match sender.post(&delivery.url, &body, &headers).await {
+            Ok(()) => return true,
+            Err(e) if attempt < policy.max_attempts => {
+                tracing::debug!("Notification of job {} failed (attempt {}): {}", delivery.notification.job_id, attempt, e);
+                tokio::time::sleep(backoff).await;
+                backoff *= 2;
+            }
+            Err(e) => {
+                tracing::warn!(
+                    "Giving up notifying {} of job {} after {} attempts: {}",
+                    delivery.url, delivery.notification.job_id, attempt, e
+                );
+            }
+        }
+    }
+    false
+}
+
+/// Deliver queued notifications until the scheduler shuts down
+pub async fn run_deliveries(
+    mut deliveries: mpsc::UnboundedReceiver<Delivery>,
+    sender: Arc<dyn CallbackSender>,
+    policy: DeliveryPolicy,
+) {
+    let policy = Arc::new(policy);
+    while let Some(delivery) = deliveries.recv().await {
+        // One slow receiver must not delay the others
+        let sender = sender.clone();
+        let policy = policy.clone();
+        tokio::spawn(async move { deliver(sender.as_ref(), &policy, &delivery).await });
+    }
+}
+
+#[cfg(feature = "webhooks")]
+mod webhook {
+    use super::*;
+
+    pub struct HttpSender {
+        client: reqwest::Client,
+    }
+
+    impl HttpSender {
+        pub fn new() -> Self {
+            Self { client: reqwest::Client::new() }
+        }
+    }
+
+    #[async_trait]
+    impl CallbackSender for HttpSender {
+        async fn post(&self, url: &str, body: &[u8], headers: &[(&str, String)]) -> Result<()> {
+            let mut request = self.client.post(url)
+                .header("Content-Type", "application/json")
+                .body(body.to_vec())
+                .timeout(Duration::from_secs(10));
+            for (name, value) in headers {
+                request = request.header(*name, value);
+            }
+            request.send().await?.error_for_status()?;
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    /// URL, body and headers of a post
+    type Post = (String, Vec<u8>, Vec<(String, String)>);
+
+    /// Fails the first `failures` posts, then records them
+    struct FlakySender {
+        failures: Mutex<u32>,
+        received: Mutex<Vec<Post>>,
+    }
+
+    #[async_trait]
+    impl CallbackSender for FlakySender {
+        async fn post(&self, url: &str, body: &[u8], headers: &[(&str, String)]) -> Result<()> {
+            let mut failures = self.failures.lock().unwrap();
+            if *failures > 0 {
+                *failures -= 1;
+                anyhow::bail!("503 Service Unavailable");
+            }
+            let headers = headers.iter().map(|(name, value)| (name.to_string(), value.clone())).collect();
+            self.received.lock().unwrap().push((url.to_string(), body.to_vec(), headers));
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_notifications_are_signed_and_retried() {
+        // The timestamp is covered, so a replayed body does not verify
+        assert_ne!(sign(b"s3cret", 1, b"{}"), sign(b"s3cret", 2, b"{}"));
+
+        let urls = Notifier::parse_tenant_urls("acme=https://acme.example/tgp, lab=http://10.0.0.5/hook").unwrap();
+        assert_eq!(urls["lab"], "http://10.0.0.5/hook");
+        assert!(Notifier::parse_tenant_urls("acme=ftp://acme.example").is_err());
+
+        let sender = FlakySender { failures: Mutex::new(2), received: Mutex::new(Vec::new()) };
+        let policy = DeliveryPolicy {
+            secret: Some(b"s3cret".to_vec()),
+            max_attempts: 3,
+            initial_backoff: Duration::from_millis(1),
+        };
+        let delivery = Delivery {
+            url: urls["acme"].clone(),
+            notification: JobNotification {
+                job_id: "job-1".to_string(),
+                status: JobStatus::Completed,
+                tenant: "acme".to_string(),
+                node_id: Some("node-1".to_string()),
+                cost_usd: Some(0.42),
+                finished_at: 1_700_000_000,
+            },
+        };
+        assert!(deliver(&sender, &policy, &delivery).await);
+
+        {
+            let received = sender.received.lock().unwrap();
+            let (url, body, headers) = &received[0];
+            assert_eq!(url, "https://acme.example/tgp");
+            let timestamp: i64 = headers.iter().find(|(name, _)| name == "X-TGP-Timestamp").unwrap().1.parse().unwrap();
+            let signature = &headers.iter().find(|(name, _)| name == "X-TGP-Signature").unwrap().1;
+            assert_eq!(signature, &sign(b"s3cret", timestamp, body));
+            assert_eq!(serde_json::from_slice::<JobNotification>(body).unwrap(), delivery.notification);
+        }
+
+        // Out of attempts
+        let sender = FlakySender { failures: Mutex::new(3), received: Mutex::new(Vec::new()) };
+        assert!(!deliver(&sender, &policy, &delivery).await);
+    }
+}
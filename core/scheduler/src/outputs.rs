@@ -0,0 +1,285 @@
+//! Job output capture
+//!
+//! A job may declare an output path (a file or directory in its container).
+//! When the container exits the worker archives that path as a tar file and
+//! uploads it with `UploadJobOutput`; clients fetch it with `GetJobOutput`.
+//! Small outputs are kept in scheduler memory, larger ones are offloaded to
+//! an `ObjectStore` (a `file://` directory, which may be a mounted bucket),
+//! and outputs over the size cap are refused. The log tail a worker sends
+//! with a job's final status is kept alongside, for the most recent jobs,
+//! and served by `GetJobLogs`.
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, VecDeque};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+use crate::error::SchedulerError;
+use crate::EconomicScheduler;
+
+/// Outputs (and log tails) kept in memory; the oldest are dropped first
+const MAX_INLINE_OUTPUTS: usize = 1_000;
+
+/// Durable storage for large job outputs
+#[async_trait]
+pub trait ObjectStore: Send + Sync {
+    async fn put(&self, key: &str, data: &[u8]) -> Result<()>;
+
+    /// The object, or None if there is no object under `key`
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>>;
+}
+
+/// Objects as files under a directory
+pub struct FileObjectStore {
+    root: PathBuf,
+}
+
+impl FileObjectStore {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+}
+
+#[async_trait]
+impl ObjectStore for FileObjectStore {
+    async fn put(&self, key: &str, data: &[u8]) -> Result<()> {
+        let path = self.root.join(key);
+        if let Some(dir) = path.parent() {
+            tokio::fs::create_dir_all(dir).await
+                .with_context(|| format!("Failed to create {}", dir.display()))?;
+        }
+        // Written aside and renamed so readers never see a partial object
+        let partial = path.with_extension("partial");
+        tokio::fs::write(&partial, data).await
+            .with_context(|| format!("Failed to write {}", partial.display()))?;
+        tokio::fs::rename(&partial, &path).await
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+        Ok(())
+    }
+
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>> {
+        let path = self.root.join(key);
+        match tokio::fs::read(&path).await {
+            Ok(data) => Ok(Some(data)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e).with_context(|| format!("Failed to read {}", path.display())),
+        }
+    }
+}
+
+/// Connect to the object store named by `url` (`file:///var/lib/tgp/outputs`)
+pub fn connect_object_store(url: &str) -> Result<Arc<dyn ObjectStore>> {
+    if let Some(path) = url.strip_prefix("file://") {
+        return Ok(Arc::new(FileObjectStore::new(path)));
+    }
+
+    anyhow::bail!("Unsupported object store URL: {}", url)
+}
+
+/// Size caps on job outputs
+#[derive(Debug, Clone, Copy)]
+pub struct OutputLimits {
+    /// Largest output accepted
+    pub max_bytes: u64,
+    /// Larger outputs go to the object store
+    pub inline_max_bytes: u64,
+}
+
+impl Default for OutputLimits {
+    fn default() -> Self {
+        Self {
+            max_bytes: 1024 * 1024 * 1024,
+            inline_max_bytes: 1024 * 1024,
+        }
+    }
+}
+
+/// Object store key of a job's output (job ids may contain any character)
+fn object_key(job_id: &str) -> String {
+    let digest: String = Sha256::digest(job_id.as_bytes())
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect();
+    format!("outputs/{}.tar", digest)
+}
+
+#[derive(Debug, Default)]
+struct InlineOutputs {
+    data: HashMap<String, Arc<Vec<u8>>>,
+    order: VecDeque<String>,
+}
+
+impl InlineOutputs {
+    fn insert(&mut self, job_id: &str, data: Vec<u8>) {
+        if self.data.insert(job_id.to_string(), Arc::new(data)).is_none() {
+            self.order.push_back(job_id.to_string());
+        }
+        while self.order.len() > MAX_INLINE_OUTPUTS {
+            if let Some(oldest) = self.order.pop_front() {
+                self.data.remove(&oldest);
+            }
+        }
+    }
+}
+
+/// Uploaded job outputs, shared by scheduler clones
+#[derive(Clone, Default)]
+pub struct JobOutputs {
+    limits: OutputLimits,
+    object_store: Option<Arc<dyn ObjectStore>>,
+    inline: Arc<Mutex<InlineOutputs>>,
+    /// Size of every output stored, inline or offloaded
+    sizes: Arc<Mutex<HashMap<String, u64>>>,
+    /// Log tails reported with final statuses
+    log_tails: Arc<Mutex<InlineOutputs>>,
+}
+
+impl JobOutputs {
+    pub fn new(limits: OutputLimits, object_store: Option<Arc<dyn ObjectStore>>) -> Self {
+        Self {
+            limits,
+            object_store,
+            ..Default::default()
+        }
+    }
+
+    pub fn limits(&self) -> OutputLimits {
+        self.limits
+    }
+
+    /// Store a job's output archive, replacing an earlier upload
+    pub async fn put(&self, job_id: &str, data: Vec<u8>) -> Result<u64> {
+        let size = data.len() as u64;
+        if size > self.limits.max_bytes {
+            return Err(SchedulerError::invalid_spec(format!(
+                "Output of job {} is {} bytes, the limit is {}",
+                job_id, size, self.limits.max_bytes
+            )).into());
+        }
+
+        if size > self.limits.inline_max_bytes {
+            let store = self.object_store.as_ref().ok_or_else(|| SchedulerError::Unavailable {
+                reason: format!(
+                    "no object store for outputs over {} bytes (job {} output is {})",
+                    self.limits.inline_max_bytes, job_id, size
+                ),
+            })?;
+            store.put(&object_key(job_id), &data).await?;
+            self.forget_inline(job_id);
+        } else {
+            self.inline.lock()
+                .map_err(|e| anyhow::anyhow!("Lock poisoned: {}", e))?
+                .insert(job_id, data);
+        }
+
+        if let Ok(mut sizes) = self.sizes.lock() {
+            sizes.insert(job_id.to_string(), size);
+        }
+        Ok(size)
+    }
+
+    /// A job's output archive, if one was uploaded
+    pub async fn get(&self, job_id: &str) -> Result<Option<Arc<Vec<u8>>>> {
+        let inline = self.inline.lock()
+            .ok()
+            .and_then(|inline| inline.data.get(job_id).cloned());
+        if inline.is_some() {
+            return Ok(inline);
+        }
+
+        // Offloaded outputs outlive a scheduler restart
+        match &self.object_store {
+            Some(store) => Ok(store.get(&object_key(job_id)).await?.map(Arc::new)),
+            None => Ok(None),
+        }
+    }
+
+    /// Size of a job's output, if it was uploaded since this scheduler started
+    pub fn size(&self, job_id: &str) -> Option<u64> {
+        self.sizes.lock().ok().and_then(|sizes| sizes.get(job_id).copied())
+    }
+
+    /// Keep the log tail a worker reported with a job's final status
+    pub fn put_log_tail(&self, job_id: &str, logs: String) {
+        if let Ok(mut tails) = self.log_tails.lock() {
+            tails.insert(job_id, logs.into_bytes());
+        }
+    }
+
+    /// A finished job's log tail, if it is still kept
+    pub fn log_tail(&self, job_id: &str) -> Option<String> {
+        self.log_tails.lock()
+            .ok()
+            .and_then(|tails| tails.data.get(job_id).map(|logs| String::from_utf8_lossy(logs).into_owned()))
+    }
+
+    fn forget_inline(&self, job_id: &str) {
+        if let Ok(mut inline) = self.inline.lock() {
+            if inline.data.remove(job_id).is_some() {
+                inline.order.retain(|id| id != job_id);
+            }
+        }
+    }
+}
+
+impl EconomicScheduler {
+    /// Cap output sizes and offload large outputs to `object_store`
+    pub fn set_output_store(&mut self, limits: OutputLimits, object_store: Option<Arc<dyn ObjectStore>>) {
+        self.outputs = JobOutputs::new(limits, object_store);
+    }
+
+    pub fn job_outputs(&self) -> &JobOutputs {
+        &self.outputs
+    }
+
+    /// Output of a job; a job served from the result cache has the output
+    /// of the job that produced the result
+    pub async fn job_output(&self, job_id: &str) -> Result<Option<Arc<Vec<u8>>>> {
+        let state = self.get_job_state(job_id)
+            .ok_or_else(|| SchedulerError::not_found("Job", job_id))?;
+        let source = state.cached_from.as_deref().unwrap_or(job_id);
+        self.outputs.get(source).await
+    }
+
+    /// Log tail of a finished job, resolved through the result cache like
+    /// its output
+    pub fn job_logs(&self, job_id: &str) -> Result<Option<String>> {
+        let state = self.get_job_state(job_id)
+            .ok_or_else(|| SchedulerError::not_found("Job", job_id))?;
+        Ok(self.outputs.log_tail(state.cached_from.as_deref().unwrap_or(job_id)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_large_outputs_are_offloaded() {
+        let limits = OutputLimits { max_bytes: 100, inline_max_bytes: 10 };
+
+        // Without an object store only small outputs are accepted
+        let outputs = JobOutputs::new(limits, None);
+        assert_eq!(outputs.put("job-1", vec![1; 10]).await.unwrap(), 10);
+        assert!(outputs.put("job-2", vec![1; 50]).await.is_err());
+        assert_eq!(outputs.get("job-1").await.unwrap().unwrap().len(), 10);
+        assert!(outputs.get("job-2").await.unwrap().is_none());
+        outputs.put_log_tail("job-2", "OOM at step 40\n".to_string());
+        assert_eq!(outputs.log_tail("job-2").as_deref(), Some("OOM at step 40\n"));
+        assert!(outputs.log_tail("job-1").is_none());
+
+        let dir = std::env::temp_dir().join(format!("tgp-outputs-test-{}", std::process::id()));
+        let outputs = JobOutputs::new(limits, Some(Arc::new(FileObjectStore::new(&dir))));
+        outputs.put("job/2", vec![2; 50]).await.unwrap();
+        assert!(outputs.put("job-3", vec![3; 101]).await.is_err());
+        assert_eq!(outputs.get("job/2").await.unwrap().unwrap().as_slice(), &[2; 50][..]);
+        assert_eq!(outputs.size("job/2"), Some(50));
+
+        // Offloaded outputs are found again by a restarted scheduler
+        let restarted = JobOutputs::new(limits, Some(Arc::new(FileObjectStore::new(&dir))));
+        assert_eq!(restarted.get("job/2").await.unwrap().unwrap().len(), 50);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,310 @@
+//! Overcommit and idle-cycle harvesting
+//!
+//! Jobs reserve their requested resources but often use far less. With an
+//! overcommit policy set, best-effort jobs are placed into that
+//! reserved-but-unused capacity on nodes where a large enough share of the
+//! reservation sits idle, as measured by worker resource reports. Harvested
+//! jobs are assumed to use their full request. When a report shows the
+//! primary workload growing back into the harvested capacity, best-effort
+//! jobs on the node are throttled or evicted (requeued), largest first.
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+
+use crate::events::SchedulerEvent;
+use crate::{unix_now, EconomicScheduler, ResourceRequirements};
+
+/// What happens to best-effort jobs when the primary workload reclaims capacity
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReclaimAction {
+    /// Ask the worker to lower the job's CPU share; the job keeps running
+    Throttle,
+    /// Requeue the job elsewhere
+    #[default]
+    Evict,
+}
+
+/// Opt-in overcommit policy
+#[derive(Debug, Clone)]
+pub struct OvercommitPolicy {
+    /// Share of a node's reservation that must be idle before harvesting it
+    pub min_unused_fraction: f64,
+    pub reclaim: ReclaimAction,
+}
+
+impl Default for OvercommitPolicy {
+    fn default() -> Self {
+        Self {
+            min_unused_fraction: 0.25,
+            reclaim: ReclaimAction::Evict,
+        }
+    }
+}
+
+/// Reservation and measured use of one node
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct NodeHarvest {
+    pub node_id: String,
+    /// Resources requested by regular (non-harvested) jobs
+    pub reserved_cpu: u32,
+    pub reserved_memory_gb: u32,
+    /// Measured use minus what harvested jobs are assumed to use
+    pub primary_used_cpu: u32,
+    pub primary_used_memory_gb: u32,
+    /// Resources requested by harvested best-effort jobs
+    pub harvested_cpu: u32,
+    pub harvested_memory_gb: u32,
+}
+
+impl NodeHarvest {
+    fn unused(&self) -> (u32, u32) {
+        (
+            self.reserved_cpu.saturating_sub(self.primary_used_cpu),
+            self.reserved_memory_gb.saturating_sub(self.primary_used_memory_gb),
+        )
+    }
+
+    /// Reserved-but-unused capacity not yet given to best-effort jobs
+    pub fn harvestable(&self) -> (u32, u32) {
+        let (cpu, memory_gb) = self.unused();
+        (cpu.saturating_sub(self.harvested_cpu), memory_gb.saturating_sub(self.harvested_memory_gb))
+    }
+
+    /// Smallest idle share of the reservation across CPU and memory
+    pub fn unused_fraction(&self) -> f64 {
+        if self.reserved_cpu == 0 || self.reserved_memory_gb == 0 {
+            return 0.0;
+        }
+        let (cpu, memory_gb) = self.unused();
+        (cpu as f64 / self.reserved_cpu as f64).min(memory_gb as f64 / self.reserved_memory_gb as f64)
+    }
+
+    /// Whether the primary workload has grown into harvested capacity
+    fn reclaimed(&self) -> bool {
+        let (cpu, memory_gb) = self.unused();
+        cpu < self.harvested_cpu || memory_gb < self.harvested_memory_gb
+    }
+}
+
+/// Measured node usage and harvested placements
+#[derive(Debug, Clone, Default)]
+pub struct HarvestTracker {
+    /// Free (cpu, memory GB) from the latest resource report
+    measured: Arc<Mutex<HashMap<String, (u32, u32)>>>,
+    /// Best-effort jobs placed in harvested capacity, mapped to their node
+    harvested: Arc<Mutex<HashMap<String, String>>>,
+}
+
+impl HarvestTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record whether `job_id` was placed in harvested capacity on `node_id`
+    pub(crate) fn set(&self, job_id: &str, node_id: Option<&str>) {
+        if let Ok(mut harvested) = self.harvested.lock() {
+            match node_id {
+                Some(node_id) => harvested.insert(job_id.to_string(), node_id.to_string()),
+                None => harvested.remove(job_id),
+            };
+        }
+    }
+
+    pub fn is_harvested(&self, job_id: &str) -> bool {
+        self.harvested.lock().is_ok_and(|harvested| harvested.contains_key(job_id))
+    }
+
+    /// Free (cpu, memory GB) of each node per its latest resource report
+    pub(crate) fn measured(&self) -> HashMap<String, (u32, u32)> {
+        self.measured.lock().map(|measured| measured.clone()).unwrap_or_default()
+    }
+}
+
+impl EconomicScheduler {
+    /// Enable (or with `None` disable) placing best-effort jobs in idle
+    /// reserved capacity
+    pub fn set_overcommit(&mut self, policy: Option<OvercommitPolicy>) {
+        self.overcommit = policy;
+    }
+
+    /// Measured free resources from a worker report; reclaims harvested
+    /// capacity the primary workload needs back
+    ///
+    /// Returns the best-effort jobs that were throttled or evicted.
+    pub async fn record_node_usage(&self, node_id: &str, free_cpu: u32, free_memory_gb: u32) -> Result<Vec<String>> {
+        {
+            let mut measured = self.harvest.measured.lock()
+                .map_err(|e| anyhow::anyhow!("Lock poisoned: {}", e))?;
+            measured.insert(node_id.to_string(), (free_cpu, free_memory_gb));
+        }
+
+        let Some(policy) = &self.overcommit else {
+            return Ok(Vec::new());
+        };
+        let Some(mut node) = self.node_harvest()?.into_iter().find(|n| n.node_id == node_id) else {
+            return Ok(Vec::new());
+        };
+        if !node.reclaimed() {
+            return Ok(Vec::new());
+        }
+
+        // Largest best-effort jobs yield first
+        let mut victims: Vec<_> = self.placed_on(node_id)?
+            .into_iter()
+            .filter(|spec| self.harvest.is_harvested(&spec.id))
+            .collect();
+        victims.sort_by(|a, b| b.resources.cpu_cores.cmp(&a.resources.cpu_cores)
+            .then_with(|| b.resources.memory_gb.cmp(&a.resources.memory_gb))
+            .then_with(|| a.id.cmp(&b.id)));
+
+        let mut reclaimed = Vec::new();
+        for spec in victims {
+            if !node.reclaimed() {
+                break;
+            }
+            node.harvested_cpu = node.harvested_cpu.saturating_sub(spec.resources.cpu_cores);
+            node.harvested_memory_gb = node.harvested_memory_gb.saturating_sub(spec.resources.memory_gb);
+
+            tracing::warn!("Primary workload on {} reclaims capacity: {:?} job {}", node_id, policy.reclaim, spec.id);
+            self.publish_event(SchedulerEvent::BestEffortReclaimed {
+                job_id: spec.id.clone(),
+                node_id: node_id.to_string(),
+                action: policy.reclaim,
+                timestamp: unix_now(),
+            });
+            reclaimed.push(spec.id.clone());
+
+            if policy.reclaim == ReclaimAction::Evict {
+                self.harvest.set(&spec.id, None);
+                if let Ok(mut placed) = self.placed_jobs.lock() {
+                    placed.remove(&spec.id);
+                }
+                let job_id = spec.id.clone();
+                if let Err(e) = self.requeue(spec).await {
+                    tracing::warn!("Failed to requeue evicted job {}: {}", job_id, e);
+                }
+            }
+        }
+        Ok(reclaimed)
+    }
+
+    /// Reservation and measured use of every node with a resource report
+    pub fn node_harvest(&self) -> Result<Vec<NodeHarvest>> {
+        let measured = self.harvest.measured.lock()
+            .map_err(|e| anyhow::anyhow!("Lock poisoned: {}", e))?
+            .clone();
+
+        let mut by_node: HashMap<String, NodeHarvest> = HashMap::new();
+        let placed = {
+            let placed = self.placed_jobs.lock()
+                .map_err(|e| anyhow::anyhow!("Lock poisoned: {}", e))?;
+            placed.values().cloned().collect::<Vec<_>>()
+        };
+        for spec in placed {
+            let Some(node_id) = self.get_job_state(&spec.id).and_then(|s| s.assigned_node) else {
+                continue;
+            };
+            let entry = by_node.entry(node_id.clone()).or_insert_with(|| NodeHarvest {
+                node_id,
+                ..Default::default()
+            });
+            if self.harvest.is_harvested(&spec.id) {
+                entry.harvested_cpu += spec.resources.cpu_cores;
+                entry.harvested_memory_gb += spec.resources.memory_gb;
+            } else {
+                entry.reserved_cpu += spec.resources.cpu_cores;
+                entry.reserved_memory_gb += spec.resources.memory_gb;
+            }
+        }
+
+        let nodes = self.available_nodes.lock()
+            .map_err(|e| anyhow::anyhow!("Lock poisoned: {}", e))?;
+        let mut report: Vec<NodeHarvest> = measured.into_iter()
+            .filter_map(|(node_id, (free_cpu, free_memory_gb))| {
+                let node = nodes.get(&node_id)?;
+                let mut entry = by_node.remove(&node_id).unwrap_or_else(|| NodeHarvest {
+                    node_id: node_id.clone(),
+                    ..Default::default()
+                });
+                let used_cpu = node.available_cpu.saturating_sub(free_cpu);
+                let used_memory_gb = node.available_memory_gb.saturating_sub(free_memory_gb);
+                entry.primary_used_cpu = used_cpu.saturating_sub(entry.harvested_cpu);
+                entry.primary_used_memory_gb = used_memory_gb.saturating_sub(entry.harvested_memory_gb);
+                Some(entry)
+            })
+            .collect();
+        report.sort_by(|a, b| a.node_id.cmp(&b.node_id));
+        Ok(report)
+    }
+
+    /// Nodes whose idle reservation can take a best-effort job of `required`
+    pub(crate) fn harvest_targets(&self, required: &ResourceRequirements) -> HashSet<String> {
+        let Some(policy) = &self.overcommit else {
+            return HashSet::new();
+        };
+
+        self.node_harvest()
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|node| node.unused_fraction() >= policy.min_unused_fraction)
+            .filter(|node| {
+                let (cpu, memory_gb) = node.harvestable();
+                cpu >= required.cpu_cores && memory_gb >= required.memory_gb && required.gpu_count == 0
+            })
+            .map(|node| node.node_id)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{JobSpec, JobStatus, NodeInfo, SlaConstraints};
+
+    fn job(id: &str, cpu_cores: u32, memory_gb: u32, best_effort: bool) -> JobSpec {
+        JobSpec {
+            id: id.to_string(),
+            resources: ResourceRequirements { cpu_cores, memory_gb, disk_gb: 1, ..Default::default() },
+            sla: SlaConstraints { max_latency_ms: 1_000, ..Default::default() },
+            job_data: id.as_bytes().to_vec(),
+            best_effort,
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_best_effort_harvests_and_is_evicted_on_reclaim() {
+        let mut scheduler = EconomicScheduler::new();
+        scheduler.set_overcommit(Some(OvercommitPolicy::default()));
+        for (id, cost_per_hour) in [("busy", 0.2), ("spare", 0.1)] {
+            scheduler.register_node(NodeInfo {
+                id: id.to_string(),
+                available_cpu: 8,
+                available_memory_gb: 32,
+                location: "vps-1".to_string(),
+                cost_per_hour,
+                ..Default::default()
+            }).unwrap();
+        }
+
+        // A regular job reserves 6 CPU / 16GB on "busy" but uses only 2 / 4
+        scheduler.schedule(job("primary", 6, 16, false)).await.unwrap();
+        scheduler.update_job_state("primary".to_string(), JobStatus::Running, Some("busy".to_string())).unwrap();
+        assert!(scheduler.record_node_usage("busy", 6, 28).await.unwrap().is_empty());
+
+        // The best-effort job goes into the idle reservation, not the cheaper node
+        let placement = scheduler.schedule(job("harvester", 3, 8, true)).await.unwrap();
+        assert_eq!(placement.node_id, "busy");
+        assert!(scheduler.harvest.is_harvested("harvester"));
+
+        // Primary load grows back into the harvested capacity
+        let reclaimed = scheduler.record_node_usage("busy", 0, 20).await.unwrap();
+        assert_eq!(reclaimed, vec!["harvester".to_string()]);
+        let state = scheduler.get_job_state("harvester").unwrap();
+        assert_eq!(state.assigned_node.as_deref(), Some("spare"));
+        assert!(!scheduler.harvest.is_harvested("harvester"));
+    }
+}
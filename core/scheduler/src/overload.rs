@@ -0,0 +1,177 @@
+//! Load shedding under overload
+//!
+//! When placement falls behind, best-effort submissions are refused with a
+//! typed TRY_LATER error instead of being queued behind SLA-bearing work.
+//! The scheduler is overloaded while the moving average of placement
+//! latency, or the depth of the shared queue in queued mode, is over the
+//! operator's threshold. Jobs with an SLA are always admitted. The current
+//! state is reported in cluster status so clients and dashboards can back
+//! off before they are refused.
+
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::error::SchedulerError;
+use crate::{EconomicScheduler, JobSpec};
+
+/// Weight of the newest placement in the latency average
+const LATENCY_SMOOTHING: f64 = 0.2;
+
+/// Back-off suggested to shed submitters
+pub const RETRY_AFTER_SECS: u64 = 30;
+
+/// Thresholds beyond which best-effort submissions are shed (None: no limit)
+#[derive(Debug, Clone, Default)]
+pub struct OverloadPolicy {
+    pub max_placement_latency: Option<Duration>,
+    pub max_queue_depth: Option<usize>,
+}
+
+/// Load signals shared by scheduler clones
+#[derive(Debug, Clone, Default)]
+pub struct LoadShedder {
+    policy: OverloadPolicy,
+    /// Moving average of placement latency in ms (None: nothing placed yet)
+    placement_ms: Arc<Mutex<Option<f64>>>,
+    shed: Arc<AtomicU64>,
+    overloaded: Arc<AtomicBool>,
+}
+
+impl LoadShedder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn policy(&self) -> &OverloadPolicy {
+        &self.policy
+    }
+
+    /// Fold one placement's latency into the average
+    pub fn record_placement(&self, latency: Duration) {
+        let ms = latency.as_secs_f64() * 1000.0;
+        if let Ok(mut average) = self.placement_ms.lock() {
+            *average = Some(match *average {
+                Some(average) => average + LATENCY_SMOOTHING * (ms - average),
+                None => ms,
+            });
+        }
+    }
+
+    fn placement_ms(&self) -> f64 {
+        self.placement_ms.lock().ok().and_then(|average| *average).unwrap_or(0.0)
+    }
+}
+
+/// Current load and whether submissions are being shed
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct OverloadStatus {
+    pub overloaded: bool,
+    /// Threshold that was crossed, empty if none
+    pub reason: String,
+    pub placement_latency_ms: f64,
+    /// Jobs waiting in the shared queue (0 outside queued mode)
+    pub queue_depth: usize,
+    /// Submissions shed since start
+    pub shed_jobs: u64,
+}
+
+impl EconomicScheduler {
+    pub fn set_overload_policy(&mut self, policy: OverloadPolicy) {
+        self.shedder.policy = policy;
+    }
+
+    pub fn load_shedder(&self) -> &LoadShedder {
+        &self.shedder
+    }
+
+    /// Load against the thresholds of the overload policy
+    pub async fn overload_status(&self) -> OverloadStatus {
+        let shedder = &self.shedder;
+        let placement_latency_ms = shedder.placement_ms();
+        let queue_depth = match self.job_queue() {
+            Some(queue) => queue.len().await.unwrap_or_else(|e| {
+                tracing::warn!("Failed to read queue depth: {}", e);
+                0
+            }),
+            None => 0,
+        };
+
+        let mut reasons = Vec::new();
+        if let Some(max) = shedder.policy.max_placement_latency {
+            if placement_latency_ms > max.as_secs_f64() * 1000.0 {
+                reasons.push(format!("placement latency {:.0}ms over {}ms", placement_latency_ms, max.as_millis()));
+            }
+        }
+        if let Some(max) = shedder.policy.max_queue_depth {
+            if queue_depth > max {
+                reasons.push(format!("queue depth {} over {}", queue_depth, max));
+            }
+        }
+
+        let overloaded = !reasons.is_empty();
+        if shedder.overloaded.swap(overloaded, Ordering::Relaxed) != overloaded {
+            if overloaded {
+                tracing::warn!("Scheduler overloaded ({}), shedding best-effort submissions", reasons.join(", "));
+            } else {
+                tracing::info!("Scheduler load back to normal, accepting best-effort submissions");
+            }
+        }
+        OverloadStatus {
+            overloaded,
+            reason: reasons.join(", "),
+            placement_latency_ms,
+            queue_depth,
+            shed_jobs: shedder.shed.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Refuse a best-effort submission while overloaded
+    pub async fn shed_if_overloaded(&self, job: &JobSpec) -> Result<(), SchedulerError> {
+        if !job.best_effort {
+            return Ok(());
+        }
+        let status = self.overload_status().await;
+        if !status.overloaded {
+            return Ok(());
+        }
+        self.shedder.shed.fetch_add(1, Ordering::Relaxed);
+        tracing::info!("Shed best-effort job {}: {}", job.id, status.reason);
+        Err(SchedulerError::TryLater {
+            job_id: job.id.clone(),
+            reason: status.reason,
+            retry_after_secs: RETRY_AFTER_SECS,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_best_effort_shed_while_placement_is_slow() {
+        let mut scheduler = EconomicScheduler::new();
+        scheduler.set_overload_policy(OverloadPolicy {
+            max_placement_latency: Some(Duration::from_millis(100)),
+            max_queue_depth: None,
+        });
+        let best_effort = JobSpec { id: "spare".to_string(), best_effort: true, ..Default::default() };
+        let sla = JobSpec { id: "paid".to_string(), ..Default::default() };
+        assert!(scheduler.shed_if_overloaded(&best_effort).await.is_ok());
+
+        scheduler.load_shedder().record_placement(Duration::from_millis(500));
+        let err = scheduler.shed_if_overloaded(&best_effort).await.unwrap_err();
+        assert_eq!(err.reason(), "TRY_LATER");
+        assert!(scheduler.shed_if_overloaded(&sla).await.is_ok());
+        let status = scheduler.overload_status().await;
+        assert!(status.overloaded);
+        assert_eq!(status.shed_jobs, 1);
+
+        // Fast placements bring the average back under the threshold
+        for _ in 0..20 {
+            scheduler.load_shedder().record_placement(Duration::from_millis(5));
+        }
+        assert!(scheduler.shed_if_overloaded(&best_effort).await.is_ok());
+    }
+}
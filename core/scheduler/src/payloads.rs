@@ -0,0 +1,332 @@
+//! Content-addressed job payload store
+//!
+//! A job's input bytes are kept out of its spec: at admission they are
+//! written to a disk-backed store under their SHA-256 (`sha256:<hex>`) and
+//! the spec carries only the digest, so specs stay small in the queue, the
+//! state store and traces, and identical inputs (array tasks, retries) are
+//! stored once. Clients with inputs too large for a submission upload them
+//! with `UploadPayload` and submit the digest. Workers fetch a payload with
+//! `GetPayload` when the job starts and check it against the digest; the
+//! store checks every read as well.
+//!
+//! The store is capped by a total quota. Payloads older than a retention
+//! period that no placed or queued job refers to are swept periodically
+//! (array tasks not started yet and later retries may still need them,
+//! so nothing is removed the moment a job finishes).
+
+use anyhow::{Context, Result};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+use crate::error::SchedulerError;
+use crate::{EconomicScheduler, JobSpec};
+
+const DIGEST_PREFIX: &str = "sha256:";
+
+/// How often unreferenced payloads are looked for
+const SWEEP_INTERVAL: Duration = Duration::from_secs(3600);
+
+/// Size caps on stored payloads
+#[derive(Debug, Clone, Copy)]
+pub struct PayloadLimits {
+    /// Largest single payload
+    pub max_payload_bytes: u64,
+    /// Total bytes stored
+    pub quota_bytes: u64,
+}
+
+impl Default for PayloadLimits {
+    fn default() -> Self {
+        Self {
+            max_payload_bytes: 64 * 1024 * 1024,
+            quota_bytes: 10 * 1024 * 1024 * 1024,
+        }
+    }
+}
+
+/// `sha256:<hex>` digest of a payload
+pub fn digest(data: &[u8]) -> String {
+    let hex: String = Sha256::digest(data).iter().map(|b| format!("{:02x}", b)).collect();
+    format!("{}{}", DIGEST_PREFIX, hex)
+}
+
+/// Hex part of a well-formed digest
+fn digest_hex(digest: &str) -> Option<&str> {
+    digest.strip_prefix(DIGEST_PREFIX)
+        .filter(|hex| hex.len() == 64 && hex.chars().all(|c| c.is_ascii_hexdigit() && !c.is_ascii_uppercase()))
+}
+
+#[derive(Debug, Default)]
+struct StoreState {
+    /// Bytes on disk
+    used: u64,
+    /// Job id to the payload it refers to
+    refs: HashMap<String, String>,
+}
+
+/// Payloads as files under a directory, shared by scheduler clones
+#[derive(Clone)]
+pub struct PayloadStore {
+    root: PathBuf,
+    limits: PayloadLimits,
+    state: Arc<Mutex<StoreState>>,
+}
+
+impl PayloadStore {
+    /// Open the store at `root`, counting what is already there
+    pub fn open(root: impl Into<PathBuf>, limits: PayloadLimits) -> Result<Self> {
+        let root = root.into();
+        std::fs::create_dir_all(&root).with_context(|| format!("Failed to create {}", root.display()))?;
+        let used = payload_files(&root)
+            .filter_map(|path| std::fs::metadata(path).ok())
+            .map(|meta| meta.len())
+            .sum();
+        Ok(Self {
+            root,
+            limits,
+            state: Arc::new(Mutex::new(StoreState { used, ..Default::default() })),
+        })
+    }
+
+    pub fn limits(&self) -> PayloadLimits {
+        self.limits
+    }
+
+    /// Bytes stored
+    pub fn used_bytes(&self) -> u64 {
+        self.state.lock().map(|state| state.used).unwrap_or(0)
+    }
+
+    fn path(&self, digest: &str) -> Result<PathBuf> {
+        let hex = digest_hex(digest)
+            .ok_or_else(|| SchedulerError::invalid_spec(format!("Invalid payload digest {:?}", digest)))?;
+        Ok(self.root.join(&hex[..2]).join(hex))
+    }
+
+    pub fn contains(&self, digest: &str) -> bool {
+        self.path(digest).is_ok_and(|path| path.exists())
+    }
+
+    /// Store a payload, returning its digest; storing it again is a no-op
+    pub async fn put(&self, data: &[u8]) -> Result<String> {
+        let size = data.len() as u64;
+        if size > self.limits.max_payload_bytes {
+            return Err(SchedulerError::invalid_spec(format!(
+                "Payload is {} bytes, the limit is {}",
+                size, self.limits.max_payload_bytes
+            )).into());
+        }
+        let digest = digest(data);
+        let path = self.path(&digest)?;
+        if path.exists() {
+            return Ok(digest);
+        }
+
+        {
+            let mut state = self.state.lock().map_err(|e| anyhow::anyhow!("Lock poisoned: {}", e))?;
+            if state.used + size > self.limits.quota_bytes {
+                return Err(SchedulerError::Unavailable {
+                    reason: format!(
+                        "payload store is full ({} of {} bytes used, {} more needed)",
+                        state.used, self.limits.quota_bytes, size
+                    ),
+                }.into());
+            }
+            state.used += size;
+        }
+
+        let written = async {
+            if let Some(dir) = path.parent() {
+                tokio::fs::create_dir_all(dir).await.with_context(|| format!("Failed to create {}", dir.display()))?;
+            }
+            // Written aside and renamed so readers never see a partial payload
+            let partial = path.with_extension("partial");
+            tokio::fs::write(&partial, data).await.with_context(|| format!("Failed to write {}", partial.display()))?;
+            tokio::fs::rename(&partial, &path).await.with_context(|| format!("Failed to write {}", path.display()))
+        }.await;
+        if let Err(e) = written {
+            self.forget_bytes(size);
+            return Err(e);
+        }
+        Ok(digest)
+    }
+
+    /// A payload, checked against its digest; None if it is not stored
+    pub async fn get(&self, digest: &str) -> Result<Option<Vec<u8>>> {
+        let path = self.path(digest)?;
+        let data = match tokio::fs::read(&path).await {
+            Ok(data) => data,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e).with_context(|| format!("Failed to read {}", path.display())),
+        };
+        if self::digest(&data) != digest {
+            // Corrupt on disk: drop it so a resubmission can store it again
+            tracing::error!("Payload {} is corrupt, removing it", digest);
+            self.remove_file(&path);
+            anyhow::bail!("Payload {} failed its integrity check", digest);
+        }
+        Ok(Some(data))
+    }
+
+    /// Record that `job_id` needs `digest`
+    pub fn retain(&self, job_id: &str, digest: &str) {
+        if let Ok(mut state) = self.state.lock() {
+            state.refs.insert(job_id.to_string(), digest.to_string());
+        }
+    }
+
+    /// Drop a finished job's reference; the payload goes in the next sweep
+    /// once nothing else refers to it
+    pub fn release(&self, job_id: &str) {
+        if let Ok(mut state) = self.state.lock() {
+            state.refs.remove(job_id);
+        }
+    }
+
+    /// Remove unreferenced payloads older than `retention`; returns how many
+    pub fn sweep(&self, retention: Duration, now: SystemTime) -> usize {
+        let referenced: Vec<PathBuf> = self.state.lock()
+            .map(|state| state.refs.values().filter_map(|digest| self.path(digest).ok()).collect())
+            .unwrap_or_default();
+        payload_files(&self.root)
+            .filter(|path| !referenced.contains(path))
+            .filter(|path| {
+                std::fs::metadata(path)
+                    .and_then(|meta| meta.modified())
+                    .ok()
+                    .and_then(|modified| now.duration_since(modified).ok())
+                    .is_some_and(|age| age >= retention)
+            })
+            .map(|path| self.remove_file(&path))
+            .count()
+    }
+
+    fn remove_file(&self, path: &Path) {
+        let size = std::fs::metadata(path).map(|meta| meta.len()).unwrap_or(0);
+        if std::fs::remove_file(path).is_ok() {
+            self.forget_bytes(size);
+        }
+    }
+
+    fn forget_bytes(&self, size: u64) {
+        if let Ok(mut state) = self.state.lock() {
+            state.used = state.used.saturating_sub(size);
+        }
+    }
+}
+
+/// Stored payload files (`<root>/<xx>/<hex>`)
+fn payload_files(root: &Path) -> impl Iterator<Item = PathBuf> {
+    std::fs::read_dir(root)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter_map(|dir| std::fs::read_dir(dir.path()).ok())
+        .flatten()
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_none())
+}
+
+/// Sweep unreferenced payloads periodically, forever
+pub async fn run_sweeper(store: PayloadStore, retention: Duration) {
+    let mut interval = tokio::time::interval(SWEEP_INTERVAL);
+    loop {
+        interval.tick().await;
+        let sweep_store = store.clone();
+        let removed = tokio::task::spawn_blocking(move || sweep_store.sweep(retention, SystemTime::now()))
+            .await
+            .unwrap_or(0);
+        if removed > 0 {
+            tracing::info!("Removed {} unreferenced payloads", removed);
+        }
+    }
+}
+
+impl EconomicScheduler {
+    pub fn set_payload_store(&mut self, store: Option<PayloadStore>) {
+        self.payloads = store;
+    }
+
+    pub fn payload_store(&self) -> Option<&PayloadStore> {
+        self.payloads.as_ref()
+    }
+
+    /// Move a submission's input into the payload store, or check that the
+    /// payload it refers to is there
+    pub async fn store_payload(&self, mut job: JobSpec) -> Result<JobSpec> {
+        let Some(store) = &self.payloads else {
+            if job.payload_digest.is_some() {
+                return Err(SchedulerError::invalid_spec("payload references need a payload store").into());
+            }
+            return Ok(job);
+        };
+        if let Some(digest) = &job.payload_digest {
+            if !job.job_data.is_empty() {
+                return Err(SchedulerError::invalid_spec("job has both inline input and a payload").into());
+            }
+            if !store.contains(digest) {
+                return Err(SchedulerError::not_found("Payload", digest).into());
+            }
+        } else if !job.job_data.is_empty() {
+            job.payload_digest = Some(store.put(&job.job_data).await?);
+            job.job_data = Vec::new();
+        }
+        Ok(job)
+    }
+
+    /// Keep a placed or queued job's payload until the job finishes
+    pub(crate) fn retain_payload(&self, job: &JobSpec) {
+        if let (Some(store), Some(digest)) = (&self.payloads, &job.payload_digest) {
+            store.retain(&job.id, digest);
+        }
+    }
+
+    pub(crate) fn release_payload(&self, job_id: &str) {
+        if let Some(store) = &self.payloads {
+            store.release(job_id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_payloads_are_deduplicated_verified_and_swept() {
+        let dir = std::env::temp_dir().join(format!("tgp-payloads-test-{}", std::process::id()));
+        let limits = PayloadLimits { max_payload_bytes: 100, quota_bytes: 150 };
+        let store = PayloadStore::open(&dir, limits).unwrap();
+
+        let digest = store.put(&[7; 80]).await.unwrap();
+        assert_eq!(store.put(&[7; 80]).await.unwrap(), digest);
+        assert_eq!(store.used_bytes(), 80);
+        assert!(store.put(&[8; 101]).await.is_err());
+        // Over the quota
+        assert!(store.put(&[9; 80]).await.is_err());
+        assert_eq!(store.get(&digest).await.unwrap().unwrap(), vec![7; 80]);
+
+        // Swept once old enough and both jobs are done with it
+        let later = SystemTime::now() + Duration::from_secs(120);
+        store.retain("task-0", &digest);
+        store.retain("task-1", &digest);
+        store.release("task-0");
+        assert_eq!(store.sweep(Duration::from_secs(60), later), 0);
+        store.release("task-1");
+        assert_eq!(store.sweep(Duration::from_secs(60), SystemTime::now()), 0);
+        assert_eq!(store.sweep(Duration::from_secs(60), later), 1);
+        assert!(!store.contains(&digest));
+        assert_eq!(store.used_bytes(), 0);
+
+        // Corruption is caught on read
+        let digest = store.put(b"input").await.unwrap();
+        std::fs::write(store.path(&digest).unwrap(), b"tampered").unwrap();
+        assert!(store.get(&digest).await.is_err());
+        assert!(!store.contains(&digest));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
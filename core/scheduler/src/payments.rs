@@ -0,0 +1,338 @@
+//! Payment webhooks
+//!
+//! Tenants top up their credit (see `credits`) through a payment gateway,
+//! which calls back when a payment succeeds. The receiver is a small HTTP
+//! endpoint, on `TGP_PAYMENT_WEBHOOK_ADDR`, taking `POST /webhooks/payments`.
+//! What a call means is up to a `PaymentProvider`: it verifies the call's
+//! signature and turns a successful payment into a top-up; other events
+//! are acknowledged and ignored. Each payment is credited once under its
+//! event id, so the gateway may retry a delivery as often as it likes.
+//!
+//! `StripeProvider` follows Stripe's scheme: `Stripe-Signature` is
+//! `t=<timestamp>,v1=<hex HMAC-SHA256 of "<timestamp>.<body>">`, calls more
+//! than `tolerance_secs` old are refused as replays, and the tenant is
+//! taken from the paid object's `metadata.tenant`.
+
+use anyhow::{Context, Result};
+use hmac::{Hmac, Mac};
+use serde::Deserialize;
+use sha2::Sha256;
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::time::Instant;
+
+use crate::credits::CreditBalance;
+use crate::{unix_now, EconomicScheduler};
+
+/// Path the gateway posts to
+pub const WEBHOOK_PATH: &str = "/webhooks/payments";
+
+/// Largest request (head and body) read
+const MAX_REQUEST_BYTES: usize = 64 * 1024;
+
+/// Time a client has to send its whole request
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A verified, successful payment
+#[derive(Debug, Clone, PartialEq)]
+pub struct PaymentEvent {
+    /// Gateway event id, credited once
+    pub event_id: String,
+    pub tenant: String,
+    pub amount_usd: f64,
+}
+
+/// A payment gateway's webhook format
+pub trait PaymentProvider: Send + Sync {
+    /// Short name, prefixed to event ids
+    fn name(&self) -> &str;
+
+    /// Verify a call (header names lowercased) and return the payment it
+    /// reports, or None for events that move no money
+    fn verify(&self, headers: &HashMap<String, String>, body: &[u8], now: i64) -> Result<Option<PaymentEvent>>;
+}
+
+/// Stripe-style signed webhooks
+#[derive(Debug, Clone)]
+pub struct StripeProvider {
+    secret: Vec<u8>,
+    /// Oldest signature timestamp accepted, in seconds before now
+    pub tolerance_secs: i64,
+}
+
+impl StripeProvider {
+    pub fn new(secret: impl Into<Vec<u8>>) -> Self {
+        Self { secret: secret.into(), tolerance_secs: 300 }
+    }
+
+    /// `Stripe-Signature` header value for `body` sent at `timestamp`
+    pub fn sign(&self, timestamp: i64, body: &[u8]) -> String {
+        let digest: String = self.mac(timestamp, body).finalize().into_bytes().iter().map(|b| format!("{:02x}", b)).collect();
+        format!("t={},v1={}", timestamp, digest)
+    }
+
+    fn mac(&self, timestamp: i64, body: &[u8]) -> Hmac<Sha256> {
+        let mut mac = Hmac::<Sha256>::new_from_slice(&self.secret).expect("HMAC takes keys of any length");
+        mac.update(timestamp.to_string().as_bytes());
+        mac.update(b".");
+        mac.update(body);
+        mac
+    }
+}
+
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    if hex.len() % 2 != 0 {
+        return None;
+    }
+    (0..hex.len()).step_by(2).map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok()).collect()
+}
+
+#[derive(Debug, Deserialize)]
+struct StripeEvent {
+    id: String,
+    #[serde(rename = "type")]
+    kind: String,
+    data: StripeData,
+}
+
+#[derive(Debug, Deserialize)]
+struct StripeData {
+    object: StripeObject,
+}
+
+#[derive(Debug, Deserialize)]
+struct StripeObject {
+    /// Checkout sessions
+    amount_total: Option<i64>,
+    /// Payment intents
+    amount_received: Option<i64>,
+    currency: Option<String>,
+    #[serde(default)]
+    metadata: HashMap<String, String>,
+}
+
+impl PaymentProvider for StripeProvider {
+    fn name(&self) -> &str {
+        "stripe"
+    }
+
+    fn verify(&self, headers: &HashMap<String, String>, body: &[u8], now: i64) -> Result<Option<PaymentEvent>> {
+        let header = headers.get("stripe-signature").context("Missing Stripe-Signature header")?;
+        let mut timestamp = None;
+        let mut signatures = Vec::new();
+        for (key, value) in header.split(',').filter_map(|part| part.trim().split_once('=')) {
+            match key {
+                "t" => timestamp = value.parse::<i64>().ok(),
+                "v1" => signatures.extend(decode_hex(value)),
+                _ => {}
+            }
+        }
+        let timestamp = timestamp.context("Stripe-Signature has no timestamp")?;
+        if now - timestamp > self.tolerance_secs {
+            anyhow::bail!("Webhook signed at {} is older than {}s", timestamp, self.tolerance_secs);
+        }
+        if !signatures.iter().any(|signature| self.mac(timestamp, body).verify_slice(signature).is_ok()) {
+            anyhow::bail!("Webhook signature does not match");
+        }
+
+        let event: StripeEvent = serde_json::from_slice(body).context("Malformed webhook event")?;
+        if !matches!(event.kind.as_str(), "checkout.session.completed" | "payment_intent.succeeded") {
+            return Ok(None);
+        }
+        let object = event.data.object;
+        let currency = object.currency.unwrap_or_default();
+        if !currency.eq_ignore_ascii_case("usd") {
+            anyhow::bail!("Payment {} is in {:?}, only USD is credited", event.id, currency);
+        }
+        let cents = object.amount_received.or(object.amount_total).context("Payment has no amount")?;
+        let tenant = object.metadata.get("tenant").filter(|tenant| !tenant.is_empty())
+            .with_context(|| format!("Payment {} names no tenant in its metadata", event.id))?;
+        Ok(Some(PaymentEvent {
+            event_id: event.id,
+            tenant: tenant.clone(),
+            amount_usd: cents as f64 / 100.0,
+        }))
+    }
+}
+
+impl EconomicScheduler {
+    /// Credit the payment a verified webhook call reports; None if it
+    /// reports none
+    pub fn receive_payment(
+        &self,
+        provider: &dyn PaymentProvider,
+        headers: &HashMap<String, String>,
+        body: &[u8],
+        now: i64,
+    ) -> Result<Option<CreditBalance>> {
+        let Some(event) = provider.verify(headers, body, now)? else {
+            return Ok(None);
+        };
+        let reference = format!("{}:{}", provider.name(), event.event_id);
+        tracing::info!("Payment {} of ${:.2} for tenant {}", reference, event.amount_usd, event.tenant);
+        Ok(Some(self.tenant_credits().top_up(&event.tenant, event.amount_usd, Some(&reference))?))
+    }
+}
+
+/// Status code and body for one request
+fn respond(
+    scheduler: &EconomicScheduler,
+    provider: &dyn PaymentProvider,
+    (method, path): (&str, &str),
+    headers: &HashMap<String, String>,
+    body: &[u8],
+) -> (u16, String) {
+    if path != WEBHOOK_PATH {
+        return (404, "not found\n".to_string());
+    }
+    if method != "POST" {
+        return (405, "method not allowed\n".to_string());
+    }
+    match scheduler.receive_payment(provider, headers, body, unix_now()) {
+        Ok(_) => (200, "ok\n".to_string()),
+        Err(e) => {
+            tracing::warn!("Rejected payment webhook: {:#}", e);
+            (400, format!("{}\n", e))
+        }
+    }
+}
+
+/// Read more of a request, giving up on a client that is still sending
+/// at `deadline`
+async fn read_by(stream: &mut TcpStream, chunk: &mut [u8], deadline: Instant) -> Result<usize> {
+    let n = tokio::time::timeout_at(deadline, stream.read(chunk))
+        .await
+        .context("Webhook request not received in time")??;
+    Ok(n)
+}
+
+async fn handle(mut stream: TcpStream, scheduler: EconomicScheduler, provider: Arc<dyn PaymentProvider>) -> Result<()> {
+    let deadline = Instant::now() + REQUEST_TIMEOUT;
+    let mut buf = Vec::with_capacity(4096);
+    let mut chunk = [0u8; 4096];
+    let head_end = loop {
+        if let Some(at) = buf.windows(4).position(|w| w == b"\r\n\r\n") {
+            break Some(at);
+        }
+        if buf.len() >= MAX_REQUEST_BYTES {
+            break None;
+        }
+        let n = read_by(&mut stream, &mut chunk, deadline).await?;
+        if n == 0 {
+            break None;
+        }
+        buf.extend_from_slice(&chunk[..n]);
+    };
+
+    let (code, body) = match head_end {
+        None => (400, "bad request\n".to_string()),
+        Some(head_end) => {
+            let head = String::from_utf8_lossy(&buf[..head_end]).into_owned();
+            let mut lines = head.lines();
+            let mut request_line = lines.next().unwrap_or_default().split_whitespace();
+            let method = request_line.next().unwrap_or_default();
+            let path = request_line.next().unwrap_or_default().split('?').next().unwrap_or_default();
+            let headers: HashMap<String, String> = lines
+                .filter_map(|line| line.split_once(':'))
+                .map(|(name, value)| (name.trim().to_ascii_lowercase(), value.trim().to_string()))
+                .collect();
+
+            let length = headers.get("content-length").and_then(|v| v.parse::<usize>().ok()).unwrap_or(0);
+            let body_start = head_end + 4;
+            if body_start + length > MAX_REQUEST_BYTES {
+                (413, "payload too large\n".to_string())
+            } else {
+                while buf.len() < body_start + length {
+                    let n = read_by(&mut stream, &mut chunk, deadline).await?;
+                    if n == 0 {
+                        break;
+                    }
+                    buf.extend_from_slice(&chunk[..n]);
+                }
+                let end = buf.len().min(body_start + length);
+                respond(&scheduler, provider.as_ref(), (method, path), &headers, &buf[body_start..end])
+            }
+        }
+    };
+
+    let reason = match code {
+        200 => "OK",
+        400 => "Bad Request",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        413 => "Payload Too Large",
+        _ => "Internal Server Error",
+    };
+    let response = format!(
+        "HTTP/1.1 {} {}\r\nContent-Type: text/plain\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        code, reason, body.len(), body
+    );
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await?;
+    Ok(())
+}
+
+/// Receive payment webhooks on `addr` until the process exits
+pub async fn serve_payment_webhooks(addr: SocketAddr, scheduler: EconomicScheduler, provider: Arc<dyn PaymentProvider>) -> Result<()> {
+    let listener = TcpListener::bind(addr)
+        .await
+        .with_context(|| format!("Failed to bind payment webhook endpoint on {}", addr))?;
+    tracing::info!("Payment webhooks ({}) on http://{}{}", provider.name(), addr, WEBHOOK_PATH);
+
+    loop {
+        let (stream, _) = listener.accept().await?;
+        let (scheduler, provider) = (scheduler.clone(), provider.clone());
+        tokio::spawn(async move {
+            if let Err(e) = handle(stream, scheduler, provider).await {
+                tracing::debug!("Payment webhook request failed: {}", e);
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(id: &str, kind: &str, tenant: &str, cents: i64) -> Vec<u8> {
+        serde_json::json!({
+            "id": id,
+            "type": kind,
+            "data": { "object": { "amount_total": cents, "currency": "usd", "metadata": { "tenant": tenant } } },
+        }).to_string().into_bytes()
+    }
+
+    #[test]
+    fn test_signed_payments_are_credited_once() {
+        let scheduler = EconomicScheduler::new();
+        let stripe = StripeProvider::new("whsec_test");
+        let now = unix_now();
+        let body = event("evt_1", "checkout.session.completed", "lab", 2_500);
+        let headers = |signature: String| HashMap::from([("stripe-signature".to_string(), signature)]);
+
+        // Forged, replayed and unsigned calls credit nothing
+        let forged = StripeProvider::new("whsec_other").sign(now, &body);
+        assert!(scheduler.receive_payment(&stripe, &headers(forged), &body, now).is_err());
+        let stale = stripe.sign(now - 3_600, &body);
+        assert!(scheduler.receive_payment(&stripe, &headers(stale), &body, now).is_err());
+        assert!(scheduler.receive_payment(&stripe, &HashMap::new(), &body, now).is_err());
+        assert_eq!(scheduler.tenant_credits().balance("lab").balance_usd, 0.0);
+
+        // A retried delivery is credited once
+        for _ in 0..2 {
+            let balance = scheduler.receive_payment(&stripe, &headers(stripe.sign(now, &body)), &body, now).unwrap().unwrap();
+            assert_eq!(balance.balance_usd, 25.0);
+        }
+
+        let refund = event("evt_2", "charge.refunded", "lab", 2_500);
+        let outcome = scheduler.receive_payment(&stripe, &headers(stripe.sign(now, &refund)), &refund, now).unwrap();
+        assert!(outcome.is_none());
+
+        let (code, _) = respond(&scheduler, &stripe, ("GET", WEBHOOK_PATH), &HashMap::new(), b"");
+        assert_eq!(code, 405);
+    }
+}
@@ -0,0 +1,179 @@
+//! Placement decision cache for repeated job shapes
+//!
+//! Many submissions (inference above all) share their resources, SLA and
+//! image. The first job of a shape evaluates every candidate with Formula
+//! 4.1 and the nodes that passed are cached cheapest first, keyed by the
+//! shape and the cluster state version. A later job of the same shape
+//! re-evaluates the cached nodes in that order and takes the first that
+//! still passes, instead of evaluating the whole cluster again. Any node
+//! registration, report or removal bumps the version and so misses
+//! the cache. Entries also expire after a TTL, which bounds how stale the
+//! ranking gets through changes that live outside the node registry
+//! (reputation, dataset replicas, plugins and scoring scripts). A job none
+//! of whose cached nodes passes falls back to a full evaluation.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::{JobSpec, JobType, ResourceRequirements};
+
+/// Shapes kept before the cache is pruned
+const MAX_SHAPES: usize = 4096;
+
+/// Everything about a job that its ranking of nodes depends on
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) struct PlacementShape {
+    job_type: JobType,
+    image: String,
+    tenant: String,
+    /// Requirements after the predicted peak memory
+    resources: (u32, u32, u32, u32, u32),
+    max_latency_ms: u64,
+    max_budget_bits: Option<u64>,
+    deadline: Option<i64>,
+    ceiling_bits: Option<u64>,
+    duration_bits: u64,
+    datasets: Vec<String>,
+    avoid_nodes: Vec<String>,
+    pin_nodes: Vec<String>,
+    pin_cpus: bool,
+    queue: Option<String>,
+    pool: Option<String>,
+    arch: Vec<String>,
+}
+
+impl PlacementShape {
+    pub(crate) fn of(job: &JobSpec, required: &ResourceRequirements, duration_hours: f64) -> Self {
+        Self {
+            job_type: job.job_type.clone(),
+            image: job.container_image.clone(),
+            tenant: job.tenant.clone(),
+            resources: (
+                required.cpu_cores,
+                required.memory_gb,
+                required.gpu_count,
+                required.disk_gb,
+                required.network_mbps,
+            ),
+            max_latency_ms: job.sla.max_latency_ms,
+            max_budget_bits: job.sla.max_budget_usd.map(f64::to_bits),
+            deadline: job.sla.deadline,
+            ceiling_bits: job.cost_ceiling_usd.map(f64::to_bits),
+            duration_bits: duration_hours.to_bits(),
+            datasets: job.datasets.clone(),
+            avoid_nodes: job.avoid_nodes.clone(),
+            pin_nodes: job.pin_nodes.clone(),
+            pin_cpus: job.pin_cpus,
+            queue: job.queue.clone(),
+            pool: job.pool.clone(),
+            arch: job.arch.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Ranking {
+    version: u64,
+    /// Node ids, cheapest first
+    nodes: Vec<String>,
+    expires: Instant,
+}
+
+/// Cached node rankings per shape, shared by scheduler clones
+#[derive(Debug, Clone, Default)]
+pub struct PlacementCache {
+    /// None: caching disabled
+    ttl: Option<Duration>,
+    rankings: Arc<Mutex<HashMap<PlacementShape, Ranking>>>,
+    hits: Arc<AtomicU64>,
+    misses: Arc<AtomicU64>,
+}
+
+impl PlacementCache {
+    pub fn new(ttl: Option<Duration>) -> Self {
+        Self { ttl, ..Self::default() }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.ttl.is_some()
+    }
+
+    /// (hits, misses) since start
+    pub fn stats(&self) -> (u64, u64) {
+        (self.hits.load(Ordering::Relaxed), self.misses.load(Ordering::Relaxed))
+    }
+
+    /// Cached ranking of `shape` if the cluster is still at `version`
+    pub(crate) fn ranking(&self, shape: &PlacementShape, version: u64) -> Option<Vec<String>> {
+        self.ttl?;
+        let ranking = self.rankings.lock().ok().and_then(|rankings| {
+            rankings.get(shape)
+                .filter(|ranking| ranking.version == version && ranking.expires > Instant::now())
+                .map(|ranking| ranking.nodes.clone())
+        });
+        let counter = if ranking.is_some() { &self.hits } else { &self.misses };
+        counter.fetch_add(1, Ordering::Relaxed);
+        ranking
+    }
+
+    pub(crate) fn store(&self, shape: PlacementShape, version: u64, nodes: Vec<String>) {
+        let Some(ttl) = self.ttl else {
+            return;
+        };
+        let Ok(mut rankings) = self.rankings.lock() else {
+            return;
+        };
+        let now = Instant::now();
+        if rankings.len() >= MAX_SHAPES {
+            rankings.retain(|_, ranking| ranking.version == version && ranking.expires > now);
+            if rankings.len() >= MAX_SHAPES {
+                rankings.clear();
+            }
+        }
+        rankings.insert(shape, Ranking { version, nodes, expires: now + ttl });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{EconomicScheduler, NodeInfo, SlaConstraints};
+
+    #[tokio::test]
+    async fn test_same_shape_reuses_ranking_until_nodes_change() {
+        let mut scheduler = EconomicScheduler::new();
+        scheduler.set_placement_cache_ttl(Some(Duration::from_secs(60)));
+        for (id, cost_per_hour) in [("cheap", 0.5), ("pricey", 2.0)] {
+            scheduler.register_node(NodeInfo {
+                id: id.to_string(),
+                available_cpu: 8,
+                available_memory_gb: 32,
+                cost_per_hour,
+                ..Default::default()
+            }).unwrap();
+        }
+        let job = |id: &str| JobSpec {
+            id: id.to_string(),
+            sla: SlaConstraints { max_latency_ms: 1_000, ..Default::default() },
+            disable_result_cache: true,
+            ..Default::default()
+        };
+
+        assert_eq!(scheduler.schedule(job("a")).await.unwrap().node_id, "cheap");
+        assert_eq!(scheduler.schedule(job("b")).await.unwrap().node_id, "cheap");
+        assert_eq!(scheduler.placement_cache().stats(), (1, 1));
+
+        // A price change re-ranks the cluster
+        scheduler.register_node(NodeInfo {
+            id: "pricey".to_string(),
+            available_cpu: 8,
+            available_memory_gb: 32,
+            cost_per_hour: 0.1,
+            ..Default::default()
+        }).unwrap();
+        assert_eq!(scheduler.schedule(job("c")).await.unwrap().node_id, "pricey");
+        assert_eq!(scheduler.placement_cache().stats(), (1, 2));
+    }
+}
@@ -0,0 +1,339 @@
+//! WASM scheduler plugins
+//!
+//! Third-party extensions ship as WebAssembly modules and run sandboxed in
+//! wasmtime, with a fuel limit per call. A plugin talks to the scheduler
+//! only through this ABI, so it keeps working across scheduler upgrades
+//! as long as `ABI_VERSION` does not change.
+//!
+//! Exports (all but `memory`, `tgp_abi_version` and `tgp_alloc` optional):
+//!
+//! - `memory`
+//! - `tgp_abi_version() -> i32`: must return `ABI_VERSION`
+//! - `tgp_alloc(len: i32) -> i32`: buffer for the host to write input into
+//! - `tgp_filter(ptr: i32, len: i32) -> i32`: 0 rejects the node
+//! - `tgp_score(ptr: i32, len: i32) -> f64`: extra score term in USD
+//! - `tgp_admit(ptr: i32, len: i32) -> i64`: `ptr << 32 | len` of an
+//!   `AdmissionDecision` JSON the plugin wrote into its memory
+//!
+//! Filter and score input is `{"node": NodeInfo, "job": JobSpec,
+//! "cost_usd": f64}`; admission input is the `JobSpec`. Host functions in
+//! module `tgp`: `log(level: i32, ptr: i32, len: i32)` (0 debug .. 3 error)
+//! and `now() -> i64` (unix seconds).
+//!
+//! Every call runs in a fresh instance, so plugins cannot keep state
+//! between calls or leak it across jobs.
+
+use anyhow::Result;
+use std::path::Path;
+#[cfg(feature = "plugins")]
+use std::sync::{Arc, Mutex};
+
+use crate::{EconomicScheduler, JobSpec, NodeInfo};
+
+/// Version of the host/plugin ABI described above
+pub const ABI_VERSION: i32 = 1;
+
+/// Fuel (roughly wasm instructions) a plugin may burn per call
+pub const FUEL_PER_CALL: u64 = 10_000_000;
+
+/// Loaded plugins, consulted in load order
+#[derive(Clone, Default)]
+pub struct Plugins {
+    #[cfg(feature = "plugins")]
+    loaded: Arc<Mutex<Vec<Arc<wasm::WasmPlugin>>>>,
+}
+
+impl Plugins {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Compile the module at `path` and add it after the loaded plugins
+    pub fn load(&self, path: &Path) -> Result<()> {
+        #[cfg(feature = "plugins")]
+        {
+            let plugin = wasm::WasmPlugin::load(path)?;
+            tracing::info!("Loaded scheduler plugin {} ({})", plugin.name, plugin.hooks());
+            self.loaded.lock()
+                .map_err(|e| anyhow::anyhow!("Lock poisoned: {}", e))?
+                .push(Arc::new(plugin));
+            Ok(())
+        }
+        #[cfg(not(feature = "plugins"))]
+        {
+            let _ = path;
+            anyhow::bail!("Plugin support not compiled in (enable the `plugins` feature)")
+        }
+    }
+
+    /// Whether every plugin accepts `node` for `job`; a failing plugin
+    /// rejects the node
+    pub fn filter(&self, node: &NodeInfo, job: &JobSpec, cost_usd: f64) -> bool {
+        #[cfg(feature = "plugins")]
+        for plugin in self.snapshot() {
+            match plugin.filter(node, job, cost_usd) {
+                Ok(true) => {}
+                Ok(false) => {
+                    tracing::debug!("Plugin {} rejected node {} for job {}", plugin.name, node.id, job.id);
+                    return false;
+                }
+                Err(e) => {
+                    tracing::warn!("Plugin {} filter failed: {}", plugin.name, e);
+                    return false;
+                }
+            }
+        }
+        #[cfg(not(feature = "plugins"))]
+        let _ = (node, job, cost_usd);
+        true
+    }
+
+    /// Sum of the plugins' score terms; a failing plugin adds nothing
+    pub fn score(&self, node: &NodeInfo, job: &JobSpec, cost_usd: f64) -> f64 {
+        #[cfg(feature = "plugins")]
+        {
+            self.snapshot()
+                .iter()
+                .map(|plugin| match plugin.score(node, job, cost_usd) {
+                    Ok(term) if term.is_finite() => term,
+                    Ok(_) => 0.0,
+                    Err(e) => {
+                        tracing::warn!("Plugin {} score failed: {}", plugin.name, e);
+                        0.0
+                    }
+                })
+                .sum()
+        }
+        #[cfg(not(feature = "plugins"))]
+        {
+            let _ = (node, job, cost_usd);
+            0.0
+        }
+    }
+
+    /// Run the plugins' admission hooks in order; each sees the previous
+    /// one's mutation
+    pub fn admit(&self, job: JobSpec) -> Result<JobSpec> {
+        #[cfg(feature = "plugins")]
+        {
+            let mut job = job;
+            for plugin in self.snapshot() {
+                let Some(decision) = plugin.admit(&job)? else {
+                    continue;
+                };
+                if !decision.allowed {
+                    return Err(crate::error::SchedulerError::AdmissionDenied {
+                        reason: format!("plugin {}: {}", plugin.name, decision.reason),
+                    }.into());
+                }
+                if let Some(mutated) = decision.job {
+                    if mutated.id != job.id {
+                        anyhow::bail!("Plugin {} may not change the job id", plugin.name);
+                    }
+                    job = mutated;
+                }
+            }
+            Ok(job)
+        }
+        #[cfg(not(feature = "plugins"))]
+        Ok(job)
+    }
+
+    #[cfg(feature = "plugins")]
+    fn snapshot(&self) -> Vec<Arc<wasm::WasmPlugin>> {
+        self.loaded.lock().map(|loaded| loaded.clone()).unwrap_or_default()
+    }
+}
+
+impl EconomicScheduler {
+    pub fn plugins(&self) -> &Plugins {
+        &self.plugins
+    }
+}
+
+#[cfg(feature = "plugins")]
+mod wasm {
+    use super::*;
+    use crate::admission::AdmissionDecision;
+    use serde::Serialize;
+    use wasmtime::{Caller, Config, Engine, InstancePre, Linker, Module, Store};
+
+    #[derive(Serialize)]
+    struct NodeInput<'a> {
+        node: &'a NodeInfo,
+        job: &'a JobSpec,
+        cost_usd: f64,
+    }
+
+    pub struct WasmPlugin {
+        pub name: String,
+        engine: Engine,
+        pre: InstancePre<()>,
+        has_filter: bool,
+        has_score: bool,
+        has_admit: bool,
+    }
+
+    impl WasmPlugin {
+        pub fn load(path: &Path) -> Result<Self> {
+            let name = path.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default();
+            let mut config = Config::new();
+            config.consume_fuel(true);
+            let engine = Engine::new(&config)?;
+            let module = Module::from_file(&engine, path)?;
+            Self::from_module(name, engine, module)
+        }
+
+        pub(super) fn from_module(name: String, engine: Engine, module: Module) -> Result<Self> {
+            let mut linker = Linker::new(&engine);
+            let plugin = name.clone();
+            linker.func_wrap("tgp", "log", move |mut caller: Caller<'_, ()>, level: i32, ptr: i32, len: i32| {
+                let Some(memory) = caller.get_export("memory").and_then(|export| export.into_memory()) else {
+                    return;
+                };
+                let data = memory.data(&caller);
+                let Some(bytes) = data.get(ptr as usize..(ptr as usize).saturating_add(len as usize)) else {
+                    return;
+                };
+                let message = String::from_utf8_lossy(bytes);
+                match level {
+                    0 => tracing::debug!("[plugin {}] {}", plugin, message),
+                    1 => tracing::info!("[plugin {}] {}", plugin, message),
+                    2 => tracing::warn!("[plugin {}] {}", plugin, message),
+                    _ => tracing::error!("[plugin {}] {}", plugin, message),
+                }
+            })?;
+            linker.func_wrap("tgp", "now", || -> i64 { crate::unix_now() })?;
+
+            let has = |export: &str| module.get_export(export).is_some();
+            let (has_filter, has_score, has_admit) = (has("tgp_filter"), has("tgp_score"), has("tgp_admit"));
+            let pre = linker.instantiate_pre(&module)?;
+            let plugin = Self { name, engine, pre, has_filter, has_score, has_admit };
+
+            let (mut store, instance) = plugin.instantiate()?;
+            let version = instance.get_typed_func::<(), i32>(&mut store, "tgp_abi_version")?
+                .call(&mut store, ())?;
+            if version != ABI_VERSION {
+                anyhow::bail!("Plugin {} targets ABI {}, host supports {}", plugin.name, version, ABI_VERSION);
+            }
+            Ok(plugin)
+        }
+
+        pub fn hooks(&self) -> String {
+            [("filter", self.has_filter), ("score", self.has_score), ("admit", self.has_admit)]
+                .iter()
+                .filter(|(_, has)| *has)
+                .map(|(hook, _)| *hook)
+                .collect::<Vec<_>>()
+                .join(", ")
+        }
+
+        fn instantiate(&self) -> Result<(Store<()>, wasmtime::Instance)> {
+            let mut store = Store::new(&self.engine, ());
+            store.set_fuel(FUEL_PER_CALL)?;
+            let instance = self.pre.instantiate(&mut store)?;
+            Ok((store, instance))
+        }
+
+        /// Fresh instance with `input` written into its memory
+        fn prepare(&self, input: &[u8]) -> Result<(Store<()>, wasmtime::Instance, i32, i32)> {
+            let (mut store, instance) = self.instantiate()?;
+            let len = i32::try_from(input.len())?;
+            let ptr = instance.get_typed_func::<i32, i32>(&mut store, "tgp_alloc")?
+                .call(&mut store, len)?;
+            let memory = instance.get_memory(&mut store, "memory")
+                .ok_or_else(|| anyhow::anyhow!("Plugin {} exports no memory", self.name))?;
+            memory.write(&mut store, ptr as usize, input)?;
+            Ok((store, instance, ptr, len))
+        }
+
+        pub fn filter(&self, node: &NodeInfo, job: &JobSpec, cost_usd: f64) -> Result<bool> {
+            if !self.has_filter {
+                return Ok(true);
+            }
+            let input = serde_json::to_vec(&NodeInput { node, job, cost_usd })?;
+            let (mut store, instance, ptr, len) = self.prepare(&input)?;
+            let keep = instance.get_typed_func::<(i32, i32), i32>(&mut store, "tgp_filter")?
+                .call(&mut store, (ptr, len))?;
+            Ok(keep != 0)
+        }
+
+        pub fn score(&self, node: &NodeInfo, job: &JobSpec, cost_usd: f64) -> Result<f64> {
+            if !self.has_score {
+                return Ok(0.0);
+            }
+            let input = serde_json::to_vec(&NodeInput { node, job, cost_usd })?;
+            let (mut store, instance, ptr, len) = self.prepare(&input)?;
+            Ok(instance.get_typed_func::<(i32, i32), f64>(&mut store, "tgp_score")?
+                .call(&mut store, (ptr, len))?)
+        }
+
+        /// The plugin's decision, or None if it has no admission hook
+        pub fn admit(&self, job: &JobSpec) -> Result<Option<AdmissionDecision>> {
+            if !self.has_admit {
+                return Ok(None);
+            }
+            let input = serde_json::to_vec(job)?;
+            let (mut store, instance, ptr, len) = self.prepare(&input)?;
+            let packed = instance.get_typed_func::<(i32, i32), i64>(&mut store, "tgp_admit")?
+                .call(&mut store, (ptr, len))?;
+            let (out_ptr, out_len) = ((packed >> 32) as u32 as usize, packed as u32 as usize);
+            let memory = instance.get_memory(&mut store, "memory")
+                .ok_or_else(|| anyhow::anyhow!("Plugin {} exports no memory", self.name))?;
+            let output = memory.data(&store)
+                .get(out_ptr..out_ptr.saturating_add(out_len))
+                .ok_or_else(|| anyhow::anyhow!("Plugin {} returned an out-of-bounds decision", self.name))?;
+            Ok(Some(serde_json::from_slice(output)?))
+        }
+    }
+}
+
+#[cfg(all(test, feature = "plugins"))]
+mod tests {
+    use super::*;
+    use crate::SlaConstraints;
+
+    /// Rejects every node whose input JSON is longer than 256 bytes
+    /// (nodes with a long location), and adds $1 to every score
+    const PLUGIN: &str = r#"
+        (module
+            (memory (export "memory") 1)
+            (func (export "tgp_abi_version") (result i32) (i32.const 1))
+            (func (export "tgp_alloc") (param i32) (result i32) (i32.const 1024))
+            (func (export "tgp_filter") (param i32 i32) (result i32)
+                (i32.le_u (local.get 1) (i32.const 256)))
+            (func (export "tgp_score") (param i32 i32) (result f64) (f64.const 1.0)))
+    "#;
+
+    #[tokio::test]
+    async fn test_wasm_plugin_filters_nodes() {
+        let mut config = wasmtime::Config::new();
+        config.consume_fuel(true);
+        let engine = wasmtime::Engine::new(&config).unwrap();
+        let module = wasmtime::Module::new(&engine, PLUGIN).unwrap();
+        let plugin = wasm::WasmPlugin::from_module("length".to_string(), engine, module).unwrap();
+        assert_eq!(plugin.hooks(), "filter, score");
+
+        let scheduler = EconomicScheduler::new();
+        scheduler.plugins().loaded.lock().unwrap().push(Arc::new(plugin));
+        for (id, location, cost_per_hour) in [("cheap", "x".repeat(300), 0.1), ("dear", "vps-1".to_string(), 0.2)] {
+            scheduler.register_node(NodeInfo {
+                id: id.to_string(),
+                available_cpu: 16,
+                available_memory_gb: 64,
+                location,
+                cost_per_hour,
+                ..Default::default()
+            }).unwrap();
+        }
+
+        let job = JobSpec {
+            id: "job-1".to_string(),
+            sla: SlaConstraints { max_latency_ms: 10_000, ..Default::default() },
+            ..Default::default()
+        };
+        let placement = scheduler.schedule(job).await.unwrap();
+        assert_eq!(placement.node_id, "dear");
+        assert!(placement.score_usd >= 1.0);
+    }
+}
@@ -0,0 +1,206 @@
+//! Node pools
+//!
+//! Operators group nodes into named pools by label, provider or region,
+//! e.g. a `gpu` pool of the nodes labelled `gpu=a100`. A node belongs to
+//! the first pool, in configured order, whose selector it matches. A job
+//! that targets a pool, itself or through its named queue, is only placed
+//! on that pool's nodes. A dedicated pool is a scheduling domain of its
+//! own: its nodes take no jobs that do not target it. Cluster status reports
+//! each pool's capacity, hourly price and utilization, and the cost summary
+//! adds up node spend per pool.
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::error::SchedulerError;
+use crate::{EconomicScheduler, JobSpec, NodeInfo};
+
+/// Nodes matching every given criterion; an empty criterion does not
+/// restrict
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct NodeSelector {
+    /// Labels the node must carry with these values
+    #[serde(default)]
+    pub labels: HashMap<String, String>,
+    #[serde(default)]
+    pub providers: Vec<String>,
+    /// Regions (node locations)
+    #[serde(default)]
+    pub locations: Vec<String>,
+}
+
+impl NodeSelector {
+    pub fn matches(&self, node: &NodeInfo) -> bool {
+        self.labels.iter().all(|(key, value)| node.labels.get(key) == Some(value))
+            && (self.providers.is_empty() || self.providers.contains(&node.provider))
+            && (self.locations.is_empty() || self.locations.contains(&node.location))
+    }
+}
+
+/// One named pool as written in the TGP_NODE_POOLS file
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct NodePool {
+    pub name: String,
+    pub nodes: NodeSelector,
+    /// Keep the pool's nodes for jobs that target it
+    #[serde(default)]
+    pub dedicated: bool,
+}
+
+/// Configured pools in match order, shared by scheduler clones
+#[derive(Debug, Clone, Default)]
+pub struct NodePools {
+    pools: Arc<Vec<NodePool>>,
+}
+
+impl NodePools {
+    pub fn new(pools: Vec<NodePool>) -> Result<Self> {
+        for (i, pool) in pools.iter().enumerate() {
+            anyhow::ensure!(!pool.name.is_empty(), "Node pool without a name");
+            anyhow::ensure!(
+                !pools[..i].iter().any(|other| other.name == pool.name),
+                "Node pool {} defined twice", pool.name
+            );
+        }
+        Ok(Self { pools: Arc::new(pools) })
+    }
+
+    pub fn get(&self, name: &str) -> Option<&NodePool> {
+        self.pools.iter().find(|pool| pool.name == name)
+    }
+
+    pub fn list(&self) -> &[NodePool] {
+        &self.pools
+    }
+
+    /// Pool `node` belongs to, if any
+    pub fn pool_of(&self, node: &NodeInfo) -> Option<&NodePool> {
+        self.pools.iter().find(|pool| pool.nodes.matches(node))
+    }
+
+    /// Whether a job targeting `pool` (None: no pool) may run on `node`
+    pub(crate) fn admits_node(&self, pool: Option<&str>, node: &NodeInfo) -> bool {
+        let home = self.pool_of(node);
+        match pool {
+            Some(name) => home.is_some_and(|home| home.name == name),
+            None => !home.is_some_and(|home| home.dedicated),
+        }
+    }
+}
+
+/// Active capacity, price and use of one pool
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PoolStatus {
+    pub name: String,
+    pub dedicated: bool,
+    pub nodes: usize,
+    pub cpu_cores: u32,
+    pub memory_gb: u32,
+    pub gpus: u32,
+    pub cost_per_hour: f64,
+    /// Utilized over paid hours of its nodes (0-1)
+    pub utilization: f64,
+}
+
+impl EconomicScheduler {
+    pub fn set_node_pools(&mut self, pools: NodePools) {
+        self.pools = pools;
+    }
+
+    pub fn node_pools(&self) -> &NodePools {
+        &self.pools
+    }
+
+    /// Refuse a job targeting a pool that does not exist
+    pub fn check_pool(&self, job: &JobSpec) -> Result<()> {
+        match job.pool.as_deref() {
+            Some(name) if self.pools.get(name).is_none() => Err(SchedulerError::not_found("Node pool", name).into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Every configured pool with its active nodes as of `now`
+    pub fn pool_status(&self, now: i64) -> Vec<PoolStatus> {
+        let efficiency: HashMap<String, (f64, f64)> = self.node_efficiency(now)
+            .into_iter()
+            .map(|e| (e.node_id, (e.paid_hours, e.utilized_hours)))
+            .collect();
+        let mut report: Vec<PoolStatus> = self.pools.list()
+            .iter()
+            .map(|pool| PoolStatus { name: pool.name.clone(), dedicated: pool.dedicated, ..Default::default() })
+            .collect();
+        let mut hours = vec![(0.0, 0.0); report.len()];
+
+        for node in self.cluster_status() {
+            let Some(home) = self.pools.pool_of(&node) else {
+                continue;
+            };
+            let Some(i) = report.iter().position(|status| status.name == home.name) else {
+                continue;
+            };
+            let status = &mut report[i];
+            status.nodes += 1;
+            status.cpu_cores += node.capacity_cpu.max(node.available_cpu);
+            status.memory_gb += node.capacity_memory_gb.max(node.available_memory_gb);
+            status.gpus += node.available_gpu;
+            status.cost_per_hour += node.cost_per_hour;
+            if let Some((paid, utilized)) = efficiency.get(&node.id) {
+                hours[i].0 += paid;
+                hours[i].1 += utilized;
+            }
+        }
+        for (status, (paid, utilized)) in report.iter_mut().zip(hours) {
+            if paid > 0.0 {
+                status.utilization = (utilized / paid).min(1.0);
+            }
+        }
+        report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SlaConstraints;
+
+    #[tokio::test]
+    async fn test_dedicated_pool_only_takes_its_jobs() {
+        let mut scheduler = EconomicScheduler::new();
+        scheduler.set_node_pools(NodePools::new(vec![NodePool {
+            name: "gpu".to_string(),
+            nodes: NodeSelector {
+                labels: HashMap::from([("gpu".to_string(), "a100".to_string())]),
+                ..Default::default()
+            },
+            dedicated: true,
+        }]).unwrap());
+        for (id, gpu, cost_per_hour) in [("shared", None, 1.0), ("a100", Some("a100"), 0.5)] {
+            scheduler.register_node(NodeInfo {
+                id: id.to_string(),
+                available_cpu: 8,
+                available_memory_gb: 32,
+                cost_per_hour,
+                labels: gpu.map(|gpu| HashMap::from([("gpu".to_string(), gpu.to_string())])).unwrap_or_default(),
+                ..Default::default()
+            }).unwrap();
+        }
+        let job = |id: &str, pool: Option<&str>| JobSpec {
+            id: id.to_string(),
+            pool: pool.map(str::to_string),
+            sla: SlaConstraints { max_latency_ms: 1_000, ..Default::default() },
+            disable_result_cache: true,
+            ..Default::default()
+        };
+
+        // The cheaper node is kept for the pool's jobs
+        assert_eq!(scheduler.schedule(job("web", None)).await.unwrap().node_id, "shared");
+        assert_eq!(scheduler.schedule(job("train", Some("gpu"))).await.unwrap().node_id, "a100");
+        assert!(scheduler.check_pool(&job("lost", Some("tpu"))).is_err());
+
+        let status = scheduler.pool_status(crate::unix_now());
+        assert_eq!(status.len(), 1);
+        assert_eq!((status[0].nodes, status[0].cpu_cores, status[0].cost_per_hour), (1, 8, 0.5));
+    }
+}
@@ -0,0 +1,345 @@
+//! Energy-aware node sleep
+//!
+//! Nodes that stay idle for a while are marked dormant: they leave the
+//! placement index, their worker is told to suspend resource reporting, and
+//! a `NodePowerChanged` event lets an external autoscaler power the VPS
+//! down. When a job finds no capacity, the cheapest dormant node that fits
+//! is marked waking (the autoscaler powers it up) and becomes active again
+//! when its worker re-registers.
+//!
+//! Time spent idle while powered is C_idle incurred; time spent dormant is
+//! C_idle avoided. Both are accounted per node.
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::cost_summary::NODE_IDLE_USD;
+use crate::events::SchedulerEvent;
+use crate::{unix_now, EconomicScheduler, NodeInfo, ResourceRequirements};
+
+/// Power state of a node
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PowerState {
+    #[default]
+    Active,
+    /// Out of the placement pool, may be powered down
+    Dormant,
+    /// Asked to power up; becomes Active when the worker re-registers
+    Waking,
+}
+
+/// Energy accounting for one node
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct NodeEnergy {
+    pub node_id: String,
+    pub state: PowerState,
+    /// Seconds powered with no jobs placed
+    pub idle_secs: u64,
+    /// Seconds dormant (or waking)
+    pub dormant_secs: u64,
+    /// C_idle incurred while powered and idle
+    pub idle_cost_usd: f64,
+    /// C_idle avoided while dormant
+    pub saved_usd: f64,
+}
+
+#[derive(Debug, Clone, Default)]
+struct NodePower {
+    state: PowerState,
+    cost_per_hour: f64,
+    /// Start of the current idle stretch (active nodes)
+    idle_since: Option<i64>,
+    /// Registration kept while the node is out of the index
+    parked: Option<NodeInfo>,
+    /// Last time idle/dormant time was accounted
+    last_sample: i64,
+    idle_secs: u64,
+    dormant_secs: u64,
+}
+
+/// Thread-safe node power tracking
+#[derive(Debug, Clone, Default)]
+pub struct PowerManager {
+    nodes: Arc<Mutex<HashMap<String, NodePower>>>,
+}
+
+impl PowerManager {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Current state of a node (untracked nodes are active)
+    pub fn state(&self, node_id: &str) -> PowerState {
+        self.nodes.lock()
+            .ok()
+            .and_then(|nodes| nodes.get(node_id).map(|n| n.state))
+            .unwrap_or_default()
+    }
+
+    /// Registrations of nodes currently out of the placement pool
+    pub fn parked_nodes(&self) -> Vec<NodeInfo> {
+        self.nodes.lock()
+            .map(|nodes| nodes.values().filter_map(|n| n.parked.clone()).collect())
+            .unwrap_or_default()
+    }
+
+    /// Per-node idle and dormant time with their C_idle value
+    pub fn report(&self) -> Vec<NodeEnergy> {
+        let nodes = match self.nodes.lock() {
+            Ok(nodes) => nodes,
+            Err(_) => return Vec::new(),
+        };
+
+        let mut report: Vec<NodeEnergy> = nodes.iter()
+            .map(|(id, n)| NodeEnergy {
+                node_id: id.clone(),
+                state: n.state,
+                idle_secs: n.idle_secs,
+                dormant_secs: n.dormant_secs,
+                idle_cost_usd: n.cost_per_hour * n.idle_secs as f64 / 3600.0,
+                saved_usd: n.cost_per_hour * n.dormant_secs as f64 / 3600.0,
+            })
+            .collect();
+        report.sort_by(|a, b| a.node_id.cmp(&b.node_id));
+        report
+    }
+}
+
+/// Node sleep policy
+#[derive(Debug, Clone)]
+pub struct EnergyConfig {
+    /// Time between sleep passes
+    pub interval: Duration,
+    /// How long a node must be idle before it is put to sleep
+    pub idle_after: Duration,
+    /// Never put the cluster below this many active nodes
+    pub min_active_nodes: usize,
+}
+
+impl Default for EnergyConfig {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(60),
+            idle_after: Duration::from_secs(900),
+            min_active_nodes: 1,
+        }
+    }
+}
+
+impl EconomicScheduler {
+    /// Account idle time and put nodes idle for `idle_after` to sleep
+    ///
+    /// Returns the nodes that became dormant.
+    pub fn energy_pass(&self, config: &EnergyConfig, now: i64) -> Result<Vec<String>> {
+        let busy: HashSet<String> = {
+            let placed = self.placed_jobs.lock()
+                .map_err(|e| anyhow::anyhow!("Lock poisoned: {}", e))?;
+            placed.keys()
+                .filter_map(|job_id| self.get_job_state(job_id)?.assigned_node)
+                .collect()
+        };
+
+        let mut index = self.available_nodes.lock()
+            .map_err(|e| anyhow::anyhow!("Lock poisoned: {}", e))?;
+        let mut power = self.power.nodes.lock()
+            .map_err(|e| anyhow::anyhow!("Lock poisoned: {}", e))?;
+
+        // Account time since the last pass
+        let active: Vec<NodeInfo> = index.values().cloned().collect();
+        for node in &active {
+            let entry = power.entry(node.id.clone()).or_insert_with(|| NodePower {
+                last_sample: now,
+                ..Default::default()
+            });
+            entry.cost_per_hour = node.cost_per_hour;
+            let elapsed = (now - entry.last_sample).max(0) as u64;
+            entry.last_sample = now;
+
+            if busy.contains(&node.id) {
+                entry.idle_since = None;
+            } else {
+                if entry.idle_since.is_some() {
+                    entry.idle_secs += elapsed;
+                    self.metrics_history.record_counter(
+                        NODE_IDLE_USD,
+                        &node.id,
+                        node.cost_per_hour * elapsed as f64 / 3600.0,
+                        now,
+                    );
+                }
+                entry.idle_since.get_or_insert(now);
+            }
+        }
+        for entry in power.values_mut().filter(|n| n.state != PowerState::Active) {
+            entry.dormant_secs += (now - entry.last_sample).max(0) as u64;
+            entry.last_sample = now;
+        }
+
+        // Most expensive idle nodes sleep first
+        let mut sleepy: Vec<&NodeInfo> = active.iter()
+            .filter(|node| {
+                power.get(&node.id)
+                    .and_then(|p| p.idle_since)
+                    .is_some_and(|since| now - since >= config.idle_after.as_secs() as i64)
+            })
+            .collect();
+        sleepy.sort_by(|a, b| b.cost_per_hour.total_cmp(&a.cost_per_hour).then_with(|| a.id.cmp(&b.id)));
+
+        let mut slept = Vec::new();
+        let mut remaining = active.len();
+        for node in sleepy {
+            if remaining <= config.min_active_nodes {
+                break;
+            }
+            let Some(parked) = index.remove(&node.id) else {
+                continue;
+            };
+            if let Some(entry) = power.get_mut(&node.id) {
+                entry.state = PowerState::Dormant;
+                entry.idle_since = None;
+                entry.parked = Some(parked);
+            }
+            remaining -= 1;
+            slept.push(node.id.clone());
+        }
+        drop(power);
+        drop(index);
+
+        for node_id in &slept {
+            tracing::info!("Node {} idle, marked dormant", node_id);
+            self.publish_event(SchedulerEvent::NodePowerChanged {
+                node_id: node_id.clone(),
+                state: PowerState::Dormant,
+                timestamp: now,
+            });
+        }
+        Ok(slept)
+    }
+
+    /// Ask the dormant node with the lowest cost-per-performance that fits
+    /// `required` to power up
+    pub fn wake_for(&self, required: &ResourceRequirements) -> Option<String> {
+        let node_id = {
+            let mut power = self.power.nodes.lock().ok()?;
+            let node_id = power.iter()
+                .filter(|(_, p)| p.state == PowerState::Dormant)
+                .filter_map(|(id, p)| p.parked.as_ref().map(|node| (id, node)))
+                .filter(|(_, node)| {
+                    node.available_cpu >= required.cpu_cores
+                        && node.available_memory_gb >= required.memory_gb
+                        && node.available_gpu >= required.gpu_count
+                        && node.fits_disk(required.disk_gb)
+                })
+                .min_by(|(a_id, a), (b_id, b)| a.cost_per_performance().total_cmp(&b.cost_per_performance()).then_with(|| a_id.cmp(b_id)))
+                .map(|(id, _)| id.clone())?;
+
+            if let Some(entry) = power.get_mut(&node_id) {
+                entry.state = PowerState::Waking;
+            }
+            node_id
+        };
+
+        tracing::info!("Waking dormant node {} for pending work", node_id);
+        self.publish_event(SchedulerEvent::NodePowerChanged {
+            node_id: node_id.clone(),
+            state: PowerState::Waking,
+            timestamp: unix_now(),
+        });
+        Some(node_id)
+    }
+
+    /// Return a dormant or waking node to the placement pool
+    ///
+    /// Called when its worker registers again. Returns false if the node
+    /// was already active.
+    pub(crate) fn reactivate_node(&self, node_id: &str) -> bool {
+        let reactivated = match self.power.nodes.lock() {
+            Ok(mut power) => match power.get_mut(node_id) {
+                Some(entry) if entry.state != PowerState::Active => {
+                    let now = unix_now();
+                    entry.dormant_secs += (now - entry.last_sample).max(0) as u64;
+                    entry.last_sample = now;
+                    entry.state = PowerState::Active;
+                    entry.parked = None;
+                    true
+                }
+                _ => false,
+            },
+            Err(_) => false,
+        };
+
+        if reactivated {
+            tracing::info!("Node {} active again", node_id);
+            self.publish_event(SchedulerEvent::NodePowerChanged {
+                node_id: node_id.to_string(),
+                state: PowerState::Active,
+                timestamp: unix_now(),
+            });
+        }
+        reactivated
+    }
+
+    /// Node power tracking and energy accounting
+    pub fn power(&self) -> &PowerManager {
+        &self.power
+    }
+}
+
+/// Run sleep passes every `config.interval` until the process exits
+pub async fn run_energy_manager(scheduler: EconomicScheduler, config: EnergyConfig) {
+    let mut ticker = tokio::time::interval(config.interval);
+    loop {
+        ticker.tick().await;
+        if let Err(e) = scheduler.energy_pass(&config, unix_now()) {
+            tracing::warn!("Energy pass failed: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{JobSpec, NodeInfo, SlaConstraints};
+    use crate::test_support::sized_node;
+
+    #[tokio::test]
+    async fn test_idle_node_sleeps_and_wakes_for_work() {
+        let scheduler = EconomicScheduler::new();
+        scheduler.register_node(NodeInfo { cost_per_hour: 0.1, ..sized_node("small", 4, 8, 0) }).unwrap();
+        scheduler.register_node(NodeInfo { cost_per_hour: 0.4, ..sized_node("large", 16, 32, 0) }).unwrap();
+
+        let config = EnergyConfig {
+            idle_after: Duration::from_secs(600),
+            ..Default::default()
+        };
+        assert!(scheduler.energy_pass(&config, 0).unwrap().is_empty());
+        // The pricier node sleeps first; the last active node never does
+        assert_eq!(scheduler.energy_pass(&config, 600).unwrap(), vec!["large".to_string()]);
+        assert_eq!(scheduler.power().state("large"), PowerState::Dormant);
+        assert_eq!(scheduler.node_count(), 1);
+
+        // A job that only fits the dormant node wakes it
+        let job = JobSpec {
+            id: "big".to_string(),
+            resources: ResourceRequirements { cpu_cores: 8, memory_gb: 8, disk_gb: 1, ..Default::default() },
+            sla: SlaConstraints { max_latency_ms: 1_000, ..Default::default() },
+            ..Default::default()
+        };
+        assert!(scheduler.schedule(job.clone()).await.is_err());
+        assert_eq!(scheduler.power().state("large"), PowerState::Waking);
+
+        // Its worker registers again once powered up
+        scheduler.register_node(NodeInfo { cost_per_hour: 0.4, ..sized_node("large", 16, 32, 0) }).unwrap();
+        assert_eq!(scheduler.power().state("large"), PowerState::Active);
+        let retry = JobSpec { id: "big-retry".to_string(), ..job };
+        assert_eq!(scheduler.schedule(retry).await.unwrap().node_id, "large");
+
+        let report = scheduler.power().report();
+        let small = report.iter().find(|n| n.node_id == "small").unwrap();
+        assert_eq!(small.idle_secs, 600);
+        assert!((small.idle_cost_usd - 0.1 / 6.0).abs() < 1e-9);
+    }
+}
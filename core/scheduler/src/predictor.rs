@@ -0,0 +1,367 @@
+//! Job duration and peak-memory prediction
+//!
+//! Finished jobs are kept in a bounded history of outcomes. Periodically a
+//! least-squares fit of reference-node duration against input size is
+//! trained per (image, job type), with a per-type fallback, and the 95th
+//! percentile of reported peak memory is kept alongside it. Predictions feed
+//! the C_comp duration when a job gives no estimate of its own and raise
+//! the memory requirement of jobs that would otherwise OOM.
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::hash::Hash;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::datasets::DatasetRegistry;
+use crate::{EconomicScheduler, JobSpec, JobStatus, JobType, ResourceRequirements};
+
+/// Outcomes kept for training (oldest are dropped first)
+const HISTORY_CAPACITY: usize = 10_000;
+
+/// Observations needed before a group gets its own fit
+const MIN_SAMPLES: usize = 3;
+
+/// Shortest duration ever predicted (one minute)
+const MIN_DURATION_HOURS: f64 = 1.0 / 60.0;
+
+/// Features a prediction is based on
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct JobFeatures {
+    pub image: String,
+    pub job_type: JobType,
+    /// Inline payload plus named input datasets
+    pub input_gb: f64,
+}
+
+impl JobFeatures {
+    pub fn from_job(job: &JobSpec, datasets: &DatasetRegistry) -> Self {
+        let dataset_gb: f64 = job.datasets.iter()
+            .filter_map(|name| datasets.get(name))
+            .map(|info| info.size_gb)
+            .sum();
+
+        Self {
+            image: job.container_image.clone(),
+            job_type: job.job_type.clone(),
+            input_gb: job.job_data.len() as f64 / 1e9 + dataset_gb,
+        }
+    }
+}
+
+/// Observed run of a finished job
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JobOutcome {
+    pub features: JobFeatures,
+    /// Wall-clock run time scaled to the reference node
+    pub duration_hours: f64,
+    pub peak_memory_gb: Option<f64>,
+}
+
+/// Forecast for a job
+#[derive(Debug, Clone, PartialEq)]
+pub struct Prediction {
+    /// Expected run time on the reference node
+    pub duration_hours: f64,
+    pub peak_memory_gb: Option<f64>,
+    /// Outcomes the fit was trained on
+    pub samples: usize,
+}
+
+#[derive(Debug, Clone)]
+struct Fit {
+    intercept: f64,
+    slope: f64,
+    peak_memory_gb: Option<f64>,
+    samples: usize,
+}
+
+impl Fit {
+    fn train(outcomes: &[&JobOutcome]) -> Self {
+        let n = outcomes.len() as f64;
+        let mean_x = outcomes.iter().map(|o| o.features.input_gb).sum::<f64>() / n;
+        let mean_y = outcomes.iter().map(|o| o.duration_hours).sum::<f64>() / n;
+        let (cov, var) = outcomes.iter().fold((0.0, 0.0), |(cov, var), o| {
+            let dx = o.features.input_gb - mean_x;
+            (cov + dx * (o.duration_hours - mean_y), var + dx * dx)
+        });
+        let slope = if var > 1e-12 { cov / var } else { 0.0 };
+
+        let mut peaks: Vec<f64> = outcomes.iter().filter_map(|o| o.peak_memory_gb).collect();
+        peaks.sort_by(f64::total_cmp);
+        let peak_memory_gb = (!peaks.is_empty())
+            .then(|| peaks[((peaks.len() - 1) as f64 * 0.95).round() as usize]);
+
+        Self {
+            intercept: mean_y - slope * mean_x,
+            slope,
+            peak_memory_gb,
+            samples: outcomes.len(),
+        }
+    }
+
+    fn predict(&self, input_gb: f64) -> Prediction {
+        Prediction {
+            duration_hours: (self.intercept + self.slope * input_gb).max(MIN_DURATION_HOURS),
+            peak_memory_gb: self.peak_memory_gb,
+            samples: self.samples,
+        }
+    }
+}
+
+/// Fit every group with enough observations
+fn fit_groups<K: Eq + Hash>(groups: HashMap<K, Vec<&JobOutcome>>) -> HashMap<K, Fit> {
+    groups.into_iter()
+        .filter(|(_, outcomes)| outcomes.len() >= MIN_SAMPLES)
+        .map(|(key, outcomes)| (key, Fit::train(&outcomes)))
+        .collect()
+}
+
+#[derive(Debug, Default)]
+struct Model {
+    by_image: HashMap<(String, JobType), Fit>,
+    by_type: HashMap<JobType, Fit>,
+}
+
+/// Job started on a node, awaiting its outcome
+#[derive(Debug, Clone)]
+struct RunningJob {
+    features: JobFeatures,
+    performance: f64,
+    started_at: i64,
+    peak_memory_gb: Option<f64>,
+}
+
+/// Thread-safe outcome history and trained model
+#[derive(Debug, Clone, Default)]
+pub struct Predictor {
+    history: Arc<Mutex<VecDeque<JobOutcome>>>,
+    running: Arc<Mutex<HashMap<String, RunningJob>>>,
+    model: Arc<Mutex<Model>>,
+}
+
+impl Predictor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a finished job to the training history
+    pub fn record(&self, outcome: JobOutcome) {
+        if let Ok(mut history) = self.history.lock() {
+            if history.len() >= HISTORY_CAPACITY {
+                history.pop_front();
+            }
+            history.push_back(outcome);
+        }
+    }
+
+    pub fn history_len(&self) -> usize {
+        self.history.lock().map(|h| h.len()).unwrap_or(0)
+    }
+
+    /// Refit the model from the current history; returns the samples used
+    pub fn retrain(&self) -> Result<usize> {
+        let history = self.history.lock()
+            .map_err(|e| anyhow::anyhow!("Lock poisoned: {}", e))?
+            .clone();
+
+        let mut by_image: HashMap<(String, JobType), Vec<&JobOutcome>> = HashMap::new();
+        let mut by_type: HashMap<JobType, Vec<&JobOutcome>> = HashMap::new();
+        for outcome in &history {
+            let features = &outcome.features;
+            by_image.entry((features.image.clone(), features.job_type.clone())).or_default().push(outcome);
+            by_type.entry(features.job_type.clone()).or_default().push(outcome);
+        }
+
+        let model = Model {
+            by_image: fit_groups(by_image),
+            by_type: fit_groups(by_type),
+        };
+
+        *self.model.lock().map_err(|e| anyhow::anyhow!("Lock poisoned: {}", e))? = model;
+        Ok(history.len())
+    }
+
+    /// Forecast duration and peak memory (None until enough history)
+    pub fn predict(&self, features: &JobFeatures) -> Option<Prediction> {
+        let model = self.model.lock().ok()?;
+        model.by_image.get(&(features.image.clone(), features.job_type.clone()))
+            .or_else(|| model.by_type.get(&features.job_type))
+            .map(|fit| fit.predict(features.input_gb))
+    }
+
+    /// Note a job starting on a node with the given performance score
+    pub(crate) fn job_started(&self, job_id: &str, features: JobFeatures, performance: f64, now: i64) {
+        if let Ok(mut running) = self.running.lock() {
+            running.entry(job_id.to_string()).or_insert(RunningJob {
+                features,
+                performance,
+                started_at: now,
+                peak_memory_gb: None,
+            });
+        }
+    }
+
+    /// Note the peak memory a running job reported
+    pub(crate) fn record_peak_memory(&self, job_id: &str, peak_memory_gb: f64) {
+        if let Ok(mut running) = self.running.lock() {
+            if let Some(job) = running.get_mut(job_id) {
+                job.peak_memory_gb = Some(job.peak_memory_gb.unwrap_or(0.0).max(peak_memory_gb));
+            }
+        }
+    }
+
+    /// Turn a finished run into a training outcome (failures are dropped)
+    pub(crate) fn job_finished(&self, job_id: &str, succeeded: bool, now: i64) {
+        let Some(job) = self.running.lock().ok().and_then(|mut running| running.remove(job_id)) else {
+            return;
+        };
+        if !succeeded {
+            return;
+        }
+
+        let wall_hours = (now - job.started_at).max(0) as f64 / 3600.0;
+        self.record(JobOutcome {
+            features: job.features,
+            duration_hours: wall_hours * job.performance,
+            peak_memory_gb: job.peak_memory_gb,
+        });
+    }
+}
+
+impl EconomicScheduler {
+    /// Duration and peak-memory forecaster trained on finished jobs
+    pub fn predictor(&self) -> &Predictor {
+        &self.predictor
+    }
+
+    /// Forecast for `job` from the current model
+    pub fn predict_job(&self, job: &JobSpec) -> Option<Prediction> {
+        self.predictor.predict(&JobFeatures::from_job(job, &self.datasets))
+    }
+
+    /// Requested resources, with memory raised to the predicted peak
+    pub(crate) fn predicted_requirements(job: &JobSpec, prediction: Option<&Prediction>) -> ResourceRequirements {
+        let mut required = job.resources.clone();
+        if let Some(peak) = prediction.and_then(|p| p.peak_memory_gb) {
+            let peak = peak.ceil() as u32;
+            if peak > required.memory_gb {
+                tracing::info!(
+                    "Job {} requests {}GB but peaks at {}GB, placing for the peak",
+                    job.id, required.memory_gb, peak
+                );
+                required.memory_gb = peak;
+            }
+        }
+        required
+    }
+
+    /// Feed job lifecycle changes to the predictor
+    pub(crate) fn observe_for_prediction(&self, job_id: &str, status: &JobStatus, now: i64) {
+        match status {
+            JobStatus::Running => {
+                let Some(spec) = self.placed_jobs.lock().ok().and_then(|placed| placed.get(job_id).cloned()) else {
+                    return;
+                };
+                let performance = self.get_job_state(job_id)
+                    .and_then(|state| state.assigned_node)
+                    .and_then(|node_id| {
+                        let nodes = self.available_nodes.lock().ok()?;
+                        nodes.get(&node_id).map(|node| node.performance())
+                    })
+                    .unwrap_or(1.0);
+                let features = JobFeatures::from_job(&spec, &self.datasets);
+                self.predictor.job_started(job_id, features, performance, now);
+            }
+            JobStatus::Completed => self.predictor.job_finished(job_id, true, now),
+            JobStatus::Failed | JobStatus::Cancelled => self.predictor.job_finished(job_id, false, now),
+            JobStatus::Pending | JobStatus::Scheduled => {}
+        }
+    }
+}
+
+/// Retrain the predictor every `interval` until the process exits
+pub async fn run_predictor_trainer(scheduler: EconomicScheduler, interval: Duration) {
+    let mut ticker = tokio::time::interval(interval);
+    loop {
+        ticker.tick().await;
+        match scheduler.predictor().retrain() {
+            Ok(samples) => tracing::debug!("Predictor retrained on {} outcomes", samples),
+            Err(e) => tracing::warn!("Predictor retraining failed: {}", e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{NodeInfo, SlaConstraints};
+
+    fn features(image: &str, input_gb: f64) -> JobFeatures {
+        JobFeatures { image: image.to_string(), job_type: JobType::Training, input_gb }
+    }
+
+    #[test]
+    fn test_fit_predicts_duration_and_peak_memory() {
+        let predictor = Predictor::new();
+        assert!(predictor.predict(&features("trainer:v1", 1.0)).is_none());
+
+        for (input_gb, peak) in [(1.0, 6.0), (2.0, 7.0), (3.0, 8.0)] {
+            predictor.record(JobOutcome {
+                features: features("trainer:v1", input_gb),
+                duration_hours: 0.5 + input_gb,
+                peak_memory_gb: Some(peak),
+            });
+        }
+        assert_eq!(predictor.retrain().unwrap(), 3);
+
+        let prediction = predictor.predict(&features("trainer:v1", 4.0)).unwrap();
+        assert!((prediction.duration_hours - 4.5).abs() < 1e-9);
+        assert_eq!(prediction.peak_memory_gb, Some(8.0));
+
+        // Unseen images fall back to the per-type fit
+        assert_eq!(predictor.predict(&features("other", 4.0)).unwrap().samples, 3);
+    }
+
+    #[tokio::test]
+    async fn test_lifecycle_feeds_history_and_avoids_oom() {
+        let scheduler = EconomicScheduler::new();
+        for (id, memory_gb, cost_per_hour) in [("small", 8, 0.1), ("large", 16, 0.2)] {
+            scheduler.register_node(NodeInfo {
+                id: id.to_string(),
+                available_cpu: 4,
+                available_memory_gb: memory_gb,
+                location: "vps-1".to_string(),
+                cost_per_hour,
+                performance_score: 2.0,
+                ..Default::default()
+            }).unwrap();
+        }
+
+        let job = |id: &str| JobSpec {
+            id: id.to_string(),
+            job_type: JobType::Training,
+            container_image: "trainer:v1".to_string(),
+            resources: ResourceRequirements { cpu_cores: 2, memory_gb: 4, disk_gb: 1, ..Default::default() },
+            sla: SlaConstraints { max_latency_ms: 1_000, ..Default::default() },
+            disable_result_cache: true,
+            ..Default::default()
+        };
+
+        // Three half-hour runs on a 2x node: one reference hour each, peaking at 10GB
+        for i in 0..3 {
+            let id = format!("run-{}", i);
+            assert_eq!(scheduler.schedule(job(&id)).await.unwrap().node_id, "small");
+            scheduler.observe_for_prediction(&id, &JobStatus::Running, 0);
+            scheduler.predictor().record_peak_memory(&id, 10.0);
+            scheduler.observe_for_prediction(&id, &JobStatus::Completed, 1800);
+            scheduler.update_job_state(id, JobStatus::Completed, None).unwrap();
+        }
+        assert_eq!(scheduler.predictor().retrain().unwrap(), 3);
+
+        let placement = scheduler.schedule(job("next")).await.unwrap();
+        assert_eq!(placement.node_id, "large");
+        assert!((placement.estimated_duration_hours - 0.5).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,142 @@
+//! Spot-instance interruption handling
+//!
+//! Preemptible nodes can be reclaimed by the provider at short notice. When
+//! a worker relays the termination warning, the node leaves the placement
+//! pool at once (re-registrations are ignored until the termination time
+//! has passed) and every job placed there is put back to Pending and
+//! requeued, or rescheduled directly when no queue is attached. Workers
+//! stop the affected containers gracefully so they can checkpoint.
+
+use anyhow::Result;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use crate::events::SchedulerEvent;
+use crate::{unix_now, EconomicScheduler};
+
+/// Nodes with a pending termination, mapped to the announced time
+#[derive(Debug, Clone, Default)]
+pub struct InterruptionTracker {
+    nodes: Arc<Mutex<HashMap<String, i64>>>,
+}
+
+impl InterruptionTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether `node_id` is about to be reclaimed at `now`
+    ///
+    /// Marks whose termination time has passed are cleared, so a node that
+    /// comes back afterwards (e.g. a restarted instance) can rejoin.
+    pub fn is_interrupted(&self, node_id: &str, now: i64) -> bool {
+        let Ok(mut nodes) = self.nodes.lock() else {
+            return false;
+        };
+        match nodes.get(node_id) {
+            Some(terminate_at) if *terminate_at > now => true,
+            Some(_) => {
+                nodes.remove(node_id);
+                false
+            }
+            None => false,
+        }
+    }
+
+    fn mark(&self, node_id: &str, terminate_at: i64) {
+        if let Ok(mut nodes) = self.nodes.lock() {
+            nodes.insert(node_id.to_string(), terminate_at);
+        }
+    }
+}
+
+impl EconomicScheduler {
+    /// Handle a provider termination warning for `node_id`
+    ///
+    /// `terminate_at` is the announced Unix time (None: treat as imminent,
+    /// two minutes from now). Returns the ids of the requeued jobs.
+    pub async fn handle_interruption(&self, node_id: &str, terminate_at: Option<i64>) -> Result<Vec<String>> {
+        let terminate_at = terminate_at.unwrap_or_else(|| unix_now() + 120);
+        self.interruptions.mark(node_id, terminate_at);
+
+        {
+            let mut nodes = self.available_nodes.lock()
+                .map_err(|e| anyhow::anyhow!("Lock poisoned: {}", e))?;
+            nodes.remove(node_id);
+        }
+
+        let affected = self.placed_on(node_id)?;
+        let job_ids: Vec<String> = affected.iter().map(|spec| spec.id.clone()).collect();
+
+        tracing::warn!(
+            "Node {} will be reclaimed at {}; requeueing {} jobs",
+            node_id, terminate_at, affected.len()
+        );
+        self.publish_event(SchedulerEvent::NodeInterrupted {
+            node_id: node_id.to_string(),
+            terminate_at,
+            affected_jobs: job_ids.clone(),
+            timestamp: unix_now(),
+        });
+
+        for spec in affected {
+            if let Ok(mut placed) = self.placed_jobs.lock() {
+                placed.remove(&spec.id);
+            }
+
+            let job_id = spec.id.clone();
+            if let Err(e) = self.requeue(spec).await {
+                tracing::warn!("Failed to requeue job {} from interrupted node {}: {}", job_id, node_id, e);
+            }
+        }
+
+        Ok(job_ids)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{JobSpec, JobStatus, NodeInfo, ResourceRequirements, SlaConstraints};
+
+    #[tokio::test]
+    async fn test_interruption_moves_jobs_and_blocks_node() {
+        let scheduler = EconomicScheduler::new();
+        for (id, cost_per_hour, preemptible) in [("spot", 0.05, true), ("ondemand", 0.2, false)] {
+            scheduler.register_node(NodeInfo {
+                id: id.to_string(),
+                available_cpu: 4,
+                available_memory_gb: 8,
+                location: "vps-1".to_string(),
+                cost_per_hour,
+                preemptible,
+                ..Default::default()
+            }).unwrap();
+        }
+
+        let job = JobSpec {
+            id: "job-1".to_string(),
+            resources: ResourceRequirements { cpu_cores: 2, memory_gb: 2, disk_gb: 1, ..Default::default() },
+            sla: SlaConstraints { max_latency_ms: 1_000, ..Default::default() },
+            ..Default::default()
+        };
+        assert_eq!(scheduler.schedule(job).await.unwrap().node_id, "spot");
+
+        let requeued = scheduler.handle_interruption("spot", Some(unix_now() + 120)).await.unwrap();
+        assert_eq!(requeued, vec!["job-1".to_string()]);
+
+        let state = scheduler.get_job_state("job-1").unwrap();
+        assert_eq!(state.status, JobStatus::Scheduled);
+        assert_eq!(state.assigned_node.as_deref(), Some("ondemand"));
+
+        // Heartbeat re-registration during the notice window is ignored
+        scheduler.register_node(NodeInfo {
+            id: "spot".to_string(),
+            available_cpu: 4,
+            available_memory_gb: 8,
+            preemptible: true,
+            ..Default::default()
+        }).unwrap();
+        assert_eq!(scheduler.node_count(), 1);
+    }
+}
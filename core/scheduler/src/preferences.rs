@@ -0,0 +1,148 @@
+//! Soft SLA constraints
+//!
+//! The hard constraints in `SlaConstraints` reject a node outright. A soft
+//! constraint only makes a node less attractive: a candidate violating it
+//! has its score raised by `weight × degree × TCO`, where the degree of
+//! violation runs from 0 (met) to 1 (missed by the constraint's own
+//! value or more, or the wrong region). A job whose preferences no node
+//! meets is still placed, on the node with the best weighted score, and
+//! its `Placement` lists what it gave up.
+
+use serde::{Deserialize, Serialize};
+
+use crate::{JobSpec, NodeInfo};
+
+/// Something a job would like from its node
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Preference {
+    /// Estimated latency at most this many milliseconds
+    MaxLatencyMs(u64),
+    /// Node location in this region: the location itself or a prefix of
+    /// it, e.g. `eu` for `eu-west-1`
+    Region(String),
+    /// Placement TCO at most this many USD
+    MaxCostUsd(f64),
+}
+
+/// A weighted preference
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SoftConstraint {
+    pub preference: Preference,
+    /// Share of the node's TCO added to its score on a full violation
+    pub weight: f64,
+}
+
+/// A soft constraint the chosen node does not meet
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PreferenceViolation {
+    pub preference: Preference,
+    pub weight: f64,
+    /// How far it is missed, from 0 (exclusive) to 1
+    pub degree: f64,
+}
+
+impl SoftConstraint {
+    pub fn new(preference: Preference, weight: f64) -> Self {
+        Self { preference, weight }
+    }
+
+    /// Degree to which a node with `latency_ms` and `cost_usd` misses the
+    /// preference, from 0 (met) to 1
+    pub fn violation(&self, node: &NodeInfo, latency_ms: u64, cost_usd: f64) -> f64 {
+        let overshoot = |actual: f64, limit: f64| {
+            if actual <= limit {
+                0.0
+            } else if limit <= 0.0 {
+                1.0
+            } else {
+                ((actual - limit) / limit).min(1.0)
+            }
+        };
+        match &self.preference {
+            Preference::MaxLatencyMs(max_ms) => overshoot(latency_ms as f64, *max_ms as f64),
+            Preference::Region(region) => if in_region(&node.location, region) { 0.0 } else { 1.0 },
+            Preference::MaxCostUsd(max_usd) => overshoot(cost_usd, *max_usd),
+        }
+    }
+}
+
+fn in_region(location: &str, region: &str) -> bool {
+    let location = location.to_ascii_lowercase();
+    let region = region.to_ascii_lowercase();
+    location == region || location.strip_prefix(&region).is_some_and(|rest| rest.starts_with('-'))
+}
+
+/// Check a job's soft constraints, naming the first invalid one
+pub fn validate(job: &JobSpec) -> Result<(), String> {
+    for constraint in &job.sla.preferences {
+        if !constraint.weight.is_finite() || constraint.weight < 0.0 {
+            return Err(format!("Preference {:?} has invalid weight {}", constraint.preference, constraint.weight));
+        }
+        match &constraint.preference {
+            Preference::Region(region) if region.is_empty() => {
+                return Err("Region preference must name a region".to_string());
+            }
+            Preference::MaxCostUsd(usd) if !usd.is_finite() || *usd < 0.0 => {
+                return Err(format!("Cost preference {} must be a non-negative amount", usd));
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// The preferences of `job` that a node misses, and the score term they
+/// add to its `cost_usd`
+pub fn assess(job: &JobSpec, node: &NodeInfo, latency_ms: u64, cost_usd: f64) -> (Vec<PreferenceViolation>, f64) {
+    let violations: Vec<PreferenceViolation> = job.sla.preferences
+        .iter()
+        .filter_map(|constraint| {
+            let degree = constraint.violation(node, latency_ms, cost_usd);
+            (degree > 0.0).then(|| PreferenceViolation {
+                preference: constraint.preference.clone(),
+                weight: constraint.weight,
+                degree,
+            })
+        })
+        .collect();
+    let penalty_usd = violations.iter().map(|v| v.weight * v.degree).sum::<f64>() * cost_usd;
+    (violations, penalty_usd)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{EconomicScheduler, NodeInfo, ResourceRequirements, SlaConstraints};
+    use crate::test_support::node;
+
+    fn job(preferences: Vec<SoftConstraint>) -> JobSpec {
+        JobSpec {
+            id: "job-1".to_string(),
+            resources: ResourceRequirements { cpu_cores: 1, memory_gb: 1, ..Default::default() },
+            sla: SlaConstraints { max_latency_ms: 1_000, preferences, ..Default::default() },
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_preferences_steer_placement_without_blocking_it() {
+        let scheduler = EconomicScheduler::new();
+        scheduler.register_node(NodeInfo { location: "us-east-1".to_string(), ..node("us-node", 0.10) }).unwrap();
+        scheduler.register_node(NodeInfo { location: "eu-west-1".to_string(), ..node("eu-node", 0.15) }).unwrap();
+
+        // Worth paying 50% more to stay in the EU
+        let region = SoftConstraint::new(Preference::Region("EU".to_string()), 1.0);
+        let placement = scheduler.schedule(job(vec![region.clone()])).await.unwrap();
+        assert_eq!(placement.node_id, "eu-node");
+        assert!(placement.preference_violations.is_empty());
+
+        // Unmeetable: placed anyway, reporting how far it is missed
+        let scheduler = EconomicScheduler::new();
+        scheduler.register_node(NodeInfo { location: "us-east-1".to_string(), ..node("us-node", 0.10) }).unwrap();
+        let latency = SoftConstraint::new(Preference::MaxLatencyMs(25), 0.5);
+        let placement = scheduler.schedule(job(vec![region, latency])).await.unwrap();
+        assert_eq!(placement.node_id, "us-node");
+        let degrees: Vec<f64> = placement.preference_violations.iter().map(|v| v.degree).collect();
+        assert_eq!(degrees, vec![1.0, 1.0]);
+    }
+}
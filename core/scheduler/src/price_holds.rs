@@ -0,0 +1,239 @@
+//! Holding over-budget jobs until prices drop
+//!
+//! A job submitted with `price_hold_secs` is not failed when its cheapest
+//! placement is over its budget. It stays Pending and is held for up to
+//! that long, and it is evaluated again whenever a node registers or a
+//! provider changes a node's price, and when a job finishes. It is placed
+//! as soon as a placement fits its budget. A held job that expires first is
+//! failed. Events are published when a job is held and when its hold
+//! expires, so notification pipelines can tell the submitter.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::Notify;
+
+use crate::events::SchedulerEvent;
+use crate::{unix_now, EconomicScheduler, JobSpec, JobStatus};
+
+/// Longest a job may be held for a price drop
+pub const MAX_PRICE_HOLD_SECS: u64 = 7 * 24 * 3600;
+
+/// How often expired holds are looked for when nothing changes
+const EXPIRY_INTERVAL: Duration = Duration::from_secs(30);
+
+/// A job waiting for a placement within its budget
+#[derive(Debug, Clone)]
+pub struct HeldJob {
+    pub job: JobSpec,
+    /// Cheapest placement when the job was last evaluated
+    pub cheapest_usd: f64,
+    pub expires_at: i64,
+}
+
+/// Jobs held for a price drop, shared by scheduler clones
+#[derive(Debug, Clone, Default)]
+pub struct PriceHolds {
+    held: Arc<Mutex<HashMap<String, HeldJob>>>,
+    changed: Arc<Notify>,
+}
+
+impl PriceHolds {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Currently held jobs
+    pub fn held(&self) -> Vec<HeldJob> {
+        self.held.lock().map(|held| held.values().cloned().collect()).unwrap_or_default()
+    }
+
+    pub fn is_held(&self, job_id: &str) -> bool {
+        self.held.lock().is_ok_and(|held| held.contains_key(job_id))
+    }
+
+    /// Hold a job; a job already held keeps its expiry. Returns the expiry
+    /// and whether the job was newly held
+    fn hold(&self, job: JobSpec, cheapest_usd: f64, expires_at: i64) -> (i64, bool) {
+        let Ok(mut held) = self.held.lock() else {
+            return (expires_at, false);
+        };
+        match held.get_mut(&job.id) {
+            Some(existing) => {
+                existing.cheapest_usd = cheapest_usd;
+                (existing.expires_at, false)
+            }
+            None => {
+                held.insert(job.id.clone(), HeldJob { job, cheapest_usd, expires_at });
+                (expires_at, true)
+            }
+        }
+    }
+
+    fn release(&self, job_id: &str) {
+        if let Ok(mut held) = self.held.lock() {
+            held.remove(job_id);
+        }
+    }
+
+    /// Prices or capacity changed: held jobs are worth evaluating again
+    pub(crate) fn wake(&self) {
+        self.changed.notify_one();
+    }
+}
+
+impl EconomicScheduler {
+    pub fn price_holds(&self) -> &PriceHolds {
+        &self.price_holds
+    }
+
+    /// Hold an over-budget job if it asked to wait for a price drop;
+    /// returns the hold's expiry, None if the job should fail instead
+    pub(crate) fn hold_for_price(&self, job: &JobSpec, max_budget_usd: f64, cheapest_usd: f64) -> Option<i64> {
+        let hold_secs = job.price_hold_secs.filter(|&secs| secs > 0)?.min(MAX_PRICE_HOLD_SECS);
+        let now = unix_now();
+        let (expires_at, new) = self.price_holds.hold(job.clone(), cheapest_usd, now + hold_secs as i64);
+        if expires_at <= now {
+            return None;
+        }
+        if new {
+            tracing::info!(
+                "Job {} held until {}: cheapest placement ${:.4} is over its ${:.4} budget",
+                job.id, expires_at, cheapest_usd, max_budget_usd
+            );
+            self.publish_event(SchedulerEvent::JobHeldForPrice {
+                job_id: job.id.clone(),
+                max_budget_usd,
+                cheapest_usd,
+                expires_at,
+                timestamp: now,
+            });
+        }
+        Some(expires_at)
+    }
+
+    /// Place held jobs that now fit their budget and fail expired ones
+    pub async fn retry_price_holds(&self) {
+        let now = unix_now();
+        for held in self.price_holds.held() {
+            let job_id = held.job.id.clone();
+            // Cancelled while held
+            if !self.get_job_state(&job_id).is_some_and(|state| state.status == JobStatus::Pending) {
+                self.price_holds.release(&job_id);
+                continue;
+            }
+
+            if held.expires_at <= now {
+                self.price_holds.release(&job_id);
+                tracing::info!("Price hold of job {} expired at ${:.4}", job_id, held.cheapest_usd);
+                if let Err(e) = self.update_job_state(job_id.clone(), JobStatus::Failed, None) {
+                    tracing::warn!("Failed to expire held job {}: {}", job_id, e);
+                }
+                self.publish_event(SchedulerEvent::JobPriceHoldExpired {
+                    job_id,
+                    max_budget_usd: held.job.sla.max_budget_usd.unwrap_or_default(),
+                    cheapest_usd: held.cheapest_usd,
+                    timestamp: now,
+                });
+                continue;
+            }
+
+            // Only go through placement once a quote fits the budget
+            let affordable = match (self.cheapest_placement_usd(&held.job), held.job.sla.max_budget_usd) {
+                (Ok(Some(cheapest_usd)), Some(budget)) => cheapest_usd <= budget,
+                _ => false,
+            };
+            if !affordable {
+                continue;
+            }
+            match self.place(held.job).await {
+                Ok(placement) => {
+                    self.price_holds.release(&job_id);
+                    tracing::info!("Held job {} placed on {} after a price drop", job_id, placement.node_id);
+                }
+                // Still over budget once placement saw the whole picture
+                Err(_) if self.get_job_state(&job_id).is_some_and(|state| state.status == JobStatus::Pending) => {}
+                Err(e) => {
+                    self.price_holds.release(&job_id);
+                    tracing::warn!("Failed to place held job {}: {}", job_id, e);
+                }
+            }
+        }
+    }
+}
+
+/// Re-evaluate held jobs on price and cluster changes, forever
+pub async fn run_price_holds(scheduler: EconomicScheduler) {
+    let changed = scheduler.price_holds.changed.clone();
+    loop {
+        tokio::select! {
+            _ = changed.notified() => {}
+            _ = tokio::time::sleep(EXPIRY_INTERVAL) => {}
+        }
+        scheduler.retry_price_holds().await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{NodeInfo, SlaConstraints};
+
+    #[tokio::test]
+    async fn test_over_budget_job_waits_for_cheaper_node() {
+        let scheduler = EconomicScheduler::new();
+        scheduler.register_node(NodeInfo {
+            id: "pricey".to_string(),
+            available_cpu: 8,
+            available_memory_gb: 32,
+            cost_per_hour: 10.0,
+            ..Default::default()
+        }).unwrap();
+
+        let job = JobSpec {
+            id: "thrifty".to_string(),
+            sla: SlaConstraints { max_latency_ms: 5000, max_budget_usd: Some(1.0), ..Default::default() },
+            estimated_duration_hours: Some(1.0),
+            price_hold_secs: Some(3600),
+            ..Default::default()
+        };
+        let mut events = scheduler.subscribe_events();
+        assert!(scheduler.schedule(job.clone()).await.is_err());
+        assert!(scheduler.price_holds().is_held("thrifty"));
+        assert_eq!(scheduler.get_job_state("thrifty").unwrap().status, JobStatus::Pending);
+        let cheapest = loop {
+            if let SchedulerEvent::JobHeldForPrice { cheapest_usd, .. } = events.recv().await.unwrap() {
+                break cheapest_usd;
+            }
+        };
+        assert!(cheapest > 1.0);
+
+        // Still too expensive: nothing changes
+        scheduler.retry_price_holds().await;
+        assert!(scheduler.price_holds().is_held("thrifty"));
+
+        scheduler.register_node(NodeInfo {
+            id: "cheap".to_string(),
+            available_cpu: 8,
+            available_memory_gb: 32,
+            cost_per_hour: 0.1,
+            ..Default::default()
+        }).unwrap();
+        scheduler.retry_price_holds().await;
+        assert!(!scheduler.price_holds().is_held("thrifty"));
+        let state = scheduler.get_job_state("thrifty").unwrap();
+        assert_eq!(state.status, JobStatus::Scheduled);
+        assert_eq!(state.assigned_node.as_deref(), Some("cheap"));
+
+        // Without a hold the job fails as before
+        let impatient = JobSpec {
+            id: "impatient".to_string(),
+            sla: SlaConstraints { max_budget_usd: Some(0.01), ..job.sla.clone() },
+            price_hold_secs: None,
+            ..job
+        };
+        assert!(scheduler.schedule(impatient).await.is_err());
+        assert!(!scheduler.price_holds().is_held("impatient"));
+        assert_eq!(scheduler.get_job_state("impatient").unwrap().status, JobStatus::Failed);
+    }
+}
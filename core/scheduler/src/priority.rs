@@ -0,0 +1,132 @@
+//! Job priority classes and queue aging
+//!
+//! Queued jobs are claimed highest effective priority first. A job's
+//! effective priority starts at its class base and rises with the time it
+//! has waited, at a per-class aging rate, so low-priority work eventually
+//! overtakes a steady stream of fresh high-priority submissions.
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::str::FromStr;
+
+/// Scheduling priority of a job
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PriorityClass {
+    Low,
+    #[default]
+    Normal,
+    High,
+}
+
+impl PriorityClass {
+    pub const ALL: [PriorityClass; 3] = [PriorityClass::Low, PriorityClass::Normal, PriorityClass::High];
+
+    /// Effective priority of a job of this class that has not waited
+    pub fn base(self) -> f64 {
+        match self {
+            PriorityClass::Low => 0.0,
+            PriorityClass::Normal => 100.0,
+            PriorityClass::High => 200.0,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            PriorityClass::Low => "low",
+            PriorityClass::Normal => "normal",
+            PriorityClass::High => "high",
+        }
+    }
+}
+
+impl FromStr for PriorityClass {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "low" => Ok(PriorityClass::Low),
+            "" | "normal" => Ok(PriorityClass::Normal),
+            "high" => Ok(PriorityClass::High),
+            other => anyhow::bail!("Unknown priority class: {}", other),
+        }
+    }
+}
+
+/// Priority points gained per minute of waiting, per class
+#[derive(Debug, Clone, PartialEq)]
+pub struct AgingPolicy {
+    rates: HashMap<PriorityClass, f64>,
+}
+
+impl Default for AgingPolicy {
+    /// One point per minute: a low-priority job overtakes fresh normal work
+    /// after 100 minutes and fresh high-priority work after 200
+    fn default() -> Self {
+        Self {
+            rates: PriorityClass::ALL.into_iter().map(|class| (class, 1.0)).collect(),
+        }
+    }
+}
+
+impl AgingPolicy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Disable aging: strict priority, FIFO within a class
+    pub fn none() -> Self {
+        Self {
+            rates: PriorityClass::ALL.into_iter().map(|class| (class, 0.0)).collect(),
+        }
+    }
+
+    pub fn with_rate(mut self, class: PriorityClass, points_per_minute: f64) -> Self {
+        self.rates.insert(class, points_per_minute.max(0.0));
+        self
+    }
+
+    pub fn rate(&self, class: PriorityClass) -> f64 {
+        self.rates.get(&class).copied().unwrap_or(0.0)
+    }
+
+    /// Parse `class=rate` pairs, e.g. `low=4,normal=1,high=0`; unnamed
+    /// classes keep the default rate
+    pub fn parse(spec: &str) -> Result<Self> {
+        let mut policy = Self::default();
+        for pair in spec.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+            let (class, rate) = pair.split_once('=')
+                .ok_or_else(|| anyhow::anyhow!("Expected class=rate, got {}", pair))?;
+            let rate: f64 = rate.trim().parse()
+                .map_err(|e| anyhow::anyhow!("Invalid aging rate {}: {}", rate, e))?;
+            policy = policy.with_rate(class.parse()?, rate);
+        }
+        Ok(policy)
+    }
+
+    /// Base priority plus aging for a job that has waited `waited_secs`
+    pub fn effective_priority(&self, class: PriorityClass, waited_secs: f64) -> f64 {
+        class.base() + self.rate(class) * waited_secs.max(0.0) / 60.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_waiting_low_priority_overtakes_fresh_high_priority() {
+        let policy = AgingPolicy::parse("low=4, high=0").unwrap();
+        assert_eq!(policy.rate(PriorityClass::Normal), 1.0);
+
+        let fresh_high = policy.effective_priority(PriorityClass::High, 0.0);
+        assert!(policy.effective_priority(PriorityClass::Low, 49.0 * 60.0) < fresh_high);
+        assert!(policy.effective_priority(PriorityClass::Low, 51.0 * 60.0) > fresh_high);
+
+        // Without aging, class order is strict regardless of wait
+        let strict = AgingPolicy::none();
+        assert!(strict.effective_priority(PriorityClass::Low, 1e9) < strict.effective_priority(PriorityClass::Normal, 0.0));
+        assert!(AgingPolicy::parse("urgent=1").is_err());
+    }
+}
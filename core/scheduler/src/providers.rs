@@ -0,0 +1,384 @@
+//! Node-provider marketplace accounting
+//!
+//! Nodes belong to providers, who set the hourly price of their nodes and
+//! are credited for the compute those nodes deliver. Every finished run is
+//! written to the ledger as an entry charging the job's tenant and crediting
+//! the node's provider, less the platform commission. Failed runs are not
+//! credited. Settlement pays out a provider's unsettled entries as one
+//! batch.
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use crate::error::SchedulerError;
+use crate::timeseries::TENANT_SPEND_USD;
+use crate::{EconomicScheduler, JobStatus};
+
+/// Compute delivered by one node for one job
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LedgerEntry {
+    pub job_id: String,
+    /// Tenant charged for the run
+    pub tenant: String,
+    /// Provider credited (empty: operator-owned node)
+    pub provider_id: String,
+    pub node_id: String,
+    pub hours: f64,
+    /// Node price times hours
+    pub charged_usd: f64,
+    /// Platform share of the charge
+    pub commission_usd: f64,
+    /// Provider share of the charge
+    pub credited_usd: f64,
+    pub recorded_at: i64,
+    /// Settlement that paid this entry out, if any
+    pub settlement_id: Option<String>,
+}
+
+/// Provider earnings over a period
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ProviderEarnings {
+    pub provider_id: String,
+    pub jobs: u32,
+    pub compute_hours: f64,
+    pub charged_usd: f64,
+    pub commission_usd: f64,
+    pub credited_usd: f64,
+    pub settled_usd: f64,
+    pub unsettled_usd: f64,
+}
+
+/// One payout to a provider
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Settlement {
+    pub settlement_id: String,
+    pub provider_id: String,
+    pub amount_usd: f64,
+    pub entries: u32,
+    pub settled_at: i64,
+}
+
+#[derive(Debug, Clone)]
+struct RunStart {
+    node_id: String,
+    provider_id: String,
+    tenant: String,
+    cost_per_hour: f64,
+    started_at: i64,
+}
+
+#[derive(Debug, Default)]
+struct LedgerState {
+    /// Nodes running each job, priced at start
+    running: HashMap<String, Vec<RunStart>>,
+    entries: Vec<LedgerEntry>,
+    settlements: Vec<Settlement>,
+}
+
+/// Provider credits and settlements shared by scheduler clones
+#[derive(Debug, Clone, Default)]
+pub struct ProviderLedger {
+    /// Platform share of every charge (0-1)
+    commission_rate: f64,
+    state: Arc<Mutex<LedgerState>>,
+}
+
+impl ProviderLedger {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn job_started(&self, job_id: &str, starts: Vec<RunStart>) {
+        if let Ok(mut state) = self.state.lock() {
+            state.running.insert(job_id.to_string(), starts);
+        }
+    }
+
+    /// Turn a job's runs into ledger entries and return them; `delivered`
+    /// is false for failed runs, which are dropped uncredited
+    fn job_finished(&self, job_id: &str, delivered: bool, now: i64) -> Vec<LedgerEntry> {
+        let Ok(mut state) = self.state.lock() else {
+            return Vec::new();
+        };
+        let Some(starts) = state.running.remove(job_id) else {
+            return Vec::new();
+        };
+        if !delivered {
+            return Vec::new();
+        }
+
+        let first = state.entries.len();
+        for start in starts {
+            let hours = (now - start.started_at).max(0) as f64 / 3600.0;
+            let charged_usd = hours * start.cost_per_hour;
+            let commission_usd = charged_usd * self.commission_rate;
+            state.entries.push(LedgerEntry {
+                job_id: job_id.to_string(),
+                tenant: start.tenant,
+                provider_id: start.provider_id,
+                node_id: start.node_id,
+                hours,
+                charged_usd,
+                commission_usd,
+                credited_usd: charged_usd - commission_usd,
+                recorded_at: now,
+                settlement_id: None,
+            });
+        }
+        state.entries[first..].to_vec()
+    }
+
+    /// Ledger entries of `provider_id`, oldest first
+    pub fn entries(&self, provider_id: &str) -> Vec<LedgerEntry> {
+        self.state.lock()
+            .map(|state| state.entries.iter().filter(|e| e.provider_id == provider_id).cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// Earnings of `provider_id` from entries recorded in `[since, until)`;
+    /// 0 leaves a bound open
+    pub fn earnings(&self, provider_id: &str, since: i64, until: i64) -> ProviderEarnings {
+        let mut earnings = ProviderEarnings {
+            provider_id: provider_id.to_string(),
+            ..Default::default()
+        };
+        let Ok(state) = self.state.lock() else {
+            return earnings;
+        };
+
+        let in_period = |at: i64| (since == 0 || at >= since) && (until == 0 || at < until);
+        for entry in state.entries.iter().filter(|e| e.provider_id == provider_id && in_period(e.recorded_at)) {
+            earnings.jobs += 1;
+            earnings.compute_hours += entry.hours;
+            earnings.charged_usd += entry.charged_usd;
+            earnings.commission_usd += entry.commission_usd;
+            earnings.credited_usd += entry.credited_usd;
+            if entry.settlement_id.is_some() {
+                earnings.settled_usd += entry.credited_usd;
+            } else {
+                earnings.unsettled_usd += entry.credited_usd;
+            }
+        }
+        earnings
+    }
+
+    /// Pay out every unsettled entry of `provider_id`; None if there is
+    /// nothing to settle
+    pub fn settle(&self, provider_id: &str, now: i64) -> Result<Option<Settlement>> {
+        let mut state = self.state.lock()
+            .map_err(|e| anyhow::anyhow!("Lock poisoned: {}", e))?;
+        let settlement_id = format!("{}-{}", provider_id, state.settlements.len() + 1);
+
+        let mut amount_usd = 0.0;
+        let mut entries = 0;
+        for entry in state.entries.iter_mut()
+            .filter(|e| e.provider_id == provider_id && e.settlement_id.is_none())
+        {
+            entry.settlement_id = Some(settlement_id.clone());
+            amount_usd += entry.credited_usd;
+            entries += 1;
+        }
+        if entries == 0 {
+            return Ok(None);
+        }
+
+        let settlement = Settlement {
+            settlement_id,
+            provider_id: provider_id.to_string(),
+            amount_usd,
+            entries,
+            settled_at: now,
+        };
+        tracing::info!(
+            "Settlement {}: ${:.4} to provider {} for {} entries",
+            settlement.settlement_id, amount_usd, provider_id, entries
+        );
+        state.settlements.push(settlement.clone());
+        Ok(Some(settlement))
+    }
+
+    /// Past settlements of `provider_id`, oldest first
+    pub fn settlements(&self, provider_id: &str) -> Vec<Settlement> {
+        self.state.lock()
+            .map(|state| state.settlements.iter().filter(|s| s.provider_id == provider_id).cloned().collect())
+            .unwrap_or_default()
+    }
+}
+
+impl EconomicScheduler {
+    /// Platform share of every provider charge (0-1)
+    pub fn set_provider_commission(&mut self, rate: f64) {
+        self.providers.commission_rate = rate.clamp(0.0, 1.0);
+    }
+
+    pub fn provider_ledger(&self) -> &ProviderLedger {
+        &self.providers
+    }
+
+    /// Set the hourly price of a node; only its provider may change it,
+    /// within the pricing policy (see `node_pricing`)
+    pub fn set_node_price(&self, provider_id: &str, node_id: &str, cost_per_hour: f64) -> Result<()> {
+        if !cost_per_hour.is_finite() || cost_per_hour < 0.0 {
+            return Err(SchedulerError::invalid_spec(format!("invalid price {} for node {}", cost_per_hour, node_id)).into());
+        }
+        let mut nodes = self.available_nodes.lock()
+            .map_err(|e| anyhow::anyhow!("Lock poisoned: {}", e))?;
+        let mut node = nodes.get(node_id)
+            .cloned()
+            .ok_or_else(|| SchedulerError::not_found("Node", node_id))?;
+        if node.provider != provider_id {
+            return Err(SchedulerError::Forbidden { action: format!("price node {} as provider {}", node_id, provider_id) }.into());
+        }
+        self.node_pricing.admit(node_id, cost_per_hour, crate::unix_now())?;
+
+        tracing::info!("Provider {} priced node {} at ${:.4}/h (was ${:.4}/h)", provider_id, node_id, cost_per_hour, node.cost_per_hour);
+        node.cost_per_hour = cost_per_hour;
+        nodes.insert(node);
+        self.price_holds.wake();
+        self.prices_changed();
+        Ok(())
+    }
+
+    /// Feed a job status change into the provider ledger and settle the
+    /// tenant's credit (see `credits`)
+    pub(crate) fn observe_for_settlement(&self, job_id: &str, status: &JobStatus, now: i64) {
+        match status {
+            JobStatus::Running => {
+                let Some(state) = self.get_job_state(job_id) else {
+                    return;
+                };
+                let tenant = self.placed_jobs.lock()
+                    .ok()
+                    .and_then(|placed| placed.get(job_id).map(|spec| spec.tenant.clone()))
+                    .unwrap_or_default();
+                let node_ids = if state.gang_nodes.is_empty() {
+                    state.assigned_node.into_iter().collect()
+                } else {
+                    state.gang_nodes
+                };
+                let Ok(nodes) = self.available_nodes.lock() else {
+                    return;
+                };
+                let starts = node_ids.iter()
+                    .filter_map(|node_id| nodes.get(node_id))
+                    .map(|node| RunStart {
+                        node_id: node.id.clone(),
+                        provider_id: node.provider.clone(),
+                        tenant: tenant.clone(),
+                        cost_per_hour: node.cost_per_hour,
+                        started_at: now,
+                    })
+                    .collect();
+                drop(nodes);
+                self.providers.job_started(job_id, starts);
+            }
+            JobStatus::Completed | JobStatus::Cancelled => {
+                let entries = self.providers.job_finished(job_id, true, now);
+                for entry in &entries {
+                    self.metrics_history.record_counter(TENANT_SPEND_USD, &entry.tenant, entry.charged_usd, now);
+                }
+                self.settle_credit(job_id, &entries);
+            }
+            JobStatus::Failed => {
+                self.providers.job_finished(job_id, false, now);
+                self.settle_credit(job_id, &[]);
+            }
+            JobStatus::Pending | JobStatus::Scheduled => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{JobSpec, NodeInfo, SlaConstraints};
+
+    #[tokio::test]
+    async fn test_completed_run_credits_provider_less_commission() {
+        let mut scheduler = EconomicScheduler::new();
+        scheduler.set_provider_commission(0.1);
+        scheduler.register_node(NodeInfo {
+            id: "node-1".to_string(),
+            available_cpu: 16,
+            available_memory_gb: 64,
+            location: "vps-1".to_string(),
+            cost_per_hour: 0.2,
+            provider: "acme-hosting".to_string(),
+            ..Default::default()
+        }).unwrap();
+        assert!(scheduler.set_node_price("someone-else", "node-1", 0.1).is_err());
+        scheduler.set_node_price("acme-hosting", "node-1", 0.5).unwrap();
+
+        let job = JobSpec {
+            id: "job-1".to_string(),
+            tenant: "lab".to_string(),
+            sla: SlaConstraints { max_latency_ms: 1_000, ..Default::default() },
+            ..Default::default()
+        };
+        scheduler.schedule(job).await.unwrap();
+        scheduler.observe_for_settlement("job-1", &JobStatus::Running, 0);
+        scheduler.observe_for_settlement("job-1", &JobStatus::Completed, 7200);
+
+        let ledger = scheduler.provider_ledger();
+        let entry = &ledger.entries("acme-hosting")[0];
+        assert_eq!(entry.tenant, "lab");
+        assert!((entry.charged_usd - 1.0).abs() < 1e-9);
+        assert!((entry.credited_usd - 0.9).abs() < 1e-9);
+
+        let settlement = ledger.settle("acme-hosting", 7300).unwrap().unwrap();
+        assert!((settlement.amount_usd - 0.9).abs() < 1e-9);
+        assert!(ledger.settle("acme-hosting", 7400).unwrap().is_none());
+        let earnings = ledger.earnings("acme-hosting", 0, 0);
+        assert_eq!(earnings.jobs, 1);
+        assert!((earnings.settled_usd - 0.9).abs() < 1e-9);
+        assert_eq!(earnings.unsettled_usd, 0.0);
+    }
+
+    #[tokio::test]
+    async fn test_only_operators_settle_providers() {
+        use crate::grpc::proto::{scheduler_service_server::SchedulerService, SettleProviderRequest};
+        use crate::rbac::AccessControl;
+
+        let mut scheduler = EconomicScheduler::new();
+        scheduler.set_access_control(AccessControl::parse("alice-token=user, ops-token=operator").unwrap());
+        let request = |token: &str| {
+            let mut request = tonic::Request::new(SettleProviderRequest { provider_id: "acme-hosting".to_string() });
+            request.metadata_mut().insert("authorization", format!("Bearer {}", token).parse().unwrap());
+            request
+        };
+
+        let status = SchedulerService::settle_provider(&scheduler, request("alice-token")).await.unwrap_err();
+        assert_eq!(status.code(), tonic::Code::PermissionDenied);
+        SchedulerService::settle_provider(&scheduler, request("ops-token")).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_node_prices_need_the_provider_token() {
+        use crate::grpc::proto::{scheduler_service_server::SchedulerService, SetNodePriceRequest};
+        use crate::rbac::AccessControl;
+
+        let mut scheduler = EconomicScheduler::new();
+        scheduler.set_access_control(AccessControl::default().with_providers("acme-token=acme, rival-token=rival").unwrap());
+        scheduler.register_node(NodeInfo { provider: "acme".to_string(), ..crate::test_support::node("node-1", 0.2) }).unwrap();
+        let request = |token: &str, provider_id: &str| {
+            let mut request = tonic::Request::new(SetNodePriceRequest {
+                provider_id: provider_id.to_string(),
+                node_id: "node-1".to_string(),
+                cost_per_hour: 0.5,
+            });
+            request.metadata_mut().insert("authorization", format!("Bearer {}", token).parse().unwrap());
+            request
+        };
+
+        // Claiming another provider's id does not help
+        for (token, provider_id) in [("rival-token", "acme"), ("rival-token", "rival"), ("unknown", "acme")] {
+            let status = SchedulerService::set_node_price(&scheduler, request(token, provider_id)).await.unwrap_err();
+            assert_eq!(status.code(), tonic::Code::PermissionDenied);
+        }
+        assert_eq!(scheduler.cluster_status()[0].cost_per_hour, 0.2);
+        SchedulerService::set_node_price(&scheduler, request("acme-token", "acme")).await.unwrap();
+        assert_eq!(scheduler.cluster_status()[0].cost_per_hour, 0.5);
+    }
+}
@@ -0,0 +1,451 @@
+//! Pending-job queue and scheduling locks
+//!
+//! In queued mode SubmitJob only enqueues and a dispatcher claims jobs and
+//! places them. A claimed job is invisible to other dispatchers for a
+//! visibility timeout and reappears if the claimer dies before
+//! acknowledging it. Jobs are claimed highest effective priority first
+//! (see `priority::AgingPolicy`).
+//!
+//! Queued mode is single-replica: job states, node registrations and
+//! capacity live in the memory of the scheduler that places, not in the
+//! queue, so only one replica may run a dispatcher. The Redis backend keeps
+//! the queue across restarts and lets a standby take over the same queue;
+//! the placement lock only keeps a second dispatcher started by mistake
+//! from placing at the same time; it does not make several active
+//! dispatchers safe.
+//!
+//! Backends: in-memory and Redis (`redis` cargo feature).
+
+use anyhow::Result;
+use async_trait::async_trait;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::priority::AgingPolicy;
+use crate::{EconomicScheduler, JobSpec};
+
+#[cfg(feature = "redis")]
+pub mod redis_backend;
+
+/// Lock serializing placement decisions between dispatchers
+pub const PLACEMENT_LOCK: &str = "placement";
+
+/// Job claimed from the queue, identified by its receipt until acknowledged
+#[derive(Debug, Clone)]
+pub struct ClaimedJob {
+    pub job: JobSpec,
+    pub receipt: String,
+}
+
+/// Shared queue of jobs waiting for placement
+#[async_trait]
+pub trait JobQueue: Send + Sync {
+    /// Append a job to the queue
+    async fn push(&self, job: &JobSpec) -> Result<()>;
+
+    /// Claim the job with the highest effective priority; it is redelivered
+    /// unless acked within `visibility_timeout`
+    async fn claim(&self, visibility_timeout: Duration) -> Result<Option<ClaimedJob>>;
+
+    /// Remove a claimed job for good
+    async fn ack(&self, claimed: &ClaimedJob) -> Result<()>;
+
+    /// Hand a claimed job back, redelivering it after `delay`; it keeps its
+    /// place in the aging order
+    async fn defer(&self, claimed: &ClaimedJob, delay: Duration) -> Result<()>;
+
+    /// Number of jobs waiting (not counting in-flight claims)
+    async fn len(&self) -> Result<usize>;
+
+    /// Whether no job is waiting
+    async fn is_empty(&self) -> Result<bool> {
+        Ok(self.len().await? == 0)
+    }
+
+    /// Every job still in the queue, waiting or claimed and not yet acked
+    async fn jobs(&self) -> Result<Vec<JobSpec>>;
+}
+
+/// Expiring mutual-exclusion locks
+#[async_trait]
+pub trait LockManager: Send + Sync {
+    /// Try to take `name` for `ttl`; returns the owner token on success
+    async fn try_lock(&self, name: &str, ttl: Duration) -> Result<Option<String>>;
+
+    /// Release `name` if still held with `token`
+    async fn unlock(&self, name: &str, token: &str) -> Result<()>;
+}
+
+/// Unique token for receipts and lock ownership
+pub(crate) fn unique_token() -> String {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let nanos = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or(0);
+    format!("{}-{}-{}", std::process::id(), nanos, COUNTER.fetch_add(1, Ordering::Relaxed))
+}
+
+/// Queued job and when it was first enqueued (kept across redeliveries)
+#[derive(Debug, Clone)]
+struct QueuedJob {
+    job: JobSpec,
+    enqueued: Instant,
+}
+
+#[derive(Debug, Default)]
+struct MemoryQueueState {
+    pending: Vec<QueuedJob>,
+    in_flight: HashMap<String, (QueuedJob, Instant)>,
+}
+
+/// In-process queue for single-replica deployments and tests
+#[derive(Debug, Default)]
+pub struct MemoryQueue {
+    state: Mutex<MemoryQueueState>,
+    aging: AgingPolicy,
+}
+
+impl MemoryQueue {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_aging(aging: AgingPolicy) -> Self {
+        Self {
+            aging,
+            ..Self::default()
+        }
+    }
+}
+
+#[async_trait]
+impl JobQueue for MemoryQueue {
+    async fn push(&self, job: &JobSpec) -> Result<()> {
+        let mut state = self.state.lock()
+            .map_err(|e| anyhow::anyhow!("Lock poisoned: {}", e))?;
+        state.pending.push(QueuedJob { job: job.clone(), enqueued: Instant::now() });
+        Ok(())
+    }
+
+    async fn claim(&self, visibility_timeout: Duration) -> Result<Option<ClaimedJob>> {
+        let mut state = self.state.lock()
+            .map_err(|e| anyhow::anyhow!("Lock poisoned: {}", e))?;
+
+        // Redeliver claims whose visibility timeout expired; they keep their
+        // original enqueue time and so their accumulated aging
+        let now = Instant::now();
+        let expired: Vec<String> = state.in_flight.iter()
+            .filter(|(_, (_, deadline))| *deadline <= now)
+            .map(|(receipt, _)| receipt.clone())
+            .collect();
+        for receipt in expired {
+            if let Some((queued, _)) = state.in_flight.remove(&receipt) {
+                state.pending.push(queued);
+            }
+        }
+
+        // Highest effective priority; the longest-waiting job breaks ties
+        let priority = |q: &QueuedJob| {
+            self.aging.effective_priority(q.job.priority, now.duration_since(q.enqueued).as_secs_f64())
+        };
+        let best = state.pending.iter()
+            .enumerate()
+            .max_by(|(_, a), (_, b)| {
+                priority(a).total_cmp(&priority(b)).then_with(|| b.enqueued.cmp(&a.enqueued))
+            })
+            .map(|(i, _)| i);
+        let Some(index) = best else {
+            return Ok(None);
+        };
+        let queued = state.pending.remove(index);
+
+        let receipt = unique_token();
+        let job = queued.job.clone();
+        state.in_flight.insert(receipt.clone(), (queued, now + visibility_timeout));
+        Ok(Some(ClaimedJob { job, receipt }))
+    }
+
+    async fn ack(&self, claimed: &ClaimedJob) -> Result<()> {
+        let mut state = self.state.lock()
+            .map_err(|e| anyhow::anyhow!("Lock poisoned: {}", e))?;
+        state.in_flight.remove(&claimed.receipt);
+        Ok(())
+    }
+
+    async fn defer(&self, claimed: &ClaimedJob, delay: Duration) -> Result<()> {
+        let mut state = self.state.lock()
+            .map_err(|e| anyhow::anyhow!("Lock poisoned: {}", e))?;
+        if let Some((_, deadline)) = state.in_flight.get_mut(&claimed.receipt) {
+            *deadline = Instant::now() + delay;
+        }
+        Ok(())
+    }
+
+    async fn len(&self) -> Result<usize> {
+        let state = self.state.lock()
+            .map_err(|e| anyhow::anyhow!("Lock poisoned: {}", e))?;
+        Ok(state.pending.len())
+    }
+
+    async fn jobs(&self) -> Result<Vec<JobSpec>> {
+        let state = self.state.lock()
+            .map_err(|e| anyhow::anyhow!("Lock poisoned: {}", e))?;
+        Ok(state.pending.iter()
+            .chain(state.in_flight.values().map(|(queued, _)| queued))
+            .map(|queued| queued.job.clone())
+            .collect())
+    }
+}
+
+/// In-process locks for single-replica deployments and tests
+#[derive(Debug, Default)]
+pub struct MemoryLocks {
+    held: Mutex<HashMap<String, (String, Instant)>>,
+}
+
+impl MemoryLocks {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl LockManager for MemoryLocks {
+    async fn try_lock(&self, name: &str, ttl: Duration) -> Result<Option<String>> {
+        let mut held = self.held.lock()
+            .map_err(|e| anyhow::anyhow!("Lock poisoned: {}", e))?;
+
+        let now = Instant::now();
+        if let Some((_, expires)) = held.get(name) {
+            if *expires > now {
+                return Ok(None);
+            }
+        }
+
+        let token = unique_token();
+        held.insert(name.to_string(), (token.clone(), now + ttl));
+        Ok(Some(token))
+    }
+
+    async fn unlock(&self, name: &str, token: &str) -> Result<()> {
+        let mut held = self.held.lock()
+            .map_err(|e| anyhow::anyhow!("Lock poisoned: {}", e))?;
+        if held.get(name).map(|(owner, _)| owner == token).unwrap_or(false) {
+            held.remove(name);
+        }
+        Ok(())
+    }
+}
+
+/// Connect to the queue named by `url` (`memory://` or `redis://...`)
+pub async fn connect_queue(url: &str, aging: AgingPolicy) -> Result<(Arc<dyn JobQueue>, Arc<dyn LockManager>)> {
+    if url.starts_with("memory://") {
+        return Ok((Arc::new(MemoryQueue::with_aging(aging)), Arc::new(MemoryLocks::new())));
+    }
+
+    if url.starts_with("redis://") || url.starts_with("rediss://") {
+        #[cfg(feature = "redis")]
+        {
+            let prefix = std::env::var("TGP_QUEUE_PREFIX").unwrap_or_else(|_| "tgp".to_string());
+            let backend = redis_backend::RedisBackend::connect(url, &prefix, aging).await?;
+            return Ok((Arc::new(backend.clone()), Arc::new(backend)));
+        }
+        #[cfg(not(feature = "redis"))]
+        anyhow::bail!("Redis support not compiled in (enable the `redis` feature)");
+    }
+
+    anyhow::bail!("Unsupported queue URL: {}", url)
+}
+
+/// Dispatcher tuning
+#[derive(Debug, Clone)]
+pub struct DispatcherConfig {
+    /// How long a claimed job stays invisible to other replicas
+    pub visibility_timeout: Duration,
+    /// Lifetime of the placement lock (guards against crashed holders)
+    pub lock_ttl: Duration,
+    /// Sleep between polls when the queue is empty
+    pub idle_poll: Duration,
+    /// Delay before retrying a job held back by a concurrency limit
+    pub limit_backoff: Duration,
+}
+
+impl Default for DispatcherConfig {
+    fn default() -> Self {
+        Self {
+            visibility_timeout: Duration::from_secs(30),
+            lock_ttl: Duration::from_secs(10),
+            idle_poll: Duration::from_millis(200),
+            limit_backoff: Duration::from_secs(2),
+        }
+    }
+}
+
+/// Claim queued jobs and place them until the process exits
+pub async fn run_dispatcher(
+    scheduler: EconomicScheduler,
+    queue: Arc<dyn JobQueue>,
+    locks: Arc<dyn LockManager>,
+    config: DispatcherConfig,
+) {
+    loop {
+        // Draining: leave the rest of the queue to the other replicas
+        let Some(_in_flight) = scheduler.begin_placement() else {
+            tracing::info!("Queue dispatcher stopped for shutdown");
+            return;
+        };
+        let claimed = match queue.claim(config.visibility_timeout).await {
+            Ok(Some(claimed)) => claimed,
+            Ok(None) => {
+                tokio::time::sleep(config.idle_poll).await;
+                continue;
+            }
+            Err(e) => {
+                tracing::warn!("Failed to claim queued job: {}", e);
+                tokio::time::sleep(config.idle_poll).await;
+                continue;
+            }
+        };
+
+        // Cancelled while queued: drop it
+        if scheduler.is_cancelled(&claimed.job.id) {
+            if let Err(e) = queue.ack(&claimed).await {
+                tracing::warn!("Failed to ack cancelled job {}: {}", claimed.job.id, e);
+            }
+            continue;
+        }
+
+        // Give up on the lock well before the claim becomes visible again,
+        // otherwise another replica could place the same job
+        let give_up = Instant::now() + config.visibility_timeout / 2;
+        let token = loop {
+            match locks.try_lock(PLACEMENT_LOCK, config.lock_ttl).await {
+                Ok(Some(token)) => break Some(token),
+                Ok(None) if Instant::now() < give_up => {
+                    tokio::time::sleep(Duration::from_millis(50)).await;
+                }
+                Ok(None) => break None,
+                Err(e) => {
+                    tracing::warn!("Placement lock unavailable: {}", e);
+                    break None;
+                }
+            }
+        };
+
+        // Without the lock the claim simply expires and is retried
+        let Some(token) = token else {
+            continue;
+        };
+
+        let job_id = claimed.job.id.clone();
+        let blocked = match scheduler.concurrency_blocked(&claimed.job) {
+            Ok(blocked) => blocked,
+            Err(e) => Some(e.to_string()),
+        };
+        let result = match &blocked {
+            Some(_) => None,
+            None => {
+                let started = Instant::now();
+                let result = scheduler.place(claimed.job.clone()).await;
+                scheduler.load_shedder().record_placement(started.elapsed());
+                Some(result)
+            }
+        };
+
+        if let Err(e) = locks.unlock(PLACEMENT_LOCK, &token).await {
+            tracing::warn!("Failed to release placement lock: {}", e);
+        }
+
+        // Over its limit: leave the job queued and look at the next one
+        let Some(result) = result else {
+            tracing::debug!("Deferring queued job {}: {}", job_id, blocked.unwrap_or_default());
+            if let Err(e) = queue.defer(&claimed, config.limit_backoff).await {
+                tracing::warn!("Failed to defer queued job {}: {}", job_id, e);
+            }
+            continue;
+        };
+
+        match result {
+            Ok(placement) => tracing::info!("Dispatched queued job {} to {}", job_id, placement.node_id),
+            Err(e) => tracing::warn!("Queued job {} could not be placed: {}", job_id, e),
+        }
+
+        if let Err(e) = queue.ack(&claimed).await {
+            tracing::warn!("Failed to ack queued job {}: {}", job_id, e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn job(id: &str) -> JobSpec {
+        JobSpec {
+            id: id.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_unacked_claim_is_redelivered_after_timeout() {
+        let queue = MemoryQueue::new();
+        queue.push(&job("a")).await.unwrap();
+        queue.push(&job("b")).await.unwrap();
+
+        let first = queue.claim(Duration::from_millis(0)).await.unwrap().unwrap();
+        assert_eq!(first.job.id, "a");
+
+        // "a" expired immediately and goes back to the front
+        let again = queue.claim(Duration::from_secs(60)).await.unwrap().unwrap();
+        assert_eq!(again.job.id, "a");
+        queue.ack(&again).await.unwrap();
+
+        let next = queue.claim(Duration::from_secs(60)).await.unwrap().unwrap();
+        assert_eq!(next.job.id, "b");
+        assert!(queue.is_empty().await.unwrap());
+        assert!(queue.claim(Duration::from_secs(60)).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_claim_order_follows_aged_priority() {
+        use crate::priority::PriorityClass;
+
+        let queue = MemoryQueue::with_aging(AgingPolicy::none().with_rate(PriorityClass::Low, 60_000.0));
+        let mut low = job("low");
+        low.priority = PriorityClass::Low;
+        queue.push(&low).await.unwrap();
+
+        let mut high = job("high");
+        high.priority = PriorityClass::High;
+        queue.push(&high).await.unwrap();
+        queue.push(&job("normal")).await.unwrap();
+
+        // Fresh: strict class order
+        let first = queue.claim(Duration::from_secs(60)).await.unwrap().unwrap();
+        assert_eq!(first.job.id, "high");
+
+        // 1000 points per second: after 250ms "low" outranks fresh normal work
+        tokio::time::sleep(Duration::from_millis(250)).await;
+        let second = queue.claim(Duration::from_secs(60)).await.unwrap().unwrap();
+        assert_eq!(second.job.id, "low");
+        let third = queue.claim(Duration::from_secs(60)).await.unwrap().unwrap();
+        assert_eq!(third.job.id, "normal");
+    }
+
+    #[tokio::test]
+    async fn test_lock_is_exclusive_until_released() {
+        let locks = MemoryLocks::new();
+        let token = locks.try_lock(PLACEMENT_LOCK, Duration::from_secs(10)).await.unwrap().unwrap();
+        assert!(locks.try_lock(PLACEMENT_LOCK, Duration::from_secs(10)).await.unwrap().is_none());
+
+        locks.unlock(PLACEMENT_LOCK, "someone-else").await.unwrap();
+        assert!(locks.try_lock(PLACEMENT_LOCK, Duration::from_secs(10)).await.unwrap().is_none());
+
+        locks.unlock(PLACEMENT_LOCK, &token).await.unwrap();
+        assert!(locks.try_lock(PLACEMENT_LOCK, Duration::from_secs(10)).await.unwrap().is_some());
+    }
+}
@@ -0,0 +1,259 @@
+//! Redis-backed job queue and scheduling locks
+//!
+//! Keys (under a configurable prefix, default `tgp`):
+//! - `<prefix>:queue:pending:<class>` sorted set per priority class of
+//!   serialized queued jobs scored by enqueue time (ms)
+//! - `<prefix>:queue:inflight` sorted set of receipts scored by visibility deadline
+//! - `<prefix>:queue:claims`   hash of receipt -> serialized queued job
+//! - `<prefix>:lock:<name>`    lock owner token with a PX expiry
+//!
+//! Claiming and redelivery run as Lua scripts so they are atomic across
+//! scheduler replicas.
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use redis::aio::ConnectionManager;
+use redis::Script;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+use super::{unique_token, ClaimedJob, JobQueue, LockManager};
+use crate::priority::{AgingPolicy, PriorityClass};
+use crate::JobSpec;
+
+/// Move expired claims back to their class with their original enqueue
+/// time, then claim the class head with the highest effective priority
+///
+/// KEYS: inflight, claims, then one pending key per class.
+/// ARGV: now, deadline, receipt, then (class, base, rate per minute) per class.
+const CLAIM_SCRIPT: &str = r#"
+local classes = {}
+for i = 3, #KEYS do
+    local a = 4 + (i - 3) * 3
+    classes[ARGV[a]] = {key = KEYS[i], base = tonumber(ARGV[a + 1]), rate = tonumber(ARGV[a + 2])}
+end
+local now = tonumber(ARGV[1])
+local expired = redis.call('ZRANGEBYSCORE', KEYS[1], '-inf', ARGV[1])
+for _, receipt in ipairs(expired) do
+    local payload = redis.call('HGET', KEYS[2], receipt)
+    if payload then
+        local queued = cjson.decode(payload)
+        local class = classes[queued.job.priority] or classes['normal']
+        redis.call('ZADD', class.key, queued.enqueued_at_ms, payload)
+    end
+    redis.call('ZREM', KEYS[1], receipt)
+    redis.call('HDEL', KEYS[2], receipt)
+end
+local best, best_key, best_score, best_enqueued
+for _, class in pairs(classes) do
+    local head = redis.call('ZRANGE', class.key, 0, 0, 'WITHSCORES')
+    if head[1] then
+        local enqueued = tonumber(head[2])
+        local score = class.base + class.rate * (now - enqueued) / 60000
+        if not best or score > best_score or (score == best_score and enqueued < best_enqueued) then
+            best, best_key, best_score, best_enqueued = head[1], class.key, score, enqueued
+        end
+    end
+end
+if not best then
+    return false
+end
+redis.call('ZREM', best_key, best)
+redis.call('HSET', KEYS[2], ARGV[3], best)
+redis.call('ZADD', KEYS[1], ARGV[2], ARGV[3])
+return best
+"#;
+
+/// Queue entry; the enqueue time survives redelivery so aging is kept
+#[derive(Serialize, Deserialize)]
+struct QueuedJob {
+    enqueued_at_ms: u64,
+    job: JobSpec,
+}
+
+/// Delete a lock only if it is still owned by the caller
+const UNLOCK_SCRIPT: &str = r#"
+if redis.call('GET', KEYS[1]) == ARGV[1] then
+    return redis.call('DEL', KEYS[1])
+end
+return 0
+"#;
+
+fn now_ms() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+/// Queue and lock manager sharing one Redis connection manager
+#[derive(Clone)]
+pub struct RedisBackend {
+    conn: ConnectionManager,
+    prefix: String,
+    aging: AgingPolicy,
+}
+
+impl RedisBackend {
+    pub async fn connect(url: &str, prefix: &str, aging: AgingPolicy) -> Result<Self> {
+        let client = redis::Client::open(url).context("Invalid Redis URL")?;
+        let conn = ConnectionManager::new(client)
+            .await
+            .context("Failed to connect to Redis")?;
+
+        tracing::info!("Connected to Redis queue at {}", url);
+        Ok(Self {
+            conn,
+            prefix: prefix.to_string(),
+            aging,
+        })
+    }
+
+    fn key(&self, suffix: &str) -> String {
+        format!("{}:{}", self.prefix, suffix)
+    }
+
+    fn pending_key(&self, class: PriorityClass) -> String {
+        self.key(&format!("queue:pending:{}", class.as_str()))
+    }
+}
+
+#[async_trait]
+impl JobQueue for RedisBackend {
+    async fn push(&self, job: &JobSpec) -> Result<()> {
+        let enqueued_at_ms = now_ms();
+        let payload = serde_json::to_string(&QueuedJob { enqueued_at_ms, job: job.clone() })?;
+        let mut conn = self.conn.clone();
+        redis::cmd("ZADD")
+            .arg(self.pending_key(job.priority))
+            .arg(enqueued_at_ms)
+            .arg(payload)
+            .query_async::<_, ()>(&mut conn)
+            .await
+            .context("Failed to enqueue job")?;
+        Ok(())
+    }
+
+    async fn claim(&self, visibility_timeout: Duration) -> Result<Option<ClaimedJob>> {
+        let now = now_ms();
+        let receipt = unique_token();
+        let mut conn = self.conn.clone();
+
+        let script = Script::new(CLAIM_SCRIPT);
+        let mut invocation = script.key(self.key("queue:inflight"));
+        invocation
+            .key(self.key("queue:claims"))
+            .arg(now)
+            .arg(now + visibility_timeout.as_millis() as u64)
+            .arg(&receipt);
+        for class in PriorityClass::ALL {
+            invocation
+                .key(self.pending_key(class))
+                .arg(class.as_str())
+                .arg(class.base())
+                .arg(self.aging.rate(class));
+        }
+
+        let payload: Option<String> = invocation
+            .invoke_async::<_, Option<String>>(&mut conn)
+            .await
+            .context("Failed to claim job")?;
+
+        match payload {
+            Some(payload) => {
+                let queued: QueuedJob = serde_json::from_str(&payload).context("Corrupt queued job")?;
+                Ok(Some(ClaimedJob { job: queued.job, receipt }))
+            }
+            None => Ok(None),
+        }
+    }
+
+    async fn ack(&self, claimed: &ClaimedJob) -> Result<()> {
+        let mut conn = self.conn.clone();
+        redis::pipe()
+            .atomic()
+            .cmd("ZREM").arg(self.key("queue:inflight")).arg(&claimed.receipt).ignore()
+            .cmd("HDEL").arg(self.key("queue:claims")).arg(&claimed.receipt).ignore()
+            .query_async::<_, ()>(&mut conn)
+            .await
+            .context("Failed to ack job")?;
+        Ok(())
+    }
+
+    async fn defer(&self, claimed: &ClaimedJob, delay: Duration) -> Result<()> {
+        let mut conn = self.conn.clone();
+        redis::cmd("ZADD")
+            .arg(self.key("queue:inflight"))
+            .arg("XX")
+            .arg(now_ms() + delay.as_millis() as u64)
+            .arg(&claimed.receipt)
+            .query_async::<_, ()>(&mut conn)
+            .await
+            .context("Failed to defer job")?;
+        Ok(())
+    }
+
+    async fn len(&self) -> Result<usize> {
+        let mut conn = self.conn.clone();
+        let mut pipe = redis::pipe();
+        for class in PriorityClass::ALL {
+            pipe.cmd("ZCARD").arg(self.pending_key(class));
+        }
+        let lens: Vec<usize> = pipe
+            .query_async(&mut conn)
+            .await
+            .context("Failed to read queue length")?;
+        Ok(lens.into_iter().sum())
+    }
+
+    async fn jobs(&self) -> Result<Vec<JobSpec>> {
+        let mut conn = self.conn.clone();
+        let mut pipe = redis::pipe();
+        for class in PriorityClass::ALL {
+            pipe.cmd("ZRANGE").arg(self.pending_key(class)).arg(0).arg(-1);
+        }
+        pipe.cmd("HVALS").arg(self.key("queue:claims"));
+        let payloads: Vec<Vec<String>> = pipe
+            .query_async(&mut conn)
+            .await
+            .context("Failed to read queued jobs")?;
+        payloads.into_iter()
+            .flatten()
+            .map(|payload| {
+                let queued: QueuedJob = serde_json::from_str(&payload).context("Corrupt queued job")?;
+                Ok(queued.job)
+            })
+            .collect()
+    }
+}
+
+#[async_trait]
+impl LockManager for RedisBackend {
+    async fn try_lock(&self, name: &str, ttl: Duration) -> Result<Option<String>> {
+        let token = unique_token();
+        let mut conn = self.conn.clone();
+
+        let acquired: Option<String> = redis::cmd("SET")
+            .arg(self.key(&format!("lock:{}", name)))
+            .arg(&token)
+            .arg("NX")
+            .arg("PX")
+            .arg(ttl.as_millis() as u64)
+            .query_async(&mut conn)
+            .await
+            .context("Failed to acquire lock")?;
+
+        Ok(acquired.map(|_| token))
+    }
+
+    async fn unlock(&self, name: &str, token: &str) -> Result<()> {
+        let mut conn = self.conn.clone();
+        Script::new(UNLOCK_SCRIPT)
+            .key(self.key(&format!("lock:{}", name)))
+            .arg(token)
+            .invoke_async::<_, i64>(&mut conn)
+            .await
+            .context("Failed to release lock")?;
+        Ok(())
+    }
+}
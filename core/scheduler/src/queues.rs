@@ -0,0 +1,196 @@
+//! Named queues with per-queue policies
+//!
+//! Operators define queues such as `gpu-training`, `batch` or
+//! `interactive`, and a job names the one it is submitted to (jobs naming
+//! none go to the default queue, if one is set). Each queue has its own
+//! policy:
+//!
+//! - priority: the class its jobs are queued at, replacing the class the
+//!   submitter asked for
+//! - quota: how many of its jobs run at once, enforced by the queue
+//!   dispatcher alongside the tenant limits
+//! - node pool: the named pool (see `pools`) its jobs run in, and/or a
+//!   selector narrowing the nodes they may be placed on
+//!
+//! Admission stamps the queue, its priority and its pool onto the spec and
+//! refuses jobs naming a queue that does not exist or a pool other than
+//! the queue's.
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::error::SchedulerError;
+use crate::pools::NodeSelector;
+use crate::priority::PriorityClass;
+use crate::{EconomicScheduler, JobSpec, NodeInfo};
+
+/// Policy of one named queue
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct QueuePolicy {
+    pub name: String,
+    /// Class its jobs are queued at (None: as submitted)
+    #[serde(default)]
+    pub priority: Option<PriorityClass>,
+    /// Its jobs running at once (None: unlimited)
+    #[serde(default)]
+    pub max_concurrent_jobs: Option<u32>,
+    /// Node pool its jobs run in (None: as submitted)
+    #[serde(default)]
+    pub pool: Option<String>,
+    #[serde(default)]
+    pub nodes: NodeSelector,
+}
+
+/// Queue definitions as written in the TGP_QUEUES file
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct QueuesConfig {
+    /// Queue of jobs that name none (None: such jobs use no queue)
+    #[serde(default)]
+    pub default_queue: Option<String>,
+    pub queues: Vec<QueuePolicy>,
+}
+
+/// Named queues, shared by scheduler clones
+#[derive(Debug, Clone, Default)]
+pub struct QueuePolicies {
+    default_queue: Option<String>,
+    queues: Arc<HashMap<String, QueuePolicy>>,
+}
+
+impl QueuePolicies {
+    pub fn new(config: QueuesConfig) -> Result<Self> {
+        let mut queues = HashMap::new();
+        for queue in config.queues {
+            anyhow::ensure!(!queue.name.is_empty(), "Queue without a name");
+            let name = queue.name.clone();
+            anyhow::ensure!(queues.insert(name.clone(), queue).is_none(), "Queue {} defined twice", name);
+        }
+        if let Some(default_queue) = &config.default_queue {
+            anyhow::ensure!(queues.contains_key(default_queue), "Default queue {} is not defined", default_queue);
+        }
+        Ok(Self { default_queue: config.default_queue, queues: Arc::new(queues) })
+    }
+
+    pub fn get(&self, name: &str) -> Option<&QueuePolicy> {
+        self.queues.get(name)
+    }
+
+    /// Queues sorted by name
+    pub fn list(&self) -> Vec<&QueuePolicy> {
+        let mut queues: Vec<_> = self.queues.values().collect();
+        queues.sort_by(|a, b| a.name.cmp(&b.name));
+        queues
+    }
+
+    /// Whether a job of `queue` may be placed on `node`
+    pub(crate) fn admits_node(&self, queue: Option<&str>, node: &NodeInfo) -> bool {
+        match queue.and_then(|name| self.get(name)) {
+            Some(policy) => policy.nodes.matches(node),
+            None => true,
+        }
+    }
+}
+
+impl EconomicScheduler {
+    pub fn set_queues(&mut self, queues: QueuePolicies) {
+        self.queues = queues;
+    }
+
+    pub fn queues(&self) -> &QueuePolicies {
+        &self.queues
+    }
+
+    /// Put a submission in its queue, applying the queue's priority and pool
+    pub fn assign_queue(&self, mut job: JobSpec) -> Result<JobSpec> {
+        if job.queue.is_none() {
+            job.queue = self.queues.default_queue.clone();
+        }
+        let Some(name) = job.queue.as_deref() else {
+            return Ok(job);
+        };
+        let policy = self.queues.get(name)
+            .ok_or_else(|| SchedulerError::not_found("Queue", name))?;
+        if let Some(priority) = policy.priority {
+            job.priority = priority;
+        }
+        if let Some(pool) = &policy.pool {
+            if let Some(requested) = job.pool.as_ref().filter(|requested| *requested != pool) {
+                return Err(SchedulerError::invalid_spec(format!(
+                    "Job {} targets pool {} but queue {} runs in pool {}",
+                    job.id, requested, name, pool
+                )).into());
+            }
+            job.pool = Some(pool.clone());
+        }
+        Ok(job)
+    }
+
+    /// Why `job` may not start now under its queue's quota, if it may not
+    pub(crate) fn queue_quota_blocked(&self, job: &JobSpec, placed: &HashMap<String, JobSpec>) -> Option<String> {
+        let name = job.queue.as_deref()?;
+        let limit = self.queues.get(name)?.max_concurrent_jobs?;
+        let running = placed.values()
+            .filter(|spec| spec.id != job.id && spec.queue.as_deref() == Some(name))
+            .count() as u32;
+        (running >= limit).then(|| format!("queue '{}' at {} concurrent jobs", name, limit))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SlaConstraints;
+
+    #[tokio::test]
+    async fn test_queue_sets_priority_and_binds_node_pool() {
+        let mut scheduler = EconomicScheduler::new();
+        scheduler.set_queues(QueuePolicies::new(QueuesConfig {
+            default_queue: Some("batch".to_string()),
+            queues: vec![
+                QueuePolicy {
+                    name: "gpu-training".to_string(),
+                    priority: Some(PriorityClass::High),
+                    max_concurrent_jobs: Some(1),
+                    pool: None,
+                    nodes: NodeSelector { locations: vec!["gpu-dc".to_string()], ..Default::default() },
+                },
+                QueuePolicy { name: "batch".to_string(), priority: Some(PriorityClass::Low), ..Default::default() },
+            ],
+        }).unwrap());
+        for (id, location, cost_per_hour) in [("cheap", "edge", 0.1), ("gpu", "gpu-dc", 3.0)] {
+            scheduler.register_node(NodeInfo {
+                id: id.to_string(),
+                available_cpu: 8,
+                available_memory_gb: 32,
+                location: location.to_string(),
+                cost_per_hour,
+                ..Default::default()
+            }).unwrap();
+        }
+        let job = |id: &str, queue: Option<&str>| JobSpec {
+            id: id.to_string(),
+            queue: queue.map(str::to_string),
+            sla: SlaConstraints { max_latency_ms: 1_000, ..Default::default() },
+            disable_result_cache: true,
+            ..Default::default()
+        };
+
+        let training = scheduler.assign_queue(job("train", Some("gpu-training"))).unwrap();
+        assert_eq!(training.priority, PriorityClass::High);
+        assert_eq!(scheduler.schedule(training.clone()).await.unwrap().node_id, "gpu");
+        assert_eq!(scheduler.get_job_state("train").unwrap().queue.as_deref(), Some("gpu-training"));
+
+        // The queue's quota holds back a second training job
+        let second = scheduler.assign_queue(job("train-2", Some("gpu-training"))).unwrap();
+        assert!(scheduler.concurrency_blocked(&second).unwrap().is_some());
+
+        let batch = scheduler.assign_queue(job("etl", None)).unwrap();
+        assert_eq!(batch.queue.as_deref(), Some("batch"));
+        assert_eq!(batch.priority, PriorityClass::Low);
+        assert_eq!(scheduler.schedule(batch).await.unwrap().node_id, "cheap");
+
+        assert!(scheduler.assign_queue(job("lost", Some("missing"))).is_err());
+    }
+}
@@ -0,0 +1,157 @@
+//! Role-based access control for operator RPCs
+//!
+//! Callers authenticate with a bearer token in the `authorization`
+//! metadata. Tokens are mapped to roles by TGP_API_TOKENS
+//! (`<token>=<role>,...`, roles `user` or `operator`). Ordinary RPCs stay
+//! open; RPCs that reach into running jobs require the operator role and
+//! are refused outright when no tokens are configured. Node providers
+//! authenticate with their own tokens (TGP_PROVIDER_TOKENS,
+//! `<token>=<provider>,...`) and may only act for that provider.
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tonic::metadata::MetadataMap;
+
+use crate::error::SchedulerError;
+use crate::EconomicScheduler;
+
+/// What a caller may do; each role includes the ones before it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum Role {
+    User,
+    /// Debugging access to running jobs
+    Operator,
+}
+
+impl Role {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "user" => Some(Self::User),
+            "operator" => Some(Self::Operator),
+            _ => None,
+        }
+    }
+}
+
+/// Bearer tokens and their roles, shared by scheduler clones
+#[derive(Debug, Clone, Default)]
+pub struct AccessControl {
+    tokens: Arc<HashMap<String, Role>>,
+    /// Provider each provider token speaks for
+    providers: Arc<HashMap<String, String>>,
+}
+
+impl AccessControl {
+    pub fn new(tokens: HashMap<String, Role>) -> Self {
+        Self { tokens: Arc::new(tokens), providers: Arc::default() }
+    }
+
+    /// Parse a `<token>=<role>,...` list
+    pub fn parse(spec: &str) -> Result<Self> {
+        let mut tokens = HashMap::new();
+        for (token, role) in parse_pairs(spec, "role")? {
+            let role = Role::parse(&role)
+                .ok_or_else(|| anyhow::anyhow!("Unknown role {}", role))?;
+            tokens.insert(token, role);
+        }
+        Ok(Self::new(tokens))
+    }
+
+    /// Add provider tokens from a `<token>=<provider>,...` list
+    pub fn with_providers(mut self, spec: &str) -> Result<Self> {
+        self.providers = Arc::new(parse_pairs(spec, "provider")?.into_iter().collect());
+        Ok(self)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tokens.is_empty()
+    }
+
+    /// Role of the caller presenting `token`
+    pub fn role(&self, token: Option<&str>) -> Option<Role> {
+        token.and_then(|token| self.tokens.get(token).copied())
+    }
+
+    /// Refuse callers without at least the `required` role
+    pub fn authorize(&self, token: Option<&str>, required: Role, action: &str) -> Result<Role, SchedulerError> {
+        match self.role(token) {
+            Some(role) if role >= required => Ok(role),
+            _ => Err(SchedulerError::Forbidden { action: action.to_string() }),
+        }
+    }
+
+    /// Refuse callers whose token is not one of `provider_id`'s
+    pub fn authorize_provider(&self, token: Option<&str>, provider_id: &str, action: &str) -> Result<(), SchedulerError> {
+        match token.and_then(|token| self.providers.get(token)) {
+            Some(provider) if provider == provider_id => Ok(()),
+            _ => Err(SchedulerError::Forbidden { action: action.to_string() }),
+        }
+    }
+}
+
+/// Split a `<token>=<value>,...` list
+fn parse_pairs(spec: &str, what: &str) -> Result<Vec<(String, String)>> {
+    let mut pairs = Vec::new();
+    for entry in spec.split(',').map(str::trim).filter(|entry| !entry.is_empty()) {
+        let (token, value) = entry.split_once('=')
+            .ok_or_else(|| anyhow::anyhow!("Token entry without a {} (expected <token>=<{}>)", what, what))?;
+        let (token, value) = (token.trim(), value.trim());
+        anyhow::ensure!(!token.is_empty(), "Empty token for {} {}", what, value);
+        anyhow::ensure!(!value.is_empty(), "Empty {} for a token", what);
+        pairs.push((token.to_string(), value.to_string()));
+    }
+    Ok(pairs)
+}
+
+/// Bearer token of a request, if it has one
+pub fn bearer_token(metadata: &MetadataMap) -> Option<&str> {
+    metadata.get("authorization")?
+        .to_str()
+        .ok()?
+        .strip_prefix("Bearer ")
+        .map(str::trim)
+}
+
+impl EconomicScheduler {
+    pub fn set_access_control(&mut self, access: AccessControl) {
+        self.access = access;
+    }
+
+    pub fn access_control(&self) -> &AccessControl {
+        &self.access
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_operator_role_required() {
+        let access = AccessControl::parse("alice-token=user, ops-token=operator").unwrap();
+        assert!(access.authorize(Some("ops-token"), Role::Operator, "exec into jobs").is_ok());
+        assert!(access.authorize(Some("ops-token"), Role::User, "submit jobs").is_ok());
+        assert!(access.authorize(Some("alice-token"), Role::Operator, "exec into jobs").is_err());
+        assert!(access.authorize(Some("unknown"), Role::Operator, "exec into jobs").is_err());
+        assert!(access.authorize(None, Role::Operator, "exec into jobs").is_err());
+
+        // Nobody is an operator when no tokens are configured
+        let open = AccessControl::default();
+        assert!(open.authorize(None, Role::Operator, "exec into jobs").is_err());
+
+        assert!(AccessControl::parse("token=admin").is_err());
+        assert!(AccessControl::parse("token").is_err());
+
+        let access = access.with_providers("acme-token=acme").unwrap();
+        assert!(access.authorize_provider(Some("acme-token"), "acme", "price nodes").is_ok());
+        assert!(access.authorize_provider(Some("acme-token"), "rival", "price nodes").is_err());
+        assert!(access.authorize_provider(Some("ops-token"), "acme", "price nodes").is_err());
+        assert!(access.authorize_provider(None, "acme", "price nodes").is_err());
+
+        let mut metadata = MetadataMap::new();
+        metadata.insert("authorization", "Bearer ops-token".parse().unwrap());
+        assert_eq!(bearer_token(&metadata), Some("ops-token"));
+    }
+}
@@ -0,0 +1,198 @@
+//! Periodic cluster rebalancing
+//!
+//! The rebalancer asks the optimizer for a consolidation plan over the jobs
+//! currently placed on nodes: lightly loaded nodes whose jobs are all
+//! movable are drained onto busier ones so the freed nodes stop accruing
+//! C_idle. In report-only mode the plan is only logged and published as
+//! events; otherwise the moved jobs are re-assigned to their new node.
+
+use anyhow::Result;
+use std::time::Duration;
+use tgp_optimizer::{ConsolidationConfig, ConsolidationPlan, NodeLoad, PlacedJob};
+
+use crate::events::SchedulerEvent;
+use crate::{unix_now, EconomicScheduler, JobStatus, DATA_TRANSFER_PRICE_PER_GB};
+
+/// Rebalancer tuning
+#[derive(Debug, Clone)]
+pub struct RebalancerConfig {
+    /// Time between passes
+    pub interval: Duration,
+    /// Only log and publish proposed migrations
+    pub report_only: bool,
+    /// Fixed cost of checkpointing and restarting one job
+    pub migration_overhead_usd: f64,
+    /// Disruption budget and savings thresholds
+    pub consolidation: ConsolidationConfig,
+}
+
+impl Default for RebalancerConfig {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(300),
+            report_only: true,
+            migration_overhead_usd: 0.01,
+            consolidation: ConsolidationConfig::default(),
+        }
+    }
+}
+
+impl EconomicScheduler {
+    /// Compute a consolidation plan for the current placements
+    ///
+    /// A job's migration cost is the restart overhead plus re-staging all of
+    /// its datasets (an upper bound; the target may already hold some).
+    pub fn plan_rebalance(&self, config: &RebalancerConfig) -> Result<ConsolidationPlan> {
+        let mut placed = {
+            let placed = self.placed_jobs.lock()
+                .map_err(|e| anyhow::anyhow!("Lock poisoned: {}", e))?;
+            placed.values().cloned().collect::<Vec<_>>()
+        };
+        if self.deterministic {
+            placed.sort_by(|a, b| a.id.cmp(&b.id));
+        }
+
+        let jobs: Vec<PlacedJob> = placed.iter()
+            .filter_map(|spec| {
+                let state = self.get_job_state(&spec.id)?;
+                let node_id = state.assigned_node?;
+                let staging_gb: f64 = spec.datasets.iter()
+                    .filter_map(|name| self.datasets.get(name))
+                    .map(|info| info.size_gb)
+                    .sum();
+
+                Some(PlacedJob {
+                    job_id: spec.id.clone(),
+                    node_id,
+                    cpu_cores: spec.resources.cpu_cores,
+                    memory_gb: spec.resources.memory_gb,
+                    gpu_count: spec.resources.gpu_count,
+                    movable: spec.movable,
+                    migration_cost_usd: config.migration_overhead_usd
+                        + staging_gb * DATA_TRANSFER_PRICE_PER_GB,
+                })
+            })
+            .collect();
+
+        let nodes: Vec<NodeLoad> = self.cluster_status()
+            .into_iter()
+            .map(|node| NodeLoad {
+                node_id: node.id,
+                free_cpu: node.available_cpu,
+                free_memory_gb: node.available_memory_gb,
+                free_gpu: node.available_gpu,
+                cost_per_hour: node.cost_per_hour,
+            })
+            .collect();
+
+        Ok(self.optimizer.plan_consolidation(&nodes, &jobs, &config.consolidation))
+    }
+
+    /// Publish a plan and, unless `report_only`, re-assign the moved jobs
+    pub fn apply_rebalance(&self, plan: &ConsolidationPlan, report_only: bool) -> Result<()> {
+        for migration in &plan.migrations {
+            if !report_only {
+                // Workers pick the job up again from its checkpoint on the new node
+                self.update_job_state(
+                    migration.job_id.clone(),
+                    JobStatus::Scheduled,
+                    Some(migration.to_node.clone()),
+                )?;
+            }
+
+            self.publish_event(SchedulerEvent::JobMigration {
+                job_id: migration.job_id.clone(),
+                from_node: migration.from_node.clone(),
+                to_node: migration.to_node.clone(),
+                applied: !report_only,
+                timestamp: unix_now(),
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Run a rebalancing pass every `config.interval` until the process exits
+pub async fn run_rebalancer(scheduler: EconomicScheduler, config: RebalancerConfig) {
+    let mut ticker = tokio::time::interval(config.interval);
+    ticker.tick().await;
+
+    loop {
+        ticker.tick().await;
+
+        let plan = match scheduler.plan_rebalance(&config) {
+            Ok(plan) => plan,
+            Err(e) => {
+                tracing::warn!("Rebalance planning failed: {}", e);
+                continue;
+            }
+        };
+
+        if plan.migrations.is_empty() {
+            tracing::debug!("Rebalance: nothing to consolidate");
+            continue;
+        }
+
+        tracing::info!(
+            "Rebalance{}: {} migrations drain {:?} (net savings ${:.4})",
+            if config.report_only { " (report only)" } else { "" },
+            plan.migrations.len(),
+            plan.drained_nodes,
+            plan.net_savings_usd
+        );
+        for migration in &plan.migrations {
+            tracing::info!("  {}: {} -> {}", migration.job_id, migration.from_node, migration.to_node);
+        }
+
+        if let Err(e) = scheduler.apply_rebalance(&plan, config.report_only) {
+            tracing::warn!("Failed to apply rebalance plan: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{JobSpec, NodeInfo, ResourceRequirements, SlaConstraints};
+
+    #[tokio::test]
+    async fn test_plan_moves_movable_job_off_light_node() {
+        let scheduler = EconomicScheduler::new();
+        for (id, cost_per_hour) in [("cheap", 0.1), ("pricey", 0.5)] {
+            scheduler.register_node(NodeInfo {
+                id: id.to_string(),
+                available_cpu: 8,
+                available_memory_gb: 32,
+                location: "vps-1".to_string(),
+                cost_per_hour,
+                ..Default::default()
+            }).unwrap();
+        }
+
+        let job = |id: &str, cpu_cores: u32| JobSpec {
+            id: id.to_string(),
+            resources: ResourceRequirements { cpu_cores, memory_gb: 1, disk_gb: 1, ..Default::default() },
+            sla: SlaConstraints { max_latency_ms: 1_000, ..Default::default() },
+            job_data: id.as_bytes().to_vec(),
+            movable: true,
+            ..Default::default()
+        };
+
+        // Both land on the cheap node; pretend one was placed on the pricey one
+        scheduler.schedule(job("big", 4)).await.unwrap();
+        scheduler.schedule(job("small", 1)).await.unwrap();
+        scheduler.update_job_state("small".to_string(), JobStatus::Running, Some("pricey".to_string())).unwrap();
+
+        let config = RebalancerConfig::default();
+        let plan = scheduler.plan_rebalance(&config).unwrap();
+        assert_eq!(plan.drained_nodes, vec!["pricey".to_string()]);
+        assert_eq!(plan.migrations[0].to_node, "cheap");
+
+        // Report-only leaves the placement untouched
+        scheduler.apply_rebalance(&plan, true).unwrap();
+        assert_eq!(scheduler.get_job_state("small").unwrap().assigned_node.as_deref(), Some("pricey"));
+
+        scheduler.apply_rebalance(&plan, false).unwrap();
+        assert_eq!(scheduler.get_job_state("small").unwrap().assigned_node.as_deref(), Some("cheap"));
+    }
+}
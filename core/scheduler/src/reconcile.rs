@@ -0,0 +1,218 @@
+//! Startup reconciliation with workers
+//!
+//! Job states restored from the store describe the cluster as it was when
+//! the previous scheduler stopped. Before trusting them, every node with a
+//! control address is asked which job containers it is running:
+//!
+//! - a job recorded as running that no probed node runs any more is lost
+//!   (it ended while the scheduler was down) and is marked failed
+//! - a job a node runs that was recorded as pending, scheduled or on
+//!   another node is marked running there
+//! - a job a node runs that is finished or unknown here is an orphan and
+//!   is only reported
+//!
+//! Nodes that cannot be reached are left as they are.
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::time::Duration;
+
+use crate::commands::{connect_worker, worker_proto::ListRunningJobsRequest, ControlAuth};
+use crate::{EconomicScheduler, JobStatus, NodeInfo};
+
+/// Asks a worker which jobs it is running
+#[async_trait]
+pub trait WorkerProbe: Send + Sync {
+    async fn running_jobs(&self, node: &NodeInfo) -> Result<Vec<String>>;
+}
+
+/// Probe calling the worker's control service over gRPC
+pub struct GrpcWorkerProbe {
+    timeout: Duration,
+    auth: ControlAuth,
+}
+
+impl GrpcWorkerProbe {
+    pub fn new(timeout: Duration, auth: ControlAuth) -> Self {
+        Self { timeout, auth }
+    }
+}
+
+#[async_trait]
+impl WorkerProbe for GrpcWorkerProbe {
+    async fn running_jobs(&self, node: &NodeInfo) -> Result<Vec<String>> {
+        let response = connect_worker(node, Some(self.timeout), &self.auth).await?
+            .list_running_jobs(ListRunningJobsRequest {})
+            .await
+            .context("ListRunningJobs failed")?;
+        Ok(response.into_inner().job_ids)
+    }
+}
+
+/// What reconciliation found and changed
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ReconcileReport {
+    /// Nodes that answered
+    pub nodes_checked: usize,
+    /// Nodes without a control address or that did not answer
+    pub nodes_unreachable: Vec<String>,
+    /// Recorded as running but running nowhere: marked failed
+    pub lost: Vec<String>,
+    /// Running on a node other than recorded: marked running there
+    pub adopted: Vec<String>,
+    /// (node, job) running on a node although finished or unknown here
+    pub orphaned: Vec<(String, String)>,
+}
+
+impl EconomicScheduler {
+    /// Correct restored job states against what workers actually run
+    pub async fn reconcile(&self, probe: &dyn WorkerProbe) -> Result<ReconcileReport> {
+        let mut report = ReconcileReport::default();
+
+        let mut running: HashMap<String, HashSet<String>> = HashMap::new();
+        for node in self.cluster_status() {
+            if node.control_addr.is_empty() {
+                report.nodes_unreachable.push(node.id);
+                continue;
+            }
+            match probe.running_jobs(&node).await {
+                Ok(job_ids) => {
+                    report.nodes_checked += 1;
+                    running.insert(node.id, job_ids.into_iter().collect());
+                }
+                Err(e) => {
+                    tracing::warn!("Cannot reconcile node {}: {:#}", node.id, e);
+                    report.nodes_unreachable.push(node.id);
+                }
+            }
+        }
+
+        let states: Vec<_> = self.job_states.lock()
+            .map_err(|e| anyhow::anyhow!("Lock poisoned: {}", e))?
+            .values()
+            .cloned()
+            .collect();
+        let states: HashMap<_, _> = states.into_iter().map(|state| (state.job_id.clone(), state)).collect();
+
+        // Recorded as running: lost once every node it was on has been
+        // probed and none still runs it
+        for state in states.values().filter(|state| state.status == JobStatus::Running) {
+            let nodes: Vec<&String> = if state.gang_nodes.is_empty() {
+                state.assigned_node.iter().collect()
+            } else {
+                state.gang_nodes.iter().collect()
+            };
+            let gone = !nodes.is_empty() && nodes.iter().all(|node| {
+                running.get(*node).is_some_and(|jobs| !jobs.contains(&state.job_id))
+            });
+            if gone {
+                tracing::warn!("Job {} is no longer running on any node, marking failed", state.job_id);
+                self.update_job_state(state.job_id.clone(), JobStatus::Failed, None)?;
+                report.lost.push(state.job_id.clone());
+            }
+        }
+
+        // Running on a node: make the recorded state agree
+        for (node_id, jobs) in &running {
+            for job_id in jobs {
+                match states.get(job_id) {
+                    Some(state) if state.status.is_terminal() => {
+                        tracing::warn!("Node {} is running job {} which already {:?}", node_id, job_id, state.status);
+                        report.orphaned.push((node_id.clone(), job_id.clone()));
+                    }
+                    None => {
+                        tracing::warn!("Node {} is running unknown job {}", node_id, job_id);
+                        report.orphaned.push((node_id.clone(), job_id.clone()));
+                    }
+                    Some(state) => {
+                        let on_node = state.assigned_node.as_ref() == Some(node_id)
+                            || state.gang_nodes.contains(node_id);
+                        if state.status == JobStatus::Running && on_node {
+                            continue;
+                        }
+                        tracing::info!("Job {} is running on node {}, recorded {:?}", job_id, node_id, state.status);
+                        let node = if on_node { None } else { Some(node_id.clone()) };
+                        self.update_job_state(job_id.clone(), JobStatus::Running, node)?;
+                        report.adopted.push(job_id.clone());
+                    }
+                }
+            }
+        }
+
+        report.nodes_unreachable.sort();
+        report.lost.sort();
+        report.adopted.sort();
+        report.orphaned.sort();
+        tracing::info!(
+            "Reconciled {} nodes ({} unreachable): {} lost, {} adopted, {} orphaned jobs",
+            report.nodes_checked, report.nodes_unreachable.len(),
+            report.lost.len(), report.adopted.len(), report.orphaned.len()
+        );
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::JobState;
+
+    struct FakeProbe(HashMap<String, Vec<String>>);
+
+    #[async_trait]
+    impl WorkerProbe for FakeProbe {
+        async fn running_jobs(&self, node: &NodeInfo) -> Result<Vec<String>> {
+            self.0.get(&node.id).cloned().context("unreachable")
+        }
+    }
+
+    #[tokio::test]
+    async fn test_reconcile_corrects_restored_states() {
+        let scheduler = EconomicScheduler::new();
+        for (id, control_addr) in [("node-1", "10.0.0.1:50053"), ("node-2", "10.0.0.2:50053"), ("node-3", "")] {
+            scheduler.register_node(NodeInfo {
+                id: id.to_string(),
+                available_cpu: 4,
+                control_addr: control_addr.to_string(),
+                ..Default::default()
+            }).unwrap();
+        }
+        {
+            let mut states = scheduler.job_states.lock().unwrap();
+            for (job_id, status, node) in [
+                ("ended", JobStatus::Running, "node-1"),
+                ("started", JobStatus::Scheduled, "node-1"),
+                ("done", JobStatus::Completed, "node-1"),
+                ("behind-outage", JobStatus::Running, "node-2"),
+                ("behind-nat", JobStatus::Running, "node-3"),
+            ] {
+                states.insert(job_id.to_string(), JobState {
+                    job_id: job_id.to_string(),
+                    status,
+                    assigned_node: Some(node.to_string()),
+                    ..Default::default()
+                });
+            }
+        }
+
+        let probe = FakeProbe(HashMap::from([(
+            "node-1".to_string(),
+            vec!["started".to_string(), "done".to_string(), "stray".to_string()],
+        )]));
+        let report = scheduler.reconcile(&probe).await.unwrap();
+
+        assert_eq!(report.nodes_checked, 1);
+        assert_eq!(report.nodes_unreachable, vec!["node-2", "node-3"]);
+        assert_eq!(report.lost, vec!["ended"]);
+        assert_eq!(report.adopted, vec!["started"]);
+        assert_eq!(report.orphaned, vec![
+            ("node-1".to_string(), "done".to_string()),
+            ("node-1".to_string(), "stray".to_string()),
+        ]);
+        assert_eq!(scheduler.get_job_state("ended").unwrap().status, JobStatus::Failed);
+        assert_eq!(scheduler.get_job_state("started").unwrap().status, JobStatus::Running);
+        assert_eq!(scheduler.get_job_state("behind-outage").unwrap().status, JobStatus::Running);
+    }
+}
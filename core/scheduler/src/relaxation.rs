@@ -0,0 +1,151 @@
+//! SLA relaxation suggestions
+//!
+//! When no node meets a job's latency limit, deadline or budget, each
+//! candidate is evaluated again with those limits lifted. Its estimate then
+//! says how far each limit would have to move for that node to qualify
+//! (e.g. raising max_latency_ms to 180 or the budget to $0.42). Suggestions
+//! that change the fewest limits come first, then the cheapest. They are
+//! attached to the scheduling error and its gRPC details. Avoided,
+//! plugin-filtered and over-ceiling nodes are never suggested, because no
+//! SLA change would place the job there.
+
+use std::fmt;
+
+use crate::node_index::NodeIndex;
+use crate::{unix_now, EconomicScheduler, JobSpec, NodeInfo, Placement};
+
+/// Suggestions attached to one error
+pub const MAX_SUGGESTIONS: usize = 3;
+
+/// Limits a job would need for `node_id` to take it; unset ones can stay
+#[derive(Debug, Clone, PartialEq)]
+pub struct Relaxation {
+    pub node_id: String,
+    pub max_latency_ms: Option<u64>,
+    /// Rounded up to the cent
+    pub max_budget_usd: Option<f64>,
+    pub deadline: Option<i64>,
+}
+
+impl Relaxation {
+    /// Number of limits to change
+    pub fn changes(&self) -> usize {
+        [self.max_latency_ms.is_some(), self.max_budget_usd.is_some(), self.deadline.is_some()]
+            .into_iter()
+            .filter(|&changed| changed)
+            .count()
+    }
+}
+
+impl fmt::Display for Relaxation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut changes = Vec::new();
+        if let Some(ms) = self.max_latency_ms {
+            changes.push(format!("max_latency_ms to {}", ms));
+        }
+        if let Some(usd) = self.max_budget_usd {
+            changes.push(format!("budget to ${:.2}", usd));
+        }
+        if let Some(deadline) = self.deadline {
+            changes.push(format!("deadline to {}", deadline));
+        }
+        write!(f, "raising {} would allow placement on node {}", changes.join(" and "), self.node_id)
+    }
+}
+
+/// Expected finish time of a placement starting now, as the deadline
+/// check computes it
+fn finish_at(placement: &Placement, now: i64) -> i64 {
+    now + (placement.estimated_latency_ms / 1000) as i64 + (placement.estimated_duration_hours * 3600.0) as i64
+}
+
+impl EconomicScheduler {
+    /// Smallest SLA changes that would let a candidate take `job`
+    pub(crate) fn suggest_relaxations(
+        &self,
+        job: &JobSpec,
+        candidates: &[&NodeInfo],
+        reference_hours: f64,
+        nodes: &NodeIndex,
+    ) -> Vec<Relaxation> {
+        let mut relaxed = job.clone();
+        relaxed.sla.max_latency_ms = u64::MAX;
+        relaxed.sla.max_budget_usd = None;
+        relaxed.sla.deadline = None;
+
+        let now = unix_now();
+        let mut suggestions: Vec<(Relaxation, f64)> = candidates.iter()
+            .filter_map(|node| self.assess_node(node, &relaxed, reference_hours, nodes).ok())
+            .map(|placement| {
+                let cost_usd = placement.estimated_cost.total_usd;
+                let finish = finish_at(&placement, now);
+                let relaxation = Relaxation {
+                    max_latency_ms: Some(placement.estimated_latency_ms)
+                        .filter(|&ms| ms > job.sla.max_latency_ms),
+                    max_budget_usd: job.sla.max_budget_usd
+                        .filter(|&budget| cost_usd > budget)
+                        .map(|_| (cost_usd * 100.0).ceil() / 100.0),
+                    deadline: job.sla.deadline.filter(|&deadline| finish > deadline).map(|_| finish),
+                    node_id: placement.node_id,
+                };
+                (relaxation, cost_usd)
+            })
+            // Nodes rejected for something other than the SLA
+            .filter(|(relaxation, _)| relaxation.changes() > 0)
+            .collect();
+
+        suggestions.sort_by(|(a, a_usd), (b, b_usd)| {
+            a.changes().cmp(&b.changes()).then(a_usd.total_cmp(b_usd))
+        });
+        suggestions.into_iter()
+            .take(MAX_SUGGESTIONS)
+            .map(|(relaxation, _)| relaxation)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::SchedulerError;
+    use crate::SlaConstraints;
+
+    #[tokio::test]
+    async fn test_failed_placement_suggests_limits_per_node() {
+        let scheduler = EconomicScheduler::new();
+        for (id, cost_per_hour) in [("cheap", 0.5), ("pricey", 4.0)] {
+            scheduler.register_node(NodeInfo {
+                id: id.to_string(),
+                available_cpu: 8,
+                available_memory_gb: 32,
+                cost_per_hour,
+                ..Default::default()
+            }).unwrap();
+        }
+
+        let job = JobSpec {
+            id: "tight".to_string(),
+            sla: SlaConstraints { max_latency_ms: 1, max_budget_usd: Some(0.1), ..Default::default() },
+            estimated_duration_hours: Some(1.0),
+            ..Default::default()
+        };
+        let err = scheduler.schedule(job.clone()).await.unwrap_err();
+        let typed = crate::error::classify(&err).unwrap();
+        assert!(matches!(typed, SchedulerError::SlaLatencyUnmet { .. }));
+        // Both limits have to move for either node; the cheaper comes first
+        let suggestions = typed.suggestions();
+        assert_eq!(suggestions.len(), 2);
+        assert_eq!(suggestions[0].node_id, "cheap");
+        assert_eq!(suggestions[0].max_latency_ms, Some(50));
+        assert!(suggestions.iter().all(|s| s.changes() == 2));
+        assert!(suggestions[0].max_budget_usd.unwrap() < suggestions[1].max_budget_usd.unwrap());
+        assert!(suggestions[0].to_string().starts_with("raising max_latency_ms to 50 and budget to $"));
+
+        // Latency met: only the budget is in the way
+        let job = JobSpec { id: "frugal".to_string(), sla: SlaConstraints { max_latency_ms: 1000, ..job.sla }, ..job };
+        let err = scheduler.schedule(job).await.unwrap_err();
+        let suggestions = crate::error::classify(&err).unwrap().suggestions();
+        assert_eq!(suggestions[0].changes(), 1);
+        assert_eq!(suggestions[0].max_latency_ms, None);
+    }
+}
@@ -0,0 +1,307 @@
+//! Worker-initiated command stream
+//!
+//! Nodes behind NAT cannot be dialled, so they open a `CommandStream` to
+//! the scheduler and keep it open, reconnecting when it drops. Commands for
+//! such a node are queued in its outbox and written to the stream; each
+//! carries the scheduler's epoch and a per-node sequence number and stays
+//! queued until the worker acknowledges it. After a reconnect everything
+//! unacknowledged is sent again, and the worker answers a command it has
+//! already handled with its earlier ack instead of running it twice.
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::{mpsc, oneshot};
+use tonic::Status;
+
+use crate::commands::{execute_request, CommandTransport, WorkerCommand};
+use crate::grpc::proto::{
+    scheduler_command::Command, CancelJobCommand, CommandAck, JobAssignment, JobHook, ListRunningJobsCommand,
+    PingCommand, SchedulerCommand,
+};
+use crate::reconcile::WorkerProbe;
+use crate::NodeInfo;
+
+/// How long ping and list requests wait for their ack
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+pub type CommandSender = mpsc::UnboundedSender<Result<SchedulerCommand, Status>>;
+
+#[derive(Default)]
+struct Outbox {
+    next_seq: u64,
+    /// Sent (or waiting to be) and not yet acknowledged, in order
+    pending: BTreeMap<u64, SchedulerCommand>,
+    waiters: HashMap<u64, oneshot::Sender<CommandAck>>,
+    /// Open stream and its connection id
+    stream: Option<(u64, CommandSender)>,
+}
+
+#[derive(Default)]
+struct RelayState {
+    outboxes: HashMap<String, Outbox>,
+    next_connection: u64,
+}
+
+/// Outboxes of nodes that reach the scheduler over a command stream
+#[derive(Clone)]
+pub struct CommandRelay {
+    epoch: u64,
+    state: Arc<Mutex<RelayState>>,
+}
+
+impl Default for CommandRelay {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CommandRelay {
+    pub fn new() -> Self {
+        let epoch = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_nanos() as u64)
+            .unwrap_or(1);
+        Self { epoch, state: Arc::new(Mutex::new(RelayState::default())) }
+    }
+
+    pub fn epoch(&self) -> u64 {
+        self.epoch
+    }
+
+    /// Attach a node's new stream, replacing any older one, and resend its
+    /// unacknowledged commands; returns the connection id
+    pub fn connect(&self, node_id: &str, stream: CommandSender) -> Result<u64> {
+        let mut state = self.state.lock()
+            .map_err(|e| anyhow::anyhow!("Lock poisoned: {}", e))?;
+        state.next_connection += 1;
+        let connection = state.next_connection;
+        let outbox = state.outboxes.entry(node_id.to_string()).or_default();
+        for command in outbox.pending.values() {
+            let _ = stream.send(Ok(command.clone()));
+        }
+        tracing::info!("Node {} opened a command stream ({} commands resent)", node_id, outbox.pending.len());
+        outbox.stream = Some((connection, stream));
+        Ok(connection)
+    }
+
+    /// Detach a stream unless a newer one replaced it
+    pub fn disconnect(&self, node_id: &str, connection: u64) {
+        if let Ok(mut state) = self.state.lock() {
+            if let Some(outbox) = state.outboxes.get_mut(node_id) {
+                if outbox.stream.as_ref().is_some_and(|(id, _)| *id == connection) {
+                    tracing::info!("Command stream of node {} closed", node_id);
+                    outbox.stream = None;
+                }
+            }
+        }
+    }
+
+    /// Whether the node has opened a command stream since this scheduler started
+    pub fn knows(&self, node_id: &str) -> bool {
+        self.state.lock().map(|state| state.outboxes.contains_key(node_id)).unwrap_or(false)
+    }
+
+    pub fn is_connected(&self, node_id: &str) -> bool {
+        self.state.lock()
+            .map(|state| state.outboxes.get(node_id).is_some_and(|outbox| outbox.stream.is_some()))
+            .unwrap_or(false)
+    }
+
+    /// Commands waiting for the node's ack
+    pub fn pending(&self, node_id: &str) -> usize {
+        self.state.lock()
+            .map(|state| state.outboxes.get(node_id).map_or(0, |outbox| outbox.pending.len()))
+            .unwrap_or(0)
+    }
+
+    /// Queue a command and send it if the node's stream is open
+    pub fn enqueue(&self, node_id: &str, command: Command) -> Result<(u64, oneshot::Receiver<CommandAck>)> {
+        let mut state = self.state.lock()
+            .map_err(|e| anyhow::anyhow!("Lock poisoned: {}", e))?;
+        let outbox = state.outboxes.entry(node_id.to_string()).or_default();
+        outbox.next_seq += 1;
+        let seq = outbox.next_seq;
+        let command = SchedulerCommand { epoch: self.epoch, seq, command: Some(command) };
+
+        if let Some((_, stream)) = &outbox.stream {
+            if stream.send(Ok(command.clone())).is_err() {
+                outbox.stream = None;
+            }
+        }
+        outbox.pending.insert(seq, command);
+        let (tx, rx) = oneshot::channel();
+        outbox.waiters.insert(seq, tx);
+        Ok((seq, rx))
+    }
+
+    /// Settle a command the node acknowledged
+    pub fn acknowledge(&self, node_id: &str, ack: CommandAck) {
+        if ack.epoch != self.epoch {
+            tracing::debug!("Ignoring ack from node {} for an earlier scheduler run", node_id);
+            return;
+        }
+        let Ok(mut state) = self.state.lock() else {
+            return;
+        };
+        let Some(outbox) = state.outboxes.get_mut(node_id) else {
+            return;
+        };
+        outbox.pending.remove(&ack.seq);
+        if !ack.ok {
+            tracing::warn!("Node {} failed command {}: {}", node_id, ack.seq, ack.message);
+        }
+        if let Some(waiter) = outbox.waiters.remove(&ack.seq) {
+            let _ = waiter.send(ack);
+        }
+    }
+
+    /// Drop a command that is no longer worth delivering
+    fn withdraw(&self, node_id: &str, seq: u64) {
+        if let Ok(mut state) = self.state.lock() {
+            if let Some(outbox) = state.outboxes.get_mut(node_id) {
+                outbox.pending.remove(&seq);
+                outbox.waiters.remove(&seq);
+            }
+        }
+    }
+
+    /// Send a query and wait for its ack; unanswered queries are dropped
+    pub async fn request(&self, node_id: &str, command: Command) -> Result<CommandAck> {
+        if !self.is_connected(node_id) {
+            anyhow::bail!("Node {} has no open command stream", node_id);
+        }
+        let (seq, ack) = self.enqueue(node_id, command)?;
+        match tokio::time::timeout(REQUEST_TIMEOUT, ack).await {
+            Ok(Ok(ack)) if ack.ok => Ok(ack),
+            Ok(Ok(ack)) => anyhow::bail!("Node {} refused command: {}", node_id, ack.message),
+            Ok(Err(_)) | Err(_) => {
+                self.withdraw(node_id, seq);
+                anyhow::bail!("Node {} did not acknowledge command {}", node_id, seq)
+            }
+        }
+    }
+
+    /// Number of jobs the node is running
+    pub async fn ping(&self, node_id: &str) -> Result<u32> {
+        let ack = self.request(node_id, Command::Ping(PingCommand {})).await?;
+        Ok(ack.running_job_ids.len() as u32)
+    }
+}
+
+/// Stream form of a worker command
+fn to_proto(command: &WorkerCommand) -> Command {
+    match command {
+        WorkerCommand::Execute(job, devices) => {
+            let request = execute_request(job, devices);
+            Command::Execute(JobAssignment {
+                job_id: request.job_id,
+                job_type: request.job_type,
+                container_image: request.container_image,
+                command: request.command,
+                environment: request.environment,
+                cpu_limit: request.cpu_limit,
+                memory_limit_mb: request.memory_limit_mb,
+                disk_limit_gb: request.disk_limit_gb,
+                gpu_indices: request.gpu_indices,
+                port_bindings: request.port_bindings,
+                cpu_set: request.cpu_set,
+                numa_node: request.numa_node,
+                disk_io_mbps: request.disk_io_mbps,
+                egress_limit_mbps: request.egress_limit_mbps,
+                input: request.input,
+                input_stdin: request.input_stdin,
+                output_path: request.output_path,
+                payload_digest: request.payload_digest,
+                pre_start_hook: request.pre_start_hook.map(stream_hook),
+                post_complete_hook: request.post_complete_hook.map(stream_hook),
+                datasets: request.datasets,
+            })
+        }
+        WorkerCommand::Cancel { job_id, grace_secs } => Command::Cancel(CancelJobCommand {
+            job_id: job_id.clone(),
+            grace_secs: *grace_secs,
+            crash: false,
+        }),
+        WorkerCommand::Kill { job_id } => Command::Cancel(CancelJobCommand {
+            job_id: job_id.clone(),
+            grace_secs: 0,
+            crash: true,
+        }),
+    }
+}
+
+fn stream_hook(hook: crate::commands::worker_proto::JobHook) -> JobHook {
+    JobHook {
+        image: hook.image,
+        command: hook.command,
+        timeout_secs: hook.timeout_secs,
+    }
+}
+
+#[async_trait]
+impl CommandTransport for CommandRelay {
+    /// Queued until acknowledged, so delivery survives reconnects
+    async fn send(&self, node: &NodeInfo, command: &WorkerCommand) -> Result<()> {
+        self.enqueue(&node.id, to_proto(command))?;
+        Ok(())
+    }
+
+    fn reaches(&self, node: &NodeInfo) -> bool {
+        self.knows(&node.id)
+    }
+}
+
+#[async_trait]
+impl WorkerProbe for CommandRelay {
+    async fn running_jobs(&self, node: &NodeInfo) -> Result<Vec<String>> {
+        let ack = self.request(&node.id, Command::ListRunningJobs(ListRunningJobsCommand {}))
+            .await
+            .context("ListRunningJobs failed")?;
+        Ok(ack.running_job_ids)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cancel(job_id: &str) -> Command {
+        Command::Cancel(CancelJobCommand { job_id: job_id.to_string(), grace_secs: 30, crash: false })
+    }
+
+    #[tokio::test]
+    async fn test_unacknowledged_commands_are_resent_after_reconnect() {
+        let relay = CommandRelay::new();
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let first = relay.connect("node-1", tx).unwrap();
+
+        relay.enqueue("node-1", cancel("job-1")).unwrap();
+        relay.enqueue("node-1", cancel("job-2")).unwrap();
+        let sent: Vec<u64> = [rx.recv().await, rx.recv().await].into_iter()
+            .map(|command| command.unwrap().unwrap().seq)
+            .collect();
+        assert_eq!(sent, vec![1, 2]);
+
+        relay.acknowledge("node-1", CommandAck { epoch: relay.epoch(), seq: 1, ok: true, ..Default::default() });
+        // Acks from an earlier scheduler run settle nothing
+        relay.acknowledge("node-1", CommandAck { epoch: relay.epoch() - 1, seq: 2, ok: true, ..Default::default() });
+        assert_eq!(relay.pending("node-1"), 1);
+
+        relay.disconnect("node-1", first);
+        assert!(!relay.is_connected("node-1"));
+        relay.enqueue("node-1", cancel("job-3")).unwrap();
+
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        relay.connect("node-1", tx).unwrap();
+        let resent: Vec<u64> = [rx.recv().await, rx.recv().await].into_iter()
+            .map(|command| command.unwrap().unwrap().seq)
+            .collect();
+        assert_eq!(resent, vec![2, 3]);
+        // A stale disconnect must not detach the new stream
+        relay.disconnect("node-1", first);
+        assert!(relay.is_connected("node-1"));
+    }
+}
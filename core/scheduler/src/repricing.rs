@@ -0,0 +1,240 @@
+//! Re-evaluating running services after price changes
+//!
+//! A long-running job keeps the node it was placed on even when a
+//! provider later cuts prices elsewhere or a cheaper node joins. After
+//! such a change the repricer looks at every running, movable job that is
+//! expected to run for a while yet, finds its cheapest alternative that
+//! still meets its SLA, and proposes a move when the price difference over
+//! the savings horizon beats the migration cost by `min_savings_usd`. At
+//! most `max_migrations` jobs move per pass (the disruption budget), the
+//! biggest savings first. Moves are applied like rebalance migrations, or
+//! only published in report-only mode.
+
+use anyhow::Result;
+use std::collections::HashMap;
+use std::time::Duration;
+use tgp_optimizer::{ConsolidationPlan, Migration};
+
+use crate::hourly_rates::hourly_rate;
+use crate::{unix_now, EconomicScheduler, JobStatus, DATA_TRANSFER_PRICE_PER_GB};
+
+/// Repricer tuning
+#[derive(Debug, Clone)]
+pub struct RepricingConfig {
+    /// Only log and publish proposed migrations
+    pub report_only: bool,
+    /// Hours of the price difference counted as savings (at most the
+    /// job's expected remaining run time)
+    pub horizon_hours: f64,
+    /// Jobs expected to finish sooner than this are left where they are
+    pub min_remaining_hours: f64,
+    /// Net savings a move must reach to be worth the disruption
+    pub min_savings_usd: f64,
+    /// Jobs moved per pass at most (disruption budget)
+    pub max_migrations: usize,
+    /// Fixed cost of checkpointing and restarting one job
+    pub migration_overhead_usd: f64,
+    /// Wait after a price change for others arriving with it
+    pub settle: Duration,
+}
+
+impl Default for RepricingConfig {
+    fn default() -> Self {
+        Self {
+            report_only: true,
+            horizon_hours: 24.0,
+            min_remaining_hours: 1.0,
+            min_savings_usd: 1.0,
+            max_migrations: 5,
+            migration_overhead_usd: 0.01,
+            settle: Duration::from_secs(10),
+        }
+    }
+}
+
+/// A proposed move and what it saves
+#[derive(Debug, Clone, PartialEq)]
+struct Candidate {
+    migration: Migration,
+    savings_usd: f64,
+    cpu_cores: u32,
+    memory_gb: u32,
+}
+
+impl EconomicScheduler {
+    /// Prices or nodes changed: running services are worth re-evaluating
+    pub(crate) fn prices_changed(&self) {
+        self.price_changes.notify_one();
+    }
+
+    /// Moves of running services onto cheaper nodes worth making now
+    pub fn plan_repricing(&self, config: &RepricingConfig) -> Result<ConsolidationPlan> {
+        let mut placed = {
+            let placed = self.placed_jobs.lock()
+                .map_err(|e| anyhow::anyhow!("Lock poisoned: {}", e))?;
+            placed.values().cloned().collect::<Vec<_>>()
+        };
+        if self.deterministic {
+            placed.sort_by(|a, b| a.id.cmp(&b.id));
+        }
+
+        let now = unix_now();
+        let nodes = self.available_nodes.lock()
+            .map_err(|e| anyhow::anyhow!("Lock poisoned: {}", e))?;
+        let mut candidates = Vec::new();
+        for spec in placed.iter().filter(|spec| spec.movable && spec.gang_size <= 1) {
+            let Some(state) = self.get_job_state(&spec.id).filter(|state| state.status == JobStatus::Running) else {
+                continue;
+            };
+            let Some(current) = state.assigned_node.as_deref().and_then(|node_id| nodes.get(node_id)) else {
+                continue;
+            };
+
+            // Time left of the expected run on the current node
+            let elapsed_hours = state.started_at.map_or(0.0, |started_at| (now - started_at).max(0) as f64 / 3600.0);
+            let remaining_hours = self.reference_duration_hours(spec, None) / current.performance() - elapsed_hours;
+            if remaining_hours < config.min_remaining_hours {
+                continue;
+            }
+
+            let Some(target) = nodes.candidates(&spec.resources)
+                .into_iter()
+                .filter(|node| node.id != current.id)
+                .filter_map(|node| self.evaluate_node(node, spec, remaining_hours * current.performance(), &nodes))
+                .min_by(Self::cheaper) else {
+                continue;
+            };
+            let Some(target_node) = nodes.get(&target.node_id) else {
+                continue;
+            };
+
+            let staging_gb: f64 = spec.datasets.iter()
+                .filter_map(|name| self.datasets.get(name))
+                .map(|info| info.size_gb)
+                .sum();
+            let migration_cost_usd = config.migration_overhead_usd + staging_gb * DATA_TRANSFER_PRICE_PER_GB;
+            let hours = remaining_hours.min(config.horizon_hours);
+            let savings_usd = (hourly_rate(spec, current) - hourly_rate(spec, target_node)) * hours - migration_cost_usd;
+            if savings_usd < config.min_savings_usd {
+                continue;
+            }
+            candidates.push(Candidate {
+                migration: Migration {
+                    job_id: spec.id.clone(),
+                    from_node: current.id.clone(),
+                    to_node: target.node_id,
+                },
+                savings_usd,
+                cpu_cores: spec.resources.cpu_cores,
+                memory_gb: spec.resources.memory_gb,
+            });
+        }
+
+        // Biggest savings first, without overfilling a target
+        candidates.sort_by(|a, b| {
+            b.savings_usd.total_cmp(&a.savings_usd).then_with(|| a.migration.job_id.cmp(&b.migration.job_id))
+        });
+        let mut taken: HashMap<String, (u32, u32)> = HashMap::new();
+        let mut plan = ConsolidationPlan::default();
+        for candidate in candidates {
+            if plan.migrations.len() >= config.max_migrations {
+                break;
+            }
+            let Some(target) = nodes.get(&candidate.migration.to_node) else {
+                continue;
+            };
+            let (cpu, memory) = taken.entry(target.id.clone()).or_default();
+            if *cpu + candidate.cpu_cores > target.available_cpu || *memory + candidate.memory_gb > target.available_memory_gb {
+                continue;
+            }
+            *cpu += candidate.cpu_cores;
+            *memory += candidate.memory_gb;
+            plan.net_savings_usd += candidate.savings_usd;
+            plan.migrations.push(candidate.migration);
+        }
+        Ok(plan)
+    }
+}
+
+/// Re-evaluate running services after every price or node change, forever
+pub async fn run_repricer(scheduler: EconomicScheduler, config: RepricingConfig) {
+    let changed = scheduler.price_changes.clone();
+    loop {
+        changed.notified().await;
+        tokio::time::sleep(config.settle).await;
+
+        let plan = match scheduler.plan_repricing(&config) {
+            Ok(plan) => plan,
+            Err(e) => {
+                tracing::warn!("Repricing pass failed: {}", e);
+                continue;
+            }
+        };
+        if plan.migrations.is_empty() {
+            tracing::debug!("Repricing: every running service is still well placed");
+            continue;
+        }
+
+        tracing::info!(
+            "Repricing{}: {} migrations save ${:.4}",
+            if config.report_only { " (report only)" } else { "" },
+            plan.migrations.len(),
+            plan.net_savings_usd
+        );
+        for migration in &plan.migrations {
+            tracing::info!("  {}: {} -> {}", migration.job_id, migration.from_node, migration.to_node);
+        }
+        if let Err(e) = scheduler.apply_rebalance(&plan, config.report_only) {
+            tracing::warn!("Failed to apply repricing plan: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{JobSpec, NodeInfo, ResourceRequirements, SlaConstraints};
+    use crate::test_support::node;
+
+    fn service(id: &str, movable: bool) -> JobSpec {
+        JobSpec {
+            id: id.to_string(),
+            resources: ResourceRequirements { cpu_cores: 2, memory_gb: 4, ..Default::default() },
+            sla: SlaConstraints { max_latency_ms: 1_000, ..Default::default() },
+            estimated_duration_hours: Some(720.0),
+            job_data: id.as_bytes().to_vec(),
+            movable,
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_price_cut_moves_running_services_within_budget() {
+        let scheduler = EconomicScheduler::new();
+        scheduler.register_node(NodeInfo { provider: "acme".to_string(), ..node("current", 0.50) }).unwrap();
+        for (id, movable) in [("api", true), ("db", false), ("web", true)] {
+            scheduler.schedule(service(id, movable)).await.unwrap();
+            scheduler.update_job_state(id.to_string(), JobStatus::Running, None).unwrap();
+        }
+
+        // Nothing cheaper yet
+        let config = RepricingConfig { max_migrations: 1, ..Default::default() };
+        assert!(scheduler.plan_repricing(&config).unwrap().migrations.is_empty());
+
+        // A cheaper node joins and a provider cuts its price further
+        scheduler.register_node(NodeInfo { provider: "acme".to_string(), ..node("rival", 0.40) }).unwrap();
+        scheduler.set_node_price("acme", "rival", 0.10).unwrap();
+        let plan = scheduler.plan_repricing(&config).unwrap();
+        assert_eq!(plan.migrations.len(), 1, "disruption budget");
+        assert_eq!(plan.migrations[0].job_id, "api");
+        assert_eq!(plan.migrations[0].to_node, "rival");
+        assert!(plan.net_savings_usd > 9.0);
+
+        // The pinned database stays; savings below the threshold are ignored
+        let config = RepricingConfig { max_migrations: 5, min_savings_usd: 100.0, ..Default::default() };
+        assert!(scheduler.plan_repricing(&config).unwrap().migrations.is_empty());
+
+        scheduler.apply_rebalance(&plan, false).unwrap();
+        assert_eq!(scheduler.get_job_state("api").unwrap().assigned_node.as_deref(), Some("rival"));
+    }
+}
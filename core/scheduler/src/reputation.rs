@@ -0,0 +1,305 @@
+//! Node reputation and reliability scoring
+//!
+//! Cheap community nodes vary widely in quality. Each node's record tracks
+//! how often its jobs fail, how often its resource reports go missing, and
+//! how far its run times stray from the scheduler's estimates. These
+//! combine into a reliability score in [0, 1]. Placement ranks candidates
+//! by TCO scaled by `1 + weight * (1 - reliability)`, so an unreliable node
+//! has to be correspondingly cheaper to win.
+//!
+//! New nodes start fully reliable; every rate is smoothed with a prior of
+//! `PRIOR_OBSERVATIONS` good observations so one early failure does not
+//! exclude a node.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use crate::{EconomicScheduler, JobStatus};
+
+/// Good observations assumed for every node before any are recorded
+pub const PRIOR_OBSERVATIONS: f64 = 5.0;
+
+/// Weight of the latest run in the estimate-error moving average
+const ESTIMATE_ERROR_ALPHA: f64 = 0.3;
+
+/// How strongly reliability affects placement
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReputationConfig {
+    /// Score multiplier per unit of unreliability (0 disables the penalty)
+    pub weight: f64,
+    /// Resource report interval workers are expected to keep
+    pub report_interval_secs: u64,
+}
+
+impl Default for ReputationConfig {
+    fn default() -> Self {
+        Self {
+            weight: 1.0,
+            report_interval_secs: 10,
+        }
+    }
+}
+
+/// Observed reliability of one node
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NodeReputation {
+    pub node_id: String,
+    pub jobs_finished: u32,
+    pub jobs_failed: u32,
+    /// Smoothed share of finished jobs that failed
+    pub failure_rate: f64,
+    /// Smoothed share of expected resource reports that never arrived
+    pub flakiness: f64,
+    /// Moving average of |actual / estimated run time - 1|, capped at 1
+    pub estimate_error: f64,
+    pub verifications: u32,
+    /// Smoothed share of verifications whose outputs disagreed
+    pub disagreement_rate: f64,
+    /// Some verification of this node's results disagreed
+    pub flagged: bool,
+    /// Combined score: 1 is fully reliable
+    pub reliability: f64,
+}
+
+#[derive(Debug, Clone, Default)]
+struct NodeRecord {
+    jobs_finished: u32,
+    jobs_failed: u32,
+    reports: u32,
+    missed_reports: u32,
+    last_report: Option<i64>,
+    estimate_error: Option<f64>,
+    verifications: u32,
+    disagreements: u32,
+}
+
+impl NodeRecord {
+    fn reputation(&self, node_id: &str) -> NodeReputation {
+        let failure_rate = self.jobs_failed as f64 / (self.jobs_finished as f64 + PRIOR_OBSERVATIONS);
+        let flakiness = self.missed_reports as f64
+            / ((self.reports + self.missed_reports) as f64 + PRIOR_OBSERVATIONS);
+        let estimate_error = self.estimate_error.unwrap_or(0.0);
+        let disagreement_rate = self.disagreements as f64 / (self.verifications as f64 + PRIOR_OBSERVATIONS);
+        let reliability = (1.0 - failure_rate)
+            * (1.0 - flakiness)
+            * (1.0 - 0.5 * estimate_error)
+            * (1.0 - disagreement_rate);
+        NodeReputation {
+            node_id: node_id.to_string(),
+            jobs_finished: self.jobs_finished,
+            jobs_failed: self.jobs_failed,
+            failure_rate,
+            flakiness,
+            estimate_error,
+            verifications: self.verifications,
+            disagreement_rate,
+            flagged: self.disagreements > 0,
+            reliability: reliability.clamp(0.0, 1.0),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct RunningJob {
+    node_id: String,
+    started_at: i64,
+    expected_hours: Option<f64>,
+}
+
+/// Per-node reliability records
+#[derive(Debug, Clone, Default)]
+pub struct ReputationTracker {
+    config: ReputationConfig,
+    records: Arc<Mutex<HashMap<String, NodeRecord>>>,
+    running: Arc<Mutex<HashMap<String, RunningJob>>>,
+}
+
+impl ReputationTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Count a resource report, and the reports missed since the last one
+    pub fn record_report(&self, node_id: &str, now: i64) {
+        let interval = self.config.report_interval_secs.max(1) as i64;
+        if let Ok(mut records) = self.records.lock() {
+            let record = records.entry(node_id.to_string()).or_default();
+            if let Some(last) = record.last_report {
+                // One late report is jitter; a gap of two intervals or more is not
+                let gap = now - last;
+                if gap >= 2 * interval {
+                    record.missed_reports += (gap / interval - 1) as u32;
+                }
+            }
+            record.reports += 1;
+            record.last_report = Some(now);
+        }
+    }
+
+    /// When `node_id` last reported its resources
+    pub fn last_report(&self, node_id: &str) -> Option<i64> {
+        self.records.lock().ok()?.get(node_id)?.last_report
+    }
+
+    /// Start timing a job on `node_id`; `expected_hours` is the run time
+    /// the scheduler planned for, if it had an estimate
+    pub fn job_started(&self, job_id: &str, node_id: &str, expected_hours: Option<f64>, now: i64) {
+        if let Ok(mut running) = self.running.lock() {
+            running.insert(job_id.to_string(), RunningJob {
+                node_id: node_id.to_string(),
+                started_at: now,
+                expected_hours,
+            });
+        }
+    }
+
+    /// Count a finished job against its node
+    pub fn job_finished(&self, job_id: &str, node_id: &str, succeeded: bool, now: i64) {
+        let started = self.running.lock().ok().and_then(|mut running| running.remove(job_id));
+        let Ok(mut records) = self.records.lock() else {
+            return;
+        };
+        let record = records.entry(node_id.to_string()).or_default();
+        record.jobs_finished += 1;
+        if !succeeded {
+            record.jobs_failed += 1;
+            return;
+        }
+
+        let Some(run) = started.filter(|run| run.node_id == node_id) else {
+            return;
+        };
+        if let Some(expected) = run.expected_hours.filter(|hours| *hours > 0.0) {
+            let actual = (now - run.started_at).max(0) as f64 / 3600.0;
+            let error = (actual / expected - 1.0).abs().min(1.0);
+            record.estimate_error = Some(match record.estimate_error {
+                Some(previous) => previous + ESTIMATE_ERROR_ALPHA * (error - previous),
+                None => error,
+            });
+        }
+    }
+
+    /// Count a redundant-execution check of the node's output
+    pub fn record_verification(&self, node_id: &str, agreed: bool) {
+        if let Ok(mut records) = self.records.lock() {
+            let record = records.entry(node_id.to_string()).or_default();
+            record.verifications += 1;
+            if !agreed {
+                record.disagreements += 1;
+            }
+        }
+    }
+
+    /// Forget a job that ended without a verdict on its node (cancelled)
+    pub fn job_abandoned(&self, job_id: &str) {
+        if let Ok(mut running) = self.running.lock() {
+            running.remove(job_id);
+        }
+    }
+
+    pub fn reputation(&self, node_id: &str) -> NodeReputation {
+        self.records.lock()
+            .ok()
+            .and_then(|records| records.get(node_id).map(|record| record.reputation(node_id)))
+            .unwrap_or_else(|| NodeRecord::default().reputation(node_id))
+    }
+
+    /// Every node with a record, least reliable first
+    pub fn reputations(&self) -> Vec<NodeReputation> {
+        let mut reputations: Vec<NodeReputation> = self.records.lock()
+            .map(|records| records.iter().map(|(id, record)| record.reputation(id)).collect())
+            .unwrap_or_default();
+        reputations.sort_by(|a, b| a.reliability.total_cmp(&b.reliability).then_with(|| a.node_id.cmp(&b.node_id)));
+        reputations
+    }
+
+    /// Multiplier applied to a placement's TCO on `node_id` when ranking
+    pub fn penalty(&self, node_id: &str) -> f64 {
+        if self.config.weight <= 0.0 {
+            return 1.0;
+        }
+        1.0 + self.config.weight * (1.0 - self.reputation(node_id).reliability)
+    }
+}
+
+impl EconomicScheduler {
+    pub fn set_reputation_config(&mut self, config: ReputationConfig) {
+        self.reputation.config = config;
+    }
+
+    pub fn reputation(&self) -> &ReputationTracker {
+        &self.reputation
+    }
+
+    /// Feed a job status change into its node's record
+    pub(crate) fn observe_for_reputation(&self, job_id: &str, status: &JobStatus, assigned_node: Option<&str>, now: i64) {
+        let Some(node_id) = assigned_node
+            .map(str::to_string)
+            .or_else(|| self.get_job_state(job_id).and_then(|state| state.assigned_node))
+        else {
+            return;
+        };
+
+        match status {
+            JobStatus::Running => {
+                let spec = self.placed_jobs.lock().ok().and_then(|placed| placed.get(job_id).cloned());
+                let expected_hours = spec.and_then(|spec| {
+                    let planned = spec.estimated_duration_hours
+                        .filter(|hours| *hours > 0.0)
+                        .or_else(|| self.predict_job(&spec).map(|p| p.duration_hours))?;
+                    let nodes = self.available_nodes.lock().ok()?;
+                    let performance = nodes.get(&node_id).map(|node| node.performance()).unwrap_or(1.0);
+                    Some(planned / performance)
+                });
+                self.reputation.job_started(job_id, &node_id, expected_hours, now);
+            }
+            JobStatus::Completed => self.reputation.job_finished(job_id, &node_id, true, now),
+            JobStatus::Failed => self.reputation.job_finished(job_id, &node_id, false, now),
+            JobStatus::Cancelled => self.reputation.job_abandoned(job_id),
+            JobStatus::Pending | JobStatus::Scheduled => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{JobSpec, NodeInfo, SlaConstraints};
+
+    #[tokio::test]
+    async fn test_unreliable_node_loses_to_slightly_dearer_reliable_node() {
+        let scheduler = EconomicScheduler::new();
+        for (id, cost_per_hour) in [("flaky", 0.10), ("steady", 0.11)] {
+            scheduler.register_node(NodeInfo {
+                id: id.to_string(),
+                available_cpu: 16,
+                available_memory_gb: 64,
+                location: "vps-1".to_string(),
+                cost_per_hour,
+                ..Default::default()
+            }).unwrap();
+        }
+
+        // Three of five jobs failed and half the reports went missing
+        let tracker = scheduler.reputation();
+        for i in 0..5 {
+            tracker.job_finished(&format!("old-{}", i), "flaky", i >= 3, 0);
+        }
+        for at in [0, 10, 40, 50, 80] {
+            tracker.record_report("flaky", at);
+        }
+        let flaky = tracker.reputation("flaky");
+        assert_eq!((flaky.jobs_finished, flaky.jobs_failed), (5, 3));
+        assert!(flaky.reliability < 0.6);
+        assert_eq!(tracker.reputation("steady").reliability, 1.0);
+
+        let job = JobSpec {
+            id: "job-1".to_string(),
+            sla: SlaConstraints { max_latency_ms: 1_000, ..Default::default() },
+            ..Default::default()
+        };
+        let placement = scheduler.schedule(job).await.unwrap();
+        assert_eq!(placement.node_id, "steady");
+    }
+}
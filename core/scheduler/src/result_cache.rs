@@ -0,0 +1,218 @@
+//! Content-addressed result cache (job deduplication)
+//!
+//! Jobs are keyed by SHA-256 over image, command, input payload, how the
+//! payload is delivered, input datasets with their current content digest,
+//! and resources. When an identical job has already completed successfully,
+//! the scheduler can return that result instead of paying C_comp again.
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tgp_cost_engine::TotalCost;
+
+use crate::datasets::DatasetRegistry;
+use crate::JobSpec;
+
+/// Upper bound on cached results; oldest entries are evicted first
+const MAX_ENTRIES: usize = 10_000;
+
+/// Successful job result available for reuse
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CachedResult {
+    /// Job that actually produced the result
+    pub source_job_id: String,
+    /// Node the source job ran on
+    pub node_id: String,
+    /// Cost paid by the source job (saved by every cache hit)
+    pub cost: TotalCost,
+    /// Unix timestamp of completion
+    pub completed_at: i64,
+}
+
+/// Thread-safe result cache shared by scheduler clones
+#[derive(Debug, Clone, Default)]
+pub struct ResultCache {
+    /// Completed results by cache key
+    entries: Arc<Mutex<HashMap<String, CachedResult>>>,
+    /// Cache key of jobs that are scheduled but not yet completed
+    in_flight: Arc<Mutex<HashMap<String, String>>>,
+}
+
+/// Compute the content address of a job
+///
+/// Only fields that influence the job's output are hashed; SLA and job id
+/// are deliberately excluded. Datasets are hashed with the digest of their
+/// latest version in `datasets`, so a result is not reused once the data
+/// it was computed from has changed.
+pub fn cache_key(job: &JobSpec, datasets: &DatasetRegistry) -> String {
+    let mut hasher = Sha256::new();
+
+    hasher.update(job.container_image.as_bytes());
+    hasher.update([0u8]);
+    for arg in &job.command {
+        hasher.update(arg.as_bytes());
+        hasher.update([0u8]);
+    }
+    hasher.update((job.job_data.len() as u64).to_le_bytes());
+    hasher.update(&job.job_data);
+    hasher.update([job.input_stdin as u8]);
+    if let Some(digest) = &job.payload_digest {
+        hasher.update(digest.as_bytes());
+        hasher.update([0u8]);
+    }
+    if let Some(path) = &job.output_path {
+        hasher.update(path.as_bytes());
+        hasher.update([0u8]);
+    }
+
+    let mut env: Vec<_> = job.env.iter().collect();
+    env.sort();
+    for (name, value) in env {
+        hasher.update(name.as_bytes());
+        hasher.update([0u8]);
+        hasher.update(value.as_bytes());
+        hasher.update([0u8]);
+    }
+
+    let mut names: Vec<_> = job.datasets.iter().collect();
+    names.sort();
+    names.dedup();
+    for name in names {
+        hasher.update(name.as_bytes());
+        hasher.update([0u8]);
+        if let Some(digest) = datasets.get(name).and_then(|info| info.digest) {
+            hasher.update(digest.as_bytes());
+        }
+        hasher.update([0u8]);
+    }
+
+    let r = &job.resources;
+    for value in [r.cpu_cores, r.memory_gb, r.gpu_count, r.disk_gb] {
+        hasher.update(value.to_le_bytes());
+    }
+
+    hasher.finalize()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+impl ResultCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Look up a completed result for this job
+    pub fn lookup(&self, key: &str) -> Option<CachedResult> {
+        self.entries.lock()
+            .ok()
+            .and_then(|entries| entries.get(key).cloned())
+    }
+
+    /// Remember the cache key of a job that is about to run
+    pub fn track(&self, job_id: &str, key: String) {
+        if let Ok(mut in_flight) = self.in_flight.lock() {
+            in_flight.insert(job_id.to_string(), key);
+        }
+    }
+
+    /// Store the result of a successfully completed job
+    pub fn complete(&self, job_id: &str, node_id: &str, cost: TotalCost, completed_at: i64) {
+        let key = match self.in_flight.lock().ok().and_then(|mut m| m.remove(job_id)) {
+            Some(key) => key,
+            None => return, // Job opted out of caching
+        };
+
+        if let Ok(mut entries) = self.entries.lock() {
+            if entries.len() >= MAX_ENTRIES && !entries.contains_key(&key) {
+                let oldest = entries.iter()
+                    .min_by_key(|(_, result)| result.completed_at)
+                    .map(|(k, _)| k.clone());
+                if let Some(oldest) = oldest {
+                    entries.remove(&oldest);
+                }
+            }
+
+            entries.insert(key, CachedResult {
+                source_job_id: job_id.to_string(),
+                node_id: node_id.to_string(),
+                cost,
+                completed_at,
+            });
+        }
+    }
+
+    /// Forget a job that did not complete successfully
+    pub fn discard(&self, job_id: &str) {
+        if let Ok(mut in_flight) = self.in_flight.lock() {
+            in_flight.remove(job_id);
+        }
+    }
+
+    /// Number of cached results
+    pub fn len(&self) -> usize {
+        self.entries.lock().map(|e| e.len()).unwrap_or(0)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn job(id: &str, command: &[&str]) -> JobSpec {
+        JobSpec {
+            id: id.to_string(),
+            container_image: "alpine:latest".to_string(),
+            command: command.iter().map(|s| s.to_string()).collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_cache_key_ignores_job_id() {
+        let datasets = DatasetRegistry::new();
+        assert_eq!(cache_key(&job("a", &["echo", "hi"]), &datasets), cache_key(&job("b", &["echo", "hi"]), &datasets));
+        assert_ne!(cache_key(&job("a", &["echo", "hi"]), &datasets), cache_key(&job("a", &["echo hi"]), &datasets));
+    }
+
+    #[test]
+    fn test_cache_key_covers_stdin_and_dataset_versions() {
+        let datasets = DatasetRegistry::new();
+        let file = job("a", &["wc"]);
+        let stdin = JobSpec { input_stdin: true, ..file.clone() };
+        assert_ne!(cache_key(&file, &datasets), cache_key(&stdin, &datasets));
+
+        let reads = JobSpec { datasets: vec!["corpus".to_string()], ..file.clone() };
+        assert_ne!(cache_key(&file, &datasets), cache_key(&reads, &datasets));
+
+        datasets.sync_node_versions("node-1", &[("corpus".to_string(), 1.0, Some("v1".to_string()))]);
+        let before = cache_key(&reads, &datasets);
+        datasets.sync_node_versions("node-1", &[("corpus".to_string(), 1.0, Some("v2".to_string()))]);
+        assert_ne!(before, cache_key(&reads, &datasets));
+    }
+
+    #[test]
+    fn test_only_completed_jobs_are_cached() {
+        let cache = ResultCache::new();
+        let datasets = DatasetRegistry::new();
+        let key = cache_key(&job("a", &["true"]), &datasets);
+
+        cache.track("a", key.clone());
+        assert!(cache.lookup(&key).is_none());
+
+        cache.complete("a", "node-1", TotalCost::new(0.5, 0.0, 0.0), 100);
+        let hit = cache.lookup(&key).unwrap();
+        assert_eq!(hit.source_job_id, "a");
+        assert_eq!(hit.node_id, "node-1");
+
+        cache.track("b", cache_key(&job("b", &["false"]), &datasets));
+        cache.discard("b");
+        cache.complete("b", "node-1", TotalCost::default(), 200);
+        assert_eq!(cache.len(), 1);
+    }
+}
@@ -0,0 +1,263 @@
+//! Right-sizing recommendations for job templates
+//!
+//! Workers meter each job's peak memory and the most CPU cores it kept
+//! busy. When a job instantiated from a template completes, its peaks are
+//! added to that template's recent runs. A template whose 95th-percentile
+//! peaks, with some headroom, stay well under what it requests gets a
+//! recommendation ("requests 8GB, peaks at 1.9GB"). Smaller requests fit
+//! cheaper nodes, so each recommendation is priced as the difference
+//! between the cheapest current placement as requested and as recommended,
+//! which is the C_comp it saves per run.
+
+use anyhow::Result;
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+use crate::error::SchedulerError;
+use crate::{EconomicScheduler, JobStatus};
+
+/// Runs kept per template (oldest are dropped first)
+const MAX_RUNS: usize = 100;
+
+/// Runs needed before a template gets a recommendation
+const MIN_RUNS: usize = 3;
+
+/// Margin kept over the observed peak
+const HEADROOM: f64 = 1.2;
+
+/// Peak use of one run (None: not metered)
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+struct RunUsage {
+    memory_gb: Option<f64>,
+    cpu_cores: Option<f64>,
+}
+
+/// Metered peaks of running jobs and recent runs per template, shared by
+/// scheduler clones
+#[derive(Debug, Clone, Default)]
+pub struct UsageHistory {
+    running: Arc<Mutex<HashMap<String, RunUsage>>>,
+    templates: Arc<Mutex<HashMap<String, VecDeque<RunUsage>>>>,
+}
+
+impl UsageHistory {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Note peaks a job reported; zero means not metered
+    pub fn record_peak(&self, job_id: &str, memory_gb: f64, cpu_cores: f64) {
+        let Ok(mut running) = self.running.lock() else {
+            return;
+        };
+        let usage = running.entry(job_id.to_string()).or_default();
+        if memory_gb > 0.0 {
+            usage.memory_gb = Some(usage.memory_gb.unwrap_or(0.0).max(memory_gb));
+        }
+        if cpu_cores > 0.0 {
+            usage.cpu_cores = Some(usage.cpu_cores.unwrap_or(0.0).max(cpu_cores));
+        }
+    }
+
+    /// Runs recorded for a template
+    pub fn runs(&self, template: &str) -> usize {
+        self.templates.lock().map(|templates| templates.get(template).map_or(0, VecDeque::len)).unwrap_or(0)
+    }
+
+    fn finished(&self, job_id: &str, template: Option<&str>) {
+        let Some(usage) = self.running.lock().ok().and_then(|mut running| running.remove(job_id)) else {
+            return;
+        };
+        let Some(template) = template else {
+            return;
+        };
+        if let Ok(mut templates) = self.templates.lock() {
+            let runs = templates.entry(template.to_string()).or_default();
+            if runs.len() == MAX_RUNS {
+                runs.pop_front();
+            }
+            runs.push_back(usage);
+        }
+    }
+}
+
+/// 95th percentile of the metered values
+fn p95(values: impl Iterator<Item = Option<f64>>) -> Option<f64> {
+    let mut values: Vec<f64> = values.flatten().collect();
+    values.sort_by(f64::total_cmp);
+    (!values.is_empty()).then(|| values[((values.len() - 1) as f64 * 0.95).round() as usize])
+}
+
+/// Request that still leaves headroom over `peak`, never above `requested`
+fn resized(requested: u32, peak: Option<f64>) -> u32 {
+    match peak {
+        Some(peak) => ((peak * HEADROOM).ceil() as u32).max(1).min(requested),
+        None => requested,
+    }
+}
+
+/// Requested versus used resources of a template
+#[derive(Debug, Clone, PartialEq)]
+pub struct RightSizing {
+    pub template: String,
+    /// Completed runs the peaks come from
+    pub runs: usize,
+    pub requested_cpu_cores: u32,
+    pub requested_memory_gb: u32,
+    /// 95th percentile over the runs (None: never metered)
+    pub peak_cpu_cores: Option<f64>,
+    pub peak_memory_gb: Option<f64>,
+    pub recommended_cpu_cores: u32,
+    pub recommended_memory_gb: u32,
+    /// Cheapest placement as requested less as recommended (None: the
+    /// template fits no current node)
+    pub saving_per_run_usd: Option<f64>,
+}
+
+impl fmt::Display for RightSizing {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut parts = Vec::new();
+        if let (true, Some(peak)) = (self.recommended_memory_gb < self.requested_memory_gb, self.peak_memory_gb) {
+            parts.push(format!(
+                "requests {}GB, peaks at {:.1}GB: request {}GB",
+                self.requested_memory_gb, peak, self.recommended_memory_gb
+            ));
+        }
+        if let (true, Some(peak)) = (self.recommended_cpu_cores < self.requested_cpu_cores, self.peak_cpu_cores) {
+            parts.push(format!(
+                "requests {} cores, peaks at {:.1}: request {}",
+                self.requested_cpu_cores, peak, self.recommended_cpu_cores
+            ));
+        }
+        write!(f, "{}", parts.join("; "))
+    }
+}
+
+impl EconomicScheduler {
+    pub fn usage_history(&self) -> &UsageHistory {
+        &self.usage
+    }
+
+    /// Recommendations for every template, or just `template`; templates
+    /// already sized to their use are left out
+    pub fn right_sizing(&self, template: Option<&str>) -> Result<Vec<RightSizing>> {
+        let templates: Vec<_> = self.list_templates()
+            .into_iter()
+            .filter(|t| template.is_none() || template == Some(t.name.as_str()))
+            .collect();
+        if let (Some(name), true) = (template, templates.is_empty()) {
+            return Err(SchedulerError::not_found("Template", name).into());
+        }
+
+        let mut report = Vec::new();
+        for template in templates {
+            let runs: Vec<RunUsage> = match self.usage.templates.lock() {
+                Ok(history) => history.get(&template.name).map(|runs| runs.iter().copied().collect()).unwrap_or_default(),
+                Err(_) => continue,
+            };
+            if runs.len() < MIN_RUNS {
+                continue;
+            }
+
+            let requested = &template.job.resources;
+            let peak_memory_gb = p95(runs.iter().map(|run| run.memory_gb));
+            let peak_cpu_cores = p95(runs.iter().map(|run| run.cpu_cores));
+            let recommended_memory_gb = resized(requested.memory_gb, peak_memory_gb);
+            let recommended_cpu_cores = resized(requested.cpu_cores, peak_cpu_cores);
+            if recommended_memory_gb >= requested.memory_gb && recommended_cpu_cores >= requested.cpu_cores {
+                continue;
+            }
+
+            let mut smaller = template.job.clone();
+            smaller.resources.memory_gb = recommended_memory_gb;
+            smaller.resources.cpu_cores = recommended_cpu_cores;
+            let saving_per_run_usd = match (self.cheapest_placement_usd(&template.job)?, self.cheapest_placement_usd(&smaller)?) {
+                (Some(as_requested), Some(as_recommended)) => Some((as_requested - as_recommended).max(0.0)),
+                _ => None,
+            };
+
+            report.push(RightSizing {
+                template: template.name,
+                runs: runs.len(),
+                requested_cpu_cores: requested.cpu_cores,
+                requested_memory_gb: requested.memory_gb,
+                peak_cpu_cores,
+                peak_memory_gb,
+                recommended_cpu_cores,
+                recommended_memory_gb,
+                saving_per_run_usd,
+            });
+        }
+        Ok(report)
+    }
+
+    /// Add a completed templated job's peaks to its template's runs
+    pub(crate) fn observe_for_rightsizing(&self, job_id: &str, status: &JobStatus) {
+        if !status.is_terminal() {
+            return;
+        }
+        let template = match status {
+            JobStatus::Completed => self.placed_jobs.lock()
+                .ok()
+                .and_then(|placed| placed.get(job_id).and_then(|spec| spec.template.clone())),
+            _ => None,
+        };
+        self.usage.finished(job_id, template.as_deref());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::templates::{JobTemplate, TemplateOverrides};
+    use crate::{JobSpec, NodeInfo, SlaConstraints};
+
+    #[tokio::test]
+    async fn test_oversized_template_gets_cheaper_recommendation() {
+        let scheduler = EconomicScheduler::new();
+        for (id, memory_gb, cost_per_hour) in [("small", 4, 0.5), ("large", 16, 2.0)] {
+            scheduler.register_node(NodeInfo {
+                id: id.to_string(),
+                available_cpu: 8,
+                available_memory_gb: memory_gb,
+                cost_per_hour,
+                ..Default::default()
+            }).unwrap();
+        }
+        let mut job = JobSpec {
+            sla: SlaConstraints { max_latency_ms: 1_000, ..Default::default() },
+            estimated_duration_hours: Some(1.0),
+            disable_result_cache: true,
+            ..Default::default()
+        };
+        job.resources.cpu_cores = 2;
+        job.resources.memory_gb = 8;
+        scheduler.put_template(JobTemplate { name: "embed".to_string(), job, ..Default::default() }, false).unwrap();
+
+        for (i, peak_gb) in [1.5, 1.9, 1.7].into_iter().enumerate() {
+            let job_id = format!("embed-{}", i);
+            let job = scheduler.instantiate_template("embed", &job_id, TemplateOverrides::default()).unwrap();
+            scheduler.schedule(job).await.unwrap();
+            scheduler.usage_history().record_peak(&job_id, peak_gb, 0.0);
+            scheduler.update_job_state(job_id, JobStatus::Completed, None).unwrap();
+        }
+        // Failed runs do not count
+        scheduler.usage_history().record_peak("embed-x", 7.0, 0.0);
+        scheduler.update_job_state("embed-x".to_string(), JobStatus::Failed, None).unwrap();
+        assert_eq!(scheduler.usage_history().runs("embed"), 3);
+
+        let report = scheduler.right_sizing(Some("embed")).unwrap();
+        assert_eq!(report.len(), 1);
+        let embed = &report[0];
+        assert_eq!(embed.peak_memory_gb, Some(1.9));
+        assert_eq!(embed.recommended_memory_gb, 3);
+        // CPU was not metered: left as requested
+        assert_eq!(embed.recommended_cpu_cores, 2);
+        assert!(embed.to_string().starts_with("requests 8GB, peaks at 1.9GB: request 3GB"));
+        // As requested only the large node fits; resized, the small one does
+        assert!((embed.saving_per_run_usd.unwrap() - 1.5).abs() < 1e-9);
+
+        assert!(scheduler.right_sizing(Some("missing")).is_err());
+    }
+}
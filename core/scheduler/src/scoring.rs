@@ -0,0 +1,177 @@
+//! Operator scoring scripts
+//!
+//! An operator can supply a small Rhai script that returns an extra score
+//! term (in USD) for each candidate node, added to the reliability-adjusted
+//! TCO before candidates are compared. Positive values discourage a node,
+//! negative values favour it. The script sees `node` and `job` (the
+//! `NodeInfo` and `JobSpec` fields) and `cost` (the node's estimated TCO):
+//!
+//! ```text
+//! if node.preemptible && job.priority == "High" { cost } else { 0.0 }
+//! ```
+//!
+//! Scripts are reloaded when their file changes; a script that fails to
+//! compile is logged and the previous one stays in effect.
+
+use anyhow::Result;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+use crate::{EconomicScheduler, JobSpec, NodeInfo};
+
+/// Operations a script may run per node before it is aborted
+pub const MAX_OPERATIONS: u64 = 100_000;
+
+/// A compiled scoring script
+pub struct ScoringScript {
+    #[cfg(feature = "scripting")]
+    engine: rhai::Engine,
+    #[cfg(feature = "scripting")]
+    ast: rhai::AST,
+}
+
+impl ScoringScript {
+    pub fn compile(source: &str) -> Result<Self> {
+        #[cfg(feature = "scripting")]
+        {
+            let mut engine = rhai::Engine::new();
+            engine.set_max_operations(MAX_OPERATIONS);
+            let ast = engine.compile(source)
+                .map_err(|e| anyhow::anyhow!("Scoring script does not compile: {}", e))?;
+            Ok(Self { engine, ast })
+        }
+        #[cfg(not(feature = "scripting"))]
+        {
+            let _ = source;
+            anyhow::bail!("Scripting support not compiled in (enable the `scripting` feature)")
+        }
+    }
+
+    /// Extra score term of `node` for `job`
+    pub fn score(&self, node: &NodeInfo, job: &JobSpec, cost_usd: f64) -> Result<f64> {
+        #[cfg(feature = "scripting")]
+        {
+            let mut scope = rhai::Scope::new();
+            scope.push_dynamic("node", rhai::serde::to_dynamic(node)?);
+            scope.push_dynamic("job", rhai::serde::to_dynamic(job)?);
+            scope.push("cost", cost_usd);
+            let value: rhai::Dynamic = self.engine.eval_ast_with_scope(&mut scope, &self.ast)
+                .map_err(|e| anyhow::anyhow!("Scoring script failed: {}", e))?;
+            value.as_float()
+                .or_else(|_| value.as_int().map(|v| v as f64))
+                .map_err(|kind| anyhow::anyhow!("Scoring script returned {}, expected a number", kind))
+        }
+        #[cfg(not(feature = "scripting"))]
+        {
+            let _ = (node, job, cost_usd);
+            Ok(0.0)
+        }
+    }
+}
+
+/// The scoring script in effect, shared by scheduler clones
+#[derive(Clone, Default)]
+pub struct CustomScoring {
+    script: Arc<Mutex<Option<Arc<ScoringScript>>>>,
+}
+
+impl CustomScoring {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Replace the script (None: no extra term)
+    pub fn set(&self, script: Option<ScoringScript>) {
+        if let Ok(mut current) = self.script.lock() {
+            *current = script.map(Arc::new);
+        }
+    }
+
+    /// Extra score term of `node` for `job`; 0 without a script or when
+    /// the script fails
+    pub fn term(&self, node: &NodeInfo, job: &JobSpec, cost_usd: f64) -> f64 {
+        let Some(script) = self.script.lock().ok().and_then(|current| current.clone()) else {
+            return 0.0;
+        };
+        match script.score(node, job, cost_usd) {
+            Ok(term) if term.is_finite() => term,
+            Ok(term) => {
+                tracing::warn!("Scoring script returned {} for node {}, ignoring", term, node.id);
+                0.0
+            }
+            Err(e) => {
+                tracing::warn!("{} (node {}, job {})", e, node.id, job.id);
+                0.0
+            }
+        }
+    }
+}
+
+impl EconomicScheduler {
+    pub fn custom_scoring(&self) -> &CustomScoring {
+        &self.scoring
+    }
+}
+
+/// Load the script at `path`, then reload it whenever the file changes
+pub async fn run_script_reloader(scheduler: EconomicScheduler, path: PathBuf, interval: Duration) {
+    let mut loaded: Option<SystemTime> = None;
+    let mut ticker = tokio::time::interval(interval);
+    loop {
+        ticker.tick().await;
+        let modified = match std::fs::metadata(&path).and_then(|meta| meta.modified()) {
+            Ok(modified) => modified,
+            Err(e) => {
+                tracing::warn!("Cannot read scoring script {}: {}", path.display(), e);
+                continue;
+            }
+        };
+        if loaded == Some(modified) {
+            continue;
+        }
+        loaded = Some(modified);
+
+        match std::fs::read_to_string(&path).map_err(anyhow::Error::from).and_then(|source| ScoringScript::compile(&source)) {
+            Ok(script) => {
+                scheduler.custom_scoring().set(Some(script));
+                tracing::info!("Loaded scoring script {}", path.display());
+            }
+            Err(e) => tracing::error!("Keeping previous scoring script, {} failed: {}", path.display(), e),
+        }
+    }
+}
+
+#[cfg(all(test, feature = "scripting"))]
+mod tests {
+    use super::*;
+    use crate::SlaConstraints;
+
+    #[tokio::test]
+    async fn test_script_term_steers_placement() {
+        let scheduler = EconomicScheduler::new();
+        for (id, cost_per_hour, preemptible) in [("spot", 0.1, true), ("steady", 0.2, false)] {
+            scheduler.register_node(NodeInfo {
+                id: id.to_string(),
+                available_cpu: 16,
+                available_memory_gb: 64,
+                location: "vps-1".to_string(),
+                cost_per_hour,
+                preemptible,
+                ..Default::default()
+            }).unwrap();
+        }
+        scheduler.custom_scoring().set(Some(ScoringScript::compile(
+            r#"if node.preemptible && job.tenant == "prod" { 1.0 } else { 0 }"#,
+        ).unwrap()));
+
+        let job = |id: &str, tenant: &str| JobSpec {
+            id: id.to_string(),
+            tenant: tenant.to_string(),
+            sla: SlaConstraints { max_latency_ms: 1_000, ..Default::default() },
+            ..Default::default()
+        };
+        assert_eq!(scheduler.schedule(job("job-1", "lab")).await.unwrap().node_id, "spot");
+        assert_eq!(scheduler.schedule(job("job-2", "prod")).await.unwrap().node_id, "steady");
+    }
+}
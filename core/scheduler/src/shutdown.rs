@@ -0,0 +1,151 @@
+//! Graceful shutdown
+//!
+//! On SIGTERM or Ctrl-C the scheduler drains: new submissions are refused
+//! with UNAVAILABLE, queue dispatchers stop claiming, workers are told
+//! through their next resource ack so they reconnect elsewhere, and a
+//! `SchedulerShuttingDown` event is published. Placements already in
+//! progress get the grace period to finish, then queued state writes are
+//! flushed to the store before the gRPC server stops.
+
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Notify;
+
+use crate::events::SchedulerEvent;
+use crate::{unix_now, EconomicScheduler};
+
+#[derive(Debug, Default)]
+struct DrainState {
+    draining: AtomicBool,
+    in_flight: AtomicUsize,
+    idle: Notify,
+}
+
+/// Drain flag and in-flight placement count shared by scheduler clones
+#[derive(Debug, Clone, Default)]
+pub struct Drain {
+    state: Arc<DrainState>,
+}
+
+impl Drain {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+/// Held while a placement is in progress
+pub struct InFlight {
+    state: Arc<DrainState>,
+}
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        if self.state.in_flight.fetch_sub(1, Ordering::SeqCst) == 1 {
+            self.state.idle.notify_waiters();
+        }
+    }
+}
+
+impl EconomicScheduler {
+    pub fn is_draining(&self) -> bool {
+        self.drain.state.draining.load(Ordering::SeqCst)
+    }
+
+    /// Start a placement; None once draining has begun
+    pub fn begin_placement(&self) -> Option<InFlight> {
+        let state = &self.drain.state;
+        state.in_flight.fetch_add(1, Ordering::SeqCst);
+        let guard = InFlight { state: state.clone() };
+        // Checked after counting so `shutdown` cannot miss this placement
+        if state.draining.load(Ordering::SeqCst) {
+            return None;
+        }
+        Some(guard)
+    }
+
+    /// Drain, wait up to `grace` for in-flight placements, then flush
+    /// state; returns whether everything finished within the grace period
+    pub async fn shutdown(&self, grace: Duration) -> bool {
+        let state = &self.drain.state;
+        if state.draining.swap(true, Ordering::SeqCst) {
+            return true;
+        }
+        tracing::info!("Scheduler draining (grace period {}s)", grace.as_secs());
+        self.publish_event(SchedulerEvent::SchedulerShuttingDown { timestamp: unix_now() });
+
+        let deadline = tokio::time::Instant::now() + grace;
+        let drained = tokio::time::timeout_at(deadline, async {
+            loop {
+                let idle = state.idle.notified();
+                if state.in_flight.load(Ordering::SeqCst) == 0 {
+                    break;
+                }
+                idle.await;
+            }
+        })
+        .await
+        .is_ok();
+        if !drained {
+            tracing::warn!(
+                "{} placements still in progress after the grace period",
+                state.in_flight.load(Ordering::SeqCst)
+            );
+        }
+
+        let flushed = match tokio::time::timeout_at(deadline, self.flush_state()).await {
+            Ok(Ok(())) => true,
+            Ok(Err(e)) => {
+                tracing::error!("Failed to flush state: {}", e);
+                false
+            }
+            Err(_) => {
+                tracing::error!("State flush did not finish within the grace period");
+                false
+            }
+        };
+        tracing::info!("Scheduler drained");
+        drained && flushed
+    }
+}
+
+/// Resolve on SIGTERM or Ctrl-C
+pub async fn wait_for_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        match signal(SignalKind::terminate()) {
+            Ok(mut term) => {
+                tokio::select! {
+                    _ = term.recv() => tracing::info!("Received SIGTERM"),
+                    _ = tokio::signal::ctrl_c() => tracing::info!("Received Ctrl-C"),
+                }
+                return;
+            }
+            Err(e) => tracing::warn!("Cannot listen for SIGTERM: {}", e),
+        }
+    }
+    let _ = tokio::signal::ctrl_c().await;
+    tracing::info!("Received Ctrl-C");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_shutdown_waits_for_in_flight_placement() {
+        let scheduler = EconomicScheduler::new();
+        let placement = scheduler.begin_placement().unwrap();
+
+        let draining = scheduler.clone();
+        let shutdown = tokio::spawn(async move { draining.shutdown(Duration::from_secs(5)).await });
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert!(scheduler.is_draining());
+        assert!(scheduler.begin_placement().is_none());
+        assert!(!shutdown.is_finished());
+
+        drop(placement);
+        assert!(shutdown.await.unwrap());
+    }
+}
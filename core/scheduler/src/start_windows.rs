@@ -0,0 +1,217 @@
+//! Calendar-based future scheduling
+//!
+//! A job submitted with `earliest_start` (alongside its deadline) is
+//! accepted now but only runs inside its window, e.g. overnight off-peak.
+//! Until the window opens the job stays Pending and is held here; a
+//! background task places it once `earliest_start` has passed. A job with a
+//! deadline also gets its resources reserved for its tenant from
+//! `earliest_start` to the deadline (see `tenant_reservations`), so other
+//! tenants' work cannot take the capacity it needs to finish in time. The
+//! reservation is released once the job is placed.
+
+use anyhow::Result;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::error::SchedulerError;
+use crate::events::SchedulerEvent;
+use crate::tenant_reservations::CapacityBlock;
+use crate::{unix_now, EconomicScheduler, JobSpec, JobStatus};
+
+/// Furthest ahead a start window may open
+pub const MAX_START_AHEAD_SECS: i64 = 90 * 24 * 3600;
+
+/// How often held jobs are checked for an open window
+const START_INTERVAL: Duration = Duration::from_secs(10);
+
+/// A job waiting for its window
+#[derive(Debug, Clone)]
+pub struct WaitingJob {
+    pub job: JobSpec,
+    pub start_at: i64,
+    /// Capacity block held for the job, if it has a deadline
+    pub reservation: Option<String>,
+}
+
+/// Jobs held until their start window, shared by scheduler clones
+#[derive(Debug, Clone, Default)]
+pub struct StartWindows {
+    waiting: Arc<Mutex<HashMap<String, WaitingJob>>>,
+}
+
+impl StartWindows {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Jobs waiting for their window, earliest first
+    pub fn waiting(&self) -> Vec<WaitingJob> {
+        let mut waiting: Vec<WaitingJob> = self.waiting.lock()
+            .map(|waiting| waiting.values().cloned().collect())
+            .unwrap_or_default();
+        waiting.sort_by(|a, b| a.start_at.cmp(&b.start_at).then_with(|| a.job.id.cmp(&b.job.id)));
+        waiting
+    }
+
+    /// When a waiting job's window opens
+    pub fn start_of(&self, job_id: &str) -> Option<i64> {
+        self.waiting.lock().ok()?.get(job_id).map(|waiting| waiting.start_at)
+    }
+
+    fn take_due(&self, now: i64) -> Vec<WaitingJob> {
+        let Ok(mut waiting) = self.waiting.lock() else {
+            return Vec::new();
+        };
+        let due: Vec<String> = waiting.values()
+            .filter(|waiting| waiting.start_at <= now)
+            .map(|waiting| waiting.job.id.clone())
+            .collect();
+        let mut jobs: Vec<WaitingJob> = due.iter().filter_map(|job_id| waiting.remove(job_id)).collect();
+        jobs.sort_by_key(|waiting| waiting.start_at);
+        jobs
+    }
+}
+
+impl EconomicScheduler {
+    pub fn start_windows(&self) -> &StartWindows {
+        &self.start_windows
+    }
+
+    /// Refuse a start window that never opens in time or opens too far ahead
+    pub fn check_start_window(&self, job: &JobSpec) -> Result<()> {
+        let Some(start_at) = job.sla.earliest_start else {
+            return Ok(());
+        };
+        if job.sla.deadline.is_some_and(|deadline| deadline <= start_at) {
+            return Err(SchedulerError::invalid_spec(format!(
+                "Job {} has its deadline before its earliest start {}", job.id, start_at
+            )).into());
+        }
+        if start_at > unix_now() + MAX_START_AHEAD_SECS {
+            return Err(SchedulerError::invalid_spec(format!(
+                "Job {} starts more than {} days ahead", job.id, MAX_START_AHEAD_SECS / 86_400
+            )).into());
+        }
+        Ok(())
+    }
+
+    /// Hold a job until `start_at`, reserving its capacity if it has a deadline
+    pub(crate) fn defer_until(&self, job: JobSpec, start_at: i64) -> Result<()> {
+        let reservation = match job.sla.deadline {
+            Some(deadline) if !job.tenant.is_empty() => {
+                let members = job.gang_size.max(1);
+                let block = CapacityBlock {
+                    id: format!("start:{}", job.id),
+                    tenant: job.tenant.clone(),
+                    cpu_cores: job.resources.cpu_cores * members,
+                    memory_gb: job.resources.memory_gb * members,
+                    gpus: job.resources.gpu_count * members,
+                    start: start_at,
+                    end: deadline,
+                };
+                let id = block.id.clone();
+                match self.reserve_capacity(block) {
+                    Ok(()) => Some(id),
+                    Err(e) => {
+                        tracing::warn!("Could not reserve capacity ahead for job {}: {}", job.id, e);
+                        None
+                    }
+                }
+            }
+            _ => None,
+        };
+
+        tracing::info!("Job {} held until its start window opens at {}", job.id, start_at);
+        self.publish_event(SchedulerEvent::JobDeferred {
+            job_id: job.id.clone(),
+            start_at,
+            timestamp: unix_now(),
+        });
+        self.start_windows.waiting.lock()
+            .map_err(|e| anyhow::anyhow!("Lock poisoned: {}", e))?
+            .insert(job.id.clone(), WaitingJob { job, start_at, reservation });
+        Ok(())
+    }
+
+    /// Place held jobs whose window has opened
+    pub async fn start_due_jobs(&self, now: i64) {
+        for waiting in self.start_windows.take_due(now) {
+            let mut job = waiting.job;
+            let job_id = job.id.clone();
+            // The window is open: place the job as of now
+            job.sla.earliest_start = None;
+            // Cancelled while held
+            if self.get_job_state(&job_id).is_some_and(|state| state.status == JobStatus::Pending) {
+                match self.place(job).await {
+                    Ok(placement) => tracing::info!("Job {} started on {} as its window opened", job_id, placement.node_id),
+                    Err(e) => tracing::warn!("Job {} could not be placed as its window opened: {}", job_id, e),
+                }
+            }
+            if let Some(id) = waiting.reservation {
+                if let Err(e) = self.cancel_capacity_block(&id) {
+                    tracing::debug!("Reservation of job {} already gone: {}", job_id, e);
+                }
+            }
+        }
+    }
+}
+
+/// Start held jobs as their windows open, forever
+pub async fn run_start_windows(scheduler: EconomicScheduler) {
+    let mut ticker = tokio::time::interval(START_INTERVAL);
+    loop {
+        ticker.tick().await;
+        scheduler.start_due_jobs(unix_now()).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{NodeInfo, ResourceRequirements, SlaConstraints};
+
+    #[tokio::test]
+    async fn test_job_waits_for_window_with_capacity_reserved() {
+        let scheduler = EconomicScheduler::new();
+        scheduler.register_node(NodeInfo {
+            id: "node-1".to_string(),
+            available_cpu: 8,
+            available_memory_gb: 32,
+            cost_per_hour: 0.5,
+            ..Default::default()
+        }).unwrap();
+        let now = unix_now();
+        let job = |id: &str, tenant: &str, earliest_start: Option<i64>| JobSpec {
+            id: id.to_string(),
+            tenant: tenant.to_string(),
+            resources: ResourceRequirements { cpu_cores: 6, memory_gb: 4, ..Default::default() },
+            sla: SlaConstraints {
+                max_latency_ms: 1_000,
+                deadline: Some(now + 8 * 3600),
+                earliest_start,
+                ..Default::default()
+            },
+            disable_result_cache: true,
+            ..Default::default()
+        };
+
+        assert!(scheduler.schedule(job("nightly", "acme", Some(now + 3600))).await.is_err());
+        assert_eq!(scheduler.get_job_state("nightly").unwrap().status, JobStatus::Pending);
+        assert_eq!(scheduler.start_windows().start_of("nightly"), Some(now + 3600));
+
+        // Nothing runs early; once the window opens its capacity is held
+        scheduler.start_due_jobs(now).await;
+        assert_eq!(scheduler.get_job_state("nightly").unwrap().status, JobStatus::Pending);
+        assert!(scheduler.capacity_held_from(&job("web", "lab", None), now + 3600).unwrap().is_some());
+
+        scheduler.start_due_jobs(now + 3600).await;
+        assert_eq!(scheduler.get_job_state("nightly").unwrap().status, JobStatus::Scheduled);
+        assert!(scheduler.start_windows().waiting().is_empty());
+        assert!(scheduler.capacity_block_status(now).unwrap().is_empty());
+
+        let mut backwards = job("late", "acme", Some(now + 60));
+        backwards.sla.deadline = Some(now);
+        assert!(scheduler.check_start_window(&backwards).is_err());
+    }
+}
@@ -0,0 +1,229 @@
+//! Scheduler state persistence
+//!
+//! The in-memory maps stay the source of truth for scheduling decisions;
+//! every node registration, job state change, node fencing identity and
+//! tenant credit change is written behind to a `StateStore` so a restarted
+//! (or second) scheduler replica can recover.
+//!
+//! Backends: `MemoryStore` (tests, single process) and `PostgresStore`
+//! (multi-replica deployments, `postgres` cargo feature).
+
+use anyhow::Result;
+use async_trait::async_trait;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tokio::sync::{mpsc, oneshot};
+
+use crate::credits::CreditBalance;
+use crate::fencing::NodeIdentity;
+use crate::{JobState, NodeInfo};
+
+#[cfg(feature = "postgres")]
+pub mod postgres;
+
+/// Durable storage for nodes and job states
+#[async_trait]
+pub trait StateStore: Send + Sync {
+    /// Insert or update a node
+    async fn put_node(&self, node: &NodeInfo) -> Result<()>;
+
+    /// Insert or update a job state
+    ///
+    /// Implementations must reject moving a job out of a terminal state, so
+    /// a stale write from another replica cannot resurrect a finished job.
+    async fn put_job(&self, state: &JobState) -> Result<()>;
+
+    /// Insert or update a node's registration identity
+    ///
+    /// Implementations must keep the higher token, so a stale write cannot
+    /// re-admit a superseded worker.
+    async fn put_identity(&self, node_id: &str, identity: &NodeIdentity) -> Result<()>;
+
+    /// Insert or update a tenant's credit account
+    async fn put_credit(&self, account: &CreditBalance) -> Result<()>;
+
+    /// Record a top-up reference as credited
+    async fn put_credit_reference(&self, reference: &str) -> Result<()>;
+
+    async fn load_nodes(&self) -> Result<Vec<NodeInfo>>;
+
+    async fn load_jobs(&self) -> Result<Vec<JobState>>;
+
+    async fn load_identities(&self) -> Result<Vec<(String, NodeIdentity)>>;
+
+    async fn load_credits(&self) -> Result<Vec<CreditBalance>>;
+
+    async fn load_credit_references(&self) -> Result<Vec<String>>;
+}
+
+/// Pending write queued by the scheduler
+#[derive(Debug)]
+pub(crate) enum PersistOp {
+    Node(NodeInfo),
+    Job(JobState),
+    Identity(String, NodeIdentity),
+    Credit(CreditBalance),
+    CreditReference(String),
+    /// Answered once every write queued before it has been applied
+    Flush(oneshot::Sender<()>),
+}
+
+/// Apply queued writes in order until every scheduler handle is dropped
+pub(crate) async fn run_persister(store: Arc<dyn StateStore>, mut rx: mpsc::UnboundedReceiver<PersistOp>) {
+    while let Some(op) = rx.recv().await {
+        let result = match op {
+            PersistOp::Node(ref node) => store.put_node(node).await,
+            PersistOp::Job(ref state) => store.put_job(state).await,
+            PersistOp::Identity(ref node_id, ref identity) => store.put_identity(node_id, identity).await,
+            PersistOp::Credit(ref account) => store.put_credit(account).await,
+            PersistOp::CreditReference(ref reference) => store.put_credit_reference(reference).await,
+            PersistOp::Flush(done) => {
+                let _ = done.send(());
+                continue;
+            }
+        };
+
+        if let Err(e) = result {
+            tracing::warn!("Failed to persist {:?}: {}", op, e);
+        }
+    }
+}
+
+/// Connect to the store named by `url` (`memory://` or `postgres://...`)
+pub async fn connect_store(url: &str) -> Result<Arc<dyn StateStore>> {
+    if url.starts_with("memory://") {
+        return Ok(Arc::new(MemoryStore::new()));
+    }
+
+    if url.starts_with("postgres://") || url.starts_with("postgresql://") {
+        #[cfg(feature = "postgres")]
+        return Ok(Arc::new(postgres::PostgresStore::connect(url).await?));
+        #[cfg(not(feature = "postgres"))]
+        anyhow::bail!("PostgreSQL support not compiled in (enable the `postgres` feature)");
+    }
+
+    anyhow::bail!("Unsupported state store URL: {}", url)
+}
+
+/// In-process store, mainly for tests and single-node setups
+#[derive(Debug, Default)]
+pub struct MemoryStore {
+    nodes: Mutex<HashMap<String, NodeInfo>>,
+    jobs: Mutex<HashMap<String, JobState>>,
+    identities: Mutex<HashMap<String, NodeIdentity>>,
+    credits: Mutex<HashMap<String, CreditBalance>>,
+    credit_references: Mutex<std::collections::HashSet<String>>,
+}
+
+impl MemoryStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl StateStore for MemoryStore {
+    async fn put_node(&self, node: &NodeInfo) -> Result<()> {
+        let mut nodes = self.nodes.lock()
+            .map_err(|e| anyhow::anyhow!("Lock poisoned: {}", e))?;
+        nodes.insert(node.id.clone(), node.clone());
+        Ok(())
+    }
+
+    async fn put_job(&self, state: &JobState) -> Result<()> {
+        let mut jobs = self.jobs.lock()
+            .map_err(|e| anyhow::anyhow!("Lock poisoned: {}", e))?;
+
+        if let Some(existing) = jobs.get(&state.job_id) {
+            if existing.status.is_terminal() && !state.status.is_terminal() {
+                anyhow::bail!(
+                    "Rejected transition of job {} from {:?} to {:?}",
+                    state.job_id, existing.status, state.status
+                );
+            }
+        }
+
+        jobs.insert(state.job_id.clone(), state.clone());
+        Ok(())
+    }
+
+    async fn put_identity(&self, node_id: &str, identity: &NodeIdentity) -> Result<()> {
+        let mut identities = self.identities.lock()
+            .map_err(|e| anyhow::anyhow!("Lock poisoned: {}", e))?;
+        if identities.get(node_id).map_or(true, |existing| existing.token <= identity.token) {
+            identities.insert(node_id.to_string(), identity.clone());
+        }
+        Ok(())
+    }
+
+    async fn put_credit(&self, account: &CreditBalance) -> Result<()> {
+        let mut credits = self.credits.lock()
+            .map_err(|e| anyhow::anyhow!("Lock poisoned: {}", e))?;
+        credits.insert(account.tenant.clone(), account.clone());
+        Ok(())
+    }
+
+    async fn put_credit_reference(&self, reference: &str) -> Result<()> {
+        let mut references = self.credit_references.lock()
+            .map_err(|e| anyhow::anyhow!("Lock poisoned: {}", e))?;
+        references.insert(reference.to_string());
+        Ok(())
+    }
+
+    async fn load_nodes(&self) -> Result<Vec<NodeInfo>> {
+        let nodes = self.nodes.lock()
+            .map_err(|e| anyhow::anyhow!("Lock poisoned: {}", e))?;
+        Ok(nodes.values().cloned().collect())
+    }
+
+    async fn load_jobs(&self) -> Result<Vec<JobState>> {
+        let jobs = self.jobs.lock()
+            .map_err(|e| anyhow::anyhow!("Lock poisoned: {}", e))?;
+        Ok(jobs.values().cloned().collect())
+    }
+
+    async fn load_identities(&self) -> Result<Vec<(String, NodeIdentity)>> {
+        let identities = self.identities.lock()
+            .map_err(|e| anyhow::anyhow!("Lock poisoned: {}", e))?;
+        Ok(identities.iter().map(|(node_id, identity)| (node_id.clone(), identity.clone())).collect())
+    }
+
+    async fn load_credits(&self) -> Result<Vec<CreditBalance>> {
+        let credits = self.credits.lock()
+            .map_err(|e| anyhow::anyhow!("Lock poisoned: {}", e))?;
+        Ok(credits.values().cloned().collect())
+    }
+
+    async fn load_credit_references(&self) -> Result<Vec<String>> {
+        let references = self.credit_references.lock()
+            .map_err(|e| anyhow::anyhow!("Lock poisoned: {}", e))?;
+        Ok(references.iter().cloned().collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::JobStatus;
+
+    fn job(status: JobStatus) -> JobState {
+        JobState {
+            job_id: "job-1".to_string(),
+            status,
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_memory_store_rejects_leaving_terminal_state() {
+        let store = MemoryStore::new();
+
+        store.put_job(&job(JobStatus::Running)).await.unwrap();
+        store.put_job(&job(JobStatus::Completed)).await.unwrap();
+        assert!(store.put_job(&job(JobStatus::Running)).await.is_err());
+
+        let jobs = store.load_jobs().await.unwrap();
+        assert_eq!(jobs.len(), 1);
+        assert_eq!(jobs[0].status, JobStatus::Completed);
+    }
+}
@@ -0,0 +1,194 @@
+//! PostgreSQL-backed StateStore (sqlx)
+//!
+//! Schema migrations live in `core/scheduler/migrations` and are applied on
+//! connect. Job writes run in a transaction that locks the existing row, so
+//! concurrent replicas cannot move a finished job back to a live state.
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use sqlx::postgres::{PgPool, PgPoolOptions};
+use sqlx::types::Json;
+
+use super::StateStore;
+use crate::credits::CreditBalance;
+use crate::fencing::NodeIdentity;
+use crate::{JobState, JobStatus, NodeInfo};
+
+/// Default size of the connection pool
+const DEFAULT_MAX_CONNECTIONS: u32 = 10;
+
+pub struct PostgresStore {
+    pool: PgPool,
+}
+
+impl PostgresStore {
+    /// Connect, create the pool and apply pending migrations
+    pub async fn connect(url: &str) -> Result<Self> {
+        let max_connections = std::env::var("TGP_DATABASE_MAX_CONNECTIONS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(DEFAULT_MAX_CONNECTIONS);
+
+        let pool = PgPoolOptions::new()
+            .max_connections(max_connections)
+            .connect(url)
+            .await
+            .context("Failed to connect to PostgreSQL")?;
+
+        sqlx::migrate!("./migrations")
+            .run(&pool)
+            .await
+            .context("Failed to run database migrations")?;
+
+        tracing::info!("PostgreSQL state store ready ({} connections)", max_connections);
+        Ok(Self { pool })
+    }
+}
+
+fn status_name(status: &JobStatus) -> String {
+    format!("{:?}", status)
+}
+
+#[async_trait]
+impl StateStore for PostgresStore {
+    async fn put_node(&self, node: &NodeInfo) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO nodes (node_id, info, updated_at) VALUES ($1, $2, now()) \
+             ON CONFLICT (node_id) DO UPDATE SET info = EXCLUDED.info, updated_at = now()",
+        )
+        .bind(&node.id)
+        .bind(Json(node))
+        .execute(&self.pool)
+        .await
+        .context("Failed to upsert node")?;
+
+        Ok(())
+    }
+
+    async fn put_job(&self, state: &JobState) -> Result<()> {
+        let mut tx = self.pool.begin().await.context("Failed to begin transaction")?;
+
+        let existing: Option<String> = sqlx::query_scalar(
+            "SELECT status FROM jobs WHERE job_id = $1 FOR UPDATE",
+        )
+        .bind(&state.job_id)
+        .fetch_optional(&mut *tx)
+        .await
+        .context("Failed to read job status")?;
+
+        let terminal = [
+            status_name(&JobStatus::Completed),
+            status_name(&JobStatus::Failed),
+            status_name(&JobStatus::Cancelled),
+        ];
+        if let Some(existing) = existing {
+            if terminal.contains(&existing) && !state.status.is_terminal() {
+                tx.rollback().await?;
+                anyhow::bail!(
+                    "Rejected transition of job {} from {} to {:?}",
+                    state.job_id, existing, state.status
+                );
+            }
+        }
+
+        sqlx::query(
+            "INSERT INTO jobs (job_id, status, assigned_node, state, updated_at) \
+             VALUES ($1, $2, $3, $4, now()) \
+             ON CONFLICT (job_id) DO UPDATE SET status = EXCLUDED.status, \
+             assigned_node = EXCLUDED.assigned_node, state = EXCLUDED.state, updated_at = now()",
+        )
+        .bind(&state.job_id)
+        .bind(status_name(&state.status))
+        .bind(&state.assigned_node)
+        .bind(Json(state))
+        .execute(&mut *tx)
+        .await
+        .context("Failed to upsert job")?;
+
+        tx.commit().await.context("Failed to commit job transition")?;
+        Ok(())
+    }
+
+    async fn put_identity(&self, node_id: &str, identity: &NodeIdentity) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO node_identities (node_id, token, identity, updated_at) VALUES ($1, $2, $3, now()) \
+             ON CONFLICT (node_id) DO UPDATE SET token = EXCLUDED.token, identity = EXCLUDED.identity, \
+             updated_at = now() WHERE node_identities.token <= EXCLUDED.token",
+        )
+        .bind(node_id)
+        .bind(identity.token as i64)
+        .bind(Json(identity))
+        .execute(&self.pool)
+        .await
+        .context("Failed to upsert node identity")?;
+
+        Ok(())
+    }
+
+    async fn put_credit(&self, account: &CreditBalance) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO tenant_credits (tenant, account, updated_at) VALUES ($1, $2, now()) \
+             ON CONFLICT (tenant) DO UPDATE SET account = EXCLUDED.account, updated_at = now()",
+        )
+        .bind(&account.tenant)
+        .bind(Json(account))
+        .execute(&self.pool)
+        .await
+        .context("Failed to upsert tenant credit")?;
+
+        Ok(())
+    }
+
+    async fn put_credit_reference(&self, reference: &str) -> Result<()> {
+        sqlx::query("INSERT INTO credit_references (reference) VALUES ($1) ON CONFLICT (reference) DO NOTHING")
+            .bind(reference)
+            .execute(&self.pool)
+            .await
+            .context("Failed to record credit reference")?;
+
+        Ok(())
+    }
+
+    async fn load_nodes(&self) -> Result<Vec<NodeInfo>> {
+        let rows: Vec<Json<NodeInfo>> = sqlx::query_scalar("SELECT info FROM nodes")
+            .fetch_all(&self.pool)
+            .await
+            .context("Failed to load nodes")?;
+
+        Ok(rows.into_iter().map(|Json(node)| node).collect())
+    }
+
+    async fn load_jobs(&self) -> Result<Vec<JobState>> {
+        let rows: Vec<Json<JobState>> = sqlx::query_scalar("SELECT state FROM jobs")
+            .fetch_all(&self.pool)
+            .await
+            .context("Failed to load jobs")?;
+
+        Ok(rows.into_iter().map(|Json(state)| state).collect())
+    }
+
+    async fn load_identities(&self) -> Result<Vec<(String, NodeIdentity)>> {
+        let rows: Vec<(String, Json<NodeIdentity>)> = sqlx::query_as("SELECT node_id, identity FROM node_identities")
+            .fetch_all(&self.pool)
+            .await
+            .context("Failed to load node identities")?;
+
+        Ok(rows.into_iter().map(|(node_id, Json(identity))| (node_id, identity)).collect())
+    }
+
+    async fn load_credits(&self) -> Result<Vec<CreditBalance>> {
+        let rows: Vec<Json<CreditBalance>> = sqlx::query_scalar("SELECT account FROM tenant_credits")
+            .fetch_all(&self.pool)
+            .await
+            .context("Failed to load tenant credits")?;
+
+        Ok(rows.into_iter().map(|Json(account)| account).collect())
+    }
+
+    async fn load_credit_references(&self) -> Result<Vec<String>> {
+        sqlx::query_scalar("SELECT reference FROM credit_references")
+            .fetch_all(&self.pool)
+            .await
+            .context("Failed to load credit references")
+    }
+}
@@ -0,0 +1,191 @@
+//! Server-side job templates
+//!
+//! Teams register named job specs once and instantiate them with a job id
+//! and overrides instead of copying whole specs around. Template commands,
+//! environment values and image may reference `{{params.<name>}}`; every
+//! referenced parameter must be supplied when instantiating.
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap};
+use std::sync::{Arc, Mutex};
+
+use crate::error::SchedulerError;
+use crate::{EconomicScheduler, JobSpec};
+
+/// Named, reusable job spec
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct JobTemplate {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    /// Spec every instance starts from (its id is ignored)
+    pub job: JobSpec,
+}
+
+/// Per-instance changes to a template
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TemplateOverrides {
+    pub container_image: Option<String>,
+    /// Replaces the template command
+    pub command: Option<Vec<String>>,
+    /// Merged over the template environment
+    pub env: HashMap<String, String>,
+    pub cpu_cores: Option<u32>,
+    pub memory_gb: Option<u32>,
+    pub gpu_count: Option<u32>,
+    pub max_budget_usd: Option<f64>,
+    pub deadline: Option<i64>,
+    /// Values for `{{params.<name>}}` placeholders
+    pub parameters: HashMap<String, String>,
+}
+
+/// Names inside `{{params.<name>}}` placeholders of `text`
+fn placeholders(text: &str) -> impl Iterator<Item = &str> {
+    text.split("{{params.")
+        .skip(1)
+        .filter_map(|rest| rest.split_once("}}").map(|(name, _)| name))
+}
+
+impl JobTemplate {
+    fn texts(&self) -> impl Iterator<Item = &str> {
+        std::iter::once(self.job.container_image.as_str())
+            .chain(self.job.command.iter().map(String::as_str))
+            .chain(self.job.env.values().map(String::as_str))
+    }
+
+    /// Parameters the template references
+    pub fn parameters(&self) -> Vec<String> {
+        self.texts()
+            .flat_map(placeholders)
+            .map(str::to_string)
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect()
+    }
+
+    /// Job `job_id` from this template with `overrides` applied
+    pub fn instantiate(&self, job_id: &str, overrides: TemplateOverrides) -> Result<JobSpec> {
+        let missing: Vec<String> = self.parameters()
+            .into_iter()
+            .filter(|name| !overrides.parameters.contains_key(name))
+            .collect();
+        if !missing.is_empty() {
+            return Err(SchedulerError::invalid_spec(format!("Template {} needs parameters: {}", self.name, missing.join(", "))).into());
+        }
+
+        let mut job = self.job.clone();
+        job.id = job_id.to_string();
+        job.template = Some(self.name.clone());
+        if let Some(image) = overrides.container_image {
+            job.container_image = image;
+        }
+        if let Some(command) = overrides.command {
+            job.command = command;
+        }
+        job.env.extend(overrides.env);
+        if let Some(cpu_cores) = overrides.cpu_cores {
+            job.resources.cpu_cores = cpu_cores;
+        }
+        if let Some(memory_gb) = overrides.memory_gb {
+            job.resources.memory_gb = memory_gb;
+        }
+        if let Some(gpu_count) = overrides.gpu_count {
+            job.resources.gpu_count = gpu_count;
+        }
+        if overrides.max_budget_usd.is_some() {
+            job.sla.max_budget_usd = overrides.max_budget_usd;
+        }
+        if overrides.deadline.is_some() {
+            job.sla.deadline = overrides.deadline;
+        }
+
+        let substitute = |text: &mut String| {
+            for (name, value) in &overrides.parameters {
+                *text = text.replace(&format!("{{{{params.{}}}}}", name), value);
+            }
+        };
+        substitute(&mut job.container_image);
+        job.command.iter_mut().for_each(substitute);
+        job.env.values_mut().for_each(substitute);
+        Ok(job)
+    }
+}
+
+/// Registered templates by name
+#[derive(Debug, Clone, Default)]
+pub struct JobTemplates {
+    templates: Arc<Mutex<HashMap<String, JobTemplate>>>,
+}
+
+impl JobTemplates {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl EconomicScheduler {
+    /// Register a template; an existing one is only overwritten with `replace`
+    pub fn put_template(&self, template: JobTemplate, replace: bool) -> Result<()> {
+        if template.name.is_empty() {
+            return Err(SchedulerError::invalid_spec("Template name must not be empty").into());
+        }
+        let mut templates = self.templates.templates.lock()
+            .map_err(|e| anyhow::anyhow!("Lock poisoned: {}", e))?;
+        if !replace && templates.contains_key(&template.name) {
+            return Err(SchedulerError::already_exists("Template", &template.name).into());
+        }
+        tracing::info!("Job template {} registered", template.name);
+        templates.insert(template.name.clone(), template);
+        Ok(())
+    }
+
+    /// All templates, sorted by name
+    pub fn list_templates(&self) -> Vec<JobTemplate> {
+        let mut templates: Vec<JobTemplate> = self.templates.templates.lock()
+            .map(|templates| templates.values().cloned().collect())
+            .unwrap_or_default();
+        templates.sort_by(|a, b| a.name.cmp(&b.name));
+        templates
+    }
+
+    /// Build job `job_id` from template `name`
+    pub fn instantiate_template(&self, name: &str, job_id: &str, overrides: TemplateOverrides) -> Result<JobSpec> {
+        let template = self.templates.templates.lock()
+            .map_err(|e| anyhow::anyhow!("Lock poisoned: {}", e))?
+            .get(name)
+            .cloned()
+            .ok_or_else(|| SchedulerError::not_found("Template", name))?;
+        template.instantiate(job_id, overrides)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_instantiate_applies_overrides_and_parameters() {
+        let scheduler = EconomicScheduler::new();
+        let mut job = JobSpec {
+            container_image: "trainer:{{params.version}}".to_string(),
+            command: vec!["train".to_string(), "--lr={{params.lr}}".to_string()],
+            ..Default::default()
+        };
+        job.resources.cpu_cores = 4;
+        scheduler.put_template(JobTemplate { name: "train".to_string(), job, ..Default::default() }, false).unwrap();
+        assert!(scheduler.put_template(JobTemplate { name: "train".to_string(), ..Default::default() }, false).is_err());
+
+        let mut overrides = TemplateOverrides { memory_gb: Some(16), ..Default::default() };
+        overrides.parameters.insert("version".to_string(), "2.1".to_string());
+        assert!(scheduler.instantiate_template("train", "run-1", overrides.clone()).is_err());
+
+        overrides.parameters.insert("lr".to_string(), "0.01".to_string());
+        let job = scheduler.instantiate_template("train", "run-1", overrides).unwrap();
+        assert_eq!(job.id, "run-1");
+        assert_eq!(job.container_image, "trainer:2.1");
+        assert_eq!(job.command[1], "--lr=0.01");
+        assert_eq!((job.resources.cpu_cores, job.resources.memory_gb), (4, 16));
+        assert_eq!(scheduler.list_templates()[0].parameters(), vec!["lr".to_string(), "version".to_string()]);
+    }
+}
@@ -0,0 +1,353 @@
+//! Reserved capacity blocks for tenants
+//!
+//! An operator reserves a block of capacity (e.g. 64 cores and 2 GPUs) for
+//! a tenant over a time window. While the window is open, the part of the
+//! block the tenant's placed jobs do not use is held back: another tenant's
+//! job is only placed if the cluster's free capacity still covers every
+//! other tenant's unused blocks afterwards. The tenant's own jobs draw on
+//! its blocks first and are never held back by them.
+//!
+//! Holding capacity has a price. The block is valued at its dominant share
+//! of the cluster (the largest of its CPU, memory and GPU shares) times the
+//! cluster's hourly cost, and the unused part is charged to the tenant as
+//! C_idle in the cost summary, sampled by `run_reservation_accounting`.
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::cost_summary::TENANT_IDLE_USD;
+use crate::error::SchedulerError;
+use crate::node_index::NodeIndex;
+use crate::{unix_now, EconomicScheduler, JobSpec, ResourceRequirements};
+
+/// Capacity held for one tenant over `[start, end)`
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CapacityBlock {
+    pub id: String,
+    pub tenant: String,
+    pub cpu_cores: u32,
+    pub memory_gb: u32,
+    pub gpus: u32,
+    pub start: i64,
+    pub end: i64,
+}
+
+impl CapacityBlock {
+    pub fn is_active(&self, now: i64) -> bool {
+        self.start <= now && now < self.end
+    }
+}
+
+/// A block with what its tenant uses now and what holding it has cost
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CapacityBlockStatus {
+    pub block: CapacityBlock,
+    pub active: bool,
+    pub used_cpu_cores: u32,
+    pub used_memory_gb: u32,
+    pub used_gpus: u32,
+    /// C_idle charged to the tenant so far
+    pub idle_cost_usd: f64,
+}
+
+#[derive(Debug, Clone)]
+struct HeldBlock {
+    block: CapacityBlock,
+    /// Time up to which unused capacity has been charged
+    charged_until: i64,
+    idle_cost_usd: f64,
+}
+
+/// Reserved blocks, shared by scheduler clones
+#[derive(Debug, Clone, Default)]
+pub struct CapacityBlocks {
+    blocks: Arc<Mutex<Vec<HeldBlock>>>,
+}
+
+impl CapacityBlocks {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn active(&self, now: i64) -> Vec<CapacityBlock> {
+        self.blocks.lock()
+            .map(|blocks| blocks.iter().filter(|held| held.block.is_active(now)).map(|held| held.block.clone()).collect())
+            .unwrap_or_default()
+    }
+}
+
+/// CPU, memory and GPUs other tenants' blocks hold back from a job, and
+/// what placed jobs already request
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct HeldCapacity {
+    held: [u32; 3],
+    committed: [u32; 3],
+}
+
+impl HeldCapacity {
+    /// Whether placing `required` (every member of a gang) still leaves the
+    /// held capacity free among the active nodes
+    pub(crate) fn leaves_room(&self, index: &NodeIndex, required: &ResourceRequirements, members: u32) -> bool {
+        let (allocatable, _) = cluster_capacity(index);
+        let required = triple(required);
+        (0..3).all(|d| {
+            self.held[d] == 0
+                || allocatable[d].saturating_sub(self.committed[d]).saturating_sub(required[d] * members) >= self.held[d]
+        })
+    }
+}
+
+/// CPU, memory and GPUs of `r`
+fn triple(r: &ResourceRequirements) -> [u32; 3] {
+    [r.cpu_cores, r.memory_gb, r.gpu_count]
+}
+
+/// Unused CPU, memory and GPUs of each block, by block id; a tenant's usage
+/// fills its blocks in window order
+fn idle_by_block(blocks: &[CapacityBlock], usage: &HashMap<String, [u32; 3]>) -> HashMap<String, [u32; 3]> {
+    let mut left = usage.clone();
+    blocks.iter()
+        .map(|block| {
+            let used = left.entry(block.tenant.clone()).or_default();
+            let reserved = [block.cpu_cores, block.memory_gb, block.gpus];
+            let idle = [0, 1, 2].map(|d| {
+                let covered = used[d].min(reserved[d]);
+                used[d] -= covered;
+                reserved[d] - covered
+            });
+            (block.id.clone(), idle)
+        })
+        .collect()
+}
+
+/// Allocatable CPU, memory and GPUs of the active nodes, and their hourly cost
+fn cluster_capacity(index: &NodeIndex) -> ([u32; 3], f64) {
+    let mut allocatable = [0u32; 3];
+    let mut cost_per_hour = 0.0;
+    for node in index.values() {
+        allocatable[0] += node.available_cpu;
+        allocatable[1] += node.available_memory_gb;
+        allocatable[2] += node.available_gpu;
+        cost_per_hour += node.cost_per_hour;
+    }
+    (allocatable, cost_per_hour)
+}
+
+impl EconomicScheduler {
+    pub fn capacity_blocks(&self) -> &CapacityBlocks {
+        &self.capacity_blocks
+    }
+
+    /// Reserve a block of capacity for a tenant
+    pub fn reserve_capacity(&self, block: CapacityBlock) -> Result<()> {
+        if block.id.is_empty() || block.tenant.is_empty() {
+            return Err(SchedulerError::invalid_spec("A capacity block needs an id and a tenant").into());
+        }
+        if block.end <= block.start {
+            return Err(SchedulerError::invalid_spec(format!("Capacity block {} ends before it starts", block.id)).into());
+        }
+        if block.cpu_cores == 0 && block.memory_gb == 0 && block.gpus == 0 {
+            return Err(SchedulerError::invalid_spec(format!("Capacity block {} reserves nothing", block.id)).into());
+        }
+        let mut blocks = self.capacity_blocks.blocks.lock()
+            .map_err(|e| anyhow::anyhow!("Lock poisoned: {}", e))?;
+        if blocks.iter().any(|held| held.block.id == block.id) {
+            return Err(SchedulerError::already_exists("Capacity block", &block.id).into());
+        }
+        tracing::info!(
+            "Reserved {} cores, {}GB and {} GPUs for tenant {} from {} to {} ({})",
+            block.cpu_cores, block.memory_gb, block.gpus, block.tenant, block.start, block.end, block.id
+        );
+        // A block reserved mid-window is charged from now on
+        let charged_until = block.start.max(unix_now());
+        blocks.push(HeldBlock { block, charged_until, idle_cost_usd: 0.0 });
+        blocks.sort_by_key(|held| held.block.start);
+        Ok(())
+    }
+
+    /// Release a block before its window ends
+    pub fn cancel_capacity_block(&self, id: &str) -> Result<CapacityBlock> {
+        let mut blocks = self.capacity_blocks.blocks.lock()
+            .map_err(|e| anyhow::anyhow!("Lock poisoned: {}", e))?;
+        let i = blocks.iter()
+            .position(|held| held.block.id == id)
+            .ok_or_else(|| SchedulerError::not_found("Capacity block", id))?;
+        tracing::info!("Released capacity block {}", id);
+        Ok(blocks.remove(i).block)
+    }
+
+    /// Every block not yet past its window, by start
+    pub fn capacity_block_status(&self, now: i64) -> Result<Vec<CapacityBlockStatus>> {
+        let usage = self.tenant_usage()?;
+        let blocks: Vec<HeldBlock> = self.capacity_blocks.blocks.lock()
+            .map_err(|e| anyhow::anyhow!("Lock poisoned: {}", e))?
+            .clone();
+        let idle = idle_by_block(&self.capacity_blocks.active(now), &usage);
+
+        Ok(blocks.into_iter()
+            .filter(|held| held.block.end > now)
+            .map(|held| {
+                let reserved = [held.block.cpu_cores, held.block.memory_gb, held.block.gpus];
+                let used = idle.get(&held.block.id)
+                    .map(|idle| [0, 1, 2].map(|d| reserved[d] - idle[d]))
+                    .unwrap_or_default();
+                CapacityBlockStatus {
+                    active: held.block.is_active(now),
+                    used_cpu_cores: used[0],
+                    used_memory_gb: used[1],
+                    used_gpus: used[2],
+                    idle_cost_usd: held.idle_cost_usd,
+                    block: held.block,
+                }
+            })
+            .collect())
+    }
+
+    /// CPU, memory and GPUs requested by each tenant's placed jobs
+    fn tenant_usage(&self) -> Result<HashMap<String, [u32; 3]>> {
+        let placed = self.placed_jobs.lock()
+            .map_err(|e| anyhow::anyhow!("Lock poisoned: {}", e))?;
+        let mut usage: HashMap<String, [u32; 3]> = HashMap::new();
+        for spec in placed.values() {
+            let members = spec.gang_size.max(1);
+            let used = usage.entry(spec.tenant.clone()).or_default();
+            for (total, request) in used.iter_mut().zip(triple(&spec.resources)) {
+                *total += request * members;
+            }
+        }
+        Ok(usage)
+    }
+
+    /// Unused capacity of other tenants' open blocks, with what all placed
+    /// jobs request, or None if no block holds anything back from `job`
+    pub(crate) fn capacity_held_from(&self, job: &JobSpec, now: i64) -> Result<Option<HeldCapacity>> {
+        let active = self.capacity_blocks.active(now);
+        if !active.iter().any(|block| block.tenant != job.tenant) {
+            return Ok(None);
+        }
+        let usage = self.tenant_usage()?;
+        let idle = idle_by_block(&active, &usage);
+
+        let mut held = HeldCapacity::default();
+        for block in active.iter().filter(|block| block.tenant != job.tenant) {
+            for (total, unused) in held.held.iter_mut().zip(idle[&block.id]) {
+                *total += unused;
+            }
+        }
+        for used in usage.values() {
+            for (total, request) in held.committed.iter_mut().zip(used) {
+                *total += request;
+            }
+        }
+        Ok(Some(held))
+    }
+
+    /// Charge each tenant the C_idle of its blocks' unused capacity up to `now`
+    pub fn charge_reserved_idle(&self, now: i64) -> Result<()> {
+        let usage = self.tenant_usage()?;
+        let (allocatable, cost_per_hour) = {
+            let index = self.available_nodes.lock()
+                .map_err(|e| anyhow::anyhow!("Lock poisoned: {}", e))?;
+            cluster_capacity(&index)
+        };
+
+        let mut blocks = self.capacity_blocks.blocks.lock()
+            .map_err(|e| anyhow::anyhow!("Lock poisoned: {}", e))?;
+        // Blocks with uncharged time, including any that closed since the last pass
+        let due: Vec<CapacityBlock> = blocks.iter()
+            .filter(|held| held.charged_until < now.min(held.block.end))
+            .map(|held| held.block.clone())
+            .collect();
+        let idle = idle_by_block(&due, &usage);
+
+        for held in blocks.iter_mut() {
+            let Some(idle) = idle.get(&held.block.id) else {
+                continue;
+            };
+            let until = now.min(held.block.end);
+            let elapsed = until - held.charged_until;
+            held.charged_until = until;
+
+            // Dominant share of the cluster the unused part holds
+            let share = (0..3)
+                .filter(|d| allocatable[*d] > 0)
+                .map(|d| idle[d] as f64 / allocatable[d] as f64)
+                .fold(0.0, f64::max)
+                .min(1.0);
+            let usd = cost_per_hour * share * elapsed as f64 / 3600.0;
+            if usd > 0.0 {
+                held.idle_cost_usd += usd;
+                self.metrics_history.record_counter(TENANT_IDLE_USD, &held.block.tenant, usd, now);
+            }
+        }
+        blocks.retain(|held| held.block.end > now);
+        Ok(())
+    }
+}
+
+/// Charge unused reserved capacity to its tenants every `interval`
+pub async fn run_reservation_accounting(scheduler: EconomicScheduler, interval: Duration) {
+    let mut ticker = tokio::time::interval(interval);
+    loop {
+        ticker.tick().await;
+        if let Err(e) = scheduler.charge_reserved_idle(unix_now()) {
+            tracing::warn!("Reserved capacity accounting failed: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{NodeInfo, SlaConstraints};
+
+    #[tokio::test]
+    async fn test_block_holds_capacity_and_charges_idle_to_tenant() {
+        let scheduler = EconomicScheduler::new();
+        for id in ["node-1", "node-2"] {
+            scheduler.register_node(NodeInfo {
+                id: id.to_string(),
+                available_cpu: 8,
+                available_memory_gb: 32,
+                cost_per_hour: 1.0,
+                ..Default::default()
+            }).unwrap();
+        }
+        let now = unix_now();
+        scheduler.reserve_capacity(CapacityBlock {
+            id: "acme-training".to_string(),
+            tenant: "acme".to_string(),
+            cpu_cores: 8,
+            start: now - 60,
+            end: now + 3600,
+            ..Default::default()
+        }).unwrap();
+        let job = |id: &str, tenant: &str, cpu_cores: u32| JobSpec {
+            id: id.to_string(),
+            tenant: tenant.to_string(),
+            resources: ResourceRequirements { cpu_cores, memory_gb: 1, ..Default::default() },
+            sla: SlaConstraints { max_latency_ms: 1_000, ..Default::default() },
+            disable_result_cache: true,
+            ..Default::default()
+        };
+
+        // 8 of the 16 cores are held for acme
+        assert!(scheduler.schedule(job("web", "lab", 8)).await.is_ok());
+        assert!(scheduler.schedule(job("etl", "lab", 4)).await.is_err());
+        assert!(scheduler.schedule(job("train", "acme", 4)).await.is_ok());
+
+        let status = scheduler.capacity_block_status(now).unwrap();
+        assert_eq!((status[0].used_cpu_cores, status[0].active), (4, true));
+
+        // Half the block (4 of 16 cores) sat idle for an hour
+        scheduler.charge_reserved_idle(now + 3600).unwrap();
+        let summary = scheduler.cost_summary(now - 60, now + 3601);
+        assert!((summary.tenants["acme"].idle_usd - 0.5).abs() < 1e-3);
+        assert!(scheduler.capacity_block_status(now + 3600).unwrap().is_empty());
+
+        assert!(scheduler.cancel_capacity_block("acme-training").is_err());
+    }
+}
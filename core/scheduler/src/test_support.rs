@@ -0,0 +1,27 @@
+//! Fixtures shared by the unit tests
+//!
+//! Tests vary one or two fields from these with struct update syntax
+//! (`NodeInfo { location: .., ..node("a", 0.1) }`) instead of keeping a
+//! factory of their own.
+
+use crate::NodeInfo;
+
+/// Node with room for the usual test job, priced at `cost_per_hour`
+pub(crate) fn node(id: &str, cost_per_hour: f64) -> NodeInfo {
+    NodeInfo {
+        cost_per_hour,
+        ..sized_node(id, 8, 32, 0)
+    }
+}
+
+/// Unpriced node with the given capacity
+pub(crate) fn sized_node(id: &str, cpu: u32, memory_gb: u32, gpu: u32) -> NodeInfo {
+    NodeInfo {
+        id: id.to_string(),
+        available_cpu: cpu,
+        available_memory_gb: memory_gb,
+        available_gpu: gpu,
+        location: "local".to_string(),
+        ..Default::default()
+    }
+}
@@ -0,0 +1,395 @@
+//! In-process cluster for end-to-end tests
+//!
+//! `TestCluster::start` serves the real gRPC API on an ephemeral localhost
+//! port and pushes worker commands as the binary does. `MockWorker`s
+//! register over that API, take jobs from their command stream and "run"
+//! them with a scripted outcome instead of a container, reporting status
+//! and resources like the real worker. Submit → dispatch → complete flows
+//! can then be tested in CI without Docker or real nodes:
+//!
+//! ```text
+//! let cluster = TestCluster::start().await?;
+//! let worker = cluster.add_worker(MockWorkerConfig::new("node-1")).await?;
+//! let job_id = cluster.submit(JobSubmitRequest { .. }).await?;
+//! cluster.wait_for(&job_id, JobStatus::Completed, Duration::from_secs(5)).await?;
+//! ```
+
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::net::TcpListener;
+use tokio::sync::{mpsc, oneshot};
+use tokio::task::JoinHandle;
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::transport::Channel;
+
+use crate::commands::{run_command_pusher, GrpcCommandTransport};
+use crate::grpc::proto::{
+    scheduler_command::Command, scheduler_service_client::SchedulerServiceClient, worker_stream_message::Body,
+    CommandAck, CommandStreamHello, JobAssignment, JobStatus as ProtoJobStatus, JobStatusUpdate,
+    JobSubmitRequest, RegisterNodeRequest, ResourceReport, SchedulerCommand, WorkerStreamMessage,
+};
+use crate::{EconomicScheduler, JobState, JobStatus};
+
+/// How a mock worker finishes a job
+#[derive(Debug, Clone, PartialEq)]
+pub struct JobOutcome {
+    /// Time spent "running"
+    pub duration: Duration,
+    /// Completed, Failed or Cancelled
+    pub status: JobStatus,
+    pub exit_code: i64,
+    pub logs: String,
+}
+
+impl JobOutcome {
+    pub fn completed() -> Self {
+        Self { duration: Duration::ZERO, status: JobStatus::Completed, exit_code: 0, logs: String::new() }
+    }
+
+    pub fn failed(exit_code: i64) -> Self {
+        Self { status: JobStatus::Failed, exit_code, ..Self::completed() }
+    }
+
+    pub fn after(mut self, duration: Duration) -> Self {
+        self.duration = duration;
+        self
+    }
+}
+
+/// Decides the outcome of each job a mock worker is given
+pub type FakeExecutor = Arc<dyn Fn(&JobAssignment) -> JobOutcome + Send + Sync>;
+
+/// A mock worker's node
+#[derive(Clone)]
+pub struct MockWorkerConfig {
+    pub node_id: String,
+    pub cpu_cores: u32,
+    pub memory_gb: f64,
+    pub location: String,
+    pub cost_per_hour: f64,
+    pub executor: FakeExecutor,
+    /// Report free resources as jobs start and finish
+    pub auto_report: bool,
+}
+
+impl MockWorkerConfig {
+    /// 8 cores and 32GB at $0.10/h; every job completes at once
+    pub fn new(node_id: impl Into<String>) -> Self {
+        Self {
+            node_id: node_id.into(),
+            cpu_cores: 8,
+            memory_gb: 32.0,
+            location: "local".to_string(),
+            cost_per_hour: 0.1,
+            executor: Arc::new(|_| JobOutcome::completed()),
+            auto_report: true,
+        }
+    }
+
+    pub fn executor(mut self, executor: impl Fn(&JobAssignment) -> JobOutcome + Send + Sync + 'static) -> Self {
+        self.executor = Arc::new(executor);
+        self
+    }
+}
+
+/// Scheduler serving gRPC in-process, stopped on drop
+pub struct TestCluster {
+    addr: SocketAddr,
+    scheduler: EconomicScheduler,
+    shutdown: Option<oneshot::Sender<()>>,
+    tasks: Vec<JoinHandle<()>>,
+}
+
+impl TestCluster {
+    pub async fn start() -> Result<Self> {
+        Self::start_with(EconomicScheduler::new()).await
+    }
+
+    /// Serve a pre-configured scheduler (queue, policies, ...)
+    pub async fn start_with(scheduler: EconomicScheduler) -> Result<Self> {
+        let listener = TcpListener::bind("127.0.0.1:0").await.context("Failed to bind a test port")?;
+        let addr = listener.local_addr()?;
+        let (shutdown, stopped) = oneshot::channel::<()>();
+
+        let server = tokio::spawn(crate::grpc::serve_on_listener(scheduler.clone(), listener, async {
+            let _ = stopped.await;
+        }));
+        let pusher = tokio::spawn(run_command_pusher(
+            scheduler.clone(),
+            Arc::new(GrpcCommandTransport::new(Duration::from_secs(2), scheduler.control_auth().clone())),
+        ));
+        let server = tokio::spawn(async move {
+            if let Ok(Err(e)) = server.await {
+                tracing::warn!("Test scheduler stopped: {}", e);
+            }
+        });
+
+        Ok(Self { addr, scheduler, shutdown: Some(shutdown), tasks: vec![server, pusher] })
+    }
+
+    pub fn url(&self) -> String {
+        format!("http://{}", self.addr)
+    }
+
+    /// The scheduler behind the server, for inspection
+    pub fn scheduler(&self) -> &EconomicScheduler {
+        &self.scheduler
+    }
+
+    pub async fn client(&self) -> Result<SchedulerServiceClient<Channel>> {
+        SchedulerServiceClient::connect(self.url()).await.context("Failed to connect to the test scheduler")
+    }
+
+    /// Register a mock worker and open its command stream
+    pub async fn add_worker(&self, config: MockWorkerConfig) -> Result<MockWorker> {
+        MockWorker::start(self.client().await?, config).await
+    }
+
+    /// Submit over gRPC; returns the job id
+    pub async fn submit(&self, request: JobSubmitRequest) -> Result<String> {
+        let response = self.client().await?.submit_job(request).await.context("SubmitJob failed")?.into_inner();
+        anyhow::ensure!(response.success, "Job refused: {}", response.message);
+        Ok(response.job_id)
+    }
+
+    /// Wait until `job_id` reaches `status`
+    pub async fn wait_for(&self, job_id: &str, status: JobStatus, timeout: Duration) -> Result<JobState> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            let state = self.scheduler.get_job_state(job_id);
+            if let Some(state) = state.as_ref().filter(|state| state.status == status) {
+                return Ok(state.clone());
+            }
+            if Instant::now() >= deadline {
+                anyhow::bail!(
+                    "Job {} did not reach {:?} within {:?} (last: {:?})",
+                    job_id, status, timeout, state.map(|state| state.status)
+                );
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    }
+}
+
+impl Drop for TestCluster {
+    fn drop(&mut self) {
+        if let Some(shutdown) = self.shutdown.take() {
+            let _ = shutdown.send(());
+        }
+        for task in &self.tasks {
+            task.abort();
+        }
+    }
+}
+
+/// Jobs a mock worker holds
+#[derive(Debug, Default)]
+struct MockJobs {
+    /// Running jobs with the CPU and memory (GB) they were given
+    running: HashMap<String, (u32, f64)>,
+    /// Killed or cancelled by the scheduler, with whether it was a crash
+    stopped: Vec<(String, bool)>,
+    finished: Vec<String>,
+}
+
+/// Worker stand-in driven by its command stream; stops on drop
+pub struct MockWorker {
+    node_id: String,
+    fencing_token: u64,
+    client: SchedulerServiceClient<Channel>,
+    jobs: Arc<Mutex<MockJobs>>,
+    stream: JoinHandle<()>,
+}
+
+impl MockWorker {
+    async fn start(mut client: SchedulerServiceClient<Channel>, config: MockWorkerConfig) -> Result<Self> {
+        let fencing_token = client.register_node(RegisterNodeRequest {
+            node_id: config.node_id.clone(),
+            hostname: config.node_id.clone(),
+            cpu_cores: config.cpu_cores,
+            total_memory_gb: config.memory_gb,
+            location: config.location.clone(),
+            cost_per_hour: config.cost_per_hour,
+            ..Default::default()
+        }).await.context("RegisterNode failed")?.into_inner().fencing_token;
+
+        let (outbound, rx) = mpsc::channel(64);
+        outbound.send(WorkerStreamMessage {
+            body: Some(Body::Hello(CommandStreamHello { node_id: config.node_id.clone(), fencing_token })),
+        }).await?;
+        let mut inbound = client.command_stream(ReceiverStream::new(rx)).await
+            .context("CommandStream failed")?
+            .into_inner();
+
+        let jobs = Arc::new(Mutex::new(MockJobs::default()));
+        let runner = MockRunner { client: client.clone(), config: config.clone(), fencing_token, jobs: jobs.clone() };
+        let stream = tokio::spawn(async move {
+            while let Ok(Some(command)) = inbound.message().await {
+                let ack = runner.handle(command);
+                if outbound.send(WorkerStreamMessage { body: Some(Body::Ack(ack)) }).await.is_err() {
+                    break;
+                }
+            }
+        });
+
+        // The relay has the stream once the call returns
+        Ok(Self { node_id: config.node_id, fencing_token, client, jobs, stream })
+    }
+
+    pub fn node_id(&self) -> &str {
+        &self.node_id
+    }
+
+    /// Send a resource report (e.g. to script a node filling up)
+    pub async fn report_resources(&self, available_cpu: u32, available_memory_gb: f64) -> Result<()> {
+        report(&mut self.client.clone(), &self.node_id, self.fencing_token, available_cpu, available_memory_gb).await
+    }
+
+    /// Jobs currently "running"
+    pub fn running(&self) -> Vec<String> {
+        self.jobs.lock().map(|jobs| jobs.running.keys().cloned().collect()).unwrap_or_default()
+    }
+
+    /// Jobs that ran to their scripted outcome, in order
+    pub fn finished(&self) -> Vec<String> {
+        self.jobs.lock().map(|jobs| jobs.finished.clone()).unwrap_or_default()
+    }
+
+    /// Jobs the scheduler cancelled or killed, with whether it was a kill
+    pub fn stopped(&self) -> Vec<(String, bool)> {
+        self.jobs.lock().map(|jobs| jobs.stopped.clone()).unwrap_or_default()
+    }
+}
+
+impl Drop for MockWorker {
+    fn drop(&mut self) {
+        self.stream.abort();
+    }
+}
+
+async fn report(
+    client: &mut SchedulerServiceClient<Channel>,
+    node_id: &str,
+    fencing_token: u64,
+    available_cpu: u32,
+    available_memory_gb: f64,
+) -> Result<()> {
+    client.report_resources(ResourceReport {
+        node_id: node_id.to_string(),
+        available_cpu,
+        available_memory_gb,
+        fencing_token,
+        ..Default::default()
+    }).await.context("ReportResources failed")?;
+    Ok(())
+}
+
+/// Runs commands for one mock worker
+#[derive(Clone)]
+struct MockRunner {
+    client: SchedulerServiceClient<Channel>,
+    config: MockWorkerConfig,
+    fencing_token: u64,
+    jobs: Arc<Mutex<MockJobs>>,
+}
+
+impl MockRunner {
+    fn handle(&self, command: SchedulerCommand) -> CommandAck {
+        let mut ack = CommandAck { epoch: command.epoch, seq: command.seq, ok: true, ..Default::default() };
+        match command.command {
+            Some(Command::Execute(assignment)) => {
+                let limits = (assignment.cpu_limit, assignment.memory_limit_mb as f64 / 1024.0);
+                let fresh = self.jobs.lock()
+                    .is_ok_and(|mut jobs| jobs.running.insert(assignment.job_id.clone(), limits).is_none());
+                if fresh {
+                    tokio::spawn(self.clone().run(assignment));
+                }
+            }
+            Some(Command::Cancel(cancel)) => {
+                let stopped = self.jobs.lock().is_ok_and(|mut jobs| {
+                    let running = jobs.running.remove(&cancel.job_id).is_some();
+                    if running {
+                        jobs.stopped.push((cancel.job_id.clone(), cancel.crash));
+                    }
+                    running
+                });
+                if stopped {
+                    let status = if cancel.crash { ProtoJobStatus::Failed } else { ProtoJobStatus::Cancelled };
+                    tokio::spawn(self.clone().finish(cancel.job_id, status, 137, String::new()));
+                }
+            }
+            Some(Command::Ping(_)) | Some(Command::ListRunningJobs(_)) => {
+                ack.running_job_ids = self.jobs.lock().map(|jobs| jobs.running.keys().cloned().collect()).unwrap_or_default();
+            }
+            None => {
+                ack.ok = false;
+                ack.message = "Unknown command".to_string();
+            }
+        }
+        ack
+    }
+
+    async fn run(self, assignment: JobAssignment) {
+        let outcome = (self.config.executor)(&assignment);
+        let _ = self.client.clone().update_job_status(JobStatusUpdate {
+            job_id: assignment.job_id.clone(),
+            status: ProtoJobStatus::Running as i32,
+            node_id: self.config.node_id.clone(),
+            fencing_token: self.fencing_token,
+            ..Default::default()
+        }).await;
+        self.report_free().await;
+
+        tokio::time::sleep(outcome.duration).await;
+        // Cancelled or killed meanwhile: that report went out already
+        let still_running = self.jobs.lock().is_ok_and(|mut jobs| {
+            let running = jobs.running.remove(&assignment.job_id).is_some();
+            if running {
+                jobs.finished.push(assignment.job_id.clone());
+            }
+            running
+        });
+        if !still_running {
+            return;
+        }
+        let status = match outcome.status {
+            JobStatus::Completed => ProtoJobStatus::Completed,
+            JobStatus::Cancelled => ProtoJobStatus::Cancelled,
+            _ => ProtoJobStatus::Failed,
+        };
+        self.finish(assignment.job_id, status, outcome.exit_code, outcome.logs).await;
+    }
+
+    async fn finish(self, job_id: String, status: ProtoJobStatus, exit_code: i64, logs: String) {
+        let _ = self.client.clone().update_job_status(JobStatusUpdate {
+            job_id,
+            status: status as i32,
+            exit_code,
+            logs,
+            node_id: self.config.node_id.clone(),
+            fencing_token: self.fencing_token,
+            ..Default::default()
+        }).await;
+        self.report_free().await;
+    }
+
+    /// Report what the running jobs leave free, as the real worker does
+    async fn report_free(&self) {
+        if !self.config.auto_report {
+            return;
+        }
+        let (cpu, memory_gb) = self.jobs.lock()
+            .map(|jobs| jobs.running.values().fold((0, 0.0), |(cpu, memory), (c, m)| (cpu + c, memory + m)))
+            .unwrap_or_default();
+        let _ = report(
+            &mut self.client.clone(),
+            &self.config.node_id,
+            self.fencing_token,
+            self.config.cpu_cores.saturating_sub(cpu),
+            (self.config.memory_gb - memory_gb).max(0.0),
+        ).await;
+    }
+}
@@ -0,0 +1,259 @@
+//! Embedded metrics history
+//!
+//! A small time-series store for the cost-over-time and utilization charts,
+//! so they do not depend on an external monitoring stack. Each series is a
+//! metric plus one label value (node or tenant id), bucketed at a fixed
+//! resolution and trimmed to the retention period. Gauges (utilization)
+//! average within a bucket; counters (spend) sum. The store can be
+//! snapshotted to a JSON file and restored on start.
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::EconomicScheduler;
+
+/// Share of a node's registered CPU in use (0-1), label: node id
+pub const NODE_CPU_UTILIZATION: &str = "node_cpu_utilization";
+/// Share of a node's registered memory in use (0-1), label: node id
+pub const NODE_MEMORY_UTILIZATION: &str = "node_memory_utilization";
+/// USD charged for delivered compute, label: tenant
+pub const TENANT_SPEND_USD: &str = "tenant_spend_usd";
+
+/// How values within a bucket combine
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SeriesKind {
+    /// Sampled level, averaged
+    Gauge,
+    /// Increments, summed
+    Counter,
+}
+
+/// Resolution and retention of the store
+#[derive(Debug, Clone, PartialEq)]
+pub struct TimeSeriesConfig {
+    pub resolution_secs: i64,
+    pub retention_secs: i64,
+}
+
+impl Default for TimeSeriesConfig {
+    fn default() -> Self {
+        Self {
+            resolution_secs: 60,
+            retention_secs: 30 * 24 * 3600,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+struct Bucket {
+    start: i64,
+    sum: f64,
+    count: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Series {
+    metric: String,
+    label: String,
+    kind: SeriesKind,
+    buckets: VecDeque<Bucket>,
+}
+
+/// Points of one series returned by a query
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SeriesPoints {
+    pub metric: String,
+    pub label: String,
+    /// (window start, value), oldest first
+    pub points: Vec<(i64, f64)>,
+}
+
+/// Thread-safe time-series store shared by scheduler clones
+#[derive(Debug, Clone, Default)]
+pub struct TimeSeriesStore {
+    config: TimeSeriesConfig,
+    series: Arc<Mutex<HashMap<(String, String), Series>>>,
+}
+
+impl TimeSeriesStore {
+    pub fn new(config: TimeSeriesConfig) -> Self {
+        Self {
+            config,
+            series: Arc::default(),
+        }
+    }
+
+    pub fn config(&self) -> &TimeSeriesConfig {
+        &self.config
+    }
+
+    fn record(&self, kind: SeriesKind, metric: &str, label: &str, value: f64, at: i64) {
+        let resolution = self.config.resolution_secs.max(1);
+        let start = at - at.rem_euclid(resolution);
+        let Ok(mut all) = self.series.lock() else {
+            return;
+        };
+        let series = all.entry((metric.to_string(), label.to_string())).or_insert_with(|| Series {
+            metric: metric.to_string(),
+            label: label.to_string(),
+            kind,
+            buckets: VecDeque::new(),
+        });
+
+        match series.buckets.back_mut() {
+            Some(bucket) if bucket.start == start => {
+                bucket.sum += value;
+                bucket.count += 1;
+            }
+            // Late samples for an older bucket are dropped
+            Some(bucket) if bucket.start > start => return,
+            _ => series.buckets.push_back(Bucket { start, sum: value, count: 1 }),
+        }
+
+        let oldest = at - self.config.retention_secs;
+        while series.buckets.front().is_some_and(|bucket| bucket.start < oldest) {
+            series.buckets.pop_front();
+        }
+    }
+
+    pub fn record_gauge(&self, metric: &str, label: &str, value: f64, at: i64) {
+        self.record(SeriesKind::Gauge, metric, label, value, at);
+    }
+
+    pub fn record_counter(&self, metric: &str, label: &str, delta: f64, at: i64) {
+        self.record(SeriesKind::Counter, metric, label, delta, at);
+    }
+
+    /// Series of `metric` (only `label` if given) in `[since, until)`, in
+    /// windows of `step_secs` (at least the resolution)
+    pub fn query(&self, metric: &str, label: Option<&str>, since: i64, until: i64, step_secs: i64) -> Vec<SeriesPoints> {
+        let step = step_secs.max(self.config.resolution_secs).max(1);
+        let Ok(all) = self.series.lock() else {
+            return Vec::new();
+        };
+
+        let mut result: Vec<SeriesPoints> = all.values()
+            .filter(|s| s.metric == metric && label.map_or(true, |label| s.label == label))
+            .map(|series| {
+                let mut points: Vec<(i64, f64)> = Vec::new();
+                let mut window: Option<(i64, f64, u32)> = None;
+                let value = |sum: f64, count: u32| match series.kind {
+                    SeriesKind::Gauge => sum / count.max(1) as f64,
+                    SeriesKind::Counter => sum,
+                };
+                for bucket in series.buckets.iter().filter(|b| b.start >= since && b.start < until) {
+                    let start = bucket.start - bucket.start.rem_euclid(step);
+                    match &mut window {
+                        Some((current, sum, count)) if *current == start => {
+                            *sum += bucket.sum;
+                            *count += bucket.count;
+                        }
+                        _ => {
+                            if let Some((current, sum, count)) = window {
+                                points.push((current, value(sum, count)));
+                            }
+                            window = Some((start, bucket.sum, bucket.count));
+                        }
+                    }
+                }
+                if let Some((current, sum, count)) = window {
+                    points.push((current, value(sum, count)));
+                }
+                SeriesPoints { metric: series.metric.clone(), label: series.label.clone(), points }
+            })
+            .collect();
+        result.sort_by(|a, b| a.label.cmp(&b.label));
+        result
+    }
+
+    /// Write every series to `path` (atomically, via a temporary file)
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let series: Vec<Series> = self.series.lock()
+            .map_err(|e| anyhow::anyhow!("Lock poisoned: {}", e))?
+            .values()
+            .cloned()
+            .collect();
+        let tmp = path.with_extension("tmp");
+        std::fs::write(&tmp, serde_json::to_vec(&series)?)?;
+        std::fs::rename(&tmp, path)?;
+        Ok(())
+    }
+
+    /// Replace the store's contents with a snapshot written by `save`
+    pub fn load(&self, path: &Path) -> Result<usize> {
+        let series: Vec<Series> = serde_json::from_slice(&std::fs::read(path)?)?;
+        let count = series.len();
+        let mut all = self.series.lock()
+            .map_err(|e| anyhow::anyhow!("Lock poisoned: {}", e))?;
+        *all = series.into_iter().map(|s| ((s.metric.clone(), s.label.clone()), s)).collect();
+        Ok(count)
+    }
+}
+
+impl EconomicScheduler {
+    pub fn set_metrics_history(&mut self, config: TimeSeriesConfig) {
+        self.metrics_history = TimeSeriesStore::new(config);
+    }
+
+    pub fn metrics_history(&self) -> &TimeSeriesStore {
+        &self.metrics_history
+    }
+
+    /// Record utilization from a worker's measured free resources
+    pub fn record_node_utilization(&self, node_id: &str, free_cpu: u32, free_memory_gb: u32, at: i64) {
+        let Some(node) = self.available_nodes.lock().ok().and_then(|nodes| nodes.get(node_id).cloned()) else {
+            return;
+        };
+        let used = |total: u32, free: u32| {
+            if total == 0 { 0.0 } else { total.saturating_sub(free) as f64 / total as f64 }
+        };
+        self.metrics_history.record_gauge(NODE_CPU_UTILIZATION, node_id, used(node.available_cpu, free_cpu), at);
+        self.metrics_history.record_gauge(NODE_MEMORY_UTILIZATION, node_id, used(node.available_memory_gb, free_memory_gb), at);
+    }
+}
+
+/// Snapshot the metrics history to `path` every `interval`
+pub async fn run_metrics_snapshots(scheduler: EconomicScheduler, path: std::path::PathBuf, interval: Duration) {
+    let mut ticker = tokio::time::interval(interval);
+    ticker.tick().await;
+    loop {
+        ticker.tick().await;
+        if let Err(e) = scheduler.metrics_history().save(&path) {
+            tracing::warn!("Failed to snapshot metrics history to {}: {}", path.display(), e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_gauges_average_and_counters_sum_per_window() {
+        let store = TimeSeriesStore::new(TimeSeriesConfig { resolution_secs: 60, retention_secs: 3600 });
+        for (at, value) in [(0, 0.2), (30, 0.4), (60, 0.9), (150, 0.5)] {
+            store.record_gauge(NODE_CPU_UTILIZATION, "node-1", value, at);
+        }
+        for at in [10, 70, 130] {
+            store.record_counter(TENANT_SPEND_USD, "lab", 1.5, at);
+        }
+        store.record_counter(TENANT_SPEND_USD, "acme", 2.0, 20);
+
+        let cpu = store.query(NODE_CPU_UTILIZATION, None, 0, 3600, 60);
+        assert_eq!(cpu[0].points.len(), 3);
+        assert!((cpu[0].points[0].1 - 0.3).abs() < 1e-9);
+
+        let spend = store.query(TENANT_SPEND_USD, Some("lab"), 0, 3600, 120);
+        assert_eq!(spend.len(), 1);
+        assert_eq!(spend[0].points, vec![(0, 3.0), (120, 1.5)]);
+
+        // Samples past the retention period are trimmed
+        store.record_gauge(NODE_CPU_UTILIZATION, "node-1", 1.0, 7200);
+        assert_eq!(store.query(NODE_CPU_UTILIZATION, None, 0, 10_000, 60)[0].points, vec![(7200, 1.0)]);
+    }
+}
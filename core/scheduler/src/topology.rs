@@ -0,0 +1,158 @@
+//! GPU interconnect topology
+//!
+//! Workers report how each pair of their GPUs is connected (as in
+//! `nvidia-smi topo -m`) and each GPU's NUMA node. Multi-GPU jobs are
+//! given the set of free GPUs whose slowest pairwise link is fastest, and
+//! nodes that can only offer a poorly connected set are ranked down, since
+//! gradients exchanged across the CPU interconnect can cost a training job
+//! more than a pricier node would.
+
+use serde::{Deserialize, Serialize};
+
+use crate::{EconomicScheduler, JobSpec, NodeInfo};
+
+/// Connection between two GPUs, slowest first
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum GpuLink {
+    #[default]
+    Unknown,
+    /// Across the SMP interconnect between NUMA nodes
+    Sys,
+    /// Same NUMA node, across PCIe host bridges
+    Node,
+    /// Through a PCIe host bridge
+    Phb,
+    /// Through multiple PCIe bridges
+    Pxb,
+    /// Through at most one PCIe bridge
+    Pix,
+    NvLink,
+}
+
+impl GpuLink {
+    /// Score multiplier for a job whose GPUs talk over this link
+    pub fn penalty(self) -> f64 {
+        match self {
+            GpuLink::NvLink | GpuLink::Unknown => 1.0,
+            GpuLink::Pix | GpuLink::Pxb => 1.1,
+            GpuLink::Phb | GpuLink::Node => 1.25,
+            GpuLink::Sys => 1.5,
+        }
+    }
+}
+
+/// GPU interconnect of one node
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct GpuTopology {
+    /// NUMA node of each GPU by index (-1: unknown)
+    pub numa_nodes: Vec<i32>,
+    /// Link between each pair of GPUs, indexed [a][b]
+    pub links: Vec<Vec<GpuLink>>,
+}
+
+impl GpuTopology {
+    /// Link between two GPUs, falling back to NUMA locality
+    pub fn link(&self, a: u32, b: u32) -> GpuLink {
+        let reported = self.links.get(a as usize)
+            .and_then(|row| row.get(b as usize))
+            .copied()
+            .unwrap_or_default();
+        if reported != GpuLink::Unknown {
+            return reported;
+        }
+        match (self.numa_nodes.get(a as usize), self.numa_nodes.get(b as usize)) {
+            (Some(&x), Some(&y)) if x >= 0 && y >= 0 => if x == y { GpuLink::Node } else { GpuLink::Sys },
+            _ => GpuLink::Unknown,
+        }
+    }
+
+    /// Slowest link within a set of GPUs (NvLink for a single GPU)
+    pub fn bottleneck(&self, gpus: &[u32]) -> GpuLink {
+        let mut slowest = GpuLink::NvLink;
+        for (i, &a) in gpus.iter().enumerate() {
+            for &b in &gpus[i + 1..] {
+                slowest = slowest.min(self.link(a, b));
+            }
+        }
+        slowest
+    }
+
+    /// The best connected `count` GPUs among `free`
+    ///
+    /// Grown greedily from each free GPU, always adding the GPU with the
+    /// fastest slowest link to those already chosen; the set with the
+    /// fastest bottleneck wins, ties going to the lowest indices.
+    pub fn best_set(&self, free: &[u32], count: usize) -> Option<Vec<u32>> {
+        if count == 0 || free.len() < count {
+            return None;
+        }
+        let mut best: Option<(GpuLink, Vec<u32>)> = None;
+        for &seed in free {
+            let mut chosen = vec![seed];
+            while chosen.len() < count {
+                let next = free.iter()
+                    .filter(|gpu| !chosen.contains(*gpu))
+                    .max_by_key(|&&gpu| {
+                        let slowest = chosen.iter().map(|&c| self.link(c, gpu)).min().unwrap_or_default();
+                        (slowest, std::cmp::Reverse(gpu))
+                    })
+                    .copied()?;
+                chosen.push(next);
+            }
+            chosen.sort_unstable();
+            let bottleneck = self.bottleneck(&chosen);
+            if best.as_ref().map_or(true, |(b, set)| bottleneck > *b || (bottleneck == *b && chosen < *set)) {
+                best = Some((bottleneck, chosen));
+            }
+        }
+        best.map(|(_, set)| set)
+    }
+}
+
+impl EconomicScheduler {
+    /// Score multiplier for a multi-GPU job on `node`: how well the GPUs it
+    /// would get there are connected (1.0 without topology information)
+    pub(crate) fn gpu_locality_penalty(&self, node: &NodeInfo, job: &JobSpec) -> f64 {
+        let count = job.resources.gpu_count as usize;
+        let Some(topology) = node.gpu_topology.as_ref().filter(|_| count > 1) else {
+            return 1.0;
+        };
+        let free = self.devices.free_gpus(&node.id, node.available_gpu);
+        topology.best_set(&free, count)
+            .map_or(1.0, |set| topology.bottleneck(&set).penalty())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Two NVLink pairs (0-1, 2-3) on one NUMA node each
+    fn dgx_like() -> GpuTopology {
+        use GpuLink::*;
+        GpuTopology {
+            numa_nodes: vec![0, 0, 1, 1],
+            links: vec![
+                vec![Unknown, NvLink, Sys, Sys],
+                vec![NvLink, Unknown, Sys, Sys],
+                vec![Sys, Sys, Unknown, NvLink],
+                vec![Sys, Sys, NvLink, Unknown],
+            ],
+        }
+    }
+
+    #[test]
+    fn test_best_set_keeps_gpus_on_one_nvlink_island() {
+        let topology = dgx_like();
+        assert_eq!(topology.best_set(&[0, 1, 2, 3], 2), Some(vec![0, 1]));
+        // GPU 0 is taken: the 2-3 pair beats spreading 1 and 2
+        assert_eq!(topology.best_set(&[1, 2, 3], 2), Some(vec![2, 3]));
+        assert_eq!(topology.bottleneck(&[0, 1, 2, 3]), GpuLink::Sys);
+        assert_eq!(topology.best_set(&[1, 2], 3), None);
+
+        // Without reported links NUMA locality still counts
+        let numa_only = GpuTopology { numa_nodes: vec![0, 1, 1], links: Vec::new() };
+        assert_eq!(numa_only.best_set(&[0, 1, 2], 2), Some(vec![1, 2]));
+        assert_eq!(numa_only.bottleneck(&[0, 1]).penalty(), GpuLink::Sys.penalty());
+    }
+}
@@ -0,0 +1,150 @@
+//! Workload trace recording
+//!
+//! With a trace attached, every node registration, resource report, job
+//! submission and job status report received over gRPC is appended to a
+//! JSON-lines file with its arrival time. Traces are portable: the
+//! simulator turns them into workloads, and they can be replayed against a
+//! staging scheduler at adjustable speed.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use tokio::io::AsyncWriteExt;
+use tokio::sync::mpsc;
+
+use crate::{JobSpec, JobStatus, NodeInfo};
+
+/// One recorded scheduler request
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum TraceRecord {
+    RegisterNode {
+        /// Unix time in milliseconds
+        at_ms: u64,
+        node: Box<NodeInfo>,
+    },
+    ResourceReport {
+        at_ms: u64,
+        node_id: String,
+        available_cpu: u32,
+        available_memory_gb: f64,
+        available_gpu: u32,
+        #[serde(default)]
+        available_disk_gb: f64,
+        #[serde(default)]
+        datasets: Vec<(String, f64)>,
+    },
+    SubmitJob {
+        at_ms: u64,
+        job: Box<JobSpec>,
+    },
+    JobStatusUpdate {
+        at_ms: u64,
+        job_id: String,
+        status: JobStatus,
+    },
+}
+
+impl TraceRecord {
+    /// Time the request was received
+    pub fn at_ms(&self) -> u64 {
+        match self {
+            TraceRecord::RegisterNode { at_ms, .. }
+            | TraceRecord::ResourceReport { at_ms, .. }
+            | TraceRecord::SubmitJob { at_ms, .. }
+            | TraceRecord::JobStatusUpdate { at_ms, .. } => *at_ms,
+        }
+    }
+}
+
+/// Current Unix time in milliseconds
+pub fn now_ms() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+/// Open `path` for appending and write queued records until every
+/// scheduler handle is dropped
+pub(crate) async fn run_trace_writer(path: PathBuf, mut rx: mpsc::UnboundedReceiver<TraceRecord>) {
+    let file = match tokio::fs::OpenOptions::new().create(true).append(true).open(&path).await {
+        Ok(file) => file,
+        Err(e) => {
+            tracing::error!("Trace recording disabled, cannot open {}: {}", path.display(), e);
+            return;
+        }
+    };
+    let mut out = tokio::io::BufWriter::new(file);
+
+    while let Some(first) = rx.recv().await {
+        // Write the whole burst, then flush so the file stays usable if we crash
+        let mut next = Some(first);
+        while let Some(record) = next {
+            match serde_json::to_vec(&record) {
+                Ok(mut line) => {
+                    line.push(b'\n');
+                    if let Err(e) = out.write_all(&line).await {
+                        tracing::warn!("Failed to write trace record: {}", e);
+                    }
+                }
+                Err(e) => tracing::warn!("Failed to serialize trace record: {}", e),
+            }
+            next = rx.try_recv().ok();
+        }
+
+        if let Err(e) = out.flush().await {
+            tracing::warn!("Failed to flush trace file: {}", e);
+        }
+    }
+
+    let _ = out.flush().await;
+}
+
+/// Read a trace file, ordered by arrival time
+pub fn read_trace(path: &Path) -> Result<Vec<TraceRecord>> {
+    let data = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read trace {}", path.display()))?;
+
+    let mut records = data.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| {
+            serde_json::from_str::<TraceRecord>(line)
+                .with_context(|| format!("Invalid trace record on line {}", i + 1))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    records.sort_by_key(|r| r.at_ms());
+    Ok(records)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_trace_round_trip() {
+        let path = std::env::temp_dir().join(format!("tgp-trace-{}.jsonl", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        let (tx, rx) = mpsc::unbounded_channel();
+        tx.send(TraceRecord::SubmitJob {
+            at_ms: 2_000,
+            job: Box::new(JobSpec { id: "job-1".to_string(), ..Default::default() }),
+        }).unwrap();
+        tx.send(TraceRecord::RegisterNode {
+            at_ms: 1_000,
+            node: Box::new(NodeInfo { id: "node-1".to_string(), ..Default::default() }),
+        }).unwrap();
+        drop(tx);
+        run_trace_writer(path.clone(), rx).await;
+
+        let records = read_trace(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(records.len(), 2);
+        assert!(matches!(&records[0], TraceRecord::RegisterNode { node, .. } if node.id == "node-1"));
+        assert!(matches!(&records[1], TraceRecord::SubmitJob { job, .. } if job.id == "job-1"));
+    }
+}
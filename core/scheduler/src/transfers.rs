@@ -0,0 +1,219 @@
+//! Peer-to-peer transfer accounting
+//!
+//! Workers copy datasets directly from each other and report progress here.
+//! The ledger keeps in-flight transfers, per-node byte counters, the
+//! bandwidth observed on each node-to-node link, how well each dataset
+//! compressed on the wire and how much of it delta syncs had to fetch.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+/// Weight of the newest sample in the link bandwidth moving average
+const BANDWIDTH_EWMA_ALPHA: f64 = 0.3;
+
+/// Progress of a single node-to-node transfer
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TransferRecord {
+    pub transfer_id: String,
+    pub dataset: String,
+    pub source_node: String,
+    pub dest_node: String,
+    pub bytes_transferred: u64,
+    pub total_bytes: u64,
+    pub elapsed_ms: u64,
+    pub completed: bool,
+    pub error: Option<String>,
+    /// Bytes that crossed the network (0 if the worker did not say)
+    #[serde(default)]
+    pub wire_bytes: u64,
+    /// The destination synced against an older copy of the dataset
+    #[serde(default)]
+    pub delta_sync: bool,
+    /// Dataset bytes taken from that older copy instead of the network
+    #[serde(default)]
+    pub reused_bytes: u64,
+}
+
+impl TransferRecord {
+    /// Bytes that crossed the network
+    fn network_bytes(&self) -> u64 {
+        if self.wire_bytes > 0 {
+            self.wire_bytes
+        } else {
+            self.bytes_transferred
+        }
+    }
+
+    /// Observed link throughput in megabits per second
+    pub fn throughput_mbps(&self) -> Option<f64> {
+        let bytes = self.network_bytes();
+        if self.elapsed_ms == 0 || bytes == 0 {
+            return None;
+        }
+        Some(bytes as f64 * 8.0 / 1_000_000.0 / (self.elapsed_ms as f64 / 1000.0))
+    }
+
+    /// Wire bytes per dataset byte, if the worker reported wire bytes
+    pub fn compression_ratio(&self) -> Option<f64> {
+        (self.wire_bytes > 0 && self.bytes_transferred > 0)
+            .then(|| self.wire_bytes as f64 / self.bytes_transferred as f64)
+    }
+
+    /// Share of the dataset a delta sync had to fetch
+    pub fn delta_fraction(&self) -> Option<f64> {
+        let dataset_bytes = self.bytes_transferred + self.reused_bytes;
+        (self.delta_sync && dataset_bytes > 0).then(|| self.bytes_transferred as f64 / dataset_bytes as f64)
+    }
+}
+
+/// Bytes moved by a node through the data service
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct NodeTransferTotals {
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+}
+
+#[derive(Debug, Default)]
+struct LedgerState {
+    active: HashMap<String, TransferRecord>,
+    totals: HashMap<String, NodeTransferTotals>,
+    /// Smoothed bandwidth per (source, destination) node pair
+    link_mbps: HashMap<(String, String), f64>,
+    /// Smoothed wire-to-dataset byte ratio per dataset
+    compression: HashMap<String, f64>,
+    /// Smoothed share of each dataset delta syncs fetched
+    delta: HashMap<String, f64>,
+}
+
+/// Thread-safe transfer ledger shared by scheduler clones
+#[derive(Debug, Clone, Default)]
+pub struct TransferLedger {
+    state: Arc<Mutex<LedgerState>>,
+}
+
+impl TransferLedger {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Apply a progress or completion report
+    pub fn report(&self, record: TransferRecord) {
+        let Ok(mut state) = self.state.lock() else {
+            return;
+        };
+
+        if !record.completed && record.error.is_none() {
+            state.active.insert(record.transfer_id.clone(), record);
+            return;
+        }
+
+        state.active.remove(&record.transfer_id);
+        if record.error.is_some() {
+            return;
+        }
+
+        state.totals.entry(record.source_node.clone()).or_default().bytes_sent += record.bytes_transferred;
+        state.totals.entry(record.dest_node.clone()).or_default().bytes_received += record.bytes_transferred;
+
+        if let Some(mbps) = record.throughput_mbps() {
+            let link = (record.source_node.clone(), record.dest_node.clone());
+            let smoothed = match state.link_mbps.get(&link) {
+                Some(prev) => BANDWIDTH_EWMA_ALPHA * mbps + (1.0 - BANDWIDTH_EWMA_ALPHA) * prev,
+                None => mbps,
+            };
+            state.link_mbps.insert(link, smoothed);
+        }
+
+        if let Some(ratio) = record.compression_ratio() {
+            smooth(&mut state.compression, &record.dataset, ratio);
+        }
+        if let Some(fraction) = record.delta_fraction() {
+            smooth(&mut state.delta, &record.dataset, fraction);
+        }
+    }
+
+    /// Transfers currently in progress
+    pub fn active_transfers(&self) -> Vec<TransferRecord> {
+        self.state.lock()
+            .map(|state| state.active.values().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// Bytes sent/received by a node
+    pub fn node_totals(&self, node_id: &str) -> NodeTransferTotals {
+        self.state.lock()
+            .ok()
+            .and_then(|state| state.totals.get(node_id).copied())
+            .unwrap_or_default()
+    }
+
+    /// Smoothed observed bandwidth between two nodes, if any transfer completed
+    pub fn link_bandwidth_mbps(&self, source: &str, dest: &str) -> Option<f64> {
+        self.state.lock()
+            .ok()
+            .and_then(|state| state.link_mbps.get(&(source.to_string(), dest.to_string())).copied())
+    }
+
+    /// Smoothed wire bytes per dataset byte seen when moving `dataset`
+    pub fn compression_ratio(&self, dataset: &str) -> Option<f64> {
+        self.state.lock()
+            .ok()
+            .and_then(|state| state.compression.get(dataset).copied())
+    }
+
+    /// Smoothed share of `dataset` that delta syncs had to fetch
+    pub fn delta_fraction(&self, dataset: &str) -> Option<f64> {
+        self.state.lock()
+            .ok()
+            .and_then(|state| state.delta.get(dataset).copied())
+    }
+}
+
+/// Fold a per-dataset sample into its moving average
+fn smooth(averages: &mut HashMap<String, f64>, dataset: &str, sample: f64) {
+    let smoothed = match averages.get(dataset) {
+        Some(prev) => BANDWIDTH_EWMA_ALPHA * sample + (1.0 - BANDWIDTH_EWMA_ALPHA) * prev,
+        None => sample,
+    };
+    averages.insert(dataset.to_string(), smoothed);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(id: &str, bytes: u64, elapsed_ms: u64, completed: bool) -> TransferRecord {
+        TransferRecord {
+            transfer_id: id.to_string(),
+            dataset: "coco".to_string(),
+            source_node: "a".to_string(),
+            dest_node: "b".to_string(),
+            bytes_transferred: bytes,
+            total_bytes: 125_000_000,
+            elapsed_ms,
+            completed,
+            error: None,
+            wire_bytes: 0,
+            delta_sync: false,
+            reused_bytes: 0,
+        }
+    }
+
+    #[test]
+    fn test_completed_transfer_updates_accounting() {
+        let ledger = TransferLedger::new();
+
+        ledger.report(record("t1", 50_000_000, 400, false));
+        assert_eq!(ledger.active_transfers().len(), 1);
+        assert_eq!(ledger.node_totals("a").bytes_sent, 0);
+
+        // 125MB in 1s = 1000 Mbit/s
+        ledger.report(record("t1", 125_000_000, 1000, true));
+        assert!(ledger.active_transfers().is_empty());
+        assert_eq!(ledger.node_totals("a").bytes_sent, 125_000_000);
+        assert_eq!(ledger.node_totals("b").bytes_received, 125_000_000);
+        assert!((ledger.link_bandwidth_mbps("a", "b").unwrap() - 1000.0).abs() < 1e-6);
+        assert!(ledger.link_bandwidth_mbps("b", "a").is_none());
+    }
+}
@@ -0,0 +1,216 @@
+//! Job state machine
+//!
+//! Status changes go through `update_job_state`, which checks them against
+//! the job lifecycle:
+//!
+//! ```text
+//! Pending ──▶ Scheduled ──▶ Running ──▶ Completed | Failed | Cancelled
+//!    │           ▲   │         │
+//!    │           └───┴─────────┘  migration, re-placement
+//!    └──▶ Running | Completed | Failed | Cancelled  (late report, cache hit, no fit)
+//! ```
+//!
+//! A finished job never changes again (a repeated report of its outcome is
+//! ignored), and only submission puts a job in Pending: requeueing after a
+//! node loss or eviction submits it anew. Every accepted change is recorded
+//! with its time in a per-job history, served by GetJobHistory, and the
+//! job's milestones (submitted, first scheduled, first started, finished)
+//! are kept on its `JobState` for status responses.
+
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+
+use crate::error::SchedulerError;
+use crate::{unix_now, EconomicScheduler, JobState, JobStatus};
+
+/// Transitions kept per job; the oldest are dropped first
+pub const MAX_TRANSITIONS_PER_JOB: usize = 64;
+
+/// One accepted status change
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JobTransition {
+    /// None for a first submission
+    pub from: Option<JobStatus>,
+    pub to: JobStatus,
+    /// Node the job was on after the change
+    pub node_id: Option<String>,
+    pub at: i64,
+}
+
+impl JobStatus {
+    /// Whether the lifecycle allows a change from `self` to `next`
+    pub fn can_transition_to(&self, next: &JobStatus) -> bool {
+        !self.is_terminal() && *next != JobStatus::Pending
+    }
+}
+
+impl JobState {
+    /// Record the time the job reached its current status; the first
+    /// placement and start are kept across migrations
+    pub fn stamp(&mut self, at: i64) {
+        match self.status {
+            JobStatus::Pending => {}
+            JobStatus::Scheduled => {
+                self.scheduled_at.get_or_insert(at);
+            }
+            JobStatus::Running => {
+                self.started_at.get_or_insert(at);
+            }
+            JobStatus::Completed | JobStatus::Failed | JobStatus::Cancelled => self.finished_at = Some(at),
+        }
+    }
+
+    /// Seconds from submission to the first start
+    pub fn queue_wait_secs(&self) -> Option<i64> {
+        Some(self.started_at? - self.submitted_at?)
+    }
+
+    /// Seconds from the first start to the end
+    pub fn run_secs(&self) -> Option<i64> {
+        Some(self.finished_at? - self.started_at?)
+    }
+}
+
+/// Transition history of every job, shared by scheduler clones
+#[derive(Debug, Clone, Default)]
+pub struct JobHistory {
+    jobs: Arc<Mutex<HashMap<String, VecDeque<JobTransition>>>>,
+}
+
+impl JobHistory {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record(&self, job_id: &str, transition: JobTransition) {
+        let mut jobs = self.jobs.lock().unwrap_or_else(|e| e.into_inner());
+        let history = jobs.entry(job_id.to_string()).or_default();
+        if history.len() == MAX_TRANSITIONS_PER_JOB {
+            history.pop_front();
+        }
+        history.push_back(transition);
+    }
+
+    /// A job's transitions, oldest first
+    pub fn get(&self, job_id: &str) -> Vec<JobTransition> {
+        self.jobs.lock()
+            .map(|jobs| jobs.get(job_id).map(|history| history.iter().cloned().collect()).unwrap_or_default())
+            .unwrap_or_default()
+    }
+}
+
+impl EconomicScheduler {
+    /// Every recorded status change of `job_id`, oldest first
+    pub fn job_history(&self, job_id: &str) -> Vec<JobTransition> {
+        self.history.get(job_id)
+    }
+
+    /// Check a status change against the job's current status; Ok(false)
+    /// when it repeats the job's outcome and should be ignored
+    pub(crate) fn check_transition(&self, job_id: &str, to: &JobStatus) -> Result<bool, SchedulerError> {
+        let Some(from) = self.get_job_state(job_id).map(|state| state.status) else {
+            return Ok(true);
+        };
+        if from.is_terminal() && from == *to {
+            return Ok(false);
+        }
+        if !from.can_transition_to(to) {
+            return Err(SchedulerError::InvalidTransition { job_id: job_id.to_string(), from, to: to.clone() });
+        }
+        Ok(true)
+    }
+
+    /// Record that `job_id` was (re)submitted and is Pending; returns its
+    /// submission time, which a requeued job keeps from before
+    pub(crate) fn record_submission(&self, job_id: &str, previous: Option<&JobState>) -> i64 {
+        let now = unix_now();
+        self.history.record(job_id, JobTransition {
+            from: previous.map(|state| state.status.clone()),
+            to: JobStatus::Pending,
+            node_id: None,
+            at: now,
+        });
+        previous
+            .filter(|state| !state.status.is_terminal())
+            .and_then(|state| state.submitted_at)
+            .unwrap_or(now)
+    }
+
+    pub(crate) fn record_transition(&self, job_id: &str, from: JobStatus, to: JobStatus, node_id: Option<String>) {
+        self.history.record(job_id, JobTransition { from: Some(from), to, node_id, at: unix_now() });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{JobSpec, NodeInfo, ResourceRequirements, SlaConstraints};
+
+    #[tokio::test]
+    async fn test_finished_jobs_cannot_be_revived() {
+        let scheduler = EconomicScheduler::new();
+        scheduler.register_node(NodeInfo {
+            id: "node-1".to_string(),
+            available_cpu: 4,
+            available_memory_gb: 8,
+            location: "local".to_string(),
+            cost_per_hour: 0.1,
+            ..Default::default()
+        }).unwrap();
+        scheduler.schedule(JobSpec {
+            id: "job-1".to_string(),
+            resources: ResourceRequirements { cpu_cores: 1, memory_gb: 1, ..Default::default() },
+            sla: SlaConstraints { max_latency_ms: 10_000, ..Default::default() },
+            ..Default::default()
+        }).await.unwrap();
+        scheduler.update_job_state("job-1".to_string(), JobStatus::Running, None).unwrap();
+        scheduler.update_job_state("job-1".to_string(), JobStatus::Completed, None).unwrap();
+
+        // A duplicate report is ignored, a late one refused
+        scheduler.update_job_state("job-1".to_string(), JobStatus::Completed, None).unwrap();
+        let err = scheduler.update_job_state("job-1".to_string(), JobStatus::Running, None).unwrap_err();
+        assert_eq!(crate::error::classify(&err).map(SchedulerError::reason), Some("INVALID_TRANSITION"));
+        assert_eq!(scheduler.get_job_state("job-1").unwrap().status, JobStatus::Completed);
+
+        let steps: Vec<_> = scheduler.job_history("job-1").into_iter().map(|t| (t.from, t.to)).collect();
+        assert_eq!(steps, vec![
+            (None, JobStatus::Pending),
+            (Some(JobStatus::Pending), JobStatus::Scheduled),
+            (Some(JobStatus::Scheduled), JobStatus::Running),
+            (Some(JobStatus::Running), JobStatus::Completed),
+        ]);
+        assert!(!JobStatus::Running.can_transition_to(&JobStatus::Pending));
+
+        let state = scheduler.get_job_state("job-1").unwrap();
+        assert!(state.submitted_at.is_some() && state.scheduled_at.is_some());
+        assert!(state.queue_wait_secs().unwrap() >= 0 && state.run_secs().unwrap() >= 0);
+    }
+
+    #[tokio::test]
+    async fn test_resubmitted_job_ids_are_refused() {
+        use crate::grpc::proto::{scheduler_service_server::SchedulerService, JobSubmitRequest};
+
+        let scheduler = EconomicScheduler::new();
+        scheduler.register_node(crate::test_support::node("node-1", 0.1)).unwrap();
+        let job = JobSpec {
+            id: "job-1".to_string(),
+            resources: ResourceRequirements { cpu_cores: 1, memory_gb: 1, ..Default::default() },
+            sla: SlaConstraints { max_latency_ms: 10_000, ..Default::default() },
+            ..Default::default()
+        };
+        scheduler.schedule(job.clone()).await.unwrap();
+        scheduler.update_job_state("job-1".to_string(), JobStatus::Completed, None).unwrap();
+
+        let err = scheduler.schedule(job).await.unwrap_err();
+        assert_eq!(crate::error::classify(&err).map(SchedulerError::reason), Some("ALREADY_EXISTS"));
+        let status = SchedulerService::submit_job(&scheduler, tonic::Request::new(JobSubmitRequest {
+            job_id: "job-1".to_string(),
+            ..Default::default()
+        })).await.unwrap_err();
+        assert_eq!(status.code(), tonic::Code::AlreadyExists);
+
+        assert_eq!(scheduler.get_job_state("job-1").unwrap().status, JobStatus::Completed);
+        assert_eq!(scheduler.job_history("job-1").len(), 3);
+    }
+}
@@ -0,0 +1,245 @@
+//! Result verification by redundant execution
+//!
+//! A sample of the jobs completed by untrusted nodes (reliability below
+//! `trusted_reliability`) is run again on a different node. Workers report
+//! a hash of each job's output; when the replica's hash disagrees with the
+//! original's, both nodes are flagged and the disagreement lowers their
+//! reputation. Two runs cannot tell which node is wrong, so an honest node
+//! recovers as its other verifications agree.
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use crate::events::SchedulerEvent;
+use crate::{unix_now, EconomicScheduler, JobStatus};
+
+/// Appended to a job id to name its verification replica
+pub const REPLICA_SUFFIX: &str = ".verify";
+
+/// Which completed jobs are re-run for verification
+#[derive(Debug, Clone, PartialEq)]
+pub struct VerificationPolicy {
+    /// Share of eligible jobs verified (0-1)
+    pub sample_rate: f64,
+    /// Nodes at or above this reliability are not verified
+    pub trusted_reliability: f64,
+}
+
+impl Default for VerificationPolicy {
+    fn default() -> Self {
+        Self {
+            sample_rate: 0.1,
+            trusted_reliability: 0.95,
+        }
+    }
+}
+
+/// State of one job's verification
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VerificationOutcome {
+    /// Replica has not finished
+    Pending,
+    /// Replica produced the same output
+    Agreed,
+    /// Replica produced different output; both nodes were flagged
+    Disagreed,
+    /// Replica did not complete, so nothing was compared
+    Inconclusive,
+}
+
+/// A verified job and its replica
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VerificationRecord {
+    pub job_id: String,
+    pub node_id: String,
+    pub output_hash: String,
+    pub replica_id: String,
+    pub replica_node: Option<String>,
+    pub outcome: VerificationOutcome,
+}
+
+/// Verification records by original job id
+#[derive(Debug, Clone, Default)]
+pub struct Verifications {
+    records: Arc<Mutex<HashMap<String, VerificationRecord>>>,
+    replicas: Arc<Mutex<HashMap<String, String>>>,
+}
+
+impl Verifications {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+/// Whether `job_id` falls in the sample; stable across restarts and
+/// scheduler replicas
+fn sampled(job_id: &str, sample_rate: f64) -> bool {
+    let digest = Sha256::digest(job_id.as_bytes());
+    let mut bytes = [0u8; 8];
+    bytes.copy_from_slice(&digest[..8]);
+    (u64::from_le_bytes(bytes) as f64 / u64::MAX as f64) < sample_rate
+}
+
+impl EconomicScheduler {
+    pub fn set_verification_policy(&mut self, policy: Option<VerificationPolicy>) {
+        self.verification_policy = policy;
+    }
+
+    pub fn verification(&self, job_id: &str) -> Option<VerificationRecord> {
+        self.verifications.records.lock().ok()?.get(job_id).cloned()
+    }
+
+    /// Start or settle verification for a job that reached terminal `status`
+    ///
+    /// Call before the status is applied, while the job's spec is still
+    /// held. A sampled original on an untrusted node gets a replica on
+    /// another node; a finished replica is compared with its original.
+    pub async fn verify_finished(&self, job_id: &str, status: &JobStatus, output_hash: &str) -> Result<()> {
+        let original = self.verifications.replicas.lock()
+            .map_err(|e| anyhow::anyhow!("Lock poisoned: {}", e))?
+            .remove(job_id);
+        if let Some(original) = original {
+            return self.settle_verification(&original, job_id, status, output_hash);
+        }
+
+        let Some(policy) = &self.verification_policy else {
+            return Ok(());
+        };
+        if *status != JobStatus::Completed || output_hash.is_empty() || !sampled(job_id, policy.sample_rate) {
+            return Ok(());
+        }
+        let Some(node_id) = self.get_job_state(job_id).and_then(|state| state.assigned_node) else {
+            return Ok(());
+        };
+        if self.reputation.reputation(&node_id).reliability >= policy.trusted_reliability {
+            return Ok(());
+        }
+        let spec = self.placed_jobs.lock()
+            .map_err(|e| anyhow::anyhow!("Lock poisoned: {}", e))?
+            .get(job_id)
+            .cloned();
+        let Some(mut replica) = spec else {
+            return Ok(());
+        };
+
+        replica.id = format!("{}{}", job_id, REPLICA_SUFFIX);
+        replica.disable_result_cache = true;
+        replica.array_id = None;
+        replica.avoid_nodes.push(node_id.clone());
+        let replica_id = replica.id.clone();
+
+        self.verifications.records.lock()
+            .map_err(|e| anyhow::anyhow!("Lock poisoned: {}", e))?
+            .insert(job_id.to_string(), VerificationRecord {
+                job_id: job_id.to_string(),
+                node_id: node_id.clone(),
+                output_hash: output_hash.to_string(),
+                replica_id: replica_id.clone(),
+                replica_node: None,
+                outcome: VerificationOutcome::Pending,
+            });
+        self.verifications.replicas.lock()
+            .map_err(|e| anyhow::anyhow!("Lock poisoned: {}", e))?
+            .insert(replica_id.clone(), job_id.to_string());
+
+        tracing::info!("Verifying job {} from node {} with replica {}", job_id, node_id, replica_id);
+        if let Err(e) = self.requeue(replica).await {
+            tracing::warn!("Verification replica {} not placed: {}", replica_id, e);
+            if let Ok(mut replicas) = self.verifications.replicas.lock() {
+                replicas.remove(&replica_id);
+            }
+            self.settle_verification(job_id, &replica_id, &JobStatus::Failed, "")?;
+        }
+        Ok(())
+    }
+
+    fn settle_verification(&self, job_id: &str, replica_id: &str, status: &JobStatus, output_hash: &str) -> Result<()> {
+        let replica_node = self.get_job_state(replica_id).and_then(|state| state.assigned_node);
+        let mut records = self.verifications.records.lock()
+            .map_err(|e| anyhow::anyhow!("Lock poisoned: {}", e))?;
+        let Some(record) = records.get_mut(job_id) else {
+            return Ok(());
+        };
+        record.replica_node = replica_node.clone();
+
+        let replica_node = match replica_node {
+            Some(node) if *status == JobStatus::Completed && !output_hash.is_empty() => node,
+            _ => {
+                record.outcome = VerificationOutcome::Inconclusive;
+                tracing::info!("Verification of job {} inconclusive: replica did not complete", job_id);
+                return Ok(());
+            }
+        };
+
+        let agreed = record.output_hash == output_hash;
+        record.outcome = if agreed { VerificationOutcome::Agreed } else { VerificationOutcome::Disagreed };
+        let nodes = vec![record.node_id.clone(), replica_node];
+        drop(records);
+
+        for node in &nodes {
+            self.reputation.record_verification(node, agreed);
+        }
+        if agreed {
+            tracing::info!("Verification of job {} agreed ({} and {})", job_id, nodes[0], nodes[1]);
+        } else {
+            tracing::warn!("Verification of job {} disagreed; flagging nodes {} and {}", job_id, nodes[0], nodes[1]);
+            self.publish_event(SchedulerEvent::VerificationFailed {
+                job_id: job_id.to_string(),
+                nodes,
+                timestamp: unix_now(),
+            });
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{JobSpec, NodeInfo, SlaConstraints};
+
+    #[tokio::test]
+    async fn test_disagreeing_replica_flags_both_nodes() {
+        let mut scheduler = EconomicScheduler::new();
+        scheduler.set_verification_policy(Some(VerificationPolicy {
+            sample_rate: 1.0,
+            trusted_reliability: 1.1,
+        }));
+        for (id, cost_per_hour) in [("cheap", 0.1), ("other", 0.2)] {
+            scheduler.register_node(NodeInfo {
+                id: id.to_string(),
+                available_cpu: 16,
+                available_memory_gb: 64,
+                location: "vps-1".to_string(),
+                cost_per_hour,
+                ..Default::default()
+            }).unwrap();
+        }
+
+        let job = JobSpec {
+            id: "job-1".to_string(),
+            sla: SlaConstraints { max_latency_ms: 1_000, ..Default::default() },
+            ..Default::default()
+        };
+        assert_eq!(scheduler.schedule(job).await.unwrap().node_id, "cheap");
+        scheduler.verify_finished("job-1", &JobStatus::Completed, "aaaa").await.unwrap();
+        scheduler.update_job_state("job-1".to_string(), JobStatus::Completed, None).unwrap();
+
+        // The replica avoids the original node
+        let replica = scheduler.get_job_state("job-1.verify").unwrap();
+        assert_eq!(replica.assigned_node.as_deref(), Some("other"));
+        assert_eq!(scheduler.verification("job-1").unwrap().outcome, VerificationOutcome::Pending);
+
+        scheduler.verify_finished("job-1.verify", &JobStatus::Completed, "bbbb").await.unwrap();
+        assert_eq!(scheduler.verification("job-1").unwrap().outcome, VerificationOutcome::Disagreed);
+        for node in ["cheap", "other"] {
+            let reputation = scheduler.reputation().reputation(node);
+            assert!(reputation.flagged);
+            assert!(reputation.reliability < 1.0);
+        }
+    }
+}
@@ -0,0 +1,181 @@
+//! Worker version checks and the self-update channel
+//!
+//! The scheduler advertises the oldest worker version it supports and,
+//! optionally, the latest worker release. Workers check in periodically
+//! with their version and platform. A worker older than the minimum is
+//! told it must update; one older than the latest release may download it
+//! through the scheduler. Releases are signed offline with the operator's
+//! Ed25519 release key: the detached signature covers `release_message`
+//! (version, platform and the binary's SHA-256), so a signed binary cannot
+//! be replayed as another version. Workers verify it against the key they
+//! were provisioned with, and refuse anything not newer than themselves,
+//! before replacing themselves, so the scheduler cannot push code the
+//! operator did not sign or roll workers back to an older signed release.
+
+use anyhow::{Context, Result};
+use sha2::{Digest, Sha256};
+use std::cmp::Ordering;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::EconomicScheduler;
+
+/// Parse "1.4.2" (an optional leading "v" and a "-suffix" are ignored)
+pub fn parse_version(version: &str) -> Option<(u64, u64, u64)> {
+    let core = version.trim().trim_start_matches('v').split(['-', '+']).next()?;
+    let mut parts = core.split('.').map(|part| part.parse::<u64>().ok());
+    let major = parts.next()??;
+    let minor = parts.next().unwrap_or(Some(0))?;
+    let patch = parts.next().unwrap_or(Some(0))?;
+    Some((major, minor, patch))
+}
+
+/// Order two versions; unparseable versions sort first
+pub fn compare_versions(a: &str, b: &str) -> Ordering {
+    parse_version(a).cmp(&parse_version(b))
+}
+
+/// What a release signature covers, e.g.
+/// `tgp-worker-release:0.3.0:linux-x86_64:<hex sha256>`
+pub fn release_message(version: &str, platform: &str, sha256: &str) -> String {
+    format!("tgp-worker-release:{}:{}:{}", version, platform, sha256)
+}
+
+/// A signed worker binary served to workers
+#[derive(Debug, Clone)]
+pub struct WorkerRelease {
+    pub version: String,
+    /// Platform the binary runs on, e.g. "linux-x86_64"
+    pub platform: String,
+    pub binary: Arc<Vec<u8>>,
+    /// Hex SHA-256 of the binary
+    pub sha256: String,
+    /// Detached Ed25519 signature over `release_message`
+    pub signature: Vec<u8>,
+}
+
+impl WorkerRelease {
+    /// Load a release binary and its signature (`<binary>.sig` by default)
+    pub fn load(version: &str, platform: &str, binary: &Path, signature: Option<&Path>) -> Result<Self> {
+        anyhow::ensure!(parse_version(version).is_some(), "Malformed worker release version {}", version);
+        let bytes = std::fs::read(binary)
+            .with_context(|| format!("Failed to read worker release {}", binary.display()))?;
+        let signature_path = signature.map(Path::to_path_buf).unwrap_or_else(|| {
+            let mut path = binary.as_os_str().to_owned();
+            path.push(".sig");
+            PathBuf::from(path)
+        });
+        let signature = std::fs::read(&signature_path)
+            .with_context(|| format!("Failed to read release signature {}", signature_path.display()))?;
+        anyhow::ensure!(signature.len() == 64, "Release signature {} is not a raw Ed25519 signature", signature_path.display());
+
+        Ok(Self {
+            version: version.to_string(),
+            platform: platform.to_string(),
+            sha256: format!("{:x}", Sha256::digest(&bytes)),
+            binary: Arc::new(bytes),
+            signature,
+        })
+    }
+}
+
+/// Supported and latest worker versions
+#[derive(Debug, Clone, Default)]
+pub struct UpdateChannel {
+    /// Workers older than this must update (None: any version)
+    pub min_version: Option<String>,
+    pub latest: Option<WorkerRelease>,
+}
+
+/// What a worker of some version should do
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct VersionCheck {
+    pub min_version: String,
+    pub latest_version: String,
+    /// Below the minimum
+    pub update_required: bool,
+    /// A newer release for the worker's platform can be downloaded
+    pub update_available: bool,
+    pub sha256: String,
+    pub signature: Vec<u8>,
+    pub size_bytes: u64,
+}
+
+impl EconomicScheduler {
+    pub fn set_update_channel(&mut self, channel: UpdateChannel) {
+        self.update_channel = channel;
+    }
+
+    pub fn update_channel(&self) -> &UpdateChannel {
+        &self.update_channel
+    }
+
+    /// Compare a worker's version against the channel
+    pub fn check_worker_version(&self, node_id: &str, version: &str, platform: &str) -> VersionCheck {
+        let channel = &self.update_channel;
+        let update_required = channel.min_version.as_deref()
+            .is_some_and(|min| compare_versions(version, min) == Ordering::Less);
+        if update_required {
+            tracing::warn!(
+                "Worker {} runs version {}, below the minimum {}",
+                node_id, version, channel.min_version.as_deref().unwrap_or_default()
+            );
+        }
+
+        let mut check = VersionCheck {
+            min_version: channel.min_version.clone().unwrap_or_default(),
+            update_required,
+            ..Default::default()
+        };
+        if let Some(release) = &channel.latest {
+            check.latest_version = release.version.clone();
+            if release.platform == platform && compare_versions(version, &release.version) == Ordering::Less {
+                check.update_available = true;
+                check.sha256 = release.sha256.clone();
+                check.signature = release.signature.clone();
+                check.size_bytes = release.binary.len() as u64;
+            }
+        }
+        check
+    }
+
+    /// Binary of the latest release, if it is `version`
+    pub fn worker_release(&self, version: &str) -> Option<WorkerRelease> {
+        self.update_channel.latest.clone().filter(|release| release.version == version)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_old_worker_is_offered_the_signed_release() {
+        let mut scheduler = EconomicScheduler::new();
+        let binary = b"#!/bin/sh\necho worker 0.3.0\n".to_vec();
+        scheduler.set_update_channel(UpdateChannel {
+            min_version: Some("0.2.0".to_string()),
+            latest: Some(WorkerRelease {
+                version: "0.3.0".to_string(),
+                platform: "linux-x86_64".to_string(),
+                sha256: format!("{:x}", Sha256::digest(&binary)),
+                binary: Arc::new(binary),
+                signature: vec![7; 64],
+            }),
+        });
+
+        let stale = scheduler.check_worker_version("node-1", "0.1.9", "linux-x86_64");
+        assert!(stale.update_required && stale.update_available);
+        assert_eq!(stale.signature.len(), 64);
+
+        let behind = scheduler.check_worker_version("node-2", "v0.2.5", "linux-x86_64");
+        assert!(!behind.update_required && behind.update_available);
+
+        // No binary for the platform, and nothing newer for current workers
+        assert!(!scheduler.check_worker_version("node-3", "0.2.5", "linux-aarch64").update_available);
+        assert!(!scheduler.check_worker_version("node-4", "0.3.0", "linux-x86_64").update_available);
+        assert!(scheduler.worker_release("0.3.0").is_some());
+        assert_eq!(compare_versions("0.10.0", "0.9.1"), Ordering::Greater);
+        assert_eq!(release_message("0.3.0", "linux-x86_64", "ab"), "tgp-worker-release:0.3.0:linux-x86_64:ab");
+    }
+}
@@ -0,0 +1,465 @@
+//! Workflow engine
+//!
+//! A workflow is a list of named steps, each a job template run as job
+//! `<workflow>-<step>-<attempt>`. Starting from the first step:
+//! - a completed step continues at its `on_success` step (default: the next
+//!   declared step; `end` stops the workflow),
+//! - a failed step is retried per its `RetryPolicy`, then continues at its
+//!   `on_failure` step, or fails the workflow if it has none.
+//!
+//! A workflow succeeds when a completed step leads to its end, including
+//! through a failure branch that recovered.
+//!
+//! Steps pass parameters through artifacts: small `name=value` outputs a
+//! job reports when it finishes (see the worker's `$TGP_ARTIFACTS_FILE`).
+//! Later steps see every earlier artifact as `TGP_ARTIFACT_<STEP>_<NAME>`
+//! and as `{{steps.<step>.<name>}}` in their command and environment values.
+//!
+//! `run_workflow_engine` advances workflows as step jobs change state.
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::broadcast::error::RecvError;
+
+use crate::error::SchedulerError;
+use crate::{EconomicScheduler, JobSpec, JobStatus};
+
+/// Step target that finishes the workflow
+pub const END_STEP: &str = "end";
+
+/// How often a failed step is attempted
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RetryPolicy {
+    /// Total attempts including the first (0 is treated as 1)
+    pub max_attempts: u32,
+    /// Delay before each retry
+    pub backoff_secs: u64,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self { max_attempts: 1, backoff_secs: 0 }
+    }
+}
+
+/// One named step of a workflow
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct WorkflowStep {
+    pub name: String,
+    /// Job template; its id is replaced per attempt
+    pub job: JobSpec,
+    #[serde(default)]
+    pub retry: RetryPolicy,
+    /// Step after success (None: next declared step)
+    #[serde(default)]
+    pub on_success: Option<String>,
+    /// Step after the last failed attempt (None: the workflow fails)
+    #[serde(default)]
+    pub on_failure: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct WorkflowSpec {
+    pub id: String,
+    pub steps: Vec<WorkflowStep>,
+}
+
+impl WorkflowSpec {
+    /// Reject empty workflows, duplicate step names and unknown targets
+    pub fn validate(&self) -> Result<()> {
+        if self.steps.is_empty() {
+            return Err(SchedulerError::invalid_spec(format!("Workflow {} has no steps", self.id)).into());
+        }
+        let mut names = HashSet::new();
+        for step in &self.steps {
+            if step.name.is_empty() || step.name == END_STEP {
+                return Err(SchedulerError::invalid_spec(format!("Invalid step name '{}' in workflow {}", step.name, self.id)).into());
+            }
+            if !names.insert(step.name.as_str()) {
+                return Err(SchedulerError::invalid_spec(format!("Duplicate step '{}' in workflow {}", step.name, self.id)).into());
+            }
+        }
+        for step in &self.steps {
+            for target in step.on_success.iter().chain(&step.on_failure) {
+                if target != END_STEP && !names.contains(target.as_str()) {
+                    return Err(SchedulerError::invalid_spec(format!("Step '{}' targets unknown step '{}'", step.name, target)).into());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn step(&self, name: &str) -> Option<&WorkflowStep> {
+        self.steps.iter().find(|s| s.name == name)
+    }
+
+    /// Step after `name` completes, or None at the end
+    fn after_success(&self, name: &str) -> Option<String> {
+        let index = self.steps.iter().position(|s| s.name == name)?;
+        match &self.steps[index].on_success {
+            Some(target) if target == END_STEP => None,
+            Some(target) => Some(target.clone()),
+            None => self.steps.get(index + 1).map(|s| s.name.clone()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WorkflowState {
+    Running,
+    Succeeded,
+    Failed,
+}
+
+/// One attempt of one step
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StepRun {
+    pub step: String,
+    pub attempt: u32,
+    pub job_id: String,
+    pub status: JobStatus,
+}
+
+/// Progress of a submitted workflow
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Workflow {
+    pub id: String,
+    pub state: WorkflowState,
+    pub current_step: Option<String>,
+    pub runs: Vec<StepRun>,
+    /// Artifacts by step name, then artifact name
+    pub artifacts: HashMap<String, HashMap<String, String>>,
+}
+
+#[derive(Debug, Clone)]
+struct WorkflowRecord {
+    spec: WorkflowSpec,
+    workflow: Workflow,
+}
+
+/// What to do after a step job finished
+enum Transition {
+    Retry { step: String, attempt: u32, delay: Duration },
+    Start(String),
+    Finish(WorkflowState),
+}
+
+/// Submitted workflows and the workflow of each step job
+#[derive(Debug, Clone, Default)]
+pub struct Workflows {
+    records: Arc<Mutex<HashMap<String, WorkflowRecord>>>,
+    by_job: Arc<Mutex<HashMap<String, String>>>,
+}
+
+impl Workflows {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn workflow_of(&self, job_id: &str) -> Option<String> {
+        self.by_job.lock().ok()?.get(job_id).cloned()
+    }
+}
+
+/// Environment-variable form of an artifact name
+fn artifact_var(step: &str, name: &str) -> String {
+    let raw = format!("TGP_ARTIFACT_{}_{}", step, name);
+    raw.chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_uppercase() } else { '_' })
+        .collect()
+}
+
+/// Job for `attempt` of `step`, with earlier artifacts substituted
+fn step_job(workflow_id: &str, step: &WorkflowStep, attempt: u32, artifacts: &HashMap<String, HashMap<String, String>>) -> JobSpec {
+    let mut job = step.job.clone();
+    job.id = format!("{}-{}-{}", workflow_id, step.name, attempt);
+
+    let substitute = |text: &str| {
+        let mut text = text.to_string();
+        for (from_step, values) in artifacts {
+            for (name, value) in values {
+                text = text.replace(&format!("{{{{steps.{}.{}}}}}", from_step, name), value);
+            }
+        }
+        text
+    };
+    job.command = job.command.iter().map(|arg| substitute(arg)).collect();
+    for value in job.env.values_mut() {
+        *value = substitute(value);
+    }
+    for (from_step, values) in artifacts {
+        for (name, value) in values {
+            job.env.insert(artifact_var(from_step, name), value.clone());
+        }
+    }
+    job.env.insert("TGP_WORKFLOW_ID".to_string(), workflow_id.to_string());
+    job.env.insert("TGP_WORKFLOW_STEP".to_string(), step.name.clone());
+    job
+}
+
+impl EconomicScheduler {
+    /// Validate and start a workflow at its first step
+    pub async fn submit_workflow(&self, spec: WorkflowSpec) -> Result<()> {
+        spec.validate()?;
+        let first = spec.steps[0].name.clone();
+        {
+            let mut records = self.workflows.records.lock()
+                .map_err(|e| anyhow::anyhow!("Lock poisoned: {}", e))?;
+            if records.contains_key(&spec.id) {
+                return Err(SchedulerError::already_exists("Workflow", &spec.id).into());
+            }
+            records.insert(spec.id.clone(), WorkflowRecord {
+                workflow: Workflow {
+                    id: spec.id.clone(),
+                    state: WorkflowState::Running,
+                    current_step: None,
+                    runs: Vec::new(),
+                    artifacts: HashMap::new(),
+                },
+                spec: spec.clone(),
+            });
+        }
+        tracing::info!("Workflow {} submitted with {} steps", spec.id, spec.steps.len());
+        self.start_step(&spec.id, &first, 1).await
+    }
+
+    /// Current progress of a workflow
+    pub fn get_workflow(&self, workflow_id: &str) -> Option<Workflow> {
+        self.workflows.records.lock().ok()?
+            .get(workflow_id)
+            .map(|record| record.workflow.clone())
+    }
+
+    /// Store artifacts reported by a finished job; ignored for jobs outside
+    /// any workflow
+    pub fn record_artifacts(&self, job_id: &str, artifacts: HashMap<String, String>) -> Result<()> {
+        let Some(workflow_id) = self.workflows.workflow_of(job_id) else {
+            return Ok(());
+        };
+        let mut records = self.workflows.records.lock()
+            .map_err(|e| anyhow::anyhow!("Lock poisoned: {}", e))?;
+        let Some(record) = records.get_mut(&workflow_id) else {
+            return Ok(());
+        };
+        let Some(step) = record.workflow.runs.iter().find(|r| r.job_id == job_id).map(|r| r.step.clone()) else {
+            return Ok(());
+        };
+        record.workflow.artifacts.entry(step).or_default().extend(artifacts);
+        Ok(())
+    }
+
+    /// Queue `attempt` of `step_name`
+    async fn start_step(&self, workflow_id: &str, step_name: &str, attempt: u32) -> Result<()> {
+        let job = {
+            let mut records = self.workflows.records.lock()
+                .map_err(|e| anyhow::anyhow!("Lock poisoned: {}", e))?;
+            let record = records.get_mut(workflow_id)
+                .ok_or_else(|| SchedulerError::not_found("Workflow", workflow_id))?;
+            let step = record.spec.step(step_name)
+                .ok_or_else(|| anyhow::anyhow!("Workflow {} has no step {}", workflow_id, step_name))?;
+            let job = step_job(workflow_id, step, attempt, &record.workflow.artifacts);
+
+            record.workflow.current_step = Some(step_name.to_string());
+            record.workflow.runs.push(StepRun {
+                step: step_name.to_string(),
+                attempt,
+                job_id: job.id.clone(),
+                status: JobStatus::Pending,
+            });
+            job
+        };
+        if let Ok(mut by_job) = self.workflows.by_job.lock() {
+            by_job.insert(job.id.clone(), workflow_id.to_string());
+        }
+
+        tracing::info!("Workflow {} starting step {} (attempt {})", workflow_id, step_name, attempt);
+        let job_id = job.id.clone();
+        // A placement failure marks the job Failed, which the engine handles
+        if let Err(e) = self.requeue(job).await {
+            tracing::warn!("Workflow step job {} not placed: {}", job_id, e);
+        }
+        Ok(())
+    }
+
+    /// Record a step job's terminal status and decide what runs next
+    fn finish_run(&self, job_id: &str) -> Result<Option<(String, Transition)>> {
+        let Some(workflow_id) = self.workflows.workflow_of(job_id) else {
+            return Ok(None);
+        };
+        let Some(status) = self.get_job_state(job_id).map(|s| s.status).filter(|s| s.is_terminal()) else {
+            return Ok(None);
+        };
+
+        let mut records = self.workflows.records.lock()
+            .map_err(|e| anyhow::anyhow!("Lock poisoned: {}", e))?;
+        let Some(record) = records.get_mut(&workflow_id) else {
+            return Ok(None);
+        };
+        if record.workflow.state != WorkflowState::Running {
+            return Ok(None);
+        }
+        // Each run is handled once
+        let Some(run) = record.workflow.runs.iter_mut().find(|r| r.job_id == job_id && !r.status.is_terminal()) else {
+            return Ok(None);
+        };
+        run.status = status.clone();
+        let (step_name, attempt) = (run.step.clone(), run.attempt);
+        let Some(step) = record.spec.step(&step_name) else {
+            return Ok(None);
+        };
+
+        let transition = if status == JobStatus::Completed {
+            match record.spec.after_success(&step_name) {
+                Some(next) => Transition::Start(next),
+                None => Transition::Finish(WorkflowState::Succeeded),
+            }
+        } else if attempt < step.retry.max_attempts.max(1) {
+            Transition::Retry {
+                step: step_name,
+                attempt: attempt + 1,
+                delay: Duration::from_secs(step.retry.backoff_secs),
+            }
+        } else {
+            match step.on_failure.as_deref() {
+                Some(END_STEP) | None => Transition::Finish(WorkflowState::Failed),
+                Some(handler) => Transition::Start(handler.to_string()),
+            }
+        };
+
+        if let Transition::Finish(state) = &transition {
+            record.workflow.state = *state;
+            record.workflow.current_step = None;
+            tracing::info!("Workflow {} finished: {:?}", workflow_id, state);
+        }
+        Ok(Some((workflow_id, transition)))
+    }
+
+    /// Advance the workflow owning `job_id` if the job just finished
+    pub async fn advance_workflow(&self, job_id: &str) -> Result<()> {
+        let Some((workflow_id, transition)) = self.finish_run(job_id)? else {
+            return Ok(());
+        };
+        match transition {
+            Transition::Start(step) => self.start_step(&workflow_id, &step, 1).await,
+            Transition::Retry { step, attempt, delay } if delay.is_zero() => {
+                self.start_step(&workflow_id, &step, attempt).await
+            }
+            Transition::Retry { step, attempt, delay } => {
+                tracing::info!("Workflow {} retrying step {} in {:?}", workflow_id, step, delay);
+                let scheduler = self.clone();
+                tokio::spawn(async move {
+                    tokio::time::sleep(delay).await;
+                    if let Err(e) = scheduler.start_step(&workflow_id, &step, attempt).await {
+                        tracing::warn!("Failed to retry workflow {} step {}: {}", workflow_id, step, e);
+                    }
+                });
+                Ok(())
+            }
+            Transition::Finish(_) => Ok(()),
+        }
+    }
+
+    /// Step jobs of running workflows that have not been seen to finish
+    fn open_step_jobs(&self) -> Vec<String> {
+        let Ok(records) = self.workflows.records.lock() else {
+            return Vec::new();
+        };
+        records.values()
+            .filter(|record| record.workflow.state == WorkflowState::Running)
+            .flat_map(|record| record.workflow.runs.iter())
+            .filter(|run| !run.status.is_terminal())
+            .map(|run| run.job_id.clone())
+            .collect()
+    }
+}
+
+/// Advance workflows as their step jobs finish, until the process exits
+pub async fn run_workflow_engine(scheduler: EconomicScheduler) {
+    let mut updates = scheduler.subscribe_job_updates();
+    loop {
+        let job_ids = match updates.recv().await {
+            Ok(job_id) => vec![job_id],
+            // Missed updates: re-check every open step
+            Err(RecvError::Lagged(_)) => scheduler.open_step_jobs(),
+            Err(RecvError::Closed) => return,
+        };
+        for job_id in job_ids {
+            if let Err(e) = scheduler.advance_workflow(&job_id).await {
+                tracing::warn!("Failed to advance workflow for job {}: {}", job_id, e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{NodeInfo, SlaConstraints};
+
+    fn step(name: &str, command: &str) -> WorkflowStep {
+        WorkflowStep {
+            name: name.to_string(),
+            job: JobSpec {
+                command: vec![command.to_string()],
+                sla: SlaConstraints { max_latency_ms: 1_000, ..Default::default() },
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_retry_then_failure_branch_with_artifacts() {
+        let scheduler = EconomicScheduler::new();
+        scheduler.register_node(NodeInfo {
+            id: "node-1".to_string(),
+            available_cpu: 8,
+            available_memory_gb: 32,
+            location: "vps-1".to_string(),
+            cost_per_hour: 0.1,
+            ..Default::default()
+        }).unwrap();
+
+        let mut train = step("train", "train --data={{steps.prepare.path}}");
+        train.retry = RetryPolicy { max_attempts: 2, backoff_secs: 0 };
+        train.on_failure = Some("alert".to_string());
+        train.on_success = Some(END_STEP.to_string());
+        let spec = WorkflowSpec {
+            id: "wf".to_string(),
+            steps: vec![step("prepare", "prepare"), train, step("alert", "alert")],
+        };
+        scheduler.submit_workflow(spec).await.unwrap();
+
+        scheduler.record_artifacts("wf-prepare-1", HashMap::from([("path".to_string(), "/data/v2".to_string())])).unwrap();
+        scheduler.update_job_state("wf-prepare-1".to_string(), JobStatus::Completed, None).unwrap();
+        scheduler.advance_workflow("wf-prepare-1").await.unwrap();
+        assert_eq!(scheduler.get_workflow("wf").unwrap().current_step.as_deref(), Some("train"));
+
+        // First failure is retried, the second takes the failure branch
+        for attempt in 1..=2 {
+            let job_id = format!("wf-train-{}", attempt);
+            scheduler.update_job_state(job_id.clone(), JobStatus::Failed, None).unwrap();
+            scheduler.advance_workflow(&job_id).await.unwrap();
+        }
+        let workflow = scheduler.get_workflow("wf").unwrap();
+        assert_eq!(workflow.current_step.as_deref(), Some("alert"));
+        assert_eq!(workflow.runs.len(), 4);
+
+        scheduler.update_job_state("wf-alert-1".to_string(), JobStatus::Completed, None).unwrap();
+        scheduler.advance_workflow("wf-alert-1").await.unwrap();
+        assert_eq!(scheduler.get_workflow("wf").unwrap().state, WorkflowState::Succeeded);
+    }
+
+    #[test]
+    fn test_step_job_substitutes_artifacts() {
+        let artifacts = HashMap::from([("prepare".to_string(), HashMap::from([("path".to_string(), "/data/v2".to_string())]))]);
+        let job = step_job("wf", &step("train", "train --data={{steps.prepare.path}}"), 1, &artifacts);
+        assert_eq!(job.id, "wf-train-1");
+        assert_eq!(job.command, vec!["train --data=/data/v2".to_string()]);
+        assert_eq!(job.env["TGP_ARTIFACT_PREPARE_PATH"], "/data/v2");
+    }
+}
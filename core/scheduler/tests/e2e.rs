@@ -0,0 +1,86 @@
+//! Submit → dispatch → complete over the real gRPC API, with mock workers
+//! standing in for the worker daemon (see `tgp_scheduler::testkit`)
+
+use std::time::Duration;
+use tgp_scheduler::grpc::proto::{JobStatusRequest, JobSubmitRequest, ResourceRequirements, SlaConstraints};
+use tgp_scheduler::testkit::{JobOutcome, MockWorkerConfig, TestCluster};
+use tgp_scheduler::JobStatus;
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+fn job(id: &str, cpu_cores: u32) -> JobSubmitRequest {
+    JobSubmitRequest {
+        job_id: id.to_string(),
+        resources: Some(ResourceRequirements { cpu_cores, memory_gb: 1, ..Default::default() }),
+        sla: Some(SlaConstraints { max_latency_ms: 10_000, ..Default::default() }),
+        container_image: "alpine:3.19".to_string(),
+        command: vec!["true".to_string()],
+        disable_result_cache: true,
+        ..Default::default()
+    }
+}
+
+#[tokio::test]
+async fn test_submitted_job_runs_to_completion() {
+    let cluster = TestCluster::start().await.unwrap();
+    let worker = cluster.add_worker(MockWorkerConfig::new("node-1")).await.unwrap();
+
+    let job_id = cluster.submit(job("job-1", 2)).await.unwrap();
+    let state = cluster.wait_for(&job_id, JobStatus::Completed, TIMEOUT).await.unwrap();
+
+    assert_eq!(state.assigned_node.as_deref(), Some("node-1"));
+    assert_eq!(worker.finished(), vec![job_id]);
+    assert!(worker.running().is_empty());
+}
+
+#[tokio::test]
+async fn test_status_carries_annotations_and_timeline() {
+    let cluster = TestCluster::start().await.unwrap();
+    let _worker = cluster.add_worker(MockWorkerConfig::new("node-1")).await.unwrap();
+
+    let mut request = job("job-1", 1);
+    request.annotations.insert("git_sha".to_string(), "4f2a9c1".to_string());
+    request.annotations.insert("owner".to_string(), "ml-team@example.com".to_string());
+    let job_id = cluster.submit(request.clone()).await.unwrap();
+    cluster.wait_for(&job_id, JobStatus::Completed, TIMEOUT).await.unwrap();
+
+    let status = cluster.client().await.unwrap()
+        .get_job_status(JobStatusRequest { job_id })
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(status.annotations, request.annotations);
+    assert!(status.submitted_at > 0);
+    assert!(status.submitted_at <= status.scheduled_at);
+    assert!(status.scheduled_at <= status.started_at);
+    assert!(status.started_at <= status.finished_at);
+}
+
+#[tokio::test]
+async fn test_scripted_failures_and_cancellations_reach_the_scheduler() {
+    let cluster = TestCluster::start().await.unwrap();
+    let worker = cluster.add_worker(MockWorkerConfig::new("node-1").executor(|assignment| {
+        match assignment.job_id.as_str() {
+            "job-fail" => JobOutcome::failed(3),
+            _ => JobOutcome::completed().after(Duration::from_secs(60)),
+        }
+    })).await.unwrap();
+
+    let failed = cluster.submit(job("job-fail", 1)).await.unwrap();
+    cluster.wait_for(&failed, JobStatus::Failed, TIMEOUT).await.unwrap();
+    assert_eq!(worker.finished(), vec![failed]);
+
+    let slow = cluster.submit(job("job-slow", 1)).await.unwrap();
+    cluster.wait_for(&slow, JobStatus::Running, TIMEOUT).await.unwrap();
+    cluster.scheduler().cancel_job(&slow).unwrap();
+    cluster.wait_for(&slow, JobStatus::Cancelled, TIMEOUT).await.unwrap();
+
+    // The cancel command reached the worker, which stopped the job
+    for _ in 0..100 {
+        if !worker.stopped().is_empty() {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    assert_eq!(worker.stopped(), vec![(slow, false)]);
+}
@@ -0,0 +1,209 @@
+//! Random submit/report/fail sequences against the scheduler invariants
+//!
+//! Each case runs a queued-mode scheduler over a small cluster whose
+//! workers register and report their free capacity over the gRPC handlers,
+//! as real workers do, and checks `check_invariants` after each operation.
+
+use proptest::prelude::*;
+use std::sync::Arc;
+use std::time::Duration;
+use tgp_scheduler::grpc::proto::scheduler_service_server::SchedulerService;
+use tgp_scheduler::grpc::proto::{RegisterNodeRequest, ResourceReport};
+use tgp_scheduler::queue::{JobQueue, MemoryQueue};
+use tgp_scheduler::{EconomicScheduler, JobSpec, JobStatus, ResourceRequirements, SlaConstraints};
+use tonic::Request;
+
+const NODES: usize = 3;
+const NODE_CPU: u32 = 8;
+const NODE_MEMORY_GB: u32 = 16;
+
+#[derive(Debug, Clone)]
+enum Op {
+    Submit { cpu: u32, memory_gb: u32 },
+    Dispatch,
+    Report,
+    Start(usize),
+    Complete(usize),
+    Fail(usize),
+    Cancel(usize),
+    Interrupt(usize),
+}
+
+fn op() -> impl Strategy<Value = Op> {
+    prop_oneof![
+        3 => (1..=NODE_CPU, 1..=NODE_MEMORY_GB).prop_map(|(cpu, memory_gb)| Op::Submit { cpu, memory_gb }),
+        3 => Just(Op::Dispatch),
+        2 => Just(Op::Report),
+        2 => any::<usize>().prop_map(Op::Start),
+        2 => any::<usize>().prop_map(Op::Complete),
+        1 => any::<usize>().prop_map(Op::Fail),
+        1 => any::<usize>().prop_map(Op::Cancel),
+        1 => (0..NODES).prop_map(Op::Interrupt),
+    ]
+}
+
+fn node_id(i: usize) -> String {
+    format!("node-{}", i)
+}
+
+/// Worker registration as sent once at startup
+fn registration(i: usize) -> RegisterNodeRequest {
+    RegisterNodeRequest {
+        node_id: node_id(i),
+        cpu_cores: NODE_CPU,
+        total_memory_gb: NODE_MEMORY_GB as f64,
+        location: "local".to_string(),
+        cost_per_hour: 0.1 * (i + 1) as f64,
+        ..Default::default()
+    }
+}
+
+fn job_spec(id: String, cpu: u32, memory_gb: u32) -> JobSpec {
+    JobSpec {
+        id,
+        resources: ResourceRequirements { cpu_cores: cpu, memory_gb, ..Default::default() },
+        sla: SlaConstraints { max_latency_ms: 10_000, ..Default::default() },
+        disable_result_cache: true,
+        ..Default::default()
+    }
+}
+
+/// The cluster as the workers see it
+struct Cluster {
+    scheduler: EconomicScheduler,
+    queue: Arc<MemoryQueue>,
+    jobs: Vec<JobSpec>,
+    fencing_tokens: Vec<u64>,
+    lost: Vec<bool>,
+}
+
+impl Cluster {
+    async fn new() -> Self {
+        let queue = Arc::new(MemoryQueue::new());
+        let mut scheduler = EconomicScheduler::new();
+        scheduler.set_deterministic(0);
+        scheduler.attach_queue(queue.clone());
+        let mut fencing_tokens = Vec::new();
+        for i in 0..NODES {
+            let response = SchedulerService::register_node(&scheduler, Request::new(registration(i))).await.unwrap();
+            fencing_tokens.push(response.into_inner().fencing_token);
+        }
+        Self { scheduler, queue, jobs: Vec::new(), fencing_tokens, lost: vec![false; NODES] }
+    }
+
+    /// Every surviving worker reports the memory its running jobs leave
+    /// free; CPU is not measured, so all of it is reported
+    async fn report_resources(&self) {
+        for i in (0..NODES).filter(|i| !self.lost[*i]) {
+            let id = node_id(i);
+            let memory_gb: u32 = self.jobs.iter()
+                .filter(|job| {
+                    self.scheduler.get_job_state(&job.id).is_some_and(|state| {
+                        state.status == JobStatus::Running && state.assigned_node.as_deref() == Some(id.as_str())
+                    })
+                })
+                .map(|job| job.resources.memory_gb)
+                .sum();
+            SchedulerService::report_resources(&self.scheduler, Request::new(ResourceReport {
+                node_id: id,
+                available_cpu: NODE_CPU,
+                available_memory_gb: NODE_MEMORY_GB.saturating_sub(memory_gb) as f64,
+                fencing_token: self.fencing_tokens[i],
+                ..Default::default()
+            })).await.unwrap();
+        }
+    }
+
+    /// The job `i` picks, if any has been submitted
+    fn job(&self, i: usize) -> Option<(String, JobStatus)> {
+        let job = self.jobs.get(i.checked_rem(self.jobs.len())?)?;
+        let state = self.scheduler.get_job_state(&job.id)?;
+        Some((job.id.clone(), state.status))
+    }
+
+    /// What the worker running job `i` reports, if it runs anywhere
+    fn report(&self, i: usize, status: JobStatus) {
+        let Some((job_id, current)) = self.job(i) else {
+            return;
+        };
+        if matches!(current, JobStatus::Scheduled | JobStatus::Running) {
+            self.scheduler.update_job_state(job_id, status, None).unwrap();
+        }
+    }
+
+    async fn apply(&mut self, op: Op) {
+        match op {
+            Op::Submit { cpu, memory_gb } => {
+                let job = job_spec(format!("job-{}", self.jobs.len()), cpu, memory_gb);
+                self.scheduler.enqueue(job.clone()).await.unwrap();
+                self.jobs.push(job);
+            }
+            // One pass of the queue dispatcher
+            Op::Dispatch => {
+                let Some(claimed) = self.queue.claim(Duration::from_secs(60)).await.unwrap() else {
+                    return;
+                };
+                if !self.scheduler.is_cancelled(&claimed.job.id) {
+                    let _ = self.scheduler.place(claimed.job.clone()).await;
+                }
+                self.queue.ack(&claimed).await.unwrap();
+            }
+            Op::Report => self.report_resources().await,
+            Op::Start(i) => self.report(i, JobStatus::Running),
+            Op::Complete(i) => self.report(i, JobStatus::Completed),
+            Op::Fail(i) => self.report(i, JobStatus::Failed),
+            Op::Cancel(i) => {
+                if let Some((job_id, status)) = self.job(i) {
+                    if !status.is_terminal() {
+                        self.scheduler.cancel_job(&job_id).unwrap();
+                    }
+                }
+            }
+            Op::Interrupt(i) => {
+                if !self.lost[i] {
+                    self.lost[i] = true;
+                    let terminate_at = i64::MAX / 2;
+                    self.scheduler.handle_interruption(&node_id(i), Some(terminate_at)).await.unwrap();
+                }
+            }
+        }
+    }
+}
+
+#[tokio::test]
+async fn jobs_beyond_a_node_are_not_all_placed_on_it() {
+    let scheduler = EconomicScheduler::new();
+    SchedulerService::register_node(&scheduler, Request::new(registration(0))).await.unwrap();
+
+    let mut placed = 0;
+    for i in 0..3 {
+        if scheduler.schedule(job_spec(format!("job-{}", i), 6, 4)).await.is_ok() {
+            placed += 1;
+        }
+    }
+    assert_eq!(placed, 1);
+    assert!(scheduler.check_invariants().await.is_empty());
+}
+
+proptest! {
+    #[test]
+    fn invariants_hold_under_random_operations(ops in prop::collection::vec(op(), 1..60)) {
+        let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+        runtime.block_on(async {
+            let mut cluster = Cluster::new().await;
+            for (step, op) in ops.into_iter().enumerate() {
+                let described = format!("{:?}", op);
+                cluster.apply(op).await;
+                let violations = cluster.scheduler.check_invariants().await;
+                prop_assert!(
+                    violations.is_empty(),
+                    "step {} ({}) broke: {}",
+                    step,
+                    described,
+                    violations.iter().map(ToString::to_string).collect::<Vec<_>>().join("; ")
+                );
+            }
+            Ok::<(), TestCaseError>(())
+        })?;
+    }
+}
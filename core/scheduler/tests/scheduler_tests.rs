@@ -1,10 +1,10 @@
 #[cfg(test)]
 mod scheduler_tests {
-    use tgp_scheduler::{EconomicScheduler, JobSpec, JobType, NodeInfo, ResourceRequirements, SlaConstraints};
+    use tgp_scheduler::{EconomicScheduler, JobProgress, JobSpec, JobStatus, JobType, NodeInfo, ResourceRequirements, SlaConstraints};
 
     #[tokio::test]
     async fn test_schedule_selects_cheapest_node() {
-        let mut scheduler = EconomicScheduler::new();
+        let scheduler = EconomicScheduler::new();
 
         // Register two nodes with different costs
         scheduler.register_node(NodeInfo {
@@ -14,7 +14,8 @@ mod scheduler_tests {
             available_gpu: 0,
             location: "vps-1".to_string(),
             cost_per_hour: 0.25, // Cheaper
-        });
+            ..Default::default()
+        }).unwrap();
 
         scheduler.register_node(NodeInfo {
             id: "expensive-node".to_string(),
@@ -23,7 +24,8 @@ mod scheduler_tests {
             available_gpu: 1,
             location: "vps-2".to_string(),
             cost_per_hour: 1.0, // More expensive
-        });
+            ..Default::default()
+        }).unwrap();
 
         let job = JobSpec {
             id: "test-job-1".to_string(),
@@ -31,14 +33,14 @@ mod scheduler_tests {
             resources: ResourceRequirements {
                 cpu_cores: 2,
                 memory_gb: 4,
-                gpu_count: 0,
                 disk_gb: 10,
+                ..Default::default()
             },
             sla: SlaConstraints {
                 max_latency_ms: 1000,
-                max_budget_usd: None,
-                deadline: None,
+                ..Default::default()
             },
+            ..Default::default()
         };
 
         let placement = scheduler.schedule(job).await.unwrap();
@@ -50,7 +52,7 @@ mod scheduler_tests {
 
     #[tokio::test]
     async fn test_schedule_respects_sla_budget() {
-        let mut scheduler = EconomicScheduler::new();
+        let scheduler = EconomicScheduler::new();
 
         scheduler.register_node(NodeInfo {
             id: "cheap-node".to_string(),
@@ -59,7 +61,8 @@ mod scheduler_tests {
             available_gpu: 0,
             location: "vps-1".to_string(),
             cost_per_hour: 0.1,
-        });
+            ..Default::default()
+        }).unwrap();
 
         scheduler.register_node(NodeInfo {
             id: "expensive-node".to_string(),
@@ -68,7 +71,8 @@ mod scheduler_tests {
             available_gpu: 1,
             location: "vps-2".to_string(),
             cost_per_hour: 10.0, // Very expensive
-        });
+            ..Default::default()
+        }).unwrap();
 
         let job = JobSpec {
             id: "budget-constrained-job".to_string(),
@@ -76,14 +80,15 @@ mod scheduler_tests {
             resources: ResourceRequirements {
                 cpu_cores: 2,
                 memory_gb: 4,
-                gpu_count: 0,
                 disk_gb: 10,
+                ..Default::default()
             },
             sla: SlaConstraints {
                 max_latency_ms: 5000,
                 max_budget_usd: Some(0.5), // Budget constraint
-                deadline: None,
+                ..Default::default()
             },
+            ..Default::default()
         };
 
         let placement = scheduler.schedule(job).await.unwrap();
@@ -95,7 +100,7 @@ mod scheduler_tests {
 
     #[tokio::test]
     async fn test_schedule_fails_insufficient_resources() {
-        let mut scheduler = EconomicScheduler::new();
+        let scheduler = EconomicScheduler::new();
 
         scheduler.register_node(NodeInfo {
             id: "small-node".to_string(),
@@ -104,7 +109,8 @@ mod scheduler_tests {
             available_gpu: 0,
             location: "vps-1".to_string(),
             cost_per_hour: 0.1,
-        });
+            ..Default::default()
+        }).unwrap();
 
         let job = JobSpec {
             id: "large-job".to_string(),
@@ -112,14 +118,14 @@ mod scheduler_tests {
             resources: ResourceRequirements {
                 cpu_cores: 8, // Too many cores
                 memory_gb: 32, // Too much memory
-                gpu_count: 0,
                 disk_gb: 100,
+                ..Default::default()
             },
             sla: SlaConstraints {
                 max_latency_ms: 5000,
-                max_budget_usd: None,
-                deadline: None,
+                ..Default::default()
             },
+            ..Default::default()
         };
 
         let result = scheduler.schedule(job).await;
@@ -139,14 +145,14 @@ mod scheduler_tests {
             resources: ResourceRequirements {
                 cpu_cores: 1,
                 memory_gb: 1,
-                gpu_count: 0,
                 disk_gb: 10,
+                ..Default::default()
             },
             sla: SlaConstraints {
                 max_latency_ms: 1000,
-                max_budget_usd: None,
-                deadline: None,
+                ..Default::default()
             },
+            ..Default::default()
         };
 
         let result = scheduler.schedule(job).await;
@@ -155,4 +161,201 @@ mod scheduler_tests {
         assert!(result.is_err());
         assert!(result.unwrap_err().to_string().contains("No nodes available"));
     }
+
+    #[tokio::test]
+    async fn test_job_progress_is_visible_in_state() {
+        let scheduler = EconomicScheduler::new();
+
+        scheduler.register_node(NodeInfo {
+            id: "node-1".to_string(),
+            available_cpu: 4,
+            available_memory_gb: 8,
+            available_gpu: 0,
+            location: "vps-1".to_string(),
+            cost_per_hour: 0.2,
+            ..Default::default()
+        }).unwrap();
+
+        let job = JobSpec {
+            id: "progress-job".to_string(),
+            job_type: JobType::Training,
+            resources: ResourceRequirements {
+                cpu_cores: 1,
+                memory_gb: 1,
+                disk_gb: 10,
+                ..Default::default()
+            },
+            sla: SlaConstraints {
+                max_latency_ms: 1000,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        scheduler.schedule(job).await.unwrap();
+
+        let mut updates = scheduler.subscribe_job_updates();
+        scheduler.report_job_progress("progress-job", JobProgress {
+            percent: 50.0,
+            step: "epoch 5/10".to_string(),
+            ..Default::default()
+        }).unwrap();
+
+        assert_eq!(updates.recv().await.unwrap(), "progress-job");
+        let progress = scheduler.get_job_state("progress-job").unwrap().progress.unwrap();
+        assert_eq!(progress.percent, 50.0);
+        assert_eq!(progress.step, "epoch 5/10");
+
+        // Unknown jobs are rejected
+        assert!(scheduler.report_job_progress("missing", JobProgress::default()).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_identical_job_served_from_result_cache() {
+        let scheduler = EconomicScheduler::new();
+
+        scheduler.register_node(NodeInfo {
+            id: "node-1".to_string(),
+            available_cpu: 4,
+            available_memory_gb: 8,
+            available_gpu: 0,
+            location: "vps-1".to_string(),
+            cost_per_hour: 0.2,
+            ..Default::default()
+        }).unwrap();
+
+        let job = |id: &str, disable_result_cache: bool| JobSpec {
+            id: id.to_string(),
+            job_type: JobType::DataProcessing,
+            resources: ResourceRequirements {
+                cpu_cores: 1,
+                memory_gb: 1,
+                disk_gb: 10,
+                ..Default::default()
+            },
+            sla: SlaConstraints {
+                max_latency_ms: 1000,
+                ..Default::default()
+            },
+            container_image: "alpine:latest".to_string(),
+            command: vec!["wc".to_string(), "-l".to_string()],
+            job_data: b"input".to_vec(),
+            disable_result_cache,
+            ..Default::default()
+        };
+
+        let first = scheduler.schedule(job("etl-1", false)).await.unwrap();
+        assert!(first.cached_from.is_none());
+        scheduler.update_job_state("etl-1".to_string(), JobStatus::Completed, None).unwrap();
+
+        let second = scheduler.schedule(job("etl-2", false)).await.unwrap();
+        assert_eq!(second.cached_from.as_deref(), Some("etl-1"));
+        assert_eq!(second.estimated_cost.total_usd, 0.0);
+        let state = scheduler.get_job_state("etl-2").unwrap();
+        assert_eq!(state.status, JobStatus::Completed);
+
+        // Opting out always runs the job
+        let third = scheduler.schedule(job("etl-3", true)).await.unwrap();
+        assert!(third.cached_from.is_none());
+        assert_eq!(scheduler.get_job_state("etl-3").unwrap().status, JobStatus::Scheduled);
+    }
+
+    #[tokio::test]
+    async fn test_schedule_prefers_node_holding_dataset() {
+        let scheduler = EconomicScheduler::new();
+
+        for (id, cost_per_hour) in [("cheap-remote", 0.2), ("local-data", 0.5)] {
+            scheduler.register_node(NodeInfo {
+                id: id.to_string(),
+                available_cpu: 4,
+                available_memory_gb: 8,
+                available_gpu: 0,
+                location: "vps-1".to_string(),
+                cost_per_hour,
+                ..Default::default()
+            }).unwrap();
+        }
+        scheduler.sync_node_datasets("local-data", &[("imagenet".to_string(), 100.0)]);
+
+        let job = JobSpec {
+            id: "train-on-imagenet".to_string(),
+            job_type: JobType::Training,
+            resources: ResourceRequirements {
+                cpu_cores: 2,
+                memory_gb: 4,
+                disk_gb: 10,
+                ..Default::default()
+            },
+            sla: SlaConstraints {
+                max_latency_ms: 1000,
+                ..Default::default()
+            },
+            datasets: vec!["imagenet".to_string()],
+            ..Default::default()
+        };
+
+        let placement = scheduler.schedule(job).await.unwrap();
+
+        // Pulling 100GB onto the cheap node costs more than the price difference
+        assert_eq!(placement.node_id, "local-data");
+        assert_eq!(placement.estimated_cost.data_transfer_usd, 0.0);
+    }
+
+    #[tokio::test]
+    async fn test_staging_time_counts_against_deadline() {
+        let scheduler = EconomicScheduler::new();
+
+        scheduler.register_node(NodeInfo {
+            id: "remote".to_string(),
+            available_cpu: 4,
+            available_memory_gb: 8,
+            location: "us-1".to_string(),
+            cost_per_hour: 0.1,
+            ..Default::default()
+        }).unwrap();
+        // "holder" is too small for the job, so the data must move
+        scheduler.register_node(NodeInfo {
+            id: "holder".to_string(),
+            available_cpu: 1,
+            available_memory_gb: 1,
+            location: "eu-1".to_string(),
+            cost_per_hour: 0.1,
+            ..Default::default()
+        }).unwrap();
+        scheduler.sync_node_datasets("holder", &[("logs".to_string(), 10.0)]);
+        scheduler.set_link_bandwidth("eu-1", "us-1", 80.0);
+
+        let job = |id: &str, max_latency_ms: u64, deadline: Option<i64>| JobSpec {
+            id: id.to_string(),
+            job_type: JobType::DataProcessing,
+            resources: ResourceRequirements {
+                cpu_cores: 2,
+                memory_gb: 4,
+                disk_gb: 20,
+                ..Default::default()
+            },
+            sla: SlaConstraints {
+                max_latency_ms,
+                deadline,
+                ..Default::default()
+            },
+            datasets: vec!["logs".to_string()],
+            estimated_duration_hours: Some(1.0),
+            ..Default::default()
+        };
+
+        // 10GB over 80 Mbit/s = 1000s of staging
+        let placement = scheduler.schedule(job("relaxed", 2_000_000, None)).await.unwrap();
+        assert_eq!(placement.node_id, "remote");
+        assert_eq!(placement.estimated_staging_ms, 1_000_000);
+        assert!(placement.estimated_latency_ms > placement.estimated_staging_ms);
+
+        assert!(scheduler.schedule(job("tight", 60_000, None)).await.is_err());
+
+        // An hour of work plus 1000s of staging: a deadline 1500s past the
+        // hour is met, one 500s past it is not
+        let now = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_secs() as i64;
+        let met = scheduler.schedule(job("in-time", 2_000_000, Some(now + 3_600 + 1_500))).await.unwrap();
+        assert_eq!(met.node_id, "remote");
+        assert!(scheduler.schedule(job("late", 2_000_000, Some(now + 3_600 + 500))).await.is_err());
+    }
 }
@@ -0,0 +1,11 @@
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // Client only: live trace replay talks to a staging scheduler
+    tonic_build::configure()
+        .build_client(true)
+        .build_server(false)
+        .compile(
+            &["../../proto/scheduler.proto"],
+            &["../../proto"],
+        )?;
+    Ok(())
+}
@@ -0,0 +1,13 @@
+//! TGP Scheduler Simulator
+//!
+//! Replays synthetic or recorded workloads against the real EconomicScheduler
+//! in virtual time, so placement policies can be evaluated offline for cost,
+//! utilization and SLA adherence.
+
+pub mod sim;
+pub mod trace;
+pub mod workload;
+
+pub use sim::{NodeReport, SimReport, Simulator};
+pub use trace::{replay_live, workload_from_trace};
+pub use workload::{synthetic, SyntheticConfig, Workload, WorkloadJob};
@@ -0,0 +1,159 @@
+//! TGP Simulator CLI
+//!
+//! Evaluate the scheduler offline against synthetic, saved or recorded workloads
+
+use anyhow::Result;
+use clap::{Parser, Subcommand};
+use std::path::PathBuf;
+use tgp_scheduler::trace::read_trace;
+use tgp_simulator::{replay_live, synthetic, workload_from_trace, SimReport, Simulator, SyntheticConfig, Workload};
+
+#[derive(Parser)]
+#[command(name = "tgp-simulator")]
+#[command(about = "TGP Simulator - Replay workloads against the scheduler in virtual time", long_about = None)]
+struct Cli {
+    /// Print the report as JSON
+    #[arg(long)]
+    json: bool,
+
+    /// Seed for the scheduler's tie-breaking (same seed, same report)
+    #[arg(long, default_value = "0")]
+    scheduler_seed: u64,
+
+    #[command(subcommand)]
+    command: Commands,
+}
+
+#[derive(Subcommand)]
+enum Commands {
+    /// Generate and run a synthetic workload
+    Synthetic {
+        /// Number of virtual nodes
+        #[arg(long, default_value = "20")]
+        nodes: usize,
+
+        /// Number of jobs
+        #[arg(long, default_value = "500")]
+        jobs: usize,
+
+        /// Mean time between arrivals in ms
+        #[arg(long, default_value = "2000")]
+        interarrival_ms: u64,
+
+        /// Mean job duration in ms
+        #[arg(long, default_value = "60000")]
+        duration_ms: u64,
+
+        /// Random seed
+        #[arg(long, default_value = "42")]
+        seed: u64,
+
+        /// Save the generated workload for later replay
+        #[arg(long)]
+        save: Option<PathBuf>,
+    },
+
+    /// Run a workload file
+    Replay {
+        /// Workload JSON file
+        workload: PathBuf,
+    },
+
+    /// Replay a recorded scheduler trace (TGP_TRACE_FILE)
+    Trace {
+        /// Trace file (JSON lines)
+        trace: PathBuf,
+
+        /// Duration for jobs whose run time is not in the trace, in ms
+        #[arg(long, default_value = "60000")]
+        default_duration_ms: u64,
+
+        /// Send the trace to a live staging scheduler instead of simulating
+        #[arg(long)]
+        target: Option<String>,
+
+        /// Live replay speed factor (2.0 = twice as fast as recorded)
+        #[arg(long, default_value = "1.0")]
+        speed: f64,
+
+        /// Save the derived workload for later replay
+        #[arg(long)]
+        save: Option<PathBuf>,
+    },
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    // Scheduler logs every placement at INFO; keep the simulator quiet by default
+    tracing_subscriber::fmt()
+        .with_env_filter(
+            tracing_subscriber::EnvFilter::try_from_default_env()
+                .unwrap_or_else(|_| tracing_subscriber::EnvFilter::new("warn")),
+        )
+        .init();
+
+    let cli = Cli::parse();
+
+    let workload = match cli.command {
+        Commands::Synthetic { nodes, jobs, interarrival_ms, duration_ms, seed, save } => {
+            let workload = synthetic(&SyntheticConfig {
+                nodes,
+                jobs,
+                mean_interarrival_ms: interarrival_ms,
+                mean_duration_ms: duration_ms,
+                seed,
+            });
+            if let Some(path) = save {
+                workload.save(&path)?;
+                println!("Workload saved to {}", path.display());
+            }
+            workload
+        }
+        Commands::Replay { workload } => Workload::load(&workload)?,
+        Commands::Trace { trace, default_duration_ms, target, speed, save } => {
+            let records = read_trace(&trace)?;
+
+            if let Some(target) = target {
+                println!("Replaying {} records to {} at {}x", records.len(), target, speed);
+                let sent = replay_live(&records, &target, speed).await?;
+                println!("Replayed {} requests", sent);
+                return Ok(());
+            }
+
+            let workload = workload_from_trace(&records, default_duration_ms);
+            if let Some(path) = save {
+                workload.save(&path)?;
+                println!("Workload saved to {}", path.display());
+            }
+            workload
+        }
+    };
+
+    let report = Simulator::seeded(workload, cli.scheduler_seed).run().await?;
+
+    if cli.json {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else {
+        print_report(&report);
+    }
+
+    Ok(())
+}
+
+fn print_report(report: &SimReport) {
+    println!("Simulation Report");
+    println!("  Jobs:           {} total, {} completed ({} from cache), {} unscheduled",
+        report.jobs_total, report.jobs_completed, report.jobs_from_cache, report.jobs_unscheduled);
+    println!("  SLA misses:     {}", report.sla_misses);
+    println!("  Estimated cost: ${:.4} (Formula 4.1)", report.estimated_cost_usd);
+    println!("  Actual cost:    ${:.4}", report.actual_cost_usd);
+    println!("  Wait:           mean {:.0}ms, max {}ms", report.mean_wait_ms, report.max_wait_ms);
+    println!("  Makespan:       {:.1}s", report.makespan_ms as f64 / 1000.0);
+    println!("  Utilization:    {:.1}% CPU", report.cluster_cpu_utilization * 100.0);
+    println!();
+    println!("  {:<16} {:>6} {:>8} {:>10}", "Node", "Jobs", "CPU %", "Cost $");
+    for node in &report.nodes {
+        println!("  {:<16} {:>6} {:>8.1} {:>10.4}",
+            node.node_id, node.jobs_run, node.cpu_utilization * 100.0, node.cost_usd);
+    }
+}
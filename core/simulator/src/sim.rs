@@ -0,0 +1,300 @@
+//! Discrete-event simulation against the real EconomicScheduler
+//!
+//! Time is virtual: arrivals and completions are processed in timestamp
+//! order without sleeping. Nodes are registered once with their full
+//! capacity; the scheduler counts the jobs placed on them itself, and a
+//! completion frees their share as it would on a real worker.
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, VecDeque};
+use tgp_scheduler::{EconomicScheduler, JobStatus, NodeInfo};
+
+use crate::workload::Workload;
+
+/// Virtual node with its full capacity
+#[derive(Debug, Clone)]
+struct VirtualNode {
+    capacity: NodeInfo,
+    /// CPU-milliseconds spent running jobs
+    busy_cpu_ms: u64,
+}
+
+impl VirtualNode {
+    fn new(capacity: NodeInfo) -> Self {
+        Self { capacity, busy_cpu_ms: 0 }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum EventKind {
+    /// Completions first so freed capacity is visible to same-time arrivals
+    Finish(usize),
+    Arrival(usize),
+}
+
+/// Per-node results
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NodeReport {
+    pub node_id: String,
+    pub jobs_run: usize,
+    pub cpu_utilization: f64,
+    pub cost_usd: f64,
+}
+
+/// Aggregate simulation results
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SimReport {
+    pub jobs_total: usize,
+    pub jobs_completed: usize,
+    pub jobs_from_cache: usize,
+    pub jobs_unscheduled: usize,
+    pub sla_misses: usize,
+    /// Sum of the scheduler's Formula 4.1 estimates
+    pub estimated_cost_usd: f64,
+    /// Cost of the time jobs actually ran (price × real duration)
+    pub actual_cost_usd: f64,
+    pub mean_wait_ms: f64,
+    pub max_wait_ms: u64,
+    /// Virtual time of the last completion
+    pub makespan_ms: u64,
+    pub cluster_cpu_utilization: f64,
+    pub nodes: Vec<NodeReport>,
+}
+
+/// Per-job bookkeeping during a run
+#[derive(Debug, Clone, Default)]
+struct JobRun {
+    /// Id the job was placed under; each retry is a new submission
+    job_id: Option<String>,
+    attempts: u32,
+    node_id: Option<String>,
+    started_ms: Option<u64>,
+    sla_missed: bool,
+}
+
+/// Replays a workload against a fresh EconomicScheduler
+pub struct Simulator {
+    scheduler: EconomicScheduler,
+    workload: Workload,
+}
+
+impl Simulator {
+    /// Runs are reproducible: the scheduler is in deterministic mode
+    pub fn new(workload: Workload) -> Self {
+        Self::seeded(workload, 0)
+    }
+
+    /// Deterministic run whose optimizer ties are broken by `seed`
+    pub fn seeded(workload: Workload, seed: u64) -> Self {
+        let mut scheduler = EconomicScheduler::new();
+        scheduler.set_deterministic(seed);
+        Self::with_scheduler(scheduler, workload)
+    }
+
+    /// Use a pre-configured scheduler (e.g. custom bandwidth model or policy)
+    pub fn with_scheduler(scheduler: EconomicScheduler, workload: Workload) -> Self {
+        Self { scheduler, workload }
+    }
+
+    /// Run the workload to completion in virtual time
+    pub async fn run(self) -> Result<SimReport> {
+        let Simulator { scheduler, workload } = self;
+
+        let mut nodes: HashMap<String, VirtualNode> = HashMap::new();
+        for node in &workload.nodes {
+            scheduler.register_node(node.clone())?;
+            nodes.insert(node.id.clone(), VirtualNode::new(node.clone()));
+        }
+
+        let mut events: BinaryHeap<Reverse<(u64, EventKind)>> = workload.jobs.iter()
+            .enumerate()
+            .map(|(i, job)| Reverse((job.arrival_ms, EventKind::Arrival(i))))
+            .collect();
+
+        let mut runs = vec![JobRun::default(); workload.jobs.len()];
+        let mut waiting: VecDeque<usize> = VecDeque::new();
+        let mut report = SimReport {
+            jobs_total: workload.jobs.len(),
+            ..Default::default()
+        };
+        let mut total_wait_ms = 0u64;
+        let mut running = 0usize;
+
+        while let Some(Reverse((now, kind))) = events.pop() {
+            match kind {
+                EventKind::Arrival(i) => waiting.push_back(i),
+                EventKind::Finish(i) => {
+                    let job = &workload.jobs[i];
+                    let job_id = runs[i].job_id.clone().unwrap_or_else(|| job.spec.id.clone());
+                    scheduler.update_job_state(job_id, JobStatus::Completed, None)?;
+
+                    if let Some(deadline) = job.deadline_after_ms {
+                        if now > job.arrival_ms + deadline {
+                            runs[i].sla_missed = true;
+                        }
+                    }
+                    running -= 1;
+                    report.jobs_completed += 1;
+                    report.makespan_ms = report.makespan_ms.max(now);
+                }
+            }
+
+            // Try every waiting job in arrival order; unplaceable ones keep waiting
+            let mut still_waiting = VecDeque::new();
+            while let Some(i) = waiting.pop_front() {
+                let job = &workload.jobs[i];
+                let mut spec = job.spec.clone();
+                // A refused job id cannot be submitted again
+                if runs[i].attempts > 0 {
+                    spec.id = format!("{}#{}", spec.id, runs[i].attempts);
+                }
+                runs[i].attempts += 1;
+                let job_id = spec.id.clone();
+                if let Some(after) = job.deadline_after_ms {
+                    // Translate the virtual deadline into the scheduler's wall clock
+                    let remaining_ms = (job.arrival_ms + after).saturating_sub(now);
+                    spec.sla.deadline = Some(unix_now() + (remaining_ms / 1000) as i64);
+                }
+
+                let placement = match scheduler.schedule(spec).await {
+                    Ok(placement) => placement,
+                    Err(_) => {
+                        still_waiting.push_back(i);
+                        continue;
+                    }
+                };
+
+                let wait_ms = now - job.arrival_ms;
+                total_wait_ms += wait_ms;
+                report.max_wait_ms = report.max_wait_ms.max(wait_ms);
+                report.estimated_cost_usd += placement.estimated_cost.total_usd;
+
+                let run = &mut runs[i];
+                run.job_id = Some(job_id);
+                run.node_id = Some(placement.node_id.clone());
+                run.started_ms = Some(now);
+                if wait_ms + placement.estimated_latency_ms > job.spec.sla.max_latency_ms {
+                    run.sla_missed = true;
+                }
+
+                if placement.cached_from.is_some() {
+                    report.jobs_from_cache += 1;
+                    report.jobs_completed += 1;
+                    continue;
+                }
+
+                let Some(node) = nodes.get_mut(&placement.node_id) else {
+                    continue;
+                };
+                node.busy_cpu_ms += job.duration_ms * job.spec.resources.cpu_cores as u64;
+
+                report.actual_cost_usd += node.capacity.cost_per_hour * job.duration_ms as f64 / 3_600_000.0;
+                running += 1;
+                events.push(Reverse((now + job.duration_ms, EventKind::Finish(i))));
+            }
+            waiting = still_waiting;
+
+            // Nothing running and nothing arriving: the rest can never be placed
+            if running == 0 && events.is_empty() {
+                break;
+            }
+        }
+
+        report.jobs_unscheduled = waiting.len();
+        report.sla_misses = runs.iter().filter(|r| r.sla_missed).count() + waiting.len();
+
+        let started = runs.iter().filter(|r| r.started_ms.is_some()).count();
+        if started > 0 {
+            report.mean_wait_ms = total_wait_ms as f64 / started as f64;
+        }
+
+        let makespan = report.makespan_ms.max(1) as f64;
+        let mut capacity_cpu_ms = 0.0;
+        let mut busy_cpu_ms = 0.0;
+        let mut node_reports: Vec<NodeReport> = nodes.values()
+            .map(|node| {
+                let capacity = node.capacity.available_cpu as f64 * makespan;
+                capacity_cpu_ms += capacity;
+                busy_cpu_ms += node.busy_cpu_ms as f64;
+                NodeReport {
+                    node_id: node.capacity.id.clone(),
+                    jobs_run: runs.iter()
+                        .filter(|r| r.node_id.as_deref() == Some(node.capacity.id.as_str()))
+                        .count(),
+                    cpu_utilization: if capacity > 0.0 { node.busy_cpu_ms as f64 / capacity } else { 0.0 },
+                    cost_usd: node.capacity.cost_per_hour * makespan / 3_600_000.0,
+                }
+            })
+            .collect();
+        node_reports.sort_by(|a, b| a.node_id.cmp(&b.node_id));
+
+        report.cluster_cpu_utilization = if capacity_cpu_ms > 0.0 { busy_cpu_ms / capacity_cpu_ms } else { 0.0 };
+        report.nodes = node_reports;
+        Ok(report)
+    }
+}
+
+fn unix_now() -> i64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::workload::WorkloadJob;
+    use tgp_scheduler::{JobSpec, ResourceRequirements, SlaConstraints};
+
+    fn job(id: &str, arrival_ms: u64, cpu_cores: u32) -> WorkloadJob {
+        WorkloadJob {
+            arrival_ms,
+            duration_ms: 10_000,
+            deadline_after_ms: None,
+            spec: JobSpec {
+                id: id.to_string(),
+                resources: ResourceRequirements {
+                    cpu_cores,
+                    memory_gb: 1,
+                    disk_gb: 1,
+                    ..Default::default()
+                },
+                sla: SlaConstraints {
+                    max_latency_ms: 1_000,
+                    ..Default::default()
+                },
+                job_data: id.as_bytes().to_vec(),
+                ..Default::default()
+            },
+        }
+    }
+
+    #[tokio::test]
+    async fn test_jobs_queue_for_capacity_in_virtual_time() {
+        let workload = Workload {
+            nodes: vec![NodeInfo {
+                id: "n1".to_string(),
+                available_cpu: 4,
+                available_memory_gb: 8,
+                location: "vps-1".to_string(),
+                cost_per_hour: 0.36,
+                ..Default::default()
+            }],
+            // Second job must wait for the first to finish
+            jobs: vec![job("a", 0, 4), job("b", 1_000, 4)],
+        };
+
+        let report = Simulator::new(workload).run().await.unwrap();
+
+        assert_eq!(report.jobs_completed, 2);
+        assert_eq!(report.jobs_unscheduled, 0);
+        assert_eq!(report.max_wait_ms, 9_000);
+        assert_eq!(report.sla_misses, 1);
+        assert_eq!(report.makespan_ms, 20_000);
+        assert!((report.actual_cost_usd - 0.002).abs() < 1e-9);
+        assert!((report.cluster_cpu_utilization - 1.0).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,285 @@
+//! Replay of recorded scheduler traces
+//!
+//! A trace (see `tgp_scheduler::trace`) can either be converted into a
+//! workload for the virtual-time simulator, or be re-sent to a live staging
+//! scheduler over gRPC with the original spacing scaled by a speed factor.
+
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::time::Duration;
+use tgp_scheduler::topology::GpuLink;
+use tgp_scheduler::trace::TraceRecord;
+use tgp_scheduler::{JobStatus, NodeInfo};
+use tonic::Request;
+
+use crate::workload::{Workload, WorkloadJob};
+
+pub mod proto {
+    // Command messages carry a whole job assignment
+    #![allow(clippy::large_enum_variant)]
+    tonic::include_proto!("tgp.scheduler.v1");
+}
+
+use proto::scheduler_service_client::SchedulerServiceClient;
+
+/// Convert a trace into a simulator workload
+///
+/// Arrivals are relative to the first record. A job's duration is the time
+/// between its Running and terminal status reports when both were traced,
+/// otherwise `default_duration_ms`. Nodes use their latest registration.
+pub fn workload_from_trace(records: &[TraceRecord], default_duration_ms: u64) -> Workload {
+    let start_ms = records.first().map(|r| r.at_ms()).unwrap_or(0);
+
+    let mut nodes: Vec<NodeInfo> = Vec::new();
+    let mut started: HashMap<&str, u64> = HashMap::new();
+    let mut durations: HashMap<&str, u64> = HashMap::new();
+
+    for record in records {
+        match record {
+            TraceRecord::RegisterNode { node, .. } => {
+                match nodes.iter_mut().find(|n| n.id == node.id) {
+                    Some(existing) => *existing = node.as_ref().clone(),
+                    None => nodes.push(node.as_ref().clone()),
+                }
+            }
+            TraceRecord::JobStatusUpdate { at_ms, job_id, status } => match status {
+                JobStatus::Running => {
+                    started.entry(job_id.as_str()).or_insert(*at_ms);
+                }
+                JobStatus::Completed | JobStatus::Failed => {
+                    if let Some(begin) = started.get(job_id.as_str()) {
+                        durations.insert(job_id.as_str(), at_ms.saturating_sub(*begin));
+                    }
+                }
+                _ => {}
+            },
+            _ => {}
+        }
+    }
+
+    let jobs = records.iter()
+        .filter_map(|record| match record {
+            TraceRecord::SubmitJob { at_ms, job } => Some((*at_ms, job.as_ref())),
+            _ => None,
+        })
+        .map(|(at_ms, job)| {
+            let mut spec = job.clone();
+            // The simulator re-derives absolute deadlines from its own clock
+            let deadline_after_ms = spec.sla.deadline.take()
+                .map(|deadline| (deadline.max(0) as u64 * 1000).saturating_sub(at_ms));
+
+            WorkloadJob {
+                arrival_ms: at_ms - start_ms,
+                duration_ms: durations.get(job.id.as_str()).copied().unwrap_or(default_duration_ms),
+                deadline_after_ms,
+                spec,
+            }
+        })
+        .collect();
+
+    Workload { nodes, jobs }
+}
+
+/// Re-send a trace to a live scheduler
+///
+/// Gaps between records are divided by `speed` (2.0 replays twice as fast).
+/// Job status reports are skipped: the target's own workers report those.
+pub async fn replay_live(records: &[TraceRecord], target: &str, speed: f64) -> Result<usize> {
+    anyhow::ensure!(speed > 0.0, "Replay speed must be positive");
+
+    let mut client = SchedulerServiceClient::connect(target.to_string())
+        .await
+        .with_context(|| format!("Failed to connect to {}", target))?;
+
+    let start_ms = records.first().map(|r| r.at_ms()).unwrap_or(0);
+    let started = tokio::time::Instant::now();
+    let mut sent = 0;
+
+    for record in records {
+        let offset = Duration::from_secs_f64((record.at_ms() - start_ms) as f64 / 1000.0 / speed);
+        tokio::time::sleep_until(started + offset).await;
+
+        let result = match record {
+            TraceRecord::RegisterNode { node, .. } => client
+                .register_node(Request::new(proto::RegisterNodeRequest {
+                    node_id: node.id.clone(),
+                    hostname: node.id.clone(),
+                    cpu_cores: node.available_cpu,
+                    total_memory_gb: node.available_memory_gb as f64,
+                    gpu_count: node.available_gpu,
+                    location: node.location.clone(),
+                    cost_per_hour: node.cost_per_hour,
+                    data_service_addr: node.data_service_addr.clone(),
+                    preemptible: node.preemptible,
+                    performance_score: node.performance_score,
+                    provider: node.provider.clone(),
+                    control_addr: node.control_addr.clone(),
+                    instance_key: String::new(),
+                    allocatable_cpu: node.available_cpu,
+                    allocatable_memory_gb: node.available_memory_gb as f64,
+                    available_disk_gb: node.available_disk_gb.map(f64::from),
+                    network_mbps: node.network_mbps.unwrap_or(0),
+                    gpu_topology: node.gpu_topology.as_ref().map(|topology| proto::GpuTopology {
+                        numa_nodes: topology.numa_nodes.clone(),
+                        links: topology.links.iter().flatten().map(|&link| gpu_link_to_proto(link) as i32).collect(),
+                    }),
+                    numa_nodes: node.numa_cpus.iter()
+                        .map(|cpus| proto::NumaNode { cpus: cpus.clone() })
+                        .collect(),
+                    labels: node.labels.clone(),
+                    arch: node.arch.clone(),
+                }))
+                .await
+                .map(|_| ()),
+            TraceRecord::ResourceReport {
+                node_id, available_cpu, available_memory_gb, available_gpu, available_disk_gb, datasets, ..
+            } => client
+                .report_resources(Request::new(proto::ResourceReport {
+                    node_id: node_id.clone(),
+                    available_cpu: *available_cpu,
+                    available_memory_gb: *available_memory_gb,
+                    available_gpu: *available_gpu,
+                    available_disk_gb: *available_disk_gb,
+                    timestamp: (record.at_ms() / 1000) as i64,
+                    datasets: datasets.iter()
+                        .map(|(name, size_gb)| proto::LocalDataset { name: name.clone(), size_gb: *size_gb, ..Default::default() })
+                        .collect(),
+                    fencing_token: 0,
+                }))
+                .await
+                .map(|_| ()),
+            TraceRecord::SubmitJob { job, .. } => client
+                .submit_job(Request::new(submit_request(job)))
+                .await
+                .map(|_| ()),
+            TraceRecord::JobStatusUpdate { .. } => continue,
+        };
+
+        // Rejections are part of the replayed behaviour, not a replay failure
+        if let Err(status) = result {
+            tracing::warn!("Replayed request rejected: {}", status.message());
+        }
+        sent += 1;
+    }
+
+    Ok(sent)
+}
+
+fn gpu_link_to_proto(link: GpuLink) -> proto::GpuLinkType {
+    match link {
+        GpuLink::Unknown => proto::GpuLinkType::GpuLinkUnknown,
+        GpuLink::Sys => proto::GpuLinkType::GpuLinkSys,
+        GpuLink::Node => proto::GpuLinkType::GpuLinkNode,
+        GpuLink::Phb => proto::GpuLinkType::GpuLinkPhb,
+        GpuLink::Pxb => proto::GpuLinkType::GpuLinkPxb,
+        GpuLink::Pix => proto::GpuLinkType::GpuLinkPix,
+        GpuLink::NvLink => proto::GpuLinkType::GpuLinkNvlink,
+    }
+}
+
+fn submit_request(job: &tgp_scheduler::JobSpec) -> proto::JobSubmitRequest {
+    proto::JobSubmitRequest {
+        job_id: job.id.clone(),
+        job_type: match job.job_type {
+            tgp_scheduler::JobType::Training => proto::JobType::Training as i32,
+            tgp_scheduler::JobType::Inference => proto::JobType::Inference as i32,
+            tgp_scheduler::JobType::DataProcessing => proto::JobType::DataProcessing as i32,
+        },
+        resources: Some(proto::ResourceRequirements {
+            cpu_cores: job.resources.cpu_cores,
+            memory_gb: job.resources.memory_gb,
+            gpu_count: job.resources.gpu_count,
+            disk_gb: job.resources.disk_gb,
+            network_mbps: job.resources.network_mbps,
+            disk_io_mbps: job.resources.disk_io_mbps,
+        }),
+        sla: Some(proto::SlaConstraints {
+            max_latency_ms: job.sla.max_latency_ms,
+            max_budget_usd: job.sla.max_budget_usd,
+            deadline: job.sla.deadline,
+            earliest_start: job.sla.earliest_start,
+            max_hourly_rate_usd: job.sla.max_hourly_rate_usd,
+            preferences: job.sla.preferences.iter().map(soft_constraint).collect(),
+        }),
+        job_data: job.job_data.clone(),
+        container_image: job.container_image.clone(),
+        command: job.command.clone(),
+        disable_result_cache: job.disable_result_cache,
+        datasets: job.datasets.clone(),
+        movable: job.movable,
+        estimated_duration_hours: job.estimated_duration_hours,
+        best_effort: job.best_effort,
+        gang_size: job.gang_size,
+        priority: match job.priority {
+            tgp_scheduler::priority::PriorityClass::Low => proto::JobPriority::Low as i32,
+            tgp_scheduler::priority::PriorityClass::Normal => proto::JobPriority::Normal as i32,
+            tgp_scheduler::priority::PriorityClass::High => proto::JobPriority::High as i32,
+        },
+        tenant: job.tenant.clone(),
+        environment: job.env.clone(),
+        ports: job.ports.iter().map(|&port| port as u32).collect(),
+        pin_cpus: job.pin_cpus,
+        egress_limit_mbps: job.egress_limit_mbps,
+        input_stdin: job.input_stdin,
+        output_path: job.output_path.clone().unwrap_or_default(),
+        payload_digest: job.payload_digest.clone().unwrap_or_default(),
+        price_hold_secs: job.price_hold_secs,
+        queue: job.queue.clone().unwrap_or_default(),
+        pool: job.pool.clone().unwrap_or_default(),
+        arch: job.arch.clone(),
+        pre_start_hook: job.pre_start_hook.as_ref().map(job_hook),
+        post_complete_hook: job.post_complete_hook.as_ref().map(job_hook),
+        callback_url: job.callback_url.clone().unwrap_or_default(),
+        annotations: job.annotations.clone(),
+    }
+}
+
+fn soft_constraint(constraint: &tgp_scheduler::preferences::SoftConstraint) -> proto::SoftConstraint {
+    use proto::soft_constraint::Preference as Wire;
+    use tgp_scheduler::preferences::Preference;
+
+    let preference = match &constraint.preference {
+        Preference::MaxLatencyMs(ms) => Wire::MaxLatencyMs(*ms),
+        Preference::Region(region) => Wire::Region(region.clone()),
+        Preference::MaxCostUsd(usd) => Wire::MaxCostUsd(*usd),
+    };
+    proto::SoftConstraint { weight: constraint.weight, preference: Some(preference) }
+}
+
+fn job_hook(hook: &tgp_scheduler::lifecycle::JobHook) -> proto::JobHook {
+    proto::JobHook {
+        image: hook.image.clone(),
+        command: hook.command.clone(),
+        timeout_secs: hook.timeout_secs,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tgp_scheduler::JobSpec;
+
+    #[test]
+    fn test_trace_durations_and_relative_arrivals() {
+        let job = |id: &str| JobSpec { id: id.to_string(), ..Default::default() };
+        let records = vec![
+            TraceRecord::RegisterNode {
+                at_ms: 10_000,
+                node: Box::new(NodeInfo { id: "n1".to_string(), available_cpu: 4, ..Default::default() }),
+            },
+            TraceRecord::SubmitJob { at_ms: 11_000, job: Box::new(job("a")) },
+            TraceRecord::JobStatusUpdate { at_ms: 12_000, job_id: "a".to_string(), status: JobStatus::Running },
+            TraceRecord::SubmitJob { at_ms: 13_000, job: Box::new(job("b")) },
+            TraceRecord::JobStatusUpdate { at_ms: 42_000, job_id: "a".to_string(), status: JobStatus::Completed },
+        ];
+
+        let workload = workload_from_trace(&records, 5_000);
+
+        assert_eq!(workload.nodes.len(), 1);
+        assert_eq!(workload.jobs.len(), 2);
+        assert_eq!(workload.jobs[0].arrival_ms, 1_000);
+        assert_eq!(workload.jobs[0].duration_ms, 30_000);
+        assert_eq!(workload.jobs[1].arrival_ms, 3_000);
+        assert_eq!(workload.jobs[1].duration_ms, 5_000);
+    }
+}
@@ -54,6 +54,8 @@ Type=simple
 User=root
 Environment="TGP_NODE_ID=vps-2"
 Environment="TGP_SCHEDULER_URL=$SCHEDULER_URL"
+# Empty: detected from cloud metadata or geo-IP
+Environment="TGP_LOCATION=${TGP_LOCATION:-}"
 Environment="RUST_LOG=info"
 ExecStart=/usr/local/bin/tgp-worker
 Restart=always
@@ -2,21 +2,12 @@
 //!
 //! Submit jobs, query status, and test cost optimization
 
-use anyhow::Result;
+use anyhow::{Context, Result};
 use clap::{Parser, Subcommand};
-use tonic::Request;
+use tgp_client::proto::{exec_in_job_response, BuildSubmitRequest, JobPhase, JobPriority, JobType, ReserveCapacityRequest};
+use tgp_client::{shell_hook, ClientError, JobSpec, TgpClient};
 use tracing::info;
 
-// Include generated proto code
-pub mod proto {
-    tonic::include_proto!("tgp.scheduler.v1");
-}
-
-use proto::{
-    scheduler_service_client::SchedulerServiceClient, JobSubmitRequest, JobType,
-    ResourceRequirements, SlaConstraints, JobStatusRequest, ClusterStatusRequest,
-};
-
 #[derive(Parser)]
 #[command(name = "tgp-test-client")]
 #[command(about = "TGP Test Client - Submit jobs and test scheduler", long_about = None)]
@@ -29,6 +20,8 @@ struct Cli {
     command: Commands,
 }
 
+// Parsed once per run, so the size of SubmitJob does not matter
+#[allow(clippy::large_enum_variant)]
 #[derive(Subcommand)]
 enum Commands {
     /// Submit a test job
@@ -56,6 +49,88 @@ enum Commands {
         /// Max latency in ms
         #[arg(long, default_value = "1000")]
         latency: u64,
+
+        /// Always run, even if an identical job already completed
+        #[arg(long)]
+        no_cache: bool,
+
+        /// Input dataset name (repeatable)
+        #[arg(long = "dataset")]
+        datasets: Vec<String>,
+
+        /// Allow the rebalancer to move the job
+        #[arg(long)]
+        movable: bool,
+
+        /// Expected run time in hours on a reference node
+        #[arg(long)]
+        duration_hours: Option<f64>,
+
+        /// Allow placement in idle reserved capacity (may be evicted)
+        #[arg(long)]
+        best_effort: bool,
+
+        /// Number of nodes the job needs at once
+        #[arg(long, default_value = "0")]
+        gang_size: u32,
+
+        /// Priority class: low, normal or high
+        #[arg(long, default_value = "normal")]
+        priority: String,
+
+        /// Owning tenant
+        #[arg(long, default_value = "")]
+        tenant: String,
+
+        /// File passed to the job as its input (JSON args or a small file)
+        #[arg(long)]
+        input: Option<std::path::PathBuf>,
+
+        /// Feed the input to the job's stdin instead of a file
+        #[arg(long)]
+        stdin: bool,
+
+        /// File or directory in the container to keep as the job's output
+        #[arg(long)]
+        output_path: Option<String>,
+
+        /// Over budget everywhere: wait this many seconds for prices to drop
+        #[arg(long)]
+        price_hold_secs: Option<u64>,
+
+        /// Named queue to submit to (default: the scheduler's default queue)
+        #[arg(long, default_value = "")]
+        queue: String,
+
+        /// Node pool to run in (default: the queue's pool, or any shared node)
+        #[arg(long, default_value = "")]
+        pool: String,
+
+        /// CPU architecture the job can run on, e.g. arm64 (repeatable;
+        /// default: any the image is built for)
+        #[arg(long)]
+        arch: Vec<String>,
+
+        /// Shell command run in the job's image before it starts
+        #[arg(long)]
+        pre_start: Option<String>,
+
+        /// Shell command run in the job's image after it finishes
+        /// ($TGP_JOB_STATUS is "completed" or "failed")
+        #[arg(long)]
+        post_complete: Option<String>,
+
+        /// URL POSTed the job's final status and cost when it finishes
+        #[arg(long, default_value = "")]
+        callback_url: String,
+
+        /// Finish by this Unix time
+        #[arg(long)]
+        deadline: Option<i64>,
+
+        /// Do not start before this Unix time (e.g. an off-peak window)
+        #[arg(long)]
+        earliest_start: Option<i64>,
     },
 
     /// Get job status
@@ -64,8 +139,149 @@ enum Commands {
         job_id: String,
     },
 
+    /// Stream status and progress updates until the job finishes
+    Watch {
+        /// Job ID
+        job_id: String,
+    },
+
     /// Get cluster status
     ClusterStatus,
+
+    /// Submit a job array; "{{index}}" in the command becomes the task index
+    SubmitArray {
+        /// Array ID (tasks are <array_id>-<index>)
+        #[arg(short, long)]
+        array_id: String,
+
+        /// Container image
+        #[arg(short, long, default_value = "alpine:latest")]
+        image: String,
+
+        /// Number of tasks
+        #[arg(long)]
+        size: u32,
+
+        /// Tasks allowed to run at once (0: unlimited)
+        #[arg(long, default_value = "0")]
+        max_parallel: u32,
+
+        /// Failed tasks tolerated before the rest are cancelled
+        #[arg(long)]
+        max_failures: Option<u32>,
+
+        /// Container command
+        #[arg(last = true)]
+        command: Vec<String>,
+    },
+
+    /// Get aggregate status of a job array
+    ArrayStatus {
+        /// Array ID
+        array_id: String,
+    },
+
+    /// Download a finished job's output (a tar archive)
+    GetOutput {
+        /// Job ID
+        job_id: String,
+
+        /// File to write the archive to
+        #[arg(short, long)]
+        out: std::path::PathBuf,
+    },
+
+    /// Print the log tail of a finished job
+    Logs {
+        /// Job ID
+        job_id: String,
+    },
+
+    /// Run a command in a running job's container (operator role)
+    Exec {
+        /// Job ID
+        job_id: String,
+
+        /// Operator API token
+        #[arg(long)]
+        token: String,
+
+        /// Allocate a terminal
+        #[arg(long)]
+        tty: bool,
+
+        /// Command and its arguments
+        #[arg(trailing_var_arg = true, required = true)]
+        command: Vec<String>,
+    },
+
+    /// Build an image from a .tar.gz build context
+    Build {
+        /// Build ID (the image is then "build:<id>")
+        build_id: String,
+
+        /// Build context archive
+        #[arg(short, long)]
+        context: std::path::PathBuf,
+
+        /// Dockerfile path within the context
+        #[arg(long, default_value = "Dockerfile")]
+        dockerfile: String,
+    },
+
+    /// Get the state of an image build
+    GetBuild {
+        /// Build ID
+        build_id: String,
+    },
+
+    /// Show templates whose jobs request more than they use
+    RightSizing {
+        /// Only this template
+        #[arg(long)]
+        template: Option<String>,
+    },
+
+    /// Reserve a block of capacity for a tenant (operator role)
+    ReserveCapacity {
+        /// Reservation ID
+        reservation_id: String,
+
+        /// Tenant the capacity is held for
+        #[arg(long)]
+        tenant: String,
+
+        /// CPU cores to hold
+        #[arg(long, default_value = "0")]
+        cpu: u32,
+
+        /// Memory in GB to hold
+        #[arg(long, default_value = "0")]
+        memory: u32,
+
+        /// GPUs to hold
+        #[arg(long, default_value = "0")]
+        gpus: u32,
+
+        /// Window start, Unix seconds (default: now)
+        #[arg(long)]
+        start: Option<i64>,
+
+        /// Window length in hours
+        #[arg(long)]
+        hours: f64,
+
+        /// Operator API token
+        #[arg(long)]
+        token: String,
+    },
+
+    /// List capacity reserved for tenants
+    Reservations {
+        /// Only this tenant
+        #[arg(long)]
+        tenant: Option<String>,
+    },
 }
 
 #[tokio::main]
@@ -79,7 +295,7 @@ async fn main() -> Result<()> {
 
     // Connect to scheduler
     info!("Connecting to scheduler at {}", cli.scheduler);
-    let mut client = SchedulerServiceClient::connect(cli.scheduler.clone()).await?;
+    let client = TgpClient::connect(cli.scheduler.clone()).await?;
     info!("Connected successfully!");
 
     match cli.command {
@@ -90,115 +306,266 @@ async fn main() -> Result<()> {
             memory,
             budget,
             latency,
+            no_cache,
+            datasets,
+            movable,
+            duration_hours,
+            best_effort,
+            gang_size,
+            priority,
+            tenant,
+            input,
+            stdin,
+            output_path,
+            price_hold_secs,
+            queue,
+            pool,
+            arch,
+            pre_start,
+            post_complete,
+            callback_url,
+            deadline,
+            earliest_start,
         } => {
-            submit_job(&mut client, job_id, image, cpu, memory, budget, latency).await?;
+            info!("Submitting job: {}", job_id);
+            info!("Resources: {} CPU, {}GB RAM", cpu, memory);
+            if let Some(b) = budget {
+                info!("Budget: ${:.2}", b);
+            }
+            info!("Max latency: {}ms", latency);
+
+            let priority = match priority.as_str() {
+                "low" => JobPriority::Low,
+                "normal" => JobPriority::Normal,
+                "high" => JobPriority::High,
+                other => anyhow::bail!("Unknown priority class: {}", other),
+            };
+            let mut spec = JobSpec::builder(image)
+                .id(job_id)
+                .job_type(JobType::Inference)
+                .cpu_cores(cpu)
+                .memory_gb(memory)
+                .max_latency_ms(latency)
+                .disable_result_cache(no_cache)
+                .movable(movable)
+                .best_effort(best_effort)
+                .gang_size(gang_size)
+                .priority(priority)
+                .tenant(tenant)
+                .input_stdin(stdin)
+                .output_path(output_path.unwrap_or_default())
+                .queue(queue)
+                .pool(pool)
+                .callback_url(callback_url);
+            if let Some(path) = input {
+                spec = spec.input(std::fs::read(&path).with_context(|| format!("Failed to read {}", path.display()))?);
+            }
+            for dataset in datasets {
+                spec = spec.dataset(dataset);
+            }
+            for arch in arch {
+                spec = spec.arch(arch);
+            }
+            if let Some(budget) = budget {
+                spec = spec.budget_usd(budget);
+            }
+            if let Some(hours) = duration_hours {
+                spec = spec.duration_hours(hours);
+            }
+            if let Some(secs) = price_hold_secs {
+                spec = spec.price_hold_secs(secs);
+            }
+            if let Some(command) = pre_start {
+                spec = spec.pre_start_hook(shell_hook(command));
+            }
+            if let Some(command) = post_complete {
+                spec = spec.post_complete_hook(shell_hook(command));
+            }
+            if let Some(deadline) = deadline {
+                spec = spec.deadline(deadline);
+            }
+            if let Some(earliest_start) = earliest_start {
+                spec = spec.earliest_start(earliest_start);
+            }
+            submit_job(&client, spec.build()?).await?;
         }
         Commands::GetStatus { job_id } => {
-            get_job_status(&mut client, job_id).await?;
+            get_job_status(&client, job_id).await?;
+        }
+        Commands::Watch { job_id } => {
+            watch_job(&client, job_id).await?;
         }
         Commands::ClusterStatus => {
-            get_cluster_status(&mut client).await?;
+            get_cluster_status(&client).await?;
+        }
+        Commands::SubmitArray { array_id, image, size, max_parallel, max_failures, command } => {
+            submit_array(&client, array_id, image, size, max_parallel, max_failures, command).await?;
+        }
+        Commands::ArrayStatus { array_id } => {
+            get_array_status(&client, array_id).await?;
+        }
+        Commands::GetOutput { job_id, out } => {
+            get_job_output(&client, job_id, out).await?;
+        }
+        Commands::Logs { job_id } => {
+            get_job_logs(&client, job_id).await?;
+        }
+        Commands::Build { build_id, context, dockerfile } => {
+            submit_build(&client, build_id, context, dockerfile).await?;
+        }
+        Commands::GetBuild { build_id } => {
+            get_build(&client, build_id).await?;
+        }
+        Commands::RightSizing { template } => {
+            get_right_sizing(&client, template).await?;
+        }
+        Commands::ReserveCapacity { reservation_id, tenant, cpu, memory, gpus, start, hours, token } => {
+            let start = match start {
+                Some(start) => start,
+                None => std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH)?.as_secs() as i64,
+            };
+            let request = ReserveCapacityRequest {
+                reservation_id,
+                tenant,
+                cpu_cores: cpu,
+                memory_gb: memory,
+                gpus,
+                start,
+                end: start + (hours * 3600.0) as i64,
+            };
+            reserve_capacity(&client, request, token).await?;
+        }
+        Commands::Reservations { tenant } => {
+            list_reservations(&client, tenant).await?;
+        }
+        Commands::Exec { job_id, token, tty, command } => {
+            let exit_code = exec_in_job(&client, job_id, token, tty, command).await?;
+            std::process::exit(exit_code as i32);
         }
     }
 
     Ok(())
 }
 
-async fn submit_job(
-    client: &mut SchedulerServiceClient<tonic::transport::Channel>,
-    job_id: String,
-    _image: String,
-    cpu: u32,
-    memory: u32,
-    budget: Option<f64>,
-    latency: u64,
-) -> Result<()> {
-    info!("Submitting job: {}", job_id);
-    info!("Resources: {} CPU, {}GB RAM", cpu, memory);
-    if let Some(b) = budget {
-        info!("Budget: ${:.2}", b);
-    }
-    info!("Max latency: {}ms", latency);
-
-    let request = Request::new(JobSubmitRequest {
-        job_id: job_id.clone(),
-        job_type: JobType::Inference.into(),
-        resources: Some(ResourceRequirements {
-            cpu_cores: cpu,
-            memory_gb: memory,
-            gpu_count: 0,
-            disk_gb: 10,
-        }),
-        sla: Some(SlaConstraints {
-            max_latency_ms: latency,
-            max_budget_usd: budget,
-            deadline: None,
-        }),
-        job_data: vec![],
-    });
+async fn submit_job(client: &TgpClient, spec: JobSpec) -> Result<()> {
+    let job = match client.submit(spec).await {
+        Ok(job) => job,
+        Err(ClientError::Rejected { message, .. }) => {
+            println!("\n**Job Submission Failed!");
+            println!("Message: {}", message);
+            return Ok(());
+        }
+        Err(e) => {
+            print_suggestions(&e);
+            return Err(e.into());
+        }
+    };
 
-    let response = client.submit_job(request).await?;
-    let job = response.into_inner();
-
-    if job.success {
-        println!("\n**Job Submitted Successfully!");
-        println!("------------------------------");
-        println!("Job ID:        {}", job.job_id);
-        println!("Assigned Node: {}", job.assigned_node);
-        
-        if let Some(cost) = job.cost_estimate {
-            println!("\nCost Estimate (Formula 4.1):");
-            println!("  C_comp (Compute):     ${:.6}", cost.compute_cost_usd);
-            println!("  C_data (Transfer):    ${:.6}", cost.data_transfer_usd);
-            println!("  C_idle (Opportunity): ${:.6}", cost.idle_opportunity_usd);
-            println!("  ─────────────────────────────");
-            println!("  C_total (TCO):        ${:.6}", cost.total_cost_usd);
-            println!("  Estimated Latency:    {}ms", cost.estimated_latency_ms);
-        }
-        
-        println!("\nMessage: {}", job.message);
-        println!("------------------------------\n");
-    } else {
-        println!("\n**Job Submission Failed!");
-        println!("Message: {}", job.message);
+    println!("\n**Job Submitted Successfully!");
+    println!("------------------------------");
+    println!("Job ID:        {}", job.job_id);
+    println!("Assigned Node: {}", job.assigned_node);
+    if !job.gang_nodes.is_empty() {
+        println!("Gang Nodes:    {}", job.gang_nodes.join(", "));
+    }
+    if !job.cached_from_job.is_empty() {
+        println!("Cached From:   {}", job.cached_from_job);
     }
+    
+    if let Some(cost) = job.cost_estimate {
+        println!("\nCost Estimate (Formula 4.1):");
+        println!("  C_comp (Compute):     ${:.6}", cost.compute_cost_usd);
+        println!("  C_data (Transfer):    ${:.6}", cost.data_transfer_usd);
+        println!("  C_idle (Opportunity): ${:.6}", cost.idle_opportunity_usd);
+        println!("  ─────────────────────────────");
+        println!("  C_total (TCO):        ${:.6}", cost.total_cost_usd);
+        println!("  Estimated Latency:    {}ms", cost.estimated_latency_ms);
+        if cost.estimated_staging_ms > 0 {
+            println!("  Data Staging:         {}ms", cost.estimated_staging_ms);
+        }
+        if cost.estimated_duration_hours > 0.0 {
+            println!("  Estimated Duration:   {:.2}h", cost.estimated_duration_hours);
+        }
+    }
+    
+    println!("\nMessage: {}", job.message);
+    println!("------------------------------\n");
 
     Ok(())
 }
 
 async fn get_job_status(
-    client: &mut SchedulerServiceClient<tonic::transport::Channel>,
+    client: &TgpClient,
     job_id: String,
 ) -> Result<()> {
     info!("Querying status for job: {}", job_id);
 
-    let request = Request::new(JobStatusRequest { job_id: job_id.clone() });
-    let response = client.get_job_status(request).await?;
-    let status = response.into_inner();
+    let status = client.status(&job_id).await?;
 
     println!("\nJob Status");
     println!("------------------------------");
     println!("Job ID:        {}", status.job_id);
     println!("Status:        {:?}", status.status);
     println!("Assigned Node: {}", status.assigned_node);
+    if !status.queue.is_empty() {
+        println!("Queue:         {}", status.queue);
+    }
+    if status.phase() != JobPhase::Unspecified {
+        println!("Phase:         {:?}", status.phase());
+    }
+    if !status.image_digest.is_empty() {
+        println!("Image Digest:  {}", status.image_digest);
+    }
+    if status.started_at > 0 && status.submitted_at > 0 {
+        println!("Queue Wait:    {}s", status.started_at - status.submitted_at);
+    }
+    if status.finished_at > 0 && status.started_at > 0 {
+        println!("Run Time:      {}s", status.finished_at - status.started_at);
+    }
     
     if let Some(cost) = status.final_cost {
         println!("\nFinal Cost:");
         println!("  C_total: ${:.6}", cost.total_cost_usd);
     }
+
+    if let Some(progress) = status.progress {
+        println!("\nProgress:      {:.1}% {}", progress.percent, progress.step);
+        for (name, value) in &progress.metrics {
+            println!("  {}: {}", name, value);
+        }
+    }
     println!("------------------------------\n");
 
     Ok(())
 }
 
+async fn watch_job(
+    client: &TgpClient,
+    job_id: String,
+) -> Result<()> {
+    info!("Watching job: {}", job_id);
+
+    use tokio_stream::StreamExt;
+
+    let mut stream = client.watch(&job_id).await?;
+
+    while let Some(status) = stream.next().await {
+        let status = status?;
+        let progress = status.progress.as_ref()
+            .map(|p| format!("{:.1}% {}", p.percent, p.step))
+            .unwrap_or_default();
+        println!("[{}] {:?} {}", status.job_id, status.status(), progress);
+    }
+
+    Ok(())
+}
+
 async fn get_cluster_status(
-    client: &mut SchedulerServiceClient<tonic::transport::Channel>,
+    client: &TgpClient,
 ) -> Result<()> {
     info!("Querying cluster status");
 
-    let request = Request::new(ClusterStatusRequest {});
-    let response = client.get_cluster_status(request).await?;
-    let cluster = response.into_inner();
+    let cluster = client.cluster_status().await?;
 
     println!("\nCluster Status");
     println!("------------------------------");
@@ -206,19 +573,283 @@ async fn get_cluster_status(
     println!("Active Nodes:  {}", cluster.active_nodes);
     println!("Total Jobs:    {}", cluster.total_jobs);
     println!("Running Jobs:  {}", cluster.running_jobs);
+    println!("Version:       {}", cluster.cluster_version);
+    if let Some(overload) = cluster.overload.as_ref().filter(|o| o.overloaded) {
+        println!("Overloaded:    {} (shed {} best-effort jobs)", overload.reason, overload.shed_jobs);
+    }
+
+    if !cluster.pools.is_empty() {
+        println!("\nNode Pools:");
+        for pool in &cluster.pools {
+            println!(
+                "  {:<16} {} nodes, {} CPU, {}GB, {} GPU, ${:.2}/h, {:.0}% utilized{}",
+                pool.name, pool.nodes, pool.cpu_cores, pool.memory_gb, pool.gpus,
+                pool.cost_per_hour, pool.utilization * 100.0,
+                if pool.dedicated { " (dedicated)" } else { "" }
+            );
+        }
+    }
     
     if !cluster.nodes.is_empty() {
         println!("\nRegistered Nodes:");
         for node in cluster.nodes {
             println!("\n  Node: {}", node.node_id);
             println!("    Hostname:   {}", node.hostname);
-            println!("    CPU:        {}", node.available_cpu);
-            println!("    Memory:     {:.1}GB", node.available_memory_gb);
+            println!("    CPU:        {} of {}", node.available_cpu, node.capacity_cpu);
+            println!("    Memory:     {:.1}GB of {:.1}GB", node.available_memory_gb, node.capacity_memory_gb);
             println!("    Location:   {}", node.location);
+            println!("    Arch:       {}", node.arch);
+            if !node.pool.is_empty() {
+                println!("    Pool:       {}", node.pool);
+            }
+            if node.clock_skew_secs.abs() >= 1.0 {
+                println!("    Clock skew: {:+.0}s", node.clock_skew_secs);
+            }
             println!("    Active:     {}", node.is_active);
+            if let Some(efficiency) = &node.efficiency {
+                println!(
+                    "    Paid:       {:.1}h, utilized {:.1}h ({:.0}%), ${:.2}",
+                    efficiency.paid_hours, efficiency.utilized_hours,
+                    efficiency.utilization * 100.0, efficiency.cost_usd
+                );
+                match efficiency.cost_per_completed_job_usd {
+                    Some(usd) => println!("    Per Job:    ${:.4} over {} completed", usd, efficiency.completed_jobs),
+                    None => println!("    Per Job:    no completed jobs"),
+                }
+            }
+        }
+    }
+    println!("------------------------------\n");
+
+    Ok(())
+}
+
+async fn get_right_sizing(
+    client: &TgpClient,
+    template: Option<String>,
+) -> Result<()> {
+    let response = client.right_sizing(template.as_deref()).await?;
+
+    println!("\nRight-Sizing");
+    println!("------------------------------");
+    if response.recommendations.is_empty() {
+        println!("All templates are sized to their use");
+    }
+    for r in response.recommendations {
+        println!("\n  Template: {} ({} runs)", r.template, r.runs);
+        println!("    {}", r.summary);
+        match r.saving_per_run_usd {
+            Some(usd) => println!("    Saving:   ${:.4} per run", usd),
+            None => println!("    Saving:   unknown (no node fits the template now)"),
         }
     }
     println!("------------------------------\n");
 
     Ok(())
 }
+
+async fn reserve_capacity(
+    client: &TgpClient,
+    reservation: ReserveCapacityRequest,
+    token: String,
+) -> Result<()> {
+    let response = client.with_token(&token)?.reserve_capacity(reservation).await?;
+
+    println!("\n{}", response.message);
+    Ok(())
+}
+
+async fn list_reservations(
+    client: &TgpClient,
+    tenant: Option<String>,
+) -> Result<()> {
+    let response = client.reservations(tenant.as_deref()).await?;
+
+    println!("\nCapacity Reservations");
+    println!("------------------------------");
+    if response.reservations.is_empty() {
+        println!("No capacity reserved");
+    }
+    for r in response.reservations {
+        println!("\n  {} (tenant {}, {})", r.reservation_id, r.tenant, if r.active { "active" } else { "upcoming" });
+        println!("    Window:   {} - {}", r.start, r.end);
+        println!("    CPU:      {}/{} cores used", r.used_cpu_cores, r.cpu_cores);
+        println!("    Memory:   {}/{} GB used", r.used_memory_gb, r.memory_gb);
+        println!("    GPUs:     {}/{} used", r.used_gpus, r.gpus);
+        println!("    Idle:     ${:.4} charged", r.idle_cost_usd);
+    }
+    println!("------------------------------\n");
+
+    Ok(())
+}
+
+async fn submit_array(
+    client: &TgpClient,
+    array_id: String,
+    image: String,
+    size: u32,
+    max_parallel: u32,
+    max_failures: Option<u32>,
+    command: Vec<String>,
+) -> Result<()> {
+    info!("Submitting job array {} with {} tasks", array_id, size);
+
+    let template = JobSpec::builder(image)
+        .id(array_id)
+        .job_type(JobType::Inference)
+        .command(command)
+        .build()?;
+    let response = client.submit_array(template, size, max_parallel, max_failures).await?;
+
+    println!("\nJob Array {}: {}", response.array_id, response.message);
+    for task_id in &response.task_ids {
+        println!("  {}", task_id);
+    }
+    Ok(())
+}
+
+async fn get_array_status(
+    client: &TgpClient,
+    array_id: String,
+) -> Result<()> {
+    let status = client.array_status(&array_id).await?;
+
+    println!("\nJob Array Status");
+    println!("------------------------------");
+    println!("Array ID:      {}", status.array_id);
+    println!("State:         {:?}", status.state());
+    println!("Tasks:         {}", status.total);
+    println!("  Pending:     {}", status.pending);
+    println!("  Running:     {}", status.running);
+    println!("  Completed:   {}", status.completed);
+    println!("  Failed:      {}", status.failed);
+    println!("  Cancelled:   {}", status.cancelled);
+    println!("------------------------------\n");
+
+    Ok(())
+}
+
+async fn get_job_output(
+    client: &TgpClient,
+    job_id: String,
+    out: std::path::PathBuf,
+) -> Result<()> {
+    info!("Downloading output of job {}", job_id);
+
+    let archive = client.output(&job_id).await?;
+    std::fs::write(&out, &archive).with_context(|| format!("Failed to write {}", out.display()))?;
+
+    println!("\nOutput of job {}: {} bytes written to {}", job_id, archive.len(), out.display());
+    Ok(())
+}
+
+async fn get_job_logs(
+    client: &TgpClient,
+    job_id: String,
+) -> Result<()> {
+    match client.logs(&job_id).await? {
+        Some(logs) => print!("{}", logs),
+        None => println!("No logs kept for job {} (still running, or too long ago)", job_id),
+    }
+    Ok(())
+}
+
+/// Relay this process's stdin and stdout to a command in a job's container,
+/// returning its exit code
+async fn exec_in_job(
+    client: &TgpClient,
+    job_id: String,
+    token: String,
+    tty: bool,
+    command: Vec<String>,
+) -> Result<i64> {
+    use std::io::Write;
+    use tokio::io::AsyncReadExt;
+
+    let (tx, rx) = tokio::sync::mpsc::channel(16);
+    tokio::spawn(async move {
+        let mut stdin = tokio::io::stdin();
+        let mut buf = vec![0u8; 4096];
+        while let Ok(read) = stdin.read(&mut buf).await {
+            if read == 0 {
+                break;
+            }
+            if tx.send(buf[..read].to_vec()).await.is_err() {
+                break;
+            }
+        }
+    });
+
+    let stdin = tokio_stream::wrappers::ReceiverStream::new(rx);
+    let mut output = client.with_token(&token)?.exec(&job_id, command, tty, stdin).await?;
+    while let Some(message) = output.message().await? {
+        match message.body {
+            Some(exec_in_job_response::Body::Stdout(data)) => {
+                let mut stdout = std::io::stdout();
+                stdout.write_all(&data)?;
+                stdout.flush()?;
+            }
+            Some(exec_in_job_response::Body::Stderr(data)) => std::io::stderr().write_all(&data)?,
+            Some(exec_in_job_response::Body::ExitCode(code)) => return Ok(code),
+            None => {}
+        }
+    }
+    anyhow::bail!("Exec ended without an exit code")
+}
+
+async fn submit_build(
+    client: &TgpClient,
+    build_id: String,
+    context: std::path::PathBuf,
+    dockerfile: String,
+) -> Result<()> {
+    let context = std::fs::read(&context).with_context(|| format!("Failed to read {}", context.display()))?;
+    info!("Submitting build {} ({} byte context)", build_id, context.len());
+
+    let response = client
+        .submit_build(BuildSubmitRequest {
+            build_id,
+            context,
+            dockerfile,
+            tenant: String::new(),
+        })
+        .await?;
+
+    println!("\nBuild submitted");
+    println!("   Build ID: {}", response.build_id);
+    println!("   Job ID:   {}", response.job_id);
+    println!("   Message:  {}", response.message);
+    Ok(())
+}
+
+async fn get_build(
+    client: &TgpClient,
+    build_id: String,
+) -> Result<()> {
+    let build = client.build_status(&build_id).await?;
+
+    println!("\nBuild {}: {}", build.build_id, build.status);
+    println!("   Job ID: {}", build.job_id);
+    if !build.image.is_empty() {
+        println!("   Image:  {}", build.image);
+    }
+    if !build.message.is_empty() {
+        println!("   Reason: {}", build.message);
+    }
+    Ok(())
+}
+
+/// Print the SLA changes a refused submission's error details suggest
+fn print_suggestions(error: &ClientError) {
+    let Some(detail) = error.detail() else {
+        return;
+    };
+    if detail.suggestions.is_empty() {
+        return;
+    }
+    println!("\nJob could not be placed ({}). Suggestions:", detail.reason);
+    for suggestion in &detail.suggestions {
+        println!("  - {}", suggestion.summary);
+    }
+    println!();
+}
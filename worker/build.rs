@@ -1,12 +1,28 @@
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     let proto_file = "../proto/scheduler.proto";
+    let data_proto_file = "../proto/data.proto";
+    let worker_proto_file = "../proto/worker.proto";
     let proto_dir = "../proto";
     
     tonic_build::configure()
         .build_client(true)
         .build_server(false)
         .compile(&[proto_file], &[proto_dir])?;
+
+    // Workers both serve and fetch datasets from each other
+    tonic_build::configure()
+        .build_client(true)
+        .build_server(true)
+        .compile(&[data_proto_file], &[proto_dir])?;
+
+    // Workers serve scheduler-initiated commands
+    tonic_build::configure()
+        .build_client(false)
+        .build_server(true)
+        .compile(&[worker_proto_file], &[proto_dir])?;
     
     println!("cargo:rerun-if-changed={}", proto_file);
+    println!("cargo:rerun-if-changed={}", data_proto_file);
+    println!("cargo:rerun-if-changed={}", worker_proto_file);
     Ok(())
 }
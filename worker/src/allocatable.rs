@@ -0,0 +1,130 @@
+//! Capacity vs allocatable resources
+//!
+//! As with the Kubernetes kubelet, part of a node is held back for the OS,
+//! the container runtime and this agent, and only the rest (allocatable) is
+//! offered to the scheduler. Packing jobs up to full capacity would let them
+//! starve or OOM-kill the agent itself.
+//!
+//! Defaults, overridable with TGP_RESERVED_CPU / TGP_RESERVED_MEMORY_GB /
+//! TGP_RESERVED_DISK_GB:
+//! - CPU: one core on nodes with 4 or more, none on smaller ones
+//! - memory: 25% of the first 4 GB, 20% of the next 4, 10% of the next 8,
+//!   6% of the next 112 and 2% above 128 GB (at least 0.25 GB), plus
+//!   0.1 GB eviction headroom
+//! - disk: 10% of the root filesystem
+
+/// Resources held back from jobs
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Reserved {
+    pub cpu: u32,
+    pub memory_gb: f64,
+    pub disk_gb: f64,
+}
+
+/// Configured reserve; `None` fields fall back to the graduated defaults
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ReserveConfig {
+    pub cpu: Option<u32>,
+    pub memory_gb: Option<f64>,
+    pub disk_gb: Option<f64>,
+}
+
+/// Memory kept free to evict from before the kernel OOM killer steps in
+const EVICTION_HEADROOM_GB: f64 = 0.1;
+
+/// (tier size in GB, share reserved) for the graduated memory reserve
+const MEMORY_TIERS: [(f64, f64); 4] = [(4.0, 0.25), (4.0, 0.20), (8.0, 0.10), (112.0, 0.06)];
+const MEMORY_SHARE_ABOVE_TIERS: f64 = 0.02;
+
+fn default_memory_reserve_gb(total_gb: f64) -> f64 {
+    let mut left = total_gb.max(0.0);
+    let mut reserved = 0.0;
+    for (size, share) in MEMORY_TIERS {
+        let tier = left.min(size);
+        reserved += tier * share;
+        left -= tier;
+    }
+    reserved += left * MEMORY_SHARE_ABOVE_TIERS;
+    reserved.max(0.25) + EVICTION_HEADROOM_GB
+}
+
+impl ReserveConfig {
+    pub fn from_env() -> Self {
+        fn var<T: std::str::FromStr>(name: &str) -> Option<T> {
+            std::env::var(name).ok().and_then(|v| v.parse().ok())
+        }
+        Self {
+            cpu: var("TGP_RESERVED_CPU"),
+            memory_gb: var("TGP_RESERVED_MEMORY_GB"),
+            disk_gb: var("TGP_RESERVED_DISK_GB"),
+        }
+    }
+
+    /// Reserve for a node of the given capacity
+    pub fn reserved(&self, cpu: u32, memory_gb: f64, disk_gb: f64) -> Reserved {
+        Reserved {
+            cpu: self.cpu.unwrap_or(if cpu >= 4 { 1 } else { 0 }),
+            memory_gb: self.memory_gb.unwrap_or_else(|| default_memory_reserve_gb(memory_gb)),
+            disk_gb: self.disk_gb.unwrap_or(disk_gb * 0.10),
+        }
+    }
+}
+
+impl Reserved {
+    /// Allocatable CPU cores; one is always left so the node stays usable
+    pub fn allocatable_cpu(&self, capacity: u32) -> u32 {
+        capacity.saturating_sub(self.cpu).max(capacity.min(1))
+    }
+
+    pub fn allocatable_memory_gb(&self, capacity_gb: f64) -> f64 {
+        (capacity_gb - self.memory_gb).max(0.0)
+    }
+
+    /// What jobs could still get of what is free right now
+    pub fn available_cpu(&self, capacity: u32, free: u32) -> u32 {
+        free.min(self.allocatable_cpu(capacity))
+    }
+
+    /// Free memory beyond the reserve (the OS and agent use theirs already,
+    /// so `free` is capped at allocatable rather than reduced by the reserve)
+    pub fn available_memory_gb(&self, capacity_gb: f64, free_gb: f64) -> f64 {
+        free_gb.min(self.allocatable_memory_gb(capacity_gb)).max(0.0)
+    }
+
+    /// Free disk minus the space that must stay free
+    pub fn available_disk_gb(&self, free_gb: f64) -> f64 {
+        (free_gb - self.disk_gb).max(0.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_reserve_scales_with_node_size() {
+        let defaults = ReserveConfig::default();
+
+        let small = defaults.reserved(2, 2.0, 40.0);
+        assert_eq!(small.cpu, 0);
+        assert!((small.memory_gb - 0.6).abs() < 1e-9);
+        assert!((small.disk_gb - 4.0).abs() < 1e-9);
+        assert_eq!(small.allocatable_cpu(2), 2);
+
+        // 25% of 4 + 20% of 4 + 10% of 8 + 6% of 16 GB, plus headroom
+        let large = defaults.reserved(16, 32.0, 100.0);
+        assert_eq!(large.allocatable_cpu(16), 15);
+        assert!((large.memory_gb - 3.66).abs() < 1e-9);
+        assert!((large.allocatable_memory_gb(32.0) - 28.34).abs() < 1e-9);
+
+        // Free resources are capped at allocatable
+        assert_eq!(large.available_cpu(16, 16), 15);
+        assert!((large.available_memory_gb(32.0, 31.0) - 28.34).abs() < 1e-9);
+        assert!((large.available_memory_gb(32.0, 10.0) - 10.0).abs() < 1e-9);
+        assert_eq!(large.available_disk_gb(5.0), 0.0);
+
+        // Configured values win, but never leave a node without a core
+        let pinned = ReserveConfig { cpu: Some(8), ..Default::default() }.reserved(4, 8.0, 10.0);
+        assert_eq!(pinned.allocatable_cpu(4), 1);
+    }
+}
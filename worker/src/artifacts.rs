@@ -0,0 +1,75 @@
+//! Job artifacts - container → worker → scheduler
+//!
+//! Containers publish small outputs for later workflow steps by writing
+//! `name=value` lines to the file named by `$TGP_ARTIFACTS_FILE`
+//! (`/tgp/artifacts`, next to the progress file):
+//!
+//! ```text
+//! model_path=/shared/models/run-42
+//! accuracy=0.913
+//! ```
+//!
+//! The worker reads the file once the container exits and reports the
+//! artifacts to the scheduler.
+
+#![allow(dead_code)]
+
+use std::collections::HashMap;
+use std::path::Path;
+use tonic::transport::Channel;
+use tracing::warn;
+
+use crate::proto::{scheduler_service_client::SchedulerServiceClient, ArtifactReport};
+
+/// Artifacts file path as seen from inside the container
+pub const CONTAINER_ARTIFACTS_FILE: &str = "/tgp/artifacts";
+
+/// Parse `name=value` lines; blank lines and `#` comments are skipped and
+/// later lines win
+pub fn parse_artifacts(text: &str) -> HashMap<String, String> {
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| line.split_once('='))
+        .map(|(name, value)| (name.trim().to_string(), value.trim().to_string()))
+        .filter(|(name, _)| !name.is_empty())
+        .collect()
+}
+
+/// Artifacts written by a finished job (empty if it wrote none)
+pub fn read_artifacts(path: &Path) -> HashMap<String, String> {
+    std::fs::read_to_string(path)
+        .map(|text| parse_artifacts(&text))
+        .unwrap_or_default()
+}
+
+/// Send a job's artifacts to the scheduler
+pub async fn report_artifacts(
+    client: &mut SchedulerServiceClient<Channel>,
+    job_id: &str,
+    artifacts: HashMap<String, String>,
+) {
+    if artifacts.is_empty() {
+        return;
+    }
+    let request = tonic::Request::new(ArtifactReport {
+        job_id: job_id.to_string(),
+        artifacts,
+    });
+    if let Err(e) = client.report_artifacts(request).await {
+        warn!("Failed to report artifacts for job {}: {}", job_id, e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_artifacts() {
+        let artifacts = parse_artifacts("# outputs\nmodel = /m/42\n\nbroken line\naccuracy=0.9\naccuracy=0.91\n");
+        assert_eq!(artifacts.len(), 2);
+        assert_eq!(artifacts["model"], "/m/42");
+        assert_eq!(artifacts["accuracy"], "0.91");
+    }
+}
@@ -0,0 +1,117 @@
+//! Registration-time node benchmark
+//!
+//! A short CPU, memory and disk run (well under a second on a typical VPS)
+//! yields a performance score relative to a reference node (1.0). The
+//! scheduler divides cost_per_hour by this score, so heterogeneous nodes are
+//! compared on cost-per-performance rather than raw price.
+
+use std::fs;
+use std::hint::black_box;
+use std::io::Write;
+use std::path::Path;
+use std::time::Instant;
+
+/// Integer mixing rounds per second on the reference node (single core)
+const REFERENCE_CPU_OPS_PER_SEC: f64 = 400_000_000.0;
+/// Memory copy bandwidth of the reference node
+const REFERENCE_MEMORY_BYTES_PER_SEC: f64 = 5_000_000_000.0;
+/// Synced sequential write throughput of the reference node
+const REFERENCE_DISK_BYTES_PER_SEC: f64 = 200_000_000.0;
+
+const CPU_ROUNDS: u64 = 40_000_000;
+const MEMORY_BUFFER_BYTES: usize = 64 * 1024 * 1024;
+const MEMORY_PASSES: usize = 4;
+const DISK_BYTES: usize = 32 * 1024 * 1024;
+
+/// Per-component scores (reference node = 1.0) and their combination
+#[derive(Debug, Clone)]
+pub struct BenchmarkResult {
+    pub cpu: f64,
+    pub memory: f64,
+    /// None if the scratch file could not be written
+    pub disk: Option<f64>,
+    pub score: f64,
+}
+
+/// Run all benchmarks, writing the disk scratch file under `scratch_dir`
+pub fn run(scratch_dir: &Path) -> BenchmarkResult {
+    let cpu = cpu_score();
+    let memory = memory_score();
+    let disk = disk_score(scratch_dir);
+
+    let mut scores = vec![cpu, memory];
+    scores.extend(disk);
+
+    BenchmarkResult { cpu, memory, disk, score: geometric_mean(&scores) }
+}
+
+/// Single-core integer throughput (core count is reported separately)
+fn cpu_score() -> f64 {
+    let start = Instant::now();
+    let mut x: u64 = 0x9E37_79B9_7F4A_7C15;
+    for _ in 0..CPU_ROUNDS {
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+    }
+    black_box(x);
+    rate(CPU_ROUNDS as f64, start) / REFERENCE_CPU_OPS_PER_SEC
+}
+
+fn memory_score() -> f64 {
+    let src = vec![1u8; MEMORY_BUFFER_BYTES];
+    let mut dst = vec![0u8; MEMORY_BUFFER_BYTES];
+
+    let start = Instant::now();
+    for _ in 0..MEMORY_PASSES {
+        dst.copy_from_slice(black_box(&src));
+        black_box(&mut dst);
+    }
+    rate((MEMORY_BUFFER_BYTES * MEMORY_PASSES) as f64, start) / REFERENCE_MEMORY_BYTES_PER_SEC
+}
+
+fn disk_score(scratch_dir: &Path) -> Option<f64> {
+    fs::create_dir_all(scratch_dir).ok()?;
+    let path = scratch_dir.join(".tgp-benchmark");
+
+    let start = Instant::now();
+    let written = write_scratch(&path);
+    let score = rate(DISK_BYTES as f64, start) / REFERENCE_DISK_BYTES_PER_SEC;
+
+    let _ = fs::remove_file(&path);
+    written.ok().map(|_| score)
+}
+
+fn write_scratch(path: &Path) -> std::io::Result<()> {
+    let block = vec![0xA5u8; 1024 * 1024];
+    let mut file = fs::File::create(path)?;
+    for _ in 0..DISK_BYTES / block.len() {
+        file.write_all(&block)?;
+    }
+    file.sync_all()
+}
+
+fn rate(amount: f64, start: Instant) -> f64 {
+    amount / start.elapsed().as_secs_f64().max(1e-6)
+}
+
+/// Geometric mean, so no single component dominates the score
+fn geometric_mean(scores: &[f64]) -> f64 {
+    if scores.is_empty() {
+        return 1.0;
+    }
+    let log_sum: f64 = scores.iter().map(|s| s.max(1e-6).ln()).sum();
+    (log_sum / scores.len() as f64).exp()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_geometric_mean() {
+        assert!((geometric_mean(&[2.0, 0.5]) - 1.0).abs() < 1e-9);
+        assert!((geometric_mean(&[4.0, 1.0, 2.0]) - 2.0).abs() < 1e-9);
+        assert_eq!(geometric_mean(&[]), 1.0);
+    }
+}
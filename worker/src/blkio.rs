@@ -0,0 +1,83 @@
+//! Per-job disk I/O throttling
+//!
+//! Jobs that declare a disk throughput cap get blkio limits on the block
+//! device holding their scratch directory, so one I/O-heavy job can't starve
+//! the other jobs on the node. cgroup throttling applies to whole disks, so
+//! a partition is resolved to its parent device. Only Linux hosts have
+//! block devices to throttle; elsewhere jobs run unthrottled.
+
+use bollard::models::ThrottleDevice;
+use std::path::Path;
+
+/// IOPS allowed per MB/s of throughput (sized for 64 KiB requests)
+const IOPS_PER_MBPS: u64 = 16;
+
+/// Never throttle below this many IOPS, so metadata-heavy jobs still move
+const MIN_IOPS: u64 = 100;
+
+/// blkio limits for one device, in bytes/s and operations/s
+#[derive(Debug, Clone, PartialEq)]
+pub struct Throttle {
+    pub bps: Vec<ThrottleDevice>,
+    pub iops: Vec<ThrottleDevice>,
+}
+
+/// Split a Linux `st_dev` into (major, minor)
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+fn major_minor(dev: u64) -> (u64, u64) {
+    let major = ((dev >> 8) & 0xfff) | ((dev >> 32) & !0xfff);
+    let minor = (dev & 0xff) | ((dev >> 12) & !0xff);
+    (major, minor)
+}
+
+/// Device node of the disk backing `path` (None for virtual filesystems
+/// such as overlay or tmpfs)
+#[cfg(target_os = "linux")]
+pub fn disk_for(path: &Path) -> Option<String> {
+    use std::fs;
+    use std::os::unix::fs::MetadataExt;
+
+    let (major, minor) = major_minor(fs::metadata(path).ok()?.dev());
+    if major == 0 {
+        return None;
+    }
+    let sys = fs::canonicalize(format!("/sys/dev/block/{}:{}", major, minor)).ok()?;
+    let disk = if sys.join("partition").exists() { sys.parent()? } else { sys.as_path() };
+    Some(format!("/dev/{}", disk.file_name()?.to_str()?))
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn disk_for(_path: &Path) -> Option<String> {
+    None
+}
+
+/// Limits for a `mbps` MB/s cap on `device`
+pub fn throttle(device: &str, mbps: u32) -> Throttle {
+    let limit = |rate: u64| vec![ThrottleDevice {
+        path: Some(device.to_string()),
+        rate: Some(rate as i64),
+    }];
+    Throttle {
+        bps: limit(mbps as u64 * 1_000_000),
+        iops: limit((mbps as u64 * IOPS_PER_MBPS).max(MIN_IOPS)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_throttle_limits() {
+        // 8:1 is /dev/sda1, 259:3 an NVMe partition (extended minor bits)
+        assert_eq!(major_minor(0x801), (8, 1));
+        assert_eq!(major_minor((259 << 8) | 3), (259, 3));
+        assert_eq!(major_minor(0x10_0000 | (259 << 8)), (259, 256));
+
+        let limits = throttle("/dev/sda", 50);
+        assert_eq!(limits.bps[0].path.as_deref(), Some("/dev/sda"));
+        assert_eq!(limits.bps[0].rate, Some(50_000_000));
+        assert_eq!(limits.iops[0].rate, Some(800));
+        assert_eq!(throttle("/dev/sda", 2).iops[0].rate, Some(100));
+    }
+}
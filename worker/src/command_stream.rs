@@ -0,0 +1,218 @@
+//! Scheduler command stream
+//!
+//! Workers the scheduler cannot dial (e.g. behind NAT) open a
+//! `CommandStream` to it and receive the same commands the control service
+//! would: execute, cancel, ping and list running jobs. The stream is
+//! reopened with backoff whenever it drops. The scheduler resends commands
+//! it has no ack for, so a command seen before is answered with its
+//! earlier ack instead of being run twice.
+
+use anyhow::{Context, Result};
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+use tracing::{info, warn};
+
+use crate::control::JobRunner;
+use crate::executor::JobExecution;
+use crate::hooks::LifecycleHook;
+use crate::proto::{
+    scheduler_command::Command, scheduler_service_client::SchedulerServiceClient,
+    worker_stream_message::Body, CommandAck, CommandStreamHello, JobAssignment, SchedulerCommand,
+    WorkerStreamMessage,
+};
+
+/// Upper bound of the reconnect backoff
+const MAX_RECONNECT_DELAY: Duration = Duration::from_secs(60);
+
+/// Acks remembered for duplicate detection
+const SEEN_CAPACITY: usize = 1024;
+
+/// Acks of recently handled commands of the current scheduler epoch
+#[derive(Debug, Default)]
+pub struct SeenCommands {
+    epoch: u64,
+    acks: HashMap<u64, CommandAck>,
+    order: VecDeque<u64>,
+}
+
+impl SeenCommands {
+    /// Earlier ack of this command, if it was handled already
+    pub fn lookup(&mut self, epoch: u64, seq: u64) -> Option<CommandAck> {
+        if epoch != self.epoch {
+            // The scheduler restarted and numbers commands from scratch
+            self.epoch = epoch;
+            self.acks.clear();
+            self.order.clear();
+            return None;
+        }
+        self.acks.get(&seq).cloned()
+    }
+
+    pub fn record(&mut self, ack: CommandAck) {
+        if ack.epoch != self.epoch {
+            return;
+        }
+        if self.order.len() == SEEN_CAPACITY {
+            if let Some(oldest) = self.order.pop_front() {
+                self.acks.remove(&oldest);
+            }
+        }
+        self.order.push_back(ack.seq);
+        self.acks.insert(ack.seq, ack);
+    }
+}
+
+fn job_execution(assignment: JobAssignment) -> JobExecution {
+    JobExecution {
+        job_id: assignment.job_id,
+        job_type: assignment.job_type,
+        container_image: assignment.container_image,
+        cpu_limit: assignment.cpu_limit,
+        memory_limit_mb: assignment.memory_limit_mb,
+        disk_limit_gb: assignment.disk_limit_gb,
+        gpu_indices: assignment.gpu_indices,
+        port_bindings: assignment.port_bindings,
+        cpu_set: assignment.cpu_set,
+        numa_node: assignment.numa_node,
+        disk_io_mbps: assignment.disk_io_mbps,
+        egress_limit_mbps: assignment.egress_limit_mbps,
+        input: assignment.input,
+        input_stdin: assignment.input_stdin,
+        output_path: (!assignment.output_path.is_empty()).then_some(assignment.output_path),
+        payload_digest: (!assignment.payload_digest.is_empty()).then_some(assignment.payload_digest),
+        command: (!assignment.command.is_empty()).then_some(assignment.command),
+        env: assignment.environment,
+        pre_start_hook: assignment.pre_start_hook
+            .and_then(|hook| LifecycleHook::new(hook.image, hook.command, hook.timeout_secs)),
+        post_complete_hook: assignment.post_complete_hook
+            .and_then(|hook| LifecycleHook::new(hook.image, hook.command, hook.timeout_secs)),
+        datasets: assignment.datasets,
+        dataset_dir: None,
+    }
+}
+
+/// Run one command and build its ack
+async fn handle(runner: &JobRunner, command: SchedulerCommand) -> CommandAck {
+    let mut ack = CommandAck { epoch: command.epoch, seq: command.seq, ok: true, ..Default::default() };
+    let result: Result<String> = match command.command {
+        Some(Command::Execute(assignment)) => {
+            let job_id = assignment.job_id.clone();
+            Ok(if runner.start(job_execution(assignment)) {
+                format!("Job {} started", job_id)
+            } else {
+                format!("Job {} is already running", job_id)
+            })
+        }
+        Some(Command::Cancel(cancel)) => runner.stop(&cancel.job_id, cancel.grace_secs.max(0), cancel.crash).await
+            .map(|cancelled| if cancelled {
+                format!("Job {} cancelled", cancel.job_id)
+            } else {
+                format!("Job {} is not running", cancel.job_id)
+            }),
+        Some(Command::Ping(_)) | Some(Command::ListRunningJobs(_)) => runner.running_jobs().await
+            .map(|job_ids| {
+                ack.running_job_ids = job_ids;
+                String::new()
+            }),
+        None => Err(anyhow::anyhow!("Unknown command")),
+    };
+    match result {
+        Ok(message) => ack.message = message,
+        Err(e) => {
+            ack.ok = false;
+            ack.message = format!("{:#}", e);
+        }
+    }
+    ack
+}
+
+/// One stream session; returns when the scheduler closes it
+async fn session(
+    scheduler_url: &str,
+    node_id: &str,
+    fencing_token: u64,
+    runner: &JobRunner,
+    seen: &mut SeenCommands,
+) -> Result<()> {
+    let mut client = SchedulerServiceClient::connect(scheduler_url.to_string())
+        .await
+        .context("Failed to connect to scheduler")?;
+
+    let (tx, rx) = mpsc::channel(64);
+    tx.send(WorkerStreamMessage {
+        body: Some(Body::Hello(CommandStreamHello { node_id: node_id.to_string(), fencing_token })),
+    }).await?;
+    let mut commands = client.command_stream(ReceiverStream::new(rx))
+        .await
+        .context("Failed to open command stream")?
+        .into_inner();
+    info!("Command stream open");
+
+    while let Some(command) = commands.message().await.context("Command stream broken")? {
+        let ack = match seen.lookup(command.epoch, command.seq) {
+            Some(ack) => ack,
+            None => {
+                let ack = handle(runner, command).await;
+                seen.record(ack.clone());
+                ack
+            }
+        };
+        tx.send(WorkerStreamMessage { body: Some(Body::Ack(ack)) })
+            .await
+            .context("Command stream closed")?;
+    }
+    Ok(())
+}
+
+/// Keep a command stream to the scheduler open until the process exits
+pub async fn run(
+    scheduler_url: String,
+    node_id: String,
+    fencing_token: Arc<AtomicU64>,
+    runner: JobRunner,
+    reconnect_delay: Duration,
+) {
+    let mut seen = SeenCommands::default();
+    let mut delay = reconnect_delay;
+    loop {
+        let opened = Instant::now();
+        let token = fencing_token.load(Ordering::SeqCst);
+        match session(&scheduler_url, &node_id, token, &runner, &mut seen).await {
+            Ok(()) => info!("Command stream closed by scheduler"),
+            Err(e) => warn!("Command stream: {:#}", e),
+        }
+        // Back off only while the stream keeps failing right away
+        if opened.elapsed() > MAX_RECONNECT_DELAY {
+            delay = reconnect_delay;
+        }
+        tokio::time::sleep(delay).await;
+        delay = (delay * 2).min(MAX_RECONNECT_DELAY);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ack(epoch: u64, seq: u64) -> CommandAck {
+        CommandAck { epoch, seq, ok: true, ..Default::default() }
+    }
+
+    #[test]
+    fn test_duplicates_reuse_ack_until_scheduler_restarts() {
+        let mut seen = SeenCommands::default();
+        assert!(seen.lookup(7, 1).is_none());
+        seen.record(ack(7, 1));
+        assert_eq!(seen.lookup(7, 1), Some(ack(7, 1)));
+        assert!(seen.lookup(7, 2).is_none());
+
+        // A new epoch starts numbering over
+        assert!(seen.lookup(8, 1).is_none());
+        seen.record(ack(7, 3));
+        assert!(seen.lookup(8, 3).is_none());
+    }
+}
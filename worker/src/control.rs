@@ -0,0 +1,656 @@
+//! Worker control service
+//!
+//! A small gRPC server the scheduler calls directly when this worker has a
+//! reachable control address: start and cancel jobs, list the job
+//! containers running here (used to reconcile state after a scheduler
+//! restart) and answer pings. Job status, progress and artifacts flow back
+//! over the usual scheduler RPCs.
+//!
+//! The service is only started when a control address is advertised, and
+//! every call must carry the worker control token (TGP_WORKER_CONTROL_TOKEN,
+//! shared with the scheduler) as a bearer token.
+
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::metadata::MetadataMap;
+use tonic::service::Interceptor;
+use tonic::transport::Server;
+use tonic::{Request, Response, Status, Streaming};
+use tracing::{error, info, warn};
+
+use crate::artifacts;
+use crate::data_service::DatasetStager;
+use crate::exec;
+use crate::executor::{JobExecution, JobExecutor, JobResult};
+use crate::hooks::{self, HookPhase, LifecycleHook};
+use crate::logs;
+use crate::outputs;
+use crate::payloads;
+use crate::progress;
+use crate::proto::{JobPhase, JobStatus, JobStatusUpdate};
+use crate::runtime::ContainerRuntime;
+use crate::scheduler_client::{GrpcScheduler, SchedulerClient};
+
+// Include generated worker service code
+pub mod worker_proto {
+    tonic::include_proto!("tgp.worker.v1");
+}
+
+use worker_proto::{
+    worker_service_server::{WorkerService, WorkerServiceServer},
+    exec_request, CancelJobRequest, CancelJobResponse, ExecRequest, ExecResponse, ExecStart,
+    ExecuteJobRequest, ExecuteJobResponse,
+    ListRunningJobsRequest, ListRunningJobsResponse, PingRequest, PingResponse,
+};
+
+/// Jobs accepted by this worker and not yet reported finished
+#[derive(Debug, Clone, Default)]
+pub struct ActiveJobs {
+    /// Job id to whether a cancel was requested
+    jobs: Arc<Mutex<HashMap<String, bool>>>,
+}
+
+impl ActiveJobs {
+    /// Accept a job; false if it is already active
+    pub fn claim(&self, job_id: &str) -> bool {
+        let mut jobs = self.jobs.lock().unwrap_or_else(|e| e.into_inner());
+        if jobs.contains_key(job_id) {
+            return false;
+        }
+        jobs.insert(job_id.to_string(), false);
+        true
+    }
+
+    /// Flag an active job as cancelled; false if it is not active
+    pub fn cancel(&self, job_id: &str) -> bool {
+        let mut jobs = self.jobs.lock().unwrap_or_else(|e| e.into_inner());
+        match jobs.get_mut(job_id) {
+            Some(cancelled) => {
+                *cancelled = true;
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn contains(&self, job_id: &str) -> bool {
+        self.jobs.lock().map(|jobs| jobs.contains_key(job_id)).unwrap_or(false)
+    }
+
+    pub fn is_cancelled(&self, job_id: &str) -> bool {
+        self.jobs.lock().map(|jobs| jobs.get(job_id).copied().unwrap_or(false)).unwrap_or(false)
+    }
+
+    /// Drop a finished job, returning whether it was cancelled
+    pub fn release(&self, job_id: &str) -> bool {
+        let mut jobs = self.jobs.lock().unwrap_or_else(|e| e.into_inner());
+        jobs.remove(job_id).unwrap_or(false)
+    }
+
+    pub fn count(&self) -> usize {
+        self.jobs.lock().map(|jobs| jobs.len()).unwrap_or(0)
+    }
+}
+
+/// Runs jobs in containers and reports their outcome to the scheduler
+#[derive(Clone)]
+pub struct JobRunner {
+    node_id: String,
+    runtime: Arc<dyn ContainerRuntime>,
+    scheduler: Arc<dyn SchedulerClient>,
+    active: ActiveJobs,
+    /// Stages jobs' input datasets (None: jobs with datasets fail)
+    datasets: Option<DatasetStager>,
+    /// Token of this worker's current registration, sent with status updates
+    fencing_token: Arc<AtomicU64>,
+}
+
+impl JobRunner {
+    /// Run jobs on the local Docker daemon
+    pub fn new(node_id: String, scheduler_url: &str) -> Result<Self> {
+        Ok(Self::with(
+            node_id,
+            Arc::new(JobExecutor::new()?),
+            // Connects on first use and reconnects after failures
+            Arc::new(GrpcScheduler::lazy(scheduler_url)?),
+        ))
+    }
+
+    pub fn with(node_id: String, runtime: Arc<dyn ContainerRuntime>, scheduler: Arc<dyn SchedulerClient>) -> Self {
+        Self {
+            node_id,
+            runtime,
+            scheduler,
+            active: ActiveJobs::default(),
+            datasets: None,
+            fencing_token: Arc::default(),
+        }
+    }
+
+    /// Send the registration token in `fencing_token` with status updates
+    pub fn with_fencing_token(mut self, fencing_token: Arc<AtomicU64>) -> Self {
+        self.fencing_token = fencing_token;
+        self
+    }
+
+    /// Stage jobs' input datasets with `stager`
+    pub fn with_datasets(mut self, stager: DatasetStager) -> Self {
+        self.datasets = Some(stager);
+        self
+    }
+
+    pub fn active_jobs(&self) -> usize {
+        self.active.count()
+    }
+
+    /// Jobs whose containers are running
+    pub async fn running_jobs(&self) -> Result<Vec<String>> {
+        self.runtime.running_jobs().await
+    }
+
+    /// Start `job` in the background; false if it is already running here
+    pub fn start(&self, job: JobExecution) -> bool {
+        if !self.active.claim(&job.job_id) {
+            return false;
+        }
+        let runner = self.clone();
+        tokio::spawn(async move { runner.run(job).await });
+        true
+    }
+
+    /// Stop a running job; false if it is not running here
+    pub async fn cancel(&self, job_id: &str, grace_secs: i64) -> Result<bool> {
+        if !self.active.cancel(job_id) {
+            return Ok(false);
+        }
+        // The job's container may not be up yet (or any more) while a hook runs
+        if let Err(e) = self.runtime.stop_job(job_id, grace_secs).await {
+            let mut stopped = false;
+            for phase in HookPhase::ALL {
+                stopped |= self.runtime.stop_job(&hooks::hook_job_id(job_id, phase), grace_secs).await.is_ok();
+            }
+            if !stopped {
+                return Err(e);
+            }
+        }
+        Ok(true)
+    }
+
+    /// Kill a running job's container without flagging it cancelled, so it
+    /// is reported failed as after a crash (scheduler fault injection)
+    pub async fn kill(&self, job_id: &str) -> Result<bool> {
+        if !self.active.contains(job_id) {
+            return Ok(false);
+        }
+        warn!("Killing job {} on scheduler request (fault injection)", job_id);
+        self.runtime.stop_job(job_id, 0).await?;
+        Ok(true)
+    }
+
+    /// Stop a job as the scheduler asked: cancelled, or killed when
+    /// `crash` is set
+    pub async fn stop(&self, job_id: &str, grace_secs: i64, crash: bool) -> Result<bool> {
+        if crash {
+            self.kill(job_id).await
+        } else {
+            self.cancel(job_id, grace_secs).await
+        }
+    }
+
+    /// Run a command in a job's container for the rest of the session
+    pub fn exec(&self, start: ExecStart, inbound: Streaming<ExecRequest>) -> ReceiverStream<Result<ExecResponse, Status>> {
+        let (tx, rx) = mpsc::channel(16);
+        tokio::spawn(exec::run(self.runtime.clone(), start, inbound, tx));
+        ReceiverStream::new(rx)
+    }
+
+    async fn run(&self, mut job: JobExecution) {
+        let job_id = job.job_id.clone();
+        let pre_start = job.pre_start_hook.take();
+        let post_complete = job.post_complete_hook.take();
+        // Phases are only reported for jobs that have more than one
+        let phased = pre_start.is_some() || post_complete.is_some();
+        let first_phase = if pre_start.is_some() { JobPhase::PreStart } else { JobPhase::Main };
+        // What the post-complete hook runs against, once the job is gone
+        let hook_base = post_complete.as_ref().map(|_| JobExecution { input: Vec::new(), ..job.clone() });
+        self.report(JobStatusUpdate {
+            job_id: job_id.clone(),
+            status: JobStatus::Running as i32,
+            phase: if phased { first_phase as i32 } else { JobPhase::Unspecified as i32 },
+            ..Default::default()
+        }).await;
+
+        let (progress_tx, progress_rx) = mpsc::channel(16);
+        if let Some(client) = self.scheduler.grpc() {
+            tokio::spawn(progress::forward_progress(client, self.node_id.clone(), job_id.clone(), progress_rx));
+        }
+        let result = async {
+            if !job.datasets.is_empty() {
+                let stager = self.datasets.as_ref().context("No data directory to stage the job's datasets into")?;
+                let mut client = self.scheduler.grpc().context("No gRPC connection to find the job's datasets")?;
+                stager.stage(&mut client, &self.node_id, &job.datasets).await?;
+                job.dataset_dir = Some(stager.data_dir().to_path_buf());
+            }
+            if let Some(hook) = &pre_start {
+                let hook_result = hooks::run(self.runtime.as_ref(), &job, hook, HookPhase::PreStart, None).await?;
+                if !hook_result.success {
+                    return Ok(hooks::failed_job(&job_id, hook_result));
+                }
+                anyhow::ensure!(!self.active.is_cancelled(&job_id), "Cancelled during its pre-start hook");
+                self.report_phase(&job_id, JobPhase::Main).await;
+            }
+            if let Some(digest) = job.payload_digest.take() {
+                let mut client = self.scheduler.grpc().context("No gRPC connection to fetch the job's input")?;
+                job.input = payloads::fetch(&mut client, &digest).await?;
+            }
+            self.runtime.execute_job(job, Some(progress_tx)).await
+        }.await;
+        let cancelled = self.active.is_cancelled(&job_id);
+
+        let update = match result {
+            Ok(mut result) => {
+                if let Some(mut client) = self.scheduler.grpc() {
+                    artifacts::report_artifacts(&mut client, &job_id, result.artifacts.clone()).await;
+                }
+                if let Some(archive) = result.output_archive.take() {
+                    let uploaded = match self.scheduler.grpc() {
+                        Some(mut client) => outputs::upload(&mut client, &job_id, &archive).await,
+                        None => Err(anyhow::anyhow!("No gRPC connection to upload it")),
+                    };
+                    match uploaded {
+                        Ok(size) => info!("Uploaded {} byte output of job {}", size, job_id),
+                        // A job whose results were lost has not succeeded
+                        Err(e) => {
+                            warn!("Failed to upload output of job {}: {:#}", job_id, e);
+                            result.success = false;
+                            result.error.get_or_insert_with(|| format!("Failed to upload output: {:#}", e));
+                        }
+                    }
+                    let _ = std::fs::remove_file(&archive);
+                }
+                finished_update(result, cancelled)
+            }
+            Err(e) => {
+                error!("Job {} could not run: {:#}", job_id, e);
+                JobStatusUpdate {
+                    job_id: job_id.clone(),
+                    status: (if cancelled { JobStatus::Cancelled } else { JobStatus::Failed }) as i32,
+                    error_message: format!("{:#}", e),
+                    ..Default::default()
+                }
+            }
+        };
+
+        if let (Some(hook), Some(base)) = (&post_complete, &hook_base) {
+            self.run_post_complete(base, hook, &update).await;
+        }
+        self.active.release(&job_id);
+        self.report(update).await;
+        logs::mark_reported(self.runtime.log_config(), &job_id);
+        for phase in HookPhase::ALL {
+            logs::mark_reported(self.runtime.log_config(), &hooks::hook_job_id(&job_id, phase));
+        }
+    }
+
+    /// Run the post-complete hook; its failure leaves the job's outcome as is
+    async fn run_post_complete(&self, job: &JobExecution, hook: &LifecycleHook, update: &JobStatusUpdate) {
+        self.report_phase(&job.job_id, JobPhase::PostComplete).await;
+        let status = match update.status() {
+            JobStatus::Completed => "completed",
+            JobStatus::Cancelled => "cancelled",
+            _ => "failed",
+        };
+        match hooks::run(self.runtime.as_ref(), job, hook, HookPhase::PostComplete, Some(status)).await {
+            Ok(result) if result.success => {}
+            Ok(result) => warn!(
+                "Post-complete hook of job {} failed: {}",
+                job.job_id,
+                result.error.unwrap_or_else(|| format!("exit code {}", result.exit_code))
+            ),
+            Err(e) => warn!("Post-complete hook of job {} could not run: {:#}", job.job_id, e),
+        }
+    }
+
+    async fn report_phase(&self, job_id: &str, phase: JobPhase) {
+        self.report(JobStatusUpdate {
+            job_id: job_id.to_string(),
+            status: JobStatus::Running as i32,
+            phase: phase as i32,
+            ..Default::default()
+        }).await;
+    }
+
+    async fn report(&self, mut update: JobStatusUpdate) {
+        update.node_id = self.node_id.clone();
+        update.fencing_token = self.fencing_token.load(Ordering::SeqCst);
+        let job_id = update.job_id.clone();
+        if let Err(e) = self.scheduler.update_job_status(update).await {
+            warn!("Failed to report status of job {}: {:#}", job_id, e);
+        }
+    }
+}
+
+/// Final status update for a job whose container ran
+fn finished_update(result: JobResult, cancelled: bool) -> JobStatusUpdate {
+    let status = if cancelled {
+        JobStatus::Cancelled
+    } else if result.success {
+        JobStatus::Completed
+    } else {
+        JobStatus::Failed
+    };
+    JobStatusUpdate {
+        job_id: result.job_id,
+        status: status as i32,
+        exit_code: result.exit_code,
+        logs: result.logs,
+        error_message: result.error.unwrap_or_default(),
+        peak_memory_gb: result.peak_memory_gb,
+        peak_cpu_cores: result.peak_cpu_cores,
+        output_hash: result.output_hash,
+        phase: JobPhase::Unspecified as i32,
+        ..Default::default()
+    }
+}
+
+/// Container parameters for an ExecuteJob request
+pub fn job_execution(req: ExecuteJobRequest) -> JobExecution {
+    JobExecution {
+        job_id: req.job_id,
+        job_type: req.job_type,
+        container_image: req.container_image,
+        cpu_limit: req.cpu_limit,
+        memory_limit_mb: req.memory_limit_mb,
+        disk_limit_gb: req.disk_limit_gb,
+        gpu_indices: req.gpu_indices,
+        port_bindings: req.port_bindings,
+        cpu_set: req.cpu_set,
+        numa_node: req.numa_node,
+        disk_io_mbps: req.disk_io_mbps,
+        egress_limit_mbps: req.egress_limit_mbps,
+        input: req.input,
+        input_stdin: req.input_stdin,
+        output_path: (!req.output_path.is_empty()).then_some(req.output_path),
+        payload_digest: (!req.payload_digest.is_empty()).then_some(req.payload_digest),
+        command: (!req.command.is_empty()).then_some(req.command),
+        env: req.environment,
+        pre_start_hook: req.pre_start_hook
+            .and_then(|hook| LifecycleHook::new(hook.image, hook.command, hook.timeout_secs)),
+        post_complete_hook: req.post_complete_hook
+            .and_then(|hook| LifecycleHook::new(hook.image, hook.command, hook.timeout_secs)),
+        datasets: req.datasets,
+        dataset_dir: None,
+    }
+}
+
+/// Refuses control calls that do not carry the worker control token
+#[derive(Clone)]
+pub struct ControlAuth {
+    token: Arc<str>,
+}
+
+impl ControlAuth {
+    pub fn new(token: &str) -> Self {
+        Self { token: token.trim().into() }
+    }
+
+    /// Whether `metadata` carries the token
+    pub fn accepts(&self, metadata: &MetadataMap) -> bool {
+        let presented = metadata.get("authorization")
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .map(str::trim);
+        !self.token.is_empty() && presented.is_some_and(|presented| constant_time_eq(presented.as_bytes(), self.token.as_bytes()))
+    }
+
+    /// Whether `metadata` carries a fresh scheduler grant to exec into `job_id`
+    pub fn grants_exec(&self, metadata: &MetadataMap, job_id: &str) -> bool {
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs() as i64)
+            .unwrap_or(0);
+        metadata.get(exec::EXEC_GRANT_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|grant| exec::verify_grant(&self.token, job_id, grant, now))
+    }
+}
+
+impl Interceptor for ControlAuth {
+    fn call(&mut self, request: Request<()>) -> Result<Request<()>, Status> {
+        if !self.accepts(request.metadata()) {
+            return Err(Status::unauthenticated("Worker control token required"));
+        }
+        Ok(request)
+    }
+}
+
+/// Compare secrets without revealing where they differ
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+/// gRPC WorkerService backed by the job runner
+pub struct ControlServer {
+    runner: JobRunner,
+    auth: ControlAuth,
+}
+
+impl ControlServer {
+    pub fn new(runner: JobRunner, auth: ControlAuth) -> Self {
+        Self { runner, auth }
+    }
+}
+
+#[tonic::async_trait]
+impl WorkerService for ControlServer {
+    async fn list_running_jobs(
+        &self,
+        _request: Request<ListRunningJobsRequest>,
+    ) -> Result<Response<ListRunningJobsResponse>, Status> {
+        let job_ids = self.runner
+            .running_jobs()
+            .await
+            .map_err(|e| Status::unavailable(format!("{:#}", e)))?;
+        Ok(Response::new(ListRunningJobsResponse { job_ids }))
+    }
+
+    async fn execute_job(
+        &self,
+        request: Request<ExecuteJobRequest>,
+    ) -> Result<Response<ExecuteJobResponse>, Status> {
+        let req = request.into_inner();
+        if req.job_id.is_empty() || req.container_image.is_empty() {
+            return Err(Status::invalid_argument("job_id and container_image are required"));
+        }
+        let job_id = req.job_id.clone();
+        info!("Scheduler pushed job {}", job_id);
+
+        let message = if self.runner.start(job_execution(req)) {
+            format!("Job {} started", job_id)
+        } else {
+            format!("Job {} is already running", job_id)
+        };
+        Ok(Response::new(ExecuteJobResponse { accepted: true, message }))
+    }
+
+    async fn cancel_job(
+        &self,
+        request: Request<CancelJobRequest>,
+    ) -> Result<Response<CancelJobResponse>, Status> {
+        let req = request.into_inner();
+        info!("Scheduler cancelled job {}", req.job_id);
+        let cancelled = self.runner
+            .stop(&req.job_id, req.grace_secs.max(0), req.crash)
+            .await
+            .map_err(|e| Status::internal(format!("{:#}", e)))?;
+        Ok(Response::new(CancelJobResponse { cancelled }))
+    }
+
+    type ExecStream = ReceiverStream<Result<ExecResponse, Status>>;
+
+    async fn exec(
+        &self,
+        request: Request<Streaming<ExecRequest>>,
+    ) -> Result<Response<Self::ExecStream>, Status> {
+        let metadata = request.metadata().clone();
+        let mut inbound = request.into_inner();
+        let start = match inbound.message().await? {
+            Some(ExecRequest { body: Some(exec_request::Body::Start(start)) })
+                if !start.job_id.is_empty() && !start.command.is_empty() => start,
+            _ => return Err(Status::invalid_argument("Exec must start with a job id and command")),
+        };
+        // The control token alone does not allow exec: the scheduler must
+        // have authorized an operator for this job
+        if !self.auth.grants_exec(&metadata, &start.job_id) {
+            warn!("Refused exec into job {} without a scheduler grant", start.job_id);
+            return Err(Status::permission_denied("Exec needs a scheduler grant for the job"));
+        }
+        Ok(Response::new(self.runner.exec(start, inbound)))
+    }
+
+    async fn ping(&self, _request: Request<PingRequest>) -> Result<Response<PingResponse>, Status> {
+        Ok(Response::new(PingResponse {
+            node_id: self.runner.node_id.clone(),
+            running_jobs: self.runner.active_jobs() as u32,
+            timestamp: std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap()
+                .as_secs() as i64,
+        }))
+    }
+}
+
+/// Serve the control service on `addr` to callers `auth` accepts until the
+/// process exits
+pub async fn serve(addr: SocketAddr, runner: JobRunner, auth: ControlAuth) -> Result<()> {
+    info!("Starting control service on {}", addr);
+
+    Server::builder()
+        .add_service(WorkerServiceServer::with_interceptor(ControlServer::new(runner, auth.clone()), auth))
+        .serve(addr)
+        .await
+        .context("Control service failed")?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runtime::mock::MockRuntime;
+    use crate::scheduler_client::mock::MockScheduler;
+
+    #[test]
+    fn test_active_jobs_dedup_and_cancel() {
+        let active = ActiveJobs::default();
+        assert!(active.claim("job-1"));
+        assert!(!active.claim("job-1"));
+        assert!(!active.cancel("job-2"));
+        assert!(active.cancel("job-1"));
+        assert_eq!(active.count(), 1);
+
+        assert!(active.release("job-1"));
+        assert_eq!(active.count(), 0);
+        assert!(active.claim("job-1"));
+        assert!(!active.release("job-1"));
+    }
+
+    #[test]
+    fn test_control_calls_need_the_token() {
+        let auth = ControlAuth::new("s3cret");
+        let mut metadata = MetadataMap::new();
+        assert!(!auth.accepts(&metadata));
+        metadata.insert("authorization", "Bearer wrong".parse().unwrap());
+        assert!(!auth.accepts(&metadata));
+        metadata.insert("authorization", "Bearer s3cret".parse().unwrap());
+        assert!(auth.accepts(&metadata));
+
+        // An empty token accepts nobody
+        let mut metadata = MetadataMap::new();
+        metadata.insert("authorization", "Bearer ".parse().unwrap());
+        assert!(!ControlAuth::new("").accepts(&metadata));
+    }
+
+    #[test]
+    fn test_exec_grants_are_bound_to_job_and_time() {
+        // Grant as the scheduler signs it for job-1 at t=1000
+        let mut mac = <hmac::Hmac<sha2::Sha256> as hmac::Mac>::new_from_slice(b"s3cret").unwrap();
+        hmac::Mac::update(&mut mac, b"1000.job-1");
+        let digest: String = hmac::Mac::finalize(mac).into_bytes().iter().map(|b| format!("{:02x}", b)).collect();
+        let grant = format!("1000.{}", digest);
+
+        assert!(exec::verify_grant("s3cret", "job-1", &grant, 1030));
+        assert!(!exec::verify_grant("s3cret", "job-2", &grant, 1030));
+        assert!(!exec::verify_grant("other", "job-1", &grant, 1030));
+        assert!(!exec::verify_grant("s3cret", "job-1", &grant, 1100), "expired");
+        assert!(!exec::verify_grant("s3cret", "job-1", "1000.zz", 1000));
+    }
+
+    /// Statuses reported for `job_id` once it has finished
+    async fn reported(scheduler: &MockScheduler, job_id: &str) -> Vec<JobStatus> {
+        for _ in 0..200 {
+            let statuses: Vec<JobStatus> = scheduler.updates().iter()
+                .filter(|update| update.job_id == job_id)
+                .map(|update| update.status())
+                .collect();
+            if statuses.last().is_some_and(|status| *status != JobStatus::Running) {
+                return statuses;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        panic!("job {} never finished", job_id);
+    }
+
+    fn job(job_id: &str) -> JobExecution {
+        job_execution(ExecuteJobRequest {
+            job_id: job_id.to_string(),
+            container_image: "alpine:3.19".to_string(),
+            ..Default::default()
+        })
+    }
+
+    #[tokio::test]
+    async fn test_assignments_are_run_once_and_reported() {
+        let scheduler = MockScheduler::new(1);
+        let runtime = Arc::new(MockRuntime::holding());
+        let runner = JobRunner::with("node-1".to_string(), runtime.clone(), Arc::new(scheduler.clone()));
+
+        // A resent assignment does not start a second container
+        assert!(runner.start(job("job-1")));
+        assert!(!runner.start(job("job-1")));
+        while runtime.started().is_empty() {
+            tokio::task::yield_now().await;
+        }
+        assert_eq!(runner.running_jobs().await.unwrap(), ["job-1"]);
+
+        assert!(runner.cancel("job-1", 10).await.unwrap());
+        assert_eq!(reported(&scheduler, "job-1").await, [JobStatus::Running, JobStatus::Cancelled]);
+        assert!(!runner.cancel("job-1", 10).await.unwrap());
+        assert_eq!(runtime.started(), ["job-1"]);
+
+        // Killed rather than cancelled: reported failed, as after a crash
+        assert!(runner.start(job("job-2")));
+        while runtime.started().len() < 2 {
+            tokio::task::yield_now().await;
+        }
+        assert!(runner.stop("job-2", 10, true).await.unwrap());
+        assert_eq!(reported(&scheduler, "job-2").await, [JobStatus::Running, JobStatus::Failed]);
+        assert_eq!(runner.active_jobs(), 0);
+
+        // A container exiting non-zero fails its job
+        let runtime = Arc::new(MockRuntime::default().exit_code("job-3", 3));
+        let runner = JobRunner::with("node-1".to_string(), runtime, Arc::new(scheduler.clone()))
+            .with_fencing_token(Arc::new(AtomicU64::new(4)));
+        assert!(runner.start(job("job-3")));
+        assert_eq!(reported(&scheduler, "job-3").await, [JobStatus::Running, JobStatus::Failed]);
+        let update = scheduler.updates().into_iter().find(|update| update.job_id == "job-3" && update.exit_code != 0);
+        assert_eq!(update.as_ref().map(|update| update.exit_code), Some(3));
+        // Sent as this registration, so a superseded process is fenced off
+        assert_eq!(update.map(|update| (update.node_id, update.fencing_token)), Some(("node-1".to_string(), 4)));
+    }
+}
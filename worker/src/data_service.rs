@@ -0,0 +1,883 @@
+//! Worker Data Service - peer-to-peer dataset transfer
+//!
+//! Serves locally held datasets (subdirectories of the data directory) to
+//! other workers and pulls missing datasets directly from peers, so input
+//! data and checkpoints never hairpin through the scheduler. Progress and
+//! completed transfers are reported to the scheduler for bandwidth
+//! accounting.
+//!
+//! When the requester accepts it, the sender compresses a transfer with
+//! zstd if a sample of the dataset shrinks by at least
+//! `MIN_COMPRESSION_SAVING` (the threshold the scheduler's cost model
+//! assumes); incompressible data such as images or archives goes as is.
+//! Chunks that do not shrink are sent raw either way. Reports carry the
+//! bytes that crossed the network so the scheduler learns each dataset's
+//! compressibility.
+//!
+//! A worker that already holds an older copy of a dataset re-stages it by
+//! delta sync (see `delta`): only chunks missing from its copy are fetched,
+//! and the bytes reused are reported so the scheduler can price the next
+//! delta sync of that dataset.
+//!
+//! Jobs name their input datasets; the `DatasetStager` pulls those not held
+//! here before the job starts and they are mounted read-only at
+//! `/data/<name>`. Peers must present the data token (TGP_DATA_TOKEN, shared
+//! by the workers of a cluster) as a bearer token.
+
+use anyhow::{Context, Result};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::metadata::{Ascii, MetadataValue};
+use tonic::service::interceptor::InterceptedService;
+use tonic::service::Interceptor;
+use tonic::transport::{Channel, Endpoint, Server};
+use tonic::{Request, Response, Status};
+use tracing::{info, warn};
+
+use crate::control::ControlAuth;
+use crate::delta;
+use crate::proto::{
+    scheduler_service_client::SchedulerServiceClient, DatasetSourcesRequest, TransferReport,
+};
+
+// Include generated data service code
+pub mod data_proto {
+    tonic::include_proto!("tgp.data.v1");
+}
+
+use data_proto::{
+    data_service_client::DataServiceClient,
+    data_service_server::{DataService, DataServiceServer},
+    ChunkRef, DataChunk, FetchChunksRequest, FetchDatasetRequest, FileManifest,
+};
+
+/// Size of each streamed chunk
+const CHUNK_SIZE: usize = 1024 * 1024;
+
+/// Minimum time between progress reports to the scheduler
+const PROGRESS_REPORT_INTERVAL: Duration = Duration::from_secs(2);
+
+/// zstd level: fast enough to keep up with a gigabit link on one core
+const COMPRESSION_LEVEL: i32 = 3;
+
+/// Bytes sampled from the start of each file to measure compressibility
+const SAMPLE_BYTES_PER_FILE: usize = 64 * 1024;
+
+/// Most bytes sampled per transfer
+const MAX_SAMPLE_BYTES: usize = 1024 * 1024;
+
+/// Smallest saving worth the CPU time; matches the scheduler's cost model
+const MIN_COMPRESSION_SAVING: f64 = 0.1;
+
+/// Chunk refs per manifest message
+const MANIFEST_CHUNKS_PER_MESSAGE: usize = 8192;
+
+/// Chunk ranges per `FetchChunks` request
+const MAX_RANGES_PER_REQUEST: usize = 4096;
+
+/// Time allowed to connect to a peer's data service
+const PEER_CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Where staged datasets are mounted in job containers
+pub const CONTAINER_DATA_DIR: &str = "/data";
+
+/// Dataset names are single directory names inside the data directory
+fn is_valid_dataset_name(name: &str) -> bool {
+    let mut components = Path::new(name).components();
+    matches!((components.next(), components.next()), (Some(Component::Normal(_)), None)) && !name.starts_with('.')
+}
+
+/// Relative file paths received from peers must stay inside the dataset root
+fn is_safe_relative_path(path: &str) -> bool {
+    !path.is_empty() && Path::new(path).components().all(|c| matches!(c, Component::Normal(_)))
+}
+
+/// All regular files below `root`, recursively
+pub(crate) fn list_files(root: &Path) -> std::io::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    let mut pending = vec![root.to_path_buf()];
+
+    while let Some(dir) = pending.pop() {
+        for entry in std::fs::read_dir(&dir)? {
+            let entry = entry?;
+            let file_type = entry.file_type()?;
+            if file_type.is_dir() {
+                pending.push(entry.path());
+            } else if file_type.is_file() {
+                files.push(entry.path());
+            }
+        }
+    }
+
+    files.sort();
+    Ok(files)
+}
+
+/// Compressed size over raw size of a sample taken from the start of `files`
+fn sample_ratio(files: &[PathBuf]) -> std::io::Result<f64> {
+    use std::io::Read;
+
+    let mut sample = Vec::new();
+    for path in files {
+        if sample.len() >= MAX_SAMPLE_BYTES {
+            break;
+        }
+        let limit = SAMPLE_BYTES_PER_FILE.min(MAX_SAMPLE_BYTES - sample.len());
+        std::fs::File::open(path)?.take(limit as u64).read_to_end(&mut sample)?;
+    }
+    if sample.is_empty() {
+        return Ok(1.0);
+    }
+    let compressed = zstd::bulk::compress(&sample, COMPRESSION_LEVEL)?;
+    Ok(compressed.len() as f64 / sample.len() as f64)
+}
+
+/// Whether a transfer is worth compressing
+fn should_compress(files: &[PathBuf]) -> bool {
+    match sample_ratio(files) {
+        Ok(ratio) => ratio <= 1.0 - MIN_COMPRESSION_SAVING,
+        Err(e) => {
+            warn!("Failed to sample dataset for compression: {}", e);
+            false
+        }
+    }
+}
+
+/// Chunk payload: the zstd frame if it is smaller, else the raw bytes
+fn encode_chunk(data: &[u8], compress: bool) -> (Vec<u8>, bool) {
+    if compress {
+        if let Ok(compressed) = zstd::bulk::compress(data, COMPRESSION_LEVEL) {
+            if compressed.len() < data.len() {
+                return (compressed, true);
+            }
+        }
+    }
+    (data.to_vec(), false)
+}
+
+/// Raw bytes of a received chunk
+fn decode_chunk(chunk: &DataChunk) -> Result<Vec<u8>> {
+    if !chunk.compressed {
+        return Ok(chunk.data.clone());
+    }
+    // Chunks are never larger than CHUNK_SIZE uncompressed
+    zstd::bulk::decompress(&chunk.data, CHUNK_SIZE)
+        .with_context(|| format!("Corrupt compressed chunk of {}", chunk.relative_path))
+}
+
+/// gRPC DataService serving datasets from the local data directory
+pub struct DataServer {
+    data_dir: PathBuf,
+}
+
+impl DataServer {
+    pub fn new(data_dir: PathBuf) -> Self {
+        Self { data_dir }
+    }
+
+    /// Directory of a dataset held locally (None for invalid names too)
+    fn dataset_root(&self, dataset: &str) -> Option<PathBuf> {
+        let root = self.data_dir.join(dataset);
+        (is_valid_dataset_name(dataset) && root.is_dir()).then_some(root)
+    }
+}
+
+fn not_held(dataset: &str) -> Status {
+    Status::not_found(format!("Dataset {} not held locally", dataset))
+}
+
+#[tonic::async_trait]
+impl DataService for DataServer {
+    type FetchDatasetStream = ReceiverStream<Result<DataChunk, Status>>;
+
+    async fn fetch_dataset(
+        &self,
+        request: Request<FetchDatasetRequest>,
+    ) -> Result<Response<Self::FetchDatasetStream>, Status> {
+        let req = request.into_inner();
+        let root = self.dataset_root(&req.dataset).ok_or_else(|| not_held(&req.dataset))?;
+
+        let files = list_files(&root)
+            .map_err(|e| Status::internal(format!("Failed to list dataset: {}", e)))?;
+        let total_bytes: u64 = files.iter()
+            .filter_map(|f| std::fs::metadata(f).ok())
+            .map(|m| m.len())
+            .sum();
+
+        let compress = if req.accept_compression {
+            let sample_files = files.clone();
+            tokio::task::spawn_blocking(move || should_compress(&sample_files))
+                .await
+                .unwrap_or(false)
+        } else {
+            false
+        };
+
+        info!(
+            "Serving dataset {} to {} ({} files, {} bytes, compressed: {})",
+            req.dataset, req.requester_node_id, files.len(), total_bytes, compress
+        );
+
+        let (tx, rx) = mpsc::channel(4);
+        tokio::spawn(async move {
+            let mut buf = vec![0u8; CHUNK_SIZE];
+
+            for path in files {
+                let relative_path = path.strip_prefix(&root)
+                    .map(|p| p.to_string_lossy().into_owned())
+                    .unwrap_or_default();
+
+                let mut file = match tokio::fs::File::open(&path).await {
+                    Ok(file) => file,
+                    Err(e) => {
+                        let _ = tx.send(Err(Status::internal(format!("Failed to open {}: {}", relative_path, e)))).await;
+                        return;
+                    }
+                };
+
+                let mut offset = 0u64;
+                loop {
+                    let n = match file.read(&mut buf).await {
+                        Ok(0) => break,
+                        Ok(n) => n,
+                        Err(e) => {
+                            let _ = tx.send(Err(Status::internal(format!("Failed to read {}: {}", relative_path, e)))).await;
+                            return;
+                        }
+                    };
+
+                    let (data, compressed) = encode_chunk(&buf[..n], compress);
+                    let chunk = DataChunk {
+                        relative_path: relative_path.clone(),
+                        offset,
+                        data,
+                        total_bytes,
+                        compressed,
+                    };
+                    if tx.send(Ok(chunk)).await.is_err() {
+                        return; // Peer went away
+                    }
+                    offset += n as u64;
+                }
+            }
+        });
+
+        Ok(Response::new(ReceiverStream::new(rx)))
+    }
+
+    type GetDatasetManifestStream = ReceiverStream<Result<FileManifest, Status>>;
+
+    async fn get_dataset_manifest(
+        &self,
+        request: Request<FetchDatasetRequest>,
+    ) -> Result<Response<Self::GetDatasetManifestStream>, Status> {
+        let req = request.into_inner();
+        let root = self.dataset_root(&req.dataset).ok_or_else(|| not_held(&req.dataset))?;
+
+        let files = tokio::task::spawn_blocking(move || delta::manifest(&root))
+            .await
+            .map_err(|e| Status::internal(format!("Indexing dataset failed: {}", e)))?
+            .map_err(|e| Status::internal(format!("Failed to index dataset: {}", e)))?;
+        info!("Sending manifest of {} to {} ({} files)", req.dataset, req.requester_node_id, files.len());
+
+        let (tx, rx) = mpsc::channel(4);
+        tokio::spawn(async move {
+            for file in files {
+                let parts: Vec<&[ChunkRef]> = if file.chunks.is_empty() {
+                    vec![&[]]
+                } else {
+                    file.chunks.chunks(MANIFEST_CHUNKS_PER_MESSAGE).collect()
+                };
+                for part in parts {
+                    let message = FileManifest {
+                        relative_path: file.relative_path.clone(),
+                        size: file.size,
+                        chunks: part.to_vec(),
+                    };
+                    if tx.send(Ok(message)).await.is_err() {
+                        return;
+                    }
+                }
+            }
+        });
+
+        Ok(Response::new(ReceiverStream::new(rx)))
+    }
+
+    type FetchChunksStream = ReceiverStream<Result<DataChunk, Status>>;
+
+    async fn fetch_chunks(
+        &self,
+        request: Request<FetchChunksRequest>,
+    ) -> Result<Response<Self::FetchChunksStream>, Status> {
+        let req = request.into_inner();
+        let root = self.dataset_root(&req.dataset).ok_or_else(|| not_held(&req.dataset))?;
+        if req.ranges.len() > MAX_RANGES_PER_REQUEST {
+            return Err(Status::invalid_argument(format!(
+                "At most {} chunks per request", MAX_RANGES_PER_REQUEST
+            )));
+        }
+        if let Some(range) = req.ranges.iter().find(|r| {
+            !is_safe_relative_path(&r.relative_path) || r.length as usize > delta::MAX_CHUNK
+        }) {
+            return Err(Status::invalid_argument(format!(
+                "Invalid chunk {} at {}", range.relative_path, range.offset
+            )));
+        }
+        let total_bytes: u64 = req.ranges.iter().map(|r| r.length as u64).sum();
+
+        let compress = if req.accept_compression {
+            let mut sample_files: Vec<PathBuf> = req.ranges.iter().map(|r| root.join(&r.relative_path)).collect();
+            sample_files.dedup();
+            tokio::task::spawn_blocking(move || should_compress(&sample_files))
+                .await
+                .unwrap_or(false)
+        } else {
+            false
+        };
+
+        info!(
+            "Serving {} chunks of {} to {} ({} bytes, compressed: {})",
+            req.ranges.len(), req.dataset, req.requester_node_id, total_bytes, compress
+        );
+
+        let (tx, rx) = mpsc::channel(4);
+        tokio::spawn(async move {
+            let mut current: Option<(String, tokio::fs::File)> = None;
+
+            for range in req.ranges {
+                if current.as_ref().map(|(path, _)| path != &range.relative_path).unwrap_or(true) {
+                    match tokio::fs::File::open(root.join(&range.relative_path)).await {
+                        Ok(file) => current = Some((range.relative_path.clone(), file)),
+                        Err(e) => {
+                            let _ = tx.send(Err(Status::not_found(format!("Failed to open {}: {}", range.relative_path, e)))).await;
+                            return;
+                        }
+                    }
+                }
+                let Some((_, file)) = current.as_mut() else {
+                    return;
+                };
+
+                let mut buf = vec![0u8; range.length as usize];
+                let read = async {
+                    file.seek(std::io::SeekFrom::Start(range.offset)).await?;
+                    file.read_exact(&mut buf).await
+                }.await;
+                if let Err(e) = read {
+                    let _ = tx.send(Err(Status::out_of_range(format!(
+                        "Failed to read {} at {}: {}", range.relative_path, range.offset, e
+                    )))).await;
+                    return;
+                }
+
+                let (data, compressed) = encode_chunk(&buf, compress);
+                let chunk = DataChunk {
+                    relative_path: range.relative_path,
+                    offset: range.offset,
+                    data,
+                    total_bytes,
+                    compressed,
+                };
+                if tx.send(Ok(chunk)).await.is_err() {
+                    return;
+                }
+            }
+        });
+
+        Ok(Response::new(ReceiverStream::new(rx)))
+    }
+}
+
+/// The data token workers present to each other's data services
+#[derive(Clone)]
+pub struct PeerAuth {
+    check: ControlAuth,
+    header: MetadataValue<Ascii>,
+}
+
+impl PeerAuth {
+    pub fn new(token: &str) -> Result<Self> {
+        anyhow::ensure!(!token.trim().is_empty(), "Data token is empty");
+        let header = format!("Bearer {}", token.trim())
+            .parse()
+            .context("Data token is not a valid header value")?;
+        Ok(Self { check: ControlAuth::new(token), header })
+    }
+
+    /// Whether `metadata` carries the token
+    fn accepts(&self, metadata: &tonic::metadata::MetadataMap) -> bool {
+        self.check.accepts(metadata)
+    }
+}
+
+/// Presents the token on calls to peers
+impl Interceptor for PeerAuth {
+    fn call(&mut self, mut request: Request<()>) -> Result<Request<()>, Status> {
+        request.metadata_mut().insert("authorization", self.header.clone());
+        Ok(request)
+    }
+}
+
+/// Refuses calls from peers without the token
+#[derive(Clone)]
+struct PeerCheck(PeerAuth);
+
+impl Interceptor for PeerCheck {
+    fn call(&mut self, request: Request<()>) -> Result<Request<()>, Status> {
+        if !self.0.accepts(request.metadata()) {
+            return Err(Status::unauthenticated("Data token required"));
+        }
+        Ok(request)
+    }
+}
+
+type PeerClient = DataServiceClient<InterceptedService<Channel, PeerAuth>>;
+
+/// Run the data service for peers holding the token until the process exits
+pub async fn serve(data_dir: PathBuf, addr: SocketAddr, auth: PeerAuth) -> Result<()> {
+    info!("Starting data service on {} (serving {})", addr, data_dir.display());
+
+    Server::builder()
+        .add_service(DataServiceServer::with_interceptor(DataServer::new(data_dir), PeerCheck(auth)))
+        .serve(addr)
+        .await
+        .context("Data service failed")?;
+
+    Ok(())
+}
+
+/// Stages jobs' input datasets into the data directory
+#[derive(Clone)]
+pub struct DatasetStager {
+    data_dir: PathBuf,
+    /// None: datasets not held here cannot be pulled
+    auth: Option<PeerAuth>,
+    /// One pull at a time, so jobs sharing a dataset do not both fetch it
+    pulling: Arc<tokio::sync::Mutex<()>>,
+}
+
+impl DatasetStager {
+    pub fn new(data_dir: PathBuf, auth: Option<PeerAuth>) -> Self {
+        Self { data_dir, auth, pulling: Arc::default() }
+    }
+
+    pub fn data_dir(&self) -> &Path {
+        &self.data_dir
+    }
+
+    /// Pull each of `datasets` not held locally from a peer
+    pub async fn stage(
+        &self,
+        scheduler: &mut SchedulerServiceClient<Channel>,
+        node_id: &str,
+        datasets: &[String],
+    ) -> Result<()> {
+        let _pulling = self.pulling.lock().await;
+        for dataset in datasets {
+            if self.data_dir.join(dataset).is_dir() && is_valid_dataset_name(dataset) {
+                continue;
+            }
+            let auth = self.auth.as_ref().with_context(|| {
+                format!("Dataset {} is not held here and TGP_DATA_TOKEN is not set to pull it", dataset)
+            })?;
+            fetch_from_peers(scheduler, node_id, &self.data_dir, dataset, auth).await?;
+        }
+        Ok(())
+    }
+}
+
+/// Pull a dataset from the best available peer into `data_dir`
+///
+/// Returns the number of bytes transferred.
+pub async fn fetch_from_peers(
+    scheduler: &mut SchedulerServiceClient<Channel>,
+    node_id: &str,
+    data_dir: &Path,
+    dataset: &str,
+    auth: &PeerAuth,
+) -> Result<u64> {
+    anyhow::ensure!(is_valid_dataset_name(dataset), "Invalid dataset name: {}", dataset);
+
+    let sources = scheduler
+        .get_dataset_sources(Request::new(DatasetSourcesRequest {
+            dataset: dataset.to_string(),
+            requester_node_id: node_id.to_string(),
+        }))
+        .await
+        .context("Failed to look up dataset sources")?
+        .into_inner()
+        .sources;
+
+    for source in sources {
+        let transfer = Transfer {
+            transfer_id: format!(
+                "{}-{}-{}",
+                dataset,
+                node_id,
+                std::time::SystemTime::now()
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .unwrap()
+                    .as_millis()
+            ),
+            dataset: dataset.to_string(),
+            source_node_id: source.node_id.clone(),
+            dest_node_id: node_id.to_string(),
+            started: Instant::now(),
+        };
+
+        match pull_dataset(scheduler, &transfer, &source.data_service_addr, data_dir, auth).await {
+            Ok(moved) => {
+                transfer.report(scheduler, moved, moved.bytes + moved.reused_bytes, true, String::new()).await;
+                info!(
+                    "Fetched dataset {} from {} ({} bytes, {} on the wire, {} reused)",
+                    dataset, source.node_id, moved.bytes, moved.wire_bytes, moved.reused_bytes
+                );
+                return Ok(moved.bytes);
+            }
+            Err(e) => {
+                warn!("Fetching {} from {} failed: {}", dataset, source.node_id, e);
+                transfer.report(scheduler, Moved::default(), 0, false, e.to_string()).await;
+            }
+        }
+    }
+
+    anyhow::bail!("No peer could serve dataset {}", dataset)
+}
+
+/// Bytes a transfer has moved
+#[derive(Debug, Clone, Copy, Default)]
+struct Moved {
+    /// Dataset bytes written
+    bytes: u64,
+    /// Bytes received over the network
+    wire_bytes: u64,
+    /// Synced against an older local copy
+    delta_sync: bool,
+    /// Bytes copied from that older copy
+    reused_bytes: u64,
+}
+
+/// Bookkeeping for one node-to-node transfer
+struct Transfer {
+    transfer_id: String,
+    dataset: String,
+    source_node_id: String,
+    dest_node_id: String,
+    started: Instant,
+}
+
+impl Transfer {
+    /// Report progress to the scheduler; failures are logged, not fatal
+    async fn report(
+        &self,
+        scheduler: &mut SchedulerServiceClient<Channel>,
+        moved: Moved,
+        total_bytes: u64,
+        completed: bool,
+        error_message: String,
+    ) {
+        let report = TransferReport {
+            transfer_id: self.transfer_id.clone(),
+            dataset: self.dataset.clone(),
+            source_node_id: self.source_node_id.clone(),
+            dest_node_id: self.dest_node_id.clone(),
+            bytes_transferred: moved.bytes,
+            total_bytes,
+            elapsed_ms: self.started.elapsed().as_millis() as u64,
+            completed,
+            error_message,
+            wire_bytes: moved.wire_bytes,
+            delta_sync: moved.delta_sync,
+            reused_bytes: moved.reused_bytes,
+        };
+
+        if let Err(e) = scheduler.report_transfer(Request::new(report)).await {
+            warn!("Failed to report transfer {}: {}", self.transfer_id, e);
+        }
+    }
+}
+
+/// Stream a dataset from one peer into a staging directory, then move it in place
+///
+/// An older local copy is synced by delta; if that fails the whole
+/// dataset is fetched instead.
+async fn pull_dataset(
+    scheduler: &mut SchedulerServiceClient<Channel>,
+    transfer: &Transfer,
+    peer_addr: &str,
+    data_dir: &Path,
+    auth: &PeerAuth,
+) -> Result<Moved> {
+    let channel = Endpoint::from_shared(peer_addr.to_string())
+        .context("Invalid peer data service address")?
+        .connect_timeout(PEER_CONNECT_TIMEOUT)
+        .connect()
+        .await
+        .context("Failed to connect to peer data service")?;
+    let mut peer = DataServiceClient::with_interceptor(channel, auth.clone());
+
+    let target = data_dir.join(&transfer.dataset);
+    let staging = data_dir.join(format!(".incoming-{}", transfer.dataset));
+
+    let mut moved = None;
+    if target.is_dir() {
+        prepare_staging(&staging).await?;
+        match pull_delta(scheduler, transfer, &mut peer, &target, &staging).await {
+            Ok(delta) => moved = Some(delta),
+            Err(e) => warn!("Delta sync of {} failed, fetching it whole: {:#}", transfer.dataset, e),
+        }
+    }
+    let moved = match moved {
+        Some(moved) => moved,
+        None => {
+            prepare_staging(&staging).await?;
+            pull_whole(scheduler, transfer, &mut peer, &staging).await?
+        }
+    };
+
+    if target.exists() {
+        tokio::fs::remove_dir_all(&target).await.context("Failed to replace old dataset copy")?;
+    }
+    tokio::fs::rename(&staging, &target).await.context("Failed to move dataset into place")?;
+
+    Ok(moved)
+}
+
+/// Empty staging directory for an incoming dataset
+async fn prepare_staging(staging: &Path) -> Result<()> {
+    let _ = tokio::fs::remove_dir_all(staging).await;
+    tokio::fs::create_dir_all(staging).await.context("Failed to create staging directory")
+}
+
+/// Stream every file of a dataset into `staging`
+async fn pull_whole(
+    scheduler: &mut SchedulerServiceClient<Channel>,
+    transfer: &Transfer,
+    peer: &mut PeerClient,
+    staging: &Path,
+) -> Result<Moved> {
+    let mut stream = peer
+        .fetch_dataset(Request::new(FetchDatasetRequest {
+            dataset: transfer.dataset.clone(),
+            requester_node_id: transfer.dest_node_id.clone(),
+            accept_compression: true,
+        }))
+        .await
+        .context("Peer rejected dataset request")?
+        .into_inner();
+
+    let mut moved = Moved::default();
+    let mut last_report = Instant::now();
+    let mut writer = ChunkWriter::new(staging);
+
+    while let Some(chunk) = stream.message().await.context("Transfer interrupted")? {
+        let data = decode_chunk(&chunk)?;
+        writer.write(&chunk.relative_path, chunk.offset, &data).await?;
+
+        moved.bytes += data.len() as u64;
+        moved.wire_bytes += chunk.data.len() as u64;
+
+        if last_report.elapsed() >= PROGRESS_REPORT_INTERVAL {
+            transfer.report(scheduler, moved, chunk.total_bytes, false, String::new()).await;
+            last_report = Instant::now();
+        }
+    }
+
+    writer.finish().await?;
+    Ok(moved)
+}
+
+/// Rebuild a dataset in `staging` from the chunks of the older copy at
+/// `previous` plus the chunks it lacks, fetched from the peer
+async fn pull_delta(
+    scheduler: &mut SchedulerServiceClient<Channel>,
+    transfer: &Transfer,
+    peer: &mut PeerClient,
+    previous: &Path,
+    staging: &Path,
+) -> Result<Moved> {
+    let mut stream = peer
+        .get_dataset_manifest(Request::new(FetchDatasetRequest {
+            dataset: transfer.dataset.clone(),
+            requester_node_id: transfer.dest_node_id.clone(),
+            accept_compression: true,
+        }))
+        .await
+        .context("Peer cannot list dataset chunks")?
+        .into_inner();
+
+    let mut files: Vec<FileManifest> = Vec::new();
+    while let Some(part) = stream.message().await.context("Manifest interrupted")? {
+        if !is_safe_relative_path(&part.relative_path) {
+            anyhow::bail!("Peer sent unsafe path: {}", part.relative_path);
+        }
+        match files.last_mut() {
+            Some(file) if file.relative_path == part.relative_path => file.chunks.extend(part.chunks),
+            _ => files.push(part),
+        }
+    }
+    let expected: HashMap<(String, u64), Vec<u8>> = files.iter()
+        .flat_map(|file| file.chunks.iter().map(|c| ((file.relative_path.clone(), c.offset), c.hash.clone())))
+        .collect();
+    let total_bytes: u64 = files.iter().map(|file| file.size).sum();
+
+    let (old_copy, stage) = (previous.to_path_buf(), staging.to_path_buf());
+    let (reused_bytes, wanted) = tokio::task::spawn_blocking(move || {
+        let index = delta::ChunkIndex::build(&old_copy)?;
+        delta::reuse_chunks(&index, &files, &stage)
+    })
+    .await
+    .context("Reusing local chunks failed")??;
+    info!(
+        "Delta sync of {}: {} bytes reused, {} chunks to fetch",
+        transfer.dataset, reused_bytes, wanted.len()
+    );
+
+    let mut moved = Moved { delta_sync: true, reused_bytes, ..Default::default() };
+    let mut last_report = Instant::now();
+    let mut writer = ChunkWriter::new(staging);
+    let mut received = 0usize;
+
+    for batch in wanted.chunks(MAX_RANGES_PER_REQUEST) {
+        let mut stream = peer
+            .fetch_chunks(Request::new(FetchChunksRequest {
+                dataset: transfer.dataset.clone(),
+                requester_node_id: transfer.dest_node_id.clone(),
+                accept_compression: true,
+                ranges: batch.to_vec(),
+            }))
+            .await
+            .context("Peer rejected chunk request")?
+            .into_inner();
+
+        while let Some(chunk) = stream.message().await.context("Transfer interrupted")? {
+            let data = decode_chunk(&chunk)?;
+            let hash = expected.get(&(chunk.relative_path.clone(), chunk.offset))
+                .with_context(|| format!("Peer sent an unrequested chunk of {}", chunk.relative_path))?;
+            if Sha256::digest(&data).as_slice() != hash.as_slice() {
+                anyhow::bail!("Chunk of {} at {} failed its integrity check", chunk.relative_path, chunk.offset);
+            }
+            writer.write(&chunk.relative_path, chunk.offset, &data).await?;
+
+            moved.bytes += data.len() as u64;
+            moved.wire_bytes += chunk.data.len() as u64;
+            received += 1;
+
+            if last_report.elapsed() >= PROGRESS_REPORT_INTERVAL {
+                transfer.report(scheduler, moved, total_bytes, false, String::new()).await;
+                last_report = Instant::now();
+            }
+        }
+    }
+
+    writer.finish().await?;
+    if received != wanted.len() {
+        anyhow::bail!("Peer sent {} of {} chunks", received, wanted.len());
+    }
+    Ok(moved)
+}
+
+/// Writes received chunks into the files of a staging directory, keeping
+/// the file last written to open
+struct ChunkWriter<'a> {
+    staging: &'a Path,
+    current: Option<(String, tokio::fs::File)>,
+}
+
+impl<'a> ChunkWriter<'a> {
+    fn new(staging: &'a Path) -> Self {
+        Self { staging, current: None }
+    }
+
+    async fn write(&mut self, relative_path: &str, offset: u64, data: &[u8]) -> Result<()> {
+        if !is_safe_relative_path(relative_path) {
+            anyhow::bail!("Peer sent unsafe path: {}", relative_path);
+        }
+
+        if self.current.as_ref().map(|(path, _)| path != relative_path).unwrap_or(true) {
+            if let Some((_, mut file)) = self.current.take() {
+                file.flush().await?;
+            }
+
+            let path = self.staging.join(relative_path);
+            if let Some(parent) = path.parent() {
+                tokio::fs::create_dir_all(parent).await?;
+            }
+            let file = tokio::fs::OpenOptions::new()
+                .create(true)
+                .write(true)
+                .truncate(false)
+                .open(&path)
+                .await
+                .with_context(|| format!("Failed to create {}", path.display()))?;
+            self.current = Some((relative_path.to_string(), file));
+        }
+
+        if let Some((_, file)) = self.current.as_mut() {
+            file.seek(std::io::SeekFrom::Start(offset)).await?;
+            file.write_all(data).await?;
+        }
+        Ok(())
+    }
+
+    async fn finish(mut self) -> Result<()> {
+        if let Some((_, mut file)) = self.current.take() {
+            file.flush().await?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dataset_name_validation() {
+        assert!(is_valid_dataset_name("imagenet"));
+        assert!(!is_valid_dataset_name("../etc"));
+        assert!(!is_valid_dataset_name("a/b"));
+        assert!(!is_valid_dataset_name(".incoming-x"));
+        assert!(!is_valid_dataset_name(""));
+    }
+
+    #[test]
+    fn test_peers_need_the_data_token() {
+        let mut auth = PeerAuth::new("s3cret").unwrap();
+        let request = auth.call(Request::new(())).unwrap();
+        assert!(PeerCheck(auth.clone()).call(request).is_ok());
+        let request = PeerAuth::new("other").unwrap().call(Request::new(())).unwrap();
+        assert!(PeerCheck(auth.clone()).call(request).is_err());
+        assert!(PeerCheck(auth).call(Request::new(())).is_err());
+        assert!(PeerAuth::new(" ").is_err());
+    }
+
+    #[test]
+    fn test_relative_path_safety() {
+        assert!(is_safe_relative_path("train/part-0001.parquet"));
+        assert!(!is_safe_relative_path("../../etc/passwd"));
+        assert!(!is_safe_relative_path("/etc/passwd"));
+        assert!(!is_safe_relative_path(""));
+    }
+
+    #[test]
+    fn test_chunks_compress_only_when_smaller() {
+        let text = b"timestamp=1 level=info msg=ok\n".repeat(1000);
+        let (data, compressed) = encode_chunk(&text, true);
+        assert!(compressed && data.len() < text.len());
+        // Already-compressed bytes go raw
+        assert!(!encode_chunk(&data, true).1);
+        assert!(!encode_chunk(&text, false).1);
+
+        let chunk = DataChunk { relative_path: "log".to_string(), data, compressed, ..Default::default() };
+        assert_eq!(decode_chunk(&chunk).unwrap(), text);
+    }
+}
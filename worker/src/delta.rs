@@ -0,0 +1,309 @@
+//! Delta sync for dataset transfers
+//!
+//! Files are split into content-defined chunks: a gear rolling hash picks
+//! the cut points from the bytes themselves, so an insert or a rewrite only
+//! changes the chunks it touches instead of shifting every later block.
+//! Chunks are identified by their SHA-256. A worker re-staging a dataset it
+//! holds an older copy of fetches the sender's manifest, copies the chunks
+//! it already has out of the old copy and asks the peer only for the rest.
+//!
+//! Chunk lists are cached per file by size and modification time. A
+//! background indexer keeps them current so resource reports can carry a
+//! content digest of each dataset, which the scheduler uses to tell copies
+//! of the latest version from stale ones.
+
+use anyhow::{Context, Result};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fs;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, SystemTime};
+use tracing::{info, warn};
+
+use crate::data_service::data_proto::{ChunkRange, ChunkRef, FileManifest};
+use crate::data_service::list_files;
+
+/// Smallest chunk, except at the end of a file
+const MIN_CHUNK: usize = 16 * 1024;
+
+/// Largest chunk
+pub const MAX_CHUNK: usize = 256 * 1024;
+
+/// Cut where the top 16 bits of the rolling hash are zero: 64 KiB chunks
+/// on average (the top bits depend on the last 64 bytes, the low ones on
+/// only a few)
+const CUT_MASK: u64 = 0xFFFF << 48;
+
+/// How often the indexer re-reads changed datasets
+const INDEX_INTERVAL: Duration = Duration::from_secs(600);
+
+/// Random value per byte for the gear hash (splitmix64, fixed seed: every
+/// worker must cut at the same places)
+const GEAR: [u64; 256] = gear_table();
+
+const fn gear_table() -> [u64; 256] {
+    let mut table = [0u64; 256];
+    let mut state = 0x9E37_79B9_7F4A_7C15u64;
+    let mut i = 0;
+    while i < 256 {
+        state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        table[i] = z ^ (z >> 31);
+        i += 1;
+    }
+    table
+}
+
+/// Length of the chunk starting `data`; `data` holds at least `MAX_CHUNK`
+/// bytes unless it is the end of the file
+fn cut_point(data: &[u8]) -> usize {
+    if data.len() <= MIN_CHUNK {
+        return data.len();
+    }
+    let end = data.len().min(MAX_CHUNK);
+    let mut hash = 0u64;
+    for (i, byte) in data.iter().enumerate().take(end).skip(MIN_CHUNK) {
+        hash = (hash << 1).wrapping_add(GEAR[*byte as usize]);
+        if hash & CUT_MASK == 0 {
+            return i + 1;
+        }
+    }
+    end
+}
+
+/// Content-defined chunks of a readable stream
+fn chunk_reader(mut reader: impl Read) -> std::io::Result<Vec<ChunkRef>> {
+    let mut chunks = Vec::new();
+    let mut buf = Vec::with_capacity(2 * MAX_CHUNK);
+    let mut offset = 0u64;
+    let mut eof = false;
+
+    loop {
+        while !eof && buf.len() < MAX_CHUNK {
+            let filled = buf.len();
+            buf.resize(filled + MAX_CHUNK, 0);
+            let n = reader.read(&mut buf[filled..])?;
+            buf.truncate(filled + n);
+            eof = n == 0;
+        }
+        if buf.is_empty() {
+            return Ok(chunks);
+        }
+
+        let length = cut_point(&buf);
+        chunks.push(ChunkRef {
+            offset,
+            length: length as u32,
+            hash: Sha256::digest(&buf[..length]).to_vec(),
+        });
+        offset += length as u64;
+        buf.drain(..length);
+    }
+}
+
+struct CachedFile {
+    len: u64,
+    modified: SystemTime,
+    chunks: Arc<Vec<ChunkRef>>,
+}
+
+/// Chunk lists by file path, shared by the data service and the indexer
+fn cache() -> &'static Mutex<HashMap<PathBuf, CachedFile>> {
+    static CACHE: OnceLock<Mutex<HashMap<PathBuf, CachedFile>>> = OnceLock::new();
+    CACHE.get_or_init(Default::default)
+}
+
+/// Cached chunk list of `path` if the file has not changed since
+fn cached_chunks(path: &Path, meta: &fs::Metadata) -> Option<Arc<Vec<ChunkRef>>> {
+    let modified = meta.modified().ok()?;
+    let cache = cache().lock().ok()?;
+    cache.get(path)
+        .filter(|cached| cached.len == meta.len() && cached.modified == modified)
+        .map(|cached| cached.chunks.clone())
+}
+
+/// Chunk list of a file, read and cached if it changed
+pub fn file_chunks(path: &Path) -> std::io::Result<Arc<Vec<ChunkRef>>> {
+    let meta = fs::metadata(path)?;
+    if let Some(chunks) = cached_chunks(path, &meta) {
+        return Ok(chunks);
+    }
+
+    let chunks = Arc::new(chunk_reader(std::io::BufReader::new(fs::File::open(path)?))?);
+    if let (Ok(modified), Ok(mut cache)) = (meta.modified(), cache().lock()) {
+        cache.insert(path.to_path_buf(), CachedFile { len: meta.len(), modified, chunks: chunks.clone() });
+    }
+    Ok(chunks)
+}
+
+fn relative_path(root: &Path, path: &Path) -> String {
+    path.strip_prefix(root)
+        .map(|p| p.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+/// Manifest of every file of the dataset at `root`
+pub fn manifest(root: &Path) -> std::io::Result<Vec<FileManifest>> {
+    list_files(root)?
+        .into_iter()
+        .map(|path| {
+            let chunks = file_chunks(&path)?;
+            Ok(FileManifest {
+                relative_path: relative_path(root, &path),
+                size: chunks.iter().map(|c| c.length as u64).sum(),
+                chunks: chunks.to_vec(),
+            })
+        })
+        .collect()
+}
+
+/// `sha256:<hex>` over the paths and chunk hashes of a manifest
+pub fn manifest_digest<'a>(files: impl IntoIterator<Item = (&'a str, &'a [ChunkRef])>) -> String {
+    let mut hasher = Sha256::new();
+    for (relative_path, chunks) in files {
+        hasher.update(relative_path.as_bytes());
+        hasher.update([0]);
+        for chunk in chunks {
+            hasher.update(&chunk.hash);
+        }
+        hasher.update([0]);
+    }
+    let hex: String = hasher.finalize().iter().map(|b| format!("{:02x}", b)).collect();
+    format!("sha256:{}", hex)
+}
+
+/// Content digest of the dataset at `root` from the cache alone; None if
+/// any file is new or changed since the indexer last read it
+pub fn cached_digest(root: &Path) -> Option<String> {
+    let files = list_files(root).ok()?;
+    let mut entries = Vec::with_capacity(files.len());
+    for path in &files {
+        let meta = fs::metadata(path).ok()?;
+        entries.push((relative_path(root, path), cached_chunks(path, &meta)?));
+    }
+    Some(manifest_digest(entries.iter().map(|(path, chunks)| (path.as_str(), chunks.as_slice()))))
+}
+
+/// Keep the chunk lists of every dataset in `data_dir` current, forever
+pub async fn run_indexer(data_dir: PathBuf) {
+    let mut interval = tokio::time::interval(INDEX_INTERVAL);
+    loop {
+        interval.tick().await;
+        let dir = data_dir.clone();
+        let indexed = tokio::task::spawn_blocking(move || -> std::io::Result<usize> {
+            let mut indexed = 0;
+            for entry in fs::read_dir(&dir)?.flatten() {
+                let path = entry.path();
+                let hidden = entry.file_name().to_string_lossy().starts_with('.');
+                if path.is_dir() && !hidden {
+                    manifest(&path)?;
+                    indexed += 1;
+                }
+            }
+            Ok(indexed)
+        }).await;
+        match indexed {
+            Ok(Ok(count)) => info!("Indexed chunks of {} datasets", count),
+            Ok(Err(e)) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Ok(Err(e)) => warn!("Failed to index datasets: {}", e),
+            Err(e) => warn!("Dataset indexer panicked: {}", e),
+        }
+    }
+}
+
+/// Where each chunk of an old dataset copy lives, by hash
+pub struct ChunkIndex {
+    chunks: HashMap<Vec<u8>, (PathBuf, u64, u32)>,
+}
+
+impl ChunkIndex {
+    pub fn build(root: &Path) -> std::io::Result<Self> {
+        let mut chunks = HashMap::new();
+        for path in list_files(root)? {
+            for chunk in file_chunks(&path)?.iter() {
+                chunks.insert(chunk.hash.clone(), (path.clone(), chunk.offset, chunk.length));
+            }
+        }
+        Ok(Self { chunks })
+    }
+
+    /// Bytes of a chunk, checked against its hash (the old copy may have
+    /// changed since it was indexed)
+    fn read(&self, hash: &[u8]) -> Option<Vec<u8>> {
+        let (path, offset, length) = self.chunks.get(hash)?;
+        let mut file = fs::File::open(path).ok()?;
+        file.seek(SeekFrom::Start(*offset)).ok()?;
+        let mut data = vec![0u8; *length as usize];
+        file.read_exact(&mut data).ok()?;
+        (Sha256::digest(&data).as_slice() == hash).then_some(data)
+    }
+}
+
+/// Lay out a dataset in `staging` from `files`, copying the chunks the
+/// index holds; returns the bytes copied and the chunks still to fetch
+pub fn reuse_chunks(index: &ChunkIndex, files: &[FileManifest], staging: &Path) -> Result<(u64, Vec<ChunkRange>)> {
+    let mut reused = 0u64;
+    let mut wanted = Vec::new();
+
+    for manifest in files {
+        let path = staging.join(&manifest.relative_path);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut file = fs::File::create(&path).with_context(|| format!("Failed to create {}", path.display()))?;
+        file.set_len(manifest.size)?;
+
+        for chunk in &manifest.chunks {
+            match index.read(&chunk.hash) {
+                Some(data) => {
+                    file.seek(SeekFrom::Start(chunk.offset))?;
+                    file.write_all(&data)?;
+                    reused += data.len() as u64;
+                }
+                None => wanted.push(ChunkRange {
+                    relative_path: manifest.relative_path.clone(),
+                    offset: chunk.offset,
+                    length: chunk.length,
+                }),
+            }
+        }
+        file.flush()?;
+    }
+
+    Ok((reused, wanted))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pseudo_random(len: usize, seed: u64) -> Vec<u8> {
+        let mut state = seed;
+        (0..len)
+            .map(|_| {
+                state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+                (state >> 33) as u8
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_insert_only_changes_nearby_chunks() {
+        let original = pseudo_random(2 * 1024 * 1024, 7);
+        let mut edited = original.clone();
+        edited.splice(500_000..500_000, b"a few inserted bytes".iter().copied());
+
+        let before = chunk_reader(original.as_slice()).unwrap();
+        let after = chunk_reader(edited.as_slice()).unwrap();
+        assert_eq!(before.iter().map(|c| c.length as usize).sum::<usize>(), original.len());
+        assert!(before.iter().all(|c| c.length as usize <= MAX_CHUNK));
+
+        let known: std::collections::HashSet<_> = before.iter().map(|c| &c.hash).collect();
+        let changed = after.iter().filter(|c| !known.contains(&c.hash)).count();
+        assert!(changed <= 2, "{} of {} chunks changed", changed, after.len());
+    }
+}
@@ -0,0 +1,67 @@
+//! Per-job network egress throttling
+//!
+//! Docker has no egress rate option, so a token bucket filter is attached
+//! to the container's interface from inside its network namespace once it
+//! has started. Bulk uploads from one job then can't fill the VPS uplink
+//! and blow the latency of inference services running next to it. Needs
+//! `nsenter` and `tc` (iproute2) on the host.
+
+use anyhow::{Context, Result};
+use tokio::process::Command;
+
+/// Interface Docker gives bridge-networked containers
+const CONTAINER_IFACE: &str = "eth0";
+
+/// Longest a packet may wait in the bucket before it is dropped
+const MAX_LATENCY: &str = "50ms";
+
+/// Bucket size: 10ms worth of traffic at the limit, at least 32 KiB so
+/// full-size TCP segments still pass at low rates
+fn burst_bytes(mbps: u32) -> u64 {
+    (mbps as u64 * 1_000_000 / 8 / 100).max(32 * 1024)
+}
+
+/// `nsenter` arguments shaping the egress of process `pid`'s network
+/// namespace to `mbps` Mbit/s
+fn tc_args(pid: i64, mbps: u32) -> Vec<String> {
+    [
+        "-t", &pid.to_string(), "-n",
+        "tc", "qdisc", "replace", "dev", CONTAINER_IFACE, "root", "tbf",
+        "rate", &format!("{}mbit", mbps),
+        "burst", &burst_bytes(mbps).to_string(),
+        "latency", MAX_LATENCY,
+    ]
+    .iter()
+    .map(|arg| arg.to_string())
+    .collect()
+}
+
+/// Limit the egress of the container whose init process is `pid`
+pub async fn limit(pid: i64, mbps: u32) -> Result<()> {
+    let output = Command::new("nsenter")
+        .args(tc_args(pid, mbps))
+        .output()
+        .await
+        .context("Failed to run nsenter")?;
+    anyhow::ensure!(
+        output.status.success(),
+        "tc failed: {}",
+        String::from_utf8_lossy(&output.stderr).trim()
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tc_args() {
+        assert_eq!(
+            tc_args(4242, 100).join(" "),
+            "-t 4242 -n tc qdisc replace dev eth0 root tbf rate 100mbit burst 125000 latency 50ms"
+        );
+        // Slow limits keep a bucket large enough for a few full packets
+        assert_eq!(burst_bytes(1), 32 * 1024);
+    }
+}
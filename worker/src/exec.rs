@@ -0,0 +1,118 @@
+//! Exec sessions in running job containers
+//!
+//! The scheduler proxies an operator's `ExecInJob` stream to the worker's
+//! `Exec` RPC. The command runs with Docker exec in the job's container;
+//! stdin is written to it until the caller closes its side, and output is
+//! relayed back until the command exits, followed by its exit code.
+//!
+//! A session needs the scheduler's exec grant for the job, which it only
+//! signs for callers it authorized as operators (see the scheduler's
+//! `exec` module).
+
+use anyhow::Result;
+use bollard::container::LogOutput;
+use bollard::exec::StartExecResults;
+use futures_util::stream::StreamExt;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::sync::Arc;
+use tokio::io::AsyncWriteExt;
+use tokio::sync::mpsc;
+use tonic::{Status, Streaming};
+use tracing::{info, warn};
+
+use crate::control::worker_proto::{exec_request, exec_response, ExecRequest, ExecResponse, ExecStart};
+use crate::runtime::ContainerRuntime;
+
+/// Metadata carrying the scheduler's exec grant
+pub const EXEC_GRANT_HEADER: &str = "x-tgp-exec-grant";
+
+/// Seconds an exec grant stays valid after it was issued
+const GRANT_MAX_AGE_SECS: i64 = 60;
+
+/// Whether `grant` (`<issued_at>.<hex>`) is a fresh grant to exec into
+/// `job_id`, signed with the worker control `token`
+pub fn verify_grant(token: &str, job_id: &str, grant: &str, now: i64) -> bool {
+    let Some((issued_at, signature)) = grant.split_once('.') else {
+        return false;
+    };
+    let Ok(issued_at) = issued_at.parse::<i64>() else {
+        return false;
+    };
+    if token.is_empty() || (now - issued_at).abs() > GRANT_MAX_AGE_SECS {
+        return false;
+    }
+    let Some(signature) = decode_hex(signature) else {
+        return false;
+    };
+    let mut mac = Hmac::<Sha256>::new_from_slice(token.as_bytes()).expect("HMAC takes keys of any length");
+    mac.update(issued_at.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(job_id.as_bytes());
+    mac.verify_slice(&signature).is_ok()
+}
+
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    if hex.len() % 2 != 0 {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+/// Run one exec session, sending output and the exit code to `tx`
+pub async fn run(
+    runtime: Arc<dyn ContainerRuntime>,
+    start: ExecStart,
+    inbound: Streaming<ExecRequest>,
+    tx: mpsc::Sender<Result<ExecResponse, Status>>,
+) {
+    info!("Exec in job {}: {:?}", start.job_id, start.command);
+    if let Err(e) = session(runtime.as_ref(), &start, inbound, &tx).await {
+        warn!("Exec in job {} failed: {:#}", start.job_id, e);
+        let _ = tx.send(Err(Status::failed_precondition(format!("{:#}", e)))).await;
+    }
+}
+
+async fn session(
+    runtime: &dyn ContainerRuntime,
+    start: &ExecStart,
+    mut inbound: Streaming<ExecRequest>,
+    tx: &mpsc::Sender<Result<ExecResponse, Status>>,
+) -> Result<()> {
+    let (exec_id, started) = runtime.exec(&start.job_id, start.command.clone(), start.tty).await?;
+    let StartExecResults::Attached { mut output, mut input } = started else {
+        anyhow::bail!("Exec started detached");
+    };
+
+    let stdin = tokio::spawn(async move {
+        while let Ok(Some(message)) = inbound.message().await {
+            if let Some(exec_request::Body::Stdin(data)) = message.body {
+                if input.write_all(&data).await.is_err() {
+                    break;
+                }
+            }
+        }
+        let _ = input.shutdown().await;
+    });
+
+    while let Some(chunk) = output.next().await {
+        let body = match chunk? {
+            LogOutput::StdErr { message } => exec_response::Body::Stderr(message.to_vec()),
+            other => exec_response::Body::Stdout(other.into_bytes().to_vec()),
+        };
+        // The caller went away; the command is left to finish on its own
+        if tx.send(Ok(ExecResponse { body: Some(body) })).await.is_err() {
+            stdin.abort();
+            return Ok(());
+        }
+    }
+    stdin.abort();
+
+    let exit_code = runtime.exec_exit_code(&exec_id).await?.unwrap_or(-1);
+    info!("Exec in job {} exited with {}", start.job_id, exit_code);
+    let _ = tx.send(Ok(ExecResponse { body: Some(exec_response::Body::ExitCode(exit_code)) })).await;
+    Ok(())
+}
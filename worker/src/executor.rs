@@ -7,14 +7,82 @@
 
 use anyhow::{Context, Result};
 use bollard::container::{
-    Config, CreateContainerOptions, LogsOptions, RemoveContainerOptions, StartContainerOptions,
-    StopContainerOptions, WaitContainerOptions,
+    AttachContainerOptions, Config, CreateContainerOptions, InspectContainerOptions, ListContainersOptions,
+    RemoveContainerOptions, StartContainerOptions, StopContainerOptions, WaitContainerOptions,
 };
-use bollard::models::HostConfig;
+use bollard::exec::{CreateExecOptions, StartExecResults};
+use bollard::models::{DeviceRequest, HostConfig, PortBinding};
 use bollard::Docker;
+use sha2::{Digest, Sha256};
 use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use tokio::io::AsyncWriteExt;
+use tokio::sync::{mpsc, oneshot};
 use tracing::{error, info, warn};
 
+use crate::artifacts;
+use crate::blkio;
+use crate::data_service;
+use crate::egress;
+use crate::hooks::{self, LifecycleHook};
+use crate::input;
+use crate::logs::{self, LogConfig};
+use crate::outputs;
+use crate::progress::{self, ProgressUpdate};
+use crate::scratch;
+use crate::usage;
+
+/// Containers are named after their job with this prefix
+const CONTAINER_PREFIX: &str = "tgp-job-";
+
+/// Job whose container has this name, if it is a TGP job container
+fn job_id_from_container_name(name: &str) -> Option<&str> {
+    // Docker reports names with a leading slash
+    name.trim_start_matches('/')
+        .strip_prefix(CONTAINER_PREFIX)
+        .filter(|id| !id.is_empty())
+}
+
+/// Docker port bindings and exposed ports for the reserved host ports
+fn published_ports(bindings: &HashMap<u32, u32>) -> HashMap<String, Option<Vec<PortBinding>>> {
+    bindings.iter()
+        .map(|(container_port, host_port)| {
+            (format!("{}/tcp", container_port), Some(vec![PortBinding {
+                host_ip: None,
+                host_port: Some(host_port.to_string()),
+            }]))
+        })
+        .collect()
+}
+
+/// Request for exactly the reserved GPUs (none: no GPU access)
+fn gpu_requests(gpu_indices: &[u32]) -> Option<Vec<DeviceRequest>> {
+    if gpu_indices.is_empty() {
+        return None;
+    }
+    Some(vec![DeviceRequest {
+        driver: Some("nvidia".to_string()),
+        device_ids: Some(gpu_indices.iter().map(|index| index.to_string()).collect()),
+        capabilities: Some(vec![vec!["gpu".to_string()]]),
+        ..Default::default()
+    }])
+}
+
+/// Docker cpuset string ("0,1,5") for pinned cores (none: not pinned)
+fn cpuset(cpu_set: &[u32]) -> Option<String> {
+    (!cpu_set.is_empty()).then(|| cpu_set.iter().map(u32::to_string).collect::<Vec<_>>().join(","))
+}
+
+/// Read-only binds of a job's staged datasets (none until they are staged)
+fn dataset_binds(job: &JobExecution) -> Vec<String> {
+    let Some(dir) = &job.dataset_dir else {
+        return Vec::new();
+    };
+    job.datasets.iter()
+        .map(|name| format!("{}:{}/{}:ro", dir.join(name).display(), data_service::CONTAINER_DATA_DIR, name))
+        .collect()
+}
+
 /// Job execution request from scheduler
 #[derive(Debug, Clone)]
 pub struct JobExecution {
@@ -23,34 +91,104 @@ pub struct JobExecution {
     pub container_image: String,
     pub cpu_limit: u32,
     pub memory_limit_mb: u64,
+    /// Scratch space the job may fill (0: unbounded)
+    pub disk_limit_gb: u32,
+    /// GPUs the scheduler reserved for the job
+    pub gpu_indices: Vec<u32>,
+    /// Container port -> host port the scheduler reserved for it
+    pub port_bindings: HashMap<u32, u32>,
+    /// Cores the scheduler dedicated to the job (empty: not pinned)
+    pub cpu_set: Vec<u32>,
+    /// NUMA node to bind the job's memory to
+    pub numa_node: Option<u32>,
+    /// Disk throughput cap in MB/s (0: unthrottled)
+    pub disk_io_mbps: u32,
+    /// Outbound traffic cap in Mbit/s (0: unlimited)
+    pub egress_limit_mbps: u32,
+    /// Job input, mounted as a file or fed to stdin
+    pub input: Vec<u8>,
+    /// Feed `input` to stdin rather than `$TGP_INPUT_FILE`
+    pub input_stdin: bool,
+    /// Path archived as the job's output once the container exits
+    pub output_path: Option<String>,
+    /// Input held by the scheduler's payload store, fetched into `input`
+    /// before the job starts
+    pub payload_digest: Option<String>,
     pub command: Option<Vec<String>>,
     pub env: HashMap<String, String>,
+    /// Run before the container; the job fails if it does (see `hooks`)
+    pub pre_start_hook: Option<LifecycleHook>,
+    /// Run after the container, whatever its outcome
+    pub post_complete_hook: Option<LifecycleHook>,
+    /// Input datasets, staged before the job starts
+    pub datasets: Vec<String>,
+    /// Where `datasets` were staged; each is mounted read-only at /data/<name>
+    pub dataset_dir: Option<PathBuf>,
 }
 
 /// Job executor using Docker containers
 pub struct JobExecutor {
     docker: Docker,
+    /// Where job output is written and how much is kept
+    logs: LogConfig,
 }
 
 impl JobExecutor {
     /// Create new job executor
     pub fn new() -> Result<Self> {
-        // Connect to Docker daemon on local Unix socket
+        // Connect to the local Docker daemon: its Unix socket, or Docker
+        // Desktop's named pipe (//./pipe/docker_engine) on Windows
         let docker = Docker::connect_with_socket_defaults()
             .context("Failed to connect to Docker daemon")?;
 
-        Ok(Self { docker })
+        Ok(Self { docker, logs: LogConfig::from_env() })
+    }
+
+    pub fn log_config(&self) -> &LogConfig {
+        &self.logs
     }
 
     /// Execute a job in a Docker container
-    pub async fn execute_job(&self, job: JobExecution) -> Result<JobResult> {
+    ///
+    /// Progress written by the container to `$TGP_PROGRESS_FILE` is sent to
+    /// `progress_tx` while the job runs.
+    pub async fn execute_job(
+        &self,
+        job: JobExecution,
+        progress_tx: Option<mpsc::Sender<ProgressUpdate>>,
+    ) -> Result<JobResult> {
         info!("Executing job {} with image {}", job.job_id, job.container_image);
 
         // Pull image if not exists
         self.pull_image(&job.container_image).await?;
 
+        // Host directory shared with the container for the progress file,
+        // holding the job's scratch space
+        let job_dir = Self::job_dir(&job.job_id);
+        std::fs::create_dir_all(job_dir.join("scratch"))
+            .context("Failed to create job directory")?;
+        if Self::input_file(&job) {
+            input::write(&job_dir, &job.input)?;
+        }
+
         // Create container with resource limits
-        let container_id = self.create_container(&job).await?;
+        let container_id = self.create_container(&job, &job_dir).await?;
+
+        // Attach before starting so no input is lost
+        let stdin = if job.input_stdin {
+            let options = AttachContainerOptions::<String> {
+                stdin: Some(true),
+                stream: Some(true),
+                ..Default::default()
+            };
+            let attached = self.docker
+                .attach_container(&container_id, Some(options))
+                .await
+                .context("Failed to attach to container stdin")?;
+            Some(attached.input)
+        } else {
+            None
+        };
 
         // Start container
         info!("Starting container: {}", container_id);
@@ -59,25 +197,129 @@ impl JobExecutor {
             .await
             .context("Failed to start container")?;
 
+        // Written in the background: a container that never reads its stdin
+        // must not block the worker
+        if let Some(mut stdin) = stdin {
+            let data = job.input.clone();
+            let container = container_id.clone();
+            tokio::spawn(async move {
+                if let Err(e) = async { stdin.write_all(&data).await?; stdin.shutdown().await }.await {
+                    warn!("Failed to write input to container {}: {}", container, e);
+                }
+            });
+        }
+
+        if job.egress_limit_mbps > 0 {
+            self.limit_egress(&container_id, job.egress_limit_mbps).await;
+        }
+
+        // Stream output to the job's log files while it runs
+        let log_collector = tokio::spawn(logs::collect(
+            self.docker.clone(),
+            container_id.clone(),
+            job.job_id.clone(),
+            self.logs.clone(),
+        ));
+
+        // Tail progress while the container runs
+        let tailer = progress_tx.map(|tx| {
+            let (stop_tx, stop_rx) = oneshot::channel();
+            let handle = tokio::spawn(progress::tail_progress(
+                job_dir.join("progress"),
+                tx,
+                stop_rx,
+            ));
+            (stop_tx, handle)
+        });
+
+        // Meter peak memory and CPU for the final status report
+        let (meter_stop, meter_rx) = oneshot::channel();
+        let meter = tokio::spawn(usage::meter(self.docker.clone(), container_id.clone(), meter_rx));
+
+        // Stop the job once its scratch space outgrows its disk requirement
+        let scratch_watch = (job.disk_limit_gb > 0).then(|| {
+            let (stop_tx, stop_rx) = oneshot::channel();
+            let docker = self.docker.clone();
+            let container = container_id.clone();
+            let dir = job_dir.join("scratch");
+            let limit_bytes = job.disk_limit_gb as u64 * 1024 * 1024 * 1024;
+            let handle = tokio::spawn(async move {
+                let exceeded = scratch::watch(dir, limit_bytes, stop_rx).await;
+                if exceeded {
+                    warn!("Container {} exceeded its scratch space, stopping it", container);
+                    if let Err(e) = docker.stop_container(&container, Some(StopContainerOptions { t: 0 })).await {
+                        error!("Failed to stop container {}: {}", container, e);
+                    }
+                }
+                exceeded
+            });
+            (stop_tx, handle)
+        });
+
         // Wait for container to complete
-        let exit_code = self.wait_for_completion(&container_id).await?;
+        let exit_code = self.wait_for_completion(&container_id).await;
+
+        let scratch_exceeded = match scratch_watch {
+            Some((stop_tx, handle)) => {
+                let _ = stop_tx.send(());
+                handle.await.unwrap_or(false)
+            }
+            None => false,
+        };
+
+        // Flush the final progress lines before reporting completion
+        if let Some((stop_tx, handle)) = tailer {
+            let _ = stop_tx.send(());
+            let _ = handle.await;
+        }
+        let _ = meter_stop.send(());
+        let peak = meter.await.unwrap_or_default();
+        let exit_code = exit_code?;
+
+        // Capture the declared output before the container is removed
+        let output = match &job.output_path {
+            Some(path) => {
+                let archive = outputs::archive_path(&job.job_id);
+                outputs::download(&self.docker, &container_id, path, &archive)
+                    .await
+                    .map(|size| {
+                        info!("Captured {} byte output of job {}", size, job.job_id);
+                        Some(archive)
+                    })
+            }
+            None => Ok(None),
+        };
 
-        // Get container logs
-        let logs = self.get_logs(&container_id).await?;
+        // The log stream ends once the container has exited
+        let logs = log_collector.await.context("Log collector failed")??;
+
+        let artifacts = artifacts::read_artifacts(&job_dir.join("artifacts"));
 
         // Clean up container
         self.cleanup_container(&container_id).await?;
+        if let Err(e) = std::fs::remove_dir_all(&job_dir) {
+            warn!("Failed to remove job directory {}: {}", job_dir.display(), e);
+        }
 
         let result = JobResult {
             job_id: job.job_id.clone(),
-            success: exit_code == 0,
+            success: exit_code == 0 && !scratch_exceeded && output.is_ok(),
             exit_code,
-            logs,
-            error: if exit_code != 0 {
+            output_hash: output_hash(logs.hasher, &artifacts),
+            logs: logs.tail,
+            artifacts,
+            error: if scratch_exceeded {
+                Some(format!("Scratch space exceeded the {}GB disk requirement", job.disk_limit_gb))
+            } else if exit_code != 0 {
                 Some(format!("Container exited with code {}", exit_code))
+            } else if let Err(e) = &output {
+                Some(format!("Failed to capture output: {:#}", e))
             } else {
                 None
             },
+            output_archive: output.ok().flatten(),
+            peak_memory_gb: peak.memory_gb,
+            peak_cpu_cores: peak.cpu_cores,
         };
 
         if result.success {
@@ -89,6 +331,11 @@ impl JobExecutor {
         Ok(result)
     }
 
+    /// Host directory holding per-job files shared with the container
+    fn job_dir(job_id: &str) -> PathBuf {
+        std::env::temp_dir().join("tgp-jobs").join(job_id)
+    }
+
     /// Pull Docker image
     async fn pull_image(&self, image: &str) -> Result<()> {
         use bollard::image::CreateImageOptions;
@@ -122,25 +369,143 @@ impl JobExecutor {
         Ok(())
     }
 
+    /// Stop a job's container, giving it `grace_secs` to checkpoint
+    ///
+    /// Docker sends SIGTERM first and SIGKILL once the grace period ends.
+    pub async fn stop_job(&self, job_id: &str, grace_secs: i64) -> Result<()> {
+        let name = format!("{}{}", CONTAINER_PREFIX, job_id);
+        info!("Stopping container {} ({}s grace)", name, grace_secs);
+
+        self.docker
+            .stop_container(&name, Some(StopContainerOptions { t: grace_secs }))
+            .await
+            .with_context(|| format!("Failed to stop container {}", name))
+    }
+
+    /// Start `command` in a job's running container with its stdin, stdout
+    /// and stderr attached; returns the exec id and the attached streams
+    pub async fn exec(&self, job_id: &str, command: Vec<String>, tty: bool) -> Result<(String, StartExecResults)> {
+        let name = format!("{}{}", CONTAINER_PREFIX, job_id);
+        let exec = self.docker
+            .create_exec(&name, CreateExecOptions {
+                cmd: Some(command),
+                attach_stdin: Some(true),
+                attach_stdout: Some(true),
+                attach_stderr: Some(true),
+                tty: Some(tty),
+                ..Default::default()
+            })
+            .await
+            .with_context(|| format!("Failed to exec in container {}", name))?;
+        let started = self.docker
+            .start_exec(&exec.id, None)
+            .await
+            .with_context(|| format!("Failed to start exec in container {}", name))?;
+        Ok((exec.id, started))
+    }
+
+    /// Exit code of a finished exec
+    pub async fn exec_exit_code(&self, exec_id: &str) -> Result<Option<i64>> {
+        let inspect = self.docker
+            .inspect_exec(exec_id)
+            .await
+            .context("Failed to inspect exec")?;
+        Ok(inspect.exit_code)
+    }
+
+    /// Ids of jobs whose containers are running
+    pub async fn running_jobs(&self) -> Result<Vec<String>> {
+        let filters = HashMap::from([
+            ("name".to_string(), vec![CONTAINER_PREFIX.to_string()]),
+            ("status".to_string(), vec!["running".to_string()]),
+        ]);
+        let containers = self.docker
+            .list_containers(Some(ListContainersOptions { filters, ..Default::default() }))
+            .await
+            .context("Failed to list containers")?;
+
+        let mut job_ids: Vec<String> = containers.iter()
+            .flat_map(|container| container.names.iter().flatten())
+            .filter_map(|name| job_id_from_container_name(name))
+            // A hook's container counts as its job's
+            .map(|id| hooks::owning_job(id).to_string())
+            .collect();
+        job_ids.sort();
+        job_ids.dedup();
+        Ok(job_ids)
+    }
+
     /// Create container with resource limits
-    async fn create_container(&self, job: &JobExecution) -> Result<String> {
+    async fn create_container(&self, job: &JobExecution, job_dir: &Path) -> Result<String> {
+        // Throttle disk I/O on the disk holding the job's scratch space
+        let throttle = match (job.disk_io_mbps, blkio::disk_for(&job_dir.join("scratch"))) {
+            (0, _) => None,
+            (mbps, Some(disk)) => Some(blkio::throttle(&disk, mbps)),
+            (_, None) => {
+                warn!("No block device found for job {} scratch space, disk I/O not throttled", job.job_id);
+                None
+            }
+        };
+
         // Set resource limits according to TGP blueprint
         let host_config = HostConfig {
             cpu_quota: Some((job.cpu_limit as i64) * 100_000), // CPU quota in microseconds
             memory: Some((job.memory_limit_mb * 1024 * 1024) as i64), // Memory in bytes
             memory_swap: Some((job.memory_limit_mb * 1024 * 1024) as i64), // No swap
             network_mode: Some("bridge".to_string()),
+            binds: Some(
+                vec![
+                    format!("{}:{}", job_dir.display(), progress::CONTAINER_PROGRESS_DIR),
+                    format!("{}:{}", job_dir.join("scratch").display(), scratch::CONTAINER_SCRATCH_DIR),
+                ]
+                .into_iter()
+                .chain(dataset_binds(job))
+                .collect(),
+            ),
+            port_bindings: Some(published_ports(&job.port_bindings)),
+            device_requests: gpu_requests(&job.gpu_indices),
+            cpuset_cpus: cpuset(&job.cpu_set),
+            cpuset_mems: job.numa_node.map(|numa| numa.to_string()),
+            blkio_device_read_bps: throttle.as_ref().map(|t| t.bps.clone()),
+            blkio_device_write_bps: throttle.as_ref().map(|t| t.bps.clone()),
+            blkio_device_read_iops: throttle.as_ref().map(|t| t.iops.clone()),
+            blkio_device_write_iops: throttle.map(|t| t.iops),
             auto_remove: Some(false), // We'll remove manually after getting logs
             ..Default::default()
         };
 
         let config = Config {
             image: Some(job.container_image.clone()),
-            cmd: job.command.clone(),
+            cmd: job.command.clone().map(input::substitute),
             env: Some(
                 job.env
                     .iter()
                     .map(|(k, v)| format!("{}={}", k, v))
+                    .chain(std::iter::once(format!(
+                        "TGP_PROGRESS_FILE={}",
+                        progress::CONTAINER_PROGRESS_FILE
+                    )))
+                    .chain(std::iter::once(format!(
+                        "TGP_ARTIFACTS_FILE={}",
+                        artifacts::CONTAINER_ARTIFACTS_FILE
+                    )))
+                    .chain(std::iter::once(format!(
+                        "TGP_SCRATCH_DIR={}",
+                        scratch::CONTAINER_SCRATCH_DIR
+                    )))
+                    .chain(Self::input_file(job).then(|| format!(
+                        "TGP_INPUT_FILE={}",
+                        input::CONTAINER_INPUT_FILE
+                    )))
+                    .collect(),
+            ),
+            attach_stdin: Some(job.input_stdin),
+            open_stdin: Some(job.input_stdin),
+            stdin_once: Some(job.input_stdin),
+            exposed_ports: Some(
+                job.port_bindings
+                    .keys()
+                    .map(|port| (format!("{}/tcp", port), HashMap::new()))
                     .collect(),
             ),
             host_config: Some(host_config),
@@ -148,7 +513,7 @@ impl JobExecutor {
         };
 
         let options = CreateContainerOptions {
-            name: format!("tgp-job-{}", job.job_id),
+            name: format!("{}{}", CONTAINER_PREFIX, job.job_id),
             platform: None,
         };
 
@@ -174,7 +539,7 @@ impl JobExecutor {
 
         let mut stream = self.docker.wait_container(container_id, options);
 
-        while let Some(result) = stream.next().await {
+        if let Some(result) = stream.next().await {
             match result {
                 Ok(response) => {
                     let code = response.status_code;
@@ -190,32 +555,29 @@ impl JobExecutor {
         Ok(0)
     }
 
-    /// Get container logs
-    async fn get_logs(&self, container_id: &str) -> Result<String> {
-        use futures_util::stream::StreamExt;
-
-        let options = Some(LogsOptions::<String> {
-            stdout: true,
-            stderr: true,
-            tail: "all".to_string(),
-            ..Default::default()
-        });
-
-        let mut stream = self.docker.logs(container_id, options);
-        let mut logs = String::new();
+    /// Whether the job's input is handed over as `$TGP_INPUT_FILE`
+    fn input_file(job: &JobExecution) -> bool {
+        !job.input.is_empty() && !job.input_stdin
+    }
 
-        while let Some(result) = stream.next().await {
-            match result {
-                Ok(output) => {
-                    logs.push_str(&output.to_string());
-                }
-                Err(e) => {
-                    warn!("Error reading logs: {}", e);
-                }
+    /// Shape the started container's outbound traffic; a job whose limit
+    /// can't be applied still runs, unthrottled
+    async fn limit_egress(&self, container_id: &str, mbps: u32) {
+        let pid = match self.docker.inspect_container(container_id, None::<InspectContainerOptions>).await {
+            Ok(info) => info.state.and_then(|state| state.pid).filter(|&pid| pid > 0),
+            Err(e) => {
+                warn!("Failed to inspect container {}: {}", container_id, e);
+                None
             }
+        };
+        let Some(pid) = pid else {
+            warn!("Container {} has no process, egress not limited", container_id);
+            return;
+        };
+        match egress::limit(pid, mbps).await {
+            Ok(()) => info!("Limited egress of container {} to {} Mbit/s", container_id, mbps),
+            Err(e) => warn!("Failed to limit egress of container {}: {:#}", container_id, e),
         }
-
-        Ok(logs)
     }
 
     /// Clean up container after execution
@@ -251,14 +613,80 @@ pub struct JobResult {
     pub job_id: String,
     pub success: bool,
     pub exit_code: i64,
+    /// Tail of the container's output (the rest is in its log files)
     pub logs: String,
+    /// `name=value` outputs from `$TGP_ARTIFACTS_FILE`
+    pub artifacts: HashMap<String, String>,
+    /// Hex SHA-256 of logs and artifacts, compared by result verification
+    pub output_hash: String,
     pub error: Option<String>,
+    /// Spooled archive of the declared output, to be uploaded
+    pub output_archive: Option<PathBuf>,
+    /// Highest memory use while running, in GB (0: not metered)
+    pub peak_memory_gb: f64,
+    /// Most CPU cores kept busy while running (0: not metered)
+    pub peak_cpu_cores: f64,
+}
+
+/// Hash of a job's observable output, independent of artifact order;
+/// `hasher` has been fed the job's whole log output
+pub fn output_hash(mut hasher: Sha256, artifacts: &HashMap<String, String>) -> String {
+    hasher.update([0u8]);
+
+    let mut artifacts: Vec<_> = artifacts.iter().collect();
+    artifacts.sort();
+    for (name, value) in artifacts {
+        hasher.update(name.as_bytes());
+        hasher.update([0u8]);
+        hasher.update(value.as_bytes());
+        hasher.update([0u8]);
+    }
+
+    hasher.finalize()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_job_id_from_container_name() {
+        assert_eq!(job_id_from_container_name("/tgp-job-job-1"), Some("job-1"));
+        assert_eq!(job_id_from_container_name("tgp-job-job-1"), Some("job-1"));
+        assert_eq!(job_id_from_container_name("/tgp-job-"), None);
+        assert_eq!(job_id_from_container_name("/postgres"), None);
+    }
+
+    #[test]
+    fn test_reserved_devices_are_passed_to_docker() {
+        let ports = published_ports(&HashMap::from([(80, 30001)]));
+        let binding = ports["80/tcp"].as_ref().unwrap();
+        assert_eq!(binding[0].host_port.as_deref(), Some("30001"));
+
+        assert!(gpu_requests(&[]).is_none());
+        let request = gpu_requests(&[1, 3]).unwrap();
+        assert_eq!(request[0].device_ids, Some(vec!["1".to_string(), "3".to_string()]));
+
+        assert_eq!(cpuset(&[]), None);
+        assert_eq!(cpuset(&[0, 1, 5]).as_deref(), Some("0,1,5"));
+    }
+
+    #[test]
+    fn test_staged_datasets_are_mounted_read_only() {
+        let mut job = crate::control::job_execution(crate::control::worker_proto::ExecuteJobRequest {
+            job_id: "job-1".to_string(),
+            datasets: vec!["imagenet".to_string()],
+            ..Default::default()
+        });
+        assert!(dataset_binds(&job).is_empty());
+
+        job.dataset_dir = Some(PathBuf::from("/var/lib/tgp/datasets"));
+        assert_eq!(dataset_binds(&job), ["/var/lib/tgp/datasets/imagenet:/data/imagenet:ro"]);
+    }
+
     #[tokio::test]
     #[ignore] // Requires Docker daemon
     async fn test_execute_simple_job() {
@@ -270,11 +698,26 @@ mod tests {
             container_image: "alpine:latest".to_string(),
             cpu_limit: 1,
             memory_limit_mb: 128,
+            disk_limit_gb: 1,
+            gpu_indices: Vec::new(),
+            port_bindings: HashMap::new(),
+            cpu_set: Vec::new(),
+            numa_node: None,
+            disk_io_mbps: 0,
+            egress_limit_mbps: 0,
+            input: Vec::new(),
+            input_stdin: false,
+            output_path: None,
+            payload_digest: None,
             command: Some(vec!["echo".to_string(), "Hello from TGP!".to_string()]),
             env: HashMap::new(),
+            pre_start_hook: None,
+            post_complete_hook: None,
+            datasets: Vec::new(),
+            dataset_dir: None,
         };
 
-        let result = executor.execute_job(job).await.unwrap();
+        let result = executor.execute_job(job, None).await.unwrap();
         assert!(result.success);
         assert_eq!(result.exit_code, 0);
         assert!(result.logs.contains("Hello from TGP"));
@@ -0,0 +1,102 @@
+//! GPU detection and interconnect topology
+//!
+//! Parses the matrix printed by `nvidia-smi topo -m`: one row per GPU with
+//! its link to every other GPU (NV# for NVLink, PIX/PXB/PHB/NODE/SYS for
+//! PCIe paths) and its NUMA affinity. The scheduler uses it to give
+//! multi-GPU jobs GPUs that talk over NVLink or at least share a NUMA node.
+
+use anyhow::{Context, Result};
+use std::process::Command;
+
+use crate::proto::{GpuLinkType, GpuTopology};
+
+fn parse_link(cell: &str) -> GpuLinkType {
+    match cell {
+        c if c.starts_with("NV") => GpuLinkType::GpuLinkNvlink,
+        "PIX" => GpuLinkType::GpuLinkPix,
+        "PXB" => GpuLinkType::GpuLinkPxb,
+        "PHB" => GpuLinkType::GpuLinkPhb,
+        "NODE" => GpuLinkType::GpuLinkNode,
+        // SOC is the pre-NODE name for crossing CPU sockets
+        "SYS" | "SOC" => GpuLinkType::GpuLinkSys,
+        _ => GpuLinkType::GpuLinkUnknown,
+    }
+}
+
+/// Parse `nvidia-smi topo -m` output (NIC rows and columns are ignored)
+pub fn parse(output: &str) -> GpuTopology {
+    let mut lines = output.lines().filter(|line| !line.trim().is_empty());
+    let Some(header) = lines.next() else {
+        return GpuTopology::default();
+    };
+    let columns: Vec<&str> = header.split('\t').map(str::trim).collect();
+    let gpu_columns: Vec<usize> = columns.iter()
+        .enumerate()
+        .filter(|(_, name)| name.starts_with("GPU") && name[3..].parse::<u32>().is_ok())
+        .map(|(i, _)| i)
+        .collect();
+    let numa_column = columns.iter().position(|name| *name == "NUMA Affinity");
+
+    let mut topology = GpuTopology::default();
+    for line in lines {
+        let cells: Vec<&str> = line.split('\t').map(str::trim).collect();
+        let is_gpu_row = cells.first()
+            .and_then(|name| name.strip_prefix("GPU"))
+            .is_some_and(|index| index.parse::<u32>().is_ok());
+        if !is_gpu_row {
+            continue;
+        }
+        topology.numa_nodes.push(
+            numa_column
+                .and_then(|i| cells.get(i))
+                .and_then(|numa| numa.parse().ok())
+                .unwrap_or(-1),
+        );
+        topology.links.extend(
+            gpu_columns.iter().map(|&i| parse_link(cells.get(i).copied().unwrap_or_default()) as i32),
+        );
+    }
+    topology
+}
+
+/// Detect NVIDIA GPUs and their topology (None without a driver)
+pub fn detect() -> Result<Option<GpuTopology>> {
+    let output = match Command::new("nvidia-smi").args(["topo", "-m"]).output() {
+        Ok(output) => output,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e).context("Failed to run nvidia-smi"),
+    };
+    anyhow::ensure!(
+        output.status.success(),
+        "nvidia-smi topo failed: {}",
+        String::from_utf8_lossy(&output.stderr).trim()
+    );
+    let topology = parse(&String::from_utf8_lossy(&output.stdout));
+    Ok((!topology.numa_nodes.is_empty()).then_some(topology))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_topo_matrix() {
+        let output = "\tGPU0\tGPU1\tGPU2\tNIC0\tCPU Affinity\tNUMA Affinity\tGPU NUMA ID\n\
+            GPU0\t X \tNV12\tSYS\tPXB\t0-23\t0\t\tN/A\n\
+            GPU1\tNV12\t X \tSYS\tPXB\t0-23\t0\t\tN/A\n\
+            GPU2\tSYS\tSYS\t X \tSYS\t24-47\t1\t\tN/A\n\
+            NIC0\tPXB\tPXB\tSYS\t X \n\
+            \n\
+            Legend:\n\
+            \n\
+              X    = Self\n";
+        let topology = parse(output);
+
+        assert_eq!(topology.numa_nodes, vec![0, 0, 1]);
+        assert_eq!(topology.links.len(), 9);
+        assert_eq!(topology.links[1], GpuLinkType::GpuLinkNvlink as i32);
+        assert_eq!(topology.links[2], GpuLinkType::GpuLinkSys as i32);
+        assert_eq!(topology.links[4], GpuLinkType::GpuLinkUnknown as i32);
+        assert!(parse("").numa_nodes.is_empty());
+    }
+}
@@ -0,0 +1,209 @@
+//! Job lifecycle hooks
+//!
+//! A job's pre-start and post-complete hooks each run in a container of
+//! their own, in sequence with the job's: in the job's image unless the
+//! hook names another, with the job's CPU and memory limits and
+//! environment but none of its GPUs, ports or input. A hook still running
+//! at its timeout is stopped and counts as failed. The post-complete hook
+//! finds the job's outcome in `TGP_JOB_STATUS`.
+
+use anyhow::Result;
+use std::collections::HashMap;
+use std::time::Duration;
+use tracing::{info, warn};
+
+use crate::executor::{JobExecution, JobResult};
+use crate::runtime::ContainerRuntime;
+
+/// Time a hook gets when the job names none
+pub const DEFAULT_TIMEOUT_SECS: u64 = 600;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HookPhase {
+    PreStart,
+    PostComplete,
+}
+
+impl HookPhase {
+    pub const ALL: [HookPhase; 2] = [HookPhase::PreStart, HookPhase::PostComplete];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            HookPhase::PreStart => "pre-start",
+            HookPhase::PostComplete => "post-complete",
+        }
+    }
+}
+
+/// A command run before or after a job's container
+#[derive(Debug, Clone, PartialEq)]
+pub struct LifecycleHook {
+    /// Image to run (empty: the job's)
+    pub image: String,
+    pub command: Vec<String>,
+    pub timeout: Duration,
+}
+
+impl LifecycleHook {
+    /// A hook from its request fields (0: the default timeout); none
+    /// without a command
+    pub fn new(image: String, command: Vec<String>, timeout_secs: u64) -> Option<Self> {
+        let timeout_secs = if timeout_secs == 0 { DEFAULT_TIMEOUT_SECS } else { timeout_secs };
+        (!command.is_empty()).then(|| Self { image, command, timeout: Duration::from_secs(timeout_secs) })
+    }
+}
+
+/// Id a hook's container runs under: `<job>.<phase>`
+pub fn hook_job_id(job_id: &str, phase: HookPhase) -> String {
+    format!("{}.{}", job_id, phase.name())
+}
+
+/// Job a container id belongs to, for hook containers their job's
+pub fn owning_job(id: &str) -> &str {
+    HookPhase::ALL
+        .iter()
+        .find_map(|phase| id.strip_suffix(phase.name()).and_then(|id| id.strip_suffix('.')))
+        .unwrap_or(id)
+}
+
+/// The execution running `hook` for `job`
+pub fn hook_execution(job: &JobExecution, hook: &LifecycleHook, phase: HookPhase, job_status: Option<&str>) -> JobExecution {
+    let mut env = job.env.clone();
+    env.insert("TGP_JOB_ID".to_string(), job.job_id.clone());
+    env.insert("TGP_HOOK".to_string(), phase.name().to_string());
+    if let Some(status) = job_status {
+        env.insert("TGP_JOB_STATUS".to_string(), status.to_string());
+    }
+    JobExecution {
+        job_id: hook_job_id(&job.job_id, phase),
+        job_type: job.job_type.clone(),
+        container_image: if hook.image.is_empty() { job.container_image.clone() } else { hook.image.clone() },
+        cpu_limit: job.cpu_limit,
+        memory_limit_mb: job.memory_limit_mb,
+        disk_limit_gb: job.disk_limit_gb,
+        gpu_indices: Vec::new(),
+        port_bindings: HashMap::new(),
+        cpu_set: job.cpu_set.clone(),
+        numa_node: job.numa_node,
+        disk_io_mbps: job.disk_io_mbps,
+        egress_limit_mbps: job.egress_limit_mbps,
+        input: Vec::new(),
+        input_stdin: false,
+        output_path: None,
+        payload_digest: None,
+        command: Some(hook.command.clone()),
+        env,
+        pre_start_hook: None,
+        post_complete_hook: None,
+        datasets: Vec::new(),
+        dataset_dir: None,
+    }
+}
+
+/// Run a job's hook to completion, stopping it at its timeout
+pub async fn run(
+    runtime: &dyn ContainerRuntime,
+    job: &JobExecution,
+    hook: &LifecycleHook,
+    phase: HookPhase,
+    job_status: Option<&str>,
+) -> Result<JobResult> {
+    let execution = hook_execution(job, hook, phase, job_status);
+    let hook_id = execution.job_id.clone();
+    info!("Running {} hook of job {}", phase.name(), job.job_id);
+
+    let run = runtime.execute_job(execution, None);
+    tokio::pin!(run);
+    let mut timed_out = false;
+    let mut result = tokio::select! {
+        result = &mut run => result,
+        _ = tokio::time::sleep(hook.timeout) => {
+            warn!("{} hook of job {} ran over {}s, stopping it", phase.name(), job.job_id, hook.timeout.as_secs());
+            timed_out = true;
+            if let Err(e) = runtime.stop_job(&hook_id, 0).await {
+                warn!("Failed to stop {}: {:#}", hook_id, e);
+            }
+            // Still awaited, so the container is cleaned up
+            run.await
+        }
+    }?;
+
+    if timed_out {
+        result.success = false;
+        result.error = Some(format!("Timed out after {}s", hook.timeout.as_secs()));
+    }
+    Ok(result)
+}
+
+/// Final result of a job whose pre-start hook failed
+pub fn failed_job(job_id: &str, hook: JobResult) -> JobResult {
+    JobResult {
+        job_id: job_id.to_string(),
+        success: false,
+        exit_code: hook.exit_code,
+        output_hash: String::new(),
+        logs: hook.logs,
+        artifacts: HashMap::new(),
+        error: Some(format!(
+            "{} hook failed: {}",
+            HookPhase::PreStart.name(),
+            hook.error.unwrap_or_else(|| format!("exit code {}", hook.exit_code))
+        )),
+        output_archive: None,
+        peak_memory_gb: hook.peak_memory_gb,
+        peak_cpu_cores: hook.peak_cpu_cores,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hooks_run_apart_from_the_job() {
+        let job = JobExecution {
+            job_id: "job-1".to_string(),
+            job_type: "training".to_string(),
+            container_image: "pytorch/pytorch:2.1".to_string(),
+            cpu_limit: 4,
+            memory_limit_mb: 8192,
+            disk_limit_gb: 20,
+            gpu_indices: vec![0, 1],
+            port_bindings: HashMap::from([(8080, 30001)]),
+            cpu_set: Vec::new(),
+            numa_node: None,
+            disk_io_mbps: 0,
+            egress_limit_mbps: 0,
+            input: b"{}".to_vec(),
+            input_stdin: false,
+            output_path: Some("/scratch/model".to_string()),
+            payload_digest: None,
+            command: None,
+            env: HashMap::from([("MODEL".to_string(), "resnet".to_string())]),
+            pre_start_hook: None,
+            post_complete_hook: None,
+            datasets: Vec::new(),
+            dataset_dir: None,
+        };
+        let hook = LifecycleHook::new("curlimages/curl".to_string(), vec!["curl".to_string()], 0).unwrap();
+        assert_eq!(hook.timeout, Duration::from_secs(DEFAULT_TIMEOUT_SECS));
+        assert!(LifecycleHook::new(String::new(), Vec::new(), 60).is_none());
+
+        let post = hook_execution(&job, &hook, HookPhase::PostComplete, Some("failed"));
+        assert_eq!(post.job_id, "job-1.post-complete");
+        assert_eq!(post.container_image, "curlimages/curl");
+        assert!(post.gpu_indices.is_empty() && post.port_bindings.is_empty() && post.input.is_empty());
+        assert!(post.output_path.is_none());
+        assert_eq!(post.env["TGP_JOB_STATUS"], "failed");
+        assert_eq!(post.env["MODEL"], "resnet");
+
+        let own_image = LifecycleHook { image: String::new(), ..hook };
+        let pre = hook_execution(&job, &own_image, HookPhase::PreStart, None);
+        assert_eq!(pre.container_image, "pytorch/pytorch:2.1");
+        assert!(!pre.env.contains_key("TGP_JOB_STATUS"));
+
+        assert_eq!(owning_job("job-1.pre-start"), "job-1");
+        assert_eq!(owning_job("job-1.post-complete"), "job-1");
+        assert_eq!(owning_job("v1.2"), "v1.2");
+    }
+}
@@ -0,0 +1,157 @@
+//! Host resource probes behind `ResourceMonitor`
+//!
+//! /proc and `df` on Linux (and other Unix hosts), the Win32 system
+//! information and volume APIs on Windows. Figures are in cores and GB.
+
+use anyhow::{Context, Result};
+
+pub use platform::{cpu_count, disk_gb, hostname, memory_gb};
+
+/// CPU architecture in the scheduler's (OCI) naming: amd64, arm64, ...
+pub fn arch() -> &'static str {
+    match std::env::consts::ARCH {
+        "x86_64" => "amd64",
+        "aarch64" => "arm64",
+        "x86" => "386",
+        "powerpc64" => "ppc64le",
+        other => other,
+    }
+}
+
+const GB: f64 = 1024.0 * 1024.0 * 1024.0;
+
+/// (total, available) GB from /proc/meminfo
+pub fn parse_meminfo(meminfo: &str) -> Result<(f64, f64)> {
+    let field = |name: &str| {
+        meminfo
+            .lines()
+            .find(|line| line.starts_with(name))
+            .and_then(|line| line.split_whitespace().nth(1))
+            .and_then(|kb| kb.parse::<u64>().ok())
+    };
+    let total = field("MemTotal:").context("MemTotal not found")? as f64 * 1024.0 / GB;
+    let available = field("MemAvailable:").context("MemAvailable not found")? as f64 * 1024.0 / GB;
+    Ok((total, available))
+}
+
+/// (total, available) GB from `df -BG` output
+pub fn parse_df(stdout: &str) -> Result<(f64, f64)> {
+    let line = stdout.lines().nth(1).context("df output too short")?;
+    let parts: Vec<&str> = line.split_whitespace().collect();
+    if parts.len() < 4 {
+        anyhow::bail!("Invalid df output format");
+    }
+    let gb = |field: &str| field.trim_end_matches('G').parse::<f64>();
+    Ok((
+        gb(parts[1]).context("Failed to parse total disk")?,
+        gb(parts[3]).context("Failed to parse available disk")?,
+    ))
+}
+
+#[cfg(not(windows))]
+mod platform {
+    use anyhow::{Context, Result};
+    use std::fs;
+
+    pub fn hostname() -> Result<String> {
+        fs::read_to_string("/etc/hostname")
+            .context("Failed to read hostname")
+            .map(|s| s.trim().to_string())
+    }
+
+    pub fn cpu_count() -> Result<u32> {
+        let cpuinfo = fs::read_to_string("/proc/cpuinfo").context("Failed to read /proc/cpuinfo")?;
+        Ok(cpuinfo.lines().filter(|line| line.starts_with("processor")).count() as u32)
+    }
+
+    pub fn memory_gb() -> Result<(f64, f64)> {
+        super::parse_meminfo(&fs::read_to_string("/proc/meminfo").context("Failed to read /proc/meminfo")?)
+    }
+
+    pub fn disk_gb() -> Result<(f64, f64)> {
+        let output = std::process::Command::new("df")
+            .arg("-BG")
+            .arg("/")
+            .output()
+            .context("Failed to execute df command")?;
+        super::parse_df(&String::from_utf8_lossy(&output.stdout))
+    }
+}
+
+#[cfg(windows)]
+mod platform {
+    use anyhow::{Context, Result};
+    use std::os::windows::ffi::OsStrExt;
+    use windows_sys::Win32::Storage::FileSystem::GetDiskFreeSpaceExW;
+    use windows_sys::Win32::System::SystemInformation::{
+        GetSystemInfo, GlobalMemoryStatusEx, MEMORYSTATUSEX, SYSTEM_INFO,
+    };
+
+    use super::GB;
+
+    pub fn hostname() -> Result<String> {
+        hostname::get()
+            .context("Failed to read hostname")?
+            .into_string()
+            .map_err(|_| anyhow::anyhow!("Hostname is not valid Unicode"))
+    }
+
+    pub fn cpu_count() -> Result<u32> {
+        // SAFETY: GetSystemInfo only writes the struct it is given
+        let info = unsafe {
+            let mut info: SYSTEM_INFO = std::mem::zeroed();
+            GetSystemInfo(&mut info);
+            info
+        };
+        Ok(info.dwNumberOfProcessors)
+    }
+
+    pub fn memory_gb() -> Result<(f64, f64)> {
+        // SAFETY: dwLength is set as the API requires; it only writes the struct
+        let status = unsafe {
+            let mut status: MEMORYSTATUSEX = std::mem::zeroed();
+            status.dwLength = std::mem::size_of::<MEMORYSTATUSEX>() as u32;
+            if GlobalMemoryStatusEx(&mut status) == 0 {
+                return Err(std::io::Error::last_os_error()).context("GlobalMemoryStatusEx failed");
+            }
+            status
+        };
+        Ok((status.ullTotalPhys as f64 / GB, status.ullAvailPhys as f64 / GB))
+    }
+
+    /// Space on the system drive (`%SystemDrive%`, usually C:)
+    pub fn disk_gb() -> Result<(f64, f64)> {
+        let drive = std::env::var("SystemDrive").unwrap_or_else(|_| "C:".to_string());
+        let root: Vec<u16> = std::ffi::OsStr::new(&format!("{}\\", drive))
+            .encode_wide()
+            .chain(std::iter::once(0))
+            .collect();
+        let (mut available, mut total) = (0u64, 0u64);
+        // SAFETY: `root` is NUL-terminated and outlives the call
+        let ok = unsafe { GetDiskFreeSpaceExW(root.as_ptr(), &mut available, &mut total, std::ptr::null_mut()) };
+        if ok == 0 {
+            return Err(std::io::Error::last_os_error()).with_context(|| format!("Failed to query free space on {}", drive));
+        }
+        Ok((total as f64 / GB, available as f64 / GB))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_probes_parse_and_report_this_host() {
+        let (total, available) = parse_meminfo("MemTotal:       16384000 kB\nMemFree: 1 kB\nMemAvailable:    8192000 kB\n").unwrap();
+        assert!((total - 15.625).abs() < 1e-9 && (available - 7.8125).abs() < 1e-9);
+        assert!(parse_meminfo("MemTotal: 1 kB\n").is_err());
+
+        let df = "Filesystem 1G-blocks Used Available Use% Mounted on\n/dev/sda1 100G 40G 60G 40% /\n";
+        assert_eq!(parse_df(df).unwrap(), (100.0, 60.0));
+
+        // Whatever this host is, it has CPUs and memory
+        assert!(cpu_count().unwrap() > 0);
+        let (total, available) = memory_gb().unwrap();
+        assert!(total > 0.0 && available <= total);
+    }
+}
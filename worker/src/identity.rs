@@ -0,0 +1,86 @@
+//! Persistent worker identity
+//!
+//! The node id and a random instance key are kept in a small JSON file so
+//! they survive restarts. The scheduler fences registrations by the key: a
+//! restart presenting the same key supersedes the old process, while a
+//! different process claiming the same node id is refused.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::io::Read;
+use std::path::Path;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WorkerIdentity {
+    pub node_id: String,
+    pub instance_key: String,
+}
+
+fn generate_key() -> Result<String> {
+    let mut bytes = [0u8; 32];
+    std::fs::File::open("/dev/urandom")
+        .and_then(|mut urandom| urandom.read_exact(&mut bytes))
+        .context("Failed to read /dev/urandom")?;
+    Ok(bytes.iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+/// Load the identity at `path`, creating it on first start
+///
+/// A non-empty `node_id` (from configuration) replaces the stored one; the
+/// instance key is kept.
+pub fn load_or_create(path: &Path, node_id: &str, default_node_id: impl FnOnce() -> String) -> Result<WorkerIdentity> {
+    let stored = match std::fs::read_to_string(path) {
+        Ok(text) => Some(serde_json::from_str::<WorkerIdentity>(&text)
+            .with_context(|| format!("Invalid identity file {}", path.display()))?),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+        Err(e) => return Err(e).with_context(|| format!("Failed to read {}", path.display())),
+    };
+
+    let identity = match stored {
+        Some(stored) if node_id.is_empty() || stored.node_id == node_id => return Ok(stored),
+        Some(stored) => WorkerIdentity { node_id: node_id.to_string(), instance_key: stored.instance_key },
+        None => WorkerIdentity {
+            node_id: if node_id.is_empty() { default_node_id() } else { node_id.to_string() },
+            instance_key: generate_key()?,
+        },
+    };
+
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).context("Failed to create identity directory")?;
+    }
+    std::fs::write(path, serde_json::to_vec_pretty(&identity)?)
+        .with_context(|| format!("Failed to write {}", path.display()))?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))
+            .context("Failed to restrict identity file permissions")?;
+    }
+    Ok(identity)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_identity_survives_restarts_and_renames() {
+        let path = std::env::temp_dir()
+            .join(format!("tgp-identity-test-{}", std::process::id()))
+            .join("identity.json");
+
+        let first = load_or_create(&path, "", || "host-a".to_string()).unwrap();
+        assert_eq!(first.node_id, "host-a");
+        assert_eq!(first.instance_key.len(), 64);
+
+        // Hostname changes do not change a stored id
+        assert_eq!(load_or_create(&path, "", || "host-b".to_string()).unwrap(), first);
+
+        // A configured id replaces the stored one but keeps the key
+        let renamed = load_or_create(&path, "gpu-7", || "host-a".to_string()).unwrap();
+        assert_eq!(renamed.node_id, "gpu-7");
+        assert_eq!(renamed.instance_key, first.instance_key);
+
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+}
@@ -0,0 +1,45 @@
+//! Job input - scheduler → worker → container
+//!
+//! A job's input (JSON args or a small file, stored with the job) is
+//! written next to the progress file and named by `$TGP_INPUT_FILE`
+//! (`/tgp/input`). Commands can refer to it as `{input}`, since Docker
+//! does not expand variables in exec-form commands:
+//!
+//! ```text
+//! python train.py --config {input}
+//! ```
+//!
+//! Jobs submitted with `input_stdin` get it on stdin instead.
+
+use anyhow::{Context, Result};
+use std::path::Path;
+
+/// Input file path as seen from inside the container
+pub const CONTAINER_INPUT_FILE: &str = "/tgp/input";
+
+/// Placeholder in command arguments replaced by the input file path
+const INPUT_PLACEHOLDER: &str = "{input}";
+
+/// Command with `{input}` replaced by the container input file path
+pub fn substitute(command: Vec<String>) -> Vec<String> {
+    command
+        .into_iter()
+        .map(|arg| arg.replace(INPUT_PLACEHOLDER, CONTAINER_INPUT_FILE))
+        .collect()
+}
+
+/// Write the input into the job directory mounted at `/tgp`
+pub fn write(job_dir: &Path, input: &[u8]) -> Result<()> {
+    std::fs::write(job_dir.join("input"), input).context("Failed to write job input")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_substitute_input_placeholder() {
+        let command = vec!["python".to_string(), "run.py".to_string(), "--args={input}".to_string()];
+        assert_eq!(substitute(command), vec!["python", "run.py", "--args=/tgp/input"]);
+    }
+}
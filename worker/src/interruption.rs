@@ -0,0 +1,123 @@
+//! Spot interruption watcher
+//!
+//! Preemptible instances poll the provider's metadata endpoint for a
+//! termination notice (AWS serves `spot/instance-action` with 404 until the
+//! instance is scheduled for reclaim). The first notice is relayed to the
+//! scheduler, which moves the node's jobs elsewhere; their containers are
+//! then stopped gracefully so they can checkpoint before the instance goes.
+
+use anyhow::{Context, Result};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tonic::transport::Channel;
+use tracing::{info, warn};
+
+use crate::executor::JobExecutor;
+use crate::proto::{scheduler_service_client::SchedulerServiceClient, InterruptionNotice};
+
+/// AWS spot termination notice endpoint
+pub const AWS_INSTANCE_ACTION_URL: &str = "http://169.254.169.254/latest/meta-data/spot/instance-action";
+
+/// Time between metadata polls
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Grace period for containers to checkpoint before they are killed
+const STOP_GRACE_SECS: i64 = 60;
+
+/// Poll `url` until a termination notice appears, then relay it once
+pub async fn watch(mut client: SchedulerServiceClient<Channel>, node_id: String, url: String) {
+    info!("Watching {} for interruption notices", url);
+
+    let mut ticker = tokio::time::interval(POLL_INTERVAL);
+    let reason = loop {
+        ticker.tick().await;
+        match http_get(&url, &[]).await {
+            Ok((200, body)) if is_notice(&body) => break body,
+            Ok(_) => {}
+            Err(e) => warn!("Interruption poll failed: {}", e),
+        }
+    };
+
+    warn!("Interruption notice received: {}", reason);
+
+    // The notice carries no reliable clock; the scheduler assumes two minutes
+    let request = tonic::Request::new(InterruptionNotice {
+        node_id,
+        terminate_at: 0,
+        reason,
+    });
+    let requeued = match client.report_interruption(request).await {
+        Ok(ack) => ack.into_inner().requeued_jobs,
+        Err(e) => {
+            warn!("Failed to report interruption: {}", e);
+            return;
+        }
+    };
+    info!("Scheduler requeued {} jobs", requeued.len());
+
+    let executor = match JobExecutor::new() {
+        Ok(executor) => executor,
+        Err(e) => {
+            warn!("Cannot stop containers of requeued jobs: {}", e);
+            return;
+        }
+    };
+    for job_id in requeued {
+        if let Err(e) = executor.stop_job(&job_id, STOP_GRACE_SECS).await {
+            warn!("{}", e);
+        }
+    }
+}
+
+/// Whether a metadata response body announces a termination
+fn is_notice(body: &str) -> bool {
+    let body = body.trim();
+    !body.is_empty() && !body.eq_ignore_ascii_case("false")
+}
+
+/// Minimal plain-HTTP GET returning status code and body (also used for
+/// the metadata lookups in `location`)
+pub(crate) async fn http_get(url: &str, headers: &[(&str, &str)]) -> Result<(u16, String)> {
+    let rest = url.strip_prefix("http://")
+        .context("Only http:// metadata URLs are supported")?;
+    let (authority, path) = match rest.find('/') {
+        Some(i) => (&rest[..i], &rest[i..]),
+        None => (rest, "/"),
+    };
+    let (host, addr) = match authority.rsplit_once(':') {
+        Some((host, _)) => (host, authority.to_string()),
+        None => (authority, format!("{}:80", authority)),
+    };
+
+    let mut stream = tokio::time::timeout(Duration::from_secs(2), TcpStream::connect(&addr))
+        .await
+        .context("Metadata endpoint timed out")??;
+    let extra: String = headers.iter().map(|(name, value)| format!("{}: {}\r\n", name, value)).collect();
+    let request = format!("GET {} HTTP/1.0\r\nHost: {}\r\n{}Connection: close\r\n\r\n", path, host, extra);
+    stream.write_all(request.as_bytes()).await?;
+
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await?;
+
+    let status = response.split_whitespace()
+        .nth(1)
+        .and_then(|code| code.parse().ok())
+        .context("Malformed HTTP response")?;
+    let body = response.split_once("\r\n\r\n")
+        .map(|(_, body)| body.to_string())
+        .unwrap_or_default();
+    Ok((status, body))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_notice() {
+        assert!(is_notice("{\"action\": \"terminate\", \"time\": \"2026-10-15T08:22:00Z\"}"));
+        assert!(!is_notice("  \n"));
+        assert!(!is_notice("FALSE"));
+    }
+}
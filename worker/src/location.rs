@@ -0,0 +1,150 @@
+//! Node location detection
+//!
+//! The scheduler keys its link-bandwidth matrix and pool data-residency
+//! rules by node location, so it has to name where the node really is.
+//! `TGP_LOCATION` wins when set. Otherwise the cloud metadata endpoints are
+//! asked for the region (AWS, GCP, Azure, DigitalOcean, Hetzner), then a
+//! geo-IP service (`TGP_GEOIP_URL`, empty to disable) for the country and
+//! region. Locations are `<provider>:<region>`, e.g. `aws:eu-west-1` or
+//! `geo:de-he`; a node nothing answers for is `unknown`.
+
+use std::time::Duration;
+use tracing::{debug, info};
+
+use crate::interruption::http_get;
+
+/// Default geo-IP lookup (plain HTTP, `<country>\n<region>`)
+pub const DEFAULT_GEOIP_URL: &str = "http://ip-api.com/line/?fields=countryCode,region";
+
+/// Location of a node nothing could place
+pub const UNKNOWN: &str = "unknown";
+
+/// Time allowed for each lookup
+const PROBE_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// A cloud metadata endpoint naming the instance's region or zone
+struct Probe {
+    provider: &'static str,
+    url: &'static str,
+    headers: &'static [(&'static str, &'static str)],
+}
+
+const PROBES: &[Probe] = &[
+    Probe {
+        provider: "aws",
+        url: "http://169.254.169.254/latest/meta-data/placement/region",
+        headers: &[],
+    },
+    Probe {
+        provider: "gcp",
+        url: "http://metadata.google.internal/computeMetadata/v1/instance/zone",
+        headers: &[("Metadata-Flavor", "Google")],
+    },
+    Probe {
+        provider: "azure",
+        url: "http://169.254.169.254/metadata/instance/compute/location?api-version=2021-02-01&format=text",
+        headers: &[("Metadata", "true")],
+    },
+    Probe {
+        provider: "do",
+        url: "http://169.254.169.254/metadata/v1/region",
+        headers: &[],
+    },
+    Probe {
+        provider: "hetzner",
+        url: "http://169.254.169.254/hetzner/v1/metadata/region",
+        headers: &[],
+    },
+];
+
+/// A metadata answer as a region: GCP answers
+/// `projects/<n>/zones/<region>-<zone>`, the others the region itself
+fn region_of(provider: &str, body: &str) -> Option<String> {
+    let body = body.trim();
+    // Error pages and JSON documents are not a region name
+    if body.is_empty() || body.len() > 64 || body.contains(char::is_whitespace) || body.contains(['{', '<']) {
+        return None;
+    }
+    let region = match provider {
+        "gcp" => {
+            let zone = body.rsplit('/').next()?;
+            zone.rsplit_once('-').map_or(zone, |(region, _)| region)
+        }
+        _ => body,
+    };
+    Some(region.to_ascii_lowercase())
+}
+
+/// `geo:<country>-<region>` from a `<country>\n<region>` answer
+fn geo_location(body: &str) -> Option<String> {
+    let parts: Vec<&str> = body.lines().map(str::trim).filter(|line| !line.is_empty()).collect();
+    let country = parts.first().filter(|country| country.len() == 2)?;
+    let location = match parts.get(1) {
+        Some(region) => format!("geo:{}-{}", country, region),
+        None => format!("geo:{}", country),
+    };
+    Some(location.to_ascii_lowercase())
+}
+
+async fn fetch(url: &str, headers: &[(&str, &str)]) -> Option<String> {
+    match tokio::time::timeout(PROBE_TIMEOUT, http_get(url, headers)).await {
+        Ok(Ok((200, body))) => Some(body),
+        Ok(Ok((status, _))) => {
+            debug!("{} answered {}", url, status);
+            None
+        }
+        Ok(Err(e)) => {
+            debug!("{} unreachable: {:#}", url, e);
+            None
+        }
+        Err(_) => None,
+    }
+}
+
+/// Where this node is: the override, the cloud region, the geo-IP region,
+/// else `unknown`
+pub async fn detect(configured: &str, geoip_url: &str) -> String {
+    if !configured.trim().is_empty() {
+        return configured.trim().to_string();
+    }
+
+    // All providers at once; the first to name a region wins, in order
+    let answers = futures_util::future::join_all(PROBES.iter().map(|probe| fetch(probe.url, probe.headers))).await;
+    for (probe, answer) in PROBES.iter().zip(answers) {
+        if let Some(region) = answer.and_then(|body| region_of(probe.provider, &body)) {
+            let location = format!("{}:{}", probe.provider, region);
+            info!("Detected location {} from {} instance metadata", location, probe.provider);
+            return location;
+        }
+    }
+
+    if !geoip_url.is_empty() {
+        if let Some(location) = fetch(geoip_url, &[]).await.and_then(|body| geo_location(&body)) {
+            info!("Detected location {} by geo-IP", location);
+            return location;
+        }
+    }
+    info!("Could not detect this node's location; set TGP_LOCATION");
+    UNKNOWN.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_location_from_metadata_answers() {
+        assert_eq!(region_of("aws", "eu-west-1\n").as_deref(), Some("eu-west-1"));
+        assert_eq!(region_of("gcp", "projects/123456/zones/us-central1-a").as_deref(), Some("us-central1"));
+        assert_eq!(region_of("azure", "westeurope").as_deref(), Some("westeurope"));
+        assert_eq!(region_of("do", "<html>Not Found</html>"), None);
+        assert_eq!(region_of("hetzner", ""), None);
+
+        assert_eq!(geo_location("DE\nHE\n").as_deref(), Some("geo:de-he"));
+        assert_eq!(geo_location("SG").as_deref(), Some("geo:sg"));
+        assert_eq!(geo_location("fail\n"), None);
+
+        // The override skips every lookup
+        assert_eq!(detect(" eu-lab-1 ", "").await, "eu-lab-1");
+    }
+}
@@ -0,0 +1,336 @@
+//! Job log persistence
+//!
+//! Container output is streamed to disk while the job runs rather than
+//! collected in memory once it exits. Each job writes
+//! `<TGP_LOG_DIR>/<job id>/job.log`, rotated to `job.log.1`, `job.log.2`, ...
+//! every TGP_LOG_FILE_MB (default 16), keeping TGP_LOG_MAX_FILES files
+//! (default 4). Output beyond TGP_LOG_JOB_MB (default 256) is dropped. Only
+//! the last TGP_LOG_TAIL_KB (default 64) is held in memory; that tail is
+//! what the scheduler receives.
+//!
+//! Once a job's status has been reported its logs are kept for
+//! TGP_LOG_RETENTION_SECS (default one day) and then removed.
+
+use anyhow::{Context, Result};
+use bollard::container::LogsOptions;
+use bollard::Docker;
+use futures_util::stream::StreamExt;
+use sha2::{Digest, Sha256};
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use tokio::io::AsyncWriteExt;
+use tracing::{info, warn};
+
+/// Current log file of a job; rotated files get a numeric suffix
+const LOG_FILE: &str = "job.log";
+
+/// Written into a job's log directory once its status has been reported
+const REPORTED_MARKER: &str = ".reported";
+
+/// How often expired logs are looked for
+const GC_INTERVAL: Duration = Duration::from_secs(600);
+
+/// Where and how much job output is kept
+#[derive(Debug, Clone, PartialEq)]
+pub struct LogConfig {
+    pub dir: PathBuf,
+    /// Rotate the current file once it reaches this size
+    pub file_bytes: u64,
+    /// Files kept per job, the current one included
+    pub max_files: u32,
+    /// Output written per job before the rest is dropped
+    pub job_bytes: u64,
+    /// Output kept in memory and reported to the scheduler
+    pub tail_bytes: usize,
+    /// How long reported logs stay on disk
+    pub retention: Duration,
+}
+
+impl Default for LogConfig {
+    fn default() -> Self {
+        Self {
+            dir: PathBuf::from("/var/lib/tgp/logs"),
+            file_bytes: 16 * 1024 * 1024,
+            max_files: 4,
+            job_bytes: 256 * 1024 * 1024,
+            tail_bytes: 64 * 1024,
+            retention: Duration::from_secs(24 * 3600),
+        }
+    }
+}
+
+impl LogConfig {
+    pub fn from_env() -> Self {
+        fn var<T: std::str::FromStr>(name: &str) -> Option<T> {
+            std::env::var(name).ok().and_then(|v| v.parse().ok())
+        }
+        let defaults = Self::default();
+        Self {
+            dir: std::env::var("TGP_LOG_DIR").map(PathBuf::from).unwrap_or(defaults.dir),
+            file_bytes: var::<u64>("TGP_LOG_FILE_MB").map_or(defaults.file_bytes, |mb| mb.max(1) * 1024 * 1024),
+            max_files: var::<u32>("TGP_LOG_MAX_FILES").map_or(defaults.max_files, |n| n.max(1)),
+            job_bytes: var::<u64>("TGP_LOG_JOB_MB").map_or(defaults.job_bytes, |mb| mb * 1024 * 1024),
+            tail_bytes: var::<usize>("TGP_LOG_TAIL_KB").map_or(defaults.tail_bytes, |kb| kb * 1024),
+            retention: var::<u64>("TGP_LOG_RETENTION_SECS").map_or(defaults.retention, Duration::from_secs),
+        }
+    }
+
+    /// Log directory of one job
+    pub fn job_dir(&self, job_id: &str) -> PathBuf {
+        self.dir.join(job_id)
+    }
+}
+
+/// The last `cap` bytes of a stream
+#[derive(Debug)]
+pub struct LogTail {
+    buf: VecDeque<u8>,
+    cap: usize,
+}
+
+impl LogTail {
+    pub fn new(cap: usize) -> Self {
+        Self { buf: VecDeque::with_capacity(cap.min(64 * 1024)), cap }
+    }
+
+    pub fn push(&mut self, data: &[u8]) {
+        let data = &data[data.len().saturating_sub(self.cap)..];
+        let overflow = (self.buf.len() + data.len()).saturating_sub(self.cap);
+        self.buf.drain(..overflow);
+        self.buf.extend(data);
+    }
+
+    pub fn to_string_lossy(&self) -> String {
+        let (front, back) = self.buf.as_slices();
+        String::from_utf8_lossy(&[front, back].concat()).into_owned()
+    }
+}
+
+/// A job's size-capped, rotated log files
+pub struct JobLog {
+    dir: PathBuf,
+    config: LogConfig,
+    file: tokio::fs::File,
+    /// Bytes in the current file
+    file_written: u64,
+    /// Bytes written for the job across all files
+    written: u64,
+    truncated: bool,
+}
+
+impl JobLog {
+    pub async fn create(config: &LogConfig, job_id: &str) -> Result<Self> {
+        let dir = config.job_dir(job_id);
+        tokio::fs::create_dir_all(&dir).await
+            .with_context(|| format!("Failed to create log directory {}", dir.display()))?;
+        let file = tokio::fs::File::create(dir.join(LOG_FILE)).await
+            .context("Failed to create log file")?;
+        Ok(Self {
+            dir,
+            config: config.clone(),
+            file,
+            file_written: 0,
+            written: 0,
+            truncated: false,
+        })
+    }
+
+    /// Append output, rotating and dropping what is over the job's cap
+    pub async fn write(&mut self, data: &[u8]) -> Result<()> {
+        if self.truncated {
+            return Ok(());
+        }
+        let room = self.config.job_bytes.saturating_sub(self.written);
+        let data = &data[..data.len().min(room as usize)];
+        if self.file_written > 0 && self.file_written + data.len() as u64 > self.config.file_bytes {
+            self.rotate().await?;
+        }
+        self.file.write_all(data).await.context("Failed to write log file")?;
+        self.file_written += data.len() as u64;
+        self.written += data.len() as u64;
+
+        if self.written >= self.config.job_bytes {
+            self.truncated = true;
+            let note = format!("\n[log truncated at {} bytes]\n", self.config.job_bytes);
+            self.file.write_all(note.as_bytes()).await.context("Failed to write log file")?;
+        }
+        Ok(())
+    }
+
+    /// Whether output was dropped for going over the job's cap
+    pub fn truncated(&self) -> bool {
+        self.truncated
+    }
+
+    pub async fn close(mut self) -> Result<()> {
+        self.file.flush().await.context("Failed to write log file")
+    }
+
+    /// Shift `job.log.N` to `job.log.N+1` (dropping the oldest) and start
+    /// a new current file
+    async fn rotate(&mut self) -> Result<()> {
+        self.file.flush().await.context("Failed to write log file")?;
+        let path = |n: u32| match n {
+            0 => self.dir.join(LOG_FILE),
+            n => self.dir.join(format!("{}.{}", LOG_FILE, n)),
+        };
+        let last = self.config.max_files.saturating_sub(1);
+        if last == 0 {
+            let _ = tokio::fs::remove_file(path(0)).await;
+        } else {
+            let _ = tokio::fs::remove_file(path(last)).await;
+            for n in (0..last).rev() {
+                let _ = tokio::fs::rename(path(n), path(n + 1)).await;
+            }
+        }
+        self.file = tokio::fs::File::create(path(0)).await.context("Failed to create log file")?;
+        self.file_written = 0;
+        Ok(())
+    }
+}
+
+/// What is kept of a finished job's output
+pub struct CollectedLogs {
+    /// The last `tail_bytes` of output
+    pub tail: String,
+    /// SHA-256 over the whole output, including anything dropped
+    pub hasher: Sha256,
+}
+
+/// Follow a container's output until it exits, writing it to the job's
+/// log files
+pub async fn collect(docker: Docker, container_id: String, job_id: String, config: LogConfig) -> Result<CollectedLogs> {
+    let mut log = JobLog::create(&config, &job_id).await?;
+    let mut tail = LogTail::new(config.tail_bytes);
+    let mut hasher = Sha256::new();
+
+    let options = Some(LogsOptions::<String> {
+        follow: true,
+        stdout: true,
+        stderr: true,
+        ..Default::default()
+    });
+    let mut stream = docker.logs(&container_id, options);
+    while let Some(result) = stream.next().await {
+        match result {
+            Ok(output) => {
+                let bytes = output.into_bytes();
+                hasher.update(&bytes);
+                tail.push(&bytes);
+                log.write(&bytes).await?;
+            }
+            Err(e) => warn!("Error reading logs of job {}: {}", job_id, e),
+        }
+    }
+
+    if log.truncated() {
+        warn!("Logs of job {} were truncated at {} bytes", job_id, config.job_bytes);
+    }
+    log.close().await?;
+    Ok(CollectedLogs { tail: tail.to_string_lossy(), hasher })
+}
+
+/// Start a reported job's retention period, or remove its logs right away
+/// when nothing is retained
+pub fn mark_reported(config: &LogConfig, job_id: &str) {
+    let dir = config.job_dir(job_id);
+    if !dir.exists() {
+        return;
+    }
+    let result = if config.retention.is_zero() {
+        std::fs::remove_dir_all(&dir)
+    } else {
+        std::fs::write(dir.join(REPORTED_MARKER), b"")
+    };
+    if let Err(e) = result {
+        warn!("Failed to release logs of job {}: {}", job_id, e);
+    }
+}
+
+/// Remove logs of jobs reported more than `retention` ago; returns how
+/// many jobs' logs were removed
+pub fn collect_garbage(config: &LogConfig, now: SystemTime) -> usize {
+    let Ok(entries) = std::fs::read_dir(&config.dir) else {
+        return 0;
+    };
+    entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|dir| expired(dir, config.retention, now))
+        .filter(|dir| match std::fs::remove_dir_all(dir) {
+            Ok(()) => true,
+            Err(e) => {
+                warn!("Failed to remove logs {}: {}", dir.display(), e);
+                false
+            }
+        })
+        .count()
+}
+
+/// Whether a job's logs were reported at least `retention` before `now`
+fn expired(dir: &Path, retention: Duration, now: SystemTime) -> bool {
+    std::fs::metadata(dir.join(REPORTED_MARKER))
+        .and_then(|meta| meta.modified())
+        .ok()
+        .and_then(|reported| now.duration_since(reported).ok())
+        .is_some_and(|age| age >= retention)
+}
+
+/// Remove expired logs periodically, forever
+pub async fn run_gc(config: LogConfig) {
+    let mut interval = tokio::time::interval(GC_INTERVAL);
+    loop {
+        interval.tick().await;
+        let gc_config = config.clone();
+        let removed = tokio::task::spawn_blocking(move || collect_garbage(&gc_config, SystemTime::now()))
+            .await
+            .unwrap_or(0);
+        if removed > 0 {
+            info!("Removed expired logs of {} jobs", removed);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_logs_rotate_cap_and_expire() {
+        let dir = std::env::temp_dir().join(format!("tgp-logs-test-{}", std::process::id()));
+        let config = LogConfig {
+            dir: dir.clone(),
+            file_bytes: 10,
+            max_files: 2,
+            job_bytes: 25,
+            tail_bytes: 8,
+            retention: Duration::from_secs(60),
+        };
+
+        let mut tail = LogTail::new(config.tail_bytes);
+        let mut log = JobLog::create(&config, "job-1").await.unwrap();
+        for chunk in [b"aaaaaaaa", b"bbbbbbbb", b"cccccccc", b"dddddddd"] {
+            tail.push(chunk);
+            log.write(chunk).await.unwrap();
+        }
+        assert!(log.truncated());
+        log.close().await.unwrap();
+        assert_eq!(tail.to_string_lossy(), "dddddddd");
+
+        // Only the newest two files survive rotation
+        let job_dir = config.job_dir("job-1");
+        assert_eq!(std::fs::read_to_string(job_dir.join("job.log.1")).unwrap(), "bbbbbbbb");
+        assert!(std::fs::read_to_string(job_dir.join("job.log")).unwrap().starts_with("ccccccccd"));
+        assert!(!job_dir.join("job.log.2").exists());
+
+        // Unreported logs are never collected; reported ones once expired
+        let later = SystemTime::now() + Duration::from_secs(120);
+        assert_eq!(collect_garbage(&config, later), 0);
+        mark_reported(&config, "job-1");
+        assert_eq!(collect_garbage(&config, SystemTime::now()), 0);
+        assert_eq!(collect_garbage(&config, later), 1);
+        assert!(!job_dir.exists());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
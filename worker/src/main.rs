@@ -13,42 +13,113 @@
 //! - Performance: Efficient resource monitoring, minimal overhead
 //! - Testability: Modular design, mockable components
 
+mod allocatable;
+mod artifacts;
+mod benchmark;
+mod blkio;
+mod command_stream;
+mod control;
+mod data_service;
+mod delta;
+mod egress;
+mod exec;
 mod executor;
+mod gpu_topology;
+mod hooks;
+mod host;
+mod identity;
+mod input;
+mod interruption;
+mod location;
+mod logs;
+mod numa;
+mod outputs;
+mod payloads;
+mod progress;
+mod runtime;
+mod scheduler_client;
+mod scratch;
+mod self_update;
+mod speedtest;
+mod status;
+mod usage;
 
 use anyhow::{Context, Result};
 use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
 use std::time::Duration;
-use tonic::transport::Channel;
 use tracing::{error, info, warn};
 
 // Include generated gRPC client code
 pub mod proto {
+    // Command messages carry a whole job assignment
+    #![allow(clippy::large_enum_variant)]
     tonic::include_proto!("tgp.scheduler.v1");
 }
 
-use proto::{
-    scheduler_service_client::SchedulerServiceClient,
-    RegisterNodeRequest, ResourceReport,
-};
+use proto::{LocalDataset, RegisterNodeRequest, ResourceAck, ResourceReport};
+use scheduler_client::{GrpcConnector, SchedulerClient, SchedulerConnector};
+
+/// While dormant, report only on every Nth tick to learn when to wake
+const DORMANT_CHECK_EVERY: u64 = 10;
+
+/// Clock offset from the scheduler worth warning about
+const MAX_CLOCK_SKEW_SECS: i64 = 30;
 
 /// Worker configuration
 #[derive(Debug, Clone)]
 struct WorkerConfig {
     node_id: String,
+    /// Where the node id and instance key persist across restarts
+    identity_file: PathBuf,
+    /// Secret proving a registration comes from this worker (see `identity`)
+    instance_key: String,
     scheduler_url: String,
     report_interval_secs: u64,
     reconnect_delay_secs: u64,
     max_retries: u32,
+    data_dir: PathBuf,
+    data_listen_addr: String,
+    data_advertise_addr: String,
+    /// Secret workers present to each other's data services (empty: no
+    /// data service, datasets are not pulled from peers)
+    data_token: String,
+    control_listen_addr: String,
+    control_advertise_addr: String,
+    /// Secret the scheduler presents on control calls (empty: no control service)
+    control_token: String,
+    /// Local HTTP status endpoint (empty: disabled)
+    status_listen_addr: String,
+    /// Keep a worker-initiated command stream open (for nodes behind NAT)
+    command_stream: bool,
+    /// Spot/preemptible instance that watches for termination notices
+    preemptible: bool,
+    interruption_url: String,
+    /// Resources held back for the OS, container runtime and agent
+    reserve: allocatable::ReserveConfig,
+    /// Known network bandwidth in Mbit/s (None: measure at registration)
+    network_mbps: Option<u32>,
+    /// Marketplace provider that owns this node (empty: operator-owned)
+    provider: String,
+    /// Where the node is, e.g. aws:eu-west-1 (empty: detect, see `location`)
+    location: String,
+    /// Geo-IP lookup used when no cloud metadata answers (empty: none)
+    geoip_url: String,
+    /// Operator labels the scheduler groups nodes into pools by
+    labels: std::collections::HashMap<String, String>,
 }
 
 impl WorkerConfig {
     fn from_env() -> Self {
-        Self {
-            node_id: std::env::var("TGP_NODE_ID")
-                .unwrap_or_else(|_| hostname::get()
-                    .ok()
-                    .and_then(|h| h.into_string().ok())
-                    .unwrap_or_else(|| "worker-unknown".to_string())),
+        let mut config = Self {
+            // Empty: the stored identity's id, else the hostname
+            node_id: std::env::var("TGP_NODE_ID").unwrap_or_default(),
+            identity_file: std::env::var("TGP_IDENTITY_FILE")
+                .map(PathBuf::from)
+                .unwrap_or_else(|_| PathBuf::from("/var/lib/tgp/identity.json")),
+            instance_key: String::new(),
             scheduler_url: std::env::var("TGP_SCHEDULER_URL")
                 .unwrap_or_else(|_| "http://YOUR_SCHEDULER_IP:50051".to_string()),
             report_interval_secs: std::env::var("TGP_REPORT_INTERVAL")
@@ -63,7 +134,68 @@ impl WorkerConfig {
                 .ok()
                 .and_then(|v| v.parse().ok())
                 .unwrap_or(5),
+            data_dir: std::env::var("TGP_DATA_DIR")
+                .map(PathBuf::from)
+                .unwrap_or_else(|_| PathBuf::from("/var/lib/tgp/datasets")),
+            data_listen_addr: std::env::var("TGP_DATA_LISTEN_ADDR")
+                .unwrap_or_else(|_| "0.0.0.0:50052".to_string()),
+            // Empty: node is not reachable by peers (e.g. behind NAT)
+            data_advertise_addr: std::env::var("TGP_DATA_ADVERTISE_ADDR")
+                .unwrap_or_default(),
+            data_token: std::env::var("TGP_DATA_TOKEN")
+                .unwrap_or_default(),
+            // Loopback unless the operator exposes it
+            control_listen_addr: std::env::var("TGP_CONTROL_LISTEN_ADDR")
+                .unwrap_or_else(|_| "127.0.0.1:50053".to_string()),
+            // Empty: the scheduler cannot reach this node's control service
+            control_advertise_addr: std::env::var("TGP_CONTROL_ADVERTISE_ADDR")
+                .unwrap_or_default(),
+            control_token: std::env::var("TGP_WORKER_CONTROL_TOKEN")
+                .unwrap_or_default(),
+            status_listen_addr: std::env::var("TGP_STATUS_LISTEN_ADDR")
+                .unwrap_or_else(|_| "127.0.0.1:9102".to_string()),
+            // Defaults to on when the control service is not advertised
+            command_stream: std::env::var("TGP_COMMAND_STREAM")
+                .map(|v| v == "1" || v.eq_ignore_ascii_case("true"))
+                .unwrap_or_else(|_| std::env::var("TGP_CONTROL_ADVERTISE_ADDR").map_or(true, |v| v.is_empty())),
+            preemptible: std::env::var("TGP_PREEMPTIBLE")
+                .map(|v| v == "1" || v.eq_ignore_ascii_case("true"))
+                .unwrap_or(false),
+            interruption_url: std::env::var("TGP_INTERRUPTION_URL")
+                .unwrap_or_else(|_| interruption::AWS_INSTANCE_ACTION_URL.to_string()),
+            provider: std::env::var("TGP_PROVIDER").unwrap_or_default(),
+            location: std::env::var("TGP_LOCATION").unwrap_or_default(),
+            geoip_url: std::env::var("TGP_GEOIP_URL")
+                .unwrap_or_else(|_| location::DEFAULT_GEOIP_URL.to_string()),
+            // e.g. TGP_NODE_LABELS=gpu=a100,tier=prod
+            labels: std::env::var("TGP_NODE_LABELS")
+                .unwrap_or_default()
+                .split(',')
+                .filter_map(|pair| pair.split_once('='))
+                .map(|(key, value)| (key.trim().to_string(), value.trim().to_string()))
+                .collect(),
+            reserve: allocatable::ReserveConfig::from_env(),
+            network_mbps: std::env::var("TGP_NETWORK_MBPS")
+                .ok()
+                .and_then(|v| v.parse().ok()),
+        };
+
+        // The data service is only served to peers holding the data token
+        if !config.data_advertise_addr.is_empty() && config.data_token.trim().is_empty() {
+            warn!("TGP_DATA_ADVERTISE_ADDR is set without TGP_DATA_TOKEN, data service disabled");
+            config.data_advertise_addr.clear();
+        }
+
+        // Without a token the control service is not served, so the
+        // scheduler must not be told to dial it
+        if !config.control_advertise_addr.is_empty() && config.control_token.trim().is_empty() {
+            warn!("TGP_CONTROL_ADVERTISE_ADDR is set without TGP_WORKER_CONTROL_TOKEN, control service disabled");
+            config.control_advertise_addr.clear();
+            if std::env::var("TGP_COMMAND_STREAM").is_err() {
+                config.command_stream = true;
+            }
         }
+        config
     }
 }
 
@@ -73,20 +205,12 @@ struct ResourceMonitor;
 impl ResourceMonitor {
     /// Get hostname with fallback
     fn get_hostname() -> Result<String> {
-        fs::read_to_string("/etc/hostname")
-            .context("Failed to read hostname")
-            .map(|s| s.trim().to_string())
+        host::hostname()
     }
 
-    /// Get CPU count from /proc/cpuinfo
+    /// Get CPU count
     fn get_cpu_info() -> Result<(u32, u32)> {
-        let cpuinfo = fs::read_to_string("/proc/cpuinfo")
-            .context("Failed to read /proc/cpuinfo")?;
-        
-        let cpu_count = cpuinfo
-            .lines()
-            .filter(|line| line.starts_with("processor"))
-            .count() as u32;
+        let cpu_count = host::cpu_count()?;
 
         if cpu_count == 0 {
             anyhow::bail!("No CPUs detected");
@@ -97,84 +221,87 @@ impl ResourceMonitor {
         Ok((cpu_count, cpu_count))
     }
 
-    /// Get memory info from /proc/meminfo
+    /// Get total and available memory in GB
     fn get_memory_info() -> Result<(f64, f64)> {
-        let meminfo = fs::read_to_string("/proc/meminfo")
-            .context("Failed to read /proc/meminfo")?;
-        
-        let mut total_kb = None;
-        let mut available_kb = None;
-
-        for line in meminfo.lines() {
-            if line.starts_with("MemTotal:") {
-                total_kb = line
-                    .split_whitespace()
-                    .nth(1)
-                    .and_then(|s| s.parse::<u64>().ok());
-            } else if line.starts_with("MemAvailable:") {
-                available_kb = line
-                    .split_whitespace()
-                    .nth(1)
-                    .and_then(|s| s.parse::<u64>().ok());
-            }
+        host::memory_gb()
+    }
+
+    /// Get total and available disk in GB
+    fn get_disk_info() -> Result<(f64, f64)> {
+        host::disk_gb()
+    }
 
-            if total_kb.is_some() && available_kb.is_some() {
-                break;
+    /// List datasets held locally: every subdirectory of `data_dir` is a
+    /// dataset named after the directory, sized by its contents
+    fn get_local_datasets(data_dir: &Path) -> Result<Vec<(String, f64)>> {
+        let entries = match fs::read_dir(data_dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e).context("Failed to read data directory"),
+        };
+
+        let mut datasets = Vec::new();
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if !path.is_dir() {
+                continue;
+            }
+            if let Some(name) = entry.file_name().to_str() {
+                let size_gb = Self::dir_size_bytes(&path) as f64 / 1024.0 / 1024.0 / 1024.0;
+                datasets.push((name.to_string(), size_gb));
             }
         }
 
-        let total = total_kb.context("MemTotal not found")? as f64 / 1024.0 / 1024.0;
-        let available = available_kb.context("MemAvailable not found")? as f64 / 1024.0 / 1024.0;
-
-        Ok((total, available))
+        Ok(datasets)
     }
 
-    /// Get disk info using df command
-    fn get_disk_info() -> Result<(f64, f64)> {
-        let output = std::process::Command::new("df")
-            .arg("-BG")
-            .arg("/")
-            .output()
-            .context("Failed to execute df command")?;
-
-        let stdout = String::from_utf8_lossy(&output.stdout);
-        
-        let line = stdout
-            .lines()
-            .nth(1)
-            .context("df output too short")?;
-
-        let parts: Vec<&str> = line.split_whitespace().collect();
-        
-        if parts.len() < 4 {
-            anyhow::bail!("Invalid df output format");
-        }
-
-        let total = parts[1]
-            .trim_end_matches('G')
-            .parse::<f64>()
-            .context("Failed to parse total disk")?;
-        
-        let available = parts[3]
-            .trim_end_matches('G')
-            .parse::<f64>()
-            .context("Failed to parse available disk")?;
-
-        Ok((total, available))
+    /// Recursive size of a directory in bytes (unreadable entries are skipped)
+    fn dir_size_bytes(dir: &Path) -> u64 {
+        let Ok(entries) = fs::read_dir(dir) else {
+            return 0;
+        };
+
+        entries
+            .flatten()
+            .map(|entry| match entry.metadata() {
+                Ok(meta) if meta.is_dir() => Self::dir_size_bytes(&entry.path()),
+                Ok(meta) => meta.len(),
+                Err(_) => 0,
+            })
+            .sum()
     }
 }
 
 /// TGP Worker Agent
 struct WorkerAgent {
     config: WorkerConfig,
-    client: Option<SchedulerServiceClient<Channel>>,
+    connector: Arc<dyn SchedulerConnector>,
+    client: Option<Arc<dyn SchedulerClient>>,
+    /// Benchmark score, measured on first registration
+    performance_score: Option<f64>,
+    /// Token of the latest accepted registration, sent with reports
+    fencing_token: Arc<AtomicU64>,
+    /// Connection state and resources served on the status endpoint
+    status: status::NodeStatus,
+    /// Local clock was last seen far off the scheduler's
+    clock_skewed: bool,
 }
 
 impl WorkerAgent {
     fn new(config: WorkerConfig) -> Self {
+        Self::with_connector(config, Arc::new(GrpcConnector))
+    }
+
+    fn with_connector(config: WorkerConfig, connector: Arc<dyn SchedulerConnector>) -> Self {
+        let status = status::NodeStatus::new(&config.node_id, &config.scheduler_url, config.report_interval_secs);
         Self {
             config,
+            connector,
             client: None,
+            performance_score: None,
+            fencing_token: Arc::new(AtomicU64::new(0)),
+            status,
+            clock_skewed: false,
         }
     }
 
@@ -183,7 +310,7 @@ impl WorkerAgent {
         info!("Connecting to scheduler at {}", self.config.scheduler_url);
 
         for attempt in 1..=self.config.max_retries {
-            match SchedulerServiceClient::connect(self.config.scheduler_url.clone()).await {
+            match self.connector.connect(&self.config.scheduler_url).await {
                 Ok(client) => {
                     info!("Connected to scheduler successfully");
                     self.client = Some(client);
@@ -205,35 +332,107 @@ impl WorkerAgent {
         anyhow::bail!("Failed to connect after {} attempts", self.config.max_retries)
     }
 
+    /// Benchmark the node once; later registrations reuse the score
+    async fn performance_score(&mut self) -> Result<f64> {
+        if let Some(score) = self.performance_score {
+            return Ok(score);
+        }
+
+        info!("Benchmarking node");
+        let scratch_dir = self.config.data_dir.clone();
+        let result = tokio::task::spawn_blocking(move || benchmark::run(&scratch_dir))
+            .await
+            .context("Benchmark task failed")?;
+        info!(
+            "Benchmark: CPU={:.2}, memory={:.2}, disk={}, score={:.2}",
+            result.cpu,
+            result.memory,
+            result.disk.map(|d| format!("{:.2}", d)).unwrap_or_else(|| "n/a".to_string()),
+            result.score
+        );
+
+        self.performance_score = Some(result.score);
+        Ok(result.score)
+    }
+
+    /// Network bandwidth in Mbit/s, configured or measured once (0: unknown)
+    async fn network_mbps(&mut self) -> u32 {
+        if let Some(mbps) = self.config.network_mbps {
+            return mbps;
+        }
+        let Some(mut client) = self.client.as_ref().and_then(|client| client.grpc()) else {
+            return 0;
+        };
+
+        info!("Measuring network bandwidth");
+        match speedtest::measure(&mut client).await {
+            Ok(mbps) => {
+                info!("Network bandwidth: {} Mbit/s", mbps);
+                self.config.network_mbps = Some(mbps);
+                mbps
+            }
+            Err(e) => {
+                warn!("Network speed test failed: {:#}", e);
+                0
+            }
+        }
+    }
+
     /// Register node with scheduler
     async fn register(&mut self) -> Result<()> {
-        let client = self.client.as_mut()
+        let performance_score = self.performance_score().await?;
+        let network_mbps = self.network_mbps().await;
+        let client = self.client.clone()
             .context("Not connected to scheduler")?;
 
         let hostname = ResourceMonitor::get_hostname()?;
         let (cpu_cores, _) = ResourceMonitor::get_cpu_info()?;
         let (total_memory, _) = ResourceMonitor::get_memory_info()?;
+        let disk = ResourceMonitor::get_disk_info().ok();
+        let gpu_topology = gpu_topology::detect().unwrap_or_else(|e| {
+            warn!("GPU detection failed: {:#}", e);
+            None
+        });
+        let total_disk = disk.map_or(0.0, |(total, _)| total);
+        let reserved = self.config.reserve.reserved(cpu_cores, total_memory, total_disk);
+        info!(
+            "Reserving CPU={}, RAM={:.1}GB, Disk={:.1}GB for the system",
+            reserved.cpu, reserved.memory_gb, reserved.disk_gb
+        );
 
-        let request = tonic::Request::new(RegisterNodeRequest {
+        let request = RegisterNodeRequest {
             node_id: self.config.node_id.clone(),
             hostname,
             cpu_cores,
             total_memory_gb: total_memory,
-            gpu_count: 0, // TODO: GPU detection
-            location: "vps-2".to_string(), // TODO: Make configurable
+            gpu_count: gpu_topology.as_ref().map_or(0, |t| t.numa_nodes.len() as u32),
+            location: self.config.location.clone(),
             cost_per_hour: 0.1, // TODO: Make configurable
-        });
+            data_service_addr: self.config.data_advertise_addr.clone(),
+            preemptible: self.config.preemptible,
+            performance_score,
+            provider: self.config.provider.clone(),
+            control_addr: self.config.control_advertise_addr.clone(),
+            instance_key: self.config.instance_key.clone(),
+            allocatable_cpu: reserved.allocatable_cpu(cpu_cores),
+            allocatable_memory_gb: reserved.allocatable_memory_gb(total_memory),
+            available_disk_gb: disk.map(|(_, free)| reserved.available_disk_gb(free)),
+            network_mbps,
+            gpu_topology,
+            numa_nodes: numa::detect(),
+            labels: self.config.labels.clone(),
+            arch: host::arch().to_string(),
+        };
 
         info!("Registering node: {}", self.config.node_id);
 
-        let response = client
+        let reply = client
             .register_node(request)
             .await
             .context("Failed to register node")?;
 
-        let reply = response.into_inner();
-        
         if reply.success {
+            self.fencing_token.store(reply.fencing_token, Ordering::SeqCst);
             info!("Registration successful: {}", reply.message);
             info!("Assigned to cluster: {}", reply.cluster_id);
         } else {
@@ -244,41 +443,81 @@ impl WorkerAgent {
         Ok(())
     }
 
-    /// Report resources to scheduler
-    async fn report_resources(&mut self) -> Result<()> {
-        let client = self.client.as_mut()
+    /// Report resources; the ack says whether the scheduler has this node
+    /// dormant and whether the scheduler is shutting down
+    async fn report_resources(&mut self) -> Result<ResourceAck> {
+        let client = self.client.clone()
             .context("Not connected to scheduler")?;
 
-        let (_, available_cpu) = ResourceMonitor::get_cpu_info()
+        let (cpu_cores, free_cpu) = ResourceMonitor::get_cpu_info()
             .unwrap_or((0, 0));
-        let (_, available_memory) = ResourceMonitor::get_memory_info()
+        let (total_memory, free_memory) = ResourceMonitor::get_memory_info()
             .unwrap_or((0.0, 0.0));
-        let (_, available_disk) = ResourceMonitor::get_disk_info()
+        let (total_disk, free_disk) = ResourceMonitor::get_disk_info()
             .unwrap_or((0.0, 0.0));
 
-        let request = tonic::Request::new(ResourceReport {
+        // Only what is left after the system reserve is offered to jobs
+        let reserved = self.config.reserve.reserved(cpu_cores, total_memory, total_disk);
+        let available_cpu = reserved.available_cpu(cpu_cores, free_cpu);
+        let available_memory = reserved.available_memory_gb(total_memory, free_memory);
+        let available_disk = reserved.available_disk_gb(free_disk);
+        self.status.record_resources(status::ResourceSnapshot {
+            cpu_cores,
+            available_cpu,
+            total_memory_gb: total_memory,
+            available_memory_gb: available_memory,
+            total_disk_gb: total_disk,
+            available_disk_gb: available_disk,
+        });
+        let datasets = ResourceMonitor::get_local_datasets(&self.config.data_dir)
+            .unwrap_or_else(|e| {
+                warn!("Failed to scan datasets: {}", e);
+                Vec::new()
+            });
+
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_secs() as i64;
+        let report = ResourceReport {
             node_id: self.config.node_id.clone(),
             available_cpu,
             available_memory_gb: available_memory,
             available_disk_gb: available_disk,
             available_gpu: 0,
-            timestamp: std::time::SystemTime::now()
-                .duration_since(std::time::UNIX_EPOCH)
-                .unwrap()
-                .as_secs() as i64,
-        });
+            timestamp: now,
+            datasets: datasets
+                .into_iter()
+                .map(|(name, size_gb)| LocalDataset {
+                    content_digest: delta::cached_digest(&self.config.data_dir.join(&name)).unwrap_or_default(),
+                    name,
+                    size_gb,
+                })
+                .collect(),
+            fencing_token: self.fencing_token.load(Ordering::SeqCst),
+        };
 
         info!(
             "Reporting resources: CPU={}, RAM={:.1}GB, Disk={:.1}GB",
             available_cpu, available_memory, available_disk
         );
 
-        client
-            .report_resources(request)
+        let ack = client
+            .report_resources(report)
             .await
             .context("Failed to report resources")?;
 
-        Ok(())
+        // The scheduler corrects our timestamps, but local deadlines drift too
+        if ack.scheduler_time > 0 {
+            let skew = now - ack.scheduler_time;
+            let skewed = skew.abs() > MAX_CLOCK_SKEW_SECS;
+            if skewed && !self.clock_skewed {
+                warn!("Local clock is {}s off the scheduler's; check NTP on this node", skew);
+            }
+            self.clock_skewed = skewed;
+        }
+
+        Ok(ack)
     }
 
     /// Main worker loop with error recovery
@@ -287,38 +526,191 @@ impl WorkerAgent {
         info!("Node ID: {}", self.config.node_id);
         info!("Scheduler: {}", self.config.scheduler_url);
 
-        // Connect and register
+        // Serve local datasets to peers holding the data token
+        let peer_auth = if self.config.data_token.trim().is_empty() {
+            None
+        } else {
+            data_service::PeerAuth::new(&self.config.data_token)
+                .map_err(|e| warn!("Invalid TGP_DATA_TOKEN, datasets will not be shared: {:#}", e))
+                .ok()
+        };
+        if peer_auth.is_none() {
+            self.config.data_advertise_addr.clear();
+        }
+        if let (false, Some(auth)) = (self.config.data_advertise_addr.is_empty(), peer_auth.clone()) {
+            match self.config.data_listen_addr.parse() {
+                Ok(addr) => {
+                    let data_dir = self.config.data_dir.clone();
+                    tokio::spawn(async move {
+                        if let Err(e) = data_service::serve(data_dir, addr, auth).await {
+                            error!("Data service stopped: {}", e);
+                        }
+                    });
+                }
+                Err(e) => warn!("Invalid TGP_DATA_LISTEN_ADDR, data service disabled: {}", e),
+            }
+        }
+
+        // Keep dataset chunk lists current for delta sync and version digests
+        tokio::spawn(delta::run_indexer(self.config.data_dir.clone()));
+
+        // Remove job logs once their retention period is over
+        tokio::spawn(logs::run_gc(logs::LogConfig::from_env()));
+
+        // Connect and register (the command stream needs the fencing token)
         self.connect().await?;
         self.register().await?;
 
+        // Accept jobs and queries pushed by the scheduler, dialled directly
+        // and/or over a stream this worker opens
+        let stager = data_service::DatasetStager::new(self.config.data_dir.clone(), peer_auth);
+        let runner = match control::JobRunner::new(self.config.node_id.clone(), &self.config.scheduler_url) {
+            Ok(runner) => {
+                let runner = runner.with_datasets(stager).with_fencing_token(self.fencing_token.clone());
+                // Served only when advertised, and only to callers with the token
+                if !self.config.control_advertise_addr.is_empty() {
+                    match self.config.control_listen_addr.parse() {
+                        Ok(addr) => {
+                            let runner = runner.clone();
+                            let auth = control::ControlAuth::new(&self.config.control_token);
+                            tokio::spawn(async move {
+                                if let Err(e) = control::serve(addr, runner, auth).await {
+                                    error!("Control service stopped: {}", e);
+                                }
+                            });
+                        }
+                        Err(e) => warn!("Invalid TGP_CONTROL_LISTEN_ADDR, control service disabled: {}", e),
+                    }
+                }
+                // Report falling behind the published worker release and,
+                // if enabled, update in place while idle
+                if let Some(client) = self.client.as_ref().and_then(|client| client.grpc()) {
+                    tokio::spawn(self_update::run(
+                        client,
+                        self.config.node_id.clone(),
+                        runner.clone(),
+                        self_update::UpdateConfig::from_env(),
+                    ));
+                }
+                if self.config.command_stream {
+                    tokio::spawn(command_stream::run(
+                        self.config.scheduler_url.clone(),
+                        self.config.node_id.clone(),
+                        self.fencing_token.clone(),
+                        runner.clone(),
+                        Duration::from_secs(self.config.reconnect_delay_secs.max(1)),
+                    ));
+                }
+                Some(runner)
+            }
+            Err(e) => {
+                warn!("Cannot run jobs, control service and command stream disabled: {}", e);
+                None
+            }
+        };
+
+        // Health, status and metrics for node-local checks
+        if !self.config.status_listen_addr.is_empty() {
+            match self.config.status_listen_addr.parse() {
+                Ok(addr) => {
+                    let status = self.status.clone();
+                    tokio::spawn(async move {
+                        if let Err(e) = status::serve(addr, status, runner).await {
+                            error!("Status endpoint stopped: {}", e);
+                        }
+                    });
+                }
+                Err(e) => warn!("Invalid TGP_STATUS_LISTEN_ADDR, status endpoint disabled: {}", e),
+            }
+        }
+
+        // Relay provider termination warnings on spot instances
+        if self.config.preemptible {
+            if let Some(client) = self.client.as_ref().and_then(|client| client.grpc()) {
+                tokio::spawn(interruption::watch(
+                    client,
+                    self.config.node_id.clone(),
+                    self.config.interruption_url.clone(),
+                ));
+            }
+        }
+
         // Main loop
         let mut report_interval = tokio::time::interval(
             Duration::from_secs(self.config.report_interval_secs)
         );
 
+        let mut dormant = false;
+        let mut ticks: u64 = 0;
+
         loop {
             report_interval.tick().await;
+            ticks += 1;
+
+            // Dormant nodes only check in occasionally to learn when to wake
+            if dormant && ticks % DORMANT_CHECK_EVERY != 0 {
+                continue;
+            }
 
             // Report resources with error handling
-            if let Err(e) = self.report_resources().await {
-                error!("Failed to report resources: {}", e);
-                
-                // Try to reconnect
-                warn!("Attempting to reconnect...");
-                if let Err(reconnect_err) = self.connect().await {
-                    error!("Reconnection failed: {}", reconnect_err);
-                    continue;
+            match self.report_resources().await {
+                Ok(ack) if ack.scheduler_draining => {
+                    self.status.record_report(ack.dormant);
+                    // Reconnect now so the next report reaches a live replica
+                    info!("Scheduler is shutting down, reconnecting");
+                    self.client = None;
+                    if let Err(e) = self.connect().await {
+                        error!("Reconnection failed: {}", e);
+                        continue;
+                    }
+                    if let Err(e) = self.register().await {
+                        error!("Re-registration failed: {}", e);
+                    }
+                }
+                Ok(ResourceAck { dormant: now_dormant, .. }) => {
+                    self.status.record_report(now_dormant);
+                    if dormant && !now_dormant {
+                        info!("Woken by scheduler, re-registering");
+                        if let Err(e) = self.register().await {
+                            error!("Re-registration failed: {}", e);
+                        }
+                    } else if !dormant && now_dormant {
+                        info!("Node marked dormant, suspending resource reports");
+                    }
+                    dormant = now_dormant;
                 }
-                
-                // Re-register after reconnection
-                if let Err(register_err) = self.register().await {
-                    error!("Re-registration failed: {}", register_err);
+                Err(e) if is_superseded(&e) => {
+                    // Re-registering would fence off the newer process in turn
+                    anyhow::bail!("Superseded by a newer registration of node {}", self.config.node_id);
+                }
+                Err(e) => {
+                    error!("Failed to report resources: {}", e);
+                    self.status.record_failure(&e);
+
+                    // Try to reconnect
+                    warn!("Attempting to reconnect...");
+                    if let Err(reconnect_err) = self.connect().await {
+                        error!("Reconnection failed: {}", reconnect_err);
+                        continue;
+                    }
+
+                    // Re-register after reconnection
+                    if let Err(register_err) = self.register().await {
+                        error!("Re-registration failed: {}", register_err);
+                    }
                 }
             }
         }
     }
 }
 
+/// Whether the scheduler rejected a call because a newer process of this
+/// node has registered since
+fn is_superseded(err: &anyhow::Error) -> bool {
+    err.downcast_ref::<tonic::Status>()
+        .is_some_and(|status| status.code() == tonic::Code::FailedPrecondition)
+}
+
 #[tokio::main]
 async fn main() -> Result<()> {
     // Initialize logging
@@ -329,10 +721,19 @@ async fn main() -> Result<()> {
         )
         .init();
 
-    info!("TGP Worker Agent v0.1.0");
+    info!("TGP Worker Agent v{}", self_update::WORKER_VERSION);
 
     // Load configuration
-    let config = WorkerConfig::from_env();
+    let mut config = WorkerConfig::from_env();
+    let identity = identity::load_or_create(&config.identity_file, &config.node_id, || {
+        hostname::get()
+            .ok()
+            .and_then(|h| h.into_string().ok())
+            .unwrap_or_else(|| "worker-unknown".to_string())
+    })?;
+    config.node_id = identity.node_id;
+    config.instance_key = identity.instance_key;
+    config.location = location::detect(&config.location, &config.geoip_url).await;
 
     // Create and run worker
     let mut worker = WorkerAgent::new(config);
@@ -345,3 +746,71 @@ async fn main() -> Result<()> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use scheduler_client::mock::MockScheduler;
+
+    fn agent(scheduler: &MockScheduler) -> WorkerAgent {
+        let config = WorkerConfig {
+            node_id: "node-1".to_string(),
+            scheduler_url: "http://scheduler:50051".to_string(),
+            max_retries: 3,
+            reconnect_delay_secs: 0,
+            network_mbps: Some(100),
+            data_dir: std::env::temp_dir().join("tgp-worker-test-no-datasets"),
+            ..WorkerConfig::from_env()
+        };
+        let mut agent = WorkerAgent::with_connector(config, Arc::new(scheduler.clone()));
+        agent.performance_score = Some(1.0);
+        agent
+    }
+
+    #[tokio::test]
+    async fn test_connects_with_retries_and_registers() {
+        let scheduler = MockScheduler::new(7);
+        let mut worker = agent(&scheduler);
+
+        scheduler.fail_connects(3);
+        assert!(worker.connect().await.is_err());
+        scheduler.fail_connects(2);
+        worker.connect().await.unwrap();
+        assert_eq!(scheduler.connects(), 6);
+
+        worker.register().await.unwrap();
+        let registrations = scheduler.registrations();
+        assert_eq!(registrations.len(), 1);
+        assert_eq!(registrations[0].node_id, "node-1");
+        assert_eq!(registrations[0].network_mbps, 100);
+
+        // Reports carry the token of the latest registration
+        worker.report_resources().await.unwrap();
+        scheduler.set_fencing_token(8);
+        worker.register().await.unwrap();
+        worker.report_resources().await.unwrap();
+        let tokens: Vec<u64> = scheduler.reports().iter().map(|report| report.fencing_token).collect();
+        assert_eq!(tokens, [7, 8]);
+
+        scheduler.reject_registrations("Node is cordoned");
+        assert!(worker.register().await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_fenced_reports_are_recognised() {
+        let scheduler = MockScheduler::new(1);
+        let mut worker = agent(&scheduler);
+        worker.connect().await.unwrap();
+
+        scheduler.fail_reports(Some(tonic::Code::Unavailable));
+        let err = worker.report_resources().await.unwrap_err();
+        assert!(!is_superseded(&err));
+
+        scheduler.fail_reports(Some(tonic::Code::FailedPrecondition));
+        let err = worker.report_resources().await.unwrap_err();
+        assert!(is_superseded(&err));
+
+        scheduler.fail_reports(None);
+        assert!(worker.report_resources().await.is_ok());
+    }
+}
@@ -0,0 +1,73 @@
+//! NUMA layout of the node
+//!
+//! Read from /sys/devices/system/node so the scheduler can pin jobs that
+//! ask for dedicated cores to cores of a single NUMA node, keeping their
+//! memory accesses local.
+
+use std::fs;
+use std::path::Path;
+
+use crate::proto::NumaNode;
+
+const NODE_DIR: &str = "/sys/devices/system/node";
+
+/// Parse a kernel cpulist such as "0-3,8,10-11"
+pub fn parse_cpulist(list: &str) -> Vec<u32> {
+    list.trim()
+        .split(',')
+        .filter(|range| !range.is_empty())
+        .flat_map(|range| match range.split_once('-') {
+            Some((start, end)) => match (start.parse::<u32>(), end.parse::<u32>()) {
+                (Ok(start), Ok(end)) => (start..=end).collect(),
+                _ => Vec::new(),
+            },
+            None => range.parse::<u32>().into_iter().collect(),
+        })
+        .collect()
+}
+
+/// CPUs of each NUMA node in node order (empty where sysfs lacks NUMA info)
+pub fn detect() -> Vec<NumaNode> {
+    detect_in(Path::new(NODE_DIR))
+}
+
+fn detect_in(dir: &Path) -> Vec<NumaNode> {
+    let mut nodes: Vec<(u32, Vec<u32>)> = fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter_map(|entry| {
+            let index = entry.file_name().to_str()?.strip_prefix("node")?.parse().ok()?;
+            let cpulist = fs::read_to_string(entry.path().join("cpulist")).ok()?;
+            Some((index, parse_cpulist(&cpulist)))
+        })
+        .collect();
+    nodes.sort_unstable_by_key(|(index, _)| *index);
+    nodes.into_iter().map(|(_, cpus)| NumaNode { cpus }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect_reads_cpulists_in_node_order() {
+        assert_eq!(parse_cpulist("0-3,8,10-11\n"), vec![0, 1, 2, 3, 8, 10, 11]);
+        assert!(parse_cpulist("\n").is_empty());
+
+        let dir = std::env::temp_dir().join(format!("tgp-numa-test-{}", std::process::id()));
+        for (node, cpulist) in [("node1", "4-7\n"), ("node0", "0-3\n")] {
+            fs::create_dir_all(dir.join(node)).unwrap();
+            fs::write(dir.join(node).join("cpulist"), cpulist).unwrap();
+        }
+        fs::create_dir_all(dir.join("power")).unwrap();
+
+        let nodes = detect_in(&dir);
+        assert_eq!(nodes.len(), 2);
+        assert_eq!(nodes[0].cpus, vec![0, 1, 2, 3]);
+        assert_eq!(nodes[1].cpus, vec![4, 5, 6, 7]);
+
+        fs::remove_dir_all(&dir).unwrap();
+        assert!(detect_in(&dir).is_empty());
+    }
+}
@@ -0,0 +1,95 @@
+//! Job output capture - container → worker → scheduler
+//!
+//! A job that declares an output path (a file or directory in its
+//! container) has that path copied out as a tar archive once the container
+//! exits. The archive is spooled to disk, uploaded to the scheduler in
+//! chunks and deleted; clients download it with `GetJobOutput`.
+
+use anyhow::{Context, Result};
+use bollard::container::DownloadFromContainerOptions;
+use bollard::Docker;
+use futures_util::stream::StreamExt;
+use std::path::{Path, PathBuf};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::transport::Channel;
+
+use crate::proto::{scheduler_service_client::SchedulerServiceClient, JobOutputChunk};
+
+/// Largest archive captured (the scheduler's default cap)
+pub const MAX_OUTPUT_BYTES: u64 = 1024 * 1024 * 1024;
+
+/// Upload chunk size
+const CHUNK_BYTES: usize = 1024 * 1024;
+
+/// Where a job's archive is spooled until it is uploaded
+pub fn archive_path(job_id: &str) -> PathBuf {
+    std::env::temp_dir().join("tgp-outputs").join(format!("{}.tar", job_id))
+}
+
+/// Copy `path` out of a stopped container as a tar archive at `dest`,
+/// returning its size
+pub async fn download(docker: &Docker, container_id: &str, path: &str, dest: &Path) -> Result<u64> {
+    if let Some(dir) = dest.parent() {
+        tokio::fs::create_dir_all(dir).await.context("Failed to create output directory")?;
+    }
+    let mut file = tokio::fs::File::create(dest).await
+        .with_context(|| format!("Failed to create {}", dest.display()))?;
+
+    let options = DownloadFromContainerOptions { path: path.to_string() };
+    let mut stream = docker.download_from_container(container_id, Some(options));
+    let mut size = 0u64;
+    let result = async {
+        while let Some(chunk) = stream.next().await {
+            let chunk = chunk.with_context(|| format!("Failed to copy {} from the container", path))?;
+            size += chunk.len() as u64;
+            anyhow::ensure!(size <= MAX_OUTPUT_BYTES, "Output {} is over the {} byte limit", path, MAX_OUTPUT_BYTES);
+            file.write_all(&chunk).await.context("Failed to write output archive")?;
+        }
+        file.flush().await.context("Failed to write output archive")
+    }.await;
+
+    if let Err(e) = result {
+        let _ = tokio::fs::remove_file(dest).await;
+        return Err(e);
+    }
+    Ok(size)
+}
+
+/// Upload a job's output archive to the scheduler, returning the size it stored
+pub async fn upload(client: &mut SchedulerServiceClient<Channel>, job_id: &str, archive: &Path) -> Result<u64> {
+    let mut file = tokio::fs::File::open(archive).await
+        .with_context(|| format!("Failed to open {}", archive.display()))?;
+    let (tx, rx) = mpsc::channel(4);
+
+    let job_id = job_id.to_string();
+    let reader = tokio::spawn(async move {
+        let mut first = true;
+        loop {
+            let mut data = vec![0u8; CHUNK_BYTES];
+            let read = file.read(&mut data).await?;
+            // The first chunk names the job, even for an empty archive
+            if read == 0 && !first {
+                return Ok::<_, std::io::Error>(());
+            }
+            data.truncate(read);
+            let chunk = JobOutputChunk {
+                job_id: if first { job_id.clone() } else { String::new() },
+                data,
+            };
+            first = false;
+            if tx.send(chunk).await.is_err() || read == 0 {
+                return Ok(());
+            }
+        }
+    });
+
+    let ack = client
+        .upload_job_output(ReceiverStream::new(rx))
+        .await
+        .context("UploadJobOutput failed")?
+        .into_inner();
+    reader.await.context("Output reader failed")?.context("Failed to read output archive")?;
+    Ok(ack.size_bytes)
+}
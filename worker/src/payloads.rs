@@ -0,0 +1,45 @@
+//! Job payloads - scheduler → worker
+//!
+//! Inputs kept in the scheduler's payload store are fetched with
+//! `GetPayload` when the job starts and checked against their SHA-256
+//! digest before the container sees them.
+
+use anyhow::{Context, Result};
+use sha2::{Digest, Sha256};
+use tonic::transport::Channel;
+
+use crate::proto::{scheduler_service_client::SchedulerServiceClient, PayloadRequest};
+
+/// Largest payload fetched (the scheduler's default cap)
+const MAX_PAYLOAD_BYTES: usize = 64 * 1024 * 1024;
+
+/// Fetch and verify the payload stored under `digest` (`sha256:<hex>`)
+pub async fn fetch(client: &mut SchedulerServiceClient<Channel>, digest: &str) -> Result<Vec<u8>> {
+    let mut stream = client
+        .get_payload(PayloadRequest { digest: digest.to_string() })
+        .await
+        .with_context(|| format!("Failed to fetch payload {}", digest))?
+        .into_inner();
+
+    let mut hasher = Sha256::new();
+    let mut payload = Vec::new();
+    while let Some(chunk) = stream.message().await.with_context(|| format!("Failed to fetch payload {}", digest))? {
+        anyhow::ensure!(
+            payload.len() + chunk.data.len() <= MAX_PAYLOAD_BYTES,
+            "Payload {} is over the {} byte limit",
+            digest,
+            MAX_PAYLOAD_BYTES
+        );
+        hasher.update(&chunk.data);
+        payload.extend_from_slice(&chunk.data);
+    }
+
+    let actual: String = hasher.finalize().iter().map(|b| format!("{:02x}", b)).collect();
+    anyhow::ensure!(
+        digest.strip_prefix("sha256:") == Some(actual.as_str()),
+        "Payload {} failed its integrity check (got sha256:{})",
+        digest,
+        actual
+    );
+    Ok(payload)
+}
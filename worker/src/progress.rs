@@ -0,0 +1,221 @@
+//! Job Progress Protocol - container → worker → scheduler
+//!
+//! Containers report progress by appending JSON lines to the file named by
+//! `$TGP_PROGRESS_FILE` (bind-mounted at `/tgp/progress`):
+//!
+//! ```text
+//! {"percent": 42.5, "step": "epoch 3/10", "metrics": {"loss": 0.12}}
+//! ```
+//!
+//! The worker tails the file while the job runs and forwards every update
+//! to the scheduler, where it shows up in GetJobStatus and WatchJob.
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::PathBuf;
+use std::time::Duration;
+use tokio::sync::{mpsc, oneshot};
+use tonic::transport::Channel;
+use tracing::{debug, warn};
+
+use crate::proto::{scheduler_service_client::SchedulerServiceClient, JobProgress, JobProgressReport};
+
+/// Directory mounted into every job container
+pub const CONTAINER_PROGRESS_DIR: &str = "/tgp";
+
+/// Progress file path as seen from inside the container
+pub const CONTAINER_PROGRESS_FILE: &str = "/tgp/progress";
+
+/// How often the progress file is polled for new lines
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Single progress update written by a container
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ProgressUpdate {
+    #[serde(default)]
+    pub percent: f64,
+    #[serde(default)]
+    pub step: String,
+    #[serde(default)]
+    pub metrics: HashMap<String, f64>,
+}
+
+/// Parse one progress line, ignoring blank or malformed lines
+pub fn parse_progress_line(line: &str) -> Option<ProgressUpdate> {
+    let line = line.trim();
+    if line.is_empty() {
+        return None;
+    }
+
+    match serde_json::from_str(line) {
+        Ok(update) => Some(update),
+        Err(e) => {
+            debug!("Ignoring malformed progress line: {}", e);
+            None
+        }
+    }
+}
+
+/// Incremental reader for an append-only progress file
+pub struct ProgressTailer {
+    path: PathBuf,
+    offset: u64,
+    /// Bytes after the last newline; may end inside a UTF-8 character
+    partial: Vec<u8>,
+}
+
+impl ProgressTailer {
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            offset: 0,
+            partial: Vec::new(),
+        }
+    }
+
+    /// Read lines appended since the last poll
+    ///
+    /// A missing file means the job has not reported anything yet.
+    pub fn poll(&mut self) -> Result<Vec<ProgressUpdate>> {
+        let mut file = match File::open(&self.path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e).context("Failed to open progress file"),
+        };
+
+        // File was truncated or replaced: start over
+        let len = file.metadata().context("Failed to stat progress file")?.len();
+        if len < self.offset {
+            self.offset = 0;
+            self.partial.clear();
+        }
+
+        file.seek(SeekFrom::Start(self.offset))
+            .context("Failed to seek progress file")?;
+        let mut buf = Vec::new();
+        file.read_to_end(&mut buf).context("Failed to read progress file")?;
+        self.offset += buf.len() as u64;
+
+        self.partial.extend_from_slice(&buf);
+
+        // Only consume complete lines; keep the trailing fragment for next
+        // poll. Lines are decoded whole, so a character split across reads
+        // is never cut in two.
+        let mut updates = Vec::new();
+        while let Some(pos) = self.partial.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = self.partial.drain(..=pos).collect();
+            if let Some(update) = parse_progress_line(&String::from_utf8_lossy(&line)) {
+                updates.push(update);
+            }
+        }
+
+        Ok(updates)
+    }
+}
+
+/// Tail a progress file until `stop` fires, sending every update to `tx`
+pub async fn tail_progress(
+    path: PathBuf,
+    tx: mpsc::Sender<ProgressUpdate>,
+    mut stop: oneshot::Receiver<()>,
+) {
+    let mut tailer = ProgressTailer::new(path);
+    let mut interval = tokio::time::interval(POLL_INTERVAL);
+
+    loop {
+        let stopping = tokio::select! {
+            _ = interval.tick() => false,
+            _ = &mut stop => true,
+        };
+
+        match tailer.poll() {
+            Ok(updates) => {
+                for update in updates {
+                    if tx.send(update).await.is_err() {
+                        return;
+                    }
+                }
+            }
+            Err(e) => warn!("Progress tailing error: {}", e),
+        }
+
+        if stopping {
+            return;
+        }
+    }
+}
+
+/// Forward progress updates for a job to the scheduler
+pub async fn forward_progress(
+    mut client: SchedulerServiceClient<Channel>,
+    node_id: String,
+    job_id: String,
+    mut rx: mpsc::Receiver<ProgressUpdate>,
+) {
+    while let Some(update) = rx.recv().await {
+        let request = tonic::Request::new(JobProgressReport {
+            job_id: job_id.clone(),
+            node_id: node_id.clone(),
+            progress: Some(JobProgress {
+                percent: update.percent,
+                step: update.step,
+                metrics: update.metrics,
+                updated_at: std::time::SystemTime::now()
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .unwrap()
+                    .as_secs() as i64,
+            }),
+        });
+
+        if let Err(e) = client.report_job_progress(request).await {
+            warn!("Failed to forward progress for job {}: {}", job_id, e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    #[test]
+    fn test_parse_progress_line() {
+        let update = parse_progress_line(r#"{"percent": 42.5, "step": "epoch 3", "metrics": {"loss": 0.12}}"#)
+            .unwrap();
+        assert_eq!(update.percent, 42.5);
+        assert_eq!(update.step, "epoch 3");
+        assert_eq!(update.metrics.get("loss"), Some(&0.12));
+
+        assert!(parse_progress_line("not json").is_none());
+        assert!(parse_progress_line("   ").is_none());
+    }
+
+    #[test]
+    fn test_tailer_reads_only_complete_lines() {
+        let path = std::env::temp_dir().join(format!("tgp-progress-test-{}", std::process::id()));
+        let mut file = File::create(&path).unwrap();
+        let mut tailer = ProgressTailer::new(path.clone());
+
+        write!(file, "{{\"percent\": 10}}\n{{\"percent\": 2").unwrap();
+        let updates = tailer.poll().unwrap();
+        assert_eq!(updates.len(), 1);
+        assert_eq!(updates[0].percent, 10.0);
+
+        writeln!(file, "0}}").unwrap();
+        let updates = tailer.poll().unwrap();
+        assert_eq!(updates.len(), 1);
+        assert_eq!(updates[0].percent, 20.0);
+
+        // A multi-byte character split between two reads
+        let line = "{\"step\": \"étape 2\"}\n".as_bytes();
+        file.write_all(&line[..11]).unwrap();
+        assert!(tailer.poll().unwrap().is_empty());
+        file.write_all(&line[11..]).unwrap();
+        assert_eq!(tailer.poll().unwrap()[0].step, "étape 2");
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,163 @@
+//! Container runtime abstraction
+//!
+//! Job handling (`control::JobRunner`, hooks, exec sessions) talks to the
+//! container runtime through `ContainerRuntime`, implemented for Docker by
+//! `JobExecutor`. Tests swap in `mock::MockRuntime` to exercise assignment
+//! and cancellation handling without a Docker daemon.
+
+use anyhow::Result;
+use bollard::exec::StartExecResults;
+use tokio::sync::mpsc;
+
+use crate::executor::{JobExecution, JobExecutor, JobResult};
+use crate::logs::LogConfig;
+use crate::progress::ProgressUpdate;
+
+/// What the worker needs from a container runtime
+#[tonic::async_trait]
+pub trait ContainerRuntime: Send + Sync {
+    /// Run a job's container to completion
+    async fn execute_job(&self, job: JobExecution, progress_tx: Option<mpsc::Sender<ProgressUpdate>>) -> Result<JobResult>;
+
+    /// Stop a job's container, giving it `grace_secs` to checkpoint
+    async fn stop_job(&self, job_id: &str, grace_secs: i64) -> Result<()>;
+
+    /// Ids of jobs whose containers are running
+    async fn running_jobs(&self) -> Result<Vec<String>>;
+
+    /// Start `command` in a job's running container, attached
+    async fn exec(&self, job_id: &str, command: Vec<String>, tty: bool) -> Result<(String, StartExecResults)>;
+
+    /// Exit code of a finished exec
+    async fn exec_exit_code(&self, exec_id: &str) -> Result<Option<i64>>;
+
+    /// Where job output is written
+    fn log_config(&self) -> &LogConfig;
+}
+
+#[tonic::async_trait]
+impl ContainerRuntime for JobExecutor {
+    async fn execute_job(&self, job: JobExecution, progress_tx: Option<mpsc::Sender<ProgressUpdate>>) -> Result<JobResult> {
+        JobExecutor::execute_job(self, job, progress_tx).await
+    }
+
+    async fn stop_job(&self, job_id: &str, grace_secs: i64) -> Result<()> {
+        JobExecutor::stop_job(self, job_id, grace_secs).await
+    }
+
+    async fn running_jobs(&self) -> Result<Vec<String>> {
+        JobExecutor::running_jobs(self).await
+    }
+
+    async fn exec(&self, job_id: &str, command: Vec<String>, tty: bool) -> Result<(String, StartExecResults)> {
+        JobExecutor::exec(self, job_id, command, tty).await
+    }
+
+    async fn exec_exit_code(&self, exec_id: &str) -> Result<Option<i64>> {
+        JobExecutor::exec_exit_code(self, exec_id).await
+    }
+
+    fn log_config(&self) -> &LogConfig {
+        JobExecutor::log_config(self)
+    }
+}
+
+#[cfg(test)]
+pub mod mock {
+    use super::*;
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex};
+    use tokio::sync::Notify;
+
+    /// Exit code of a container stopped before it finished
+    pub const STOPPED_EXIT_CODE: i64 = 137;
+
+    /// Runtime whose "containers" exit at once with a scripted code, or
+    /// run until stopped when `hold` is set
+    #[derive(Default)]
+    pub struct MockRuntime {
+        hold: bool,
+        exit_codes: Mutex<HashMap<String, i64>>,
+        running: Mutex<HashMap<String, Arc<Notify>>>,
+        started: Mutex<Vec<String>>,
+        logs: LogConfig,
+    }
+
+    impl MockRuntime {
+        /// Containers that keep running until stopped
+        pub fn holding() -> Self {
+            Self { hold: true, ..Self::default() }
+        }
+
+        /// Make `job_id`'s container exit with `code`
+        pub fn exit_code(self, job_id: &str, code: i64) -> Self {
+            self.exit_codes.lock().unwrap().insert(job_id.to_string(), code);
+            self
+        }
+
+        /// Jobs started so far, in order
+        pub fn started(&self) -> Vec<String> {
+            self.started.lock().unwrap().clone()
+        }
+    }
+
+    #[tonic::async_trait]
+    impl ContainerRuntime for MockRuntime {
+        async fn execute_job(&self, job: JobExecution, _progress_tx: Option<mpsc::Sender<ProgressUpdate>>) -> Result<JobResult> {
+            let stop = Arc::new(Notify::new());
+            self.running.lock().unwrap().insert(job.job_id.clone(), stop.clone());
+            self.started.lock().unwrap().push(job.job_id.clone());
+
+            if self.hold {
+                stop.notified().await;
+            }
+            let stopped = self.running.lock().unwrap().remove(&job.job_id).is_none();
+            let exit_code = if stopped {
+                STOPPED_EXIT_CODE
+            } else {
+                self.exit_codes.lock().unwrap().get(&job.job_id).copied().unwrap_or(0)
+            };
+            Ok(JobResult {
+                job_id: job.job_id,
+                success: exit_code == 0,
+                exit_code,
+                logs: String::new(),
+                artifacts: HashMap::new(),
+                output_hash: String::new(),
+                error: (exit_code != 0).then(|| format!("Container exited with code {}", exit_code)),
+                output_archive: None,
+                peak_memory_gb: 0.0,
+                peak_cpu_cores: 0.0,
+            })
+        }
+
+        async fn stop_job(&self, job_id: &str, _grace_secs: i64) -> Result<()> {
+            let stop = self.running.lock().unwrap().remove(job_id);
+            match stop {
+                Some(stop) => {
+                    stop.notify_one();
+                    Ok(())
+                }
+                None => anyhow::bail!("No such container: tgp-job-{}", job_id),
+            }
+        }
+
+        async fn running_jobs(&self) -> Result<Vec<String>> {
+            let mut job_ids: Vec<String> = self.running.lock().unwrap().keys().cloned().collect();
+            job_ids.sort();
+            Ok(job_ids)
+        }
+
+        async fn exec(&self, job_id: &str, _command: Vec<String>, _tty: bool) -> Result<(String, StartExecResults)> {
+            anyhow::bail!("Cannot exec in job {}: not supported by the mock runtime", job_id)
+        }
+
+        async fn exec_exit_code(&self, _exec_id: &str) -> Result<Option<i64>> {
+            Ok(None)
+        }
+
+        fn log_config(&self) -> &LogConfig {
+            &self.logs
+        }
+    }
+}
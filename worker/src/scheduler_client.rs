@@ -0,0 +1,208 @@
+//! Scheduler client abstraction
+//!
+//! Registration, resource reports and job status updates go through
+//! `SchedulerClient`, and the agent gets its client from a
+//! `SchedulerConnector`, so connect/reconnect, registration and
+//! assignment handling can be tested against `mock::MockScheduler`
+//! instead of a live scheduler. Streaming and less common RPCs (speed
+//! test, uploads, progress, self-update) use the gRPC client directly.
+
+use anyhow::{Context, Result};
+use std::sync::Arc;
+use tonic::transport::{Channel, Endpoint};
+
+use crate::proto::{
+    scheduler_service_client::SchedulerServiceClient, JobStatusUpdate, RegisterNodeRequest,
+    RegisterNodeResponse, ResourceAck, ResourceReport,
+};
+
+/// The scheduler RPCs the worker's main logic makes
+#[tonic::async_trait]
+pub trait SchedulerClient: Send + Sync {
+    async fn register_node(&self, request: RegisterNodeRequest) -> Result<RegisterNodeResponse>;
+
+    /// Report resources; the ack says whether this node is dormant and
+    /// whether the scheduler is shutting down
+    async fn report_resources(&self, report: ResourceReport) -> Result<ResourceAck>;
+
+    async fn update_job_status(&self, update: JobStatusUpdate) -> Result<()>;
+
+    /// gRPC client for the RPCs not covered above (None: not gRPC-backed)
+    fn grpc(&self) -> Option<SchedulerServiceClient<Channel>> {
+        None
+    }
+}
+
+/// Opens scheduler connections
+#[tonic::async_trait]
+pub trait SchedulerConnector: Send + Sync {
+    async fn connect(&self, url: &str) -> Result<Arc<dyn SchedulerClient>>;
+}
+
+/// `SchedulerClient` over the scheduler's gRPC API
+#[derive(Debug, Clone)]
+pub struct GrpcScheduler {
+    client: SchedulerServiceClient<Channel>,
+}
+
+impl GrpcScheduler {
+    /// Connect now, failing if the scheduler is unreachable
+    pub async fn connect(url: &str) -> Result<Self> {
+        let client = SchedulerServiceClient::connect(url.to_string()).await?;
+        Ok(Self { client })
+    }
+
+    /// Connect on first use and reconnect after failures
+    pub fn lazy(url: &str) -> Result<Self> {
+        let channel = Endpoint::from_shared(url.to_string())
+            .context("Invalid scheduler URL")?
+            .connect_lazy();
+        Ok(Self { client: SchedulerServiceClient::new(channel) })
+    }
+}
+
+#[tonic::async_trait]
+impl SchedulerClient for GrpcScheduler {
+    async fn register_node(&self, request: RegisterNodeRequest) -> Result<RegisterNodeResponse> {
+        Ok(self.client.clone().register_node(request).await?.into_inner())
+    }
+
+    async fn report_resources(&self, report: ResourceReport) -> Result<ResourceAck> {
+        Ok(self.client.clone().report_resources(report).await?.into_inner())
+    }
+
+    async fn update_job_status(&self, update: JobStatusUpdate) -> Result<()> {
+        self.client.clone().update_job_status(update).await?;
+        Ok(())
+    }
+
+    fn grpc(&self) -> Option<SchedulerServiceClient<Channel>> {
+        Some(self.client.clone())
+    }
+}
+
+/// Connects `GrpcScheduler`s
+#[derive(Debug, Clone, Copy, Default)]
+pub struct GrpcConnector;
+
+#[tonic::async_trait]
+impl SchedulerConnector for GrpcConnector {
+    async fn connect(&self, url: &str) -> Result<Arc<dyn SchedulerClient>> {
+        Ok(Arc::new(GrpcScheduler::connect(url).await?))
+    }
+}
+
+#[cfg(test)]
+pub mod mock {
+    use super::*;
+    use std::sync::Mutex;
+
+    #[derive(Debug, Default)]
+    struct State {
+        /// Connection attempts to fail before one succeeds
+        failing_connects: u32,
+        connects: u32,
+        fencing_token: u64,
+        /// Reject registrations with this message
+        rejection: Option<String>,
+        /// Fail resource reports with this status
+        report_error: Option<tonic::Code>,
+        registrations: Vec<RegisterNodeRequest>,
+        reports: Vec<ResourceReport>,
+        updates: Vec<JobStatusUpdate>,
+    }
+
+    /// In-memory scheduler recording what the worker sends; clones share
+    /// their state, and it is its own connector
+    #[derive(Debug, Clone, Default)]
+    pub struct MockScheduler {
+        state: Arc<Mutex<State>>,
+    }
+
+    impl MockScheduler {
+        pub fn new(fencing_token: u64) -> Self {
+            let scheduler = Self::default();
+            scheduler.state.lock().unwrap().fencing_token = fencing_token;
+            scheduler
+        }
+
+        /// Refuse the next `attempts` connections
+        pub fn fail_connects(&self, attempts: u32) {
+            self.state.lock().unwrap().failing_connects = attempts;
+        }
+
+        pub fn reject_registrations(&self, message: &str) {
+            self.state.lock().unwrap().rejection = Some(message.to_string());
+        }
+
+        /// Fail every resource report with `code` (None: accept them)
+        pub fn fail_reports(&self, code: Option<tonic::Code>) {
+            self.state.lock().unwrap().report_error = code;
+        }
+
+        /// Issue `fencing_token` to later registrations
+        pub fn set_fencing_token(&self, fencing_token: u64) {
+            self.state.lock().unwrap().fencing_token = fencing_token;
+        }
+
+        pub fn connects(&self) -> u32 {
+            self.state.lock().unwrap().connects
+        }
+
+        pub fn registrations(&self) -> Vec<RegisterNodeRequest> {
+            self.state.lock().unwrap().registrations.clone()
+        }
+
+        pub fn reports(&self) -> Vec<ResourceReport> {
+            self.state.lock().unwrap().reports.clone()
+        }
+
+        pub fn updates(&self) -> Vec<JobStatusUpdate> {
+            self.state.lock().unwrap().updates.clone()
+        }
+    }
+
+    #[tonic::async_trait]
+    impl SchedulerClient for MockScheduler {
+        async fn register_node(&self, request: RegisterNodeRequest) -> Result<RegisterNodeResponse> {
+            let mut state = self.state.lock().unwrap();
+            state.registrations.push(request);
+            Ok(match &state.rejection {
+                Some(message) => RegisterNodeResponse { success: false, message: message.clone(), ..Default::default() },
+                None => RegisterNodeResponse {
+                    success: true,
+                    message: "Registered".to_string(),
+                    cluster_id: "mock".to_string(),
+                    fencing_token: state.fencing_token,
+                },
+            })
+        }
+
+        async fn report_resources(&self, report: ResourceReport) -> Result<ResourceAck> {
+            let mut state = self.state.lock().unwrap();
+            if let Some(code) = state.report_error {
+                return Err(tonic::Status::new(code, "Report refused").into());
+            }
+            state.reports.push(report);
+            Ok(ResourceAck { received: true, ..Default::default() })
+        }
+
+        async fn update_job_status(&self, update: JobStatusUpdate) -> Result<()> {
+            self.state.lock().unwrap().updates.push(update);
+            Ok(())
+        }
+    }
+
+    #[tonic::async_trait]
+    impl SchedulerConnector for MockScheduler {
+        async fn connect(&self, url: &str) -> Result<Arc<dyn SchedulerClient>> {
+            let mut state = self.state.lock().unwrap();
+            state.connects += 1;
+            if state.failing_connects > 0 {
+                state.failing_connects -= 1;
+                anyhow::bail!("Connection to {} refused", url);
+            }
+            Ok(Arc::new(self.clone()))
+        }
+    }
+}
@@ -0,0 +1,78 @@
+//! Bounded per-job scratch space
+//!
+//! Each job gets a scratch directory on the host, mounted at `/scratch`
+//! in its container. Container storage drivers rarely support size quotas,
+//! so the directory is measured periodically instead and the job is
+//! stopped once it writes more than its `disk_gb` requirement.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio::sync::oneshot;
+
+/// Mount point of the scratch directory inside the container
+pub const CONTAINER_SCRATCH_DIR: &str = "/scratch";
+
+/// How often the scratch directory is measured
+const CHECK_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Recursive size of a directory in bytes (unreadable entries are skipped)
+pub fn dir_size_bytes(dir: &Path) -> u64 {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return 0;
+    };
+
+    entries
+        .flatten()
+        .map(|entry| match entry.metadata() {
+            Ok(meta) if meta.is_dir() => dir_size_bytes(&entry.path()),
+            Ok(meta) => meta.len(),
+            Err(_) => 0,
+        })
+        .sum()
+}
+
+/// Watch `dir` until `stop_rx` fires; returns true as soon as it holds
+/// more than `limit_bytes`
+pub async fn watch(dir: PathBuf, limit_bytes: u64, mut stop_rx: oneshot::Receiver<()>) -> bool {
+    let mut interval = tokio::time::interval(CHECK_INTERVAL);
+    loop {
+        tokio::select! {
+            _ = interval.tick() => {
+                let dir = dir.clone();
+                let used = tokio::task::spawn_blocking(move || dir_size_bytes(&dir))
+                    .await
+                    .unwrap_or(0);
+                if used > limit_bytes {
+                    return true;
+                }
+            }
+            _ = &mut stop_rx => return false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_watch_reports_exceeded_limit() {
+        let dir = std::env::temp_dir().join(format!("tgp-scratch-test-{}", std::process::id()));
+        fs::create_dir_all(dir.join("nested")).unwrap();
+        fs::write(dir.join("a"), vec![0u8; 600]).unwrap();
+        fs::write(dir.join("nested").join("b"), vec![0u8; 600]).unwrap();
+        assert_eq!(dir_size_bytes(&dir), 1200);
+
+        let (_stop_tx, stop_rx) = oneshot::channel();
+        assert!(watch(dir.clone(), 1000, stop_rx).await);
+
+        // Within the limit the watch runs until stopped
+        let (stop_tx, stop_rx) = oneshot::channel();
+        let watcher = tokio::spawn(watch(dir.clone(), 2000, stop_rx));
+        stop_tx.send(()).unwrap();
+        assert!(!watcher.await.unwrap());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,280 @@
+//! Worker self-update
+//!
+//! The worker checks its version with the scheduler every
+//! `TGP_UPDATE_CHECK_SECS` (default an hour) and logs when it falls behind.
+//! With `TGP_AUTO_UPDATE=1` and the operator's Ed25519 release key in
+//! `TGP_UPDATE_PUBLIC_KEY` (hex), it downloads a newer release once no jobs
+//! are running, checks its SHA-256 and the signature over its version,
+//! platform and digest (see `release_message`), replaces its own binary
+//! and restarts itself with the same arguments (exec on Unix; on Windows,
+//! where a running binary can be renamed but not overwritten, the old one
+//! is moved aside and the new one spawned before this process exits).
+//! Containers keep running across the restart. A release that fails
+//! verification, or is not newer than the running worker, is never written.
+
+use anyhow::{Context, Result};
+use ed25519_dalek::{Signature, Verifier, VerifyingKey};
+use sha2::{Digest, Sha256};
+use std::path::Path;
+use std::time::Duration;
+use tonic::transport::Channel;
+use tracing::{debug, error, info, warn};
+
+use crate::control::JobRunner;
+use crate::proto::{scheduler_service_client::SchedulerServiceClient, WorkerUpdateRequest, WorkerVersionRequest};
+
+/// Version of this worker binary
+pub const WORKER_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Largest release binary downloaded
+const MAX_RELEASE_BYTES: u64 = 512 * 1024 * 1024;
+
+#[derive(Debug, Clone)]
+pub struct UpdateConfig {
+    /// Install releases, not just report them
+    pub auto_update: bool,
+    /// Operator key release binaries must be signed with
+    pub public_key: Option<VerifyingKey>,
+    pub check_interval: Duration,
+}
+
+impl UpdateConfig {
+    pub fn from_env() -> Self {
+        let public_key = std::env::var("TGP_UPDATE_PUBLIC_KEY").ok().and_then(|hex| match parse_public_key(&hex) {
+            Ok(key) => Some(key),
+            Err(e) => {
+                warn!("Ignoring TGP_UPDATE_PUBLIC_KEY: {}", e);
+                None
+            }
+        });
+        Self {
+            auto_update: matches!(std::env::var("TGP_AUTO_UPDATE").as_deref(), Ok("1") | Ok("true")),
+            public_key,
+            check_interval: Duration::from_secs(
+                std::env::var("TGP_UPDATE_CHECK_SECS")
+                    .ok()
+                    .and_then(|v| v.parse::<u64>().ok())
+                    .unwrap_or(3600)
+                    .max(60),
+            ),
+        }
+    }
+}
+
+/// Platform releases are published for, e.g. "linux-x86_64"
+pub fn platform() -> String {
+    format!("{}-{}", std::env::consts::OS, std::env::consts::ARCH)
+}
+
+/// Decode a 32-byte hex Ed25519 public key
+pub fn parse_public_key(hex: &str) -> Result<VerifyingKey> {
+    let hex = hex.trim();
+    anyhow::ensure!(hex.len() == 64 && hex.is_ascii(), "expected 64 hex characters");
+    let mut bytes = [0u8; 32];
+    for (byte, pair) in bytes.iter_mut().zip(hex.as_bytes().chunks(2)) {
+        let pair = std::str::from_utf8(pair)?;
+        *byte = u8::from_str_radix(pair, 16).with_context(|| format!("invalid hex {:?}", pair))?;
+    }
+    VerifyingKey::from_bytes(&bytes).context("not an Ed25519 public key")
+}
+
+/// Parse "1.4.2" (an optional leading "v" and a "-suffix" are ignored)
+fn parse_version(version: &str) -> Option<(u64, u64, u64)> {
+    let core = version.trim().trim_start_matches('v').split(['-', '+']).next()?;
+    let mut parts = core.split('.').map(|part| part.parse::<u64>().ok());
+    let major = parts.next()??;
+    let minor = parts.next().unwrap_or(Some(0))?;
+    let patch = parts.next().unwrap_or(Some(0))?;
+    Some((major, minor, patch))
+}
+
+/// Whether `version` is newer than this worker; a downgrade, even to a
+/// signed release, is never installed
+pub fn is_newer(version: &str) -> bool {
+    match (parse_version(version), parse_version(WORKER_VERSION)) {
+        (Some(version), Some(current)) => version > current,
+        _ => false,
+    }
+}
+
+/// What the operator signs for a release: it binds the binary's digest to
+/// its version and platform (the scheduler's `release_message`)
+pub fn release_message(version: &str, platform: &str, sha256: &str) -> String {
+    format!("tgp-worker-release:{}:{}:{}", version, platform, sha256)
+}
+
+/// Check a downloaded release against its digest and the operator's
+/// signature of its version, platform and digest
+pub fn verify_release(
+    key: &VerifyingKey,
+    version: &str,
+    platform: &str,
+    binary: &[u8],
+    sha256: &str,
+    signature: &[u8],
+) -> Result<()> {
+    let actual: String = Sha256::digest(binary).iter().map(|b| format!("{:02x}", b)).collect();
+    anyhow::ensure!(actual == sha256, "Release digest mismatch (got {})", actual);
+    let signature = Signature::from_slice(signature).context("Malformed release signature")?;
+    key.verify(release_message(version, platform, sha256).as_bytes(), &signature)
+        .context("Release signature does not match the update key")
+}
+
+async fn download(client: &mut SchedulerServiceClient<Channel>, version: &str) -> Result<Vec<u8>> {
+    let mut stream = client
+        .download_worker_update(WorkerUpdateRequest { version: version.to_string() })
+        .await
+        .with_context(|| format!("Failed to download worker {}", version))?
+        .into_inner();
+
+    let mut binary = Vec::new();
+    while let Some(chunk) = stream.message().await.with_context(|| format!("Failed to download worker {}", version))? {
+        anyhow::ensure!(
+            (binary.len() + chunk.data.len()) as u64 <= MAX_RELEASE_BYTES,
+            "Worker {} is over the {} byte limit",
+            version,
+            MAX_RELEASE_BYTES
+        );
+        binary.extend_from_slice(&chunk.data);
+    }
+    Ok(binary)
+}
+
+/// Replace the running binary, keeping the old one next to it
+fn install(exe: &Path, binary: &[u8]) -> Result<()> {
+    let staged = exe.with_extension("new");
+    std::fs::write(&staged, binary).with_context(|| format!("Failed to write {}", staged.display()))?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(&staged, std::fs::Permissions::from_mode(0o755))?;
+        if let Err(e) = std::fs::copy(exe, exe.with_extension("old")) {
+            debug!("Could not keep the previous binary: {}", e);
+        }
+    }
+    #[cfg(windows)]
+    {
+        let old = exe.with_extension("old.exe");
+        let _ = std::fs::remove_file(&old);
+        std::fs::rename(exe, &old).with_context(|| format!("Failed to move {} aside", exe.display()))?;
+    }
+    std::fs::rename(&staged, exe).with_context(|| format!("Failed to replace {}", exe.display()))
+}
+
+/// Run the (new) binary in place of this process
+#[cfg(unix)]
+fn restart(exe: &Path) -> Result<()> {
+    use std::os::unix::process::CommandExt;
+
+    let err = std::process::Command::new(exe).args(std::env::args_os().skip(1)).exec();
+    Err(err).with_context(|| format!("Failed to restart {}", exe.display()))
+}
+
+/// Run the (new) binary in place of this process
+#[cfg(windows)]
+fn restart(exe: &Path) -> Result<()> {
+    std::process::Command::new(exe)
+        .args(std::env::args_os().skip(1))
+        .spawn()
+        .with_context(|| format!("Failed to restart {}", exe.display()))?;
+    std::process::exit(0)
+}
+
+/// Check for releases and, if allowed, update while idle, forever
+pub async fn run(mut client: SchedulerServiceClient<Channel>, node_id: String, runner: JobRunner, config: UpdateConfig) {
+    if config.auto_update && config.public_key.is_none() {
+        warn!("TGP_AUTO_UPDATE is set without TGP_UPDATE_PUBLIC_KEY, updates will only be reported");
+    }
+    let platform = platform();
+    let mut ticker = tokio::time::interval(config.check_interval);
+    loop {
+        ticker.tick().await;
+
+        let check = match client
+            .check_worker_version(WorkerVersionRequest {
+                node_id: node_id.clone(),
+                version: WORKER_VERSION.to_string(),
+                platform: platform.clone(),
+            })
+            .await
+        {
+            Ok(response) => response.into_inner(),
+            Err(e) => {
+                debug!("Version check failed: {}", e);
+                continue;
+            }
+        };
+        if check.update_required {
+            warn!("Worker {} is below the minimum supported version {}", WORKER_VERSION, check.min_version);
+        }
+        if !check.update_available {
+            continue;
+        }
+        if !is_newer(&check.latest_version) {
+            warn!("Scheduler offered worker {}, not newer than {}; ignoring it", check.latest_version, WORKER_VERSION);
+            continue;
+        }
+        let Some(key) = config.public_key.filter(|_| config.auto_update) else {
+            info!("Worker {} is available (running {})", check.latest_version, WORKER_VERSION);
+            continue;
+        };
+        if check.size_bytes > MAX_RELEASE_BYTES {
+            warn!("Worker {} is over the {} byte limit, not updating", check.latest_version, MAX_RELEASE_BYTES);
+            continue;
+        }
+        // Never restart under a running job; try again next check
+        if runner.active_jobs() > 0 {
+            info!("Worker {} is available, updating once running jobs finish", check.latest_version);
+            continue;
+        }
+
+        let result = async {
+            let binary = download(&mut client, &check.latest_version).await?;
+            verify_release(&key, &check.latest_version, &platform, &binary, &check.sha256, &check.signature)?;
+            let exe = std::env::current_exe().context("Cannot locate the worker binary")?;
+            install(&exe, &binary)?;
+            info!("Updated to worker {}, restarting", check.latest_version);
+            restart(&exe)
+        }
+        .await;
+        if let Err(e) = result {
+            error!("Update to worker {} failed: {:#}", check.latest_version, e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ed25519_dalek::{Signer, SigningKey};
+
+    #[test]
+    fn test_release_must_match_digest_and_signature() {
+        let signing = SigningKey::from_bytes(&[7; 32]);
+        let hex: String = signing.verifying_key().to_bytes().iter().map(|b| format!("{:02x}", b)).collect();
+        let key = parse_public_key(&hex).unwrap();
+
+        let binary = b"\x7fELF worker 0.2.0".to_vec();
+        let sha256: String = Sha256::digest(&binary).iter().map(|b| format!("{:02x}", b)).collect();
+        let message = release_message("0.2.0", "linux-x86_64", &sha256);
+        let signature = signing.sign(message.as_bytes()).to_bytes();
+        verify_release(&key, "0.2.0", "linux-x86_64", &binary, &sha256, &signature).unwrap();
+
+        // Tampered in transit, re-signed with another key, or replayed as
+        // another version or platform
+        let mut tampered = binary.clone();
+        tampered[5] ^= 1;
+        assert!(verify_release(&key, "0.2.0", "linux-x86_64", &tampered, &sha256, &signature).is_err());
+        let other = SigningKey::from_bytes(&[9; 32]).sign(message.as_bytes()).to_bytes();
+        assert!(verify_release(&key, "0.2.0", "linux-x86_64", &binary, &sha256, &other).is_err());
+        assert!(verify_release(&key, "9.0.0", "linux-x86_64", &binary, &sha256, &signature).is_err());
+        assert!(verify_release(&key, "0.2.0", "linux-aarch64", &binary, &sha256, &signature).is_err());
+        assert!(parse_public_key("abcd").is_err());
+
+        // Only upgrades are installed
+        assert!(is_newer("999.0.0"));
+        assert!(!is_newer(WORKER_VERSION));
+        assert!(!is_newer("0.0.1"));
+        assert!(!is_newer("garbage"));
+    }
+}
@@ -0,0 +1,58 @@
+//! Registration-time network speed test
+//!
+//! The worker downloads a few MB from the scheduler's SpeedTest stream and
+//! reports the throughput, which the scheduler treats as the node's network
+//! capacity for jobs that declare a bandwidth requirement. Nodes whose
+//! bandwidth is known can set TGP_NETWORK_MBPS instead.
+
+use anyhow::{Context, Result};
+use std::time::{Duration, Instant};
+use tonic::transport::Channel;
+
+use crate::proto::{scheduler_service_client::SchedulerServiceClient, SpeedTestRequest};
+
+/// Download size of one test
+const TEST_SIZE_MB: u32 = 16;
+
+/// Give up on links too slow to finish within this
+const TEST_TIMEOUT: Duration = Duration::from_secs(20);
+
+/// Mbit/s for `bytes` moved in `elapsed`
+fn mbps(bytes: u64, elapsed: Duration) -> u32 {
+    (bytes as f64 * 8.0 / 1_000_000.0 / elapsed.as_secs_f64().max(1e-3)).round() as u32
+}
+
+/// Measure download bandwidth from the scheduler in Mbit/s
+pub async fn measure(client: &mut SchedulerServiceClient<Channel>) -> Result<u32> {
+    let run = async {
+        let start = Instant::now();
+        let mut stream = client
+            .speed_test(SpeedTestRequest { size_mb: TEST_SIZE_MB })
+            .await
+            .context("SpeedTest failed")?
+            .into_inner();
+
+        let mut bytes = 0u64;
+        while let Some(chunk) = stream.message().await.context("SpeedTest stream broken")? {
+            bytes += chunk.data.len() as u64;
+        }
+        anyhow::ensure!(bytes > 0, "SpeedTest returned no data");
+        Ok(mbps(bytes, start.elapsed()))
+    };
+
+    tokio::time::timeout(TEST_TIMEOUT, run)
+        .await
+        .context("SpeedTest timed out")?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mbps() {
+        // 16 MB in 1.28s = 100 Mbit/s (decimal units, as links are sold)
+        assert_eq!(mbps(16_000_000, Duration::from_millis(1280)), 100);
+        assert_eq!(mbps(125_000_000, Duration::from_secs(1)), 1000);
+    }
+}
@@ -0,0 +1,255 @@
+//! Local status endpoint
+//!
+//! A tiny HTTP server for node-local debugging and health checks, on
+//! `TGP_STATUS_LISTEN_ADDR` (default 127.0.0.1:9102, empty to disable):
+//!
+//! - `/healthz`: 200 while the worker is connected and its last resource
+//!   report was accepted recently, 503 otherwise (systemd watchdogs,
+//!   container HEALTHCHECKs)
+//! - `/status`: connection state, running jobs and the last resource
+//!   snapshot as JSON
+//! - `/metrics`: the same in the Prometheus text format
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::fmt::Write as _;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tracing::{debug, info};
+
+use crate::control::JobRunner;
+
+/// Largest request head read
+const MAX_REQUEST_BYTES: usize = 8 * 1024;
+
+/// Node resources as last reported
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ResourceSnapshot {
+    pub cpu_cores: u32,
+    pub available_cpu: u32,
+    pub total_memory_gb: f64,
+    pub available_memory_gb: f64,
+    pub total_disk_gb: f64,
+    pub available_disk_gb: f64,
+}
+
+/// What the endpoint reports
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct StatusSnapshot {
+    pub node_id: String,
+    pub version: String,
+    pub scheduler_url: String,
+    pub connected: bool,
+    pub dormant: bool,
+    /// Unix time of the last accepted resource report
+    pub last_report_at: Option<i64>,
+    pub last_error: Option<String>,
+    pub report_failures: u64,
+    pub resources: Option<ResourceSnapshot>,
+    pub running_jobs: Vec<String>,
+}
+
+/// Worker state shared between the agent loop and the endpoint
+#[derive(Debug, Clone, Default)]
+pub struct NodeStatus {
+    inner: Arc<Mutex<StatusSnapshot>>,
+    /// Reports older than this make the worker unhealthy
+    stale_after_secs: i64,
+}
+
+impl NodeStatus {
+    pub fn new(node_id: &str, scheduler_url: &str, report_interval_secs: u64) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(StatusSnapshot {
+                node_id: node_id.to_string(),
+                version: crate::self_update::WORKER_VERSION.to_string(),
+                scheduler_url: scheduler_url.to_string(),
+                ..Default::default()
+            })),
+            stale_after_secs: 3 * report_interval_secs.max(1) as i64,
+        }
+    }
+
+    fn update(&self, f: impl FnOnce(&mut StatusSnapshot)) {
+        f(&mut self.inner.lock().unwrap_or_else(|e| e.into_inner()));
+    }
+
+    pub fn snapshot(&self) -> StatusSnapshot {
+        self.inner.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    pub fn record_resources(&self, resources: ResourceSnapshot) {
+        self.update(|status| status.resources = Some(resources));
+    }
+
+    /// A resource report the scheduler accepted
+    pub fn record_report(&self, dormant: bool) {
+        self.update(|status| {
+            status.connected = true;
+            status.dormant = dormant;
+            status.last_report_at = Some(unix_now());
+            status.last_error = None;
+        });
+    }
+
+    /// A failed report or reconnection
+    pub fn record_failure(&self, error: &anyhow::Error) {
+        self.update(|status| {
+            status.connected = false;
+            status.report_failures += 1;
+            status.last_error = Some(format!("{:#}", error));
+        });
+    }
+
+    pub fn is_healthy(&self, now: i64) -> bool {
+        let status = self.snapshot();
+        status.connected && status.last_report_at.is_some_and(|at| now - at <= self.stale_after_secs)
+    }
+}
+
+fn unix_now() -> i64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0)
+}
+
+/// Prometheus text exposition of a snapshot
+fn render_metrics(status: &StatusSnapshot, active_jobs: usize) -> String {
+    let mut out = String::new();
+    let mut gauge = |name: &str, help: &str, value: f64| {
+        let _ = writeln!(out, "# HELP {} {}\n# TYPE {} gauge\n{} {}", name, help, name, name, value);
+    };
+    gauge("tgp_worker_connected", "Whether the worker reaches the scheduler", status.connected as u8 as f64);
+    gauge("tgp_worker_dormant", "Whether the scheduler has the node dormant", status.dormant as u8 as f64);
+    gauge("tgp_worker_active_jobs", "Jobs accepted and not yet finished", active_jobs as f64);
+    gauge("tgp_worker_report_failures", "Failed resource reports since start", status.report_failures as f64);
+    gauge(
+        "tgp_worker_last_report_timestamp_seconds",
+        "Unix time of the last accepted resource report",
+        status.last_report_at.unwrap_or(0) as f64,
+    );
+    if let Some(r) = &status.resources {
+        gauge("tgp_worker_cpu_cores", "CPU cores of the node", r.cpu_cores as f64);
+        gauge("tgp_worker_available_cpu", "CPU cores offered to jobs", r.available_cpu as f64);
+        gauge("tgp_worker_memory_gb", "Memory of the node in GB", r.total_memory_gb);
+        gauge("tgp_worker_available_memory_gb", "Memory offered to jobs in GB", r.available_memory_gb);
+        gauge("tgp_worker_disk_gb", "Disk of the node in GB", r.total_disk_gb);
+        gauge("tgp_worker_available_disk_gb", "Disk offered to jobs in GB", r.available_disk_gb);
+    }
+    let _ = writeln!(
+        out,
+        "# HELP tgp_worker_info Worker build\n# TYPE tgp_worker_info gauge\ntgp_worker_info{{node=\"{}\",version=\"{}\"}} 1",
+        status.node_id, status.version
+    );
+    out
+}
+
+/// Status code, content type and body for a request path
+async fn respond(path: &str, status: &NodeStatus, runner: Option<&JobRunner>) -> (u16, &'static str, String) {
+    match path {
+        "/healthz" if status.is_healthy(unix_now()) => (200, "text/plain", "ok\n".to_string()),
+        "/healthz" => (503, "text/plain", "unhealthy\n".to_string()),
+        "/status" => {
+            let mut snapshot = status.snapshot();
+            if let Some(runner) = runner {
+                snapshot.running_jobs = runner.running_jobs().await.unwrap_or_else(|e| {
+                    debug!("Cannot list running jobs: {}", e);
+                    Vec::new()
+                });
+            }
+            match serde_json::to_string_pretty(&snapshot) {
+                Ok(body) => (200, "application/json", body + "\n"),
+                Err(e) => (500, "text/plain", format!("{}\n", e)),
+            }
+        }
+        "/metrics" => {
+            let active = runner.map_or(0, JobRunner::active_jobs);
+            (200, "text/plain; version=0.0.4", render_metrics(&status.snapshot(), active))
+        }
+        _ => (404, "text/plain", "not found\n".to_string()),
+    }
+}
+
+async fn handle(mut stream: TcpStream, status: NodeStatus, runner: Option<JobRunner>) -> Result<()> {
+    let mut buf = Vec::with_capacity(1024);
+    let mut chunk = [0u8; 1024];
+    while !buf.windows(4).any(|w| w == b"\r\n\r\n") && buf.len() < MAX_REQUEST_BYTES {
+        let n = stream.read(&mut chunk).await?;
+        if n == 0 {
+            break;
+        }
+        buf.extend_from_slice(&chunk[..n]);
+    }
+
+    let head = String::from_utf8_lossy(&buf);
+    let mut request_line = head.lines().next().unwrap_or_default().split_whitespace();
+    let (code, content_type, body) = match (request_line.next(), request_line.next()) {
+        (Some("GET"), Some(target)) => {
+            let path = target.split('?').next().unwrap_or(target);
+            respond(path, &status, runner.as_ref()).await
+        }
+        _ => (405, "text/plain", "method not allowed\n".to_string()),
+    };
+    let reason = match code {
+        200 => "OK",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        503 => "Service Unavailable",
+        _ => "Internal Server Error",
+    };
+    let response = format!(
+        "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        code, reason, content_type, body.len(), body
+    );
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await?;
+    Ok(())
+}
+
+/// Serve the status endpoint until the process exits
+pub async fn serve(addr: SocketAddr, status: NodeStatus, runner: Option<JobRunner>) -> Result<()> {
+    let listener = TcpListener::bind(addr)
+        .await
+        .with_context(|| format!("Failed to bind status endpoint on {}", addr))?;
+    info!("Status endpoint listening on http://{}", addr);
+
+    loop {
+        let (stream, _) = listener.accept().await?;
+        let (status, runner) = (status.clone(), runner.clone());
+        tokio::spawn(async move {
+            if let Err(e) = handle(stream, status, runner).await {
+                debug!("Status request failed: {}", e);
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_health_follows_reports() {
+        let status = NodeStatus::new("node-1", "http://scheduler:50051", 10);
+        assert_eq!(respond("/healthz", &status, None).await.0, 503);
+
+        status.record_resources(ResourceSnapshot { cpu_cores: 8, available_cpu: 6, ..Default::default() });
+        status.record_report(false);
+        assert_eq!(respond("/healthz", &status, None).await.0, 200);
+        let (_, _, metrics) = respond("/metrics", &status, None).await;
+        assert!(metrics.contains("tgp_worker_available_cpu 6"));
+        assert!(metrics.contains("tgp_worker_info{node=\"node-1\""));
+
+        // A stale or failed report makes the node unhealthy again
+        assert!(!status.is_healthy(unix_now() + 31));
+        status.record_failure(&anyhow::anyhow!("connection refused"));
+        let (code, _, body) = respond("/status", &status, None).await;
+        assert_eq!(code, 200);
+        assert!(body.contains("connection refused"));
+        assert_eq!(respond("/healthz", &status, None).await.0, 503);
+        assert_eq!(respond("/nope", &status, None).await.0, 404);
+    }
+}
@@ -0,0 +1,75 @@
+//! Metering of a job's resource use
+//!
+//! Samples the container's Docker stats while it runs and keeps the highest
+//! memory use and the most CPU cores it kept busy. Both are reported with
+//! the job's final status, where the scheduler compares them with what the
+//! job requested (right-sizing) and learns peak memory for placement.
+
+use bollard::container::{Stats, StatsOptions};
+use bollard::Docker;
+use futures_util::stream::StreamExt;
+use tokio::sync::oneshot;
+use tracing::warn;
+
+/// Highest use observed over a job's run (0: never sampled)
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct PeakUsage {
+    pub memory_gb: f64,
+    pub cpu_cores: f64,
+}
+
+impl PeakUsage {
+    fn observe(&mut self, stats: &Stats) {
+        let memory_bytes = stats.memory_stats.max_usage
+            .or(stats.memory_stats.usage)
+            .unwrap_or(0);
+        self.memory_gb = self.memory_gb.max(memory_bytes as f64 / (1024.0 * 1024.0 * 1024.0));
+
+        let cpu_delta = stats.cpu_stats.cpu_usage.total_usage
+            .saturating_sub(stats.precpu_stats.cpu_usage.total_usage);
+        let system_delta = stats.cpu_stats.system_cpu_usage.unwrap_or(0)
+            .saturating_sub(stats.precpu_stats.system_cpu_usage.unwrap_or(0));
+        let online_cpus = stats.cpu_stats.online_cpus.unwrap_or(1);
+        self.cpu_cores = self.cpu_cores.max(cpu_cores_used(cpu_delta, system_delta, online_cpus));
+    }
+}
+
+/// Cores kept busy between two samples, as `docker stats` computes them
+pub fn cpu_cores_used(cpu_delta: u64, system_delta: u64, online_cpus: u64) -> f64 {
+    if system_delta == 0 {
+        return 0.0;
+    }
+    cpu_delta as f64 / system_delta as f64 * online_cpus as f64
+}
+
+/// Sample `container_id` until it stops or `stop_rx` fires
+pub async fn meter(docker: Docker, container_id: String, mut stop_rx: oneshot::Receiver<()>) -> PeakUsage {
+    let options = StatsOptions { stream: true, one_shot: false };
+    let mut stats = docker.stats(&container_id, Some(options));
+    let mut peak = PeakUsage::default();
+    loop {
+        tokio::select! {
+            sample = stats.next() => match sample {
+                Some(Ok(sample)) => peak.observe(&sample),
+                Some(Err(e)) => {
+                    warn!("Failed to sample usage of container {}: {}", container_id, e);
+                    return peak;
+                }
+                None => return peak,
+            },
+            _ = &mut stop_rx => return peak,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cpu_cores_used() {
+        // Half of the host's time on a 4-core host: two cores busy
+        assert!((cpu_cores_used(500, 1000, 4) - 2.0).abs() < 1e-9);
+        assert_eq!(cpu_cores_used(500, 0, 4), 0.0);
+    }
+}